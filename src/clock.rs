@@ -0,0 +1,89 @@
+use std::{cell::Cell, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+
+// Time of the engine, so tests can control it (Database::with_clock):
+// timestamps (dump manifest, start of trace spans), durations and the sleeps of the I/O throttle.
+// The nonces of encrypted columns always come from the operating system (see table/encryption.rs).
+// The B-tree node cache (ttl_cache crate) measures its TTL with its own Instant, and the buffer pool (CachedStore)
+// evicts by a logical access counter, which is deterministic already.
+pub trait Clock {
+    /// Wall clock time
+    fn now(&self) -> SystemTime;
+    /// Monotonic time since an arbitrary start (for durations)
+    fn monotonic(&self) -> Duration;
+    fn sleep(&self, duration: Duration);
+}
+
+pub fn unix_seconds(clock: &dyn Clock) -> u64 {
+    clock.now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+pub struct SystemClock {
+    started: Instant,
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self { started: Instant::now() }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn monotonic(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// Time only moves with advance() and sleep(), sleep returns immediately
+pub struct ManualClock {
+    start: SystemTime,
+    elapsed: Cell<Duration>,
+}
+
+impl ManualClock {
+    pub fn new(start: SystemTime) -> Self {
+        Self { start, elapsed: Cell::new(Duration::ZERO) }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.elapsed.set(self.elapsed.get() + duration);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        self.start + self.elapsed.get()
+    }
+
+    fn monotonic(&self) -> Duration {
+        self.elapsed.get()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::clock::{Clock, ManualClock, unix_seconds};
+
+    #[test]
+    fn should_control_time() {
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(1000));
+        clock.sleep(Duration::from_secs(5));
+        clock.advance(Duration::from_millis(500));
+        assert_eq!(clock.monotonic(), Duration::from_millis(5500));
+        assert_eq!(unix_seconds(&clock), 1005);
+
+    }
+}
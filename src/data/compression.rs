@@ -0,0 +1,123 @@
+use thiserror::Error;
+
+// A small LZ77 compressor (no external crate). The output is a sequence of tokens:
+// 0nnnnnnn               literal run: n + 1 bytes follow
+// 1nnnnnnn oooooooo x2   match: copy n + MIN_MATCH bytes from `offset` bytes back (offset big endian, 1..=65535)
+// Matches are found with a hash table over 4 byte sequences, so the compression is fast but not optimal.
+const MIN_MATCH: usize = 4;
+const MAX_MATCH: usize = 127 + MIN_MATCH;
+const MAX_LITERALS: usize = 128;
+const MAX_OFFSET: usize = u16::MAX as usize;
+const HASH_BITS: u32 = 12;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum CompressionError {
+    #[error("CompressionError - invalid compressed data: {0}")]
+    InvalidData(String),
+}
+
+fn hash(bytes: &[u8]) -> usize {
+    let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    (value.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+}
+
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() / 2 + 16);
+    // last position + 1 of every hash (0 = none)
+    let mut table = vec![0usize; 1 << HASH_BITS];
+    let mut literal_start = 0;
+    let mut pos = 0;
+
+    while pos + MIN_MATCH <= data.len() {
+        let h = hash(&data[pos..]);
+        let candidate = table[h];
+        table[h] = pos + 1;
+
+        if candidate > 0 && pos - (candidate - 1) <= MAX_OFFSET {
+            let start = candidate - 1;
+            let len = data[start..].iter()
+                .zip(data[pos..].iter())
+                .take(MAX_MATCH)
+                .take_while(|(a, b)| a == b)
+                .count();
+            if len >= MIN_MATCH {
+                write_literals(&mut out, &data[literal_start..pos]);
+                out.push(0x80 | (len - MIN_MATCH) as u8);
+                out.extend_from_slice(&((pos - start) as u16).to_be_bytes());
+                pos += len;
+                literal_start = pos;
+                continue;
+            }
+        }
+        pos += 1;
+    }
+
+    write_literals(&mut out, &data[literal_start..]);
+    out
+}
+
+fn write_literals(out: &mut Vec<u8>, literals: &[u8]) {
+    for chunk in literals.chunks(MAX_LITERALS) {
+        out.push((chunk.len() - 1) as u8);
+        out.extend_from_slice(chunk);
+    }
+}
+
+/// expected_len is the length of the uncompressed data (stored next to the compressed data by the caller)
+pub fn decompress(data: &[u8], expected_len: usize) -> Result<Vec<u8>, CompressionError> {
+    let mut out: Vec<u8> = Vec::with_capacity(expected_len);
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let token = data[pos] as usize;
+        pos += 1;
+        if token & 0x80 == 0 {
+            let literals = data.get(pos..pos + token + 1)
+                .ok_or_else(|| CompressionError::InvalidData("literal run exceeds the input".to_owned()))?;
+            out.extend_from_slice(literals);
+            pos += token + 1;
+        } else {
+            let offset = data.get(pos..pos + 2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]) as usize)
+                .ok_or_else(|| CompressionError::InvalidData("missing match offset".to_owned()))?;
+            pos += 2;
+            if offset == 0 || offset > out.len() {
+                return Err(CompressionError::InvalidData(format!("invalid match offset {}", offset)));
+            }
+            // the match can overlap the bytes it produces, so it is copied byte by byte
+            let start = out.len() - offset;
+            for i in 0..(token & 0x7F) + MIN_MATCH {
+                out.push(out[start + i]);
+            }
+        }
+        if out.len() > expected_len {
+            return Err(CompressionError::InvalidData("data is longer than expected".to_owned()));
+        }
+    }
+
+    if out.len() != expected_len {
+        return Err(CompressionError::InvalidData(format!("expected {} bytes, got {}", expected_len, out.len())));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::compression::{compress, decompress};
+
+    #[test]
+    fn should_compress_and_decompress() {
+        let repetitive: Vec<u8> = (0..5000).map(|i| (i % 7) as u8).collect();
+        let mixed: Vec<u8> = (0..3000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8).chain(b"abcabcabcabc".iter().copied()).collect();
+        for data in [vec![], vec![1, 2, 3], repetitive.clone(), mixed] {
+            let compressed = compress(&data);
+            assert_eq!(decompress(&compressed, data.len()).unwrap(), data);
+        }
+        assert!(compress(&repetitive).len() < repetitive.len() / 10);
+
+        let compressed = compress(&repetitive);
+        assert!(decompress(&compressed, repetitive.len() - 1).is_err());
+        assert!(decompress(&compressed[..compressed.len() - 1], repetitive.len()).is_err());
+        assert!(decompress(&[0x80, 0, 1], 4).is_err());
+    }
+}
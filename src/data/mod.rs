@@ -1,4 +1,9 @@
+// Pages and stores read data from disk: corrupted or hostile files must return errors, not panic.
+#![cfg_attr(not(test), deny(clippy::unwrap_used, clippy::expect_used))]
+
 pub mod page;
+pub mod checksum;
+pub mod compression;
 mod fsm;
 
 
@@ -1,10 +1,17 @@
-use std::rc::Rc;
+use std::{borrow::Cow, rc::Rc};
 
 use thiserror::Error;
 
-#[derive(Debug)]
+use crate::data::{checksum::Crc32, compression::{compress, decompress}};
+
+// Pages of a table file are numbered from 1 (0 is not a page, e.g. of a Page that was not allocated yet)
+pub type PageId = u64;
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct PageDataLayout {
     page_size: u16,
+    // pages are written compressed by the FileStore (see compress_page)
+    compression: bool,
 }
 
 #[derive(Error, Debug)]
@@ -17,13 +24,29 @@ impl PageDataLayout {
     const INDEX_NUMBER_ROWS: usize = 0;
     const INDEX_ROW_OFFSET: usize = 2;
     const INDEX_PAGE_ID: usize = 6;
-    const INDEX_FREE_SLOTS_OFFSET: usize = 10;
-    const INDEX_FREE_SLOTS_START: usize = 14;
-
-    // table meta data: 4 bytes next_id, 4 bytes number_of_pages
-    pub const META_DATA_SIZE: usize = 8;
-    // page header: 2 bytes num_rows, 4 bytes data_offset, 4 bytes page_id, 4 bytes slots_offset
-    const PAGE_HEADER_SIZE: u16 = 14;
+    const INDEX_FREE_SLOTS_OFFSET: usize = 14;
+    const INDEX_CHECKSUM: usize = 18;
+    const INDEX_LSN: usize = 22;
+    const INDEX_FREE_SLOTS_START: usize = 30;
+    // The highest byte of slots_offset is always 0 (pages have at most 64 KiB), on disk it holds the page flags
+    const INDEX_FLAGS: usize = 14;
+    const FLAG_COMPRESSED: u8 = 0x01;
+
+    // table file header (in the metadata file next to the table file, see store/free_space.rs):
+    // 4 bytes magic, 1 byte format version, 3 bytes reserved,
+    // 8 bytes next_id, 8 bytes number_of_pages,
+    // layout: 2 bytes page_size, 2 bytes metadata_size, 1 byte format flags, 3 bytes reserved,
+    // 8 bytes allocated_pages (the file has space for them, see FileStore::with_extent_size)
+    pub const META_DATA_SIZE: usize = 40;
+    pub const MAGIC: [u8; 4] = *b"PDBT";
+    // incremented when the file or page format changes incompatibly
+    pub const FORMAT_VERSION: u8 = 5;
+    // format flag: the file was created with page compression (compression itself is flagged per page)
+    pub const FORMAT_COMPRESSION: u8 = 0x01;
+    const KNOWN_FORMAT_FLAGS: u8 = Self::FORMAT_COMPRESSION;
+    // page header: 2 bytes num_rows, 4 bytes data_offset, 8 bytes page_id, 4 bytes slots_offset, 4 bytes checksum,
+    // 8 bytes lsn
+    const PAGE_HEADER_SIZE: u16 = 30;
     const MIN_PAGE_SIZE: u16 = 32; // just arbitrarily value so it's easy to test with few bytes
 
 
@@ -33,13 +56,38 @@ impl PageDataLayout {
     const SLOT_PAGE_OFFSET_INDEX: usize = 1;
     const SLOT_RECORD_LENGTH_INDEX: usize = 5;
     const MAX_ROW_LENGTH: u16 = u16::MAX;
+    // an insert compacts the page first if more than this share of the page data is dead (see Page::fragmentation)
+    const COMPACTION_THRESHOLD: f64 = 0.25;
 
     pub fn new(page_size: u16) -> Result<Self, PageDataLayoutError> {
         if page_size < Self::MIN_PAGE_SIZE {
             return Err(PageDataLayoutError::InvalidPageSize);
         }
 
-        Ok(Self { page_size })
+        Ok(Self { page_size, compression: false })
+    }
+
+    /// Layout of a page size known at compile time, a size below the minimum doesn't compile
+    pub const fn of_size<const SIZE: u16>() -> Self {
+        const { assert!(SIZE >= Self::MIN_PAGE_SIZE, "The page size is below the minimum") };
+        Self { page_size: SIZE, compression: false }
+    }
+
+    /// The same layout with another page size (e.g. for a table that was created with its own page size)
+    pub fn with_page_size(&self, page_size: u16) -> Result<Self, PageDataLayoutError> {
+        Ok(Self { compression: self.compression, ..Self::new(page_size)? })
+    }
+
+    /// Pages are compressed when they are written to the file (if that makes them smaller) and decompressed
+    /// when they are read. The page keeps its place in the file, the bytes after the compressed data are 0.
+    #[cfg(feature = "page-compression")]
+    pub fn with_compression(mut self) -> Self {
+        self.compression = true;
+        self
+    }
+
+    pub fn compression(&self) -> bool {
+        self.compression
     }
 
     pub fn page_size(&self) -> usize {
@@ -50,63 +98,182 @@ impl PageDataLayout {
         (self.page_size - Self::PAGE_HEADER_SIZE) as usize
     }
 
+    /// Size of the largest record that fits into an empty page
+    pub fn max_record_size(&self) -> usize {
+        (self.page_data_size() - Self::SLOT_SIZE).min(Self::MAX_ROW_LENGTH as usize)
+    }
+
     pub fn metadata_size(&self) -> usize {
         Self::META_DATA_SIZE
     }
 
+    pub fn format_flags(&self) -> u8 {
+        if self.compression { Self::FORMAT_COMPRESSION } else { 0 }
+    }
+
 }
 
 #[derive(Debug)]
 pub struct PageFileMetadata {
-    next_id: i32, // There is currently just a signed int for ids
-    number_of_pages: i32, // because of next_id being i32
+    next_id: PageId,
+    number_of_pages: PageId,
+    // layout the file was written with, checked when the file is opened
+    page_size: u16,
+    metadata_size: u16,
+    format_flags: u8,
+    // pages the file is preallocated for, the pages after number_of_pages are not in use yet
+    allocated_pages: PageId,
 }
 
 impl PageFileMetadata {
-    pub fn new() -> Self {
+    pub fn new(layout: &PageDataLayout) -> Self {
         Self {
             next_id: 1,
             number_of_pages: 0,
+            page_size: layout.page_size,
+            metadata_size: layout.metadata_size() as u16,
+            format_flags: layout.format_flags(),
+            allocated_pages: 0,
         }
     }
-    pub fn deserialize(buf: &[u8]) -> Self {
-        let next_id = i32::from_be_bytes(buf[0..4].try_into().unwrap());
-        let number_of_pages = i32::from_be_bytes(buf[4..8].try_into().unwrap());
+    /// Header of a table file that was rewritten into the current format (see store/format_upgrade.rs),
+    /// the file has exactly `number_of_pages` pages
+    pub(crate) fn upgraded(layout: &PageDataLayout, next_id: PageId, number_of_pages: PageId, format_flags: u8) -> Self {
         Self {
             next_id,
             number_of_pages,
+            format_flags,
+            allocated_pages: number_of_pages,
+            ..Self::new(layout)
+        }
+    }
+
+    pub fn deserialize(buf: &[u8]) -> Result<Self, PageError> {
+        // check magic and version first, so that other files aren't interpreted as a table
+        if read_array::<4>(buf, 0)? != PageDataLayout::MAGIC {
+            return Err(PageError::UnknownFormat("not a playdb table file".to_owned()));
+        }
+        let version = read_array::<1>(buf, 4)?[0];
+        if version != PageDataLayout::FORMAT_VERSION {
+            return Err(PageError::UnknownFormat(format!("format version {} is not supported (expected {})", version, PageDataLayout::FORMAT_VERSION)));
         }
+
+        Ok(Self {
+            next_id: PageId::from_be_bytes(read_array(buf, 8)?),
+            number_of_pages: PageId::from_be_bytes(read_array(buf, 16)?),
+            page_size: u16::from_be_bytes(read_array(buf, 24)?),
+            metadata_size: u16::from_be_bytes(read_array(buf, 26)?),
+            format_flags: read_array::<1>(buf, 28)?[0],
+            allocated_pages: PageId::from_be_bytes(read_array(buf, 32)?),
+        })
     }
     pub fn serialize(&self, layout: &PageDataLayout) -> Vec<u8> {
         let mut buf = vec![0u8; layout.metadata_size()];
-        buf[0..4].copy_from_slice(&self.next_id.to_be_bytes());
-        buf[4..8].copy_from_slice(&self.number_of_pages.to_be_bytes());
+        buf[0..4].copy_from_slice(&PageDataLayout::MAGIC);
+        buf[4] = PageDataLayout::FORMAT_VERSION;
+        buf[8..16].copy_from_slice(&self.next_id.to_be_bytes());
+        buf[16..24].copy_from_slice(&self.number_of_pages.to_be_bytes());
+        buf[24..26].copy_from_slice(&self.page_size.to_be_bytes());
+        buf[26..28].copy_from_slice(&self.metadata_size.to_be_bytes());
+        buf[28] = self.format_flags;
+        buf[32..40].copy_from_slice(&self.allocated_pages.to_be_bytes());
         buf
     }
-    pub fn next_id(&self) -> i32 {
+
+    // The file must be read with the layout it was written with. The compression flag isn't compared,
+    // because every page records whether it is compressed, so a file can be read with or without it.
+    pub fn check_layout(&self, layout: &PageDataLayout) -> Result<(), PageError> {
+        if self.page_size as usize != layout.page_size() {
+            return Err(PageError::LayoutMismatch(format!("file has page size {}, but layout has {}", self.page_size, layout.page_size())));
+        }
+        if self.metadata_size as usize != layout.metadata_size() {
+            return Err(PageError::LayoutMismatch(format!("file has metadata size {}, but layout has {}", self.metadata_size, layout.metadata_size())));
+        }
+        if self.format_flags & !PageDataLayout::KNOWN_FORMAT_FLAGS != 0 {
+            return Err(PageError::LayoutMismatch(format!("unknown format flags {:#04x}", self.format_flags)));
+        }
+        Ok(())
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.page_size as usize
+    }
+
+    pub fn format_flags(&self) -> u8 {
+        self.format_flags
+    }
+    pub fn next_id(&self) -> PageId {
         self.next_id
     }
 
-    pub fn number_of_pages(&self) -> i32 {
+    pub fn number_of_pages(&self) -> PageId {
         self.number_of_pages
     }
 
-    pub fn allocate_next_page_id(&mut self) -> i32 {
+    pub fn allocated_pages(&self) -> PageId {
+        self.allocated_pages
+    }
+
+    pub fn set_allocated_pages(&mut self, allocated_pages: PageId) {
+        self.allocated_pages = allocated_pages;
+    }
+
+    pub fn allocate_next_page_id(&mut self) -> PageId {
         let id = self.next_id;
         self.next_id += 1;
         self.number_of_pages += 1;
         id
     }
+
+    /// Header of a file that was cut off behind `number_of_pages` (see Store::truncate), without preallocated pages
+    pub(crate) fn truncate(&mut self, number_of_pages: PageId) {
+        self.next_id = number_of_pages + 1;
+        self.number_of_pages = number_of_pages;
+        self.allocated_pages = number_of_pages;
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct Slot {
     record_length: u16,
     page_offset: usize, // stored as u32
     deleted: bool,
+    kind: SlotKind,
 }
 
-#[derive(Debug)]
+// Rows that grow on update and don't fit into their page any more are moved to another page, but keep their
+// address (the slot, which the indexes point to): the slot becomes a forwarding pointer to the new place, and the
+// moved record starts with the address of its home slot, so the pointer can be changed or deleted with it.
+// Both pointers are 8 bytes page id and 4 bytes slot index. A forwarding pointer always points to a moved record,
+// there are no chains: if the moved record has to move again, the pointer in its home slot is changed.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum SlotKind {
+    #[default]
+    Row,
+    Forward,
+    MovedIn,
+}
+
+impl SlotKind {
+    // stored in the flag byte of the slot next to the deleted flag (bit 0)
+    fn flag(self) -> u8 {
+        match self {
+            SlotKind::Row => 0,
+            SlotKind::Forward => 0x02,
+            SlotKind::MovedIn => 0x04,
+        }
+    }
+
+    fn from_flag(flag: u8) -> Self {
+        match flag {
+            f if f & 0x02 != 0 => SlotKind::Forward,
+            f if f & 0x04 != 0 => SlotKind::MovedIn,
+            _ => SlotKind::Row,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct RecordData {
     // I need ownership because of the lazy filter chain in QueryResult
     // Maybe a converter that directly converts a record to any type could bypass this step
@@ -127,9 +294,9 @@ impl RecordData {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Record {
-    page_id: i32,
+    page_id: PageId,
     record_index: usize,
     data: RecordData,
 }
@@ -139,7 +306,7 @@ impl Record {
         self.data.data()
     }
 
-    pub fn page_id(&self) -> &i32 {
+    pub fn page_id(&self) -> &PageId {
         &self.page_id
     }
 
@@ -149,9 +316,10 @@ impl Record {
 }
 
 pub struct RecordIterator {
-    page_id: i32,
+    page_id: PageId,
     data: Rc<Vec<u8>>,
-    slots: Vec<Slot>,
+    // original slot index (record index) and slot
+    slots: Vec<(usize, Slot)>,
     next_slot: usize,
 }
 
@@ -161,7 +329,6 @@ impl RecordIterator {
         let slots = page.slots.drain(..).into_iter()
             .enumerate()
             .filter(|(index, _)| slot_ids.contains(index) )
-            .map(|(_, s)| s)
             .collect();
 
         Self {
@@ -176,18 +343,33 @@ impl RecordIterator {
         Self {
             page_id: page.page_id(),
             data: Rc::new(page.data),
-            slots: page.slots,
+            slots: page.slots.into_iter().enumerate().collect(),
             next_slot: 0,
         }
     }
 }
 
-fn record_from_slot(page_id: i32, data: Rc<Vec<u8>>, slot_index: usize, slot: &Slot) -> Record {
+fn record_from_slot(page_id: PageId, data: Rc<Vec<u8>>, slot_index: usize, slot: &Slot) -> Record {
     let data_to = slot.page_offset + slot.record_length as usize;
     Record {
         page_id: page_id,
         record_index: slot_index,
-        data: RecordData::new(data, slot.page_offset, data_to),
+        data: RecordData::new(data, slot.page_offset + slot.header_size(), data_to),
+    }
+}
+
+impl Slot {
+    // the home pointer in front of a moved record
+    fn header_size(&self) -> usize {
+        match self.kind {
+            SlotKind::MovedIn => Page::FORWARD_SIZE,
+            _ => 0,
+        }
+    }
+
+    // a record that is visible as row (not deleted, not a forwarding pointer)
+    fn has_row(&self) -> bool {
+        !self.deleted && self.kind != SlotKind::Forward
     }
 }
 
@@ -198,10 +380,10 @@ impl Iterator for RecordIterator {
         let mut found = None;
 
         while self.next_slot < self.slots.len() && found.is_none() {
-            let slot = &self.slots[self.next_slot];
+            let (slot_index, slot) = &self.slots[self.next_slot];
 
-            if !slot.deleted {
-                let record = record_from_slot(self.page_id, Rc::clone(&self.data), self.next_slot, slot) ;
+            if slot.has_row() {
+                let record = record_from_slot(self.page_id, Rc::clone(&self.data), *slot_index, slot) ;
 
                 found = Some(record);
             }
@@ -213,7 +395,7 @@ impl Iterator for RecordIterator {
     }
 }
 
-// Page Layout
+// Page Layout (slotted page)
 // ------------
 // Header
 // ------------
@@ -225,11 +407,18 @@ impl Iterator for RecordIterator {
 // row2
 // row1
 // rows (go upwards)
-#[derive(Debug)]
-pub struct Page<'database> {
+// A record is addressed by its slot index (line pointer), which doesn't change when the record is updated
+// (update_record, even with another length) or when the page is compacted. Deleting a record only marks its slot,
+// the slot can be reused by the next insert and the space is reclaimed by compact().
+// Slots grow from the header and rows from the end of the page (like the line pointers of Postgres), both meet
+// in the free space in the middle. The opposite direction would work the same, but would change the file format.
+// The page owns a copy of its layout, so that it doesn't depend on the lifetime of the database
+// (e.g. pages can be cached or sent to another thread).
+#[derive(Debug, Clone)]
+pub struct Page {
     pub data: Vec<u8>,
     slots: Vec<Slot>,
-    layout: &'database PageDataLayout,
+    layout: PageDataLayout,
     // header
     number_of_records: u16,
     // data_offset is actually the free space pointer
@@ -239,7 +428,10 @@ pub struct Page<'database> {
     // Place where the next free slot can be inserted:
     // stored as i32 (starts from 0)
     pub slots_offset: usize,
-    page_id: i32, // it's because of id being a i32
+    page_id: PageId,
+    // log sequence number of the last change of the page, so recovery can tell whether the page already
+    // contains a change of the log. There is no WAL yet, nothing sets it except set_lsn.
+    lsn: u64,
 }
 
 #[derive(Error, Debug)]
@@ -250,23 +442,70 @@ pub enum PageError {
     ReadPageError,
     #[error("Failed to update record")]
     UpdateRecordError,
+    #[error("Checksum of the page doesn't match its content")]
+    ChecksumMismatch,
+    #[error("Page layout doesn't match the file: {0}")]
+    LayoutMismatch(String),
+    #[error("Unknown file format: {0}")]
+    UnknownFormat(String),
+    #[error("Page has forwarded or moved rows, it can't be ordered")]
+    NotOrderable,
 }
 
 #[cfg(target_pointer_width = "64")] // so that I can use always 8 bytes for usize
-impl<'database> Page<'database> {
-    pub fn new(layout: &'database PageDataLayout) -> Self {
+impl Page {
+    /// Size of a forwarding pointer, a moved row is this much longer (see SlotKind)
+    pub const FORWARD_SIZE: usize = 12;
+
+    pub fn new(layout: &PageDataLayout) -> Self {
 
         Self {
-            layout,
+            layout: layout.clone(),
             data: vec![0; layout.page_data_size()],
             data_offset: layout.page_data_size(),
             number_of_records: 0,
             page_id: 0,
+            lsn: 0,
             slots: Vec::new(),
             slots_offset: 0,
         }
     }
 
+    /// Page with the records at the given slot indexes (None is a deleted slot), e.g. to rewrite a page of another
+    /// format without changing the addresses of its records. Fails if the records don't fit.
+    pub fn with_records(layout: &PageDataLayout, page_id: PageId, records: &[Option<&[u8]>]) -> Result<Self, PageError> {
+        let records: Vec<Option<(u8, &[u8])>> = records.iter().map(|r| r.map(|r| (0, r))).collect();
+        Self::with_flagged_records(layout, page_id, &records)
+    }
+
+    /// Same as with_records, each record with the flag byte of its slot on disk (its kind, see SlotKind)
+    pub(crate) fn with_flagged_records(layout: &PageDataLayout, page_id: PageId, records: &[Option<(u8, &[u8])>]) -> Result<Self, PageError> {
+        let mut page = Page::new(layout);
+        page.page_id = page_id;
+        let live_data: usize = records.iter().flatten().map(|(_, r)| r.len()).sum();
+        if live_data + records.len() * PageDataLayout::SLOT_SIZE > layout.page_data_size()
+            || records.iter().flatten().any(|(_, r)| r.len() > PageDataLayout::MAX_ROW_LENGTH as usize) {
+            return Err(PageError::InsertRowError);
+        }
+
+        for record in records {
+            let (flag, record) = record.unwrap_or_default();
+            let start_of_data = page.data_offset - record.len();
+            page.data[start_of_data..page.data_offset].copy_from_slice(record);
+            page.data_offset = start_of_data;
+            page.slots.push(Slot { record_length: record.len() as u16, page_offset: start_of_data, deleted: false, kind: SlotKind::from_flag(flag) });
+        }
+        for (slot, record) in page.slots.iter_mut().zip(records) {
+            slot.deleted = record.is_none();
+        }
+        while page.slots.last().is_some_and(|s| s.deleted) {
+            page.slots.pop();
+        }
+        page.number_of_records = page.slots.iter().filter(|s| !s.deleted).count() as u16;
+        page.slots_offset = page.slot_size();
+        Ok(page)
+    }
+
     pub fn record_iterator(self) -> RecordIterator {
         RecordIterator::new(self)
     }
@@ -291,18 +530,105 @@ impl<'database> Page<'database> {
         self.number_of_records
     }
 
-    pub fn page_id(&self) -> i32 {
+    pub fn page_id(&self) -> PageId {
         self.page_id
     }
 
-    pub fn set_page_id(&mut self, page_id: i32) {
+    pub fn live_rows(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.has_row()).count()
+    }
+
+    /// Deleted rows whose slot was not reused yet
+    pub fn dead_rows(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.deleted).count()
+    }
+
+    /// Bytes that compact() frees: records of deleted slots, holes of updated records and deleted slots at the end
+    pub fn dead_space(&self) -> usize {
+        let live_data: usize = self.slots.iter()
+            .filter(|s| !s.deleted)
+            .map(|s| s.record_length as usize)
+            .sum();
+        let trailing_slots = self.slots.iter().rev().take_while(|s| s.deleted).count();
+        (self.layout.page_data_size() - self.data_offset - live_data) + trailing_slots * PageDataLayout::SLOT_SIZE
+    }
+
+    /// Share of the page data that compact() would free, between 0 and 1
+    pub fn fragmentation(&self) -> f64 {
+        self.dead_space() as f64 / self.layout.page_data_size() as f64
+    }
+
+    pub fn set_page_id(&mut self, page_id: PageId) {
         self.page_id = page_id;
     }
 
+    pub fn lsn(&self) -> u64 {
+        self.lsn
+    }
+
+    pub fn set_lsn(&mut self, lsn: u64) {
+        self.lsn = lsn;
+    }
+
+    /// The row of the slot, None if it is deleted or forwarded to another page (see forward)
     pub fn read_slot(&self, slot_id: usize) -> Option<&[u8]> {
         self.slots.get(slot_id)
-            .filter(|slot| !slot.deleted)
-            .map(|slot| self.read_data(slot))
+            .filter(|slot| slot.has_row())
+            .map(|slot| &self.read_data(slot)[slot.header_size()..])
+    }
+
+    /// Address (page id, slot) the row of the slot was moved to, None if it is not forwarded
+    pub fn forward(&self, record_index: usize) -> Option<(PageId, usize)> {
+        self.slots.get(record_index)
+            .filter(|slot| !slot.deleted && slot.kind == SlotKind::Forward)
+            .and_then(|slot| read_pointer(self.read_data(slot)))
+    }
+
+    /// Address (page id, slot) of the home slot of a moved row, None if the row is in its home slot
+    pub fn home(&self, record_index: usize) -> Option<(PageId, usize)> {
+        self.slots.get(record_index)
+            .filter(|slot| !slot.deleted && slot.kind == SlotKind::MovedIn)
+            .and_then(|slot| read_pointer(self.read_data(slot)))
+    }
+
+    /// Whether forward_record can replace the record of the slot
+    pub fn can_forward(&self, record_index: usize) -> bool {
+        let Some(slot) = self.slots.get(record_index).filter(|slot| !slot.deleted) else {
+            return false;
+        };
+        let live_data: usize = self.slots.iter()
+            .filter(|s| !s.deleted)
+            .map(|s| s.record_length as usize)
+            .sum();
+        slot.record_length as usize >= Self::FORWARD_SIZE
+            || live_data - slot.record_length as usize + Self::FORWARD_SIZE + self.slot_size() <= self.layout.page_data_size()
+    }
+
+    /// Replaces the record of the slot by a forwarding pointer to the address of its moved row
+    pub fn forward_record(&mut self, record_index: usize, target: (PageId, usize)) -> Result<(), PageError> {
+        if !self.can_forward(record_index) {
+            return Err(PageError::UpdateRecordError);
+        }
+        self.slots[record_index].kind = SlotKind::Row;
+        self.update_record(record_index, &pointer(target))?;
+        self.slots[record_index].kind = SlotKind::Forward;
+        Ok(())
+    }
+
+    /// Whether a moved row fits into the page (with the pointer to its home slot)
+    pub fn can_insert_moved(&self, row_bytes: &[u8]) -> bool {
+        let mut record = pointer((0, 0)).to_vec();
+        record.extend_from_slice(row_bytes);
+        self.can_insert(&record)
+    }
+
+    /// Inserts a row that was moved from its home slot (see forward_record), returns its slot index
+    pub fn insert_moved_record(&mut self, home: (PageId, usize), row_bytes: &[u8]) -> Result<usize, PageError> {
+        let mut record = pointer(home).to_vec();
+        record.extend_from_slice(row_bytes);
+        let slot_index = self.insert_record(record)?;
+        self.slots[slot_index].kind = SlotKind::MovedIn;
+        Ok(slot_index)
     }
 
     fn max_fragmented_free_space(&self) -> usize {
@@ -326,6 +652,26 @@ impl<'database> Page<'database> {
         // one time by the caller to check if the data fits in the page
         // and a second time by the page itself to verify that it really fits (maybe the caller didn't check)
         // the first call can be replaced by using FSM in the future
+        self.fits_without_compaction(row_bytes) || self.fits_after_compaction(row_bytes)
+    }
+
+    /// Page data that is not used by live records and slots, a record that is longer never fits into the page
+    pub fn free_space(&self) -> usize {
+        let live_data: usize = self.slots.iter()
+            .filter(|s| !s.deleted)
+            .map(|s| s.record_length as usize)
+            .sum();
+        self.layout.page_data_size() - live_data - self.slot_size()
+    }
+
+    fn fits_after_compaction(&self, row_bytes: &[u8]) -> bool {
+        // deleted slots are kept (except at the end), so this is a lower bound of the free space
+        let free_space = self.free_space();
+
+        row_bytes.len() + PageDataLayout::SLOT_SIZE <= free_space && row_bytes.len() <= PageDataLayout::MAX_ROW_LENGTH as usize
+    }
+
+    fn fits_without_compaction(&self, row_bytes: &Vec<u8>) -> bool {
         if let Some(slot) = self.find_free_slot_index(row_bytes) {
             let perfect_fit = self.slots.get(slot)
                 .map(|s| s.record_length as usize == row_bytes.len())
@@ -341,7 +687,7 @@ impl<'database> Page<'database> {
     }
 
     // just returns the index, so that the caller can decide if it wants to get the slot mutable or not.
-    fn find_free_slot_index(&'database self, row_bytes: &Vec<u8>) -> Option<usize> {
+    fn find_free_slot_index(&self, row_bytes: &Vec<u8>) -> Option<usize> {
         let mut fallback = None;
 
         for (index, s) in self.slots.iter().enumerate() {
@@ -382,20 +728,241 @@ impl<'database> Page<'database> {
         }
     }
 
+    /// Update that keeps the slot index (and so the index entries), also if the length changed.
+    /// A shorter record is written in place, its remaining space is free after the next compaction.
+    /// A longer record is written into the free space (the page is compacted if needed), the slot points to it then.
+    /// Fails if the slot doesn't exist or the record doesn't fit into the page any more.
+    /// A moved row (see insert_moved_record) keeps the pointer to its home slot.
+    pub fn update_record(&mut self, record_index: usize, row_bytes: &[u8]) -> Result<(), PageError> {
+        if let Some(home) = self.home(record_index) {
+            let mut record = pointer(home).to_vec();
+            record.extend_from_slice(row_bytes);
+            return self.update_stored_record(record_index, &record);
+        }
+        self.update_stored_record(record_index, row_bytes)
+    }
+
+    fn update_stored_record(&mut self, record_index: usize, row_bytes: &[u8]) -> Result<(), PageError> {
+        let Some(slot) = self.slots.get(record_index).filter(|slot| !slot.deleted) else {
+            return Err(PageError::UpdateRecordError);
+        };
+        if row_bytes.len() > PageDataLayout::MAX_ROW_LENGTH as usize {
+            return Err(PageError::UpdateRecordError);
+        }
+        let old_length = slot.record_length as usize;
+        if row_bytes.len() <= old_length {
+            let start = slot.page_offset;
+            self.data[start..start + row_bytes.len()].copy_from_slice(row_bytes);
+            self.slots[record_index].record_length = row_bytes.len() as u16;
+            return Ok(());
+        }
+
+        let contiguous_space = self.data_offset - self.slot_size();
+        if row_bytes.len() > contiguous_space {
+            let live_data: usize = self.slots.iter()
+                .filter(|s| !s.deleted)
+                .map(|s| s.record_length as usize)
+                .sum();
+            if live_data - old_length + row_bytes.len() + self.slot_size() > self.layout.page_data_size() {
+                return Err(PageError::UpdateRecordError);
+            }
+            // the old record is not copied by the compaction
+            self.slots[record_index].record_length = 0;
+            self.compact();
+        }
+
+        let start_of_data = self.data_offset - row_bytes.len();
+        self.data[start_of_data..self.data_offset].copy_from_slice(row_bytes);
+        self.data_offset = start_of_data;
+        let slot = &mut self.slots[record_index];
+        slot.page_offset = start_of_data;
+        slot.record_length = row_bytes.len() as u16;
+        Ok(())
+    }
+
+    /// Rewrites the records contiguously at the end of the page, so that the holes of deleted
+    /// records become one free area. Slot indexes don't change (they are referenced by indexes),
+    /// deleted slots just lose their space (insert_record reuses them). Deleted slots at the end of the slot array
+    /// are removed.
+    pub fn compact(&mut self) {
+        let page_data_size = self.layout.page_data_size();
+        let mut data = vec![0; page_data_size];
+        let mut offset = page_data_size;
+
+        for slot in self.slots.iter_mut() {
+            if slot.deleted {
+                slot.record_length = 0;
+                continue;
+            }
+            let len = slot.record_length as usize;
+            offset -= len;
+            data[offset..offset + len].copy_from_slice(&self.data[slot.page_offset..slot.page_offset + len]);
+            slot.page_offset = offset;
+        }
+
+        for slot in self.slots.iter_mut().filter(|s| s.deleted) {
+            slot.page_offset = offset;
+        }
+        while self.slots.last().is_some_and(|s| s.deleted) {
+            self.slots.pop();
+        }
+
+        self.data = data;
+        self.data_offset = offset;
+        self.slots_offset = self.slot_size();
+    }
+
+    // Ordered pages (e.g. clustered tables): the live slots are kept in key order, so that a record can be found
+    // with a binary search instead of checking every record. Ordering moves slots, so slot indexes change:
+    // the caller has to update references to the moved slots (e.g. B-tree entries).
+    // insert_record doesn't know the order, so ordered pages must only be filled by insert_record_ordered.
+    // Pages with forwarding pointers or moved rows are never ordered: these slots are addressed from another page.
+    // Insertion time ordering doesn't need this: new slots are always appended.
+
+    /// true, if the page has only rows of its own (no forwarding pointers or moved rows)
+    pub fn can_be_ordered(&self) -> bool {
+        self.slots.iter().all(|slot| slot.deleted || slot.kind == SlotKind::Row)
+    }
+
+    /// Sorts the slots by the key of their records and removes deleted slots.
+    /// Returns (old slot index, new slot index) for every record.
+    pub fn sort_slots_by_key<K: Ord, F: Fn(&[u8]) -> K>(&mut self, key: F) -> Result<Vec<(usize, usize)>, PageError> {
+        if !self.can_be_ordered() {
+            return Err(PageError::NotOrderable);
+        }
+        let mut live: Vec<(usize, Slot)> = self.slots.drain(..)
+            .enumerate()
+            .filter(|(_, slot)| !slot.deleted)
+            .collect();
+        live.sort_by_cached_key(|(_, slot)| key(self.read_data(slot)));
+
+        let mut moved = Vec::with_capacity(live.len());
+        for (new_index, (old_index, slot)) in live.into_iter().enumerate() {
+            moved.push((old_index, new_index));
+            self.slots.push(slot);
+        }
+        // space of the removed slots and their records
+        self.compact();
+
+        Ok(moved)
+    }
+
+    /// Finds the records with the key on an ordered page. Returns their slot indexes.
+    pub fn binary_search_by_key<K: Ord, F: Fn(&[u8]) -> K>(&self, search_key: &K, key: F) -> Vec<usize> {
+        let live = self.live_slot_indexes();
+        let first = live.partition_point(|index| key(self.read_data(&self.slots[*index])) < *search_key);
+        let end = first + live[first..].partition_point(|index| key(self.read_data(&self.slots[*index])) == *search_key);
+        live[first..end].to_vec()
+    }
+
+    /// true, if insert_record_ordered can insert the record (it always needs a new slot)
+    pub fn can_insert_ordered(&self, row_bytes: &[u8]) -> bool {
+        self.can_be_ordered() && self.fits_after_compaction(row_bytes)
+    }
+
+    /// Inserts the record behind all records with a smaller or equal key and returns its slot index.
+    /// All slots from this index on are moved by one.
+    pub fn insert_record_ordered<K: Ord, F: Fn(&[u8]) -> K>(&mut self, row_bytes: Vec<u8>, key: F) -> Result<usize, PageError> {
+        if !self.can_be_ordered() {
+            return Err(PageError::NotOrderable);
+        }
+        // a new slot is always needed, deleted slots can't be reused without breaking the order
+        if !self.fits_after_compaction(&row_bytes) {
+            return Err(PageError::InsertRowError);
+        }
+        if row_bytes.len() + PageDataLayout::SLOT_SIZE > self.data_offset - self.slot_size() {
+            self.compact();
+        }
+
+        let new_key = key(&row_bytes);
+        let live = self.live_slot_indexes();
+        let pos = live.partition_point(|index| key(self.read_data(&self.slots[*index])) <= new_key);
+        let slot_index = live.get(pos).copied().unwrap_or(self.slots.len());
+
+        let start_of_data = self.data_offset - row_bytes.len();
+        let record_length = row_bytes.len() as u16; // size checked by fits_after_compaction
+        self.data[start_of_data..self.data_offset].copy_from_slice(&row_bytes);
+        self.data_offset = start_of_data;
+        self.slots.insert(slot_index, Slot { record_length, page_offset: start_of_data, deleted: false, kind: SlotKind::Row });
+        self.number_of_records += 1;
+
+        Ok(slot_index)
+    }
+
+    // Split and merge: records move between two pages of the same layout, e.g. when a full B-tree page is split
+    // or two sparse data pages are combined. A moved record gets a new slot in the other page (with its kind, a
+    // moved row keeps the pointer to its home slot), its old slot is deleted: the caller has to update the
+    // references to it (indexes, the forwarding pointer in the home slot of a moved row).
+    // Forwarding pointers never move, the indexes point to their slot. Nothing is changed, if the records don't fit.
+    // The records are inserted in slot order, an ordered page stays ordered if the other page is empty.
+
+    /// Moves the upper half of the live records (in slot order) into `other`.
+    /// Returns (old slot index, slot index in other) for every moved record.
+    pub fn split_into(&mut self, other: &mut Page) -> Result<Vec<(usize, usize)>, PageError> {
+        let movable = self.movable_slot_indexes();
+        let upper_half = movable[movable.len() / 2..].to_vec();
+        self.move_records(&upper_half, other)
+    }
+
+    /// Moves all live records of `other` into this page.
+    /// Returns (slot index in other, new slot index) for every moved record.
+    pub fn merge_from(&mut self, other: &mut Page) -> Result<Vec<(usize, usize)>, PageError> {
+        let movable = other.movable_slot_indexes();
+        other.move_records(&movable, self)
+    }
+
+    fn movable_slot_indexes(&self) -> Vec<usize> {
+        self.live_slot_indexes().into_iter()
+            .filter(|index| self.slots[*index].kind != SlotKind::Forward)
+            .collect()
+    }
+
+    fn move_records(&mut self, slot_indexes: &[usize], target: &mut Page) -> Result<Vec<(usize, usize)>, PageError> {
+        // into a copy first: if a record doesn't fit, the target is not changed
+        let mut changed = target.clone();
+        let mut moved = Vec::with_capacity(slot_indexes.len());
+        for index in slot_indexes {
+            let slot = &self.slots[*index];
+            let new_index = changed.insert_record(self.read_data(slot).to_vec())?;
+            changed.slots[new_index].kind = slot.kind;
+            moved.push((*index, new_index));
+        }
+
+        *target = changed;
+        for index in slot_indexes {
+            self.delete_record(*index);
+        }
+        self.compact();
+        Ok(moved)
+    }
+
+    pub(crate) fn live_slot_indexes(&self) -> Vec<usize> {
+        self.slots.iter()
+            .enumerate()
+            .filter(|(_, slot)| !slot.deleted)
+            .map(|(index, _)| index)
+            .collect()
+    }
+
     /// Inserts the record into the page and returns the slot index of the inserted record
     pub fn insert_record(&mut self, row_bytes: Vec<u8>) -> Result<usize, PageError> {
-        if !self.can_insert(&row_bytes) {
-            return Err(PageError::InsertRowError);
+        if !self.fits_without_compaction(&row_bytes) {
+            if !self.fits_after_compaction(&row_bytes) {
+                return Err(PageError::InsertRowError);
+            }
+            self.compact();
+        } else if self.fragmentation() > PageDataLayout::COMPACTION_THRESHOLD {
+            // holes are only reused by records that fit into them, so many small holes are merged into the free space
+            self.compact();
         }
 
         let new_record_len = row_bytes.len() as u16; // size already checked
+        // space between the slots and the row data: a slot for the rest of a reused slot must fit into it
+        let contiguous_space = self.data_offset - self.slot_size();
         let slot_index;
-        // It's quite expensive and messy to insert directly into deleted slots
-        // better: use page compaction regularly and put the dead tuples into the free space area
-        // How this could work:
-        // - defragment the page and update the slot pointers that are not deleted
-        // - don't delete the deleted slot pointer (because that would break the index)
-        // - only reuse slot pointers after the index is not using them anymore (do index cleanup regularly)
+        // A deleted slot whose record has enough space is reused with its space.
+        // After a compaction, deleted slots have no space any more: the record is written into the free space then,
+        // but still gets a deleted slot, so the slot array doesn't grow with every delete and insert.
         let deleted_slot = self.find_free_slot_index(&row_bytes)
             .and_then(|slot_index| {
                 self.slots.get_mut(slot_index)
@@ -408,25 +975,36 @@ impl<'database> Page<'database> {
             let end_of_data = slot.page_offset + row_bytes.len();
             self.data[slot.page_offset..end_of_data].copy_from_slice(&row_bytes);
 
-            // Need a new slot if the length of the inserted record is not as long as the slot
-            let remaining_slot_len = slot.record_length - new_record_len;
-            
-            if remaining_slot_len > 0 {
-                slot.record_length = new_record_len;
-            }
+            // Need a new slot if the length of the inserted record is not as long as the slot.
+            // Without space for it, the rest stays unused until the next compaction.
+            let remaining_slot_len = match contiguous_space >= PageDataLayout::SLOT_SIZE {
+                true => slot.record_length - new_record_len,
+                false => 0,
+            };
+            slot.record_length = new_record_len;
 
             slot.deleted = false;
+            slot.kind = SlotKind::Row;
 
             (true, remaining_slot_len, end_of_data)
         } else {
             // just returning the length of slots should be correct
             // because a new slot must be allocated for this data
-            slot_index = self.slots.len();
             let start_of_data = self.data_offset - row_bytes.len();
             self.data[start_of_data..self.data_offset].copy_from_slice(&row_bytes);
             self.data_offset -= row_bytes.len();
 
-            (false, row_bytes.len() as u16, start_of_data)
+            match self.slots.iter().position(|s| s.deleted && s.record_length == 0) {
+                Some(index) => {
+                    slot_index = index;
+                    self.slots[index] = Slot { record_length: new_record_len, page_offset: start_of_data, deleted: false, kind: SlotKind::Row };
+                    (false, 0, start_of_data)
+                },
+                None => {
+                    slot_index = self.slots.len();
+                    (false, new_record_len, start_of_data)
+                },
+            }
         };
 
         if new_slot_len > 0 {
@@ -442,7 +1020,7 @@ impl<'database> Page<'database> {
         // It's possible to allocate deleted slot
         // for example, if a deleted slot has been overwritten with a smaller record,
         // the remaining free space can be allocated as a new slot
-        self.slots.push(Slot { record_length, page_offset, deleted });
+        self.slots.push(Slot { record_length, page_offset, deleted, kind: SlotKind::Row });
     }
 
     pub fn delete_record(&mut self, record_index: usize) -> bool {
@@ -450,6 +1028,7 @@ impl<'database> Page<'database> {
 
         if let Some(slot) = slot {
             slot.deleted = true;
+            slot.kind = SlotKind::Row;
             true
         } else {
             // Proper error handling?
@@ -472,7 +1051,7 @@ impl<'database> Page<'database> {
         buf[PageDataLayout::INDEX_ROW_OFFSET..PageDataLayout::INDEX_PAGE_ID]
             .copy_from_slice(&offset_bytes);
 
-        // PageId 4 Bytes
+        // PageId 8 Bytes
         let page_id_bytes = self.page_id.to_be_bytes();
         buf[PageDataLayout::INDEX_PAGE_ID..PageDataLayout::INDEX_FREE_SLOTS_OFFSET]
             .copy_from_slice(&page_id_bytes);
@@ -482,7 +1061,11 @@ impl<'database> Page<'database> {
         buf[PageDataLayout::INDEX_FREE_SLOTS_OFFSET..PageDataLayout::INDEX_FREE_SLOTS_OFFSET + 4]
             .copy_from_slice(&free_slots_offset_bytes);
 
-        buf[PageDataLayout::INDEX_FREE_SLOTS_OFFSET + 4..self.layout.page_size()].copy_from_slice(&self.data);
+        // LSN 8 Bytes
+        buf[PageDataLayout::INDEX_LSN..PageDataLayout::INDEX_FREE_SLOTS_START]
+            .copy_from_slice(&self.lsn.to_be_bytes());
+
+        buf[PageDataLayout::INDEX_FREE_SLOTS_START..self.layout.page_size()].copy_from_slice(&self.data);
 
         // serialize Slots:
         for (i, slot) in self.slots.iter().enumerate() {
@@ -492,7 +1075,7 @@ impl<'database> Page<'database> {
             let length_end = length_index + 2;
             
             // deleted flag
-            buf[deleted_flag] = if slot.deleted { 1 } else { 0 };
+            buf[deleted_flag] = u8::from(slot.deleted) | slot.kind.flag();
             // offset
             buf[offset_of_data_index..length_index]
                 .copy_from_slice(&(slot.page_offset as u32).to_be_bytes());
@@ -501,63 +1084,137 @@ impl<'database> Page<'database> {
                 .copy_from_slice(&(slot.record_length).to_be_bytes());
         }
 
+        let checksum = page_checksum(&buf);
+        buf[PageDataLayout::INDEX_CHECKSUM..PageDataLayout::INDEX_LSN]
+            .copy_from_slice(&checksum.to_be_bytes());
+
         buf
     }
 
-    pub fn deserialize(buf: &[u8], layout: &'database PageDataLayout) -> Self {
-        let num_rows = u16::from_be_bytes(
-            buf[PageDataLayout::INDEX_NUMBER_ROWS..PageDataLayout::INDEX_ROW_OFFSET].try_into().unwrap()
-        );
-
-        let offset = i32::from_be_bytes(
-            buf[PageDataLayout::INDEX_ROW_OFFSET..PageDataLayout::INDEX_PAGE_ID].try_into().unwrap()
-        
-        );
-
-        let page_id = i32::from_be_bytes(
-            buf[PageDataLayout::INDEX_PAGE_ID..PageDataLayout::INDEX_FREE_SLOTS_OFFSET].try_into().unwrap()
-        );
-
-        let free_slots_offset = i32::from_be_bytes(
-            buf[PageDataLayout::INDEX_FREE_SLOTS_OFFSET..PageDataLayout::INDEX_FREE_SLOTS_OFFSET + 4].try_into().unwrap()
-        ) as usize;
+    /// Fails if the buffer is shorter than a page, the checksum doesn't match (ChecksumMismatch)
+    /// or the header doesn't fit the layout (corrupted page)
+    pub fn deserialize(buf: &[u8], layout: &PageDataLayout) -> Result<Self, PageError> {
+        let buf = buf.get(..layout.page_size()).ok_or(PageError::ReadPageError)?;
+        if u32::from_be_bytes(read_array(buf, PageDataLayout::INDEX_CHECKSUM)?) != page_checksum(buf) {
+            return Err(PageError::ChecksumMismatch);
+        }
 
-        let data = buf[PageDataLayout::INDEX_FREE_SLOTS_OFFSET + 4..layout.page_size()].to_vec();
+        let num_rows = u16::from_be_bytes(read_array(buf, PageDataLayout::INDEX_NUMBER_ROWS)?);
+        let offset = i32::from_be_bytes(read_array(buf, PageDataLayout::INDEX_ROW_OFFSET)?);
+        let page_id = PageId::from_be_bytes(read_array(buf, PageDataLayout::INDEX_PAGE_ID)?);
+        let free_slots_offset = i32::from_be_bytes(read_array(buf, PageDataLayout::INDEX_FREE_SLOTS_OFFSET)?) as usize;
+        let lsn = u64::from_be_bytes(read_array(buf, PageDataLayout::INDEX_LSN)?);
+
+        let data = buf.get(PageDataLayout::INDEX_FREE_SLOTS_START..layout.page_size())
+            .ok_or(PageError::ReadPageError)?
+            .to_vec();
+        if free_slots_offset > data.len() || offset < 0 || offset as usize > data.len() {
+            return Err(PageError::ReadPageError);
+        }
 
-        let free_slots: Vec<Slot> = data[0..free_slots_offset].to_vec()
+        let free_slots = data[0..free_slots_offset]
             .chunks_exact(7)
             .map(|chunk| {
-                let deleted = if chunk[0] == 1 { true } else { false };
-                let offset = u32::from_be_bytes(chunk[1..5].try_into().unwrap()) as usize;
-                let length = u16::from_be_bytes(chunk[5..7].try_into().unwrap());
                 let slot = Slot {
-                    page_offset: offset,
-                    record_length: length,
-                    deleted,
+                    page_offset: u32::from_be_bytes(read_array(chunk, 1)?) as usize,
+                    record_length: u16::from_be_bytes(read_array(chunk, 5)?),
+                    deleted: chunk[0] & 0x01 != 0,
+                    kind: SlotKind::from_flag(chunk[0]),
                 };
-                slot
-            }).collect();
-
-        Self {
-            layout,
+                if slot.page_offset + slot.record_length as usize > data.len() {
+                    return Err(PageError::ReadPageError);
+                }
+                Ok(slot)
+            }).collect::<Result<Vec<Slot>, PageError>>()?;
+
+        Ok(Self {
+            layout: layout.clone(),
             number_of_records: num_rows,
             data_offset: offset as usize,
             page_id,
             data,
             slots: free_slots,
             slots_offset: free_slots_offset,
-        }
+            lsn,
+        })
+    }
+}
+
+
+/// Compresses the slots and rows of a serialized page, the header stays as it is except for the flag.
+/// Returns the page unchanged if compression doesn't make it smaller.
+pub fn compress_page(buf: Vec<u8>) -> Vec<u8> {
+    let payload_start = PageDataLayout::INDEX_FREE_SLOTS_START;
+    let compressed = compress(&buf[payload_start..]);
+    // 2 bytes length of the compressed data
+    if payload_start + 2 + compressed.len() >= buf.len() {
+        return buf;
+    }
+
+    let mut out = vec![0u8; buf.len()];
+    out[..payload_start].copy_from_slice(&buf[..payload_start]);
+    out[PageDataLayout::INDEX_FLAGS] |= PageDataLayout::FLAG_COMPRESSED;
+    out[payload_start..payload_start + 2].copy_from_slice(&(compressed.len() as u16).to_be_bytes());
+    out[payload_start + 2..payload_start + 2 + compressed.len()].copy_from_slice(&compressed);
+    out
+}
+
+/// Reverses compress_page if the page has the compressed flag, the result can be passed to Page::deserialize
+/// (which checks the checksum of the uncompressed page)
+pub fn decompress_page<'b>(buf: &'b [u8], layout: &PageDataLayout) -> Result<Cow<'b, [u8]>, PageError> {
+    let flags = *buf.get(PageDataLayout::INDEX_FLAGS).ok_or(PageError::ReadPageError)?;
+    if flags & PageDataLayout::FLAG_COMPRESSED == 0 {
+        return Ok(Cow::Borrowed(buf));
     }
+
+    let payload_start = PageDataLayout::INDEX_FREE_SLOTS_START;
+    let compressed_len = u16::from_be_bytes(read_array(buf, payload_start)?) as usize;
+    let compressed = buf.get(payload_start + 2..payload_start + 2 + compressed_len).ok_or(PageError::ReadPageError)?;
+    let payload = decompress(compressed, layout.page_size() - payload_start).map_err(|_| PageError::ReadPageError)?;
+
+    let mut out = Vec::with_capacity(layout.page_size());
+    out.extend_from_slice(&buf[..payload_start]);
+    out[PageDataLayout::INDEX_FLAGS] &= !PageDataLayout::FLAG_COMPRESSED;
+    out.extend_from_slice(&payload);
+    Ok(Cow::Owned(out))
 }
 
+// CRC-32 of the serialized page without the checksum field itself
+fn page_checksum(buf: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(&buf[..PageDataLayout::INDEX_CHECKSUM]);
+    crc.update(&buf[PageDataLayout::INDEX_LSN..]);
+    crc.finish()
+}
+
+// forwarding pointer and home pointer of moved rows (see SlotKind)
+fn pointer((page_id, slot): (PageId, usize)) -> [u8; Page::FORWARD_SIZE] {
+    let mut buf = [0u8; Page::FORWARD_SIZE];
+    buf[0..8].copy_from_slice(&page_id.to_be_bytes());
+    buf[8..12].copy_from_slice(&(slot as u32).to_be_bytes());
+    buf
+}
+
+fn read_pointer(buf: &[u8]) -> Option<(PageId, usize)> {
+    let page_id = PageId::from_be_bytes(read_array(buf, 0).ok()?);
+    let slot = u32::from_be_bytes(read_array(buf, 8).ok()?);
+    Some((page_id, slot as usize))
+}
+
+// N bytes at pos, fails if the buffer is too short
+fn read_array<const N: usize>(buf: &[u8], pos: usize) -> Result<[u8; N], PageError> {
+    buf.get(pos..pos + N)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(PageError::ReadPageError)
+}
 
 #[cfg(test)]
 mod tests {
-    use crate::data::page::{Page, PageDataLayout, PageDataLayoutError};
+    use crate::data::page::{Page, PageDataLayout, PageDataLayoutError, PageError, PageFileMetadata, compress_page, decompress_page};
 
     #[test]
     fn should_insert_new_data_in_deleted_slot_if_it_fits() {
-        let layout = PageDataLayout::new(64).unwrap();
+        let layout = PageDataLayout::new(76).unwrap();
         let mut page = Page::new(&layout);
 
         page.insert_record(vec![1, 2, 1, 2]).unwrap();
@@ -581,6 +1238,188 @@ mod tests {
     }
 
 
+    #[test]
+    fn should_compact_fragmented_page_on_insert() {
+        let layout = PageDataLayout::new(76).unwrap();
+        let mut page = Page::new(&layout);
+
+        // 50 bytes page data: 3 * (6 bytes + 7 bytes slot) = 39 bytes used
+        page.insert_record(vec![1; 6]).unwrap();
+        page.insert_record(vec![2; 6]).unwrap();
+        page.insert_record(vec![3; 6]).unwrap();
+        page.delete_record(0);
+        page.delete_record(2);
+
+        // 11 bytes contiguous free space and two holes with 6 bytes each: only fits after compaction
+        let record = vec![4; 8];
+        assert!(page.can_insert(&record));
+        let slot_index = page.insert_record(record).unwrap();
+
+        // trailing deleted slot has been removed, the first one reused, the record in slot 1 kept its index
+        assert_eq!(slot_index, 0);
+        assert_eq!(page.read_slot(1).unwrap(), &[2; 6]);
+        assert_eq!(page.read_slot(0).unwrap(), &[4; 8]);
+        assert_eq!(page.read_slot(2), None);
+
+        let page = Page::deserialize(&page.serialize(), &layout).unwrap();
+        assert_eq!(page.read_slot(1).unwrap(), &[2; 6]);
+        assert_eq!(page.read_slot(0).unwrap(), &[4; 8]);
+        assert_eq!(page.row_data_size(), 14);
+    }
+
+    #[test]
+    fn should_report_the_space_freed_by_compaction() {
+        let layout = PageDataLayout::new(128).unwrap();
+        let mut page = Page::new(&layout);
+        page.insert_record(vec![1; 6]).unwrap();
+        page.insert_record(vec![2; 6]).unwrap();
+        page.insert_record(vec![3; 6]).unwrap();
+        assert_eq!(page.dead_space(), 0);
+
+        page.delete_record(0);
+        page.update_record(1, &[2; 4]).unwrap();
+        page.delete_record(2);
+        // record 0, 2 bytes of the shorter record 1, record 2 and its trailing slot
+        assert_eq!(page.dead_space(), 6 + 2 + 6 + 7);
+
+        page.compact();
+        assert_eq!(page.dead_space(), 0);
+        assert_eq!(page.read_slot(1).unwrap(), &[2; 4]);
+    }
+
+    #[test]
+    fn should_not_grow_the_slots_into_the_row_data() {
+        let layout = PageDataLayout::new(130).unwrap();
+        let mut page = Page::new(&layout);
+        for i in 0..4 {
+            page.insert_record(vec![i; 17]).unwrap();
+        }
+        page.delete_record(1);
+        let slot_index = page.insert_record(vec![9; 5]).unwrap();
+
+        assert!(page.slot_size() <= page.data_offset());
+        let page = Page::deserialize(&page.serialize(), &layout).unwrap();
+        assert_eq!(page.read_slot(slot_index).unwrap(), &[9; 5]);
+        for i in [0, 2, 3] {
+            assert_eq!(page.read_slot(i as usize).unwrap(), &[i; 17]);
+        }
+    }
+
+    #[test]
+    fn should_reuse_the_deleted_slots_after_a_compaction() {
+        let layout = PageDataLayout::new(128).unwrap();
+        let mut page = Page::new(&layout);
+        for i in 0..4 {
+            page.insert_record(vec![i; 10]).unwrap();
+        }
+
+        for i in 0..100u8 {
+            // a slot in the middle, so the compaction keeps it
+            page.delete_record(1 + (i % 2) as usize);
+            page.compact();
+            let slot_index = page.insert_record(vec![i; 10 + (i % 3) as usize]).unwrap();
+            assert_eq!(slot_index, 1 + (i % 2) as usize);
+            assert_eq!(page.read_slot(slot_index).unwrap(), vec![i; 10 + (i % 3) as usize]);
+            assert_eq!(page.slot_size(), 4 * PageDataLayout::SLOT_SIZE);
+        }
+        assert_eq!(page.live_rows(), 4);
+        assert_eq!(page.read_slot(0).unwrap(), &[0; 10]);
+    }
+
+    #[test]
+    fn should_compact_on_insert_if_the_page_is_too_fragmented() {
+        let layout = PageDataLayout::new(128).unwrap();
+        // 110 bytes page data, 4 * (10 bytes + 7 bytes slot) = 68 bytes used
+        let filled = || {
+            let mut page = Page::new(&layout);
+            for i in 0..4 {
+                page.insert_record(vec![i; 10]).unwrap();
+            }
+            page
+        };
+
+        // 10 dead bytes: the hole is reused without compaction
+        let mut page = filled();
+        page.delete_record(0);
+        assert_eq!(page.insert_record(vec![9; 5]).unwrap(), 0);
+
+        // 30 dead bytes (27%): compacted first, the new record gets the first deleted slot without space
+        let mut page = filled();
+        for i in 0..3 {
+            page.delete_record(i);
+        }
+        assert!(page.fragmentation() > 0.25);
+        assert_eq!(page.insert_record(vec![9; 5]).unwrap(), 0);
+        assert_eq!(page.dead_space(), 0);
+        assert_eq!(page.read_slot(3).unwrap(), &[3; 10]);
+        assert_eq!(page.read_slot(0).unwrap(), &[9; 5]);
+        assert_eq!(page.slot_size(), 4 * PageDataLayout::SLOT_SIZE);
+    }
+
+    #[test]
+    fn should_not_insert_if_page_is_full_even_after_compaction() {
+        let layout = PageDataLayout::new(76).unwrap();
+        let mut page = Page::new(&layout);
+
+        page.insert_record(vec![1; 20]).unwrap();
+        page.insert_record(vec![2; 10]).unwrap();
+        page.delete_record(1);
+
+        let record = vec![3; 15];
+        assert!(!page.can_insert(&record));
+        assert!(page.insert_record(record).is_err());
+        assert_eq!(page.read_slot(0).unwrap(), &[1; 20]);
+    }
+
+    #[test]
+    fn should_keep_ordered_page_sorted_and_find_records_by_key() {
+        let layout = PageDataLayout::new(128).unwrap();
+        let mut page = Page::new(&layout);
+
+        for key in [5u8, 1, 9, 3] {
+            page.insert_record(vec![key, key]).unwrap();
+        }
+        page.delete_record(2);
+
+        let moved = page.sort_slots_by_key(|data| data[0]).unwrap();
+        assert_eq!(moved, vec![(1, 0), (3, 1), (0, 2)]);
+        assert_eq!(page.slots.len(), 3);
+
+        let slot_index = page.insert_record_ordered(vec![4, 4], |data| data[0]).unwrap();
+        assert_eq!(slot_index, 2);
+        page.insert_record_ordered(vec![7, 7], |data| data[0]).unwrap();
+
+        let keys: Vec<u8> = page.clone().record_iterator().map(|r| r.data()[0]).collect();
+        assert_eq!(keys, vec![1, 3, 4, 5, 7]);
+
+        // a page with a forwarding pointer is addressed from another page
+        page.forward_record(0, (2, 0)).unwrap();
+        assert!(matches!(page.sort_slots_by_key(|data| data[0]), Err(PageError::NotOrderable)));
+        assert!(!page.can_insert_ordered(&[2, 2]));
+    }
+
+    #[test]
+    fn should_binary_search_ordered_page_with_deleted_slots() {
+        let layout = PageDataLayout::new(128).unwrap();
+        let mut page = Page::new(&layout);
+
+        for key in [1u8, 2, 3, 4, 5, 6, 6, 7, 8] {
+            page.insert_record_ordered(vec![key], |data| data[0]).unwrap();
+        }
+        page.delete_record(3);
+
+        assert_eq!(page.binary_search_by_key(&5, |data| data[0]), vec![4]);
+        assert_eq!(page.binary_search_by_key(&6, |data| data[0]), vec![5, 6]);
+        assert!(page.binary_search_by_key(&4, |data| data[0]).is_empty());
+        assert!(page.binary_search_by_key(&42, |data| data[0]).is_empty());
+
+        while page.can_insert_ordered(&[9; 10]) {
+            page.insert_record_ordered(vec![9; 10], |data| data[0]).unwrap();
+        }
+        assert!(matches!(page.insert_record_ordered(vec![9; 10], |data| data[0]), Err(PageError::InsertRowError)));
+        assert!(page.slot_size() <= page.data_offset());
+    }
+
     #[test]
     fn should_update_record_in_place() {
         let layout = PageDataLayout::new(64).unwrap();
@@ -593,13 +1432,54 @@ mod tests {
         let ser_page = page.serialize();
         let record = page.record_iterator().find(|v| v.data.data()[0] == 2).unwrap();
 
-        let mut page = Page::deserialize(&ser_page, &layout);
+        let mut page = Page::deserialize(&ser_page, &layout).unwrap();
         page.write_record(record.record_index, vec![2, 2, 2, 2]).unwrap();
         let record = page.record_iterator().find(|v| v.data.data()[0] == 2).unwrap();
         assert_eq!(record.data.data(), &[2, 2, 2, 2]);
         assert_eq!(record.record_index, 1);
     }
 
+    #[test]
+    fn should_update_record_with_other_length_and_keep_its_slot() {
+        let layout = PageDataLayout::new(76).unwrap();
+        let mut page = Page::new(&layout);
+        for value in 1..=3u8 {
+            page.insert_record(vec![value; 4]).unwrap();
+        }
+
+        page.update_record(1, &[7, 7]).unwrap();
+        assert_eq!(page.read_slot(1).unwrap(), &[7, 7]);
+        // into the free space
+        page.update_record(1, &[8; 10]).unwrap();
+        // needs a compaction: the old records of slot 0 and 1 are holes
+        page.update_record(0, &[9; 10]).unwrap();
+
+        let page = Page::deserialize(&page.serialize(), &layout).unwrap();
+        assert_eq!(page.read_slot(0).unwrap(), &[9; 10]);
+        assert_eq!(page.read_slot(1).unwrap(), &[8; 10]);
+        assert_eq!(page.read_slot(2).unwrap(), &[3; 4]);
+
+        let mut page = page;
+        assert!(page.update_record(2, &[1; 40]).is_err());
+        assert_eq!(page.read_slot(2).unwrap(), &[3; 4]);
+        page.delete_record(2);
+        assert!(page.update_record(2, &[1]).is_err());
+        assert!(page.update_record(5, &[1]).is_err());
+    }
+
+
+    #[test]
+    fn page_should_outlive_its_layout_and_move_to_other_thread() {
+        let page = {
+            let layout = PageDataLayout::new(44).unwrap();
+            let mut page = Page::new(&layout);
+            page.insert_record(vec![1, 2, 3]).unwrap();
+            page
+        };
+
+        let handle = std::thread::spawn(move || page.row_data().to_vec());
+        assert_eq!(handle.join().unwrap(), vec![1, 2, 3]);
+    }
 
     #[test]
     fn should_not_allow_page_layout_size_less_than_32() {
@@ -616,24 +1496,24 @@ mod tests {
 
     #[test]
     fn should_calc_all_values_correctly_when_insert_row() {
-        let layout = PageDataLayout::new(32).unwrap();
+        let layout = PageDataLayout::new(44).unwrap();
         let mut page = Page::new(&layout);
 
         // insert 7 bytes
         let row = vec![1, 2, 3, 4, 5, 6, 7];
         page.insert_record(row.clone()).unwrap();
 
-        // 32 - 14(header) = 18
-        assert_eq!(page.data.len(), 18);
+        // 32 - 18(header) = 14
+        assert_eq!(page.data.len(), 14);
         assert_eq!(page.slots.len(), 1);
-        //points to offset 11, but no free space
+        //points to offset 7, but no free space
         
         let slot_option = page.slots.get(0);
         assert!(slot_option.is_some());
         let slot = slot_option.unwrap();
         assert_eq!(slot.deleted, false);
         assert_eq!(slot.record_length, 7);
-        assert_eq!(slot.page_offset, 11);
+        assert_eq!(slot.page_offset, 7);
 
         assert_eq!(page.row_data_size(), 7);
         let data = page.row_data();
@@ -642,9 +1522,10 @@ mod tests {
 
     #[test]
     fn should_serialize_and_deserialize_correctly() {
-        let layout = PageDataLayout::new(32).unwrap();
+        let layout = PageDataLayout::new(44).unwrap();
         let mut page = Page::new(&layout);
-        page.set_page_id(1);
+        // beyond the 4 bytes of the page ids before format version 5
+        page.set_page_id(1 << 40);
 
         // insert 7 bytes
         let row = vec![1, 2, 3, 4, 5, 6, 7];
@@ -653,19 +1534,19 @@ mod tests {
         // act: serialize
         let bytes = page.serialize();
 
-        let deserialized_page = Page::deserialize(&bytes, &layout);
+        let deserialized_page = Page::deserialize(&bytes, &layout).unwrap();
 
-        assert_eq!(deserialized_page.page_id, 1);
-        // 32 - 14(header) = 18
-        assert_eq!(deserialized_page.data.len(), 18);
+        assert_eq!(deserialized_page.page_id, 1 << 40);
+        // 32 - 18(header) = 14
+        assert_eq!(deserialized_page.data.len(), 14);
         assert_eq!(deserialized_page.slots.len(), 1);
-        // Slot points to offset 11 and it isn't deleted
+        // Slot points to offset 7 and it isn't deleted
         let slot_option = deserialized_page.slots.get(0);
         assert!(slot_option.is_some());
         let slot = slot_option.unwrap();
         assert_eq!(slot.deleted, false);
         assert_eq!(slot.record_length, 7);
-        assert_eq!(slot.page_offset, 11);
+        assert_eq!(slot.page_offset, 7);
 
         assert_eq!(deserialized_page.row_data_size(), 7);
         let data = deserialized_page.row_data();
@@ -674,7 +1555,7 @@ mod tests {
 
     #[test]
     fn should_serialize_and_deserialize_correctly_multiple_inserts() {
-        let layout = PageDataLayout::new(64).unwrap();
+        let layout = PageDataLayout::new(76).unwrap();
         let mut page = Page::new(&layout);
         page.set_page_id(1);
 
@@ -684,11 +1565,11 @@ mod tests {
 
         // act: serialize + deserialize
         let bytes = page.serialize();
-        let deserialized_page = Page::deserialize(&bytes, &layout);
+        let deserialized_page = Page::deserialize(&bytes, &layout).unwrap();
 
         assert_eq!(deserialized_page.page_id, 1);
-        // 64 - 14(header) = 50
-        assert_eq!(deserialized_page.data.len(), 50);
+        // 64 - 18(header) = 46
+        assert_eq!(deserialized_page.data.len(), 46);
         assert_eq!(deserialized_page.slots.len(), 3);
 
         let slot_option = page.slots.get(2);
@@ -696,8 +1577,8 @@ mod tests {
         let slot = slot_option.unwrap();
         assert_eq!(slot.deleted, false);
         assert_eq!(slot.record_length, 3);
-        // 50 - 9 = 41
-        assert_eq!(slot.page_offset, 41);
+        // 46 - 9 = 37
+        assert_eq!(slot.page_offset, 37);
 
         assert_eq!(deserialized_page.row_data_size(), 9);
     }
@@ -745,4 +1626,220 @@ mod tests {
         assert!(record_data.is_none());
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn should_reject_corrupted_pages() {
+        let layout = PageDataLayout::new(64).unwrap();
+        let mut page = Page::new(&layout);
+        page.insert_record(vec![1; 6]).unwrap();
+        let bytes = page.serialize();
+        assert!(Page::deserialize(&bytes, &layout).is_ok());
+
+        assert!(Page::deserialize(&bytes[..10], &layout).is_err());
+        assert!(Page::deserialize(&[], &layout).is_err());
+        for corrupted in [vec![0xFF; 64], (0..64u8).collect()] {
+            assert!(Page::deserialize(&corrupted, &layout).is_err());
+        }
+        assert!(PageFileMetadata::deserialize(&[0, 0, 1]).is_err());
+    }
+
+    #[test]
+    fn should_store_the_layout_in_the_file_metadata() {
+        let layout = PageDataLayout::new(64).unwrap();
+        let mut metadata = PageFileMetadata::new(&layout);
+        metadata.allocate_next_page_id();
+        metadata.set_allocated_pages(8);
+
+        let bytes = metadata.serialize(&layout);
+        assert_eq!(bytes.len(), 40);
+        assert_eq!(&bytes[24..29], &[0, 64, 0, 40, 0]);
+
+        let metadata = PageFileMetadata::deserialize(&bytes).unwrap();
+        assert_eq!(metadata.next_id(), 2);
+        assert_eq!(metadata.number_of_pages(), 1);
+        assert_eq!(metadata.allocated_pages(), 8);
+        assert_eq!(metadata.page_size(), 64);
+        assert!(metadata.check_layout(&layout).is_ok());
+        assert!(matches!(metadata.check_layout(&PageDataLayout::new(128).unwrap()), Err(PageError::LayoutMismatch(_))));
+
+        let mut unknown_flags = bytes.clone();
+        unknown_flags[28] = 0x80;
+        let metadata = PageFileMetadata::deserialize(&unknown_flags).unwrap();
+        assert!(matches!(metadata.check_layout(&layout), Err(PageError::LayoutMismatch(msg)) if msg == "unknown format flags 0x80"));
+    }
+
+    #[test]
+    fn should_reject_metadata_without_magic_or_with_another_version() {
+        let layout = PageDataLayout::new(64).unwrap();
+        let bytes = PageFileMetadata::new(&layout).serialize(&layout);
+        assert_eq!(&bytes[0..5], b"PDBT\x05");
+
+        // e.g. a file of an older playdb without the header magic
+        let old = [0, 0, 0, 3, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert!(matches!(PageFileMetadata::deserialize(&old), Err(PageError::UnknownFormat(msg)) if msg == "not a playdb table file"));
+
+        let mut newer = bytes.clone();
+        newer[4] = 6;
+        assert!(matches!(PageFileMetadata::deserialize(&newer), Err(PageError::UnknownFormat(msg)) if msg == "format version 6 is not supported (expected 5)"));
+
+        // version 4 had page ids of 4 bytes
+        let mut older = bytes.clone();
+        older[4] = 4;
+        assert!(matches!(PageFileMetadata::deserialize(&older), Err(PageError::UnknownFormat(_))));
+    }
+
+    #[test]
+    fn should_compress_page_and_restore_it() {
+        let layout = PageDataLayout::new(256).unwrap();
+        let mut page = Page::new(&layout);
+        page.set_page_id(2);
+        for _ in 0..5 {
+            page.insert_record(b"the same row again".to_vec()).unwrap();
+        }
+        let bytes = page.serialize();
+
+        let compressed = compress_page(bytes.clone());
+        assert_eq!(compressed.len(), 256);
+        assert_ne!(compressed, bytes);
+        // the payload is shorter, the rest of the page is 0
+        assert!(compressed[128..].iter().all(|b| *b == 0));
+
+        let restored = decompress_page(&compressed, &layout).unwrap();
+        assert_eq!(restored.as_ref(), bytes.as_slice());
+        let restored_page = Page::deserialize(&restored, &layout).unwrap();
+        assert_eq!(restored_page.num_rows(), 5);
+        assert_eq!(restored_page.page_id(), 2);
+
+        // uncompressed pages pass through
+        assert_eq!(decompress_page(&bytes, &layout).unwrap().as_ref(), bytes.as_slice());
+        // a compressed page can't be read without decompressing it
+        assert!(Page::deserialize(&compressed, &layout).is_err());
+    }
+
+    #[test]
+    fn should_store_page_uncompressed_if_compression_does_not_help() {
+        let layout = PageDataLayout::new(76).unwrap();
+        let mut page = Page::new(&layout);
+        // no repeated 4 byte sequences
+        page.insert_record((0..39u8).map(|i| i.wrapping_mul(97)).collect()).unwrap();
+        let bytes = page.serialize();
+
+        assert_eq!(compress_page(bytes.clone()), bytes);
+    }
+
+    #[test]
+    fn should_detect_changed_bytes_with_the_checksum() {
+        let layout = PageDataLayout::new(64).unwrap();
+        let mut page = Page::new(&layout);
+        page.set_page_id(3);
+        page.insert_record(vec![1, 2, 3, 4]).unwrap();
+        let bytes = page.serialize();
+
+        // a flipped bit in the header, the checksum, the slots or a record
+        for pos in [0, 15, 20, 63] {
+            let mut corrupted = bytes.clone();
+            corrupted[pos] ^= 0x01;
+            assert!(matches!(Page::deserialize(&corrupted, &layout), Err(PageError::ChecksumMismatch)));
+        }
+    }
+
+    #[test]
+    fn should_keep_the_lsn_in_the_page_header() {
+        let layout = PageDataLayout::new(64).unwrap();
+        let mut page = Page::new(&layout);
+        assert_eq!(page.lsn(), 0);
+        page.set_lsn(u64::MAX - 1);
+        page.insert_record(vec![1, 2, 3]).unwrap();
+
+        let bytes = page.serialize();
+        assert_eq!(&bytes[22..30], &(u64::MAX - 1).to_be_bytes());
+        let restored = Page::deserialize(&bytes, &layout).unwrap();
+        assert_eq!(restored.lsn(), u64::MAX - 1);
+        assert_eq!(restored.read_slot(0), Some(&[1, 2, 3][..]));
+
+        // the lsn is covered by the checksum
+        let mut corrupted = bytes.clone();
+        corrupted[29] ^= 0x01;
+        assert!(matches!(Page::deserialize(&corrupted, &layout), Err(PageError::ChecksumMismatch)));
+    }
+
+    #[test]
+    fn should_forward_a_slot_to_its_moved_row() {
+        let layout = PageDataLayout::new(128).unwrap();
+        let mut home_page = Page::new(&layout);
+        home_page.set_page_id(1);
+        home_page.insert_record(vec![1; 10]).unwrap();
+        let slot = home_page.insert_record(vec![2; 10]).unwrap();
+
+        let mut other_page = Page::new(&layout);
+        other_page.set_page_id(2);
+        other_page.insert_record(vec![3; 5]).unwrap();
+        let moved = other_page.insert_moved_record((1, slot), &[4; 20]).unwrap();
+        assert!(home_page.can_forward(slot));
+        home_page.forward_record(slot, (2, moved)).unwrap();
+
+        // the home slot has no row, only the pointer, the moved row doesn't show its home
+        let home_page = Page::deserialize(&home_page.serialize(), &layout).unwrap();
+        assert_eq!(home_page.forward(slot), Some((2, moved)));
+        assert_eq!(home_page.read_slot(slot), None);
+        assert_eq!(home_page.clone().record_iterator().count(), 1);
+        let mut other_page = Page::deserialize(&other_page.serialize(), &layout).unwrap();
+        assert_eq!(other_page.home(moved), Some((1, slot)));
+        assert_eq!(other_page.read_slot(moved), Some(&[4; 20][..]));
+        assert_eq!(other_page.home(0), None);
+
+        // an update keeps the pointer to the home slot
+        other_page.update_record(moved, &[5; 30]).unwrap();
+        assert_eq!(other_page.home(moved), Some((1, slot)));
+        assert_eq!(other_page.read_slot(moved), Some(&[5; 30][..]));
+        assert_eq!(other_page.clone().record_iterator().map(|r| r.data().to_vec()).collect::<Vec<_>>(), vec![vec![3; 5], vec![5; 30]]);
+
+        // a deleted slot is a plain row again when it is reused
+        other_page.delete_record(moved);
+        assert_eq!(other_page.home(moved), None);
+        let reused = other_page.insert_record(vec![6; 10]).unwrap();
+        assert_eq!(reused, moved);
+        assert_eq!(other_page.home(reused), None);
+        assert_eq!(other_page.read_slot(reused), Some(&[6; 10][..]));
+    }
+
+    #[test]
+    fn should_split_a_page_and_merge_it_again() {
+        let layout = PageDataLayout::new(256).unwrap();
+        let mut page = Page::new(&layout);
+        page.set_page_id(1);
+        for i in 1..=5u8 {
+            page.insert_record(vec![i; 10]).unwrap();
+        }
+        page.delete_record(1);
+        let moved_in = page.insert_moved_record((7, 3), &[6; 4]).unwrap();
+        let forward = page.insert_record(vec![0; 10]).unwrap();
+        page.forward_record(forward, (8, 0)).unwrap();
+
+        // live rows in the slots 0, 2, 3, 4 and the moved row, the forwarding pointer (reused slot 1) stays
+        let mut right = Page::new(&layout);
+        right.set_page_id(2);
+        let moved = page.split_into(&mut right).unwrap();
+        assert_eq!(moved, vec![(3, 0), (4, 1), (moved_in, 2)]);
+        assert_eq!(right.read_slot(0), Some(&[4; 10][..]));
+        assert_eq!(right.home(2), Some((7, 3)));
+        assert_eq!(right.read_slot(2), Some(&[6; 4][..]));
+        assert_eq!(page.read_slot(3), None);
+        assert_eq!(page.forward(forward), Some((8, 0)));
+        assert_eq!(page.live_rows() + right.live_rows(), 5);
+
+        let moved = page.merge_from(&mut right).unwrap();
+        assert_eq!(moved.len(), 3);
+        assert_eq!(right.live_rows(), 0);
+        let merged: Vec<Vec<u8>> = page.clone().record_iterator().map(|r| r.data().to_vec()).collect();
+        assert_eq!(merged, vec![vec![1; 10], vec![3; 10], vec![4; 10], vec![5; 10], vec![6; 4]]);
+
+        // doesn't fit: nothing is moved
+        let mut full = Page::new(&layout);
+        while full.can_insert(&vec![9; 20]) {
+            full.insert_record(vec![9; 20]).unwrap();
+        }
+        let before = page.serialize();
+        assert!(matches!(page.split_into(&mut full), Err(PageError::InsertRowError)));
+        assert_eq!(page.serialize(), before);
+    }
+}
@@ -0,0 +1,115 @@
+use std::{path::Path, rc::Rc};
+
+use crate::{
+    data::page::PageDataLayout,
+    database::{Database, DatabaseError, blob, table_access::QueryResult, virtual_table::{Constraint, VirtualRows, VirtualTable, VirtualTableError}},
+    store::{PageIterator, Store, file_store::FileStore},
+    table::{TableSchema, identifier::Identifier, table::Table},
+};
+
+// Attached databases: Database::attach mounts the directory of another playdb database read-only under an alias.
+// In SQL, its tables are `alias.table` (FROM alias.table), they are read like virtual tables (see virtual_table.rs),
+// so they can be combined with the tables of this database without copying data.
+// A foreign table is always scanned completely (no index lookups, no pushdown) and the attached database is opened
+// without an encryption key, so tables with encrypted columns can't be read. The attached database uses the default
+// page size for its catalog, tables with another page size are read with the page size of their file header.
+
+impl<S: Store> Database<S> {
+    pub fn attach(&self, path: &Path, alias: &str) -> Result<(), DatabaseError> {
+        let alias = Identifier::normalize(alias);
+        if alias.is_empty() || alias.contains('.') {
+            return Err(DatabaseError::UnknownError(format!("Invalid alias for an attached database: '{}'", alias)));
+        }
+        if self.attached.borrow().contains_key(&alias) {
+            return Err(DatabaseError::UnknownError(format!("A database is already attached as '{}'", alias)));
+        }
+
+        if !path.is_dir() {
+            return Err(DatabaseError::UnknownError(format!("Cannot attach {:?}, it is not a directory", path)));
+        }
+        let db = Database::new_with_store(&alias, FileStore::new_read_only(path));
+        // fails if the directory doesn't contain a database
+        db.store.read_metadata(&db.layout, &db.table_instance())?;
+        self.attached.borrow_mut().insert(alias, Rc::new(db));
+        Ok(())
+    }
+
+    pub fn detach(&self, alias: &str) -> bool {
+        self.attached.borrow_mut().remove(&Identifier::normalize(alias)).is_some()
+    }
+
+    /// The table `alias.table` of an attached database, None if the name has no alias of an attached database
+    pub fn foreign_table(&self, name: &str) -> Result<Option<Rc<dyn VirtualTable>>, DatabaseError> {
+        let Some((alias, table)) = name.split_once('.') else {
+            return Ok(None);
+        };
+        let Some(db) = self.attached.borrow().get(&Identifier::normalize(alias)).cloned() else {
+            return Ok(None);
+        };
+
+        let table = db.read_table(table)?;
+        if let Some(column) = table.schema().columns.iter().find(|c| c.encrypted) {
+            return Err(DatabaseError::UnknownError(format!("Column '{}' of the attached table '{}' is encrypted", column.name, name)));
+        }
+        let layout = db.table_layout(&table)?;
+        Ok(Some(Rc::new(ForeignTable { name: name.to_owned(), db, table, layout })))
+    }
+}
+
+/// A table of an attached database
+pub struct ForeignTable {
+    name: String,
+    db: Rc<Database<FileStore>>,
+    table: Table,
+    layout: PageDataLayout,
+}
+
+impl VirtualTable for ForeignTable {
+    fn schema(&self) -> TableSchema {
+        self.table.schema().clone()
+    }
+
+    fn scan(&self, _constraints: &[Constraint]) -> Result<VirtualRows<'_>, VirtualTableError> {
+        let page_iter = PageIterator::try_new(&self.table, &self.db.store, &self.layout)
+            .map_err(|err| VirtualTableError::Failed(self.name.clone(), err.to_string()))?;
+        let rows = QueryResult::new(page_iter, self.table.schema().clone())
+            .into_iter()
+            .map(|res| res
+                .and_then(|(_, row)| Ok(blob::resolve_blobs(&self.db.store, &self.layout, &self.table, row)?))
+                .map_err(|err| VirtualTableError::Failed(self.name.clone(), err.to_string())));
+        Ok(Box::new(rows))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::Database, store::file_store::FileStore, table::{ColumnType, table::{Cell, Row}}};
+
+    #[test]
+    fn should_read_the_tables_of_an_attached_database() {
+        let other_path = tempfile::tempdir().unwrap();
+        let other = Database::new_with_store("other", FileStore::new(other_path.path()));
+        other.drop_create().unwrap();
+        other.create_table("persons", vec![("id", ColumnType::Int, false, true), ("name", ColumnType::Varchar(20), false, false)]).unwrap();
+        let persons = other.table_access(other.read_table("persons").unwrap()).unwrap();
+        persons.insert(&Row::new(vec![Cell::Int(1), Cell::Varchar("Alice".to_owned())])).unwrap();
+
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        db.attach(other_path.path(), "Ext").unwrap();
+        assert!(db.attach(other_path.path(), "ext").is_err());
+        assert!(db.attach(base_path.path().join("missing").as_path(), "missing").is_err());
+        let empty = tempfile::tempdir().unwrap();
+        assert!(db.attach(empty.path(), "empty").is_err());
+
+        let table = db.foreign_table("ext.persons").unwrap().unwrap();
+        let rows: Vec<Row> = table.scan(&[]).unwrap().map(|row| row.unwrap()).collect();
+        assert_eq!(rows, vec![Row::new(vec![Cell::Int(1), Cell::Varchar("Alice".to_owned())])]);
+        assert!(db.foreign_table("ext.missing").is_err());
+        assert!(db.foreign_table("persons").unwrap().is_none());
+
+        assert!(db.detach("ext"));
+        assert!(db.foreign_table("ext.persons").unwrap().is_none());
+    }
+}
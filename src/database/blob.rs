@@ -0,0 +1,254 @@
+use crate::{
+    data::page::PageDataLayout,
+    store::{Store, StoreError, page_guard::PageGuard},
+    table::{ColumnType, TableSchema, table::{Cell, Row, Table}},
+};
+
+// BLOB values are stored out of line in the overflow pages of their table (file table_{id}.blob, see
+// Table::overflow_table), so wide values don't fill up the data pages. The row only keeps a pointer: the id of
+// the first overflow page (u32) and the length of the value (u32), as a Blob cell of these 8 bytes.
+// The overflow pages of a value form a chain, every page has a single record: the id of the next page (u32, 0 for the
+// last one) and the next chunk of the value. An empty value has no pages (page id 0).
+// The table files have page ids of 8 bytes since format version 5, the chains keep 4 bytes: the values of a table
+// can use the first u32::MAX overflow pages.
+//
+// TableAccess writes the chains on insert and update and resolves the pointers when it reads rows, outside of it
+// a Blob cell always has the complete value. The chain of a value is freed when its row is deleted or the value
+// is replaced, the records are deleted but the pages are not reused yet (a new chain is always appended).
+
+pub(crate) const POINTER_SIZE: usize = 8;
+// id of the next page in front of every chunk
+const NEXT_PAGE_SIZE: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct BlobPointer {
+    page_id: u32,
+    len: u32,
+}
+
+impl BlobPointer {
+    fn to_cell(self) -> Cell {
+        let mut bytes = self.page_id.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&self.len.to_be_bytes());
+        Cell::Blob(bytes)
+    }
+
+    fn from_cell(cell: &Cell) -> Result<Self, StoreError> {
+        match cell {
+            Cell::Blob(bytes) if bytes.len() == POINTER_SIZE => Ok(Self {
+                page_id: u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+                len: u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+            }),
+            other => Err(StoreError::DeserializationError(format!("Invalid BLOB pointer {:?}", other))),
+        }
+    }
+}
+
+fn is_blob(cell: &Cell, col_type: &ColumnType) -> bool {
+    matches!((cell, col_type), (Cell::Blob(_), ColumnType::Blob))
+}
+
+/// Writes the value into a new chain of overflow pages
+pub(crate) fn write_blob<S: Store>(store: &S, layout: &PageDataLayout, table: &Table, value: &[u8]) -> Result<BlobPointer, StoreError> {
+    let len = u32::try_from(value.len())
+        .map_err(|_| StoreError::IoError(format!("BLOB of {} bytes is too large", value.len())))?;
+    let overflow = table.overflow_table();
+    let chunk_size = layout.max_record_size() - NEXT_PAGE_SIZE;
+
+    // from the last chunk to the first, so the id of the next page is known when a page is written
+    let mut next_page_id: u32 = 0;
+    for chunk in value.chunks(chunk_size).rev() {
+        let mut page = PageGuard::allocate(store, layout, &overflow)?;
+        let mut record = next_page_id.to_be_bytes().to_vec();
+        record.extend_from_slice(chunk);
+        page.insert_record(record)?;
+        next_page_id = u32::try_from(page.page_id())
+            .map_err(|_| StoreError::QuotaExceeded(format!("table '{}' has no more overflow pages for BLOB values", table.name())))?;
+        page.write()?;
+    }
+
+    Ok(BlobPointer { page_id: next_page_id, len })
+}
+
+/// Reads the value of the chain
+pub(crate) fn read_blob<S: Store>(store: &S, layout: &PageDataLayout, table: &Table, pointer: BlobPointer) -> Result<Vec<u8>, StoreError> {
+    let overflow = table.overflow_table();
+    let corrupted = |msg: &str| StoreError::DeserializationError(format!("BLOB of table '{}' at page {}: {}", table.name(), pointer.page_id, msg));
+
+    let mut value = Vec::with_capacity(pointer.len as usize);
+    let mut page_id = pointer.page_id;
+    while page_id != 0 {
+        // a chain that is longer than the value would be a cycle
+        if value.len() >= pointer.len as usize {
+            return Err(corrupted("the chain is longer than the value"));
+        }
+        let page = store.read_page(layout, page_id.into(), &overflow)?;
+        let record = page.read_slot(0).ok_or_else(|| corrupted(&format!("overflow page {} has no chunk", page_id)))?;
+        let (next, chunk) = record.split_at_checked(NEXT_PAGE_SIZE)
+            .ok_or_else(|| corrupted(&format!("overflow page {} has an invalid chunk", page_id)))?;
+        value.extend_from_slice(chunk);
+        page_id = u32::from_be_bytes([next[0], next[1], next[2], next[3]]);
+    }
+
+    if value.len() != pointer.len as usize {
+        return Err(corrupted(&format!("expected {} bytes, the chain has {}", pointer.len, value.len())));
+    }
+    Ok(value)
+}
+
+/// Deletes the chunks of the chain
+pub(crate) fn free_blob<S: Store>(store: &S, layout: &PageDataLayout, table: &Table, pointer: BlobPointer) -> Result<(), StoreError> {
+    let overflow = table.overflow_table();
+    let mut page_id = pointer.page_id;
+    while page_id != 0 {
+        let mut page = store.page_guard(layout, page_id.into(), &overflow)?;
+        let next = page.read_slot(0)
+            .and_then(|record| record.get(0..NEXT_PAGE_SIZE))
+            .map(|next| u32::from_be_bytes([next[0], next[1], next[2], next[3]]));
+        page.delete_record(0);
+        page.write()?;
+        // already freed
+        page_id = next.unwrap_or(0);
+    }
+    Ok(())
+}
+
+/// The row as it is stored: the BLOB values are written to overflow pages and replaced by their pointers
+pub(crate) fn store_blobs<S: Store>(store: &S, layout: &PageDataLayout, table: &Table, row: &Row) -> Result<Row, StoreError> {
+    let cells = row.cells().iter()
+        .zip(table.schema().columns.iter())
+        .map(|(cell, column)| match cell {
+            Cell::Blob(value) if column.col_type == ColumnType::Blob => Ok(write_blob(store, layout, table, value)?.to_cell()),
+            cell => Ok(cell.clone()),
+        })
+        .collect::<Result<Vec<Cell>, StoreError>>()?;
+    Ok(Row::new(cells))
+}
+
+/// The row as it is read: the pointers are replaced by the BLOB values
+pub(crate) fn resolve_blobs<S: Store>(store: &S, layout: &PageDataLayout, table: &Table, row: Row) -> Result<Row, StoreError> {
+    if !table.has_blobs() {
+        return Ok(row);
+    }
+    let cells = row.cells().iter()
+        .zip(table.schema().columns.iter())
+        .map(|(cell, column)| match is_blob(cell, &column.col_type) {
+            true => Ok(Cell::Blob(read_blob(store, layout, table, BlobPointer::from_cell(cell)?)?)),
+            false => Ok(cell.clone()),
+        })
+        .collect::<Result<Vec<Cell>, StoreError>>()?;
+    Ok(Row::new(cells))
+}
+
+/// Frees the chains of the pointers of a stored row (of a deleted row)
+pub(crate) fn free_blobs<S: Store>(store: &S, layout: &PageDataLayout, table: &Table, stored: &Row) -> Result<(), StoreError> {
+    for (cell, column) in stored.cells().iter().zip(table.schema().columns.iter()) {
+        if is_blob(cell, &column.col_type) {
+            free_blob(store, layout, table, BlobPointer::from_cell(cell)?)?;
+        }
+    }
+    Ok(())
+}
+
+/// The stored row of an update: the values of the updated BLOB columns are written to new chains (the old chains
+/// are freed), the other BLOB columns keep the pointers of the stored row
+pub(crate) fn store_update<S: Store>(store: &S, layout: &PageDataLayout, table: &Table, stored: &Row, updated: &Row, updated_columns: &[usize]) -> Result<Row, StoreError> {
+    let cells = stored.cells().iter()
+        .zip(updated.cells().iter())
+        .zip(table.schema().columns.iter())
+        .enumerate()
+        .map(|(index, ((stored_cell, updated_cell), column))| match (column.col_type == ColumnType::Blob, updated_cell) {
+            (true, Cell::Blob(value)) if updated_columns.contains(&index) => {
+                let pointer = write_blob(store, layout, table, value)?;
+                free_blob(store, layout, table, BlobPointer::from_cell(stored_cell)?)?;
+                Ok(pointer.to_cell())
+            },
+            (true, _) => Ok(stored_cell.clone()),
+            (false, _) => Ok(updated_cell.clone()),
+        })
+        .collect::<Result<Vec<Cell>, StoreError>>()?;
+    Ok(Row::new(cells))
+}
+
+/// The row with a placeholder pointer for every BLOB value, e.g. to estimate the pages of rows
+pub(crate) fn with_placeholder_pointers(row: &Row, schema: &TableSchema) -> Row {
+    Row::new(row.cells().iter()
+        .zip(schema.columns.iter())
+        .map(|(cell, column)| match is_blob(cell, &column.col_type) {
+            true => BlobPointer { page_id: 0, len: 0 }.to_cell(),
+            false => cell.clone(),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        data::page::{PageDataLayout, PageId},
+        database::{Database, blob::{free_blob, read_blob, write_blob}},
+        store::{PageIterator, Store, file_store::FileStore},
+        table::{Column, ColumnType, TableSchema, table::{Cell, Row, Table}},
+    };
+
+    #[test]
+    fn should_write_a_value_into_a_chain_of_overflow_pages() {
+        let base_path = tempfile::tempdir().unwrap();
+        let store = FileStore::new(base_path.path());
+        let layout = PageDataLayout::new(64).unwrap();
+        let table = Table::new(5, "files".to_owned(), TableSchema::new(vec![Column::new(1, "content", ColumnType::Blob)]));
+        store.create(&layout, &table.overflow_table()).unwrap();
+
+        let value: Vec<u8> = (0..200).map(|i| i as u8).collect();
+        let pointer = write_blob(&store, &layout, &table, &value).unwrap();
+        let chunk_size = layout.max_record_size() - 4;
+        assert_eq!(store.read_metadata(&layout, &table.overflow_table()).unwrap().number_of_pages() as usize, value.len().div_ceil(chunk_size));
+        assert_eq!(read_blob(&store, &layout, &table, pointer).unwrap(), value);
+
+        let empty = write_blob(&store, &layout, &table, &[]).unwrap();
+        assert!(read_blob(&store, &layout, &table, empty).unwrap().is_empty());
+
+        free_blob(&store, &layout, &table, pointer).unwrap();
+        assert!(read_blob(&store, &layout, &table, pointer).is_err());
+    }
+
+    fn number_of_pages(db: &Database<FileStore>, table: &Table) -> PageId {
+        db.store.read_metadata(&db.layout, table).unwrap().number_of_pages()
+    }
+
+    fn live_chunks(db: &Database<FileStore>, table: &Table) -> usize {
+        PageIterator::try_new(table, &db.store, &db.layout).unwrap().map(|page| page.unwrap().live_rows()).sum()
+    }
+
+    #[test]
+    fn should_keep_only_the_pointer_in_the_row() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        let table = db.create_table("files", vec![("id", ColumnType::Int, false, true), ("content", ColumnType::Blob, false, false)]).unwrap();
+        let access = db.table_access(table.clone()).unwrap();
+
+        // larger than a page
+        let large: Vec<u8> = (0..10_000).map(|i| (i % 251) as u8).collect();
+        access.insert(&Row::new(vec![Cell::Int(1), Cell::Blob(large.clone())])).unwrap();
+        access.insert(&Row::new(vec![Cell::Int(2), Cell::Blob(vec![1, 2, 3])])).unwrap();
+        assert_eq!(number_of_pages(&db, &table), 1);
+        assert_eq!(number_of_pages(&db, &table.overflow_table()), 4);
+
+        let (record, row) = access.find("id", Cell::Int(1)).unwrap().first().unwrap().unwrap();
+        assert_eq!(row.cells()[1], Cell::Blob(large));
+        // id + length and pointer of the BLOB
+        assert_eq!(record.data().len(), 4 + 4 + 8);
+
+        access.update(access.find("id", Cell::Int(2)).unwrap(), vec![("content", Cell::Blob(vec![4; 5000]))]).unwrap();
+        let rows: Vec<Row> = access.find_all().unwrap().rows().unwrap().into_iter().map(|(_, row)| row).collect();
+        assert_eq!(rows[1].cells()[1], Cell::Blob(vec![4; 5000]));
+        assert_eq!(access.find_all_batches().unwrap().map(|batch| batch.unwrap().len()).sum::<usize>(), 2);
+
+        access.delete(access.find("id", Cell::Int(1)).unwrap()).unwrap();
+        // only the chain of the updated value is left
+        assert_eq!(live_chunks(&db, &table.overflow_table()), 2);
+
+        db.drop_table("files").unwrap();
+        assert!(!base_path.path().join(table.overflow_table().file_path()).exists());
+    }
+}
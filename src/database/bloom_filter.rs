@@ -0,0 +1,76 @@
+use crate::{
+    database::{Database, DatabaseError},
+    store::Store,
+    table::ColumnType,
+};
+
+// Bloom filters per page over a chosen column, so TableAccess::find skips the pages that don't contain the value
+// (see store/bloom_filter.rs). A table has at most one: creating another replaces it. It is worth it for a column
+// that is searched without index and whose values are spread over the pages (the zone maps of an Int column only
+// help if the values are in the order of the pages).
+// - BLOB columns are compared by their value, but the pages only have a pointer, so they cannot have a filter
+// - encrypted columns cannot have one, because the filter would tell which pages have a value
+
+impl<S: Store> Database<S> {
+    pub fn create_bloom_filter(&self, table_name: &str, column_name: &str) -> Result<(), DatabaseError> {
+        let table = self.read_table(table_name)?;
+        let column = table.schema().columns.iter()
+            .find(|c| c.name == column_name)
+            .ok_or_else(|| DatabaseError::UnknownError(format!("Column '{}' not found in table '{}'", column_name, table.name())))?;
+        if column.col_type == ColumnType::Blob || column.encrypted {
+            return Err(DatabaseError::UnknownError(format!("Column '{}' cannot have a bloom filter (BLOB or encrypted)", column_name)));
+        }
+        let layout = self.table_layout(&table)?;
+        self.store.create_bloom_filter(&layout, &table, column.id)?;
+        Ok(())
+    }
+
+    pub fn drop_bloom_filter(&self, table_name: &str) -> Result<(), DatabaseError> {
+        let table = self.read_table(table_name)?;
+        self.store.drop_bloom_filter(&table)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::Database, store::{Store, file_store::FileStore}, table::{ColumnType, table::{Cell, Row}}};
+
+    #[test]
+    fn should_skip_the_pages_without_the_value() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        let persons = db.create_table_with_page_size("persons", vec![("id", ColumnType::Int), ("email", ColumnType::Varchar(100)), ("photo", ColumnType::Blob)], 256).unwrap();
+        let access = db.table_access(persons.clone()).unwrap();
+        for id in 0..100 {
+            access.insert(&Row::new(vec![Cell::Int(id), Cell::Varchar(format!("{}@example.com", id * 37 % 100)), Cell::Blob(vec![])])).unwrap();
+        }
+        let pages = db.store.read_metadata(&db.table_layout(&persons).unwrap(), &persons).unwrap().number_of_pages();
+        assert!(pages > 10);
+
+        assert!(db.create_bloom_filter("persons", "photo").is_err());
+        assert!(db.create_bloom_filter("persons", "unknown").is_err());
+        db.create_bloom_filter("persons", "email").unwrap();
+
+        let pages_read = |email: &str| {
+            let before = db.store.io_stats();
+            let rows = access.find("email", Cell::Varchar(email.to_owned())).unwrap().rows().unwrap();
+            (rows.len(), db.store.io_stats().since(&before).pages_read)
+        };
+        let (rows, read) = pages_read("74@example.com");
+        assert_eq!(rows, 1);
+        assert!(read < 3, "{} pages read", read);
+        assert!(pages_read("nobody@example.com").1 < 3);
+
+        // the filters follow the written pages
+        access.insert(&Row::new(vec![Cell::Int(100), Cell::Varchar("new@example.com".to_owned()), Cell::Blob(vec![])])).unwrap();
+        assert_eq!(pages_read("new@example.com").0, 1);
+        access.update(access.find("id", Cell::Int(2)).unwrap(), vec![("email", Cell::Varchar("changed@example.com".to_owned()))]).unwrap();
+        assert_eq!(pages_read("changed@example.com").0, 1);
+        assert_eq!(pages_read("74@example.com").0, 0);
+
+        db.drop_bloom_filter("persons").unwrap();
+        assert_eq!(pages_read("nobody@example.com").1, pages + 1);
+    }
+}
@@ -0,0 +1,101 @@
+use std::path::PathBuf;
+
+use crate::{
+    database::{Database, DatabaseError, is_safe_dir_name},
+    store::{branch_store::BranchStore, file_store::FileStore},
+};
+
+// Branches: Database::branch creates a copy-on-write copy of the database in <data directory>/branches/<name>.
+// It shares the pages of this database until it writes them (see store/branch_store.rs), so it is cheap to create
+// even for large tables, e.g. to test a migration against the data of production. The branch has the page layout
+// and the encryption key of this database. A branch is kept until drop_branch, open_branch opens it again.
+
+const BRANCHES_DIR: &str = "branches";
+
+impl Database<FileStore> {
+    pub fn branch(&self, name: &str) -> Result<Database<BranchStore>, DatabaseError> {
+        let path = self.branch_path(name)?;
+        if path.exists() {
+            return Err(DatabaseError::UnknownError(format!("Branch '{}' already exists", name)));
+        }
+        std::fs::create_dir_all(&path)
+            .map_err(|e| DatabaseError::UnknownError(format!("Could not create branch directory: {}", e)))?;
+        self.open_branch(name)
+    }
+
+    pub fn open_branch(&self, name: &str) -> Result<Database<BranchStore>, DatabaseError> {
+        let path = self.branch_path(name)?;
+        if !path.is_dir() {
+            return Err(DatabaseError::UnknownError(format!("Branch '{}' does not exist", name)));
+        }
+        let mut branch = Database::new_with_store(name, BranchStore::new(self.store.base_path(), &path));
+        branch.layout = self.layout.clone();
+        branch.encryption_key = self.encryption_key.clone();
+        Ok(branch)
+    }
+
+    /// Deletes the branch with all its changes, this database is not changed
+    pub fn drop_branch(&self, name: &str) -> Result<(), DatabaseError> {
+        let path = self.branch_path(name)?;
+        std::fs::remove_dir_all(path)
+            .map_err(|e| DatabaseError::UnknownError(format!("Could not delete branch '{}': {}", name, e)))
+    }
+
+    fn branch_path(&self, name: &str) -> Result<PathBuf, DatabaseError> {
+        if !is_safe_dir_name(name) {
+            return Err(DatabaseError::UnknownError("Branch names only allow alphanumeric chars and '_', '-'".to_owned()));
+        }
+        Ok(self.store.base_path().join(BRANCHES_DIR).join(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::Database, store::{Store, file_store::FileStore}, table::{ColumnType, table::{Cell, Row}}};
+
+    fn names<S: Store>(db: &Database<S>) -> Vec<Cell> {
+        let access = db.table_access(db.read_table("persons").unwrap()).unwrap();
+        access.find_all().unwrap().rows().unwrap().into_iter().map(|(_, row)| row.cells()[1].clone()).collect()
+    }
+
+    #[test]
+    fn should_share_the_pages_of_the_parent_until_they_are_written() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        let persons = db.create_table("persons", vec![("id", ColumnType::Int, false, true), ("name", ColumnType::Varchar(100), false, false)]).unwrap();
+        let access = db.table_access(persons.clone()).unwrap();
+        for id in 0..600 {
+            access.insert(&Row::new(vec![Cell::Int(id), Cell::Varchar(format!("person {}", id))])).unwrap();
+        }
+        let parent_pages = db.store.read_metadata(&db.layout, &persons).unwrap().number_of_pages();
+        assert!(parent_pages > 3);
+
+        let branch = db.branch("experiment").unwrap();
+        assert!(db.branch("experiment").is_err());
+        assert!(db.branch("../escape").is_err());
+        let branch_access = branch.table_access(branch.read_table("persons").unwrap()).unwrap();
+        branch_access.update(branch_access.find("id", Cell::Int(0)).unwrap(), vec![("name", Cell::Varchar("changed".to_owned()))]).unwrap();
+        branch_access.insert(&Row::new(vec![Cell::Int(600), Cell::Varchar("new".to_owned())])).unwrap();
+        branch.create_table("only_in_branch", vec![("id", ColumnType::Int)]).unwrap();
+
+        // the updated page and the page of the new row
+        assert!(branch.store.own_pages(&persons).unwrap() <= 2);
+        assert_eq!(names(&branch)[0], Cell::Varchar("changed".to_owned()));
+        assert_eq!(names(&branch).len(), 601);
+        assert_eq!(names(&db)[0], Cell::Varchar("person 0".to_owned()));
+        assert_eq!(names(&db).len(), 600);
+        assert!(db.read_table("only_in_branch").is_err());
+
+        branch.drop_table("persons").unwrap();
+        assert!(branch.read_table("persons").is_err());
+        assert_eq!(names(&db).len(), 600);
+
+        let reopened = db.open_branch("experiment").unwrap();
+        assert!(reopened.read_table("only_in_branch").is_ok());
+        assert!(reopened.read_table("persons").is_err());
+
+        db.drop_branch("experiment").unwrap();
+        assert!(db.open_branch("experiment").is_err());
+    }
+}
@@ -0,0 +1,231 @@
+use crate::{
+    database::{Database, DatabaseError, table_access::TableAccess},
+    store::{ReadConsistency, file_store::FileStore},
+    table::{ColumnType, table::{Cell, Row}},
+};
+
+// Isolation test suite: Hermitage-style scenarios of two interleaved "transactions" T1 and T2 (sequences of
+// operations, there are no transactions yet). Every scenario runs against each read consistency on a scratch
+// database in a temporary directory and checks the values it read for the anomaly.
+// Commit and abort are no-ops (every write is applied immediately and can't be rolled back), so today every
+// anomaly is possible at every level. The report changes as soon as the engine prevents one.
+
+/// The anomalies of the scenarios, see `code` for the names in Adya's/Hermitage's terms
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anomaly {
+    DirtyWrite,
+    AbortedRead,
+    IntermediateRead,
+    CircularInformationFlow,
+    LostUpdate,
+    ReadSkew,
+    WriteSkew,
+    Phantom,
+}
+
+pub const ANOMALIES: [Anomaly; 8] = [
+    Anomaly::DirtyWrite,
+    Anomaly::AbortedRead,
+    Anomaly::IntermediateRead,
+    Anomaly::CircularInformationFlow,
+    Anomaly::LostUpdate,
+    Anomaly::ReadSkew,
+    Anomaly::WriteSkew,
+    Anomaly::Phantom,
+];
+
+impl Anomaly {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Anomaly::DirtyWrite => "G0",
+            Anomaly::AbortedRead => "G1a",
+            Anomaly::IntermediateRead => "G1b",
+            Anomaly::CircularInformationFlow => "G1c",
+            Anomaly::LostUpdate => "P4",
+            Anomaly::ReadSkew => "G-single",
+            Anomaly::WriteSkew => "G2-item",
+            Anomaly::Phantom => "PMP",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelReport {
+    pub level: ReadConsistency,
+    /// Every anomaly of ANOMALIES and whether the scenario observed it
+    pub anomalies: Vec<(Anomaly, bool)>,
+}
+
+/// Result of Database::capabilities
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capabilities {
+    pub transactions: bool,
+    pub levels: Vec<LevelReport>,
+}
+
+impl Capabilities {
+    /// None, if the level was not tested
+    pub fn is_possible(&self, level: ReadConsistency, anomaly: Anomaly) -> Option<bool> {
+        self.levels.iter()
+            .find(|report| report.level == level)
+            .and_then(|report| report.anomalies.iter().find(|(a, _)| *a == anomaly))
+            .map(|(_, possible)| *possible)
+    }
+
+    /// {"transactions":false,"levels":[{"level":"ReadCommitted","anomalies":{"G0":true, ...}}, ...]}
+    pub fn to_json(&self) -> String {
+        let levels: Vec<String> = self.levels.iter().map(|report| {
+            let anomalies: Vec<String> = report.anomalies.iter()
+                .map(|(anomaly, possible)| format!("\"{}\":{}", anomaly.code(), possible))
+                .collect();
+            format!("{{\"level\":\"{:?}\",\"anomalies\":{{{}}}}}", report.level, anomalies.join(","))
+        }).collect();
+        format!("{{\"transactions\":{},\"levels\":[{}]}}", self.transactions, levels.join(","))
+    }
+}
+
+impl Database<FileStore> {
+    /// Runs the isolation test suite and reports which anomalies are possible at each read consistency
+    pub fn capabilities() -> Result<Capabilities, DatabaseError> {
+        let dir = tempfile::tempdir().map_err(|e| DatabaseError::UnknownError(e.to_string()))?;
+        let db = Database::new_with_store("capabilities", FileStore::new(dir.path()));
+        db.drop_create()?;
+
+        let mut levels = Vec::new();
+        for (i, level) in [ReadConsistency::ReadCommitted, ReadConsistency::ReadUncommitted].into_iter().enumerate() {
+            let mut anomalies = Vec::new();
+            for (j, anomaly) in ANOMALIES.into_iter().enumerate() {
+                let table = db.create_table(&format!("t{}_{}", i, j), vec![("id", ColumnType::Int, false, true), ("value", ColumnType::Int, false, false)])
+                    .map_err(|e| DatabaseError::UnknownError(e.to_string()))?;
+                let run = Run { access: db.table_access(table)?, level };
+                run.insert(1, 10)?;
+                run.insert(2, 20)?;
+                anomalies.push((anomaly, run.observes(anomaly)?));
+            }
+            levels.push(LevelReport { level, anomalies });
+        }
+        Ok(Capabilities { transactions: false, levels })
+    }
+}
+
+// x is the row with id 1 (10), y the row with id 2 (20)
+struct Run<'db> {
+    access: TableAccess<'db, FileStore>,
+    level: ReadConsistency,
+}
+
+impl<'db> Run<'db> {
+    fn observes(&'db self, anomaly: Anomaly) -> Result<bool, DatabaseError> {
+        let observed = match anomaly {
+            Anomaly::DirtyWrite => {
+                self.write(1, 11)?; // T1
+                self.write(1, 12)?; // T2
+                self.write(2, 22)?; // T2
+                self.write(2, 21)?; // T1
+                // x of T2 and y of T1
+                (self.read(1)?, self.read(2)?) == (12, 21)
+            },
+            Anomaly::AbortedRead => {
+                self.write(1, 101)?; // T1, aborts after T2 read
+                self.read(1)? == 101 // T2
+            },
+            Anomaly::IntermediateRead => {
+                self.write(1, 101)?; // T1
+                let seen = self.read(1)?; // T2
+                self.write(1, 11)?; // T1 commits
+                seen == 101
+            },
+            Anomaly::CircularInformationFlow => {
+                self.write(1, 11)?; // T1
+                self.write(2, 22)?; // T2
+                // each sees the other's write
+                self.read(2)? == 22 && self.read(1)? == 11
+            },
+            Anomaly::LostUpdate => {
+                let t1 = self.read(1)?;
+                let t2 = self.read(1)?;
+                self.write(1, t1 + 1)?;
+                self.write(1, t2 + 1)?;
+                self.read(1)? != 12
+            },
+            Anomaly::ReadSkew => {
+                let x = self.read(1)?; // T1
+                self.write(1, 12)?; // T2 keeps x + y = 30
+                self.write(2, 18)?;
+                let y = self.read(2)?; // T1
+                x + y != 30
+            },
+            Anomaly::WriteSkew => {
+                // constraint x + y >= 0: both check it for their own write, then both write
+                let t1_ok = self.read(1)? + self.read(2)? - 20 >= 0;
+                let t2_ok = self.read(1)? + self.read(2)? - 20 >= 0;
+                if t1_ok {
+                    self.write(1, self.read(1)? - 20)?;
+                }
+                if t2_ok {
+                    self.write(2, self.read(2)? - 20)?;
+                }
+                self.read(1)? + self.read(2)? < 0
+            },
+            Anomaly::Phantom => {
+                let before = self.count(30)?; // T1
+                self.insert(3, 30)?; // T2
+                self.count(30)? != before // T1
+            },
+        };
+        Ok(observed)
+    }
+
+    fn read(&'db self, id: i32) -> Result<i32, DatabaseError> {
+        self.values()?.into_iter()
+            .find(|(row_id, _)| *row_id == id)
+            .map(|(_, value)| value)
+            .ok_or_else(|| DatabaseError::UnknownError(format!("Row {} not found", id)))
+    }
+
+    fn count(&'db self, value: i32) -> Result<usize, DatabaseError> {
+        Ok(self.values()?.into_iter().filter(|(_, v)| *v == value).count())
+    }
+
+    // (id, value) of all rows, scanned with the level of the run
+    fn values(&'db self) -> Result<Vec<(i32, i32)>, DatabaseError> {
+        self.access.find_all_with(self.level)?.rows()?.into_iter()
+            .map(|(_, row)| match row.cells().as_slice() {
+                [Cell::Int(id), Cell::Int(value)] => Ok((*id, *value)),
+                _ => Err(DatabaseError::UnknownError("Invalid row".to_owned())),
+            })
+            .collect()
+    }
+
+    fn write(&'db self, id: i32, value: i32) -> Result<(), DatabaseError> {
+        self.access.update(self.access.find("id", Cell::Int(id))?, vec![("value", Cell::Int(value))])?;
+        Ok(())
+    }
+
+    fn insert(&self, id: i32, value: i32) -> Result<(), DatabaseError> {
+        self.access.insert(&Row::new(vec![Cell::Int(id), Cell::Int(value)]))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::{Database, capabilities::{ANOMALIES, Anomaly}}, store::ReadConsistency};
+
+    // The expected results per level: without transactions, nothing is prevented yet
+    #[test]
+    fn should_report_the_anomalies_of_each_level() {
+        let capabilities = Database::capabilities().unwrap();
+        assert!(!capabilities.transactions);
+        for level in [ReadConsistency::ReadCommitted, ReadConsistency::ReadUncommitted] {
+            for anomaly in ANOMALIES {
+                assert_eq!(capabilities.is_possible(level, anomaly), Some(true), "{:?} {:?}", level, anomaly);
+            }
+        }
+
+        let json = capabilities.to_json();
+        assert!(json.starts_with("{\"transactions\":false,\"levels\":[{\"level\":\"ReadCommitted\",\"anomalies\":{\"G0\":true,"));
+        assert!(json.contains("\"G2-item\":true"));
+        assert_eq!(Anomaly::WriteSkew.code(), "G2-item");
+    }
+}
@@ -0,0 +1,137 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use crate::{
+    database::{Database, DatabaseError, NULL_INT},
+    store::Store,
+    table::table::Cell,
+};
+
+// Approximate number of distinct values with a HyperLogLog sketch (Flajolet et al., with the linear counting
+// correction for small cardinalities). The sketch has 2^PRECISION one-byte registers (4 KiB), the standard
+// error is about 1.04 / sqrt(2^PRECISION) = 1.6%.
+//
+// The statistics don't keep sketches, so approx_count_distinct reads the whole column: it still needs a full scan,
+// but only 4 KiB of memory instead of a set of all values.
+const PRECISION: u32 = 12;
+const REGISTERS: usize = 1 << PRECISION;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HyperLogLog {
+    pub fn new() -> Self {
+        Self {
+            registers: vec![0; REGISTERS],
+        }
+    }
+
+    pub fn add<T: Hash>(&mut self, value: &T) {
+        // DefaultHasher::new() always uses the same keys, so a sketch is the same for the same values
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let register = (hash >> (64 - PRECISION)) as usize;
+        // position of the first 1 bit in the remaining bits
+        let rank = ((hash << PRECISION) | (1 << (PRECISION - 1))).leading_zeros() as u8 + 1;
+        self.registers[register] = self.registers[register].max(rank);
+    }
+
+    /// The sketch of the union of both inputs
+    pub fn merge(&mut self, other: &HyperLogLog) {
+        for (register, other) in self.registers.iter_mut().zip(other.registers.iter()) {
+            *register = (*register).max(*other);
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        let m = REGISTERS as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|r| 2f64.powi(-(*r as i32))).sum();
+        let estimate = alpha * m * m / sum;
+
+        let zeros = self.registers.iter().filter(|r| **r == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+}
+
+impl<S: Store> Database<S> {
+    /// Approximate number of distinct values of the column (see HyperLogLog), NULLs are not counted
+    pub fn approx_count_distinct(&self, table_name: &str, column: &str) -> Result<u64, DatabaseError> {
+        let table = self.read_table(table_name)?;
+        let index = table.schema().find_index_by_name(column)
+            .ok_or_else(|| DatabaseError::UnknownError(format!("Column '{}' not found in table '{}'", column, table_name)))?;
+
+        let access = self.table_access(table)?;
+        let mut sketch = HyperLogLog::new();
+        for res in access.find_all()? {
+            let (_, row) = res?;
+            let cell = &row.cells()[index];
+            if *cell != Cell::Int(NULL_INT) {
+                sketch.add(cell);
+            }
+        }
+        Ok(sketch.count())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::{Database, NULL_INT, cardinality::HyperLogLog}, store::file_store::FileStore, table::{ColumnType, table::{Cell, Row}}};
+
+    #[test]
+    fn should_estimate_distinct_values() {
+        let mut sketch = HyperLogLog::new();
+        assert_eq!(sketch.count(), 0);
+        for i in 0..100 {
+            sketch.add(&(i % 10));
+        }
+        assert_eq!(sketch.count(), 10);
+
+        let mut large = HyperLogLog::new();
+        for i in 0..100_000 {
+            large.add(&Cell::Int(i));
+            large.add(&Cell::Int(i));
+        }
+        let count = large.count() as f64;
+        assert!((count - 100_000.0).abs() < 5_000.0, "{}", count);
+
+        let mut other = HyperLogLog::new();
+        for i in 50_000..150_000 {
+            other.add(&Cell::Int(i));
+        }
+        large.merge(&other);
+        let count = large.count() as f64;
+        assert!((count - 150_000.0).abs() < 7_500.0, "{}", count);
+    }
+
+    #[test]
+    fn should_count_distinct_values_of_a_column() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        db.create_table("t", vec![("id", ColumnType::Int), ("name", ColumnType::Varchar(10))]).unwrap();
+
+        let access = db.table_access(db.read_table("t").unwrap()).unwrap();
+        for i in 0..50 {
+            access.insert(&Row::new(vec![Cell::Int(i % 20), Cell::Varchar(format!("n{}", i % 5))])).unwrap();
+        }
+        access.insert(&Row::new(vec![Cell::Int(NULL_INT), Cell::Varchar("n0".to_owned())])).unwrap();
+
+        assert_eq!(db.approx_count_distinct("t", "id").unwrap(), 20);
+        assert_eq!(db.approx_count_distinct("t", "name").unwrap(), 5);
+        assert!(db.approx_count_distinct("t", "unknown").is_err());
+    }
+}
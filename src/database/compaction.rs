@@ -0,0 +1,376 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{Arc, Mutex, MutexGuard, PoisonError, mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError}},
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use crate::{
+    data::page::{PageDataLayout, PageId},
+    database::{Database, DatabaseError, table_access::TableAccess, throttle::{ResourceConfig, Throttle}},
+    store::{PageIterator, Store, StoreError, file_store::FileStore},
+    table::table::{Cell, Table},
+};
+
+// Background compaction: an opt-in std thread that scans the tables every `interval` for pages with many dead rows
+// (deleted rows whose slot was not reused yet, see vacuum.rs).
+//
+// A Database is single threaded (Rc, RefCell), so the thread must not write a page the Database may write at the
+// same time. The work is split:
+// - the thread reads the tables with its own read-only Database on a FileStore::read_only_view of the store and
+//   collects the pages whose share of dead rows is at least min_dead_ratio. The view shares the page lock of the
+//   store, so a page is never read while the Database writes it or truncates the file.
+// - the collected pages are handed off to the thread of the Database: the next insert, update or delete of a
+//   TableAccess of the table (created by Database::table_access while the job runs) compacts the found pages of its
+//   table after its own write. run_pending_compaction compacts the found pages of all tables at once (e.g. when the
+//   application is idle). The pages are compacted like by vacuum: the slot indexes don't change, so the indexes are
+//   not rewritten. A found page may have changed until then, so it is read again and skipped if it has no dead space
+//   anymore. Writing the page updates its entry in the free space map, so inserts use the reclaimed space. The pages
+//   at the end of a table that have no slots left are cut off (Store::truncate), empty pages in between stay
+//   allocated (see vacuum.rs).
+// The scans are throttled with the ResourceConfig of the Database. The thread is stopped by stop_compaction or when the
+// Database is dropped.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompactionConfig {
+    /// Pause between two scans of all tables
+    pub interval: Duration,
+    /// A page is compacted if at least this share of its rows is deleted (0 to 1)
+    pub min_dead_ratio: f64,
+}
+
+impl Default for CompactionConfig {
+    fn default() -> Self {
+        Self { interval: Duration::from_secs(60), min_dead_ratio: 0.25 }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompactionProgress {
+    /// Finished scans of all tables
+    pub rounds: u64,
+    pub pages_scanned: u64,
+    /// Pages found by the scans that were not compacted yet (see CompactionHandoff)
+    pub pages_pending: usize,
+    pub pages_compacted: u64,
+    pub bytes_reclaimed: u64,
+    /// Empty pages removed from the end of the tables
+    pub pages_truncated: u64,
+    /// Error of the last scan or compaction that failed, the next round scans the table again
+    pub last_error: Option<String>,
+}
+
+#[derive(Default)]
+struct SharedState {
+    progress: CompactionProgress,
+    // table name => pages to compact
+    pending: BTreeMap<String, BTreeSet<PageId>>,
+}
+
+type Shared = Arc<Mutex<SharedState>>;
+
+// the thread only reads, a panic in it cannot leave the state half written
+fn lock(shared: &Shared) -> MutexGuard<'_, SharedState> {
+    shared.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+pub(crate) struct CompactionJob {
+    stop: Sender<()>,
+    handle: Option<JoinHandle<()>>,
+    shared: Shared,
+}
+
+impl CompactionJob {
+    pub(crate) fn handoff(&self) -> CompactionHandoff {
+        CompactionHandoff(self.shared.clone())
+    }
+
+    fn progress(&self) -> CompactionProgress {
+        let state = lock(&self.shared);
+        CompactionProgress {
+            pages_pending: state.pending.values().map(BTreeSet::len).sum(),
+            ..state.progress.clone()
+        }
+    }
+}
+
+impl Drop for CompactionJob {
+    fn drop(&mut self) {
+        let _ = self.stop.send(());
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// The found pages, compacted by the writes of a TableAccess (see TableAccess::with_compaction)
+#[derive(Clone)]
+pub struct CompactionHandoff(Shared);
+
+impl CompactionHandoff {
+    /// Compacts the found pages of the table. A write that triggered it has already succeeded, so an error is
+    /// only recorded in the progress (last_error).
+    pub(crate) fn compact<S: Store>(&self, store: &S, layout: &PageDataLayout, table: &Table) {
+        let Some(page_ids) = lock(&self.0).pending.remove(table.name()) else {
+            return;
+        };
+        if let Err(e) = compact_pages(store, layout, table, page_ids, &self.0, None) {
+            lock(&self.0).progress.last_error = Some(format!("table '{}': {}", table.name(), e));
+        }
+    }
+}
+
+impl Database<FileStore> {
+    /// Starts the thread that looks for pages to compact, fails if it is already running
+    pub fn start_compaction(&self, config: CompactionConfig) -> Result<(), DatabaseError> {
+        if self.compaction.borrow().is_some() {
+            return Err(DatabaseError::UnknownError("The compaction is already running".to_owned()));
+        }
+        let (stop, stopped) = mpsc::channel();
+        let shared = Shared::default();
+        let thread_shared = shared.clone();
+        let (name, store, resource_config) = (self.name.clone(), self.store.read_only_view(), self.resource_config);
+        let handle = std::thread::Builder::new()
+            .name(format!("{}-compaction", name))
+            .spawn(move || scan_loop(name, store, config, resource_config, stopped, thread_shared))
+            .map_err(|e| DatabaseError::UnknownError(format!("Cannot start the compaction thread: {}", e)))?;
+
+        *self.compaction.borrow_mut() = Some(CompactionJob { stop, handle: Some(handle), shared });
+        Ok(())
+    }
+
+    /// Stops the thread and returns its final progress, None if it was not running.
+    /// The pages that were found but not compacted yet are dropped (also for the TableAccesses created before).
+    pub fn stop_compaction(&self) -> Option<CompactionProgress> {
+        let job = self.compaction.borrow_mut().take()?;
+        let progress = job.progress();
+        drop(job);
+        Some(progress)
+    }
+
+    /// None, if the compaction is not running
+    pub fn compaction_progress(&self) -> Option<CompactionProgress> {
+        self.compaction.borrow().as_ref().map(CompactionJob::progress)
+    }
+
+    /// Compacts the pages the thread has found so far in all tables, returns their number (0 if the compaction is
+    /// not running). Pages that were compacted or emptied by vacuum in the meantime are skipped. Afterwards, the empty
+    /// pages at the end of the tables are removed.
+    pub fn run_pending_compaction(&self) -> Result<usize, DatabaseError> {
+        let Some(shared) = self.compaction.borrow().as_ref().map(|job| job.shared.clone()) else {
+            return Ok(0);
+        };
+        let pending = std::mem::take(&mut lock(&shared).pending);
+
+        let mut compacted = 0;
+        let mut throttle = self.throttle();
+        for (name, page_ids) in pending {
+            // dropped in the meantime
+            let Ok(table) = self.read_table(&name) else {
+                continue;
+            };
+            let layout = self.table_layout(&table)?;
+            compacted += compact_pages(&self.store, &layout, &table, page_ids, &shared, Some(&mut throttle))?;
+        }
+        Ok(compacted)
+    }
+}
+
+fn compact_pages<S: Store>(store: &S, layout: &PageDataLayout, table: &Table, page_ids: BTreeSet<PageId>, shared: &Shared, mut throttle: Option<&mut Throttle>) -> Result<usize, StoreError> {
+    let number_of_pages = store.read_metadata(layout, table)?.number_of_pages();
+    let mut compacted = 0;
+    let mut last_compacted = 0;
+    for page_id in page_ids.into_iter().filter(|page_id| *page_id <= number_of_pages) {
+        let mut page = store.read_page(layout, page_id, table)?;
+        let dead_space = page.dead_space();
+        if dead_space == 0 {
+            continue;
+        }
+        page.compact();
+        store.write_page(layout, &page, table)?;
+        if let Some(throttle) = throttle.as_deref_mut() {
+            throttle.consume(2, 2 * layout.page_size() as u64);
+        }
+        compacted += 1;
+        last_compacted = page_id;
+
+        let mut state = lock(shared);
+        state.progress.pages_compacted += 1;
+        state.progress.bytes_reclaimed += dead_space as u64;
+    }
+
+    // only a compaction at the end can have left empty pages there
+    if last_compacted == number_of_pages {
+        let mut last_used = number_of_pages;
+        while last_used > 0 && store.read_page(layout, last_used, table)?.slot_size() == 0 {
+            if let Some(throttle) = throttle.as_deref_mut() {
+                throttle.consume(1, layout.page_size() as u64);
+            }
+            last_used -= 1;
+        }
+        let truncated = store.truncate(layout, table, last_used)?;
+        lock(shared).progress.pages_truncated += truncated;
+    }
+    Ok(compacted)
+}
+
+fn scan_loop(name: String, store: FileStore, config: CompactionConfig, resource_config: ResourceConfig, stopped: Receiver<()>, shared: Shared) {
+    let db = Database::new_with_store(&name, store);
+    let is_stopped = || matches!(stopped.try_recv(), Ok(()) | Err(TryRecvError::Disconnected));
+    loop {
+        let mut throttle = Throttle::new(resource_config);
+        let tables = match table_names(&db) {
+            Ok(tables) => tables,
+            Err(e) => {
+                lock(&shared).progress.last_error = Some(e.to_string());
+                Vec::new()
+            },
+        };
+        for table_name in tables {
+            if is_stopped() {
+                return;
+            }
+            if let Err(e) = scan_table(&db, &table_name, &config, &mut throttle, &shared) {
+                lock(&shared).progress.last_error = Some(format!("table '{}': {}", table_name, e));
+            }
+        }
+        lock(&shared).progress.rounds += 1;
+
+        match stopped.recv_timeout(config.interval) {
+            Err(RecvTimeoutError::Timeout) => continue,
+            _ => return,
+        }
+    }
+}
+
+// names of all tables in the catalog (see disk_usage)
+fn table_names(db: &Database<FileStore>) -> Result<Vec<String>, DatabaseError> {
+    let access = TableAccess::new(db.table_instance(), &db.store, &db.layout);
+    access.find_all()?.rows()?.into_iter()
+        .map(|(_, row)| match row.cells().as_slice() {
+            [Cell::Int(_), Cell::Varchar(name)] => Ok(name.clone()),
+            _ => Err(DatabaseError::CorruptedDatabase("Invalid row in 'tables' table".to_owned())),
+        })
+        .collect()
+}
+
+fn scan_table(db: &Database<FileStore>, table_name: &str, config: &CompactionConfig, throttle: &mut Throttle, shared: &Shared) -> Result<(), DatabaseError> {
+    let table = db.read_table(table_name)?;
+    let layout = db.table_layout(&table)?;
+    for page in PageIterator::try_new(&table, &db.store, &layout)? {
+        let page = page?;
+        throttle.consume(1, layout.page_size() as u64);
+        let rows = page.live_rows() + page.dead_rows();
+        let dead_ratio = match rows {
+            0 => 0.0,
+            rows => page.dead_rows() as f64 / rows as f64,
+        };
+
+        let mut state = lock(shared);
+        state.progress.pages_scanned += 1;
+        if page.dead_rows() > 0 && dead_ratio >= config.min_dead_ratio {
+            state.pending.entry(table_name.to_owned()).or_default().insert(page.page_id());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{database::{Database, compaction::CompactionConfig}, store::{Store, file_store::FileStore}, table::{ColumnType, table::{Cell, Row}}};
+
+    #[test]
+    fn should_find_the_pages_with_dead_rows_in_the_background_and_compact_them() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        let table = db.create_table_with_page_size("t", vec![("id", ColumnType::Int, false, true), ("name", ColumnType::Varchar(100), false, false)], 256).unwrap();
+        let access = db.table_access(table.clone()).unwrap();
+        for i in 0..30 {
+            access.insert(&Row::new(vec![Cell::Int(i), Cell::Varchar(format!("a longer name {}", i))])).unwrap();
+        }
+        let layout = db.table_layout(&table).unwrap();
+        // half of the rows of page 1, one row of page 2
+        let page = db.store.read_page(&layout, 1, &table).unwrap();
+        let first_ids: Vec<i32> = (0..30).take(page.live_rows()).collect();
+        for i in first_ids.iter().step_by(2).chain([first_ids.len() as i32].iter()) {
+            access.delete(access.find("id", Cell::Int(*i)).unwrap()).unwrap();
+        }
+
+        assert_eq!(db.run_pending_compaction().unwrap(), 0, "not running");
+        let config = CompactionConfig { interval: Duration::from_millis(10), min_dead_ratio: 0.4 };
+        db.start_compaction(config).unwrap();
+        assert!(db.start_compaction(config).is_err());
+
+        let started = Instant::now();
+        while db.compaction_progress().unwrap().rounds == 0 {
+            assert!(started.elapsed() < Duration::from_secs(10), "no scan finished");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        let progress = db.compaction_progress().unwrap();
+        assert_eq!(progress.pages_pending, 1, "page 2 has too few dead rows");
+        assert!(progress.pages_scanned >= 3);
+
+        assert_eq!(db.run_pending_compaction().unwrap(), 1);
+        assert_eq!(db.store.read_page(&layout, 1, &table).unwrap().dead_space(), 0);
+        assert_eq!(access.find_all().unwrap().rows().unwrap().len(), 30 - first_ids.len().div_ceil(2) - 1);
+        assert_eq!(access.find("id", Cell::Int(1)).unwrap().rows().unwrap()[0].1.cells()[1], Cell::Varchar("a longer name 1".to_owned()));
+
+        // the emptied pages at the end are removed
+        let pages = db.store.read_metadata(&layout, &table).unwrap().number_of_pages();
+        for i in 20..30 {
+            access.delete(access.find("id", Cell::Int(i)).unwrap()).unwrap();
+        }
+        let rounds = db.compaction_progress().unwrap().rounds;
+        while db.compaction_progress().unwrap().rounds < rounds + 2 {
+            assert!(started.elapsed() < Duration::from_secs(10), "no scan finished");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert!(db.run_pending_compaction().unwrap() > 0);
+        let remaining = db.store.read_metadata(&layout, &table).unwrap().number_of_pages();
+        assert!(remaining < pages);
+        assert!(db.store.read_page(&layout, remaining, &table).unwrap().live_rows() > 0);
+        assert_eq!(access.find_all().unwrap().rows().unwrap().len(), 20 - first_ids.len().div_ceil(2) - 1);
+
+        let progress = db.stop_compaction().unwrap();
+        assert!(progress.pages_compacted > 1);
+        assert_eq!(progress.pages_truncated, pages - remaining);
+        assert!(progress.bytes_reclaimed > 0);
+        assert!(db.compaction_progress().is_none() && db.stop_compaction().is_none());
+    }
+
+    #[test]
+    fn should_compact_the_found_pages_on_the_next_write_of_the_table() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        let table = db.create_table_with_page_size("t", vec![("id", ColumnType::Int, false, true), ("name", ColumnType::Varchar(100), false, false)], 256).unwrap();
+        let access = db.table_access(table.clone()).unwrap();
+        for i in 0..10 {
+            access.insert(&Row::new(vec![Cell::Int(i), Cell::Varchar(format!("a longer name {}", i))])).unwrap();
+        }
+        for i in (0..6).step_by(2) {
+            access.delete(access.find("id", Cell::Int(i)).unwrap()).unwrap();
+        }
+
+        db.start_compaction(CompactionConfig { interval: Duration::from_millis(10), min_dead_ratio: 0.1 }).unwrap();
+        let started = Instant::now();
+        while db.compaction_progress().unwrap().pages_pending == 0 {
+            assert!(started.elapsed() < Duration::from_secs(10), "page 1 not found");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        // only the accesses created while the job runs compact
+        let access = db.table_access(table.clone()).unwrap();
+        access.delete(access.find("id", Cell::Int(1)).unwrap()).unwrap();
+        let layout = db.table_layout(&table).unwrap();
+        assert_eq!(db.store.read_page(&layout, 1, &table).unwrap().dead_space(), 0);
+        let progress = db.compaction_progress().unwrap();
+        assert!(progress.pages_compacted >= 1);
+        assert_eq!(progress.last_error, None);
+        assert_eq!(access.find_all().unwrap().rows().unwrap().len(), 6);
+        assert_eq!(db.run_pending_compaction().unwrap(), 0, "nothing left to compact");
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use crate::{database::{CreateColumnCommand, CreateTableError, Database, DatabaseError}, store::Store, table::table::{Cell, Table}};
+
+impl<S: Store> Database<S> {
+    /// Copies the table `src_name` of `src_db` into this database as `dest_name` (schema, unique indexes, sequences and rows).
+    /// If both tables use the same page layout (and there are no encrypted or BLOB columns), the pages are copied as they are.
+    /// Otherwise the rows are inserted one by one.
+    pub fn copy_table<T: Store>(&self, src_db: &Database<T>, src_name: &str, dest_name: &str) -> Result<Table, CreateTableError> {
+        let src_table = src_db.read_table(src_name)?;
+        let src_access = src_db.table_access(src_table.clone())?;
+        let indexed = src_access.indexed_column_ids();
+        let sequences = src_db.sequence_values(&src_table)?;
+
+        let commands: Vec<CreateColumnCommand> = src_table.schema().columns.iter()
+            .map(|c| (c.name.as_str(), c.col_type.clone(), sequences.contains_key(&c.id), indexed.contains(&c.id), c.encrypted).into())
+            .collect();
+        let dest_table = self.create_table(dest_name, commands)?;
+
+        let res = self.copy_table_data(src_db, &src_table, &dest_table, &sequences);
+        if res.is_err() {
+            // don't leave a half copied table behind
+            let _ = self.drop_table(dest_table.name());
+        }
+        res.map(|_| dest_table)
+    }
+
+    fn copy_table_data<T: Store>(&self, src_db: &Database<T>, src_table: &Table, dest_table: &Table, sequences: &HashMap<i32, i32>)
+     -> Result<(), CreateTableError> {
+        for (src_col, dest_col) in src_table.schema().columns.iter().zip(dest_table.schema().columns.iter()) {
+            if let Some(current) = sequences.get(&src_col.id) {
+                self.set_sequence_value(dest_col.id, *current)?;
+            }
+        }
+
+        let dest_access = self.table_access(dest_table.clone())?;
+        // encrypted values must be re-encrypted with the key of this database
+        let has_encrypted_columns = src_table.schema().columns.iter().any(|c| c.encrypted);
+        let src_layout = src_db.table_layout(src_table)?;
+        // the pointers of BLOB values point into the overflow pages of the source table
+        if src_layout == self.table_layout(dest_table)? && !has_encrypted_columns && !src_table.has_blobs() {
+            dest_access.load_pages(src_db.store.seq_page_iterator(&src_layout, src_table)?)?;
+        } else {
+            let src_access = src_db.table_access(src_table.clone())?;
+            for res in src_access.find_all()? {
+                let (_, row) = res?;
+                dest_access.insert(&row)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // column id => current value of its sequence
+    fn sequence_values(&self, table: &Table) -> Result<HashMap<i32, i32>, DatabaseError> {
+        let seq_access = self.table_access(self.read_table("sequences")?)?;
+        let mut values = HashMap::new();
+        for column in table.schema().columns.iter() {
+            let query = seq_access.find("col_id", Cell::Int(column.id))?;
+            let current_idx = query.schema().find_index_by_name("current")
+                .ok_or_else(|| DatabaseError::CorruptedDatabase("Table 'sequences' does not have a 'current' column".to_owned()))?;
+            if let Some((_, row)) = query.rows()?.into_iter().next() {
+                match &row.cells()[current_idx] {
+                    Cell::Int(current) => values.insert(column.id, *current),
+                    _ => return Err(DatabaseError::CorruptedDatabase("Column 'current' of table 'sequences' must be of type INT".to_owned())),
+                };
+            }
+        }
+        Ok(values)
+    }
+
+    fn set_sequence_value(&self, col_id: i32, current: i32) -> Result<(), DatabaseError> {
+        let seq_access = self.table_access(self.read_table("sequences")?)?;
+        seq_access.update(seq_access.find("col_id", Cell::Int(col_id))?, vec![("current", Cell::Int(current))])?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::Database, store::file_store::FileStore, table::{ColumnType, table::{Cell, Row}}};
+
+    #[test]
+    fn should_copy_table_with_indexes_and_sequences() {
+        let src_path = tempfile::tempdir().unwrap();
+        let src = Database::new_with_store("src_db", FileStore::new(src_path.path()));
+        src.drop_create().unwrap();
+
+        let table = src.create_table("users", vec![
+            ("id", ColumnType::Int, true, true),
+            ("name", ColumnType::Varchar(200), false, false),
+        ]).unwrap();
+        let mut seq = src.seq_access_for_table(table.clone()).unwrap();
+        let access = src.table_access(table).unwrap();
+        for i in 0..100 {
+            let id = seq.next_val("id").unwrap();
+            access.insert(&Row::new(vec![Cell::Int(id), Cell::Varchar(format!("user {}", i))])).unwrap();
+        }
+        access.delete(access.find("id", Cell::Int(50)).unwrap()).unwrap();
+
+        let dest_path = tempfile::tempdir().unwrap();
+        let dest = Database::new_with_store("dest_db", FileStore::new(dest_path.path()));
+        dest.drop_create().unwrap();
+
+        let copy = dest.copy_table(&src, "users", "users_backup").unwrap();
+        assert_eq!(copy.name(), "users_backup");
+
+        let copy_access = dest.table_access(copy.clone()).unwrap();
+        assert_eq!(copy_access.find_all().unwrap().rows().unwrap().len(), 99);
+        let by_index = copy_access.find("id", Cell::Int(77)).unwrap().rows().unwrap();
+        assert_eq!(by_index[0].1.cells()[1], Cell::Varchar("user 76".to_owned()));
+        assert!(copy_access.find("id", Cell::Int(50)).unwrap().rows().unwrap().is_empty());
+
+        // the sequence continues where the source stopped
+        assert_eq!(dest.seq_access_for_table(copy).unwrap().next_val("id").unwrap(), 101);
+    }
+}
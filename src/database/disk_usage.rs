@@ -0,0 +1,151 @@
+use crate::{
+    data::page::PageId,
+    database::{Database, DatabaseError, table_access::TableAccess},
+    store::{PageIterator, Store},
+    table::table::Cell,
+};
+
+// Disk usage per table (catalog tables included), computed by reading all pages of every table.
+// Dead rows are deleted rows whose slot was not reused yet, they take space on their page until the next vacuum.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableDiskUsage {
+    pub t_id: i32,
+    pub name: String,
+    pub heap_pages: PageId,
+    // pages without live rows
+    pub free_pages: i32,
+    // B-tree pages of all indexes of the table (including deleted B-tree pages)
+    pub index_pages: u32,
+    pub live_rows: usize,
+    pub dead_rows: usize,
+    // table file and index files, including the pages preallocated by FileStore::with_extent_size
+    pub bytes_on_disk: u64,
+}
+
+impl<S: Store> Database<S> {
+    pub fn disk_usage(&self) -> Result<Vec<TableDiskUsage>, DatabaseError> {
+        let table_access = TableAccess::new(self.table_instance(), &self.store, &self.layout);
+
+        let mut usage = Vec::new();
+        for (_, row) in table_access.find_all()?.rows()? {
+            let name = match row.cells().as_slice() {
+                [Cell::Int(_), Cell::Varchar(name)] => name.clone(),
+                _ => return Err(DatabaseError::CorruptedDatabase("Invalid row in 'tables' table".to_owned())),
+            };
+            usage.push(self.table_disk_usage(&name)?);
+        }
+
+        Ok(usage)
+    }
+
+    pub fn table_disk_usage(&self, table_name: &str) -> Result<TableDiskUsage, DatabaseError> {
+        let table = self.read_table(table_name)?;
+        let layout = self.table_layout(&table)?;
+
+        let mut usage = TableDiskUsage {
+            t_id: table.id(),
+            name: table.name().to_owned(),
+            heap_pages: self.store.read_metadata(&layout, &table)?.number_of_pages(),
+            free_pages: 0,
+            index_pages: 0,
+            live_rows: 0,
+            dead_rows: 0,
+            bytes_on_disk: self.store.disk_size(&table)?,
+        };
+
+        let mut throttle = self.throttle();
+        for page in PageIterator::try_new(&table, &self.store, &layout)? {
+            let page = page?;
+            throttle.consume(1, layout.page_size() as u64);
+            let live_rows = page.live_rows();
+            if live_rows == 0 {
+                usage.free_pages += 1;
+            }
+            usage.live_rows += live_rows;
+            usage.dead_rows += page.dead_rows();
+        }
+
+        for btree_id in self.index_ids(table.id())? {
+            usage.index_pages += self.store.read_btree(btree_id)?.number_of_pages();
+            usage.bytes_on_disk += self.store.btree_disk_size(btree_id)?;
+        }
+        // the overflow pages of the BLOB values (see blob.rs)
+        if table.has_blobs() {
+            usage.bytes_on_disk += self.store.disk_size(&table.overflow_table())?;
+        }
+
+        Ok(usage)
+    }
+
+    fn index_ids(&self, t_id: i32) -> Result<Vec<i32>, DatabaseError> {
+        // the table 'indexes' itself cannot have indexes (see table_access)
+        let indexes = self.read_table("indexes")?;
+        let access = TableAccess::new(indexes, &self.store, &self.layout);
+        let result = access.find("t_id", Cell::Int(t_id))?;
+        let id_idx = result.schema().find_index_by_name("id")
+            .ok_or_else(|| DatabaseError::CorruptedDatabase("Table 'indexes' does not have an 'id' column".to_owned()))?;
+
+        result.rows()?.into_iter()
+            .map(|(_, row)| match &row.cells()[id_idx] {
+                Cell::Int(id) => Ok(*id),
+                _ => Err(DatabaseError::CorruptedDatabase("Column 'id' of table 'indexes' must be of type INT".to_owned())),
+            })
+            .collect()
+    }
+}
+
+/// Formats the usage like psql's \dt+ (one line per table, sizes in kB)
+pub fn format_disk_usage(usage: &[TableDiskUsage]) -> String {
+    let name_width = usage.iter()
+        .map(|u| u.name.len())
+        .chain(std::iter::once("name".len()))
+        .max()
+        .unwrap_or(0);
+
+    let mut out = format!("{:<name_width$} | {:>10} | {:>10} | {:>11} | {:>10} | {:>9} | {:>10}\n",
+        "name", "heap pages", "free pages", "index pages", "live rows", "dead rows", "size");
+    out.push_str(&format!("{}\n", "-".repeat(name_width + 85)));
+    for u in usage {
+        out.push_str(&format!("{:<name_width$} | {:>10} | {:>10} | {:>11} | {:>10} | {:>9} | {:>7} kB\n",
+            u.name, u.heap_pages, u.free_pages, u.index_pages, u.live_rows, u.dead_rows, u.bytes_on_disk.div_ceil(1024)));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::{Database, disk_usage::format_disk_usage}, store::file_store::FileStore, table::{ColumnType, table::{Cell, Row}}};
+
+    #[test]
+    fn should_report_pages_rows_and_bytes_of_tables() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+
+        let table = db.create_table("persons", vec![
+            ("id", ColumnType::Int, false, true),
+            ("name", ColumnType::Varchar(100), false, false),
+        ]).unwrap();
+        let access = db.table_access(table.clone()).unwrap();
+        for i in 1..=3 {
+            access.insert(&Row::new(vec![Cell::Int(i), Cell::Varchar(format!("person {}", i))])).unwrap();
+        }
+        access.delete(access.find("id", Cell::Int(2)).unwrap()).unwrap();
+
+        let usage = db.table_disk_usage("persons").unwrap();
+        assert_eq!(usage.heap_pages, 1);
+        assert_eq!(usage.free_pages, 0);
+        assert_eq!((usage.live_rows, usage.dead_rows), (2, 1));
+        assert!(usage.index_pages >= 1);
+        let table_file = std::fs::metadata(base_path.path().join(table.file_path())).unwrap().len();
+        assert!(usage.bytes_on_disk > table_file);
+
+        let all = db.disk_usage().unwrap();
+        assert_eq!(all.len(), 5);
+        assert!(all.contains(&usage));
+
+        let formatted = format_disk_usage(&all);
+        assert_eq!(formatted.lines().count(), 7);
+        assert!(formatted.lines().any(|line| line.starts_with("persons ")));
+    }
+}
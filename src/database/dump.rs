@@ -0,0 +1,268 @@
+use std::{ffi::OsStr, fs, path::Path};
+
+use crate::{
+    clock::unix_seconds,
+    database::{Database, DatabaseError, export::{ExportError, Format, verify_export}, table_access::TableAccess},
+    store::Store,
+    table::{identifier::Identifier, table::Cell},
+};
+
+// A dump is a directory with one binary export per user table (catalog and system tables are not dumped)
+// and the manifest file DUMP_MANIFEST. Encrypted columns stay encrypted (see export.rs), a dump with encrypted
+// columns can only be restored into a database with the same key.
+// The manifest:
+//   # playdb dump
+//   version <DUMP_VERSION>
+//   created <unix time in seconds>
+//   table <name> <file> <rows>     (one line per table)
+// The file of a table is table_<id>.bin. Names can contain any character (quoted identifiers): in the manifest a
+// backslash, space, line feed and carriage return are written as \\, \s, \n and \r (since version 2).
+//
+// There is no MVCC and no WAL (so no LSN) yet. The dump is consistent because a Database can't be shared
+// between threads and dump() borrows it for the whole run: no write can happen between the exports of two tables.
+pub const DUMP_MANIFEST: &str = "dump.manifest";
+pub const DUMP_VERSION: u8 = 2;
+const MANIFEST_HEADER: &str = "# playdb dump";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DumpedTable {
+    pub name: String,
+    pub file: String,
+    pub rows: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DumpManifest {
+    pub version: u8,
+    pub created: u64,
+    pub tables: Vec<DumpedTable>,
+}
+
+impl<S: Store> Database<S> {
+    /// Exports all user tables into dir (created if missing) and writes the manifest
+    pub fn dump(&self, dir: &Path) -> Result<DumpManifest, ExportError> {
+        fs::create_dir_all(dir)?;
+        let created = unix_seconds(self.clock());
+
+        let mut throttle = self.throttle();
+        let mut tables = Vec::new();
+        for name in self.user_table_names()? {
+            let table = self.read_table(&Identifier::quote(&name))?;
+            let file = format!("table_{}.bin", table.id());
+            let layout = self.table_layout(&table)?;
+            let pages = self.store.read_metadata(&layout, &table).map_err(DatabaseError::from)?.number_of_pages();
+            let access = self.table_access(table)?;
+            access.export(&dir.join(&file), Format::Binary)?;
+            // whole tables are exported at once, so the throttle waits after each table
+            throttle.consume(pages, pages * layout.page_size() as u64);
+            let rows = verify_export(&dir.join(&file))?.rows;
+            tables.push(DumpedTable { name, file, rows });
+        }
+
+        let manifest = DumpManifest { version: DUMP_VERSION, created, tables };
+        fs::write(dir.join(DUMP_MANIFEST), manifest.serialize())?;
+        Ok(manifest)
+    }
+
+    /// Imports all tables of a dump. The tables must not exist yet.
+    pub fn restore_dump(&self, dir: &Path) -> Result<DumpManifest, ExportError> {
+        let manifest = read_dump_manifest(dir)?;
+        for table in manifest.tables.iter() {
+            let summary = verify_export(&dir.join(&table.file))?;
+            if summary.rows != table.rows {
+                return Err(ExportError::InvalidFormat(format!(
+                    "'{}' has {} rows, the manifest expects {}", table.file, summary.rows, table.rows)));
+            }
+            self.import_table(&dir.join(&table.file))?;
+        }
+        Ok(manifest)
+    }
+
+    fn user_table_names(&self) -> Result<Vec<String>, DatabaseError> {
+        let access = TableAccess::new(self.table_instance(), &self.store, &self.layout);
+        let mut names = Vec::new();
+        for (_, row) in access.find_all()?.rows()? {
+            match row.cells().as_slice() {
+                [Cell::Int(id), Cell::Varchar(name)] => if Self::is_user_table(*id, name) {
+                    names.push(name.clone());
+                },
+                _ => return Err(DatabaseError::CorruptedDatabase("Invalid row in 'tables' table".to_owned())),
+            }
+        }
+        Ok(names)
+    }
+}
+
+impl DumpManifest {
+    fn serialize(&self) -> String {
+        let mut out = format!("{}\nversion {}\ncreated {}\n", MANIFEST_HEADER, self.version, self.created);
+        for table in self.tables.iter() {
+            out.push_str(&format!("table {} {} {}\n", escape_name(&table.name), table.file, table.rows));
+        }
+        out
+    }
+}
+
+pub fn read_dump_manifest(dir: &Path) -> Result<DumpManifest, ExportError> {
+    let content = fs::read_to_string(dir.join(DUMP_MANIFEST))?;
+    let invalid = |line: &str| ExportError::InvalidFormat(format!("Invalid line in dump manifest: '{}'", line));
+
+    let mut lines = content.lines();
+    if lines.next() != Some(MANIFEST_HEADER) {
+        return Err(ExportError::InvalidFormat("Not a playdb dump".to_owned()));
+    }
+
+    let mut manifest = DumpManifest { version: 0, created: 0, tables: Vec::new() };
+    for line in lines {
+        let fields: Vec<&str> = line.split(' ').collect();
+        match fields.as_slice() {
+            ["version", version] => manifest.version = version.parse().map_err(|_| invalid(line))?,
+            ["created", created] => manifest.created = created.parse().map_err(|_| invalid(line))?,
+            ["table", name, file, rows] => manifest.tables.push(DumpedTable {
+                name: match manifest.version {
+                    1 => name.to_string(),
+                    _ => unescape_name(name).ok_or_else(|| invalid(line))?,
+                },
+                file: file.to_string(),
+                rows: rows.parse().map_err(|_| invalid(line))?,
+            }),
+            _ => return Err(invalid(line)),
+        }
+    }
+
+    if manifest.version != 1 && manifest.version != DUMP_VERSION {
+        return Err(ExportError::InvalidFormat(format!("Unsupported dump version {}", manifest.version)));
+    }
+    // the file names are written by dump, but the manifest can come from anywhere
+    if let Some(table) = manifest.tables.iter().find(|t| Path::new(&t.file).file_name() != Some(OsStr::new(&t.file))) {
+        return Err(ExportError::InvalidFormat(format!("Invalid file name '{}' in dump manifest", table.file)));
+    }
+    Ok(manifest)
+}
+
+fn escape_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for c in name.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            ' ' => out.push_str("\\s"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// None for an unknown escape
+fn unescape_name(escaped: &str) -> Option<String> {
+    let mut out = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        out.push(match c {
+            '\\' => match chars.next()? {
+                '\\' => '\\',
+                's' => ' ',
+                'n' => '\n',
+                'r' => '\r',
+                _ => return None,
+            },
+            c => c,
+        });
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::{Database, dump::{DUMP_MANIFEST, read_dump_manifest}, export::ExportError}, store::file_store::FileStore, table::{ColumnType, table::{Cell, Row}}};
+
+    #[test]
+    fn should_dump_and_restore_all_user_tables() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+
+        let persons = db.create_table("persons", vec![("id", ColumnType::Int, false, true), ("name", ColumnType::Varchar(20), false, false)]).unwrap();
+        let groups = db.create_table("groups", vec![("id", ColumnType::Int)]).unwrap();
+        let access = db.table_access(persons).unwrap();
+        access.insert(&Row::new(vec![Cell::Int(1), Cell::Varchar("Alice".to_owned())])).unwrap();
+        access.insert(&Row::new(vec![Cell::Int(2), Cell::Varchar("Bob".to_owned())])).unwrap();
+        db.table_access(groups).unwrap().insert(&Row::new(vec![Cell::Int(10)])).unwrap();
+        db.analyze("persons").unwrap();
+
+        let dump_dir = tempfile::tempdir().unwrap();
+        let manifest = db.dump(dump_dir.path()).unwrap();
+        // _statistics is a system table
+        assert_eq!(manifest.tables.iter().map(|t| (t.name.as_str(), t.rows)).collect::<Vec<_>>(), vec![("persons", 2), ("groups", 1)]);
+        assert_eq!(read_dump_manifest(dump_dir.path()).unwrap(), manifest);
+
+        let target_path = tempfile::tempdir().unwrap();
+        let target = Database::new_with_store("target_db", FileStore::new(target_path.path()));
+        target.drop_create().unwrap();
+        target.restore_dump(dump_dir.path()).unwrap();
+        let rows = target.table_access(target.read_table("persons").unwrap()).unwrap().find("id", Cell::Int(2)).unwrap().rows().unwrap();
+        assert_eq!(rows[0].1, Row::new(vec![Cell::Int(2), Cell::Varchar("Bob".to_owned())]));
+        assert!(target.read_table("groups").is_ok());
+
+        let groups_file = &manifest.tables[1].file;
+        std::fs::write(dump_dir.path().join(DUMP_MANIFEST), format!("# playdb dump\nversion 2\ncreated 0\ntable groups {} 5\n", groups_file)).unwrap();
+        let other_path = tempfile::tempdir().unwrap();
+        let other = Database::new_with_store("other_db", FileStore::new(other_path.path()));
+        other.drop_create().unwrap();
+        assert!(other.restore_dump(dump_dir.path()).is_err());
+        std::fs::write(dump_dir.path().join(DUMP_MANIFEST), "# playdb dump\nversion 2\ncreated 0\ntable groups ../groups.bin 1\n").unwrap();
+        assert!(matches!(read_dump_manifest(dump_dir.path()), Err(ExportError::InvalidFormat(_))));
+    }
+
+    #[test]
+    fn should_dump_tables_with_any_characters_in_the_name() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        let table = db.create_table("\"My ../table\\ x\"", vec![("\"first name\"", ColumnType::Varchar(20))]).unwrap();
+        assert_eq!(table.name(), "My ../table\\ x");
+        db.table_access(table).unwrap().insert(&Row::new(vec![Cell::Varchar("Alice".to_owned())])).unwrap();
+
+        let parent = tempfile::tempdir().unwrap();
+        let dump_dir = parent.path().join("dump");
+        let manifest = db.dump(&dump_dir).unwrap();
+        assert_eq!(manifest.tables[0].name, "My ../table\\ x");
+        assert_eq!(std::fs::read_dir(parent.path()).unwrap().count(), 1, "nothing is written outside of the dump");
+        assert_eq!(read_dump_manifest(&dump_dir).unwrap(), manifest);
+
+        let target_path = tempfile::tempdir().unwrap();
+        let target = Database::new_with_store("target_db", FileStore::new(target_path.path()));
+        target.drop_create().unwrap();
+        target.restore_dump(&dump_dir).unwrap();
+        let table = target.read_table("\"My ../table\\ x\"").unwrap();
+        assert_eq!(table.schema().columns[0].name, "first name");
+        assert_eq!(target.table_access(table).unwrap().find_all().unwrap().rows().unwrap().len(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "encryption")]
+    fn should_keep_encrypted_columns_encrypted_in_the_dump() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path())).with_encryption_key([42; 32]);
+        db.drop_create().unwrap();
+        let persons = db.create_table("persons", vec![("id", ColumnType::Int, false, true, false), ("ssn", ColumnType::Varchar(20), false, false, true)]).unwrap();
+        db.table_access(persons).unwrap().insert(&Row::new(vec![Cell::Int(1), Cell::Varchar("123-45-6789".to_owned())])).unwrap();
+
+        let dump_dir = tempfile::tempdir().unwrap();
+        db.dump(dump_dir.path()).unwrap();
+        for entry in std::fs::read_dir(dump_dir.path()).unwrap() {
+            let content = std::fs::read(entry.unwrap().path()).unwrap();
+            assert!(!content.windows(11).any(|w| w == b"123-45-6789"));
+        }
+
+        let target_path = tempfile::tempdir().unwrap();
+        let target = Database::new_with_store("target_db", FileStore::new(target_path.path())).with_encryption_key([42; 32]);
+        target.drop_create().unwrap();
+        target.restore_dump(dump_dir.path()).unwrap();
+        let persons = target.read_table("persons").unwrap();
+        assert!(persons.schema().columns[1].encrypted);
+        let rows = target.table_access(persons).unwrap().find("id", Cell::Int(1)).unwrap().rows().unwrap();
+        assert_eq!(rows[0].1.cells()[1], Cell::Varchar("123-45-6789".to_owned()));
+    }
+}
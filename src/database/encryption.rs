@@ -0,0 +1,102 @@
+use crate::{
+    database::{Database, DatabaseError},
+    store::Store,
+    table::{ColumnType, encryption::CHECK_VALUE_LEN, table::{Cell, Row, Table}},
+};
+
+// The key of the encrypted columns is not stored, but its check value is (see table/encryption.rs): one row per table
+// with encrypted columns in the system table _encryption (t_id, check_value as hex), written by create_table.
+// table_access compares it with the key of the Database, so a wrong key fails before a value is read or written.
+// Tampered values are rejected by the tag of each value.
+pub const ENCRYPTION_TABLE: &str = "_encryption";
+
+impl<S: Store> Database<S> {
+    // called by create_table for tables with encrypted columns
+    pub(crate) fn store_key_check(&self, table: &Table) -> Result<(), DatabaseError> {
+        let key = self.encryption_key.as_ref()
+            .ok_or_else(|| DatabaseError::MissingEncryptionKey(table.name().to_owned()))?;
+        let access = self.table_access(self.encryption_table()?)?;
+        access.delete(access.find("t_id", Cell::Int(table.id()))?)?;
+        access.insert(&Row::new(vec![Cell::Int(table.id()), Cell::Varchar(to_hex(&key.check_value()))]))?;
+        Ok(())
+    }
+
+    // called by table_access
+    pub(crate) fn check_encryption_key(&self, table: &Table) -> Result<(), DatabaseError> {
+        if !table.schema().columns.iter().any(|c| c.encrypted) {
+            return Ok(());
+        }
+        let key = self.encryption_key.as_ref()
+            .ok_or_else(|| DatabaseError::MissingEncryptionKey(table.name().to_owned()))?;
+        let stored = match self.read_table(ENCRYPTION_TABLE) {
+            Ok(encryption_table) => {
+                let access = self.table_access(encryption_table)?;
+                access.find("t_id", Cell::Int(table.id()))?.rows()?.into_iter().next()
+            },
+            Err(DatabaseError::TableNotFound(_)) => None,
+            Err(e) => return Err(e),
+        };
+        match stored.as_ref().map(|(_, row)| row.cells().as_slice()) {
+            Some([_, Cell::Varchar(check_value)]) if *check_value == to_hex(&key.check_value()) => Ok(()),
+            Some([_, Cell::Varchar(_)]) => Err(DatabaseError::WrongEncryptionKey(table.name().to_owned())),
+            Some(_) => Err(DatabaseError::CorruptedDatabase(format!("Invalid row in '{}'", ENCRYPTION_TABLE))),
+            None => Err(DatabaseError::CorruptedDatabase(format!("Table '{}' has encrypted columns, but no key check value", table.name()))),
+        }
+    }
+
+    pub(crate) fn delete_key_check(&self, table: &Table) -> Result<(), DatabaseError> {
+        match self.read_table(ENCRYPTION_TABLE) {
+            Ok(encryption_table) => {
+                let access = self.table_access(encryption_table)?;
+                access.delete(access.find("t_id", Cell::Int(table.id()))?)?;
+                Ok(())
+            },
+            Err(DatabaseError::TableNotFound(_)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn encryption_table(&self) -> Result<Table, DatabaseError> {
+        match self.read_table(ENCRYPTION_TABLE) {
+            Err(DatabaseError::TableNotFound(_)) => Ok(self.create_system_table(ENCRYPTION_TABLE, vec![
+                ("t_id", ColumnType::Int, false, true),
+                ("check_value", ColumnType::Varchar(2 * CHECK_VALUE_LEN as u16), false, false),
+            ]).map_err(|e| DatabaseError::UnknownError(e.to_string()))?),
+            result => result,
+        }
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(all(test, feature = "encryption"))]
+mod tests {
+    use crate::{database::{Database, DatabaseError}, store::file_store::FileStore, table::{ColumnType, table::{Cell, Row}}};
+
+    #[test]
+    fn should_reject_a_wrong_or_missing_key() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path())).with_encryption_key([42; 32]);
+        db.drop_create().unwrap();
+        let table = db.create_table("persons", vec![("id", ColumnType::Int, false, true, false), ("ssn", ColumnType::Varchar(20), false, false, true)]).unwrap();
+        db.table_access(table).unwrap().insert(&Row::new(vec![Cell::Int(1), Cell::Varchar("123-45-6789".to_owned())])).unwrap();
+
+        let wrong_key = Database::new_with_store("test_db", FileStore::new(base_path.path())).with_encryption_key([43; 32]);
+        let table = wrong_key.read_table("persons").unwrap();
+        assert!(matches!(wrong_key.table_access(table), Err(DatabaseError::WrongEncryptionKey(_))));
+
+        let without_key = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        let table = without_key.read_table("persons").unwrap();
+        assert!(matches!(without_key.table_access(table), Err(DatabaseError::MissingEncryptionKey(_))));
+
+        let rows = db.table_access(db.read_table("persons").unwrap()).unwrap().find_all().unwrap().rows().unwrap();
+        assert_eq!(rows[0].1.cells()[1], Cell::Varchar("123-45-6789".to_owned()));
+
+        // the check value of a dropped table is removed with it
+        db.drop_table("persons").unwrap();
+        let access = db.table_access(db.read_table("_encryption").unwrap()).unwrap();
+        assert!(access.find_all().unwrap().rows().unwrap().is_empty());
+    }
+}
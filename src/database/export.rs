@@ -0,0 +1,1245 @@
+use std::{fs::File, io::{BufReader, BufWriter, Read, Write}, path::Path};
+
+use thiserror::Error;
+
+use crate::{
+    data::{checksum::{Crc32, crc32}, compression::{compress, decompress}},
+    database::{CreateColumnCommand, CreateTableError, Database, DatabaseError, NULL_INT, blob, masking::MaskingPolicy, table_access::{TableAccess, TableAccessError, pack_rows}},
+    store::Store,
+    table::{Column, ColumnType, TableSchema, identifier::Identifier, table::{Cell, Point, Row, Table, parse_hex, to_hex}},
+};
+
+// Self-describing export of a single table: the file contains the table name, the columns
+// (with type, unique and encrypted flag) and all rows. Sequences are not exported.
+//
+// The values of encrypted columns are exported encrypted (with a new nonce, like a BLOB: hex in Csv and JsonL), also
+// if they are masked. The import decrypts them with the key of the importing database, which must be the same key.
+//
+// Csv:    line 1: "# playdb table <name>"
+//         line 2: header, one field per column: "<name>:<type>[:unique][:encrypted]" (type: int, varchar(n), byte)
+//         rows:   ints and bytes unquoted, varchars always quoted ("" for a quote), NULL ints as empty field
+// JsonL:  line 1: {"table":"<name>","columns":[{"name":"id","type":"int","unique":true,"encrypted":false}, ...]}
+//         rows:   one JSON array per line, NULL ints as null
+// Binary: magic "PLAYTBL", format version, then (all numbers big endian):
+//         2 bytes name length + name, 2 bytes number of columns,
+//         per column: 2 bytes name length + name, 1 byte type (0 Int, 1 Varchar, 2 Byte), 2 bytes varchar length,
+//         1 byte flags (1 unique, 2 encrypted)
+//         version 1: per row: 4 bytes length + serialized row (same format as in the pages)
+//         version 2: blocks of up to BLOCK_ROWS rows (rows as in version 1, compressed, see data::compression):
+//                    'B', 4 bytes rows, 4 bytes uncompressed length, 4 bytes compressed length, data, 4 bytes CRC-32 of data
+//                    trailer: 'E', 8 bytes total rows, 4 bytes number of blocks, 4 bytes CRC-32 of the file before the trailer
+//         Version 1 files can still be imported, export always writes version 2.
+pub const BINARY_MAGIC: &[u8; 7] = b"PLAYTBL";
+pub const BINARY_VERSION: u8 = 2;
+const BLOCK_ROWS: u32 = 1024;
+const BLOCK_TAG: u8 = b'B';
+const TRAILER_TAG: u8 = b'E';
+const CSV_PREFIX: &str = "# playdb table ";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Csv,
+    JsonL,
+    Binary,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ImportOptions {
+    /// Checks all rows before anything is written and reports every violation (see import_table_with).
+    pub deferred_constraints: bool,
+}
+
+/// Row number starts with 1 (order in the file)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstraintViolation {
+    pub row: usize,
+    pub column: Option<String>,
+    pub message: String,
+}
+
+/// Result of dry_run_import
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportReport {
+    pub table: String,
+    // the import would fail with TableError
+    pub table_exists: bool,
+    pub columns: usize,
+    pub rows: usize,
+    pub violations: Vec<ConstraintViolation>,
+    // estimated pages (and bytes) of the valid rows
+    pub pages: usize,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error("ExportError - I/O error: {0}")]
+    IoError(String),
+    #[error("ExportError - invalid file: {0}")]
+    InvalidFormat(String),
+    #[error("ExportError - {0}")]
+    TableError(String),
+    #[error("ExportError - {} constraint violation(s): {}", .0.len(), format_violations(.0))]
+    ConstraintViolations(Vec<ConstraintViolation>),
+    #[error("ExportError - checksum mismatch: {0}")]
+    ChecksumMismatch(String),
+}
+
+/// Totals from the trailer of a binary export (see verify_export)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExportSummary {
+    pub rows: u64,
+    pub blocks: u32,
+}
+
+fn format_violations(violations: &[ConstraintViolation]) -> String {
+    violations.iter()
+        .map(|v| match &v.column {
+            Some(column) => format!("row {} ({}): {}", v.row, column, v.message),
+            None => format!("row {}: {}", v.row, v.message),
+        })
+        .collect::<Vec<String>>()
+        .join("; ")
+}
+
+impl From<std::io::Error> for ExportError {
+    fn from(err: std::io::Error) -> Self {
+        ExportError::IoError(err.to_string())
+    }
+}
+
+impl From<TableAccessError> for ExportError {
+    fn from(err: TableAccessError) -> Self {
+        ExportError::TableError(err.to_string())
+    }
+}
+
+impl From<DatabaseError> for ExportError {
+    fn from(err: DatabaseError) -> Self {
+        ExportError::TableError(err.to_string())
+    }
+}
+
+impl From<CreateTableError> for ExportError {
+    fn from(err: CreateTableError) -> Self {
+        ExportError::TableError(err.to_string())
+    }
+}
+
+const UNIQUE_FLAG: u8 = 1;
+const ENCRYPTED_FLAG: u8 = 2;
+
+struct ExportedColumn {
+    name: String,
+    col_type: ColumnType,
+    unique: bool,
+    encrypted: bool,
+}
+
+impl ExportedColumn {
+    // the type of the values in the file: encrypted values are bytes
+    fn file_type(&self) -> ColumnType {
+        match self.encrypted {
+            true => ColumnType::Blob,
+            false => self.col_type.clone(),
+        }
+    }
+}
+
+impl<'db, S: Store> TableAccess<'db, S> {
+    pub fn export(&'db self, path: &Path, format: Format) -> Result<(), ExportError> {
+        self.export_masked(path, format, &[])
+    }
+
+    /// Like export, but the values of the given columns are masked
+    pub fn export_masked(&'db self, path: &Path, format: Format, masks: &[(&str, MaskingPolicy)]) -> Result<(), ExportError> {
+        let schema = self.table().schema();
+        let mut column_masks: Vec<Option<&MaskingPolicy>> = vec![None; schema.columns.len()];
+        for (col_name, policy) in masks {
+            let idx = schema.find_index_by_name(col_name)
+                .ok_or_else(|| ExportError::TableError(format!("Cannot mask unknown column '{}'", col_name)))?;
+            column_masks[idx] = Some(policy);
+        }
+
+        let indexed = self.indexed_column_ids();
+        let columns: Vec<ExportedColumn> = self.table().schema().columns.iter()
+            .map(|c| ExportedColumn { name: c.name.clone(), col_type: c.col_type.clone(), unique: indexed.contains(&c.id), encrypted: c.encrypted })
+            .collect();
+
+        let mut out = ChecksumWriter::new(BufWriter::new(File::create(path)?));
+        let mut block = RowBlock::default();
+        let name = self.table().name();
+
+        match format {
+            Format::Csv => {
+                writeln!(out, "{}{}", CSV_PREFIX, name)?;
+                let header: Vec<String> = columns.iter()
+                    .map(|c| csv_quote(&format!("{}:{}{}{}", c.name, type_spec(&c.col_type),
+                        if c.unique { ":unique" } else { "" }, if c.encrypted { ":encrypted" } else { "" })))
+                    .collect();
+                writeln!(out, "{}", header.join(","))?;
+            },
+            Format::JsonL => {
+                let cols: Vec<String> = columns.iter()
+                    .map(|c| format!("{{\"name\":{},\"type\":{},\"unique\":{},\"encrypted\":{}}}",
+                        json_string(&c.name), json_string(&type_spec(&c.col_type)), c.unique, c.encrypted))
+                    .collect();
+                writeln!(out, "{{\"table\":{},\"columns\":[{}]}}", json_string(name), cols.join(","))?;
+            },
+            Format::Binary => {
+                out.write_all(BINARY_MAGIC)?;
+                out.write_all(&[BINARY_VERSION])?;
+                write_str(&mut out, name)?;
+                out.write_all(&(columns.len() as u16).to_be_bytes())?;
+                for c in columns.iter() {
+                    write_str(&mut out, &c.name)?;
+                    let (type_id, len) = match c.col_type {
+                        ColumnType::Int => (0u8, 0u16),
+                        ColumnType::Varchar(len) => (1, len),
+                        ColumnType::Byte => (2, 0),
+                        ColumnType::Blob => (3, 0),
+                        ColumnType::Point => (4, 0),
+                    };
+                    out.write_all(&[type_id])?;
+                    out.write_all(&len.to_be_bytes())?;
+                    let flags = if c.unique { UNIQUE_FLAG } else { 0 } | if c.encrypted { ENCRYPTED_FLAG } else { 0 };
+                    out.write_all(&[flags])?;
+                }
+            },
+        }
+
+        for res in self.find_all()? {
+            let (_, row) = res?;
+            let row = Row::new(row.cells().iter().zip(schema.columns.iter()).zip(column_masks.iter())
+                .map(|((cell, column), mask)| {
+                    let cell = match mask {
+                        Some(policy) => policy.apply(cell, column)?,
+                        None => cell.clone(),
+                    };
+                    match &column.key {
+                        Some(key) if column.encrypted => key.encrypt(&cell.serialize())
+                            .map(Cell::Blob)
+                            .map_err(|e| ExportError::TableError(format!("Encryption of column '{}' failed: {}", column.name, e))),
+                        _ if column.encrypted => Err(ExportError::TableError(format!("No encryption key for column '{}'", column.name))),
+                        _ => Ok(cell),
+                    }
+                })
+                .collect::<Result<Vec<Cell>, ExportError>>()?);
+            match format {
+                Format::Csv => {
+                    let fields: Vec<String> = row.cells().iter().map(|cell| match cell {
+                        Cell::Int(NULL_INT) => String::new(),
+                        Cell::Int(v) => v.to_string(),
+                        Cell::Byte(v) => v.to_string(),
+                        Cell::Varchar(v) => csv_quote(v),
+                        Cell::Blob(v) => to_hex(v),
+                        Cell::Point(p) => csv_quote(&p.to_string()),
+                    }).collect();
+                    writeln!(out, "{}", fields.join(","))?;
+                },
+                Format::JsonL => {
+                    let fields: Vec<String> = row.cells().iter().map(|cell| match cell {
+                        Cell::Int(NULL_INT) => "null".to_owned(),
+                        Cell::Int(v) => v.to_string(),
+                        Cell::Byte(v) => v.to_string(),
+                        Cell::Varchar(v) => json_string(v),
+                        Cell::Blob(v) => json_string(&to_hex(v)),
+                        Cell::Point(p) => json_string(&p.to_string()),
+                    }).collect();
+                    writeln!(out, "[{}]", fields.join(","))?;
+                },
+                Format::Binary => {
+                    block.push(&row);
+                    if block.rows == BLOCK_ROWS {
+                        block.write_to(&mut out)?;
+                    }
+                },
+            }
+        }
+
+        if format == Format::Binary {
+            block.write_to(&mut out)?;
+            let file_crc = out.crc.finish();
+            out.write_all(&[TRAILER_TAG])?;
+            out.write_all(&block.total_rows.to_be_bytes())?;
+            out.write_all(&block.blocks.to_be_bytes())?;
+            out.write_all(&file_crc.to_be_bytes())?;
+        }
+
+        out.flush()?;
+        Ok(())
+    }
+}
+
+/// Checks all block checksums and the file checksum of a binary export without importing it
+pub fn verify_export(path: &Path) -> Result<ExportSummary, ExportError> {
+    let mut content = Vec::new();
+    BufReader::new(File::open(path)?).read_to_end(&mut content)?;
+    if !content.starts_with(BINARY_MAGIC) {
+        return Err(ExportError::InvalidFormat("Not a binary export".to_owned()));
+    }
+
+    let mut reader = ByteReader { content: &content, pos: BINARY_MAGIC.len() };
+    if reader.take(1)?[0] != BINARY_VERSION {
+        return Err(ExportError::InvalidFormat("Only binary exports of version 2 have checksums".to_owned()));
+    }
+    reader.take_str()?;
+    let columns = read_columns(&mut reader)?;
+    let (_, summary) = read_blocks(&mut reader, &columns)?;
+    Ok(summary)
+}
+
+// Updates the CRC-32 of the file with everything that is written
+struct ChecksumWriter<W: Write> {
+    inner: W,
+    crc: Crc32,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, crc: Crc32::new() }
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.crc.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[derive(Default)]
+struct RowBlock {
+    data: Vec<u8>,
+    rows: u32,
+    total_rows: u64,
+    blocks: u32,
+}
+
+impl RowBlock {
+    fn push(&mut self, row: &Row) {
+        let data = row.serialize();
+        self.data.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        self.data.extend_from_slice(&data);
+        self.rows += 1;
+    }
+
+    // writes nothing if the block is empty
+    fn write_to<W: Write>(&mut self, out: &mut W) -> std::io::Result<()> {
+        if self.rows == 0 {
+            return Ok(());
+        }
+        let compressed = compress(&self.data);
+        out.write_all(&[BLOCK_TAG])?;
+        out.write_all(&self.rows.to_be_bytes())?;
+        out.write_all(&(self.data.len() as u32).to_be_bytes())?;
+        out.write_all(&(compressed.len() as u32).to_be_bytes())?;
+        out.write_all(&compressed)?;
+        out.write_all(&crc32(&compressed).to_be_bytes())?;
+
+        self.total_rows += self.rows as u64;
+        self.blocks += 1;
+        self.rows = 0;
+        self.data.clear();
+        Ok(())
+    }
+}
+
+impl<S: Store> Database<S> {
+    /// Creates the table of an exported file (the format is detected) and inserts all rows.
+    pub fn import_table(&self, path: &Path) -> Result<Table, ExportError> {
+        self.import_table_with(path, ImportOptions::default())
+    }
+
+    /// Without deferred constraints, rows are inserted one by one and the import stops at the first invalid row
+    /// (the table and the rows before stay).
+    ///
+    /// With deferred constraints, all rows are validated first: unique columns are checked by sorting their values
+    /// (like building the index from sorted input). If a row violates a constraint, nothing is created and
+    /// the error lists all violating rows. Otherwise the rows are packed into pages and loaded with load_pages.
+    pub fn import_table_with(&self, path: &Path, options: ImportOptions) -> Result<Table, ExportError> {
+        let (name, columns, rows) = self.read_decrypted(path)?;
+
+        // the names in the file are canonical, quoted they are created as they are
+        let commands: Vec<CreateColumnCommand> = columns.iter()
+            .map(|c| (Identifier::quote(&c.name).as_str(), c.col_type.clone(), false, c.unique, c.encrypted).into())
+            .collect();
+        let quoted_name = Identifier::quote(&name);
+
+        if !options.deferred_constraints {
+            let table = self.create_table(&quoted_name, commands)?;
+            let access = self.table_access(table.clone())?;
+            for row in rows {
+                access.insert(&row)?;
+            }
+            return Ok(table);
+        }
+
+        let violations = check_constraints(&columns, &import_schema(&columns)?, &rows);
+        if !violations.is_empty() {
+            return Err(ExportError::ConstraintViolations(violations));
+        }
+
+        let table = self.create_table(&quoted_name, commands)?;
+        let result = self.table_access(table.clone()).map_err(ExportError::from).and_then(|access| {
+            let rows = access.store_blobs(rows)?;
+            let pages = pack_rows(&rows, table.schema(), &self.layout)?;
+            let mut throttle = self.throttle();
+            let page_size = self.layout.page_size() as u64;
+            access.load_pages(pages.into_iter().map(|page| {
+                throttle.consume(1, page_size);
+                Ok(page)
+            }))?;
+            Ok(())
+        });
+        if let Err(err) = result {
+            let _ = self.drop_table(&quoted_name);
+            return Err(err);
+        }
+
+        Ok(table)
+    }
+
+    /// Validates an import without writing anything: the file is parsed (values are converted to the column types),
+    /// all rows are checked like with deferred constraints and the pages needed for the rows are estimated
+    /// (without the overflow pages of BLOB values).
+    pub fn dry_run_import(&self, path: &Path) -> Result<ImportReport, ExportError> {
+        let (name, columns, rows) = self.read_decrypted(path)?;
+
+        let table_exists = match self.read_table(&name) {
+            Ok(_) => true,
+            Err(DatabaseError::TableNotFound(_)) => false,
+            Err(err) => return Err(err.into()),
+        };
+        let schema = import_schema(&columns)?;
+        let violations = check_constraints(&columns, &schema, &rows);
+        // rows that can't be serialized are already reported as violations
+        let valid_rows: Vec<Row> = rows.iter()
+            .enumerate()
+            .filter(|(i, _)| !violations.iter().any(|v| v.row == i + 1 && v.column.is_none()))
+            .map(|(_, row)| blob::with_placeholder_pointers(row, &schema))
+            .collect();
+        let pages = pack_rows(&valid_rows, &schema, &self.layout)?.len();
+
+        Ok(ImportReport {
+            table: name,
+            table_exists,
+            columns: columns.len(),
+            rows: rows.len(),
+            violations,
+            pages,
+            bytes: (pages * self.layout.page_size()) as u64,
+        })
+    }
+
+    // read_import with the values of the encrypted columns decrypted by the key of this database
+    fn read_decrypted(&self, path: &Path) -> Result<Parsed, ExportError> {
+        let (name, columns, rows) = read_import(path)?;
+        if !columns.iter().any(|c| c.encrypted) {
+            return Ok((name, columns, rows));
+        }
+        let key = self.encryption_key.as_ref()
+            .ok_or_else(|| ExportError::TableError(format!("Table '{}' has encrypted columns, but the database has no encryption key", name)))?;
+
+        let rows = rows.into_iter().enumerate().map(|(i, row)| {
+            let cells = row.cells().iter().zip(columns.iter()).map(|(cell, c)| match cell {
+                Cell::Blob(encrypted) if c.encrypted => {
+                    let plain = key.decrypt(encrypted)
+                        .ok_or_else(|| ExportError::InvalidFormat(format!("Row {}: cannot decrypt column '{}' (wrong key or modified value)", i + 1, c.name)))?;
+                    let (cell, _) = Cell::deserialize(&plain, &Column::new(0, &c.name, c.col_type.clone()))
+                        .map_err(|e| ExportError::InvalidFormat(format!("Row {}: {}", i + 1, e.context())))?;
+                    Ok(cell)
+                },
+                cell => Ok(cell.clone()),
+            }).collect::<Result<Vec<Cell>, ExportError>>()?;
+            Ok(Row::new(cells))
+        }).collect::<Result<Vec<Row>, ExportError>>()?;
+        Ok((name, columns, rows))
+    }
+}
+
+fn read_import(path: &Path) -> Result<Parsed, ExportError> {
+    let mut content = Vec::new();
+    BufReader::new(File::open(path)?).read_to_end(&mut content)?;
+
+    let parsed = if content.starts_with(BINARY_MAGIC) {
+        parse_binary(&content)?
+    } else {
+        let text = String::from_utf8(content)
+            .map_err(|_| ExportError::InvalidFormat("File is neither binary nor UTF-8 text".to_owned()))?;
+        if text.starts_with(CSV_PREFIX) {
+            parse_csv(&text)?
+        } else if text.starts_with('{') {
+            parse_jsonl(&text)?
+        } else {
+            return Err(ExportError::InvalidFormat("Unknown export format".to_owned()));
+        }
+    };
+
+    if parsed.1.is_empty() {
+        return Err(ExportError::InvalidFormat("The file doesn't define any columns".to_owned()));
+    }
+    Ok(parsed)
+}
+
+fn import_schema(columns: &[ExportedColumn]) -> Result<TableSchema, ExportError> {
+    TableSchema::try_new(columns.iter()
+        .enumerate()
+        .map(|(i, c)| Column::new(i as i32 + 1, &c.name, c.col_type.clone()))
+        .collect())
+        .map_err(|e| ExportError::InvalidFormat(e.to_string()))
+}
+
+fn check_constraints(columns: &[ExportedColumn], schema: &TableSchema, rows: &[Row]) -> Vec<ConstraintViolation> {
+
+    let mut violations: Vec<ConstraintViolation> = rows.iter()
+        .enumerate()
+        .filter_map(|(i, row)| row.validate(schema).err().map(|e| ConstraintViolation {
+            row: i + 1,
+            column: None,
+            message: e.to_string(),
+        }))
+        .collect();
+
+    for (col_idx, column) in columns.iter().enumerate().filter(|(_, c)| c.unique) {
+        // sorted by value and row number: every row after the first of a value is a duplicate
+        // (indexes only exist for Int columns, other cells were already reported by validate)
+        let mut values: Vec<(i32, usize)> = rows.iter()
+            .enumerate()
+            .filter_map(|(i, row)| match row.cells().get(col_idx) {
+                Some(Cell::Int(value)) => Some((*value, i + 1)),
+                _ => None,
+            })
+            .collect();
+        values.sort();
+        for pair in values.windows(2) {
+            let ((prev, first_row), (cell, row)) = (pair[0], pair[1]);
+            if prev == cell {
+                violations.push(ConstraintViolation {
+                    row,
+                    column: Some(column.name.clone()),
+                    message: format!("duplicate value {} (first in row {})", cell, first_row),
+                });
+            }
+        }
+    }
+
+    violations.sort_by_key(|v| v.row);
+    violations
+}
+
+fn type_spec(col_type: &ColumnType) -> String {
+    match col_type {
+        ColumnType::Int => "int".to_owned(),
+        ColumnType::Varchar(len) => format!("varchar({})", len),
+        ColumnType::Byte => "byte".to_owned(),
+        ColumnType::Blob => "blob".to_owned(),
+        ColumnType::Point => "point".to_owned(),
+    }
+}
+
+fn parse_type_spec(spec: &str) -> Result<ColumnType, ExportError> {
+    match spec {
+        "int" => Ok(ColumnType::Int),
+        "byte" => Ok(ColumnType::Byte),
+        "blob" => Ok(ColumnType::Blob),
+        "point" => Ok(ColumnType::Point),
+        _ => spec.strip_prefix("varchar(")
+            .and_then(|rest| rest.strip_suffix(')'))
+            .and_then(|len| len.parse::<u16>().ok())
+            .map(ColumnType::Varchar)
+            .ok_or_else(|| ExportError::InvalidFormat(format!("Unknown column type '{}'", spec))),
+    }
+}
+
+fn parse_cell(value: Option<&str>, column: &ExportedColumn) -> Result<Cell, ExportError> {
+    parse_value(value, &column.name, &column.file_type())
+}
+
+// the text of a value as export writes it, None is NULL (also used by database/ingest.rs)
+pub(super) fn parse_value(value: Option<&str>, col_name: &str, col_type: &ColumnType) -> Result<Cell, ExportError> {
+    let invalid = || ExportError::InvalidFormat(format!("Invalid value {:?} for column '{}'", value, col_name));
+    match (col_type, value) {
+        (ColumnType::Int, None) => Ok(Cell::Int(NULL_INT)),
+        (ColumnType::Int, Some(v)) => v.parse::<i32>().map(Cell::Int).map_err(|_| invalid()),
+        (ColumnType::Byte, Some(v)) => v.parse::<u8>().map(Cell::Byte).map_err(|_| invalid()),
+        (ColumnType::Varchar(_), Some(v)) => Ok(Cell::Varchar(v.to_owned())),
+        (ColumnType::Blob, None) => Ok(Cell::Blob(Vec::new())),
+        (ColumnType::Blob, Some(v)) => parse_hex(v).map(Cell::Blob).ok_or_else(invalid),
+        (ColumnType::Point, Some(v)) => Point::parse(v).map(Cell::Point).ok_or_else(invalid),
+        _ => Err(invalid()),
+    }
+}
+
+type Parsed = (String, Vec<ExportedColumn>, Vec<Row>);
+
+struct ByteReader<'a> {
+    content: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], ExportError> {
+        let bytes = self.content.get(self.pos..self.pos + n)
+            .ok_or_else(|| ExportError::InvalidFormat("Unexpected end of file".to_owned()))?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N], ExportError> {
+        self.take(N)?.try_into()
+            .map_err(|_| ExportError::InvalidFormat("Unexpected end of file".to_owned()))
+    }
+
+    fn take_u16(&mut self) -> Result<u16, ExportError> {
+        Ok(u16::from_be_bytes(self.take_array()?))
+    }
+
+    fn take_u32(&mut self) -> Result<u32, ExportError> {
+        Ok(u32::from_be_bytes(self.take_array()?))
+    }
+
+    fn take_str(&mut self) -> Result<String, ExportError> {
+        let len = self.take_u16()? as usize;
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| ExportError::InvalidFormat("Invalid UTF-8 in name".to_owned()))
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.pos >= self.content.len()
+    }
+}
+
+fn parse_binary(content: &[u8]) -> Result<Parsed, ExportError> {
+    let mut reader = ByteReader { content, pos: BINARY_MAGIC.len() };
+
+    let version = reader.take(1)?[0];
+    if version != 1 && version != BINARY_VERSION {
+        return Err(ExportError::InvalidFormat("Unsupported binary format version".to_owned()));
+    }
+
+    let name = reader.take_str()?;
+    let columns = read_columns(&mut reader)?;
+
+    let rows = match version {
+        1 => {
+            let mut rows = Vec::new();
+            while !reader.is_at_end() {
+                let len = reader.take_u32()? as usize;
+                rows.push(read_row(reader.take(len)?, &columns)?);
+            }
+            rows
+        },
+        _ => read_blocks(&mut reader, &columns)?.0,
+    };
+
+    Ok((name, columns, rows))
+}
+
+fn read_columns(reader: &mut ByteReader) -> Result<Vec<ExportedColumn>, ExportError> {
+    let number_of_columns = reader.take_u16()?;
+    let mut columns = Vec::new();
+    for _ in 0..number_of_columns {
+        let col_name = reader.take_str()?;
+        let type_id = reader.take(1)?[0];
+        let len = reader.take_u16()?;
+        let flags = reader.take(1)?[0];
+        let col_type = match type_id {
+            0 => ColumnType::Int,
+            1 => ColumnType::Varchar(len),
+            2 => ColumnType::Byte,
+            3 => ColumnType::Blob,
+            4 => ColumnType::Point,
+            _ => return Err(ExportError::InvalidFormat(format!("Unknown column type {}", type_id))),
+        };
+        columns.push(ExportedColumn { name: col_name, col_type, unique: flags & UNIQUE_FLAG != 0, encrypted: flags & ENCRYPTED_FLAG != 0 });
+    }
+    Ok(columns)
+}
+
+// blocks and trailer of version 2, every checksum and total is verified
+fn read_blocks(reader: &mut ByteReader, columns: &[ExportedColumn]) -> Result<(Vec<Row>, ExportSummary), ExportError> {
+    let mut rows = Vec::new();
+    let mut blocks = 0u32;
+    loop {
+        let trailer_pos = reader.pos;
+        match reader.take(1)?[0] {
+            BLOCK_TAG => {
+                blocks += 1;
+                let block_rows = reader.take_u32()?;
+                let len = reader.take_u32()? as usize;
+                let compressed_len = reader.take_u32()? as usize;
+                let compressed = reader.take(compressed_len)?;
+                if reader.take_u32()? != crc32(compressed) {
+                    return Err(ExportError::ChecksumMismatch(format!("block {}", blocks)));
+                }
+                let data = decompress(compressed, len)
+                    .map_err(|e| ExportError::InvalidFormat(format!("block {}: {}", blocks, e)))?;
+
+                let mut block = ByteReader { content: &data, pos: 0 };
+                for _ in 0..block_rows {
+                    let row_len = block.take_u32()? as usize;
+                    rows.push(read_row(block.take(row_len)?, columns)?);
+                }
+                if !block.is_at_end() {
+                    return Err(ExportError::InvalidFormat(format!("block {} has more data than rows", blocks)));
+                }
+            },
+            TRAILER_TAG => {
+                let total_rows = u64::from_be_bytes(reader.take_array()?);
+                let total_blocks = reader.take_u32()?;
+                let file_crc = reader.take_u32()?;
+                if total_rows != rows.len() as u64 || total_blocks != blocks {
+                    return Err(ExportError::InvalidFormat(format!(
+                        "trailer expects {} rows in {} blocks, found {} rows in {} blocks", total_rows, total_blocks, rows.len(), blocks)));
+                }
+                if file_crc != crc32(&reader.content[..trailer_pos]) {
+                    return Err(ExportError::ChecksumMismatch("file".to_owned()));
+                }
+                if !reader.is_at_end() {
+                    return Err(ExportError::InvalidFormat("Data after the trailer".to_owned()));
+                }
+                return Ok((rows, ExportSummary { rows: total_rows, blocks }));
+            },
+            tag => return Err(ExportError::InvalidFormat(format!("Unknown block tag {}", tag))),
+        }
+    }
+}
+
+fn read_row(data: &[u8], columns: &[ExportedColumn]) -> Result<Row, ExportError> {
+    let mut offset = 0;
+    let mut cells = Vec::new();
+    for c in columns.iter() {
+        let column = Column::new(0, &c.name, c.file_type());
+        let (cell, read) = Cell::deserialize(data.get(offset..).unwrap_or_default(), &column)
+            .map_err(|err| ExportError::InvalidFormat(format!("Invalid row data: {}", err.at_offset(offset).context())))?;
+        offset += read;
+        cells.push(cell);
+    }
+    Ok(Row::new(cells))
+}
+
+fn parse_csv(text: &str) -> Result<Parsed, ExportError> {
+    let (first_line, rest) = text.split_once('\n')
+        .ok_or_else(|| ExportError::InvalidFormat("Missing CSV header".to_owned()))?;
+    let name = first_line.trim_end_matches('\r')[CSV_PREFIX.len()..].to_owned();
+
+    let mut records = csv_records(rest)?.into_iter();
+    let header = records.next()
+        .ok_or_else(|| ExportError::InvalidFormat("Missing CSV header".to_owned()))?;
+
+    let mut columns = Vec::new();
+    for field in header {
+        let field = field.ok_or_else(|| ExportError::InvalidFormat("Empty column definition".to_owned()))?;
+        let (definition, encrypted) = match field.strip_suffix(":encrypted") {
+            Some(definition) => (definition, true),
+            None => (field.as_str(), false),
+        };
+        let (definition, unique) = match definition.strip_suffix(":unique") {
+            Some(definition) => (definition, true),
+            None => (definition, false),
+        };
+        let (col_name, spec) = definition.rsplit_once(':')
+            .ok_or_else(|| ExportError::InvalidFormat(format!("Invalid column definition '{}'", field)))?;
+        columns.push(ExportedColumn { name: col_name.to_owned(), col_type: parse_type_spec(spec)?, unique, encrypted });
+    }
+
+    let rows = records.map(|record| {
+        if record.len() != columns.len() {
+            return Err(ExportError::InvalidFormat("Number of fields doesn't match the header".to_owned()));
+        }
+        let cells = record.iter().zip(columns.iter())
+            .map(|(value, column)| parse_cell(value.as_deref(), column))
+            .collect::<Result<Vec<Cell>, ExportError>>()?;
+        Ok(Row::new(cells))
+    }).collect::<Result<Vec<Row>, ExportError>>()?;
+
+    Ok((name, columns, rows))
+}
+
+// Empty unquoted fields are None (NULL), quoted fields are always Some
+pub(super) fn csv_records(text: &str) -> Result<Vec<Vec<Option<String>>>, ExportError> {
+    let mut records = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while chars.peek().is_some() {
+        let mut record = Vec::new();
+        loop {
+            let field = if chars.peek() == Some(&'"') {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            value.push('"');
+                        },
+                        Some('"') => break,
+                        Some(c) => value.push(c),
+                        None => return Err(ExportError::InvalidFormat("Unterminated quoted field".to_owned())),
+                    }
+                }
+                Some(value)
+            } else {
+                let mut value = String::new();
+                while let Some(c) = chars.peek() {
+                    if *c == ',' || *c == '\n' || *c == '\r' {
+                        break;
+                    }
+                    value.push(*c);
+                    chars.next();
+                }
+                if value.is_empty() { None } else { Some(value) }
+            };
+            record.push(field);
+
+            match chars.next() {
+                Some(',') => continue,
+                Some('\r') => {
+                    if chars.peek() == Some(&'\n') {
+                        chars.next();
+                    }
+                    break;
+                },
+                Some('\n') | None => break,
+                Some(c) => return Err(ExportError::InvalidFormat(format!("Unexpected character '{}' after quoted field", c))),
+            }
+        }
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+fn parse_jsonl(text: &str) -> Result<Parsed, ExportError> {
+    let mut lines = text.lines().filter(|l| !l.trim().is_empty());
+    let header = Json::parse(lines.next().unwrap_or_default())?;
+
+    let name = header.get("table").and_then(Json::as_str)
+        .ok_or_else(|| ExportError::InvalidFormat("Missing table name".to_owned()))?
+        .to_owned();
+    let columns = match header.get("columns") {
+        Some(Json::Array(columns)) => columns.iter().map(|c| {
+            let col_name = c.get("name").and_then(Json::as_str)
+                .ok_or_else(|| ExportError::InvalidFormat("Missing column name".to_owned()))?;
+            let spec = c.get("type").and_then(Json::as_str)
+                .ok_or_else(|| ExportError::InvalidFormat("Missing column type".to_owned()))?;
+            let unique = matches!(c.get("unique"), Some(Json::Bool(true)));
+            let encrypted = matches!(c.get("encrypted"), Some(Json::Bool(true)));
+            Ok(ExportedColumn { name: col_name.to_owned(), col_type: parse_type_spec(spec)?, unique, encrypted })
+        }).collect::<Result<Vec<ExportedColumn>, ExportError>>()?,
+        _ => return Err(ExportError::InvalidFormat("Missing columns".to_owned())),
+    };
+
+    let rows = lines.map(|line| {
+        let values = match Json::parse(line)? {
+            Json::Array(values) if values.len() == columns.len() => values,
+            _ => return Err(ExportError::InvalidFormat("Row must be an array with one value per column".to_owned())),
+        };
+        let cells = values.iter().zip(columns.iter())
+            .map(|(value, column)| match value {
+                Json::Null => parse_cell(None, column),
+                Json::Number(n) => parse_cell(Some(&n.to_string()), column),
+                Json::String(s) => parse_cell(Some(s), column),
+                _ => Err(ExportError::InvalidFormat(format!("Invalid value for column '{}'", column.name))),
+            })
+            .collect::<Result<Vec<Cell>, ExportError>>()?;
+        Ok(Row::new(cells))
+    }).collect::<Result<Vec<Row>, ExportError>>()?;
+
+    Ok((name, columns, rows))
+}
+
+// Just enough JSON to read back what export writes
+pub(super) enum Json {
+    Null,
+    Bool(bool),
+    Number(i64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub(super) fn parse(text: &str) -> Result<Json, ExportError> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut pos = 0;
+        let value = Self::parse_value(&chars, &mut pos)?;
+        Self::skip_whitespace(&chars, &mut pos);
+        if pos != chars.len() {
+            return Err(ExportError::InvalidFormat("Unexpected characters after JSON value".to_owned()));
+        }
+        Ok(value)
+    }
+
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn skip_whitespace(chars: &[char], pos: &mut usize) {
+        while *pos < chars.len() && chars[*pos].is_whitespace() {
+            *pos += 1;
+        }
+    }
+
+    fn parse_value(chars: &[char], pos: &mut usize) -> Result<Json, ExportError> {
+        let invalid = |msg: &str| ExportError::InvalidFormat(format!("Invalid JSON: {}", msg));
+        Self::skip_whitespace(chars, pos);
+
+        let rest: String = chars[*pos..].iter().take(5).collect();
+        match chars.get(*pos) {
+            Some('n') if rest.starts_with("null") => { *pos += 4; Ok(Json::Null) },
+            Some('t') if rest.starts_with("true") => { *pos += 4; Ok(Json::Bool(true)) },
+            Some('f') if rest.starts_with("false") => { *pos += 5; Ok(Json::Bool(false)) },
+            Some('"') => Self::parse_string(chars, pos).map(Json::String),
+            Some('[') => {
+                *pos += 1;
+                let mut values = Vec::new();
+                Self::skip_whitespace(chars, pos);
+                if chars.get(*pos) == Some(&']') {
+                    *pos += 1;
+                    return Ok(Json::Array(values));
+                }
+                loop {
+                    values.push(Self::parse_value(chars, pos)?);
+                    Self::skip_whitespace(chars, pos);
+                    match chars.get(*pos) {
+                        Some(',') => *pos += 1,
+                        Some(']') => { *pos += 1; return Ok(Json::Array(values)); },
+                        _ => return Err(invalid("expected ',' or ']'")),
+                    }
+                }
+            },
+            Some('{') => {
+                *pos += 1;
+                let mut fields = Vec::new();
+                Self::skip_whitespace(chars, pos);
+                if chars.get(*pos) == Some(&'}') {
+                    *pos += 1;
+                    return Ok(Json::Object(fields));
+                }
+                loop {
+                    Self::skip_whitespace(chars, pos);
+                    let key = Self::parse_string(chars, pos)?;
+                    Self::skip_whitespace(chars, pos);
+                    if chars.get(*pos) != Some(&':') {
+                        return Err(invalid("expected ':'"));
+                    }
+                    *pos += 1;
+                    fields.push((key, Self::parse_value(chars, pos)?));
+                    Self::skip_whitespace(chars, pos);
+                    match chars.get(*pos) {
+                        Some(',') => *pos += 1,
+                        Some('}') => { *pos += 1; return Ok(Json::Object(fields)); },
+                        _ => return Err(invalid("expected ',' or '}'")),
+                    }
+                }
+            },
+            Some(c) if *c == '-' || c.is_ascii_digit() => {
+                let start = *pos;
+                *pos += 1;
+                while *pos < chars.len() && chars[*pos].is_ascii_digit() {
+                    *pos += 1;
+                }
+                let number: String = chars[start..*pos].iter().collect();
+                number.parse::<i64>().map(Json::Number).map_err(|_| invalid("number"))
+            },
+            _ => Err(invalid("unexpected character")),
+        }
+    }
+
+    fn parse_string(chars: &[char], pos: &mut usize) -> Result<String, ExportError> {
+        let invalid = || ExportError::InvalidFormat("Invalid JSON string".to_owned());
+        if chars.get(*pos) != Some(&'"') {
+            return Err(invalid());
+        }
+        *pos += 1;
+
+        let mut value = String::new();
+        loop {
+            match chars.get(*pos) {
+                Some('"') => { *pos += 1; return Ok(value); },
+                Some('\\') => {
+                    let escaped = match chars.get(*pos + 1) {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            let hex: String = chars.get(*pos + 2..*pos + 6).ok_or_else(invalid)?.iter().collect();
+                            let code = u32::from_str_radix(&hex, 16).map_err(|_| invalid())?;
+                            *pos += 4;
+                            char::from_u32(code).ok_or_else(invalid)?
+                        },
+                        _ => return Err(invalid()),
+                    };
+                    value.push(escaped);
+                    *pos += 2;
+                },
+                Some(c) => { value.push(*c); *pos += 1; },
+                None => return Err(invalid()),
+            }
+        }
+    }
+}
+
+fn write_str<W: Write>(out: &mut W, value: &str) -> std::io::Result<()> {
+    out.write_all(&(value.len() as u16).to_be_bytes())?;
+    out.write_all(value.as_bytes())
+}
+
+fn csv_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::{Database, NULL_INT, export::{BINARY_MAGIC, ConstraintViolation, ExportError, ExportSummary, Format, ImportOptions, verify_export}, masking::MaskingPolicy}, store::file_store::FileStore, table::{ColumnType, table::{Cell, Row}}};
+
+    #[test]
+    fn should_export_and_import_table_in_all_formats() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+
+        let table = db.create_table("persons", vec![
+            ("id", ColumnType::Int, false, true),
+            ("name", ColumnType::Varchar(50), false, false),
+            ("age", ColumnType::Int, false, false),
+            ("flag", ColumnType::Byte, false, false),
+        ]).unwrap();
+        let access = db.table_access(table).unwrap();
+        access.insert(&Row::new(vec![Cell::Int(1), Cell::Varchar("Alice \"A\", \n\\ ü".to_owned()), Cell::Int(30), Cell::Byte(1)])).unwrap();
+        access.insert(&Row::new(vec![Cell::Int(2), Cell::Varchar("".to_owned()), Cell::Int(NULL_INT), Cell::Byte(0)])).unwrap();
+        let expected: Vec<Row> = access.find_all().unwrap().rows().unwrap().into_iter().map(|(_, r)| r).collect();
+
+        let export_dir = tempfile::tempdir().unwrap();
+        for format in [Format::Csv, Format::JsonL, Format::Binary] {
+            let path = export_dir.path().join(format!("{:?}", format));
+            access.export(&path, format).unwrap();
+
+            let target_path = tempfile::tempdir().unwrap();
+            let target = Database::new_with_store("target_db", FileStore::new(target_path.path()));
+            target.drop_create().unwrap();
+
+            let imported = target.import_table(&path).unwrap();
+            assert_eq!(imported.name(), "persons");
+            assert_eq!(imported.schema().columns.len(), 4);
+
+            let imported_access = target.table_access(imported).unwrap();
+            let rows: Vec<Row> = imported_access.find_all().unwrap().rows().unwrap().into_iter().map(|(_, r)| r).collect();
+            assert_eq!(rows, expected, "format {:?}", format);
+
+            // unique index has been recreated
+            assert!(imported_access.insert(&Row::new(vec![Cell::Int(1), Cell::Varchar("x".to_owned()), Cell::Int(1), Cell::Byte(1)])).is_err());
+        }
+    }
+
+    #[test]
+    fn should_mask_exported_columns() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+
+        let table = db.create_table("persons", vec![
+            ("id", ColumnType::Int, false, true),
+            ("name", ColumnType::Varchar(50), false, false),
+            ("email", ColumnType::Varchar(50), false, false),
+        ]).unwrap();
+        let access = db.table_access(table).unwrap();
+        access.insert(&Row::new(vec![Cell::Int(1), Cell::Varchar("Alice".to_owned()), Cell::Varchar("alice@example.com".to_owned())])).unwrap();
+
+        let path = base_path.path().join("masked.jsonl");
+        access.export_masked(&path, Format::JsonL, &[
+            ("id", MaskingPolicy::Hash),
+            ("name", MaskingPolicy::Redact),
+            ("email", MaskingPolicy::Partial { keep_start: 2, keep_end: 4 }),
+        ]).unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("Alice") && !content.contains("example"));
+        assert!(content.contains("\"*****\",\"al***********.com\""));
+
+        assert!(matches!(access.export_masked(&path, Format::Csv, &[("missing", MaskingPolicy::Redact)]), Err(ExportError::TableError(_))));
+    }
+
+    #[test]
+    #[cfg(feature = "encryption")]
+    fn should_export_encrypted_columns_as_ciphertext() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path())).with_encryption_key([42; 32]);
+        db.drop_create().unwrap();
+        let table = db.create_table("persons", vec![
+            ("id", ColumnType::Int, false, true, false),
+            ("ssn", ColumnType::Varchar(20), false, false, true),
+        ]).unwrap();
+        let access = db.table_access(table).unwrap();
+        access.insert(&Row::new(vec![Cell::Int(1), Cell::Varchar("123-45-6789".to_owned())])).unwrap();
+        let expected: Vec<Row> = access.find_all().unwrap().rows().unwrap().into_iter().map(|(_, r)| r).collect();
+
+        for format in [Format::Csv, Format::JsonL, Format::Binary] {
+            let path = base_path.path().join(format!("{:?}", format));
+            access.export(&path, format).unwrap();
+            let content = std::fs::read(&path).unwrap();
+            assert!(!content.windows(4).any(|w| w == b"6789"), "format {:?}", format);
+            // masked values are encrypted, too
+            access.export_masked(&path, format, &[("ssn", MaskingPolicy::Partial { keep_start: 0, keep_end: 4 })]).unwrap();
+            let content = std::fs::read(&path).unwrap();
+            assert!(!content.windows(4).any(|w| w == b"6789"), "masked, format {:?}", format);
+            access.export(&path, format).unwrap();
+
+            let target_path = tempfile::tempdir().unwrap();
+            let target = Database::new_with_store("target_db", FileStore::new(target_path.path())).with_encryption_key([42; 32]);
+            target.drop_create().unwrap();
+            let imported = target.import_table(&path).unwrap();
+            assert!(imported.schema().columns[1].encrypted);
+            let rows: Vec<Row> = target.table_access(imported).unwrap().find_all().unwrap().rows().unwrap().into_iter().map(|(_, r)| r).collect();
+            assert_eq!(rows, expected, "format {:?}", format);
+
+            let other_path = tempfile::tempdir().unwrap();
+            let wrong_key = Database::new_with_store("wrong_db", FileStore::new(other_path.path())).with_encryption_key([43; 32]);
+            wrong_key.drop_create().unwrap();
+            assert!(matches!(wrong_key.import_table(&path), Err(ExportError::InvalidFormat(_))));
+            let without_key = Database::new_with_store("plain_db", FileStore::new(other_path.path()));
+            assert!(matches!(without_key.import_table(&path), Err(ExportError::TableError(_))));
+        }
+    }
+
+    #[test]
+    fn should_reject_unknown_files() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+
+        let path = base_path.path().join("unknown.txt");
+        std::fs::write(&path, "id,name\n1,a\n").unwrap();
+        assert!(matches!(db.import_table(&path), Err(ExportError::InvalidFormat(_))));
+
+        std::fs::write(&path, "# playdb table t\n\"id:float\"\n").unwrap();
+        assert!(matches!(db.import_table(&path), Err(ExportError::InvalidFormat(_))));
+    }
+
+    #[test]
+    fn should_report_all_constraint_violations_with_deferred_constraints() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        let deferred = ImportOptions { deferred_constraints: true };
+
+        let path = base_path.path().join("persons.csv");
+        std::fs::write(&path, "# playdb table persons\n\"id:int:unique\",\"name:varchar(5)\"\n\
+            1,\"Alice\"\n2,\"Bob\"\n1,\"Carol\"\n3,\"Dave\"\n2,\"Eve\"\n4,\"Mallory\"\n").unwrap();
+        let Err(ExportError::ConstraintViolations(violations)) = db.import_table_with(&path, deferred) else {
+            panic!("Expected constraint violations");
+        };
+        assert_eq!(violations.iter().map(|v| (v.row, v.column.clone())).collect::<Vec<_>>(), vec![
+            (3, Some("id".to_owned())),
+            (5, Some("id".to_owned())),
+            (6, None),
+        ]);
+        assert_eq!(violations[0], ConstraintViolation { row: 3, column: Some("id".to_owned()), message: "duplicate value 1 (first in row 1)".to_owned() });
+        // nothing has been created
+        assert!(db.read_table("persons").is_err());
+
+        std::fs::write(&path, "# playdb table persons\n\"id:int:unique\",\"name:varchar(5)\"\n1,\"Alice\"\n2,\"Bob\"\n").unwrap();
+        let table = db.import_table_with(&path, deferred).unwrap();
+        let access = db.table_access(table).unwrap();
+        assert_eq!(access.find("id", Cell::Int(2)).unwrap().rows().unwrap().len(), 1);
+        assert!(access.insert(&Row::new(vec![Cell::Int(1), Cell::Varchar("x".to_owned())])).is_err());
+    }
+
+    #[test]
+    fn should_validate_import_without_writing() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+
+        let path = base_path.path().join("persons.csv");
+        std::fs::write(&path, "# playdb table persons\n\"id:int:unique\",\"name:varchar(5)\"\n\
+            1,\"Alice\"\n1,\"Bob\"\n2,\"Mallory\"\n").unwrap();
+        let report = db.dry_run_import(&path).unwrap();
+        assert_eq!((report.table.as_str(), report.table_exists, report.columns, report.rows), ("persons", false, 2, 3));
+        assert_eq!(report.violations.iter().map(|v| v.row).collect::<Vec<usize>>(), vec![2, 3]);
+        assert_eq!(report.pages, 1);
+        assert_eq!(report.bytes, db.layout.page_size() as u64);
+        assert!(db.read_table("persons").is_err());
+
+        db.create_table("persons", vec![("id", ColumnType::Int)]).unwrap();
+        assert!(db.dry_run_import(&path).unwrap().table_exists);
+    }
+
+    #[test]
+    fn should_verify_checksums_of_binary_exports() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+
+        let table = db.create_table("numbers", vec![("id", ColumnType::Int, false, true), ("name", ColumnType::Varchar(20), false, false)]).unwrap();
+        let access = db.table_access(table).unwrap();
+        for i in 0..1500 {
+            access.insert(&Row::new(vec![Cell::Int(i), Cell::Varchar(format!("number {}", i % 10))])).unwrap();
+        }
+
+        let path = base_path.path().join("numbers.bin");
+        access.export(&path, Format::Binary).unwrap();
+        assert_eq!(verify_export(&path).unwrap(), ExportSummary { rows: 1500, blocks: 2 });
+
+        let content = std::fs::read(&path).unwrap();
+        // flip a byte in the first block
+        let mut corrupted = content.clone();
+        corrupted[content.len() / 4] ^= 0xFF;
+        std::fs::write(&path, &corrupted).unwrap();
+        assert!(matches!(verify_export(&path), Err(ExportError::ChecksumMismatch(_))));
+        assert!(matches!(db.import_table(&path), Err(ExportError::ChecksumMismatch(_))));
+
+        // trailer missing
+        std::fs::write(&path, &content[..content.len() - 17]).unwrap();
+        assert!(matches!(verify_export(&path), Err(ExportError::InvalidFormat(_))));
+
+        // version 1 without blocks can still be imported
+        let mut v1 = BINARY_MAGIC.to_vec();
+        v1.push(1);
+        v1.extend_from_slice(&[0, 2, b'v', b'1', 0, 1, 0, 2, b'i', b'd', 0, 0, 0, 0]);
+        let row = Row::new(vec![Cell::Int(7)]).serialize();
+        v1.extend_from_slice(&(row.len() as u32).to_be_bytes());
+        v1.extend_from_slice(&row);
+        std::fs::write(&path, &v1).unwrap();
+        let imported = db.import_table(&path).unwrap();
+        let rows = db.table_access(imported).unwrap().find_all().unwrap().rows().unwrap();
+        assert_eq!(rows[0].1, Row::new(vec![Cell::Int(7)]));
+    }
+}
@@ -0,0 +1,182 @@
+use std::{fmt::Debug, rc::Rc};
+
+use thiserror::Error;
+
+use crate::{
+    database::Database,
+    store::Store,
+    table::{ColumnType, table::Cell},
+};
+
+// User-defined scalar functions: Rust closures registered with Database::register_fn, called by name from SQL
+// (SELECT slugify(name) FROM t, WHERE slugify(name) = 'x', see sql/function.rs) or with Database::call_fn.
+// The signature is checked before a query reads its first row: the number and the types of the arguments
+// and the type of the result (VARCHAR matches VARCHAR of any length). The closure gets the cells of the arguments
+// as they are, also NULL (NULL_INT), and its result must have the type of the signature.
+// Names are case insensitive, registering a name again replaces the function.
+
+pub type ScalarFn = dyn Fn(&[Cell]) -> Result<Cell, String>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    pub args: Vec<ColumnType>,
+    pub returns: ColumnType,
+}
+
+impl Signature {
+    pub fn new(args: Vec<ColumnType>, returns: ColumnType) -> Self {
+        Self { args, returns }
+    }
+}
+
+#[derive(Clone)]
+pub struct ScalarFunction {
+    name: String,
+    signature: Signature,
+    function: Rc<ScalarFn>,
+}
+
+impl Debug for ScalarFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScalarFunction")
+            .field("name", &self.name)
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum FunctionError {
+    #[error("Invalid function name: {0}")]
+    InvalidName(String),
+    #[error("Function does not exist: {0}")]
+    UnknownFunction(String),
+    #[error("Wrong arguments: {0}")]
+    WrongArguments(String),
+    #[error("Function {0} failed: {1}")]
+    Failed(String, String),
+}
+
+// names of the functions of the SQL layer, they can't be replaced
+const BUILTIN_FUNCTIONS: [&str; 1] = ["approx_count_distinct"];
+
+impl ScalarFunction {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    /// Checks the types of the arguments against the signature
+    pub fn check(&self, arg_types: &[ColumnType]) -> Result<(), FunctionError> {
+        if arg_types.len() != self.signature.args.len() {
+            return Err(FunctionError::WrongArguments(format!(
+                "{} expects {} arguments, got {}", self.name, self.signature.args.len(), arg_types.len()
+            )));
+        }
+        for (position, (expected, actual)) in self.signature.args.iter().zip(arg_types.iter()).enumerate() {
+            if !same_type(expected, actual) {
+                return Err(FunctionError::WrongArguments(format!(
+                    "argument {} of {} must be {}, not {}", position + 1, self.name, expected, actual
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Calls the function with arguments that were checked with check()
+    pub fn call(&self, args: &[Cell]) -> Result<Cell, FunctionError> {
+        let result = (self.function)(args).map_err(|msg| FunctionError::Failed(self.name.clone(), msg))?;
+        let matches = match (&self.signature.returns, &result) {
+            (ColumnType::Int, Cell::Int(_)) | (ColumnType::Byte, Cell::Byte(_)) | (ColumnType::Blob, Cell::Blob(_)) | (ColumnType::Point, Cell::Point(_)) => true,
+            (ColumnType::Varchar(len), Cell::Varchar(value)) => value.len() <= *len as usize,
+            _ => false,
+        };
+        match matches {
+            true => Ok(result),
+            false => Err(FunctionError::Failed(
+                self.name.clone(),
+                format!("result {:?} is not a valid {}", result, self.signature.returns),
+            )),
+        }
+    }
+}
+
+pub(crate) fn same_type(a: &ColumnType, b: &ColumnType) -> bool {
+    matches!((a, b), (ColumnType::Int, ColumnType::Int) | (ColumnType::Varchar(_), ColumnType::Varchar(_)) | (ColumnType::Byte, ColumnType::Byte) | (ColumnType::Blob, ColumnType::Blob) | (ColumnType::Point, ColumnType::Point))
+}
+
+impl<S: Store> Database<S> {
+    pub fn register_fn<F>(&self, name: &str, signature: Signature, function: F) -> Result<(), FunctionError>
+    where
+        F: Fn(&[Cell]) -> Result<Cell, String> + 'static,
+    {
+        let name = name.to_lowercase();
+        let valid = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid || BUILTIN_FUNCTIONS.contains(&name.as_str()) {
+            return Err(FunctionError::InvalidName(name));
+        }
+
+        self.functions.borrow_mut().insert(name.clone(), ScalarFunction { name, signature, function: Rc::new(function) });
+        Ok(())
+    }
+
+    pub fn function(&self, name: &str) -> Result<ScalarFunction, FunctionError> {
+        self.functions.borrow()
+            .get(&name.to_lowercase())
+            .cloned()
+            .ok_or_else(|| FunctionError::UnknownFunction(name.to_owned()))
+    }
+
+    /// Calls a registered function, the types of the arguments are checked like in a query
+    pub fn call_fn(&self, name: &str, args: &[Cell]) -> Result<Cell, FunctionError> {
+        let function = self.function(name)?;
+        let arg_types: Vec<ColumnType> = args.iter()
+            .map(|cell| match cell {
+                Cell::Int(_) => ColumnType::Int,
+                Cell::Varchar(value) => ColumnType::Varchar(value.len() as u16),
+                Cell::Byte(_) => ColumnType::Byte,
+                Cell::Blob(_) => ColumnType::Blob,
+                Cell::Point(_) => ColumnType::Point,
+            })
+            .collect();
+        function.check(&arg_types)?;
+        function.call(args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::{Database, functions::{FunctionError, Signature}}, store::file_store::FileStore, table::{ColumnType, table::Cell}};
+
+    #[test]
+    fn should_call_a_registered_function() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.register_fn("Double", Signature::new(vec![ColumnType::Int], ColumnType::Int), |args| match &args[0] {
+            Cell::Int(value) => Ok(Cell::Int(value * 2)),
+            other => Err(format!("not an int: {:?}", other)),
+        }).unwrap();
+
+        assert_eq!(db.call_fn("double", &[Cell::Int(21)]).unwrap(), Cell::Int(42));
+        assert!(matches!(db.call_fn("double", &[Cell::Varchar("21".to_owned())]), Err(FunctionError::WrongArguments(_))));
+        assert!(matches!(db.call_fn("double", &[]), Err(FunctionError::WrongArguments(_))));
+        assert!(matches!(db.call_fn("triple", &[Cell::Int(1)]), Err(FunctionError::UnknownFunction(_))));
+        assert!(matches!(
+            db.register_fn("approx_count_distinct", Signature::new(vec![], ColumnType::Int), |_| Ok(Cell::Int(0))),
+            Err(FunctionError::InvalidName(_))
+        ));
+    }
+
+    #[test]
+    fn should_reject_a_result_of_another_type() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.register_fn("broken", Signature::new(vec![], ColumnType::Varchar(3)), |_| Ok(Cell::Varchar("too long".to_owned()))).unwrap();
+
+        assert!(matches!(db.call_fn("broken", &[]), Err(FunctionError::Failed(_, _))));
+    }
+}
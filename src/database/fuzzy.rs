@@ -0,0 +1,205 @@
+use std::{collections::{BTreeSet, HashMap, HashSet}, rc::Rc};
+
+use crate::{
+    database::table_access::{TableAccess, TableAccessError},
+    store::Store,
+    table::table::Cell,
+};
+
+// Fuzzy string matching for typo-tolerant lookups in small tables (TableAccess::find_fuzzy): a Varchar value matches
+// if its Levenshtein distance to the pattern (insertions, deletions and substitutions of chars) is at most
+// max_distance. The table is always scanned, without an index the distance of every value is computed.
+//
+// A TrigramIndex saves most of the distance computations. It is built in memory from the distinct values of a column
+// (it is not persisted and not updated by writes). The trigrams of a value are its substrings of 3 chars, with two
+// padding chars on each side. One edit changes at most 3 trigrams, so a value within distance d of the pattern has
+// at least |trigrams(pattern)| - 3d of the trigrams of the pattern, the other values are skipped without computing
+// their distance. Values that were written after the index was built are not in it and are compared as without index.
+// For short patterns or large distances the bound is 0 and every value of the index is compared.
+
+type Trigram = [char; 3];
+// ids of the values of a TrigramIndex
+type ValueIds = Rc<HashMap<String, usize>>;
+
+const PADDING: char = '\0';
+
+/// Levenshtein distance in chars
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    distance(a, b, usize::MAX)
+}
+
+/// levenshtein(a, b) <= max_distance, stops as soon as the distance is larger
+pub fn within_distance(a: &str, b: &str, max_distance: usize) -> bool {
+    distance(a, b, max_distance) <= max_distance
+}
+
+/// Share of the trigrams the values have in common (Jaccard index), between 0 and 1 (1 for equal values)
+pub fn trigram_similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (trigrams(a), trigrams(b));
+    let common = a.intersection(&b).count();
+    common as f64 / (a.len() + b.len() - common) as f64
+}
+
+// a value larger than max_distance, if the distance is larger
+fn distance(a: &str, b: &str, max_distance: usize) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > max_distance {
+        return a.len().abs_diff(b.len());
+    }
+
+    // the previous and the current row of the matrix
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, a_char) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        // the distance is at least the minimum of the row
+        if current.iter().min().is_some_and(|min| *min > max_distance) {
+            return max_distance.saturating_add(1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+fn trigrams(value: &str) -> HashSet<Trigram> {
+    let chars: Vec<char> = [PADDING, PADDING].into_iter()
+        .chain(value.chars())
+        .chain([PADDING, PADDING])
+        .collect();
+    chars.windows(3).map(|w| [w[0], w[1], w[2]]).collect()
+}
+
+/// The predicate of TableAccess::find_fuzzy
+#[derive(Debug, Clone)]
+pub struct FuzzyMatch {
+    pattern: String,
+    max_distance: usize,
+    // from a TrigramIndex: the ids of its values and the ids of the values that match
+    indexed: Option<(ValueIds, HashSet<usize>)>,
+}
+
+impl FuzzyMatch {
+    pub fn new(pattern: &str, max_distance: usize) -> Self {
+        Self { pattern: pattern.to_owned(), max_distance, indexed: None }
+    }
+
+    pub fn matches(&self, value: &str) -> bool {
+        match &self.indexed {
+            Some((ids, matching)) if let Some(id) = ids.get(value) => matching.contains(id),
+            _ => within_distance(value, &self.pattern, self.max_distance),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TrigramIndex {
+    column: String,
+    ids: ValueIds,
+    // the values by id
+    values: Vec<String>,
+    // trigram => ids of the values that contain it
+    postings: HashMap<Trigram, Vec<usize>>,
+}
+
+impl TrigramIndex {
+    /// Indexes the distinct values of the Varchar column
+    pub fn build<'db, S: Store>(access: &'db TableAccess<'db, S>, col_name: &str) -> Result<Self, TableAccessError> {
+        let col_index = access.table().schema().find_index_by_name(col_name)
+            .ok_or_else(|| TableAccessError::LoadRowsError(format!("Column '{}' not found", col_name)))?;
+        let mut distinct = BTreeSet::new();
+        for (_, row) in access.find_all()?.rows()? {
+            match &row.cells()[col_index] {
+                Cell::Varchar(value) => distinct.insert(value.clone()),
+                _ => return Err(TableAccessError::LoadRowsError(format!("Column '{}' is not a VARCHAR column", col_name))),
+            };
+        }
+
+        let values: Vec<String> = distinct.into_iter().collect();
+        let mut postings: HashMap<Trigram, Vec<usize>> = HashMap::new();
+        for (id, value) in values.iter().enumerate() {
+            for trigram in trigrams(value) {
+                postings.entry(trigram).or_default().push(id);
+            }
+        }
+        let ids = values.iter().enumerate().map(|(id, value)| (value.clone(), id)).collect();
+        Ok(Self { column: col_name.to_owned(), ids: Rc::new(ids), values, postings })
+    }
+
+    pub fn column(&self) -> &str {
+        &self.column
+    }
+
+    /// Number of distinct values
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// The predicate for find_fuzzy, the values of the index that match are computed here
+    pub fn fuzzy_match(&self, pattern: &str, max_distance: usize) -> FuzzyMatch {
+        let pattern_trigrams = trigrams(pattern);
+        let required = pattern_trigrams.len().saturating_sub(3 * max_distance);
+        let candidates: Vec<usize> = match required {
+            0 => (0..self.values.len()).collect(),
+            required => {
+                let mut common: HashMap<usize, usize> = HashMap::new();
+                for id in pattern_trigrams.iter().filter_map(|t| self.postings.get(t)).flatten() {
+                    *common.entry(*id).or_default() += 1;
+                }
+                common.into_iter().filter(|(_, count)| *count >= required).map(|(id, _)| id).collect()
+            },
+        };
+        let matching = candidates.into_iter()
+            .filter(|id| within_distance(&self.values[*id], pattern, max_distance))
+            .collect();
+
+        FuzzyMatch { indexed: Some((self.ids.clone(), matching)), ..FuzzyMatch::new(pattern, max_distance) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::{Database, fuzzy::{FuzzyMatch, TrigramIndex, levenshtein, trigram_similarity, within_distance}}, store::file_store::FileStore, table::{ColumnType, table::{Cell, Row}}};
+
+    #[test]
+    fn should_find_the_values_within_the_edit_distance_with_and_without_trigram_index() {
+        assert_eq!((levenshtein("kitten", "sitting"), levenshtein("", "abc"), levenshtein("straße", "strasse")), (3, 3, 2));
+        assert!(within_distance("flaw", "lawn", 2) && !within_distance("flaw", "lawn", 1));
+        assert_eq!(trigram_similarity("berlin", "berlin"), 1.0);
+        assert!(trigram_similarity("berlin", "berlim") > trigram_similarity("berlin", "bern"));
+
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        let table = db.create_table("cities", vec![("id", ColumnType::Int, false, true), ("name", ColumnType::Varchar(50), false, false)]).unwrap();
+        let access = db.table_access(table).unwrap();
+        for (id, name) in ["Berlin", "Bern", "Bremen", "Dresden", "Merlin", "Berlin"].iter().enumerate() {
+            access.insert(&Row::new(vec![Cell::Int(id as i32), Cell::Varchar(name.to_string())])).unwrap();
+        }
+        let ids = |fuzzy: FuzzyMatch| -> Vec<Cell> {
+            access.find_fuzzy("name", fuzzy).unwrap().rows().unwrap().into_iter().map(|(_, row)| row.cells()[0].clone()).collect()
+        };
+
+        assert_eq!(ids(FuzzyMatch::new("Berlim", 1)), vec![Cell::Int(0), Cell::Int(5)]);
+        assert_eq!(ids(FuzzyMatch::new("Berlim", 2)), vec![Cell::Int(0), Cell::Int(4), Cell::Int(5)]);
+        assert!(access.find_fuzzy("id", FuzzyMatch::new("1", 0)).is_err());
+
+        let index = TrigramIndex::build(&access, "name").unwrap();
+        assert_eq!((index.column(), index.len()), ("name", 5));
+        for (pattern, max_distance) in [("Berlim", 1), ("Berlim", 2), ("Bremem", 1), ("Drezden", 3), ("X", 5)] {
+            assert_eq!(ids(index.fuzzy_match(pattern, max_distance)), ids(FuzzyMatch::new(pattern, max_distance)), "{} {}", pattern, max_distance);
+        }
+
+        // written after the index was built: compared without it
+        access.insert(&Row::new(vec![Cell::Int(6), Cell::Varchar("Berliner".to_owned())])).unwrap();
+        assert_eq!(ids(index.fuzzy_match("Berlinr", 1)), vec![Cell::Int(0), Cell::Int(5), Cell::Int(6)]);
+    }
+}
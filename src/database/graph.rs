@@ -0,0 +1,223 @@
+use std::collections::{HashMap, HashSet, VecDeque, hash_map::Entry};
+
+use thiserror::Error;
+
+use crate::{
+    database::{CreateTableError, Database, DatabaseError, table_access::TableAccessError},
+    store::Store,
+    table::{ColumnType, table::{Cell, Row, Table}},
+};
+
+// Directed graph on top of a table of edges (src, dst). The indexes are unique and have Int keys, so the table has
+// two composite keys instead of one index per column: src_dst = (src, dst) and dst_src = (dst, src), packed into one
+// Int with the first node in the upper 16 bits. All edges of a node are then one range of the index
+// (TableAccess::find_range), the outgoing edges in src_dst and the incoming ones in dst_src.
+// - node ids are 0..=MAX_NODE_ID, so that both keys fit into an Int
+// - an edge exists at most once (the unique index on src_dst rejects a second insert)
+// - bfs and shortest_path read the edges of every visited node with one range scan, they count hops (no weights)
+pub const MAX_NODE_ID: i32 = i16::MAX as i32;
+
+const SRC: usize = 0;
+const DST: usize = 1;
+
+#[derive(Debug, Error)]
+pub enum GraphError {
+    #[error("GraphError - node id {0} is not in 0..={MAX_NODE_ID}")]
+    InvalidNode(i32),
+    #[error("GraphError - table '{0}' is not an edge table (src, dst, src_dst, dst_src)")]
+    InvalidTable(String),
+    #[error("GraphError - {0}")]
+    DatabaseError(String),
+}
+
+impl From<DatabaseError> for GraphError {
+    fn from(err: DatabaseError) -> Self {
+        GraphError::DatabaseError(err.to_string())
+    }
+}
+
+impl From<CreateTableError> for GraphError {
+    fn from(err: CreateTableError) -> Self {
+        GraphError::DatabaseError(err.to_string())
+    }
+}
+
+impl From<TableAccessError> for GraphError {
+    fn from(err: TableAccessError) -> Self {
+        GraphError::DatabaseError(err.to_string())
+    }
+}
+
+pub struct Graph<'db, S: Store> {
+    db: &'db Database<S>,
+    edges: Table,
+}
+
+impl<'db, S: Store> Graph<'db, S> {
+    pub fn edges(&self) -> &Table {
+        &self.edges
+    }
+
+    pub fn add_edge(&self, src: i32, dst: i32) -> Result<(), GraphError> {
+        let row = Row::new(vec![Cell::Int(src), Cell::Int(dst), Cell::Int(key(src, dst)?), Cell::Int(key(dst, src)?)]);
+        self.db.table_access(self.edges.clone())?.insert(&row)?;
+        Ok(())
+    }
+
+    /// false, if there was no such edge
+    pub fn remove_edge(&self, src: i32, dst: i32) -> Result<bool, GraphError> {
+        let access = self.db.table_access(self.edges.clone())?;
+        let deleted = access.delete_returning(access.find("src_dst", Cell::Int(key(src, dst)?))?)?.rows()?;
+        Ok(!deleted.is_empty())
+    }
+
+    /// Targets of the edges from the node, ascending
+    pub fn neighbors(&self, node: i32) -> Result<Vec<i32>, GraphError> {
+        self.adjacent("src_dst", node, DST)
+    }
+
+    /// Sources of the edges to the node, ascending
+    pub fn incoming(&self, node: i32) -> Result<Vec<i32>, GraphError> {
+        self.adjacent("dst_src", node, SRC)
+    }
+
+    /// The nodes reachable from the node with at most `depth` edges and their distance, in the order of the
+    /// breadth-first search (the node itself first with distance 0)
+    pub fn bfs(&self, node: i32, depth: usize) -> Result<Vec<(i32, usize)>, GraphError> {
+        check_node(node)?;
+        let mut seen = HashSet::from([node]);
+        let mut visited = vec![(node, 0)];
+        let mut queue = VecDeque::from([(node, 0)]);
+        while let Some((current, distance)) = queue.pop_front() {
+            if distance == depth {
+                continue;
+            }
+            for next in self.neighbors(current)? {
+                if seen.insert(next) {
+                    visited.push((next, distance + 1));
+                    queue.push_back((next, distance + 1));
+                }
+            }
+        }
+        Ok(visited)
+    }
+
+    /// The nodes of a path with the fewest edges from `from` to `to` (both included), None if there is no path
+    pub fn shortest_path(&self, from: i32, to: i32) -> Result<Option<Vec<i32>>, GraphError> {
+        check_node(from)?;
+        check_node(to)?;
+        // the node each node was reached from
+        let mut parents = HashMap::from([(from, from)]);
+        let mut queue = VecDeque::from([from]);
+        while let Some(current) = queue.pop_front() {
+            if current == to {
+                let mut path = vec![to];
+                while let Some(last) = path.last().copied() && last != from {
+                    path.push(parents[&last]);
+                }
+                path.reverse();
+                return Ok(Some(path));
+            }
+            for next in self.neighbors(current)? {
+                if let Entry::Vacant(entry) = parents.entry(next) {
+                    entry.insert(current);
+                    queue.push_back(next);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    // the other node of the edges in the range of the node in the index
+    fn adjacent(&self, index_column: &str, node: i32, other: usize) -> Result<Vec<i32>, GraphError> {
+        let (first, last) = (key(node, 0)?, key(node, MAX_NODE_ID)?);
+        let access = self.db.table_access(self.edges.clone())?;
+        let nodes = access.find_range(index_column, first, last)?.rows()?.into_iter()
+            .filter_map(|(_, row)| match row.cells()[other] {
+                Cell::Int(node) => Some(node),
+                _ => None,
+            })
+            .collect();
+        Ok(nodes)
+    }
+}
+
+impl<S: Store> Database<S> {
+    /// Creates the edge table of a graph (see database/graph.rs)
+    pub fn create_graph(&self, name: &str) -> Result<Graph<'_, S>, GraphError> {
+        let edges = self.create_table(name, vec![
+            ("src", ColumnType::Int, false, false),
+            ("dst", ColumnType::Int, false, false),
+            ("src_dst", ColumnType::Int, false, true),
+            ("dst_src", ColumnType::Int, false, true),
+        ])?;
+        Ok(Graph { db: self, edges })
+    }
+
+    /// The graph of an edge table created with create_graph
+    pub fn graph(&self, name: &str) -> Result<Graph<'_, S>, GraphError> {
+        let edges = self.read_table(name)?;
+        let names: Vec<&str> = edges.schema().columns.iter()
+            .filter(|c| c.col_type == ColumnType::Int)
+            .map(|c| c.name.as_str())
+            .collect();
+        if names != ["src", "dst", "src_dst", "dst_src"] || edges.schema().columns.len() != names.len() {
+            return Err(GraphError::InvalidTable(name.to_owned()));
+        }
+        Ok(Graph { db: self, edges })
+    }
+}
+
+fn check_node(node: i32) -> Result<(), GraphError> {
+    match (0..=MAX_NODE_ID).contains(&node) {
+        true => Ok(()),
+        false => Err(GraphError::InvalidNode(node)),
+    }
+}
+
+// the first node in the upper 16 bits, the order of the keys is the order of (first, second)
+fn key(first: i32, second: i32) -> Result<i32, GraphError> {
+    check_node(first)?;
+    check_node(second)?;
+    Ok(first << 16 | second)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::{Database, graph::{GraphError, MAX_NODE_ID}}, store::file_store::FileStore, table::ColumnType};
+
+    #[test]
+    fn should_traverse_the_edges_by_index_range_scans() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        let graph = db.create_graph("roads").unwrap();
+        // 1 -> 2 -> 3 -> 4 -> 5, 1 -> 6 -> 5, 7 -> 1, 8 isolated
+        for (src, dst) in [(1, 2), (2, 3), (3, 4), (4, 5), (1, 6), (6, 5), (7, 1), (MAX_NODE_ID, 0)] {
+            graph.add_edge(src, dst).unwrap();
+        }
+        assert!(graph.add_edge(1, 2).is_err(), "an edge exists only once");
+
+        let graph = db.graph("roads").unwrap();
+        assert_eq!(graph.neighbors(1).unwrap(), vec![2, 6]);
+        assert_eq!(graph.neighbors(5).unwrap(), Vec::<i32>::new());
+        assert_eq!(graph.neighbors(MAX_NODE_ID).unwrap(), vec![0]);
+        assert_eq!(graph.incoming(5).unwrap(), vec![4, 6]);
+        assert_eq!(graph.incoming(1).unwrap(), vec![7]);
+
+        assert_eq!(graph.bfs(1, 2).unwrap(), vec![(1, 0), (2, 1), (6, 1), (3, 2), (5, 2)]);
+        assert_eq!(graph.bfs(8, 3).unwrap(), vec![(8, 0)]);
+        assert_eq!(graph.shortest_path(7, 5).unwrap(), Some(vec![7, 1, 6, 5]));
+        assert_eq!(graph.shortest_path(3, 3).unwrap(), Some(vec![3]));
+        assert_eq!(graph.shortest_path(5, 1).unwrap(), None);
+
+        assert!(graph.remove_edge(6, 5).unwrap());
+        assert!(!graph.remove_edge(6, 5).unwrap());
+        assert_eq!(graph.shortest_path(7, 5).unwrap(), Some(vec![7, 1, 2, 3, 4, 5]));
+
+        assert!(matches!(graph.add_edge(-1, 2), Err(GraphError::InvalidNode(-1))));
+        assert!(matches!(graph.neighbors(MAX_NODE_ID + 1), Err(GraphError::InvalidNode(_))));
+        db.create_table("persons", vec![("id", ColumnType::Int)]).unwrap();
+        assert!(matches!(db.graph("persons"), Err(GraphError::InvalidTable(_))));
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::{
+    database::{CreateTableError, Database, DatabaseError, export::{self, ExportError, Json}, table_access::TableAccessError},
+    store::Store,
+    table::{ColumnType, table::{Cell, Row, Table}},
+};
+
+// Ingestion of a stream of serialized records (feature ingest), e.g. the messages of a Kafka topic or the lines of a
+// log file. Every record has an offset (its position in the stream), the records are mapped to the columns of a table
+// and written in batches with the bulk loader (TableAccess::insert_all). After a batch, the offset of its last record
+// is recorded in the system table _ingest_offsets (name of the ingestor, offset), so an ingestor that is started again
+// skips the records it has already written.
+// - JSON: one object per record, a field is written to the column of the mapping or to the column of the same name,
+//   fields without column are ignored. CSV: one line per record, the fields are named by their position (RecordFormat::csv).
+// - missing columns get their default value (see TableAccess::row_from_map), null and empty CSV fields are NULL
+// - exactly once as long as nothing crashes: there are no transactions, a batch and its offset are two writes.
+//   If the process stops between them, the batch is written again on resume (a unique column rejects it instead).
+// - an invalid record stops the ingestion: its batch is not written, the batches before stay committed
+// - like insert_all: the hooks of the write pipeline don't run, free space of existing pages is not used
+
+pub const INGEST_OFFSETS_TABLE: &str = "_ingest_offsets";
+const DEFAULT_BATCH_ROWS: usize = 1000;
+
+#[derive(Debug, Error)]
+pub enum IngestError {
+    #[error("IngestError - record at offset {offset}: {message}")]
+    InvalidRecord { offset: u64, message: String },
+    #[error("IngestError - {0}")]
+    DatabaseError(String),
+}
+
+impl From<DatabaseError> for IngestError {
+    fn from(err: DatabaseError) -> Self {
+        IngestError::DatabaseError(err.to_string())
+    }
+}
+
+impl From<CreateTableError> for IngestError {
+    fn from(err: CreateTableError) -> Self {
+        IngestError::DatabaseError(err.to_string())
+    }
+}
+
+impl From<TableAccessError> for IngestError {
+    fn from(err: TableAccessError) -> Self {
+        IngestError::DatabaseError(err.to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordFormat {
+    JsonLines,
+    /// The names of the fields, in the order of the line
+    Csv(Vec<String>),
+}
+
+impl RecordFormat {
+    pub fn csv(fields: &[&str]) -> Self {
+        RecordFormat::Csv(fields.iter().map(|f| f.to_string()).collect())
+    }
+}
+
+/// Result of StreamIngestor::ingest
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct IngestSummary {
+    pub rows: usize,
+    pub batches: usize,
+    /// records with an offset that was already committed
+    pub skipped: usize,
+    pub committed_offset: Option<u64>,
+}
+
+pub struct StreamIngestor<'db, S: Store> {
+    db: &'db Database<S>,
+    name: String,
+    table: Table,
+    format: RecordFormat,
+    // field => column
+    mapping: HashMap<String, String>,
+    batch_rows: usize,
+}
+
+impl<'db, S: Store> StreamIngestor<'db, S> {
+    /// Writes the field of the records into the column
+    pub fn map_field(mut self, field: &str, column: &str) -> Self {
+        self.mapping.insert(field.to_owned(), column.to_owned());
+        self
+    }
+
+    /// Records per batch (at least 1)
+    pub fn with_batch_rows(mut self, rows: usize) -> Self {
+        self.batch_rows = rows.max(1);
+        self
+    }
+
+    /// Offset of the last record that was written, None if nothing was written yet
+    pub fn committed_offset(&self) -> Result<Option<u64>, IngestError> {
+        let access = self.db.table_access(self.db.ingest_offsets_table()?)?;
+        let rows = access.find("name", Cell::Varchar(self.name.clone()))?.rows()?;
+        match rows.first().map(|(_, row)| &row.cells()[1]) {
+            Some(Cell::Varchar(offset)) => offset.parse::<u64>().map(Some)
+                .map_err(|_| IngestError::DatabaseError(format!("Invalid offset '{}' of ingestor '{}'", offset, self.name))),
+            Some(_) => Err(IngestError::DatabaseError(format!("Column 'offset' of table '{}' must be of type VARCHAR", INGEST_OFFSETS_TABLE))),
+            None => Ok(None),
+        }
+    }
+
+    /// Writes the records (offset, record) after the committed offset, the offsets must increase
+    pub fn ingest<I, R>(&self, records: I) -> Result<IngestSummary, IngestError>
+    where
+        I: IntoIterator<Item = (u64, R)>,
+        R: AsRef<str>,
+    {
+        let access = self.db.table_access(self.table.clone())?;
+        let mut summary = IngestSummary { committed_offset: self.committed_offset()?, ..IngestSummary::default() };
+        let mut batch = Vec::with_capacity(self.batch_rows);
+        let mut last_offset = None;
+
+        for (offset, record) in records {
+            if summary.committed_offset.is_some_and(|committed| offset <= committed) {
+                summary.skipped += 1;
+                continue;
+            }
+            let values = self.parse(record.as_ref())
+                .map_err(|e| IngestError::InvalidRecord { offset, message: e.to_string() })?;
+            let row = access.row_from_map(&values)
+                .map_err(|e| IngestError::InvalidRecord { offset, message: e.to_string() })?;
+            batch.push(row);
+            last_offset = Some(offset);
+
+            if batch.len() >= self.batch_rows {
+                self.commit(std::mem::take(&mut batch), offset, &mut summary)?;
+            }
+        }
+        if let Some(offset) = last_offset.filter(|_| !batch.is_empty()) {
+            self.commit(batch, offset, &mut summary)?;
+        }
+        Ok(summary)
+    }
+
+    /// Same as ingest, the offset of a line is its position (starting with 0)
+    pub fn ingest_lines<I, R>(&self, lines: I) -> Result<IngestSummary, IngestError>
+    where
+        I: IntoIterator<Item = R>,
+        R: AsRef<str>,
+    {
+        self.ingest(lines.into_iter().enumerate().map(|(i, line)| (i as u64, line)))
+    }
+
+    fn commit(&self, batch: Vec<Row>, offset: u64, summary: &mut IngestSummary) -> Result<(), IngestError> {
+        let access = self.db.table_access(self.table.clone())?;
+        summary.rows += access.insert_all(batch)?;
+        summary.batches += 1;
+
+        let offsets = self.db.table_access(self.db.ingest_offsets_table()?)?;
+        let offset_cell = Cell::Varchar(offset.to_string());
+        match summary.committed_offset {
+            Some(_) => offsets.update(offsets.find("name", Cell::Varchar(self.name.clone()))?, vec![("offset", offset_cell)])?,
+            None => offsets.insert(&Row::new(vec![Cell::Varchar(self.name.clone()), offset_cell]))?,
+        }
+        summary.committed_offset = Some(offset);
+        Ok(())
+    }
+
+    // column => value of the record
+    fn parse(&self, record: &str) -> Result<HashMap<String, Cell>, ExportError> {
+        let fields: Vec<(String, Option<String>)> = match &self.format {
+            RecordFormat::JsonLines => match Json::parse(record)? {
+                Json::Object(fields) => fields.into_iter()
+                    .map(|(field, value)| match value {
+                        Json::Null => Ok((field, None)),
+                        Json::Number(n) => Ok((field, Some(n.to_string()))),
+                        Json::String(s) => Ok((field, Some(s))),
+                        _ => Err(ExportError::InvalidFormat(format!("Field '{}' must be a number, a string or null", field))),
+                    })
+                    .collect::<Result<_, ExportError>>()?,
+                _ => return Err(ExportError::InvalidFormat("Record must be a JSON object".to_owned())),
+            },
+            RecordFormat::Csv(names) => {
+                let mut records = export::csv_records(record.trim_end_matches(['\r', '\n']))?;
+                let values = match records.len() {
+                    1 => records.remove(0),
+                    _ => return Err(ExportError::InvalidFormat("Record must be one CSV line".to_owned())),
+                };
+                if values.len() != names.len() {
+                    return Err(ExportError::InvalidFormat(format!("Expected {} fields, found {}", names.len(), values.len())));
+                }
+                names.iter().cloned().zip(values).collect()
+            },
+        };
+
+        let mut values = HashMap::new();
+        for (field, value) in fields {
+            let col_name = self.mapping.get(&field).unwrap_or(&field);
+            if let Some(column) = self.table.schema().columns.iter().find(|c| &c.name == col_name) {
+                let cell = export::parse_value(value.as_deref(), &column.name, &column.col_type)?;
+                values.insert(column.name.clone(), cell);
+            }
+        }
+        Ok(values)
+    }
+}
+
+impl<S: Store> Database<S> {
+    /// Ingestor for the records of a stream into the table, see database/ingest.rs.
+    /// The name identifies its committed offset.
+    pub fn stream_ingestor(&self, name: &str, table_name: &str, format: RecordFormat) -> Result<StreamIngestor<'_, S>, IngestError> {
+        let table = self.read_table(table_name)?;
+        Ok(StreamIngestor {
+            db: self,
+            name: name.to_owned(),
+            table,
+            format,
+            mapping: HashMap::new(),
+            batch_rows: DEFAULT_BATCH_ROWS,
+        })
+    }
+
+    fn ingest_offsets_table(&self) -> Result<Table, IngestError> {
+        match self.read_table(INGEST_OFFSETS_TABLE) {
+            Ok(table) => Ok(table),
+            // u64 offsets don't fit into an INT column
+            Err(DatabaseError::TableNotFound(_)) => Ok(self.create_system_table(INGEST_OFFSETS_TABLE, vec![
+                ("name", ColumnType::Varchar(255), false, false),
+                ("offset", ColumnType::Varchar(20), false, false),
+            ])?),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::{Database, NULL_INT, ingest::{IngestError, IngestSummary, RecordFormat}}, store::file_store::FileStore, table::{ColumnType, table::Cell}};
+
+    #[test]
+    fn should_ingest_records_in_batches_and_resume_after_the_committed_offset() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        db.create_table("events", vec![
+            ("id", ColumnType::Int, false, true),
+            ("kind", ColumnType::Varchar(20), false, false),
+            ("amount", ColumnType::Int, false, false),
+        ]).unwrap();
+        let rows = || {
+            let access = db.table_access(db.read_table("events").unwrap()).unwrap();
+            let mut rows: Vec<Vec<Cell>> = access.find_all().unwrap().rows().unwrap().into_iter().map(|(_, row)| row.cells().clone()).collect();
+            rows.sort_by_key(|cells| cells[0].expect_int("").unwrap());
+            rows
+        };
+
+        let ingestor = db.stream_ingestor("events-topic", "events", RecordFormat::JsonLines).unwrap()
+            .map_field("event_id", "id")
+            .with_batch_rows(2);
+        assert_eq!(ingestor.committed_offset().unwrap(), None);
+        let messages = [
+            (10, r#"{"event_id": 1, "kind": "click", "amount": 5, "partition": 0}"#),
+            (11, r#"{"event_id": 2, "kind": "view"}"#),
+            (12, r#"{"event_id": 3, "kind": "click", "amount": null}"#),
+        ];
+        let summary = ingestor.ingest(messages).unwrap();
+        assert_eq!(summary, IngestSummary { rows: 3, batches: 2, skipped: 0, committed_offset: Some(12) });
+        assert_eq!(rows()[1], vec![Cell::Int(2), Cell::Varchar("view".to_owned()), Cell::Int(NULL_INT)]);
+
+        // the consumer starts again from an older offset: the committed records are skipped
+        let ingestor = db.stream_ingestor("events-topic", "events", RecordFormat::JsonLines).unwrap()
+            .map_field("event_id", "id");
+        let summary = ingestor.ingest(messages.into_iter().chain([(13, r#"{"event_id": 4, "kind": "buy", "amount": 7}"#)])).unwrap();
+        assert_eq!((summary.rows, summary.skipped, summary.committed_offset), (1, 3, Some(13)));
+        assert_eq!(rows().len(), 4);
+
+        // an invalid record: its batch is not written, the offset stays
+        let err = ingestor.ingest([(14, r#"{"event_id": 5}"#), (15, r#"{"event_id": "x"}"#)]).unwrap_err();
+        assert!(matches!(err, IngestError::InvalidRecord { offset: 15, .. }), "{}", err);
+        assert_eq!(ingestor.committed_offset().unwrap(), Some(13));
+        assert_eq!(rows().len(), 4);
+    }
+
+    #[test]
+    fn should_ingest_csv_lines() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        db.create_table("persons", vec![("id", ColumnType::Int), ("name", ColumnType::Varchar(20))]).unwrap();
+
+        let ingestor = db.stream_ingestor("import", "persons", RecordFormat::csv(&["name", "person_id"])).unwrap()
+            .map_field("person_id", "id");
+        let lines = ["\"Ada, Countess\",1", "Bob,2\n"];
+        assert_eq!(ingestor.ingest_lines(lines).unwrap().committed_offset, Some(1));
+        assert_eq!(ingestor.ingest_lines(lines.into_iter().chain(["Eve,3"])).unwrap().rows, 1);
+
+        let access = db.table_access(db.read_table("persons").unwrap()).unwrap();
+        let ada = access.find("id", Cell::Int(1)).unwrap().rows().unwrap();
+        assert_eq!(ada[0].1.cells()[1], Cell::Varchar("Ada, Countess".to_owned()));
+        assert_eq!(access.find_all().unwrap().rows().unwrap().len(), 3);
+        assert!(ingestor.ingest([(3, "Mallory")]).is_err());
+    }
+}
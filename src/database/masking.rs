@@ -0,0 +1,93 @@
+use crate::{database::{NULL_INT, export::ExportError}, table::{Column, ColumnType, table::{Cell, Point}}};
+
+// Masking policies are applied by the exporters (see TableAccess::export_masked), the stored data is never changed.
+//
+// Hash is deterministic: equal values get equal masks, so joins between masked tables still work
+// (and unique columns stay unique in most cases). Values with few possible inputs can be guessed by hashing
+// all candidates, so use Redact for them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MaskingPolicy {
+    /// Int and Byte: hash of the value. Varchar: hex hash, truncated to the column length. Blob: the 8 bytes of the hash.
+    /// Point: the two halves of the hash of its 16 bytes as coordinates.
+    Hash,
+    /// Int: NULL, Byte: 0, Varchar: '*' for every character, Blob: empty, Point: (0,0)
+    Redact,
+    /// Varchar only: keeps the first and last characters, the others are replaced by '*'
+    Partial { keep_start: usize, keep_end: usize },
+}
+
+impl MaskingPolicy {
+    pub fn apply(&self, cell: &Cell, column: &Column) -> Result<Cell, ExportError> {
+        let masked = match (self, cell) {
+            (_, Cell::Int(NULL_INT)) => Cell::Int(NULL_INT),
+            (MaskingPolicy::Hash, Cell::Int(v)) => match fnv1a(&v.to_be_bytes()) as i32 {
+                NULL_INT => Cell::Int(NULL_INT + 1),
+                h => Cell::Int(h),
+            },
+            (MaskingPolicy::Hash, Cell::Byte(v)) => Cell::Byte(fnv1a(&[*v]) as u8),
+            (MaskingPolicy::Hash, Cell::Varchar(v)) => {
+                let max_len = match column.col_type {
+                    ColumnType::Varchar(len) => len as usize,
+                    _ => 0,
+                };
+                let mut hash = format!("{:016x}", fnv1a(v.as_bytes()));
+                hash.truncate(max_len);
+                Cell::Varchar(hash)
+            },
+            (MaskingPolicy::Redact, Cell::Int(_)) => Cell::Int(NULL_INT),
+            (MaskingPolicy::Redact, Cell::Byte(_)) => Cell::Byte(0),
+            (MaskingPolicy::Redact, Cell::Varchar(v)) => Cell::Varchar("*".repeat(v.chars().count())),
+            (MaskingPolicy::Hash, Cell::Blob(v)) => Cell::Blob(fnv1a(v).to_be_bytes().to_vec()),
+            (MaskingPolicy::Redact, Cell::Blob(_)) => Cell::Blob(Vec::new()),
+            (MaskingPolicy::Hash, Cell::Point(_)) => {
+                let hash = fnv1a(&cell.serialize());
+                Cell::Point(Point((hash >> 32) as f64, (hash as u32) as f64))
+            },
+            (MaskingPolicy::Redact, Cell::Point(_)) => Cell::Point(Point(0.0, 0.0)),
+            (MaskingPolicy::Partial { keep_start, keep_end }, Cell::Varchar(v)) => {
+                let len = v.chars().count();
+                Cell::Varchar(v.chars().enumerate()
+                    .map(|(i, c)| if i < *keep_start || i + keep_end >= len { c } else { '*' })
+                    .collect())
+            },
+            (MaskingPolicy::Partial { .. }, _) => {
+                return Err(ExportError::TableError(format!("Partial masking is only supported for varchar columns, not for column '{}'", column.name)));
+            },
+        };
+        Ok(masked)
+    }
+}
+
+// stable across versions and platforms (exports must be reproducible)
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::{NULL_INT, masking::MaskingPolicy}, table::{Column, ColumnType, table::Cell}};
+
+    #[test]
+    fn should_mask_cells() {
+        let name = Column::new(1, "name", ColumnType::Varchar(8));
+        let id = Column::new(2, "id", ColumnType::Int);
+
+        let partial = MaskingPolicy::Partial { keep_start: 1, keep_end: 2 };
+        assert_eq!(partial.apply(&Cell::Varchar("Johnson".to_owned()), &name).unwrap(), Cell::Varchar("J****on".to_owned()));
+        assert_eq!(partial.apply(&Cell::Varchar("Al".to_owned()), &name).unwrap(), Cell::Varchar("Al".to_owned()));
+        assert!(partial.apply(&Cell::Int(1), &id).is_err());
+
+        assert_eq!(MaskingPolicy::Redact.apply(&Cell::Varchar("Jö".to_owned()), &name).unwrap(), Cell::Varchar("**".to_owned()));
+        assert_eq!(MaskingPolicy::Redact.apply(&Cell::Int(7), &id).unwrap(), Cell::Int(NULL_INT));
+
+        let hashed = MaskingPolicy::Hash.apply(&Cell::Varchar("Johnson".to_owned()), &name).unwrap();
+        assert_eq!(hashed, MaskingPolicy::Hash.apply(&Cell::Varchar("Johnson".to_owned()), &name).unwrap());
+        assert!(matches!(&hashed, Cell::Varchar(v) if v.len() == 8 && v != "Johnson"));
+        assert_ne!(MaskingPolicy::Hash.apply(&Cell::Int(1), &id).unwrap(), MaskingPolicy::Hash.apply(&Cell::Int(2), &id).unwrap());
+    }
+}
@@ -0,0 +1,260 @@
+use thiserror::Error;
+
+#[cfg(feature = "sql")]
+use crate::sql::{SqlError, executor, parser};
+
+use crate::{
+    database::{CreateTableError, Database, DatabaseError, table_access::TableAccessError},
+    store::Store,
+    table::{ColumnType, table::{Cell, Row}},
+};
+
+// Applied versions are recorded in the system table _migrations (version, name).
+//
+// There are no transactions yet, so a migration is not atomic: if a step fails, the changes it made
+// so far stay and its version is not recorded. Earlier migrations of the same run stay applied.
+pub const MIGRATIONS_TABLE: &str = "_migrations";
+
+#[derive(Debug, Error)]
+pub enum MigrationError {
+    #[error("MigrationError - version {0} is registered more than once")]
+    DuplicateVersion(i32),
+    #[error("MigrationError - migration {0} failed: {1}")]
+    Failed(i32, String),
+    #[error("MigrationError - {0}")]
+    DatabaseError(String),
+}
+
+impl From<DatabaseError> for MigrationError {
+    fn from(err: DatabaseError) -> Self {
+        MigrationError::DatabaseError(err.to_string())
+    }
+}
+
+impl From<CreateTableError> for MigrationError {
+    fn from(err: CreateTableError) -> Self {
+        MigrationError::DatabaseError(err.to_string())
+    }
+}
+
+impl From<TableAccessError> for MigrationError {
+    fn from(err: TableAccessError) -> Self {
+        MigrationError::DatabaseError(err.to_string())
+    }
+}
+
+#[cfg(feature = "sql")]
+impl From<SqlError> for MigrationError {
+    fn from(err: SqlError) -> Self {
+        MigrationError::DatabaseError(err.to_string())
+    }
+}
+
+pub type MigrationFn<S> = Box<dyn Fn(&Database<S>) -> Result<(), MigrationError>>;
+
+pub enum MigrationStep<S: Store> {
+    #[cfg(feature = "sql")]
+    Sql(String),
+    Func(MigrationFn<S>),
+}
+
+pub struct Migration<S: Store> {
+    version: i32,
+    name: String,
+    step: MigrationStep<S>,
+}
+
+/// Result of dry_run_migrate. error is set if the SQL of the step doesn't parse.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingMigration {
+    pub version: i32,
+    pub name: String,
+    pub error: Option<String>,
+}
+
+/// Versioned migration steps. They are applied in the order of their version, not in the order of registration.
+pub struct Migrations<S: Store> {
+    migrations: Vec<Migration<S>>,
+}
+
+impl<S: Store> Default for Migrations<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: Store> Migrations<S> {
+    pub fn new() -> Self {
+        Self { migrations: Vec::new() }
+    }
+
+    #[cfg(feature = "sql")]
+    pub fn sql(self, version: i32, name: &str, sql: &str) -> Self {
+        self.add(version, name, MigrationStep::Sql(sql.to_owned()))
+    }
+
+    pub fn step<F: Fn(&Database<S>) -> Result<(), MigrationError> + 'static>(self, version: i32, name: &str, f: F) -> Self {
+        self.add(version, name, MigrationStep::Func(Box::new(f)))
+    }
+
+    fn add(mut self, version: i32, name: &str, step: MigrationStep<S>) -> Self {
+        self.migrations.push(Migration { version, name: name.to_owned(), step });
+        self
+    }
+}
+
+impl<S: Store> Database<S> {
+    /// Applies all migrations that are not recorded in _migrations yet. Returns the applied versions.
+    pub fn migrate(&self, migrations: &Migrations<S>) -> Result<Vec<i32>, MigrationError> {
+        let mut pending: Vec<&Migration<S>> = migrations.migrations.iter().collect();
+        pending.sort_by_key(|m| m.version);
+        if let Some(w) = pending.windows(2).find(|w| w[0].version == w[1].version) {
+            return Err(MigrationError::DuplicateVersion(w[0].version));
+        }
+
+        let applied = self.applied_migrations()?;
+        let mut newly_applied = Vec::new();
+        for migration in pending.into_iter().filter(|m| !applied.contains(&m.version)) {
+            match &migration.step {
+                #[cfg(feature = "sql")]
+                MigrationStep::Sql(sql) => executor::execute(self, sql).map(|_| ()).map_err(MigrationError::from),
+                MigrationStep::Func(f) => f(self),
+            }.map_err(|e| MigrationError::Failed(migration.version, e.to_string()))?;
+
+            let access = self.table_access(self.read_table(MIGRATIONS_TABLE)?)?;
+            access.insert(&Row::new(vec![Cell::Int(migration.version), Cell::Varchar(migration.name.clone())]))?;
+            newly_applied.push(migration.version);
+        }
+
+        Ok(newly_applied)
+    }
+
+    /// Pending migrations in the order migrate() would apply them, without applying or recording anything.
+    /// SQL steps are parsed; functions can't be checked without running them.
+    pub fn dry_run_migrate(&self, migrations: &Migrations<S>) -> Result<Vec<PendingMigration>, MigrationError> {
+        let mut pending: Vec<&Migration<S>> = migrations.migrations.iter().collect();
+        pending.sort_by_key(|m| m.version);
+        if let Some(w) = pending.windows(2).find(|w| w[0].version == w[1].version) {
+            return Err(MigrationError::DuplicateVersion(w[0].version));
+        }
+
+        // applied_migrations() would create _migrations
+        let applied = match self.read_table(MIGRATIONS_TABLE) {
+            Ok(_) => self.applied_migrations()?,
+            Err(DatabaseError::TableNotFound(_)) => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(pending.into_iter()
+            .filter(|m| !applied.contains(&m.version))
+            .map(|m| PendingMigration {
+                version: m.version,
+                name: m.name.clone(),
+                error: match &m.step {
+                    #[cfg(feature = "sql")]
+                    MigrationStep::Sql(sql) => parser::parse(sql).err().map(|e| e.to_string()),
+                    MigrationStep::Func(_) => None,
+                },
+            })
+            .collect())
+    }
+
+    /// Versions recorded in _migrations (creates the table if it doesn't exist)
+    pub fn applied_migrations(&self) -> Result<Vec<i32>, MigrationError> {
+        let table = match self.read_table(MIGRATIONS_TABLE) {
+            Ok(table) => table,
+            Err(DatabaseError::TableNotFound(_)) => self.create_system_table(MIGRATIONS_TABLE, vec![
+                ("version", ColumnType::Int, false, true),
+                ("name", ColumnType::Varchar(255), false, false),
+            ])?,
+            Err(err) => return Err(err.into()),
+        };
+
+        let access = self.table_access(table)?;
+        let mut versions = access.find_all()?.rows()?.into_iter()
+            .map(|(_, row)| row.cells()[0].expect_int("version must be of type Int")
+                .map_err(|e| MigrationError::DatabaseError(e.to_string())))
+            .collect::<Result<Vec<i32>, MigrationError>>()?;
+        versions.sort();
+        Ok(versions)
+    }
+}
+
+#[cfg(all(test, feature = "sql"))]
+mod tests {
+    use crate::{database::{Database, migrations::{MIGRATIONS_TABLE, MigrationError, Migrations, PendingMigration}}, store::file_store::FileStore, table::{ColumnType, table::{Cell, Row}}};
+
+    #[test]
+    fn should_apply_pending_migrations_once() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+
+        let migrations = Migrations::new()
+            .step(2, "add admin", |db: &Database<FileStore>| {
+                let access = db.table_access(db.read_table("users")?)?;
+                access.insert(&Row::new(vec![Cell::Int(1), Cell::Varchar("admin".to_owned())]))?;
+                Ok(())
+            })
+            .sql(1, "create users", "CREATE TABLE users (id INT UNIQUE, name VARCHAR(50))");
+
+        assert_eq!(db.migrate(&migrations).unwrap(), vec![1, 2]);
+        assert_eq!(db.migrate(&migrations).unwrap(), Vec::<i32>::new());
+        assert_eq!(db.applied_migrations().unwrap(), vec![1, 2]);
+
+        let migrations = migrations.step(3, "create groups", |db: &Database<FileStore>| {
+            db.create_table("groups", vec![("id", ColumnType::Int)])?;
+            Ok(())
+        });
+        assert_eq!(db.migrate(&migrations).unwrap(), vec![3]);
+
+        let users = db.table_access(db.read_table("users").unwrap()).unwrap();
+        assert_eq!(users.find_all().unwrap().rows().unwrap().len(), 1);
+        assert!(db.read_table("groups").is_ok());
+    }
+
+    #[test]
+    fn should_stop_at_failing_migration() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+
+        let migrations = Migrations::new()
+            .sql(1, "create users", "CREATE TABLE users (id INT)")
+            .sql(2, "broken", "INSERT INTO missing VALUES (1)")
+            .sql(3, "never", "CREATE TABLE never (id INT)");
+
+        assert!(matches!(db.migrate(&migrations), Err(MigrationError::Failed(2, _))));
+        assert_eq!(db.applied_migrations().unwrap(), vec![1]);
+        assert!(db.read_table("never").is_err());
+
+        let duplicate = Migrations::new()
+            .sql(1, "a", "CREATE TABLE a (id INT)")
+            .sql(1, "b", "CREATE TABLE b (id INT)");
+        assert!(matches!(db.migrate(&duplicate), Err(MigrationError::DuplicateVersion(1))));
+    }
+
+    #[test]
+    fn should_list_pending_migrations_without_applying_them() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+
+        let migrations = Migrations::new()
+            .sql(1, "create users", "CREATE TABLE users (id INT)")
+            .sql(2, "broken", "CREATE TABLE")
+            .step(3, "func", |_: &Database<FileStore>| Ok(()));
+
+        let pending = db.dry_run_migrate(&migrations).unwrap();
+        assert_eq!(pending.iter().map(|m| m.version).collect::<Vec<i32>>(), vec![1, 2, 3]);
+        assert_eq!(pending[0], PendingMigration { version: 1, name: "create users".to_owned(), error: None });
+        assert!(pending[1].error.is_some());
+        assert!(pending[2].error.is_none());
+        assert!(db.read_table(MIGRATIONS_TABLE).is_err());
+        assert!(db.read_table("users").is_err());
+
+        db.migrate(&Migrations::new().sql(1, "create users", "CREATE TABLE users (id INT)")).unwrap();
+        let pending = db.dry_run_migrate(&migrations).unwrap();
+        assert_eq!(pending.iter().map(|m| m.version).collect::<Vec<i32>>(), vec![2, 3]);
+    }
+}
@@ -1,15 +1,60 @@
+// The engine behind the public API: wrong input and corrupted data must return errors, not panic.
+#![cfg_attr(not(test), deny(clippy::unwrap_used, clippy::expect_used))]
 pub mod table_access;
+pub mod table_snapshot;
 pub mod seq_access;
-
-use std::{cell::RefCell, fs::create_dir, num::ParseIntError, path::Path};
+pub mod system_views;
+pub mod snapshot;
+pub mod export;
+pub mod copy;
+pub mod migrations;
+pub mod masking;
+pub mod disk_usage;
+pub mod statistics;
+pub mod cardinality;
+pub mod functions;
+pub mod vacuum;
+pub mod attach;
+pub mod table_functions;
+pub mod virtual_table;
+pub mod temp_table;
+pub mod dump;
+pub mod throttle;
+pub mod trace;
+pub mod sort;
+pub mod blob;
+pub mod bloom_filter;
+pub mod branch;
+pub mod upgrade;
+pub mod write_pipeline;
+pub mod time_series;
+pub mod graph;
+pub mod compaction;
+pub mod fuzzy;
+pub mod encryption;
+pub mod capabilities;
+#[cfg(feature = "async-sink")]
+pub mod row_sink;
+#[cfg(feature = "ingest")]
+pub mod ingest;
+
+use std::{cell::RefCell, collections::HashMap, fs::create_dir, num::ParseIntError, path::Path, rc::Rc};
 
 use thiserror::Error;
 
-use crate::{data::page::PageDataLayout, database::{seq_access::{SeqAccess, SeqAccessError}, table_access::{TableAccess, TableAccessError}}, store::{Store, StoreError, file_store::FileStore}, table::{Column, ColumnType, TableSchema, table::{Cell, Row, Table}}, tree::store::BTreeStore};
+use crate::{clock::{Clock, SystemClock}, data::page::{PageDataLayout, PageId}, database::{compaction::CompactionJob, functions::ScalarFunction, table_functions::{TableFunction, builtin_table_functions}, virtual_table::VirtualTable, write_pipeline::WritePipeline, seq_access::{SeqAccess, SeqAccessError}, statistics::{RowChangeCounter, StatisticsConfig}, throttle::ResourceConfig, trace::Tracer, table_access::{IntoRow, QueryResult, TableAccess, TableAccessError}}, store::{IoStats, Store, StoreError, timed_store::StoreMetrics, file_store::FileStore, kv_store::{KvStore, KvStoreError}}, table::{Column, ColumnType, TableSchema, encryption::ColumnKey, identifier::{Identifier, IdentifierError, RESERVED_PREFIX}, table::{Cell, Row, Table}}, tree::store::BTreeStore};
 
 // TODO: define constants for system catalog
 // Not a good solution for NULL, but very simple for now (see comment in btree module)
 pub const NULL_INT: i32 = i32::MIN;
+// set in the 'type' byte of the columns table for encrypted columns
+pub const ENCRYPTED_TYPE_FLAG: u8 = 0x80;
+// ids of the catalog tables, user and system tables get larger ids
+pub(crate) const TABLES_TABLE_ID: i32 = 1;
+pub(crate) const COLUMNS_TABLE_ID: i32 = 2;
+pub(crate) const SEQUENCES_TABLE_ID: i32 = 3;
+pub(crate) const INDEXES_TABLE_ID: i32 = 4;
+pub(crate) const LAST_CATALOG_TABLE_ID: i32 = INDEXES_TABLE_ID;
 pub const PAGE_SIZE: u16 = 4096;
 
 
@@ -17,6 +62,24 @@ pub struct Database<S: Store> {
     pub name: String,
     store: S,
     layout: PageDataLayout,
+    encryption_key: Option<ColumnKey>,
+    statistics_config: StatisticsConfig,
+    row_changes: RowChangeCounter,
+    resource_config: ResourceConfig,
+    tracer: Option<Rc<dyn Tracer>>,
+    clock: Rc<dyn Clock>,
+    // see database/functions.rs
+    functions: RefCell<HashMap<String, ScalarFunction>>,
+    // see database/virtual_table.rs
+    virtual_tables: RefCell<HashMap<String, Rc<dyn VirtualTable>>>,
+    // see database/table_functions.rs
+    table_functions: RefCell<HashMap<String, Rc<TableFunction>>>,
+    // databases attached read-only by alias, see database/attach.rs
+    attached: RefCell<HashMap<String, Rc<Database<FileStore>>>>,
+    // hooks of the insert path by table id, see database/write_pipeline.rs
+    write_pipelines: RefCell<HashMap<i32, WritePipeline>>,
+    // background thread that finds pages to compact, see database/compaction.rs
+    compaction: RefCell<Option<CompactionJob>>,
 }
 
 #[derive(Debug, Error)]
@@ -27,6 +90,10 @@ pub enum DatabaseError {
     TableNotFound(String),
     #[error("Corrupted database: {0}")]
     CorruptedDatabase(String),
+    #[error("Table '{0}' has encrypted columns, but the database has no encryption key")]
+    MissingEncryptionKey(String),
+    #[error("The encryption key of the database is not the key of table '{0}'")]
+    WrongEncryptionKey(String),
 }
 
 impl From<StoreError> for DatabaseError {
@@ -41,11 +108,18 @@ impl From<TableAccessError> for DatabaseError {
     }
 }
 
+impl From<KvStoreError> for DatabaseError {
+    fn from(err: KvStoreError) -> Self {
+        DatabaseError::UnknownError(err.to_string())
+    }
+}
+
 pub struct CreateColumnCommand {
     name: String,
     col_type: ColumnType,
     has_sequence: bool,
     is_unique: bool,
+    is_encrypted: bool,
 }
 
 impl From<(&str, ColumnType)> for CreateColumnCommand {
@@ -55,6 +129,7 @@ impl From<(&str, ColumnType)> for CreateColumnCommand {
             col_type: value.1,
             has_sequence: false,
             is_unique: false,
+            is_encrypted: false,
         }
     }
 }
@@ -66,6 +141,7 @@ impl From<(&str, ColumnType, bool)> for CreateColumnCommand {
             col_type: value.1,
             has_sequence: value.2,
             is_unique: false,
+            is_encrypted: false,
         }
     }
 }
@@ -77,6 +153,19 @@ impl From<(&str, ColumnType, bool, bool)> for CreateColumnCommand {
             col_type: value.1,
             has_sequence: value.2,
             is_unique: value.3,
+            is_encrypted: false,
+        }
+    }
+}
+
+impl From<(&str, ColumnType, bool, bool, bool)> for CreateColumnCommand {
+    fn from(value: (&str, ColumnType, bool, bool, bool)) -> Self {
+        Self {
+            name: value.0.to_owned(),
+            col_type: value.1,
+            has_sequence: value.2,
+            is_unique: value.3,
+            is_encrypted: value.4,
         }
     }
 }
@@ -89,6 +178,8 @@ pub enum CreateTableError {
     InvalidSchemaDefinition(String),
     #[error("Table with the same name already exists")]
     TableAlreadyExists,
+    #[error("Invalid identifier: {0}")]
+    InvalidIdentifier(String),
     #[error("Table creation unknown error: {0}")]
     UnknownError(String),
 }
@@ -117,21 +208,29 @@ impl From<StoreError> for CreateTableError {
     }
 }
 
+impl From<IdentifierError> for CreateTableError {
+    fn from(err: IdentifierError) -> Self {
+        CreateTableError::InvalidIdentifier(err.to_string())
+    }
+}
+
 fn is_safe_dir_name(name: &str) -> bool {
     !name.is_empty()
         && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
 }
 
 impl Database<FileStore> {
-    pub fn new(name: &str) -> Self {
+    /// Opens (or creates and initializes) the database in the directory ./<name>
+    pub fn new(name: &str) -> Result<Self, DatabaseError> {
         let mut do_init = false;
         if !is_safe_dir_name(name) {
-            panic!("Database names only allow alphanumeric chars and '_', '-'");
+            return Err(DatabaseError::UnknownError("Database names only allow alphanumeric chars and '_', '-'".to_owned()));
         }
         let path = Path::new(".").join(name);
 
         if !path.exists() {
-            create_dir(path.clone()).expect("Could not create database directory");
+            create_dir(path.clone())
+                .map_err(|e| DatabaseError::UnknownError(format!("Could not create database directory: {}", e)))?;
             do_init = true;
         }
 
@@ -140,14 +239,26 @@ impl Database<FileStore> {
         let db = Self {
             store,
             name: name.to_owned(),
-            layout: PageDataLayout::new(PAGE_SIZE).unwrap(),
+            layout: PageDataLayout::of_size::<PAGE_SIZE>(),
+            encryption_key: None,
+            statistics_config: StatisticsConfig::default(),
+            row_changes: RowChangeCounter::default(),
+            resource_config: ResourceConfig::default(),
+            tracer: None,
+            clock: Rc::new(SystemClock::default()),
+            functions: RefCell::new(HashMap::new()),
+            virtual_tables: RefCell::new(HashMap::new()),
+            table_functions: RefCell::new(builtin_table_functions()),
+            attached: RefCell::new(HashMap::new()),
+            write_pipelines: RefCell::new(HashMap::new()),
+            compaction: RefCell::new(None),
         };
 
         if do_init {
-            db.init().unwrap();
+            db.init()?;
         }
 
-        db
+        Ok(db)
     }
 }
 
@@ -156,10 +267,51 @@ impl<S: Store> Database<S> {
         Self {
             name: name.to_string(),
             store,
-            layout: PageDataLayout::new(PAGE_SIZE).unwrap(),
+            layout: PageDataLayout::of_size::<PAGE_SIZE>(),
+            encryption_key: None,
+            statistics_config: StatisticsConfig::default(),
+            row_changes: RowChangeCounter::default(),
+            resource_config: ResourceConfig::default(),
+            tracer: None,
+            clock: Rc::new(SystemClock::default()),
+            functions: RefCell::new(HashMap::new()),
+            virtual_tables: RefCell::new(HashMap::new()),
+            table_functions: RefCell::new(builtin_table_functions()),
+            attached: RefCell::new(HashMap::new()),
+            write_pipelines: RefCell::new(HashMap::new()),
+            compaction: RefCell::new(None),
         }
     }
 
+    /// Key for the encrypted columns. It is not stored anywhere, it must be passed every time the database is opened.
+    #[cfg(feature = "encryption")]
+    pub fn with_encryption_key(mut self, key: [u8; crate::table::encryption::KEY_LEN]) -> Self {
+        self.encryption_key = Some(ColumnKey::new(key));
+        self
+    }
+
+    /// Pages written from now on are compressed, existing pages stay readable (every page has its own flag)
+    #[cfg(feature = "page-compression")]
+    pub fn with_page_compression(mut self) -> Self {
+        self.layout = self.layout.with_compression();
+        self
+    }
+
+    /// Time source for timestamps, durations and the sleeps of the throttle (see clock.rs)
+    pub fn with_clock(mut self, clock: Rc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Not a catalog table and not a system table (reserved prefix)
+    pub(crate) fn is_user_table(table_id: i32, name: &str) -> bool {
+        table_id > LAST_CATALOG_TABLE_ID && !name.starts_with(RESERVED_PREFIX)
+    }
+
+    pub(crate) fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
     pub fn drop_create(&self) -> Result<(), DatabaseError> {
         self.store.delete_all()?;
         self.init()?;
@@ -167,16 +319,16 @@ impl<S: Store> Database<S> {
     }
 
     fn table_instance(&self) -> Table {
-        let table_schema = TableSchema::new(vec![
+        let table_schema = TableSchema::of([
             Column::new(10, "id", ColumnType::Int),
             Column::new(20, "name", ColumnType::Varchar(512)),
         ]);
 
-        Table::new(1, "tables".to_owned(), table_schema)
+        Table::new(TABLES_TABLE_ID, "tables".to_owned(), table_schema)
     }
 
     fn col_table_instance(&self) -> Table {
-        let col_schema = TableSchema::new(vec![
+        let col_schema = TableSchema::of([
             Column::new(30, "id", ColumnType::Int),
             Column::new(40, "t_id", ColumnType::Int),
             Column::new(50, "name", ColumnType::Varchar(512)),
@@ -184,7 +336,7 @@ impl<S: Store> Database<S> {
             Column::new(70, "length", ColumnType::Int),
         ]);
 
-        Table::new(2, "columns".to_owned(), col_schema)
+        Table::new(COLUMNS_TABLE_ID, "columns".to_owned(), col_schema)
     }
 
     fn init_table_table(&self) -> Result<(), DatabaseError> {
@@ -361,13 +513,13 @@ impl<S: Store> Database<S> {
     }
 
     fn init_sequences_table(&self) -> Result<(), DatabaseError> {
-        let seq_schema = TableSchema::new(vec![
+        let seq_schema = TableSchema::of([
             Column::new(80, "id", ColumnType::Int),
             Column::new(85, "col_id", ColumnType::Int),
             Column::new(90, "current", ColumnType::Int),
         ]);
 
-        let seq_table = Table::new(3, "sequences".to_owned(), seq_schema);
+        let seq_table = Table::new(SEQUENCES_TABLE_ID, "sequences".to_owned(), seq_schema);
         self.store.create(&self.layout, &seq_table)?;
 
         let tbl_seq = TableAccess::new(seq_table, &self.store, &self.layout);
@@ -404,13 +556,13 @@ impl<S: Store> Database<S> {
     }
 
     fn init_indexes_table(&self) -> Result<(), DatabaseError> {
-        let idx_schema = TableSchema::new(vec![
+        let idx_schema = TableSchema::of([
             Column::new(100, "id", ColumnType::Int),
             Column::new(110, "t_id", ColumnType::Int),
             Column::new(120, "col_ids", ColumnType::Varchar(512)),
         ]);
 
-        let idx_table = Table::new(4, "indexes".to_owned(), idx_schema);
+        let idx_table = Table::new(INDEXES_TABLE_ID, "indexes".to_owned(), idx_schema);
         self.store.create(&self.layout, &idx_table)?;
 
         Ok(())
@@ -438,11 +590,14 @@ impl<S: Store> Database<S> {
     //
     // Same is valid for read_sequence_table and SeqAccess
     pub fn table_access<'db>(&'db self, table: Table) -> Result<TableAccess<'db, S>, DatabaseError> {
+        self.check_encryption_key(&table)?;
+        let layout = self.table_layout(&table)?;
+
         // ignore the index table itself
         // means: the index table cannot have indexes at the moment (they are simply never read).
         // the problem here is the infinite recursion, it's fixable by using a cache of the catalog table indexes
         // as soon as the index of 'indexes' is cached, it's not necessary to read it again. 
-        if table.id() != 4 {
+        if table.id() != INDEXES_TABLE_ID {
             let indexes = self.read_table("indexes")?;
             let idx_acc = self.table_access(indexes)?;
             let indexes = idx_acc.find("t_id", Cell::Int(table.id()))?;
@@ -454,7 +609,7 @@ impl<S: Store> Database<S> {
 
             // indexed_columns:
             // Vec of items column_id and BTree stores (Vec<i32, BTreeStore>)
-            let indexed_columns = indexes.rows().into_iter().map(|(_, row)| {
+            let indexed_columns = indexes.rows()?.into_iter().map(|(_, row)| {
                 let btree_id = match &row.cells()[id_idx] {
                     Cell::Int(val) => val,
                     _ => {
@@ -488,22 +643,104 @@ impl<S: Store> Database<S> {
                 Ok((cold_id, btree))
             }).collect::<Result<Vec<(i32, RefCell<BTreeStore>)>, DatabaseError>>()?;
 
-            Ok(TableAccess::new(table, &self.store, &self.layout)
-                .with_indexes(indexed_columns))
+            let write_pipeline = self.write_pipeline(&table).unwrap_or_default();
+            let mut access = TableAccess::new(table, &self.store, &layout)
+                .with_indexes(indexed_columns)
+                .with_write_pipeline(write_pipeline);
+            if let Some(job) = self.compaction.borrow().as_ref() {
+                access = access.with_compaction(job.handoff());
+            }
+            if self.tracks_row_changes(access.table()) {
+                self.auto_analyze(access.table())?;
+                return self.index_moved_records(access.with_row_changes(self.row_changes.clone()));
+            }
+
+            self.index_moved_records(access)
         } else {
-            Ok(TableAccess::new(table, &self.store, &self.layout))
+            self.index_moved_records(TableAccess::new(table, &self.store, &layout))
         }
     }
 
+    // the rows a format upgrade on open has moved (FileStore::with_upgrade_on_open): their index entries still
+    // point to the old slot, they are updated before the table is accessed (like in upgrade_format)
+    fn index_moved_records<'db>(&'db self, access: TableAccess<'db, S>) -> Result<TableAccess<'db, S>, DatabaseError> {
+        let moved = self.store.moved_records(access.table())?;
+        if !moved.is_empty() {
+            for (page_id, slot_id) in moved {
+                access.repoint_index(page_id, slot_id)?;
+            }
+            self.store.finish_upgrade(access.table())?;
+        }
+        Ok(access)
+    }
+
+    /// Layout of the pages of the table: the layout of the database with the page size the table was created with
+    /// (see create_table_with_page_size). The page size is read from the header of the table file.
+    pub fn table_layout(&self, table: &Table) -> Result<PageDataLayout, DatabaseError> {
+        // the catalog tables always have the page size of the database
+        if table.id() <= LAST_CATALOG_TABLE_ID {
+            return Ok(self.layout.clone());
+        }
+        let page_size = self.store.read_page_size(table)?;
+        if page_size == self.layout.page_size() {
+            return Ok(self.layout.clone());
+        }
+        u16::try_from(page_size).ok()
+            .and_then(|page_size| self.layout.with_page_size(page_size).ok())
+            .ok_or_else(|| DatabaseError::CorruptedDatabase(format!("Table '{}' has an invalid page size {}", table.name(), page_size)))
+    }
+
+    /// Page reads, writes and buffer pool hits of the store since it was opened
+    pub fn io_stats(&self) -> IoStats {
+        self.store.io_stats()
+    }
+
+    /// Latencies of the store operations per operation and table. Empty, unless the store is a TimedStore.
+    pub fn metrics(&self) -> StoreMetrics {
+        self.store.metrics()
+    }
+
+    /// Reads all pages of the table sequentially, so that they are in the buffer pool (see CachedStore)
+    /// or at least in the page cache of the OS. Returns the number of pages read.
+    pub fn warm(&self, table_name: &str) -> Result<PageId, DatabaseError> {
+        const BATCH_SIZE: PageId = 64;
+        let table = self.read_table(table_name)?;
+        let layout = self.table_layout(&table)?;
+        let number_of_pages = self.store.read_metadata(&layout, &table)?.number_of_pages();
+
+        let mut page_id = 1;
+        while page_id <= number_of_pages {
+            let batch: Vec<PageId> = (page_id..=number_of_pages.min(page_id + BATCH_SIZE - 1)).collect();
+            self.store.read_pages(&layout, &batch, &table)?;
+            page_id += BATCH_SIZE;
+        }
+
+        Ok(number_of_pages)
+    }
+
+    /// Warms the given tables when the database is opened, so that the first queries don't have to wait for I/O
+    pub fn preload_on_open(self, table_names: &[&str]) -> Result<Self, DatabaseError> {
+        for name in table_names {
+            self.warm(name)?;
+        }
+        Ok(self)
+    }
+
+    /// Key-value namespace for data that doesn't need a schema (e.g. engine metadata or small blobs)
+    pub fn kv_store(&self, namespace: u16) -> Result<KvStore<'_, S>, KvStoreError> {
+        KvStore::open(&self.store, &self.layout, namespace)
+    }
+
     pub fn read_table(&self, table_name: &str) -> Result<Table, DatabaseError> {
+        let table_name = &Identifier::normalize(table_name);
         let table_table = self.table_instance();
         let access = TableAccess::new(table_table, &self.store, &self.layout);
         let table_query = access.find("name", Cell::Varchar(table_name.to_owned()))?;
         let table_id_index = table_query.schema().find_index_by_name("id")
             .ok_or_else(|| DatabaseError::CorruptedDatabase("Column 'id' not found in 'tables' table".to_owned()))?;
-        let rows = table_query.rows();
+        let rows = table_query.rows()?;
 
-        if rows.len() == 0 {
+        if rows.is_empty() {
             return Err(DatabaseError::TableNotFound(table_name.to_owned()));
         }
         if rows.len() > 1 {
@@ -530,7 +767,7 @@ impl<S: Store> Database<S> {
         let length_index = col_schema.find_index_by_name("length")
             .ok_or_else(|| DatabaseError::CorruptedDatabase("Column 'length' not found in 'columns' table".to_owned()))?;
 
-        let col_rows = col_query.rows().into_iter()
+        let col_rows = col_query.rows()?.into_iter()
             .map(|(_, row)| {
                 let id = match &row.cells()[id_index] {
                     Cell::Int(val) => val,
@@ -546,22 +783,30 @@ impl<S: Store> Database<S> {
                     _ => return Err(DatabaseError::CorruptedDatabase("Column 'length' has wrong type in 'columns' table".to_owned())),
                 };
                 
-                let col_type = match row.cells()[type_index] {
+                let (col_type, encrypted) = match row.cells()[type_index] {
                     Cell::Byte(val) => {
-                        match val {
+                        let col_type = match val & !ENCRYPTED_TYPE_FLAG {
                             0 => ColumnType::Int,
                             1 => ColumnType::Varchar(length as u16), // length is stored separately
                             2 => ColumnType::Byte,
+                            3 => ColumnType::Blob,
+                            4 => ColumnType::Point,
                             _ => return Err(DatabaseError::CorruptedDatabase(format!("Invalid column 'type' value: {}", val))),
-                        }
+                        };
+                        (col_type, val & ENCRYPTED_TYPE_FLAG != 0)
                     },
                     _ => return Err(DatabaseError::CorruptedDatabase("Column 'type' has wrong type in 'columns' table".to_owned())),
                 };
 
-                Ok(Column::new(*id, name, col_type))
+                if encrypted {
+                    Ok(Column::new_encrypted(*id, name, col_type, self.encryption_key.clone()))
+                } else {
+                    Ok(Column::new(*id, name, col_type))
+                }
             }).collect::<Result<Vec<Column>, DatabaseError>>()?;
             
-        let schema = TableSchema::new(col_rows);
+        let schema = TableSchema::try_new(col_rows)
+            .map_err(|e| DatabaseError::CorruptedDatabase(format!("Table '{}': {}", table_name, e)))?;
 
         Ok(Table::new(table_id, table_name.to_owned(), schema))
     }
@@ -573,7 +818,7 @@ impl<S: Store> Database<S> {
         // delete table entry in tables
         let table_table = self.read_table("tables")?;
         let tbl_access = self.table_access(table_table)?;
-        let tbl_query = tbl_access.find("name", Cell::Varchar(name.to_owned()))?;
+        let tbl_query = tbl_access.find("name", Cell::Varchar(table_to_drop.name().to_owned()))?;
         tbl_access.delete(tbl_query)?;
 
         // delete column entries in columns
@@ -582,20 +827,100 @@ impl<S: Store> Database<S> {
 
         let col_query = col_access.find("t_id", Cell::Int(table_to_drop.id()))?;
         col_access.delete(col_query)?;
+        self.delete_key_check(&table_to_drop)?;
         
         // delete sequence if exists
         // delete index if exists
 
         // drop pages file
         self.store.delete(&table_to_drop)?;
+        if table_to_drop.has_blobs() {
+            self.store.delete(&table_to_drop.overflow_table())?;
+        }
         Ok(())
     }
 
     pub fn create_table<C: Into<CreateColumnCommand>>(&self, name: &str, schema_command: Vec<C>)
+     -> Result<Table, CreateTableError> {
+        self.create_table_with_identifier(Identifier::parse_user_defined(name)?, schema_command, &self.layout)
+    }
+
+    /// Same as create_table, but the pages of the table have the given size instead of the page size of the database
+    /// (e.g. larger pages for a table with long rows). The size is stored in the header of the table file.
+    pub fn create_table_with_page_size<C: Into<CreateColumnCommand>>(&self, name: &str, schema_command: Vec<C>, page_size: u16)
+     -> Result<Table, CreateTableError> {
+        let layout = self.layout.with_page_size(page_size)
+            .map_err(|e| CreateTableError::InvalidSchemaDefinition(e.to_string()))?;
+        self.create_table_with_identifier(Identifier::parse_user_defined(name)?, schema_command, &layout)
+    }
+
+    /// CREATE TABLE ... AS SELECT: the columns get the names and types of the query result (without unique
+    /// indexes and encryption) and the rows are written with the bulk loader. If loading fails, the table
+    /// is dropped again, so the catalog contains either the filled table or nothing.
+    pub fn create_table_from<'r, I: IntoRow + 'r>(&self, name: &str, source: QueryResult<'r, I>)
+     -> Result<(Table, usize), CreateTableError> {
+        self.create_table_with_identifier_from(Identifier::parse_user_defined(name)?, source)
+    }
+
+    fn create_table_with_identifier_from<'r, I: IntoRow + 'r>(&self, name: Identifier, source: QueryResult<'r, I>)
+     -> Result<(Table, usize), CreateTableError> {
+        let commands: Vec<CreateColumnCommand> = source.schema().columns.iter()
+            .map(|c| (c.name.as_str(), c.col_type.clone()).into())
+            .collect();
+        // read the query before the table exists
+        let rows: Vec<Row> = source.rows()?.into_iter().map(IntoRow::into_row).collect();
+
+        let table = self.create_table_with_identifier(name, commands, &self.layout)?;
+        let count = self.table_access(table.clone())
+            .map_err(CreateTableError::from)
+            .and_then(|access| Ok(access.insert_all(rows)?));
+        match count {
+            Ok(count) => Ok((table, count)),
+            Err(err) => {
+                let _ = self.drop_table(table.name());
+                Err(err)
+            },
+        }
+    }
+
+    /// Same as create_table, but for system tables: the name must start with the reserved prefix
+    pub(crate) fn create_system_table<C: Into<CreateColumnCommand>>(&self, name: &str, schema_command: Vec<C>)
+     -> Result<Table, CreateTableError> {
+        let identifier = Identifier::parse(name)?;
+        if !identifier.is_reserved() {
+            return Err(CreateTableError::InvalidSchemaDefinition(format!("System table '{}' must start with '{}'", name, RESERVED_PREFIX)));
+        }
+        self.create_table_with_identifier(identifier, schema_command, &self.layout)
+    }
+
+    fn create_table_with_identifier<C: Into<CreateColumnCommand>>(&self, name: Identifier, schema_command: Vec<C>, layout: &PageDataLayout)
      -> Result<Table, CreateTableError> {
         // check if unique index is only created on int
         // create columns
-        let column_commands: Vec<CreateColumnCommand> = schema_command.into_iter().map(|c| c.into()).collect();
+        let name = name.into_string();
+        let name = name.as_str();
+        let mut column_commands: Vec<CreateColumnCommand> = schema_command.into_iter().map(|c| c.into()).collect();
+        if column_commands.is_empty() {
+            return Err(CreateTableError::InvalidSchemaDefinition(format!("Table '{}' needs at least one column", name)));
+        }
+        for i in 0..column_commands.len() {
+            let col_name = Identifier::parse_user_defined(&column_commands[i].name)?.into_string();
+            if column_commands[..i].iter().any(|other| other.name == col_name) {
+                return Err(CreateTableError::InvalidSchemaDefinition(format!("Column '{}' is defined more than once", col_name)));
+            }
+            column_commands[i].name = col_name;
+
+            let cc = &column_commands[i];
+            if cc.is_encrypted && cc.is_unique {
+                return Err(CreateTableError::InvalidSchemaDefinition(format!("Encrypted column '{}' cannot have a unique index (the index would contain the plain values)", cc.name)));
+            }
+            if cc.is_encrypted && cc.col_type == ColumnType::Blob {
+                return Err(CreateTableError::InvalidSchemaDefinition(format!("BLOB column '{}' cannot be encrypted", cc.name)));
+            }
+            if cc.is_encrypted && self.encryption_key.is_none() {
+                return Err(CreateTableError::InvalidSchemaDefinition(format!("Encrypted column '{}' needs an encryption key", cc.name)));
+            }
+        }
         // create table entry in tables
         let table_table = self.read_table("tables")?;
         let mut tbl_seq_acc = self.seq_access_for_table(table_table.clone())?;
@@ -606,7 +931,7 @@ impl<S: Store> Database<S> {
             "name",
             Cell::Varchar(name.to_owned())
         )?;
-        if existing_table_query.rows().len() > 0 {
+        if !existing_table_query.rows()?.is_empty() {
             return Err(CreateTableError::TableAlreadyExists);
         }
 
@@ -631,7 +956,11 @@ impl<S: Store> Database<S> {
                 return Err(CreateTableError::InvalidSchemaDefinition(format!("Sequence can only be created on int columns. Column '{}' has type '{}'", cc.name, cc.col_type)));
             }
             let col_id = col_seq_acc.next_val("id")?;
-            let column = Column::new(col_id, &cc.name, cc.col_type);
+            let column = if cc.is_encrypted {
+                Column::new_encrypted(col_id, &cc.name, cc.col_type, self.encryption_key.clone())
+            } else {
+                Column::new(col_id, &cc.name, cc.col_type)
+            };
             col_access.insert(&Row::new(vec![
                 Cell::Int(col_id),
                 Cell::Int(tbl_id),
@@ -640,11 +969,13 @@ impl<S: Store> Database<S> {
                     ColumnType::Int => 0,
                     ColumnType::Varchar(_) => 1,
                     ColumnType::Byte => 2,
-                }),
+                    ColumnType::Blob => 3,
+                    ColumnType::Point => 4,
+                } | if column.encrypted { ENCRYPTED_TYPE_FLAG } else { 0 }),
                 Cell::Int(match column.col_type {
                     ColumnType::Int => 0,
                     ColumnType::Varchar(len) => len as i32,
-                    ColumnType::Byte => 0,
+                    ColumnType::Byte | ColumnType::Blob | ColumnType::Point => 0,
                 }),
             ]))?;
             
@@ -680,10 +1011,18 @@ impl<S: Store> Database<S> {
             columns.push(column);
         }
         
-        let schema = TableSchema::new(columns);
+        // not empty, checked above
+        let schema = TableSchema::try_new(columns)
+            .map_err(|e| CreateTableError::InvalidSchemaDefinition(e.to_string()))?;
         let new_table = Table::new(tbl_id, name.to_owned(), schema);
         
-        self.store.create(&self.layout, &new_table)?;
+        self.store.create(layout, &new_table)?;
+        if new_table.has_blobs() {
+            self.store.create(layout, &new_table.overflow_table())?;
+        }
+        if new_table.schema().columns.iter().any(|c| c.encrypted) {
+            self.store_key_check(&new_table)?;
+        }
 
         Ok(new_table)
     }
@@ -692,7 +1031,36 @@ impl<S: Store> Database<S> {
 #[cfg(test)]
 mod tests {
 
-    use crate::{database::{CreateTableError, Database, DatabaseError}, store::file_store::FileStore, table::{ColumnType, table::{Cell, Row}}};
+    use std::{rc::Rc, time::{Duration, UNIX_EPOCH}};
+
+    use crate::{clock::ManualClock, database::{CreateTableError, Database, DatabaseError, system_views::STATS_BUFFER_POOL}, store::{Store, file_store::FileStore, page_cache::CachedStore}, table::{ColumnType, table::{Cell, Row}}};
+
+    #[test]
+    fn should_use_the_page_size_of_the_table() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        let small = db.create_table_with_page_size("small", vec![("id", ColumnType::Int, false, true)], 64).unwrap();
+        let default = db.create_table("default", vec![("id", ColumnType::Int)]).unwrap();
+        assert_eq!(db.table_layout(&small).unwrap().page_size(), 64);
+        assert_eq!(db.table_layout(&default).unwrap(), db.layout);
+
+        let access = db.table_access(small).unwrap();
+        for i in 0..20 {
+            access.insert(&Row::new(vec![Cell::Int(i)])).unwrap();
+        }
+        let pages = db.analyze("small").unwrap().pages;
+        assert!(pages > 1, "{}", pages);
+
+        // the page size is read from the file when the database is opened again
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        let access = db.table_access(db.read_table("small").unwrap()).unwrap();
+        assert_eq!(access.find_all().unwrap().rows().unwrap().len(), 20);
+        assert_eq!(access.find("id", Cell::Int(17)).unwrap().rows().unwrap().len(), 1);
+        assert_eq!(db.table_disk_usage("small").unwrap().heap_pages as i32, pages);
+
+        assert!(matches!(db.create_table_with_page_size("tiny", vec![("id", ColumnType::Int)], 16), Err(CreateTableError::InvalidSchemaDefinition(_))));
+    }
 
     #[test]
     fn should_contain_base_tables_after_init_db() {
@@ -707,7 +1075,7 @@ mod tests {
         // Assert
         let table_tables = db.read_table("tables").unwrap();
         let access = db.table_access(table_tables).unwrap();
-        let table_entries = access.find_all().unwrap().rows()
+        let table_entries = access.find_all().unwrap().rows().unwrap()
             .into_iter()
             .map(|(_, row)| row)
             .collect::<Vec<Row>>();
@@ -720,7 +1088,7 @@ mod tests {
 
         let table_tables = db.read_table("columns").unwrap();
         let access = db.table_access(table_tables).unwrap();
-        let column_entries = access.find_all().unwrap().rows()
+        let column_entries = access.find_all().unwrap().rows().unwrap()
             .into_iter()
             .map(|(_, row)| row)
             .collect::<Vec<Row>>();
@@ -772,7 +1140,7 @@ mod tests {
         ])).unwrap();
 
         let query_result = access.find("id", Cell::Int(1)).unwrap();
-        let rows = query_result.rows();
+        let rows = query_result.rows().unwrap();
         assert_eq!(rows.len(), 1);
         assert_eq!(rows[0].1.cells(), &[Cell::Int(1), Cell::Varchar("Alice".to_owned()), Cell::Int(30)]);
 
@@ -810,6 +1178,8 @@ mod tests {
             Cell::Varchar("Alice".to_owned()),
             Cell::Int(30),
         ])).unwrap();
+        // the table would stay in the catalog
+        assert!(access.drop().is_err());
 
         db.drop_table("persons").unwrap();
         let result = db.read_table("persons");
@@ -818,12 +1188,118 @@ mod tests {
         let tbl_table = db.read_table("tables").unwrap();
         let access = db.table_access(tbl_table).unwrap();
         let query_result = access.find("name", Cell::Varchar("persons".to_owned())).unwrap();
-        assert_eq!(query_result.rows().len(), 0);
+        assert_eq!(query_result.rows().unwrap().len(), 0);
 
         let col_table = db.read_table("columns").unwrap();
         let col_access = db.table_access(col_table).unwrap();
         let query_result = col_access.find("t_id", Cell::Int(table_id)).unwrap();
-        assert_eq!(query_result.rows().len(), 0);
+        assert_eq!(query_result.rows().unwrap().len(), 0);
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn should_apply_identifier_rules_when_creating_tables() {
+        let base_path = tempfile::tempdir().unwrap();
+        let store = FileStore::new(base_path.path());
+        let db = Database::new_with_store("test_db", store);
+        db.drop_create().unwrap();
+
+        let result = db.create_table("_tables", vec![("id", ColumnType::Int)]);
+        assert!(matches!(result, Err(CreateTableError::InvalidIdentifier(_))));
+
+        let result = db.create_table("persons", vec![("id", ColumnType::Int), ("ID", ColumnType::Int)]);
+        assert!(matches!(result, Err(CreateTableError::InvalidSchemaDefinition(_))));
+
+        db.create_table("Persons", vec![("Id", ColumnType::Int)]).unwrap();
+        let table = db.read_table("PERSONS").unwrap();
+        assert_eq!(table.name(), "persons");
+        assert_eq!(table.schema().columns[0].name, "id");
+
+        let result = db.create_table("persons", vec![("id", ColumnType::Int)]);
+        assert!(matches!(result, Err(CreateTableError::TableAlreadyExists)));
+    }
+
+    #[test]
+    #[cfg(feature = "encryption")]
+    fn should_encrypt_marked_columns() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()))
+            .with_encryption_key([42; 32]);
+        db.drop_create().unwrap();
+
+        let table = db.create_table("persons", vec![
+            ("id", ColumnType::Int, false, true, false),
+            ("ssn", ColumnType::Varchar(20), false, false, true),
+            ("salary", ColumnType::Int, false, false, true),
+        ]).unwrap();
+        let access = db.table_access(table.clone()).unwrap();
+        access.insert(&Row::new(vec![Cell::Int(1), Cell::Varchar("123-45-6789".to_owned()), Cell::Int(5000)])).unwrap();
+        access.update(access.find("id", Cell::Int(1)).unwrap(), vec![("ssn", Cell::Varchar("987-65-4321".to_owned()))]).unwrap();
+
+        let table = db.read_table("persons").unwrap();
+        assert!(table.schema().columns[1].encrypted);
+        let rows = db.table_access(table.clone()).unwrap().find_all().unwrap().rows().unwrap();
+        assert_eq!(rows[0].1.cells(), &vec![Cell::Int(1), Cell::Varchar("987-65-4321".to_owned()), Cell::Int(5000)]);
+
+        let raw = std::fs::read(base_path.path().join(table.file_path())).unwrap();
+        assert!(!raw.windows(11).any(|w| w == b"987-65-4321"));
+
+        // without key, the table cannot be accessed
+        let without_key = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        let table = without_key.read_table("persons").unwrap();
+        assert!(without_key.table_access(table).is_err());
+
+        let result = db.create_table("other", vec![("id", ColumnType::Int, false, true, true)]);
+        assert!(matches!(result, Err(CreateTableError::InvalidSchemaDefinition(_))));
+        let result = without_key.create_table("other", vec![("id", ColumnType::Int, false, false, true)]);
+        assert!(matches!(result, Err(CreateTableError::InvalidSchemaDefinition(_))));
+    }
+
+    #[test]
+    fn should_use_the_clock_of_the_database() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()))
+            .with_clock(Rc::new(ManualClock::new(UNIX_EPOCH + Duration::from_secs(1234))));
+        db.drop_create().unwrap();
+
+        let dump_dir = tempfile::tempdir().unwrap();
+        assert_eq!(db.dump(dump_dir.path()).unwrap().created, 1234);
+    }
+
+    #[test]
+    fn should_warm_tables_into_the_buffer_pool() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        let table = db.create_table("persons", vec![("id", ColumnType::Int), ("name", ColumnType::Varchar(1000))]).unwrap();
+        let access = db.table_access(table).unwrap();
+        for i in 0..10 {
+            access.insert(&Row::new(vec![Cell::Int(i), Cell::Varchar("x".repeat(1000))])).unwrap();
+        }
+        drop(access);
+        drop(db);
+
+        let db = Database::new_with_store("test_db", CachedStore::new(FileStore::new(base_path.path()), 100))
+            .preload_on_open(&["persons"]).unwrap();
+        let table = db.read_table("persons").unwrap();
+        let pages = db.store.read_metadata(&db.layout, &table).unwrap().number_of_pages();
+        assert!(pages > 1);
+        assert!((1..=pages).all(|page_id| db.store.contains(&table, page_id)));
+
+        let cached = db.system_view(STATS_BUFFER_POOL).unwrap()
+            .filter(move |row| row.cells()[0] == Cell::Int(table.id()))
+            .rows().unwrap();
+        assert_eq!(cached.len(), pages as usize);
+    }
+
+    #[test]
+    fn should_reject_tables_without_columns_and_invalid_names() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+
+        let no_columns: Vec<(&str, ColumnType)> = vec![];
+        assert!(matches!(db.create_table("t", no_columns), Err(CreateTableError::InvalidSchemaDefinition(_))));
+        assert!(db.read_table("t").is_err());
+        assert!(Database::new("../outside").is_err());
+    }
+}
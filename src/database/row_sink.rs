@@ -0,0 +1,140 @@
+use std::{pin::Pin, task::{Context, Poll}};
+
+use futures_sink::Sink;
+
+use crate::{
+    database::{Database, DatabaseError, table_access::{TableAccess, TableAccessError}},
+    store::Store,
+    table::table::Row,
+};
+
+// Async insert sink (feature async-sink): a futures::Sink<Row> for streaming producers (e.g. a Kafka consumer),
+// so they don't need their own batching. The rows are collected and written with the bulk loader
+// (TableAccess::insert_all) when the batch is full, on flush and on close.
+// - batch size: half of the pages of the buffer pool (see CachedStore), so a batch doesn't evict the pages the
+//   readers use; DEFAULT_BATCH_PAGES without buffer pool. The size of a row is estimated by its serialized size.
+// - backpressure: the store is synchronous, so poll_ready writes a full batch before it is ready again, the
+//   producer waits for the write. It is never Pending. There is no WAL yet, its capacity is not taken into account.
+// - like insert_all: a batch with an invalid row or a duplicate value is not written at all, the error is returned
+//   by the poll that writes it. The hooks of the write pipeline don't run, free space of existing pages is not used.
+
+const DEFAULT_BATCH_PAGES: usize = 32;
+
+pub struct RowSink<'db, S: Store> {
+    access: TableAccess<'db, S>,
+    batch: Vec<Row>,
+    batch_bytes: usize,
+    max_batch_bytes: usize,
+}
+
+impl<'db, S: Store> RowSink<'db, S> {
+    pub(crate) fn new(access: TableAccess<'db, S>, max_batch_bytes: usize) -> Self {
+        Self { access, batch: Vec::new(), batch_bytes: 0, max_batch_bytes }
+    }
+
+    /// Rows sent, but not written yet
+    pub fn buffered_rows(&self) -> usize {
+        self.batch.len()
+    }
+
+    fn write_batch(&mut self) -> Result<(), TableAccessError> {
+        let batch = std::mem::take(&mut self.batch);
+        self.batch_bytes = 0;
+        if !batch.is_empty() {
+            self.access.insert_all(batch)?;
+        }
+        Ok(())
+    }
+}
+
+impl<S: Store> Sink<Row> for RowSink<'_, S> {
+    type Error = TableAccessError;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let sink = self.get_mut();
+        if sink.batch_bytes >= sink.max_batch_bytes {
+            return Poll::Ready(sink.write_batch());
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, row: Row) -> Result<(), Self::Error> {
+        let sink = self.get_mut();
+        sink.batch_bytes += row.serialize().len();
+        sink.batch.push(row);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(self.get_mut().write_batch())
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
+    }
+}
+
+impl<S: Store> Database<S> {
+    /// Sink for the rows of the table, see database/row_sink.rs
+    pub fn row_sink(&self, table_name: &str) -> Result<RowSink<'_, S>, DatabaseError> {
+        let table = self.read_table(table_name)?;
+        let layout = self.table_layout(&table)?;
+        let batch_pages = self.store.buffer_pool_capacity()
+            .map(|capacity| (capacity / 2).max(1))
+            .unwrap_or(DEFAULT_BATCH_PAGES);
+        let access = self.table_access(table)?;
+        Ok(RowSink::new(access, batch_pages * layout.page_data_size()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{pin::Pin, task::{Context, Poll, Waker}};
+
+    use futures_sink::Sink;
+
+    use crate::{database::{Database, row_sink::RowSink, table_access::TableAccessError}, store::{Store, file_store::FileStore, page_cache::CachedStore}, table::{ColumnType, table::{Cell, Row}}};
+
+    fn poll<T>(poll: Poll<T>) -> T {
+        match poll {
+            Poll::Ready(value) => value,
+            Poll::Pending => panic!("the sink is never pending"),
+        }
+    }
+
+    fn send<S: Store>(sink: &mut RowSink<S>, row: Row) -> Result<(), TableAccessError> {
+        let mut cx = Context::from_waker(Waker::noop());
+        poll(Pin::new(&mut *sink).poll_ready(&mut cx))?;
+        Pin::new(sink).start_send(row)
+    }
+
+    #[test]
+    fn should_write_the_rows_in_batches_of_the_buffer_pool_size() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", CachedStore::new(FileStore::new(base_path.path()), 8));
+        db.drop_create().unwrap();
+        db.create_table_with_page_size("events", vec![("id", ColumnType::Int), ("payload", ColumnType::Varchar(50))], 256).unwrap();
+        let access = db.table_access(db.read_table("events").unwrap()).unwrap();
+        let count = || access.find_all().unwrap().rows().unwrap().len();
+
+        let mut sink = db.row_sink("events").unwrap();
+        for id in 0..200 {
+            send(&mut sink, Row::new(vec![Cell::Int(id), Cell::Varchar(format!("event {}", id))])).unwrap();
+        }
+        // 4 pages per batch: the full batches are written while sending, the rest is buffered
+        let written = count();
+        assert!(written > 0 && written < 200, "{} rows written", written);
+        assert_eq!(written + sink.buffered_rows(), 200);
+
+        let mut cx = Context::from_waker(Waker::noop());
+        poll(Pin::new(&mut sink).poll_close(&mut cx)).unwrap();
+        assert_eq!(sink.buffered_rows(), 0);
+        assert_eq!(count(), 200);
+
+        // a batch with an invalid row is not written
+        send(&mut sink, Row::new(vec![Cell::Int(200), Cell::Varchar("ok".to_owned())])).unwrap();
+        send(&mut sink, Row::new(vec![Cell::Int(201)])).unwrap();
+        assert!(poll(Pin::new(&mut sink).poll_flush(&mut cx)).is_err());
+        assert_eq!(count(), 200);
+    }
+}
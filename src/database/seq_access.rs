@@ -54,7 +54,7 @@ impl<'db, S: Store> SeqAccess<'db, S> {
         let current_idx = seq_query.schema().find_index_by_name("current")
             .ok_or(SeqAccessError::NotASequence)?;
 
-        let seq = seq_query.rows();
+        let seq = seq_query.rows()?;
 
         if seq.len() == 0 {
             return Err(SeqAccessError::SequenceNotFound);
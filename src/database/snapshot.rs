@@ -0,0 +1,191 @@
+use std::{fs::File, io::{Cursor, Read, Write}, path::{Path, PathBuf}};
+
+use tempfile::TempDir;
+
+use crate::{database::{Database, DatabaseError, table_access::TableAccess}, store::file_store::FileStore, table::table::Cell};
+
+// Snapshot stream format (all numbers big endian):
+//   8 bytes: magic "PLAYSNAP"
+//   1 byte:  format version
+//   entries, one per file of the data directory:
+//     2 bytes: length of the file name (0 marks the end of the stream)
+//     n bytes: file name (UTF-8, no directories)
+//     8 bytes: length of the file content
+//     n bytes: file content
+//
+// There is no WAL yet, so a replica cannot catch up incrementally. To catch up, a new snapshot is
+// shipped and restored into the replica directory (restore overwrites all files).
+pub const SNAPSHOT_MAGIC: &[u8; 8] = b"PLAYSNAP";
+pub const SNAPSHOT_VERSION: u8 = 1;
+
+/// A consistent copy of the data directory. The files are copied when the stream is created,
+/// so that changes to the database afterwards are not part of the stream.
+pub struct SnapshotStream {
+    reader: Box<dyn Read>,
+    // keeps the copied files alive until the stream is dropped
+    _copy_dir: TempDir,
+}
+
+impl Read for SnapshotStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+impl Database<FileStore> {
+    pub fn snapshot_stream(&self) -> Result<SnapshotStream, DatabaseError> {
+        // Index files are created lazily on first access. Open all indexes once,
+        // so that the replica does not need to create them (it cannot write).
+        let tables = TableAccess::new(self.table_instance(), &self.store, &self.layout);
+        for (_, row) in tables.find_all()?.rows()? {
+            if let Cell::Varchar(name) = &row.cells()[1] {
+                self.table_access(self.read_table(name)?)?;
+            }
+        }
+
+        // Database is not Sync and all operations are synchronous, so nothing can be written while copying.
+        let copy_dir = tempfile::tempdir().map_err(io_error)?;
+        let mut reader: Box<dyn Read> = Box::new(Cursor::new([SNAPSHOT_MAGIC.as_slice(), &[SNAPSHOT_VERSION]].concat()));
+
+        let mut entries = std::fs::read_dir(self.store.base_path()).map_err(io_error)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<Vec<PathBuf>, std::io::Error>>()
+            .map_err(io_error)?;
+        entries.sort();
+
+        let mut throttle = self.throttle();
+        for path in entries.into_iter().filter(|p| p.is_file()) {
+            let name = path.file_name()
+                .and_then(|n| n.to_str())
+                .ok_or_else(|| DatabaseError::UnknownError(format!("Invalid file name in data directory: {:?}", path)))?
+                .to_owned();
+
+            let copy_path = copy_dir.path().join(&name);
+            let len = std::fs::copy(&path, &copy_path).map_err(io_error)?;
+            throttle.consume(len.div_ceil(self.layout.page_size() as u64), len);
+
+            let mut entry_header = Vec::new();
+            entry_header.extend_from_slice(&(name.len() as u16).to_be_bytes());
+            entry_header.extend_from_slice(name.as_bytes());
+            entry_header.extend_from_slice(&len.to_be_bytes());
+
+            let file = File::open(&copy_path).map_err(io_error)?;
+            reader = Box::new(reader.chain(Cursor::new(entry_header)).chain(file));
+        }
+
+        reader = Box::new(reader.chain(Cursor::new(0u16.to_be_bytes())));
+
+        Ok(SnapshotStream {
+            reader,
+            _copy_dir: copy_dir,
+        })
+    }
+
+    /// Writes the files of a snapshot stream into `target_dir` (which is created if necessary).
+    pub fn restore_snapshot<R: Read>(mut reader: R, target_dir: &Path) -> Result<(), DatabaseError> {
+        let mut magic = [0u8; 9];
+        reader.read_exact(&mut magic).map_err(io_error)?;
+        if &magic[..8] != SNAPSHOT_MAGIC {
+            return Err(DatabaseError::CorruptedDatabase("Not a playdb snapshot".to_owned()));
+        }
+        if magic[8] != SNAPSHOT_VERSION {
+            return Err(DatabaseError::CorruptedDatabase(format!("Unsupported snapshot version {}", magic[8])));
+        }
+
+        std::fs::create_dir_all(target_dir).map_err(io_error)?;
+
+        loop {
+            let mut name_len = [0u8; 2];
+            reader.read_exact(&mut name_len).map_err(io_error)?;
+            let name_len = u16::from_be_bytes(name_len) as usize;
+            if name_len == 0 {
+                return Ok(());
+            }
+
+            let mut name = vec![0u8; name_len];
+            reader.read_exact(&mut name).map_err(io_error)?;
+            let name = String::from_utf8(name)
+                .map_err(|_| DatabaseError::CorruptedDatabase("Invalid file name in snapshot".to_owned()))?;
+            // file names must not leave the target directory
+            if name.contains(['/', '\\']) || name == "." || name == ".." {
+                return Err(DatabaseError::CorruptedDatabase(format!("Invalid file name in snapshot: {}", name)));
+            }
+
+            let mut len = [0u8; 8];
+            reader.read_exact(&mut len).map_err(io_error)?;
+            let len = u64::from_be_bytes(len);
+
+            let mut file = File::create(target_dir.join(&name)).map_err(io_error)?;
+            let copied = std::io::copy(&mut (&mut reader).take(len), &mut file).map_err(io_error)?;
+            if copied != len {
+                return Err(DatabaseError::CorruptedDatabase(format!("Snapshot ended in the middle of file '{}'", name)));
+            }
+            file.flush().map_err(io_error)?;
+        }
+    }
+
+    /// Opens a restored snapshot. Every write operation fails.
+    pub fn open_replica(name: &str, dir: &Path) -> Self {
+        Self::new_with_store(name, FileStore::new_read_only(dir))
+    }
+}
+
+fn io_error(err: std::io::Error) -> DatabaseError {
+    DatabaseError::UnknownError(format!("Snapshot I/O error: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use crate::{database::{Database, DatabaseError}, store::file_store::FileStore, table::{ColumnType, table::{Cell, Row}}};
+
+    #[test]
+    fn should_restore_snapshot_as_read_only_replica() {
+        let primary_dir = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(primary_dir.path()));
+        db.drop_create().unwrap();
+
+        let table = db.create_table("persons", vec![
+            ("id", ColumnType::Int, false, true),
+            ("name", ColumnType::Varchar(100), false, false),
+        ]).unwrap();
+        let access = db.table_access(table).unwrap();
+        access.insert(&Row::new(vec![Cell::Int(1), Cell::Varchar("Alice".to_owned())])).unwrap();
+
+        let mut snapshot = Vec::new();
+        db.snapshot_stream().unwrap().read_to_end(&mut snapshot).unwrap();
+
+        // not part of the snapshot anymore
+        access.insert(&Row::new(vec![Cell::Int(2), Cell::Varchar("Bob".to_owned())])).unwrap();
+
+        let replica_dir = tempfile::tempdir().unwrap();
+        Database::restore_snapshot(snapshot.as_slice(), replica_dir.path()).unwrap();
+        let replica = Database::open_replica("test_db", replica_dir.path());
+
+        let table = replica.read_table("persons").unwrap();
+        let replica_access = replica.table_access(table).unwrap();
+        let rows = replica_access.find_all().unwrap().rows().unwrap();
+        assert_eq!(rows.len(), 1);
+        let by_index = replica_access.find("id", Cell::Int(1)).unwrap().rows().unwrap();
+        assert_eq!(by_index[0].1.cells()[1], Cell::Varchar("Alice".to_owned()));
+
+        assert!(replica_access.insert(&Row::new(vec![Cell::Int(3), Cell::Varchar("Carol".to_owned())])).is_err());
+        assert!(replica.create_table("other", vec![("id", ColumnType::Int)]).is_err());
+        assert_eq!(replica_access.find_all().unwrap().rows().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn should_reject_invalid_snapshots() {
+        let target = tempfile::tempdir().unwrap();
+
+        let not_a_snapshot = b"NOTASNAPSHOT".as_slice();
+        assert!(matches!(Database::restore_snapshot(not_a_snapshot, target.path()), Err(DatabaseError::CorruptedDatabase(_))));
+
+        let mut escaping = b"PLAYSNAP\x01".to_vec();
+        escaping.extend_from_slice(&5u16.to_be_bytes());
+        escaping.extend_from_slice(b"../x1");
+        escaping.extend_from_slice(&0u64.to_be_bytes());
+        assert!(matches!(Database::restore_snapshot(escaping.as_slice(), target.path()), Err(DatabaseError::CorruptedDatabase(_))));
+    }
+}
@@ -0,0 +1,290 @@
+use std::{cmp::Ordering, fmt::Display};
+
+use crate::{
+    data::page::Record,
+    database::{NULL_INT, table_access::{QueryResult, TableAccess, TableAccessError, find_column_for_query}},
+    store::Store,
+    table::{ColumnType, TableSchema, table::{Cell, Row}},
+};
+
+// Sorting by several columns, each ascending or descending, with NULLs first or last.
+// Only Int columns have a NULL (NULL_INT). Without an explicit NullsOrder, NULLs are treated as the largest value
+// (like Postgres): last for ASC, first for DESC.
+// The sort is stable and in memory: there is no external merge sort yet, so the rows must fit into memory.
+// With a limit, TopK keeps only the best `limit` rows in a bounded heap (O(n log k) time, O(k) memory).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortDirection {
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NullsOrder {
+    First,
+    Last,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortKey {
+    pub column: String,
+    pub direction: SortDirection,
+    pub nulls: Option<NullsOrder>,
+}
+
+impl SortKey {
+    pub fn asc(column: &str) -> Self {
+        Self { column: column.to_owned(), direction: SortDirection::Asc, nulls: None }
+    }
+
+    pub fn desc(column: &str) -> Self {
+        Self { column: column.to_owned(), direction: SortDirection::Desc, nulls: None }
+    }
+
+    pub fn nulls_first(mut self) -> Self {
+        self.nulls = Some(NullsOrder::First);
+        self
+    }
+
+    pub fn nulls_last(mut self) -> Self {
+        self.nulls = Some(NullsOrder::Last);
+        self
+    }
+
+    fn nulls_order(&self) -> NullsOrder {
+        match (self.nulls, self.direction) {
+            (Some(nulls), _) => nulls,
+            (None, SortDirection::Asc) => NullsOrder::Last,
+            (None, SortDirection::Desc) => NullsOrder::First,
+        }
+    }
+}
+
+impl Display for SortKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.column)?;
+        if self.direction == SortDirection::Desc {
+            f.write_str(" DESC")?;
+        }
+        match self.nulls {
+            Some(NullsOrder::First) => f.write_str(" NULLS FIRST"),
+            Some(NullsOrder::Last) => f.write_str(" NULLS LAST"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Compares rows of one schema by the sort keys
+pub struct RowComparator {
+    // column index, direction, nulls
+    keys: Vec<(usize, SortDirection, NullsOrder)>,
+}
+
+impl RowComparator {
+    pub fn new(schema: &TableSchema, keys: &[SortKey]) -> Result<Self, TableAccessError> {
+        let keys = keys.iter()
+            .map(|key| Ok((find_column_for_query(schema, &key.column)?, key.direction, key.nulls_order())))
+            .collect::<Result<Vec<_>, TableAccessError>>()?;
+        Ok(Self { keys })
+    }
+
+    pub fn compare(&self, a: &Row, b: &Row) -> Ordering {
+        for (index, direction, nulls) in self.keys.iter() {
+            let ordering = compare_cells(&a.cells()[*index], &b.cells()[*index], *direction, *nulls);
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    }
+
+    /// Stable sort of any items that contain a row
+    pub fn sort<T, F: Fn(&T) -> &Row>(&self, items: &mut [T], row: F) {
+        items.sort_by(|a, b| self.compare(row(a), row(b)));
+    }
+}
+
+/// The first `limit` items in sort order of all pushed items. Equal items keep their push order (like the stable sort).
+pub struct TopK<'c, T, F: Fn(&T) -> &Row> {
+    comparator: &'c RowComparator,
+    row: F,
+    limit: usize,
+    // max-heap: the worst of the kept items is at index 0
+    heap: Vec<(u64, T)>,
+    pushed: u64,
+}
+
+impl<'c, T, F: Fn(&T) -> &Row> TopK<'c, T, F> {
+    pub fn new(comparator: &'c RowComparator, limit: usize, row: F) -> Self {
+        Self { comparator, row, limit, heap: Vec::with_capacity(limit.min(1024)), pushed: 0 }
+    }
+
+    // ties by push order, so the result is the same as of the stable sort
+    fn compare(&self, a: &(u64, T), b: &(u64, T)) -> Ordering {
+        self.comparator.compare((self.row)(&a.1), (self.row)(&b.1))
+            .then(a.0.cmp(&b.0))
+    }
+
+    pub fn push(&mut self, item: T) {
+        let entry = (self.pushed, item);
+        self.pushed += 1;
+        if self.limit == 0 {
+            return;
+        }
+        if self.heap.len() < self.limit {
+            self.heap.push(entry);
+            self.sift_up(self.heap.len() - 1);
+        } else if self.compare(&entry, &self.heap[0]) == Ordering::Less {
+            self.heap[0] = entry;
+            self.sift_down(0);
+        }
+    }
+
+    fn sift_up(&mut self, mut pos: usize) {
+        while pos > 0 {
+            let parent = (pos - 1) / 2;
+            if self.compare(&self.heap[pos], &self.heap[parent]) != Ordering::Greater {
+                break;
+            }
+            self.heap.swap(pos, parent);
+            pos = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut pos: usize) {
+        loop {
+            let mut largest = pos;
+            for child in [2 * pos + 1, 2 * pos + 2] {
+                if child < self.heap.len() && self.compare(&self.heap[child], &self.heap[largest]) == Ordering::Greater {
+                    largest = child;
+                }
+            }
+            if largest == pos {
+                break;
+            }
+            self.heap.swap(pos, largest);
+            pos = largest;
+        }
+    }
+
+    /// Number of items pushed so far
+    pub fn pushed(&self) -> u64 {
+        self.pushed
+    }
+
+    pub fn into_sorted(self) -> Vec<T> {
+        let mut heap = self.heap;
+        let (comparator, row) = (self.comparator, self.row);
+        heap.sort_by(|a, b| comparator.compare(row(&a.1), row(&b.1)).then(a.0.cmp(&b.0)));
+        heap.into_iter().map(|(_, item)| item).collect()
+    }
+}
+
+fn is_null(cell: &Cell) -> bool {
+    matches!(cell, Cell::Int(NULL_INT))
+}
+
+fn compare_cells(a: &Cell, b: &Cell, direction: SortDirection, nulls: NullsOrder) -> Ordering {
+    match (is_null(a), is_null(b)) {
+        (true, true) => return Ordering::Equal,
+        (true, false) => return if nulls == NullsOrder::First { Ordering::Less } else { Ordering::Greater },
+        (false, true) => return if nulls == NullsOrder::First { Ordering::Greater } else { Ordering::Less },
+        (false, false) => {},
+    }
+    let ordering = match (a, b) {
+        (Cell::Int(a), Cell::Int(b)) => a.cmp(b),
+        (Cell::Varchar(a), Cell::Varchar(b)) => a.cmp(b),
+        (Cell::Byte(a), Cell::Byte(b)) => a.cmp(b),
+        // rows of one schema always have the same types in a column
+        _ => Ordering::Equal,
+    };
+    match direction {
+        SortDirection::Asc => ordering,
+        SortDirection::Desc => ordering.reverse(),
+    }
+}
+
+impl<'db, S: Store> TableAccess<'db, S> {
+    /// Full scan, sorted by the keys (the first key is the primary sort key).
+    /// All rows are read before the first one is returned.
+    pub fn scan_sorted(&'db self, keys: &[SortKey]) -> Result<QueryResult<'db, (Record, Row)>, TableAccessError> {
+        let schema = self.table().schema().clone();
+        let comparator = RowComparator::new(&schema, keys)?;
+        let mut rows = self.find_all()?.rows()?;
+        comparator.sort(&mut rows, |(_, row)| row);
+        Ok(with_join_order(QueryResult::from_rows(rows, schema), keys))
+    }
+
+    /// The first `limit` rows of scan_sorted, without keeping all rows in memory
+    pub fn scan_sorted_limit(&'db self, keys: &[SortKey], limit: usize) -> Result<QueryResult<'db, (Record, Row)>, TableAccessError> {
+        let schema = self.table().schema().clone();
+        let comparator = RowComparator::new(&schema, keys)?;
+        let mut top = TopK::new(&comparator, limit, |(_, row): &(Record, Row)| row);
+        for res in self.find_all()? {
+            top.push(res?);
+        }
+        Ok(with_join_order(QueryResult::from_rows(top.into_sorted(), schema), keys))
+    }
+}
+
+// Sorted rows are input for a merge join, if they are ascending by the first key with NULLs first (the order of the index)
+fn with_join_order<'db>(result: QueryResult<'db, (Record, Row)>, keys: &[SortKey]) -> QueryResult<'db, (Record, Row)> {
+    let Some(key) = keys.first() else {
+        return result;
+    };
+    let Ok(index) = find_column_for_query(result.schema(), &key.column) else {
+        return result;
+    };
+    let nullable = result.schema().columns[index].col_type == ColumnType::Int;
+    if key.direction == SortDirection::Asc && (!nullable || key.nulls_order() == NullsOrder::First) {
+        result.with_ordered_by(index)
+    } else {
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::{Database, NULL_INT, sort::SortKey}, store::file_store::FileStore, table::{ColumnType, table::{Cell, Row}}};
+
+    #[test]
+    fn should_sort_by_multiple_columns() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        let table = db.create_table("persons", vec![("name", ColumnType::Varchar(20)), ("age", ColumnType::Int)]).unwrap();
+        let access = db.table_access(table).unwrap();
+        for (name, age) in [("Carol", 30), ("Alice", NULL_INT), ("Bob", 30), ("Alice", 25), ("Dave", 20)] {
+            access.insert(&Row::new(vec![Cell::Varchar(name.to_owned()), Cell::Int(age)])).unwrap();
+        }
+
+        let sorted = |keys: &[SortKey]| -> Vec<(String, i32)> {
+            access.scan_sorted(keys).unwrap().rows().unwrap().into_iter()
+                .map(|(_, row)| match row.cells().as_slice() {
+                    [Cell::Varchar(name), Cell::Int(age)] => (name.clone(), *age),
+                    other => panic!("Unexpected row {:?}", other),
+                })
+                .collect()
+        };
+        let names = |rows: Vec<(String, i32)>| rows.into_iter().map(|(name, age)| format!("{}{}", name, age)).collect::<Vec<_>>();
+        let null = NULL_INT;
+
+        assert_eq!(names(sorted(&[SortKey::desc("age"), SortKey::asc("name")])),
+            vec![format!("Alice{}", null), "Bob30".to_owned(), "Carol30".to_owned(), "Alice25".to_owned(), "Dave20".to_owned()]);
+        assert_eq!(names(sorted(&[SortKey::desc("age").nulls_last(), SortKey::desc("name")])),
+            vec!["Carol30".to_owned(), "Bob30".to_owned(), "Alice25".to_owned(), "Dave20".to_owned(), format!("Alice{}", null)]);
+        assert_eq!(names(sorted(&[SortKey::asc("name"), SortKey::asc("age").nulls_first()])),
+            vec![format!("Alice{}", null), "Alice25".to_owned(), "Bob30".to_owned(), "Carol30".to_owned(), "Dave20".to_owned()]);
+        // stable: insert order for equal keys
+        assert_eq!(names(sorted(&[SortKey::asc("age")]))[2..4], ["Carol30".to_owned(), "Bob30".to_owned()]);
+
+        assert!(access.scan_sorted(&[SortKey::asc("unknown")]).is_err());
+
+        for limit in 0..=6 {
+            let keys = [SortKey::asc("age"), SortKey::desc("name")];
+            let top: Vec<Row> = access.scan_sorted_limit(&keys, limit).unwrap().rows().unwrap().into_iter().map(|(_, row)| row).collect();
+            let all: Vec<Row> = access.scan_sorted(&keys).unwrap().rows().unwrap().into_iter().map(|(_, row)| row).take(limit).collect();
+            assert_eq!(top, all, "limit {}", limit);
+        }
+        assert_eq!(SortKey::desc("age").nulls_first().to_string(), "age DESC NULLS FIRST");
+    }
+}
@@ -0,0 +1,252 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::{
+    data::page::PageId,
+    database::{Database, DatabaseError},
+    store::{PageIterator, Store, sample::{self, SampleSize}},
+    table::{ColumnType, table::{Cell, Row, Table}},
+};
+
+// Results of analyze() are stored in the system table _statistics (t_id, row_count, pages).
+//
+// Every TableAccess created by Database::table_access counts inserted and deleted rows. The statistics of a table
+// are stale as soon as these changes reach stale_fraction of the row count of the last analyze.
+// With auto_analyze, a stale table is analyzed again on the next call of table_access.
+// The counters are only in memory: changes before the database was opened again are not counted.
+pub const STATISTICS_TABLE: &str = "_statistics";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StatisticsConfig {
+    pub stale_fraction: f64,
+    pub auto_analyze: bool,
+}
+
+impl Default for StatisticsConfig {
+    fn default() -> Self {
+        Self {
+            stale_fraction: 0.2,
+            auto_analyze: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TableStatistics {
+    pub row_count: i32,
+    pub pages: i32,
+    // inserted and deleted rows since the last analyze
+    pub changed_rows: u64,
+    pub stale: bool,
+}
+
+/// Inserted and deleted rows per table id. Shared by the database and its TableAccess instances.
+#[derive(Debug, Clone, Default)]
+pub struct RowChangeCounter {
+    changes: Rc<RefCell<HashMap<i32, u64>>>,
+}
+
+impl RowChangeCounter {
+    pub fn add(&self, t_id: i32, rows: u64) {
+        *self.changes.borrow_mut().entry(t_id).or_insert(0) += rows;
+    }
+
+    pub fn get(&self, t_id: i32) -> u64 {
+        self.changes.borrow().get(&t_id).copied().unwrap_or(0)
+    }
+
+    fn reset(&self, t_id: i32) {
+        self.changes.borrow_mut().remove(&t_id);
+    }
+}
+
+impl<S: Store> Database<S> {
+    pub fn with_statistics_config(mut self, config: StatisticsConfig) -> Self {
+        self.statistics_config = config;
+        self
+    }
+
+    /// Catalog and system tables are not tracked (analyze itself writes to _statistics)
+    pub(crate) fn tracks_row_changes(&self, table: &Table) -> bool {
+        Self::is_user_table(table.id(), table.name())
+    }
+
+    /// Counts rows and pages of the table and stores them in _statistics
+    pub fn analyze(&self, table_name: &str) -> Result<TableStatistics, DatabaseError> {
+        let table = self.read_table(table_name)?;
+
+        let mut throttle = self.throttle();
+        let layout = self.table_layout(&table)?;
+        let mut row_count = 0;
+        for page in PageIterator::try_new(&table, &self.store, &layout)? {
+            row_count += page?.live_rows() as i32;
+            throttle.consume(1, layout.page_size() as u64);
+        }
+        let pages = self.store.read_metadata(&layout, &table)?.number_of_pages();
+
+        self.store_statistics(&table, row_count, pages)
+    }
+
+    /// Like analyze, but only reads a sample of the pages (see store::sample) and extrapolates the row count.
+    /// Falls back to a full analyze if the sample doesn't contain a page.
+    pub fn analyze_sample(&self, table_name: &str, size: SampleSize, seed: u64) -> Result<TableStatistics, DatabaseError> {
+        size.validate().map_err(DatabaseError::UnknownError)?;
+        let table = self.read_table(table_name)?;
+        let layout = self.table_layout(&table)?;
+        let pages = self.store.read_metadata(&layout, &table)?.number_of_pages();
+        let sampler = sample::page_sampler(&self.store, &layout, &table, size, seed)?;
+
+        let mut throttle = self.throttle();
+        let (mut sampled_pages, mut sampled_rows) = (0, 0);
+        for page in PageIterator::try_new(&table, &self.store, &layout)?.with_sampler(sampler) {
+            sampled_rows += page?.live_rows();
+            sampled_pages += 1;
+            throttle.consume(1, layout.page_size() as u64);
+        }
+        if sampled_pages == 0 {
+            return self.analyze(table_name);
+        }
+
+        let row_count = (sampled_rows as f64 * pages as f64 / sampled_pages as f64).round() as i32;
+        self.store_statistics(&table, row_count, pages)
+    }
+
+    fn store_statistics(&self, table: &Table, row_count: i32, pages: PageId) -> Result<TableStatistics, DatabaseError> {
+        // the statistics are INT columns like the row count
+        let pages = i32::try_from(pages).unwrap_or(i32::MAX);
+        let access = self.table_access(self.statistics_table()?)?;
+        access.delete(access.find("t_id", Cell::Int(table.id()))?)?;
+        access.insert(&Row::new(vec![Cell::Int(table.id()), Cell::Int(row_count), Cell::Int(pages)]))?;
+        self.row_changes.reset(table.id());
+
+        Ok(TableStatistics { row_count, pages, changed_rows: 0, stale: false })
+    }
+
+    /// None if the table was never analyzed
+    pub fn statistics(&self, table_name: &str) -> Result<Option<TableStatistics>, DatabaseError> {
+        let table = self.read_table(table_name)?;
+        let access = self.table_access(self.statistics_table()?)?;
+        let rows = access.find("t_id", Cell::Int(table.id()))?.rows()?;
+
+        let Some((_, row)) = rows.first() else {
+            return Ok(None);
+        };
+        let (row_count, pages) = match row.cells().as_slice() {
+            [_, Cell::Int(row_count), Cell::Int(pages)] => (*row_count, *pages),
+            _ => return Err(DatabaseError::CorruptedDatabase(format!("Invalid row in '{}'", STATISTICS_TABLE))),
+        };
+
+        let changed_rows = self.row_changes.get(table.id());
+        Ok(Some(TableStatistics {
+            row_count,
+            pages,
+            changed_rows,
+            stale: self.is_stale(row_count, changed_rows),
+        }))
+    }
+
+    // called by table_access
+    pub(crate) fn auto_analyze(&self, table: &Table) -> Result<(), DatabaseError> {
+        let changed_rows = self.row_changes.get(table.id());
+        if !self.statistics_config.auto_analyze || changed_rows == 0 || !self.tracks_row_changes(table) {
+            return Ok(());
+        }
+
+        let stale = match self.statistics(table.name())? {
+            Some(statistics) => statistics.stale,
+            None => true,
+        };
+        if stale {
+            self.analyze(table.name())?;
+        }
+        Ok(())
+    }
+
+    fn is_stale(&self, row_count: i32, changed_rows: u64) -> bool {
+        changed_rows as f64 >= self.statistics_config.stale_fraction * row_count.max(1) as f64
+    }
+
+    fn statistics_table(&self) -> Result<Table, DatabaseError> {
+        match self.read_table(STATISTICS_TABLE) {
+            Err(DatabaseError::TableNotFound(_)) => Ok(self.create_system_table(STATISTICS_TABLE, vec![
+                ("t_id", ColumnType::Int, false, true),
+                ("row_count", ColumnType::Int, false, false),
+                ("pages", ColumnType::Int, false, false),
+            ]).map_err(|e| DatabaseError::UnknownError(e.to_string()))?),
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::{Database, statistics::StatisticsConfig}, store::{file_store::FileStore, sample::SampleSize}, table::{ColumnType, table::{Cell, Row}}};
+
+    #[test]
+    fn should_mark_statistics_stale_after_changes() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()))
+            .with_statistics_config(StatisticsConfig { stale_fraction: 0.5, auto_analyze: false });
+        db.drop_create().unwrap();
+        db.create_table("t", vec![("id", ColumnType::Int)]).unwrap();
+
+        let access = db.table_access(db.read_table("t").unwrap()).unwrap();
+        for i in 0..10 {
+            access.insert(&Row::new(vec![Cell::Int(i)])).unwrap();
+        }
+        assert_eq!(db.statistics("t").unwrap(), None);
+
+        let statistics = db.analyze("t").unwrap();
+        assert_eq!((statistics.row_count, statistics.pages), (10, 1));
+        assert!(!db.statistics("t").unwrap().unwrap().stale);
+
+        for i in 10..14 {
+            access.insert(&Row::new(vec![Cell::Int(i)])).unwrap();
+        }
+        access.delete(access.find("id", Cell::Int(0)).unwrap()).unwrap();
+        let statistics = db.statistics("t").unwrap().unwrap();
+        assert_eq!((statistics.row_count, statistics.changed_rows, statistics.stale), (10, 5, true));
+    }
+
+    #[test]
+    fn should_auto_analyze_stale_tables() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()))
+            .with_statistics_config(StatisticsConfig { stale_fraction: 0.2, auto_analyze: true });
+        db.drop_create().unwrap();
+        db.create_table("t", vec![("id", ColumnType::Int)]).unwrap();
+
+        {
+            let access = db.table_access(db.read_table("t").unwrap()).unwrap();
+            access.insert(&Row::new(vec![Cell::Int(1)])).unwrap();
+        }
+
+        // the next access analyzes the table
+        db.table_access(db.read_table("t").unwrap()).unwrap();
+        let statistics = db.statistics("t").unwrap().unwrap();
+        assert_eq!((statistics.row_count, statistics.changed_rows, statistics.stale), (1, 0, false));
+    }
+
+    #[test]
+    fn should_analyze_a_sample() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        db.create_table("t", vec![("id", ColumnType::Int)]).unwrap();
+
+        let access = db.table_access(db.read_table("t").unwrap()).unwrap();
+        for i in 0..10 {
+            access.insert(&Row::new(vec![Cell::Int(i)])).unwrap();
+        }
+
+        let statistics = db.analyze_sample("t", SampleSize::Fraction(1.0), 1).unwrap();
+        assert_eq!((statistics.row_count, statistics.pages), (10, 1));
+        assert_eq!(db.statistics("t").unwrap().unwrap().row_count, 10);
+
+        // no page in the sample: full analyze
+        access.insert(&Row::new(vec![Cell::Int(10)])).unwrap();
+        let statistics = db.analyze_sample("t", SampleSize::Fraction(0.0), 1).unwrap();
+        assert_eq!(statistics.row_count, 11);
+
+        assert!(db.analyze_sample("t", SampleSize::Fraction(-1.0), 1).is_err());
+    }
+}
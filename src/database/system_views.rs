@@ -0,0 +1,178 @@
+use std::rc::Rc;
+
+use crate::{
+    database::{Database, DatabaseError, table_access::{QueryResult, TableAccess}, virtual_table::{Constraint, VirtualRows, VirtualTable, VirtualTableError}},
+    store::{PageIterator, Store},
+    table::{Column, ColumnType, TableSchema, identifier::Identifier, table::{Cell, Row}},
+};
+
+// System views are virtual tables: they have a schema, but no pages.
+// Their rows are computed from the runtime state every time they are scanned.
+// In SQL, they are found by the table lookup of a query like a registered virtual table (SELECT * FROM _stats_tables),
+// the rows are computed once when the query starts.
+pub const STATS_TABLES: &str = "_stats_tables";
+pub const STATS_BUFFER_POOL: &str = "_stats_buffer_pool";
+pub const LOCKS: &str = "_locks";
+pub const ACTIVE_TRANSACTIONS: &str = "_active_transactions";
+
+pub fn is_system_view(name: &str) -> bool {
+    view_schema(name).is_some()
+}
+
+pub fn view_schema(name: &str) -> Option<TableSchema> {
+    let schema = match name {
+        STATS_TABLES => TableSchema::of([
+            Column::new(1, "t_id", ColumnType::Int),
+            Column::new(2, "name", ColumnType::Varchar(512)),
+            Column::new(3, "pages", ColumnType::Int),
+            Column::new(4, "rows", ColumnType::Int),
+        ]),
+        STATS_BUFFER_POOL => TableSchema::of([
+            Column::new(1, "t_id", ColumnType::Int),
+            Column::new(2, "page_id", ColumnType::Int),
+            Column::new(3, "dirty", ColumnType::Byte),
+        ]),
+        LOCKS => TableSchema::of([
+            Column::new(1, "t_id", ColumnType::Int),
+            Column::new(2, "page_id", ColumnType::Int),
+            Column::new(3, "mode", ColumnType::Byte),
+        ]),
+        ACTIVE_TRANSACTIONS => TableSchema::of([
+            Column::new(1, "tx_id", ColumnType::Int),
+            Column::new(2, "state", ColumnType::Byte),
+        ]),
+        _ => return None,
+    };
+
+    Some(schema)
+}
+
+impl<S: Store> Database<S> {
+    /// Scans a system view. The result behaves like any other QueryResult (filter, rows, ...).
+    pub fn system_view(&self, name: &str) -> Result<QueryResult<'_, Row>, DatabaseError> {
+        let schema = view_schema(name)
+            .ok_or_else(|| DatabaseError::TableNotFound(name.to_owned()))?;
+
+        let rows = match name {
+            STATS_TABLES => self.table_stats()?,
+            STATS_BUFFER_POOL => self.store.buffer_pool_pages().into_iter()
+                .map(|(t_id, page_id, dirty)| Row::new(vec![Cell::Int(t_id), Cell::Int(i32::try_from(page_id).unwrap_or(i32::MAX)), Cell::Byte(dirty as u8)]))
+                .collect(),
+            // There is no lock manager and no transactions yet.
+            // The views already exist, so that callers can rely on their schema.
+            _ => Vec::new(),
+        };
+
+        Ok(QueryResult::from_rows(rows, schema))
+    }
+
+    /// The system view as a virtual table for the SQL layer, None if there is no system view with the name
+    pub fn system_view_table(&self, name: &str) -> Result<Option<Rc<dyn VirtualTable>>, DatabaseError> {
+        let name = Identifier::normalize(name);
+        if !is_system_view(&name) {
+            return Ok(None);
+        }
+        let result = self.system_view(&name)?;
+        let schema = result.schema().clone();
+        Ok(Some(Rc::new(SystemViewRows { schema, rows: result.rows()? })))
+    }
+
+    fn table_stats(&self) -> Result<Vec<Row>, DatabaseError> {
+        let table_access = TableAccess::new(self.table_instance(), &self.store, &self.layout);
+        let table_rows = table_access.find_all()?.rows()?;
+
+        let mut stats = Vec::new();
+        for (_, row) in table_rows {
+            let (t_id, name) = match row.cells().as_slice() {
+                [Cell::Int(t_id), Cell::Varchar(name)] => (*t_id, name.clone()),
+                _ => return Err(DatabaseError::CorruptedDatabase("Invalid row in 'tables' table".to_owned())),
+            };
+
+            let table = self.read_table(&name)?;
+            let layout = self.table_layout(&table)?;
+            let pages = self.store.read_metadata(&layout, &table)?.number_of_pages();
+            // from the slots of the pages, the rows are not deserialized
+            let mut rows = 0;
+            for page in PageIterator::try_new(&table, &self.store, &layout)? {
+                rows += page?.live_rows();
+            }
+
+            stats.push(Row::new(vec![
+                Cell::Int(t_id),
+                Cell::Varchar(name),
+                Cell::Int(i32::try_from(pages).unwrap_or(i32::MAX)),
+                Cell::Int(i32::try_from(rows).unwrap_or(i32::MAX)),
+            ]));
+        }
+
+        Ok(stats)
+    }
+}
+
+// rows of a system view at the time of the lookup
+struct SystemViewRows {
+    schema: TableSchema,
+    rows: Vec<Row>,
+}
+
+impl VirtualTable for SystemViewRows {
+    fn schema(&self) -> TableSchema {
+        self.schema.clone()
+    }
+
+    fn scan(&self, _constraints: &[Constraint]) -> Result<VirtualRows<'_>, VirtualTableError> {
+        Ok(Box::new(self.rows.iter().cloned().map(Ok)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::{Database, DatabaseError, system_views::{LOCKS, STATS_TABLES}}, store::file_store::FileStore, table::{ColumnType, table::{Cell, Row}}};
+
+    #[test]
+    fn should_report_pages_and_rows_of_tables() {
+        let base_path = tempfile::tempdir().unwrap();
+        let store = FileStore::new(base_path.path());
+        let db = Database::new_with_store("test_db", store);
+        db.drop_create().unwrap();
+
+        let table = db.create_table("persons", vec![
+            ("id", ColumnType::Int),
+            ("name", ColumnType::Varchar(255)),
+        ]).unwrap();
+
+        let access = db.table_access(table).unwrap();
+        access.insert(&Row::new(vec![Cell::Int(1), Cell::Varchar("Alice".to_owned())])).unwrap();
+        access.insert(&Row::new(vec![Cell::Int(2), Cell::Varchar("Bob".to_owned())])).unwrap();
+        // the deleted row is not counted
+        access.insert(&Row::new(vec![Cell::Int(3), Cell::Varchar("Carol".to_owned())])).unwrap();
+        access.delete(access.find("id", Cell::Int(3)).unwrap()).unwrap();
+
+        let stats = db.system_view(STATS_TABLES).unwrap()
+            .filter(|row| row.cells()[1] == Cell::Varchar("persons".to_owned()))
+            .rows().unwrap();
+
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].cells()[2], Cell::Int(1));
+        assert_eq!(stats[0].cells()[3], Cell::Int(2));
+
+        // catalog tables are listed as well
+        let all_stats = db.system_view(STATS_TABLES).unwrap().rows().unwrap();
+        assert_eq!(all_stats.len(), 5);
+    }
+
+    #[test]
+    fn should_return_empty_views_without_runtime_state() {
+        let base_path = tempfile::tempdir().unwrap();
+        let store = FileStore::new(base_path.path());
+        let db = Database::new_with_store("test_db", store);
+        db.drop_create().unwrap();
+
+        let locks = db.system_view(LOCKS).unwrap();
+        assert_eq!(locks.schema().columns.len(), 3);
+        assert_eq!(locks.rows().unwrap().len(), 0);
+
+        let unknown = db.system_view("_unknown");
+        assert!(matches!(unknown, Err(DatabaseError::TableNotFound(_))));
+    }
+}
@@ -1,15 +1,22 @@
-use std::{cell::RefCell, collections::HashMap};
+use std::{cell::RefCell, cmp::Ordering, collections::{HashMap, HashSet, VecDeque}, iter::Peekable, rc::Rc};
 
 use thiserror::Error;
 
-use crate::{data::page::{PageDataLayout, PageError, Record}, store::{IndexedRowIterator, PageIterator, PageRowIterator, Store}, table::{Column, TableSchema, table::{Cell, Row, RowValidationError, Table}}, tree::store::BTreeStore};
+use crate::{data::page::{Page, PageDataLayout, PageError, PageId, Record, RecordIterator}, database::{NULL_INT, blob, compaction::CompactionHandoff, fuzzy::FuzzyMatch, statistics::RowChangeCounter, table_snapshot::TableSnapshot, write_pipeline::{PendingInsert, WritePipeline, WriteStage}}, store::{IndexedRowIterator, PageIterator, PageRowIterator, ReadConsistency, Store, StoreError, index_position, index_value, page_guard::PageGuard, row_batch::RowBatch, sample::{self, SampleSize}, bounding_box::BoundingBox}, table::{Column, ColumnType, TableSchema, identifier::Identifier, table::{Cell, Point, Row, RowValidationError, Table}}, tree::store::BTreeStore};
 
 pub struct TableAccess<'db, S: ?Sized> {
     table: Table,
     // Having BTreeStore as a normal ref would fore TableAccess to be mutable everywhere
     indexed_columns: Vec<(i32, RefCell<BTreeStore>)>,
     store: &'db S,
-    layout: &'db PageDataLayout,
+    // owned, every table can have its own page size (see Database::table_layout)
+    layout: PageDataLayout,
+    // inserted and deleted rows are counted for the statistics (see Database::statistics)
+    row_changes: Option<RowChangeCounter>,
+    // hooks of the insert path (see database/write_pipeline.rs)
+    write_pipeline: WritePipeline,
+    // the pages the background compaction has found, compacted after each write (see database/compaction.rs)
+    compaction: Option<CompactionHandoff>,
     #[cfg(test)]
     index_used: RefCell<Vec<i32>>, // just values from find clause
 }
@@ -24,6 +31,17 @@ pub enum TableAccessError {
     UpdateRowsError(String),
     #[error("TableAccessError - delete error: {0}")]
     DeleteRowsError(String),
+    #[error("TableAccessError - quota exceeded: {0}")]
+    QuotaExceeded(String),
+    // a hook of the write pipeline rejected the row
+    #[error("TableAccessError - insert rejected at {stage}: {reason}")]
+    InsertRejected { stage: WriteStage, reason: String },
+    // row: number of the rejected row (starting at 1), the rows before it are inserted
+    #[error("TableAccessError - row {row} not inserted ({} rows before it are inserted): {error}", row - 1)]
+    InsertManyError { row: usize, #[source] error: Box<TableAccessError> },
+    // update_versioned: the row was changed since the caller read it
+    #[error("TableAccessError - stale row: version {found} instead of {expected}")]
+    StaleRow { expected: i32, found: i32 },
 }
 
 struct UpdateIndexCommand {
@@ -64,38 +82,156 @@ impl UpdateIndexCommand {
 
 }
 
+// How an updated row that doesn't fit into its page any more gets to another page
+enum Relocation {
+    // moved with a forwarding pointer in its home slot, which keeps the address of the row
+    Forward((PageId, usize)),
+    // inserted as new row, the indexes point to the new place (if there is no space for a forwarding pointer)
+    Reinsert { stale_home: Option<(PageId, usize)> },
+}
+
 
 pub struct QueryResult<'db, I> {
-    row_iter: Box<dyn Iterator<Item = I> +'db>,
+    row_iter: Box<dyn Iterator<Item = Result<I, TableAccessError>> +'db>,
     schema: TableSchema,
+    // index of the column the rows are ascending by (NULLs first), if known
+    ordered_by: Option<usize>,
 }
 
 impl<'db, I: 'db> QueryResult<'db, I> {
-    pub fn rows(self) -> Vec<I> {
-        self.row_iter.into_iter().collect()
+    /// For results that are not backed by pages (e.g. system views)
+    pub fn from_rows(rows: Vec<I>, schema: TableSchema) -> Self {
+        QueryResult {
+            row_iter: Box::new(rows.into_iter().map(Ok)),
+            schema,
+            ordered_by: None,
+        }
+    }
+
+    pub(crate) fn from_iter<T: Iterator<Item = Result<I, TableAccessError>> + 'db>(iter: T, schema: TableSchema) -> Self {
+        QueryResult {
+            row_iter: Box::new(iter),
+            schema,
+            ordered_by: None,
+        }
+    }
+
+    /// Fails with the first error that occurred while reading the rows (e.g. a page that cannot be read)
+    pub fn rows(self) -> Result<Vec<I>, TableAccessError> {
+        self.row_iter.collect()
     }
 
     pub fn schema(&self) -> &TableSchema {
         &self.schema
     }
 
-    pub fn filter<F: FnMut(&I) -> bool + 'db>(self, f: F) -> QueryResult<'db, I> {
-        let iter = self.row_iter.filter(f);
+    pub fn ordered_by(&self) -> Option<usize> {
+        self.ordered_by
+    }
+
+    /// Marks the rows as ascending by the column (NULLs first), e.g. for a merge join
+    pub(crate) fn with_ordered_by(mut self, col_index: usize) -> Self {
+        self.ordered_by = Some(col_index);
+        self
+    }
+
+    /// Only reads until the first row (and its page)
+    pub fn first(mut self) -> Result<Option<I>, TableAccessError> {
+        self.row_iter.next().transpose()
+    }
+
+    pub fn any(self) -> Result<bool, TableAccessError> {
+        Ok(self.first()?.is_some())
+    }
+
+    /// Reads all rows, so the result can be the input of several queries (like a CTE that is used more than once):
+    ///   let recent = orders.find_all()?.filter(...).materialize()?;
+    ///   persons.find_all()?.hash_join(recent.result(), "id", "person_id")?;
+    /// A result that is used once is passed on as it is, its rows are streamed into the next query.
+    pub fn materialize(self) -> Result<MaterializedResult<I>, TableAccessError> {
+        let ordered_by = self.ordered_by;
+        let schema = self.schema.clone();
+        Ok(MaterializedResult { rows: Rc::new(self.rows()?), schema, ordered_by })
+    }
+
+    pub fn filter<F: FnMut(&I) -> bool + 'db>(self, mut f: F) -> QueryResult<'db, I> {
+        // errors are never filtered out, so that rows() can report them
+        let iter = self.row_iter.filter(move |res| res.as_ref().map(&mut f).unwrap_or(true));
         QueryResult { 
             row_iter: Box::new(iter),
             schema: self.schema,
+            ordered_by: self.ordered_by,
         }
     }
 }
 
+/// Rows of a QueryResult that were read once (see QueryResult::materialize)
+pub struct MaterializedResult<I> {
+    rows: Rc<Vec<I>>,
+    schema: TableSchema,
+    ordered_by: Option<usize>,
+}
+
+impl<I: Clone> MaterializedResult<I> {
+    /// A new result over the rows, as often as needed
+    pub fn result<'db>(&self) -> QueryResult<'db, I> where I: 'db {
+        let rows = self.rows.clone();
+        QueryResult {
+            row_iter: Box::new((0..rows.len()).map(move |i| Ok(rows[i].clone()))),
+            schema: self.schema.clone(),
+            ordered_by: self.ordered_by,
+        }
+    }
+
+    pub fn schema(&self) -> &TableSchema {
+        &self.schema
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+}
+
+/// Items of a QueryResult that can be inserted into a table (TableAccess::insert_from)
+pub trait IntoRow {
+    fn into_row(self) -> Row;
+}
+
+impl IntoRow for Row {
+    fn into_row(self) -> Row {
+        self
+    }
+}
+
+impl IntoRow for (Record, Row) {
+    fn into_row(self) -> Row {
+        self.1
+    }
+}
+
+// Rows are loaded lazily while iterating, rows() loads all of them at once
+impl<'db, I: 'db> IntoIterator for QueryResult<'db, I> {
+    type Item = Result<I, TableAccessError>;
+    type IntoIter = Box<dyn Iterator<Item = Result<I, TableAccessError>> + 'db>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.row_iter
+    }
+}
+
 impl<'db> QueryResult<'db, (Record, Row)> {
     pub fn from_indexes<S: Store>(
         index_iter: IndexedRowIterator<'_, S>,
         schema: TableSchema,
     ) -> QueryResult<'_, (Record, Row)> {
         QueryResult {
-            row_iter: Box::new(index_iter),
-            schema: schema.clone()
+            row_iter: Box::new(index_iter.map(|res| res.map_err(TableAccessError::from))),
+            schema: schema.clone(),
+            ordered_by: None,
         }
     }
 
@@ -105,13 +241,17 @@ impl<'db> QueryResult<'db, (Record, Row)> {
     ) -> QueryResult<'_, (Record, Row)> {
 
         let schema_iter = schema.clone();
-        let i = page_iter.flat_map(move |p| {
-            PageRowIterator::new(p, schema_iter.clone())
+        let i = page_iter.flat_map(move |p| -> Box<dyn Iterator<Item = Result<(Record, Row), TableAccessError>>> {
+            match p {
+                Ok(page) => Box::new(PageRowIterator::new(page, schema_iter.clone()).map(|res| res.map_err(TableAccessError::from))),
+                Err(err) => Box::new(std::iter::once(Err(err.into()))),
+            }
         });
 
         QueryResult {
             row_iter: Box::new(i),
-            schema: schema.clone()
+            schema: schema.clone(),
+            ordered_by: None,
         }
     }
 
@@ -120,62 +260,270 @@ impl<'db> QueryResult<'db, (Record, Row)> {
         inner_query: QueryResult<'db, (Record, Row)>,
         this_join_column: &str, 
         that_join_column: &str
-    ) -> Result<QueryResult<'db, Row>, TableAccessError> {
-        let that_col_index = find_column_for_query(&inner_query.schema, that_join_column)?;
-        let this_col_index = find_column_for_query(&self.schema, this_join_column)?;
-
-        // 1. check if type is equal
-        let this_col_type = self.schema.columns[this_col_index].col_type.raw_type();
-        let that_col_type = inner_query.schema.columns[that_col_index].col_type.raw_type();
-
-        if this_col_type != that_col_type {
-            return Err(TableAccessError::LoadRowsError(format!("Join columns have different types: {} vs {}", this_col_type, that_col_type)));
-        }
+) -> Result<QueryResult<'db, Row>, TableAccessError> {
+        let (this_col_index, that_col_index) = join_columns(&self.schema, &inner_query.schema, this_join_column, that_join_column)?;
         
         let mut inner_table_hashes = HashMap::new();
         
         let inner_schema = inner_query.schema.clone();
-        for (_, row) in inner_query.rows().into_iter() {
+        for (_, row) in inner_query.rows()?.into_iter() {
             let join_key = row.cells()[that_col_index].clone();
             inner_table_hashes.entry(join_key)
                 .or_insert_with(Vec::new)
                 .push(row);
         }
 
-        let join_iter = self.row_iter.flat_map(move |(_, row)| {            
+        let join_iter = self.row_iter.flat_map(move |res| {            
             let mut result = Vec::new();
+            let row = match res {
+                Ok((_, row)) => row,
+                Err(err) => return vec![Err(err)].into_iter(),
+            };
             if let Some(join_tuples) = inner_table_hashes.get(&row.cells()[this_col_index]) {
                 for inner_row in join_tuples {
                     let joined_cells: Vec<Cell> = row.cells().iter()
                     .chain(inner_row.cells().iter())
                     .cloned()
                     .collect();
-                    result.push(Row::new(joined_cells));
+                    result.push(Ok(Row::new(joined_cells)));
                 }
             }
             result.into_iter()            
         });
 
-        let joined_cols: Vec<Column> = self.schema.columns
-            .iter()
-            .chain(inner_schema.columns.iter())
-            .map(|col| (*col).clone())
-            .collect();
+        Ok(QueryResult {
+            row_iter: Box::new(join_iter),
+            schema: joined_schema(&self.schema, &inner_schema),
+            ordered_by: None,
+        })
+    }
 
-        let joined_schema = TableSchema::new(joined_cols);
+    /// Join of two inputs that are both ascending by their join column (see ordered_by), e.g. index scans (TableAccess::scan_by_index).
+    /// Both inputs are streamed, only the inner rows of the current join key are kept in memory (unlike hash_join,
+    /// which builds a hash table of the whole inner input). Fails when an input turns out not to be ordered.
+    /// The result is ordered by the join column of this (outer) input.
+    pub fn merge_join(
+        self,
+        inner_query: QueryResult<'db, (Record, Row)>,
+        this_join_column: &str,
+        that_join_column: &str
+    ) -> Result<QueryResult<'db, Row>, TableAccessError> {
+        let (this_col_index, that_col_index) = join_columns(&self.schema, &inner_query.schema, this_join_column, that_join_column)?;
+        let schema = joined_schema(&self.schema, &inner_query.schema);
+
+        let join_iter = MergeJoinIterator {
+            outer: self.row_iter,
+            inner: inner_query.row_iter.peekable(),
+            outer_col: this_col_index,
+            inner_col: that_col_index,
+            last_outer_key: None,
+            last_inner_key: None,
+            group_key: None,
+            group: Vec::new(),
+            pending: VecDeque::new(),
+        };
 
         Ok(QueryResult {
             row_iter: Box::new(join_iter),
-            schema: joined_schema,
+            schema,
+            ordered_by: Some(this_col_index),
         })
     }
+
+    /// Rows of this result with at least one matching row in the inner result (`WHERE col IN (SELECT ...)`).
+    /// Only the join keys of the inner result are kept in memory, every row is returned at most once and NULL never matches.
+    pub fn semi_join(
+        self,
+        inner_query: QueryResult<'db, (Record, Row)>,
+        this_join_column: &str,
+        that_join_column: &str
+    ) -> Result<QueryResult<'db, (Record, Row)>, TableAccessError> {
+        self.filter_by_keys(inner_query, this_join_column, that_join_column, true)
+    }
+
+    /// Rows of this result without a matching row in the inner result (`WHERE NOT EXISTS (...)`), rows with NULL included
+    pub fn anti_join(
+        self,
+        inner_query: QueryResult<'db, (Record, Row)>,
+        this_join_column: &str,
+        that_join_column: &str
+    ) -> Result<QueryResult<'db, (Record, Row)>, TableAccessError> {
+        self.filter_by_keys(inner_query, this_join_column, that_join_column, false)
+    }
+
+    fn filter_by_keys(
+        self,
+        inner_query: QueryResult<'db, (Record, Row)>,
+        this_join_column: &str,
+        that_join_column: &str,
+        keep_matches: bool
+    ) -> Result<QueryResult<'db, (Record, Row)>, TableAccessError> {
+        let (this_col_index, that_col_index) = join_columns(&self.schema, &inner_query.schema, this_join_column, that_join_column)?;
+
+        let mut keys = HashSet::new();
+        for res in inner_query {
+            let (_, row) = res?;
+            let key = &row.cells()[that_col_index];
+            if !is_null(key) {
+                keys.insert(key.clone());
+            }
+        }
+
+        Ok(self.filter(move |(_, row)| {
+            let key = &row.cells()[this_col_index];
+            (!is_null(key) && keys.contains(key)) == keep_matches
+        }))
+    }
+
+    /// Merge join if both inputs are ordered by their join column, otherwise hash join
+    pub fn join(
+        self,
+        inner_query: QueryResult<'db, (Record, Row)>,
+        this_join_column: &str,
+        that_join_column: &str
+    ) -> Result<QueryResult<'db, Row>, TableAccessError> {
+        let this_ordered = self.ordered_by.is_some() && self.ordered_by == find_column_for_query(&self.schema, this_join_column).ok();
+        let that_ordered = inner_query.ordered_by.is_some() && inner_query.ordered_by == find_column_for_query(&inner_query.schema, that_join_column).ok();
+        if this_ordered && that_ordered {
+            self.merge_join(inner_query, this_join_column, that_join_column)
+        } else {
+            self.hash_join(inner_query, this_join_column, that_join_column)
+        }
+    }
+}
+
+fn join_columns(this: &TableSchema, that: &TableSchema, this_join_column: &str, that_join_column: &str) -> Result<(usize, usize), TableAccessError> {
+    let that_col_index = find_column_for_query(that, that_join_column)?;
+    let this_col_index = find_column_for_query(this, this_join_column)?;
+
+    // 1. check if type is equal
+    let this_col_type = this.columns[this_col_index].col_type.raw_type();
+    let that_col_type = that.columns[that_col_index].col_type.raw_type();
+
+    if this_col_type != that_col_type {
+        return Err(TableAccessError::LoadRowsError(format!("Join columns have different types: {} vs {}", this_col_type, that_col_type)));
+    }
+    Ok((this_col_index, that_col_index))
+}
+
+fn joined_schema(this: &TableSchema, that: &TableSchema) -> TableSchema {
+    let joined_cols: Vec<Column> = this.columns
+        .iter()
+        .chain(that.columns.iter())
+        .map(|col| (*col).clone())
+        .collect();
+
+    // both schemas have columns
+    TableSchema { columns: joined_cols }
+}
+
+fn is_null(cell: &Cell) -> bool {
+    matches!(cell, Cell::Int(NULL_INT))
+}
+
+// Order of join keys for the merge join: like the B-tree, NULL_INT is the smallest Int
+fn compare_join_keys(a: &Cell, b: &Cell) -> Ordering {
+    match (a, b) {
+        (Cell::Int(a), Cell::Int(b)) => a.cmp(b),
+        (Cell::Varchar(a), Cell::Varchar(b)) => a.cmp(b),
+        (Cell::Byte(a), Cell::Byte(b)) => a.cmp(b),
+        // join columns have the same type (checked in join_columns)
+        _ => Ordering::Equal,
+    }
+}
+
+type RowIter<'db> = Box<dyn Iterator<Item = Result<(Record, Row), TableAccessError>> + 'db>;
+
+struct MergeJoinIterator<'db> {
+    outer: RowIter<'db>,
+    inner: Peekable<RowIter<'db>>,
+    outer_col: usize,
+    inner_col: usize,
+    last_outer_key: Option<Cell>,
+    last_inner_key: Option<Cell>,
+    // inner rows with the key of the current outer row
+    group_key: Option<Cell>,
+    group: Vec<Row>,
+    pending: VecDeque<Row>,
+}
+
+impl MergeJoinIterator<'_> {
+    fn check_order(last: &mut Option<Cell>, key: &Cell, side: &str) -> Result<(), TableAccessError> {
+        if let Some(last_key) = last.as_ref()
+            && compare_join_keys(last_key, key) == Ordering::Greater {
+            return Err(TableAccessError::LoadRowsError(format!("Merge join: {} input is not ordered by the join column ({:?} after {:?})", side, key, last_key)));
+        }
+        *last = Some(key.clone());
+        Ok(())
+    }
+
+    // skips inner rows with a smaller key and collects the ones with the key
+    fn advance_inner(&mut self, key: &Cell) -> Result<(), TableAccessError> {
+        self.group.clear();
+        while let Some(next) = self.inner.peek() {
+            let inner_key = match next {
+                Ok((_, row)) => row.cells()[self.inner_col].clone(),
+                Err(_) => return match self.inner.next() {
+                    Some(Err(err)) => Err(err),
+                    _ => Ok(()),
+                },
+            };
+            Self::check_order(&mut self.last_inner_key, &inner_key, "inner")?;
+            match compare_join_keys(&inner_key, key) {
+                Ordering::Greater => break,
+                ordering => {
+                    if let Some(Ok((_, row))) = self.inner.next()
+                        && ordering == Ordering::Equal {
+                        self.group.push(row);
+                    }
+                }
+            }
+        }
+        self.group_key = Some(key.clone());
+        Ok(())
+    }
+}
+
+impl Iterator for MergeJoinIterator<'_> {
+    type Item = Result<Row, TableAccessError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(row) = self.pending.pop_front() {
+                return Some(Ok(row));
+            }
+
+            let outer_row = match self.outer.next()? {
+                Ok((_, row)) => row,
+                Err(err) => return Some(Err(err)),
+            };
+            let key = outer_row.cells()[self.outer_col].clone();
+            if let Err(err) = Self::check_order(&mut self.last_outer_key, &key, "outer") {
+                return Some(Err(err));
+            }
+
+            if self.group_key.as_ref() != Some(&key)
+                && let Err(err) = self.advance_inner(&key) {
+                return Some(Err(err));
+            }
+
+            for inner_row in self.group.iter() {
+                let joined_cells: Vec<Cell> = outer_row.cells().iter()
+                    .chain(inner_row.cells().iter())
+                    .cloned()
+                    .collect();
+                self.pending.push_back(Row::new(joined_cells));
+            }
+        }
+    }
 }
 
-fn find_column_for_query(schema: &TableSchema, col_name: &str) -> Result<usize, TableAccessError> {
+pub(crate) fn find_column_for_query(schema: &TableSchema, col_name: &str) -> Result<usize, TableAccessError> {
     let mut col_index = 0;
     let mut col_found = false;
+    let normalized_name = Identifier::normalize(col_name);
     for (index, col) in schema.columns.iter().enumerate() {
-        if col.name == col_name.trim() {
+        if col.name == normalized_name {
             col_index = index;
             col_found = true;
             break;
@@ -206,6 +554,19 @@ impl From<PageError> for TableAccessError {
             PageError::InsertRowError => TableAccessError::InsertRowError("Failed to insert row into page.".to_string()),
             PageError::ReadPageError => TableAccessError::LoadRowsError("Failed to read page.".to_string()),
             PageError::UpdateRecordError => TableAccessError::LoadRowsError("Failed to update page.".to_string()),
+            PageError::ChecksumMismatch => TableAccessError::LoadRowsError(err.to_string()),
+            PageError::LayoutMismatch(_) => TableAccessError::LoadRowsError(err.to_string()),
+            PageError::UnknownFormat(_) => TableAccessError::LoadRowsError(err.to_string()),
+            PageError::NotOrderable => TableAccessError::UpdateRowsError(err.to_string()),
+        }
+    }
+}
+
+impl From<StoreError> for TableAccessError {
+    fn from(err: StoreError) -> Self {
+        match err {
+            StoreError::QuotaExceeded(msg) => TableAccessError::QuotaExceeded(msg),
+            err => TableAccessError::LoadRowsError(err.to_string()),
         }
     }
 }
@@ -216,34 +577,214 @@ impl From<RowValidationError> for TableAccessError {
     }
 }
 
+// Value for columns that are missing in insert_map.
+// Int uses the NULL sentinel, the other types don't have NULL yet and use their 'zero' value.
+// Columns of an INSERT ... SELECT: same number and types, a varchar may have another length (the rows are validated)
+fn check_insert_schema(source: &TableSchema, target: &TableSchema) -> Result<(), TableAccessError> {
+    if source.columns.len() != target.columns.len() {
+        return Err(TableAccessError::InsertRowError(
+            format!("Query returns {} columns, but the table has {}", source.columns.len(), target.columns.len())
+        ));
+    }
+    for (source, target) in source.columns.iter().zip(target.columns.iter()) {
+        if source.col_type.raw_type() != target.col_type.raw_type() {
+            return Err(TableAccessError::InsertRowError(format!(
+                "Column '{}' is of type {}, but the query returns {} (column '{}')", target.name, target.col_type, source.col_type, source.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Packs the rows into new pages (e.g. for load_pages)
+pub(crate) fn pack_rows<'a, I: IntoIterator<Item = &'a Row>>(rows: I, schema: &TableSchema, layout: &PageDataLayout) -> Result<Vec<Page>, TableAccessError> {
+    let mut pages = Vec::new();
+    let mut page = Page::new(layout);
+    for row in rows {
+        let row_data = row.serialize_for(schema)
+            .map_err(|e| TableAccessError::InsertRowError(e.to_string()))?;
+        if !page.can_insert(&row_data) {
+            pages.push(std::mem::replace(&mut page, Page::new(layout)));
+        }
+        page.insert_record(row_data)?;
+    }
+    if page.num_rows() > 0 {
+        pages.push(page);
+    }
+    Ok(pages)
+}
+
+fn default_cell(col_type: &ColumnType) -> Cell {
+    match col_type {
+        ColumnType::Int => Cell::Int(NULL_INT),
+        ColumnType::Varchar(_) => Cell::Varchar(String::new()),
+        ColumnType::Byte => Cell::Byte(0),
+        ColumnType::Blob => Cell::Blob(Vec::new()),
+        ColumnType::Point => Cell::Point(Point(0.0, 0.0)),
+    }
+}
+
 impl<'db, S: Store> TableAccess<'db, S> {
     pub fn with_indexes(mut self, indexed_columns: Vec<(i32, RefCell<BTreeStore>)>) -> Self {
         self.indexed_columns = indexed_columns;
         self
     }
 
-    pub fn new(table: Table, store: &'db S, layout: &'db PageDataLayout) -> Self {
+    pub fn with_row_changes(mut self, row_changes: RowChangeCounter) -> Self {
+        self.row_changes = Some(row_changes);
+        self
+    }
+
+    pub fn with_write_pipeline(mut self, write_pipeline: WritePipeline) -> Self {
+        self.write_pipeline = write_pipeline;
+        self
+    }
+
+    pub fn with_compaction(mut self, compaction: CompactionHandoff) -> Self {
+        self.compaction = Some(compaction);
+        self
+    }
+
+    pub fn new(table: Table, store: &'db S, layout: &PageDataLayout) -> Self {
         Self { 
             table,
             store,
-            layout,
+            layout: layout.clone(),
             indexed_columns: Vec::new(),
+            row_changes: None,
+            write_pipeline: WritePipeline::default(),
+            compaction: None,
             #[cfg(test)]
             index_used: RefCell::new(Vec::new()),
          }
     }
 
-    /// Drop the table by deleting its underlying file
+    pub fn table(&self) -> &Table {
+        &self.table
+    }
+
+    fn compact_found_pages(&self) {
+        if let Some(compaction) = &self.compaction {
+            compaction.compact(self.store, &self.layout, &self.table);
+        }
+    }
+
+    fn count_row_changes(&self, rows: usize) {
+        if let Some(row_changes) = &self.row_changes {
+            row_changes.add(self.table.id(), rows as u64);
+        }
+    }
+
+    /// Ids of the columns with a (unique) index
+    pub(crate) fn indexed_column_ids(&self) -> Vec<i32> {
+        self.indexed_columns.iter().map(|(col_id, _)| *col_id).collect()
+    }
+
+    /// Points the index entries of the row at this position to it, after the row was moved there
+    /// outside of TableAccess (see database/upgrade.rs)
+    pub(crate) fn repoint_index(&self, page_id: PageId, slot_id: usize) -> Result<(), TableAccessError> {
+        let page = self.store.read_page(&self.layout, page_id, &self.table)?;
+        let record = page.read_slot(slot_id)
+            .ok_or_else(|| TableAccessError::UpdateRowsError(format!("No row at slot {} of page {}", slot_id, page_id)))?;
+        let row = Row::deserialize(record, self.table.schema()).map_err(StoreError::from)?;
+
+        let mut uic = UpdateIndexCommand::new();
+        for (col_idx, btree_idx) in self.column_index_to_btree_pointer_map()? {
+            let val = row.cells()[col_idx].expect_int("Indexed value must be of type Int")
+                .map_err(|e| TableAccessError::UpdateRowsError(e.to_string()))?;
+            uic.push_update((btree_idx, val, val));
+        }
+        self.update_index(page_id, slot_id, uic)
+    }
+
+    /// Always fails: deleting only the file would leave the table in the catalog, use Database::drop_table
     pub fn drop(&self) -> Result<(), TableAccessError> {
-        unimplemented!()
-        // std::fs::remove_file(&self.table.file_path())?;
-        // Ok(())
+        Err(TableAccessError::DeleteRowsError(format!("Table '{}' can only be dropped with Database::drop_table", self.table.name())))
     }
 
     /// Load all rows from all pages in the table
     pub fn find_all(&'db self) -> Result<QueryResult<'db, (Record, Row)>, TableAccessError> {
-        let page_iter = PageIterator::new(&self.table, self.store, self.layout);
-        Ok(QueryResult::new(page_iter, self.table.schema().clone()))
+        self.find_all_with(ReadConsistency::ReadCommitted)
+    }
+
+    /// Full scan with the given consistency, ReadUncommitted is a dirty read (see ReadConsistency)
+    pub fn find_all_with(&'db self, consistency: ReadConsistency) -> Result<QueryResult<'db, (Record, Row)>, TableAccessError> {
+        let page_iter = PageIterator::try_new(&self.table, self.store, &self.layout)?
+            .with_consistency(consistency);
+        Ok(self.with_blobs(QueryResult::new(page_iter, self.table.schema().clone())))
+    }
+
+    /// Rows of a random sample of the pages (see store::sample), the same seed returns the same pages
+    pub fn scan_sample(&'db self, size: SampleSize, seed: u64) -> Result<QueryResult<'db, (Record, Row)>, TableAccessError> {
+        size.validate().map_err(TableAccessError::LoadRowsError)?;
+        let sampler = sample::page_sampler(self.store, &self.layout, &self.table, size, seed)?;
+        let page_iter = PageIterator::try_new(&self.table, self.store, &self.layout)?
+            .with_sampler(sampler);
+        Ok(self.with_blobs(QueryResult::new(page_iter, self.table.schema().clone())))
+    }
+
+    /// Copy of the table that doesn't change anymore (see table_snapshot.rs)
+    pub fn snapshot(&self) -> Result<TableSnapshot, TableAccessError> {
+        Ok(TableSnapshot::copy(self.store, &self.layout, &self.table)?)
+    }
+
+    /// Full scan that returns the rows in columnar batches (for tight loops over single columns)
+    pub fn find_all_batches(&'db self) -> Result<Box<dyn Iterator<Item = Result<RowBatch, TableAccessError>> + 'db>, TableAccessError> {
+        let page_iter = PageIterator::try_new(&self.table, self.store, &self.layout)?;
+        let schema = self.table.schema().clone();
+
+        let batches = page_iter.flat_map(move |p| -> Box<dyn Iterator<Item = Result<RowBatch, TableAccessError>>> {
+            match p {
+                Ok(page) => {
+                    let mut rows = PageRowIterator::new(page, schema.clone());
+                    Box::new(std::iter::from_fn(move || rows.next_batch()).map(|res| res.map_err(TableAccessError::from)))
+                },
+                Err(err) => Box::new(std::iter::once(Err(err.into()))),
+            }
+        });
+        if !self.table.has_blobs() {
+            return Ok(Box::new(batches));
+        }
+        Ok(Box::new(batches.map(move |batch| {
+            let batch = batch?;
+            let mut resolved = RowBatch::new(self.table.schema(), batch.len());
+            for (record, row) in batch.into_rows() {
+                resolved.push(record, blob::resolve_blobs(self.store, &self.layout, &self.table, row)?)?;
+            }
+            Ok(resolved)
+        })))
+    }
+
+    // replaces the BLOB pointers of the rows read from the pages with the values (see blob.rs)
+    fn with_blobs(&'db self, query_result: QueryResult<'db, (Record, Row)>) -> QueryResult<'db, (Record, Row)> {
+        if !self.table.has_blobs() {
+            return query_result;
+        }
+        let schema = query_result.schema().clone();
+        QueryResult::from_iter(query_result.into_iter().map(move |res| {
+            let (record, row) = res?;
+            Ok((record, blob::resolve_blobs(self.store, &self.layout, &self.table, row)?))
+        }), schema)
+    }
+
+    /// Stops the scan at the first matching row, the remaining pages are not read.
+    /// Like find_all, it only sees the pages that existed when the scan started.
+    pub fn exists_where<F: FnMut(&Row) -> bool + 'db>(&'db self, pred: F) -> Result<bool, TableAccessError> {
+        Ok(self.first_where(pred)?.is_some())
+    }
+
+    pub fn first_where<F: FnMut(&Row) -> bool + 'db>(&'db self, mut pred: F) -> Result<Option<(Record, Row)>, TableAccessError> {
+        self.find_all()?.filter(move |(_, row)| pred(row)).first()
+    }
+
+    /// First row of the table (in page order)
+    pub fn first(&'db self) -> Result<Option<(Record, Row)>, TableAccessError> {
+        self.find_all()?.first()
+    }
+
+    /// true, if the table has at least one row
+    pub fn any(&'db self) -> Result<bool, TableAccessError> {
+        self.find_all()?.any()
     }
 
     pub fn find(&'db self, col_name: &str, cell: Cell) -> Result<QueryResult<'db, (Record, Row)>, TableAccessError> {
@@ -258,19 +799,31 @@ impl<'db, S: Store> TableAccess<'db, S> {
             // This will later return an Vec<(i32, i32)> for non unique indexes
             let res = self.indexed_columns[*btree_pointer].1.borrow().find(val)
                 .map_err(|e| TableAccessError::LoadRowsError(e.to_string()))?
+                .map(index_position)
+                .transpose()?
                 .map(|v| vec![v])
                 .unwrap_or_default();
 
-            let iter = IndexedRowIterator::new(&self.table, self.store, self.layout, res);
-            let qr = QueryResult::from_indexes(iter, self.table.schema().clone());
+            let iter = IndexedRowIterator::new(&self.table, self.store, &self.layout, res);
+            let qr = self.with_blobs(QueryResult::from_indexes(iter, self.table.schema().clone()));
 
             #[cfg(test)]
             self.index_used.borrow_mut().push(val);
         
             Ok(qr)
         } else {
-            let page_iter = PageIterator::new(&self.table, self.store, self.layout);
-            let qr = QueryResult::new(page_iter, self.table.schema().clone());
+            let mut page_iter = PageIterator::try_new(&self.table, self.store, &self.layout)?;
+            // pages whose min/max of the column doesn't include the value are skipped
+            if let Cell::Int(value) = cell
+                && let Some(filter) = self.store.read_zone_map(&self.table)?.and_then(|zones| zones.filter(col_index, value)) {
+                page_iter = page_iter.with_zone_filter(filter);
+            }
+            // and the pages whose bloom filter doesn't contain it
+            let column_id = self.table.schema().columns[col_index].id;
+            if let Some(probe) = self.store.read_bloom_filter(&self.table)?.and_then(|filters| filters.probe(column_id, &cell)) {
+                page_iter = page_iter.with_bloom_probe(probe);
+            }
+            let qr = self.with_blobs(QueryResult::new(page_iter, self.table.schema().clone()));
 
             Ok(qr.filter(move |(_, row)| {
                 row.cells()[col_index] == cell
@@ -278,12 +831,154 @@ impl<'db, S: Store> TableAccess<'db, S> {
         }
     }
 
+    /// Rows with from <= value <= to in the Int column. With an index on the column, only the leaves of the range
+    /// are read and the rows are ascending by the value, otherwise the table is scanned (in page order).
+    pub fn find_range(&'db self, col_name: &str, from: i32, to: i32) -> Result<QueryResult<'db, (Record, Row)>, TableAccessError> {
+        let col_index = find_column_for_query_by_cell(self.table.schema(), col_name, &Cell::Int(from))?;
+
+        if let Some(btree_pointer) = self.column_index_to_btree_pointer_map()?.get(&col_index) {
+            let positions = self.indexed_columns[*btree_pointer].1.borrow().find_range(from, to)
+                .map_err(|e| TableAccessError::LoadRowsError(e.to_string()))?
+                .into_iter()
+                .map(index_position)
+                .collect::<Result<Vec<_>, StoreError>>()?;
+
+            let iter = IndexedRowIterator::new(&self.table, self.store, &self.layout, positions);
+            let qr = self.with_blobs(QueryResult::from_indexes(iter, self.table.schema().clone()));
+            Ok(qr.with_ordered_by(col_index))
+        } else {
+            let page_iter = PageIterator::try_new(&self.table, self.store, &self.layout)?;
+            let qr = self.with_blobs(QueryResult::new(page_iter, self.table.schema().clone()));
+
+            Ok(qr.filter(move |(_, row)| {
+                matches!(row.cells()[col_index], Cell::Int(value) if from <= value && value <= to)
+            }))
+        }
+    }
+
+    /// The rows whose Varchar value in the column matches the fuzzy pattern (see database/fuzzy.rs), in page order.
+    /// The table is scanned, a FuzzyMatch of a TrigramIndex only saves comparisons.
+    pub fn find_fuzzy(&'db self, col_name: &str, fuzzy: FuzzyMatch) -> Result<QueryResult<'db, (Record, Row)>, TableAccessError> {
+        let col_index = find_column_for_query_by_cell(self.table.schema(), col_name, &Cell::Varchar(String::new()))?;
+
+        let page_iter = PageIterator::try_new(&self.table, self.store, &self.layout)?;
+        let qr = self.with_blobs(QueryResult::new(page_iter, self.table.schema().clone()));
+
+        Ok(qr.filter(move |(_, row)| {
+            matches!(&row.cells()[col_index], Cell::Varchar(value) if fuzzy.matches(value))
+        }))
+    }
+
+    /// The rows whose point in the column lies in the box (the border included).
+    /// Pages whose bounding box doesn't intersect it are not read (see store/bounding_box.rs).
+    pub fn within_bbox(&'db self, col_name: &str, bbox: BoundingBox) -> Result<QueryResult<'db, (Record, Row)>, TableAccessError> {
+        let col_index = find_column_for_query_by_cell(self.table.schema(), col_name, &Cell::Point(Point(0.0, 0.0)))?;
+
+        let mut page_iter = PageIterator::try_new(&self.table, self.store, &self.layout)?;
+        if let Some(filter) = self.store.read_bounding_boxes(&self.table)?.and_then(|boxes| boxes.filter(col_index, bbox)) {
+            page_iter = page_iter.with_bbox_filter(filter);
+        }
+        let qr = self.with_blobs(QueryResult::new(page_iter, self.table.schema().clone()));
+
+        Ok(qr.filter(move |(_, row)| {
+            matches!(&row.cells()[col_index], Cell::Point(point) if bbox.contains(point))
+        }))
+    }
+
+    /// The k rows whose point in the column is nearest to the point, the nearest first (equal distances in page order).
+    /// The pages are read in the order of the distance of their bounding box, the scan stops at the first page
+    /// that is farther away than the k-th row found so far. Without bounding boxes every page is read.
+    pub fn nearest(&'db self, col_name: &str, point: Point, k: usize) -> Result<QueryResult<'db, (Record, Row)>, TableAccessError> {
+        let col_index = find_column_for_query_by_cell(self.table.schema(), col_name, &Cell::Point(point))?;
+        let schema = self.table.schema().clone();
+        if k == 0 {
+            return Ok(QueryResult::from_rows(Vec::new(), schema));
+        }
+
+        let total_pages = self.store.read_metadata(&self.layout, &self.table)?.number_of_pages();
+        let boxes = self.store.read_bounding_boxes(&self.table)?;
+        // (squared distance, page id), the pages without rows are not read
+        let mut pages: Vec<(f64, PageId)> = (1..=total_pages)
+            .map(|page_id| (boxes.as_ref().map_or(0.0, |boxes| boxes.distance_squared(page_id, col_index, &point)), page_id))
+            .filter(|(distance, _)| distance.is_finite())
+            .collect();
+        pages.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+        // the nearest rows so far, ascending by their squared distance
+        let mut nearest: Vec<(f64, Record, Row)> = Vec::with_capacity(k + 1);
+        for (page_distance, page_id) in pages {
+            if nearest.len() == k && nearest.last().is_some_and(|(distance, _, _)| *distance <= page_distance) {
+                break;
+            }
+            let page = self.store.read_page(&self.layout, page_id, &self.table)?;
+            for res in PageRowIterator::new(page, schema.clone()) {
+                let (record, row) = res?;
+                let Some(Cell::Point(value)) = row.cells().get(col_index) else {
+                    continue;
+                };
+                let distance = value.distance_squared(&point);
+                let position = nearest.partition_point(|(d, _, _)| *d <= distance);
+                if position < k {
+                    nearest.insert(position, (distance, record, row));
+                    nearest.truncate(k);
+                }
+            }
+        }
+
+        let rows = nearest.into_iter()
+            .map(|(_, record, row)| Ok((record, blob::resolve_blobs(self.store, &self.layout, &self.table, row)?)))
+            .collect::<Result<Vec<_>, TableAccessError>>()?;
+        Ok(QueryResult::from_rows(rows, schema))
+    }
+
+    /// All rows in the order of the index of the column (ascending), e.g. as input of a merge join.
+    /// The rows are read one by one by their position, the last page is kept for the following rows on the same page.
+    pub fn scan_by_index(&'db self, col_name: &str) -> Result<QueryResult<'db, (Record, Row)>, TableAccessError> {
+        let col_index = find_column_for_query(self.table.schema(), col_name)?;
+        let btree_pointer = *self.column_index_to_btree_pointer_map()?.get(&col_index)
+            .ok_or_else(|| TableAccessError::LoadRowsError(format!("Column '{}' has no index", col_name)))?;
+
+        let positions = self.indexed_columns[btree_pointer].1.borrow().values_in_key_order()
+            .map_err(|e| TableAccessError::LoadRowsError(e.to_string()))?;
+
+        let mut last_page: Option<Page> = None;
+        let iter = positions.into_iter().map(move |position| {
+            let (page_id, slot_id) = index_position(position)?;
+            let mut page = match last_page.take() {
+                Some(page) if page.page_id() == page_id => page,
+                _ => self.store.read_page(&self.layout, page_id, &self.table)?,
+            };
+            // a moved row is read at its new place
+            let (page_id, slot_id) = match page.forward(slot_id) {
+                Some((target_page, target_slot)) => {
+                    page = self.store.read_page(&self.layout, target_page, &self.table)?;
+                    (target_page, target_slot)
+                },
+                None => (page_id, slot_id),
+            };
+            let record = RecordIterator::from_slots(page.clone(), vec![slot_id]).next()
+                .ok_or_else(|| TableAccessError::LoadRowsError(format!("Index points to a missing record (page {}, slot {})", page_id, slot_id)))?;
+            last_page = Some(page);
+            let row = Row::deserialize(record.data(), self.table.schema())
+                .map_err(|err| StoreError::from(err.in_record(self.table.name(), page_id, slot_id)))?;
+            Ok((record, blob::resolve_blobs(self.store, &self.layout, &self.table, row)?))
+        });
+
+        Ok(QueryResult::from_iter(iter, self.table.schema().clone()).with_ordered_by(col_index))
+    }
+
     pub fn delete(&self, query_result: QueryResult<(Record, Row)>) -> Result<(), TableAccessError> {
+        self.delete_returning(query_result).map(|_| ())
+    }
+
+    /// Same as delete, but returns the deleted rows (DELETE ... RETURNING)
+    pub fn delete_returning(&self, query_result: QueryResult<(Record, Row)>) -> Result<QueryResult<'db, Row>, TableAccessError> {
         let mut page_row_map = HashMap::new();
+        let mut deleted_rows = Vec::new();
 
         let col_index_btree_map = self.column_index_to_btree_pointer_map()?;
 
-        for (record, row) in query_result.rows() {
+        for (record, row) in query_result.rows()? {
             let delete_tuples = page_row_map.entry(*record.page_id()).or_insert(Vec::new());
             let mut uic = UpdateIndexCommand::new();
 
@@ -294,25 +989,46 @@ impl<'db, S: Store> TableAccess<'db, S> {
             }
 
             delete_tuples.push((record, uic));
+            deleted_rows.push(row);
         }
 
         for (page_id, records_to_delete) in page_row_map {
             for (record, uic) in records_to_delete {
-                let mut page = self.store.read_page(self.layout, page_id, &self.table)
+                let mut page = self.store.page_guard(&self.layout, page_id, &self.table)
                     .map_err(|e| TableAccessError::DeleteRowsError(e.to_string()))?;
 
-                page.delete_record(*record.record_index());
+                if self.table.has_blobs() {
+                    let stored = Row::deserialize(record.data(), self.table.schema()).map_err(StoreError::from)?;
+                    blob::free_blobs(self.store, &self.layout, &self.table, &stored)?;
+                }
+                let home = page.home(*record.record_index());
+                // the index first: on error, the page is not changed
                 self.update_index(page_id, *record.record_index(), uic)?;
+                page.delete_record(*record.record_index());
 
-                self.store.write_page(self.layout, &page, &self.table)
+                page.write()
                     .map_err(|e| TableAccessError::DeleteRowsError(e.to_string()))?;
+                if let Some(home) = home {
+                    self.delete_forward(home)?;
+                }
+                self.count_row_changes(1);
             }
         }
 
-        Ok(())
+        self.compact_found_pages();
+        Ok(QueryResult::from_rows(deleted_rows, self.table.schema().clone()))
+    }
+
+    fn check_row_size(&self, row_data: &[u8]) -> Result<(), TableAccessError> {
+        match self.store.quota().max_row_size {
+            Some(max_row_size) if row_data.len() > max_row_size => Err(TableAccessError::QuotaExceeded(
+                format!("Row has {} bytes, the maximum is {} bytes", row_data.len(), max_row_size)
+            )),
+            _ => Ok(()),
+        }
     }
 
-    fn update_index(&self, page_id: i32, slot_id: usize, update_index_cmd: UpdateIndexCommand) -> Result<(), TableAccessError> {
+    fn update_index(&self, page_id: PageId, slot_id: usize, update_index_cmd: UpdateIndexCommand) -> Result<(), TableAccessError> {
         for (idx, old_val, new_val) in update_index_cmd.update_cells {
             if let Some(old_val) = old_val {
                 self.indexed_columns[idx].1.borrow_mut().delete(old_val)
@@ -320,7 +1036,7 @@ impl<'db, S: Store> TableAccess<'db, S> {
             }
             
             if let Some(new_val) = new_val {
-                self.indexed_columns[idx].1.borrow_mut().insert(new_val, (page_id, slot_id as i32))
+                self.indexed_columns[idx].1.borrow_mut().insert(new_val, index_value((page_id, slot_id))?)
                     .map_err(|e| TableAccessError::UpdateRowsError(e.to_string()))?;
             }
         }
@@ -339,6 +1055,38 @@ impl<'db, S: Store> TableAccess<'db, S> {
     } 
 
     pub fn update(&self, query_result: QueryResult<(Record, Row)>, updates: Vec<(&str, Cell)>) -> Result<(), TableAccessError> {
+        self.update_returning(query_result, updates).map(|_| ())
+    }
+
+    /// Optimistic locking with an Int version column: updates the rows only if all of them still have the
+    /// version the caller read, and increments it (the other columns are set to `updates`). Otherwise nothing is
+    /// written and the error is StaleRow. The check and the write can't be interleaved with another write,
+    /// the Database is single threaded. Returns the rows with the new values.
+    pub fn update_versioned(&self, query_result: QueryResult<(Record, Row)>, version_col: &str, expected: i32, updates: Vec<(&str, Cell)>) -> Result<QueryResult<'db, Row>, TableAccessError> {
+        let col_index = find_column_for_query_by_cell(self.table.schema(), version_col, &Cell::Int(expected))?;
+        if updates.iter().any(|(col_name, _)| self.table.schema().find_index_by_name(col_name) == Some(col_index)) {
+            return Err(TableAccessError::UpdateRowsError(format!("Column '{}' is the version column, it is set by update_versioned", version_col)));
+        }
+        let next = expected.checked_add(1)
+            .ok_or_else(|| TableAccessError::UpdateRowsError(format!("Version {} cannot be incremented", expected)))?;
+
+        let schema = query_result.schema().clone();
+        let rows = query_result.rows()?;
+        for (_, row) in rows.iter() {
+            match row.cells()[col_index] {
+                Cell::Int(found) if found == expected => {},
+                Cell::Int(found) => return Err(TableAccessError::StaleRow { expected, found }),
+                _ => return Err(TableAccessError::UpdateRowsError(format!("Column '{}' must be of type INT", version_col))),
+            }
+        }
+
+        let mut updates = updates;
+        updates.push((version_col, Cell::Int(next)));
+        self.update_returning(QueryResult::from_rows(rows, schema), updates)
+    }
+
+    /// Same as update, but returns the rows with the new values (UPDATE ... RETURNING)
+    pub fn update_returning(&self, query_result: QueryResult<(Record, Row)>, updates: Vec<(&str, Cell)>) -> Result<QueryResult<'db, Row>, TableAccessError> {
         if query_result.schema != *self.table.schema() {
             return Err(TableAccessError::UpdateRowsError("QueryResult schema does not match the schema of the table that is supposed to be updated".to_string()));
         }
@@ -352,15 +1100,13 @@ impl<'db, S: Store> TableAccess<'db, S> {
             Ok((index, cell.clone()))
         }).collect::<Result<HashMap<usize, Cell>, TableAccessError>>()?;
 
-        let not_in_place_update = updates.iter().any(|(_, cell)| cell.column_type().is_var_size());
-
         // updated_rows_map are complete rows constructed of old values and the updated values
         // key is the 'page_id' of the current data
         // Better approach: instead of cloning everything, just replace the updated Cells in the existing Row. E.g, Row::replace(index, new_cell);
-        let mut updated_rows_map: HashMap<i32, Vec<(Record, Row, UpdateIndexCommand)>> = HashMap::new();
+        let mut updated_rows_map: HashMap<PageId, Vec<(Record, Row, UpdateIndexCommand)>> = HashMap::new();
+        let mut returned_rows = Vec::new();
 
-
-        for (record, row) in query_result.rows() {
+        for (record, row) in query_result.rows()? {
             let mut updated_cells = Vec::new();
             let mut index_update_cmd = UpdateIndexCommand::new();
             for (queried_cell_index, old_cell) in row.cells().iter().enumerate() {
@@ -382,175 +1128,871 @@ impl<'db, S: Store> TableAccess<'db, S> {
                 }
             }
             let updated_row = Row::new(updated_cells);
+            returned_rows.push(updated_row.clone());
 
             let updated_rows_per_page = updated_rows_map.entry(*record.page_id()).or_insert(Vec::new());
             updated_rows_per_page.push((record, updated_row, index_update_cmd));
         }
 
         let mut rows_needs_another_page = Vec::new();
-        // iterate over updated_rows_map and write back updated rows to pages
-        // in place or delete and reinsert
+        // iterate over updated_rows_map and write back updated rows to pages:
+        // a row keeps its slot if it still fits into its page (also with another length), otherwise it moves to another page
         for (page_id, updated_rows) in updated_rows_map.into_iter() {
-            let mut page = self.store.read_page(self.layout, page_id, &self.table)
+            let mut page = self.store.page_guard(&self.layout, page_id, &self.table)
                 .map_err(|e| TableAccessError::UpdateRowsError(e.to_string()))?;
 
-            if not_in_place_update {
-                // delete and reinsert
-                for (record, updated_row, update_index_cmd) in updated_rows {
-                    page.delete_record(*record.record_index());
-                    let row_data = updated_row.serialize();
-                    if page.can_insert(&row_data) {
-                        let slot_id = page.insert_record(row_data)?;
-                    
-                        // Update index (before writing page, so that on error the age will not be written)
-                        self.update_index(page.page_id(), slot_id, update_index_cmd)?;
-                    } else {
-                        rows_needs_another_page.push((row_data, update_index_cmd));
+            // on error, the page is not written
+            let updated = updated_rows.into_iter().try_for_each(|(record, updated_row, mut update_index_cmd)| {
+                let stored_row = match self.table.has_blobs() {
+                    true => {
+                        let stored = Row::deserialize(record.data(), self.table.schema()).map_err(StoreError::from)?;
+                        let updated_columns: Vec<usize> = new_values.keys().copied().collect();
+                        blob::store_update(self.store, &self.layout, &self.table, &stored, &updated_row, &updated_columns)?
+                    },
+                    false => updated_row,
+                };
+                let row_data = stored_row.serialize_for(self.table.schema())
+                    .map_err(|e| TableAccessError::UpdateRowsError(e.to_string()))?;
+                self.check_row_size(&row_data)?;
+                let slot_id = *record.record_index();
+                // the indexes point to the home slot of a moved row
+                let home = page.home(slot_id);
+                let (index_page, index_slot) = home.unwrap_or((page_id, slot_id));
+                if page.update_record(slot_id, &row_data).is_ok() {
+                    // Update index (before writing page, so that on error the page will not be written)
+                    self.update_index(index_page, index_slot, update_index_cmd)?;
+                } else if row_data.len() + Page::FORWARD_SIZE <= self.layout.max_record_size()
+                    && (home.is_some() || page.can_forward(slot_id)) {
+                    // the row moves to another page, its home slot forwards to it, so its address stays the same.
+                    // A row that was moved before is deleted here, the forwarding pointer is changed.
+                    if home.is_some() {
+                        page.delete_record(slot_id);
                     }
-
-                    self.store.write_page(self.layout, &page, &self.table)
-                        .map_err(|_| TableAccessError::UpdateRowsError("Reinsert update error: cannot write page".to_string()))?
-                }
-            } else {
-                // easy peasy in place update
-                for (record, updated_row, update_index_cmd) in updated_rows {
-                    let row_data = updated_row.serialize();
-                    page.write_record(*record.record_index(), row_data)?;
-                    self.update_index(page.page_id(), *record.record_index(), update_index_cmd)?;
-
-                    self.store.write_page(self.layout, &page, &self.table)
-                        .map_err(|_| TableAccessError::UpdateRowsError("Write in place error: cannot write page".to_string()))?;
+                    rows_needs_another_page.push((row_data, Relocation::Forward((index_page, index_slot)), update_index_cmd));
+                } else {
+                    page.delete_record(slot_id);
+                    // the entries of the unchanged indexed values must point to the new place, too
+                    for (col_index, btree_pointer) in index_to_btree_pointer_map.iter() {
+                        if !update_index_cmd.update_cells.iter().any(|(pointer, _, _)| pointer == btree_pointer) {
+                            let value = stored_row.cells()[*col_index].expect_int("Int expected for indexed values")
+                                .map_err(|e| TableAccessError::UpdateRowsError(e.to_string()))?;
+                            update_index_cmd.push_update((*btree_pointer, value, value));
+                        }
+                    }
+                    rows_needs_another_page.push((row_data, Relocation::Reinsert { stale_home: home }, update_index_cmd));
                 }
+                Ok(())
+            });
+            if let Err(err) = updated {
+                page.discard();
+                return Err(err);
             }
+
+            page.write()
+                .map_err(|_| TableAccessError::UpdateRowsError("Update error: cannot write page".to_string()))?;
         }
 
-        for (updated_row_data, update_index_cmd) in rows_needs_another_page {
-            self.raw_insert(
-                updated_row_data,
-                move |s, (page_id, slot_id)| {
-                    s.update_index(page_id, slot_id, update_index_cmd)
-                }
-            )?;
+        for (updated_row_data, relocation, update_index_cmd) in rows_needs_another_page {
+            match relocation {
+                Relocation::Forward(home) => {
+                    self.move_row(home, updated_row_data)?;
+                    self.update_index(home.0, home.1, update_index_cmd)?;
+                },
+                Relocation::Reinsert { stale_home } => {
+                    if let Some(home) = stale_home {
+                        self.delete_forward(home)?;
+                    }
+                    self.raw_insert(
+                        updated_row_data,
+                        move |s, (page_id, slot_id)| {
+                            s.update_index(page_id, slot_id, update_index_cmd)
+                        }
+                    )?;
+                },
+            }
         }       
         
-        Ok(())
+        self.compact_found_pages();
+        Ok(QueryResult::from_rows(returned_rows, self.table.schema().clone()))
     }
 
-    // Currently most naive insert implementation: go over all pages and look if there is space left :)
-    // Should be refactored, so that FSM is used to find pages with free space
+    // Inserts into the first page with space left, the free space map of the store skips the full pages
     /// Returns (page_id, slot_id)
-    fn raw_insert<B: FnOnce(&Self, (i32, usize)) -> Result<(), TableAccessError>>(&self, row_data: Vec<u8>, before_saving_hook: B) -> Result<(i32, usize), TableAccessError> {
-        let page_iterator = self.store.seq_page_iterator(self.layout, &self.table)
-            .map_err(|_| TableAccessError::InsertRowError("Cannot retrieve page iterator".to_string()))?;
+    fn raw_insert<B: FnOnce(&Self, (PageId, usize)) -> Result<(), TableAccessError>>(&self, row_data: Vec<u8>, before_saving_hook: B) -> Result<(PageId, usize), TableAccessError> {
+        self.place_record(row_data, None, before_saving_hook)
+    }
 
-        for mut page in page_iterator {
-            if page.can_insert(&row_data) {
-                let slot_id = page.insert_record(row_data)?;
+    // Inserts the moved row of an update and points its home slot to it (see Page::forward_record)
+    fn move_row(&self, home: (PageId, usize), row_data: Vec<u8>) -> Result<(), TableAccessError> {
+        let target = self.place_record(row_data, Some(home), |_, _| Ok(()))?;
+        let mut home_page = self.store.page_guard(&self.layout, home.0, &self.table)?;
+        home_page.forward_record(home.1, target)?;
+        home_page.write()
+            .map_err(|e| TableAccessError::UpdateRowsError(format!("Cannot write page: {}", e)))
+    }
 
-                before_saving_hook(self, (page.page_id(), slot_id))?;
+    // Deletes the forwarding pointer of a moved row that was deleted (or moved back into a home slot of its own)
+    fn delete_forward(&self, home: (PageId, usize)) -> Result<(), TableAccessError> {
+        let mut home_page = self.store.page_guard(&self.layout, home.0, &self.table)?;
+        home_page.delete_record(home.1);
+        home_page.write()
+            .map_err(|e| TableAccessError::DeleteRowsError(format!("Cannot write page: {}", e)))
+    }
 
-                self.store.write_page(self.layout, &page, &self.table)
-                    .map_err(|e| TableAccessError::InsertRowError(format!("Cannot write page: {}", e.to_string())))?;
+    // home: the row is moved from this slot (stored with the pointer to it)
+    fn place_record<B: FnOnce(&Self, (PageId, usize)) -> Result<(), TableAccessError>>(&self, row_data: Vec<u8>, home: Option<(PageId, usize)>, before_saving_hook: B) -> Result<(PageId, usize), TableAccessError> {
+        let (page, slot_id) = self.place(row_data, home, None)?;
+        let page_id = page.page_id();
 
-                return Ok((page.page_id(), slot_id));
-            }
+        if let Err(err) = before_saving_hook(self, (page_id, slot_id)) {
+            page.discard();
+            return Err(err);
+        }
+
+        page.write()
+            .map_err(|e| TableAccessError::InsertRowError(format!("Cannot write page: {}", e)))?;
+
+        Ok((page_id, slot_id))
+    }
+
+    /// The page with the record inserted (written when the guard is dropped) and its slot.
+    /// With ordered_by, the record is inserted in the order of the column (clustered table).
+    fn place(&self, row_data: Vec<u8>, home: Option<(PageId, usize)>, ordered_by: Option<usize>) -> Result<(PageGuard<'_, S>, usize), TableAccessError> {
+        self.check_row_size(&row_data)?;
+        let fits = |page: &Page| match (home, ordered_by) {
+            (Some(_), _) => page.can_insert_moved(&row_data),
+            (None, Some(_)) => page.can_insert_ordered(&row_data),
+            (None, None) => page.can_insert(&row_data),
+        };
+
+        let mut target = None;
+        match self.store.read_free_space(&self.table)? {
+            // only the pages with enough free space are read (see store/free_space.rs)
+            Some(free_space) => {
+                let number_of_pages = self.store.read_metadata(&self.layout, &self.table)?.number_of_pages();
+                for page_id in free_space.pages_with(row_data.len(), number_of_pages) {
+                    let page = self.store.read_page(&self.layout, page_id, &self.table)
+                        .map_err(|e| TableAccessError::InsertRowError(format!("Cannot read page: {}", e)))?;
+                    if fits(&page) {
+                        target = Some(page);
+                        break;
+                    }
+                }
+            },
+            None => {
+                let page_iterator = self.store.seq_page_iterator(&self.layout, &self.table)
+                    .map_err(|_| TableAccessError::InsertRowError("Cannot retrieve page iterator".to_string()))?;
+                for page in page_iterator {
+                    let page = page
+                        .map_err(|e| TableAccessError::InsertRowError(format!("Cannot read page: {}", e)))?;
+                    if fits(&page) {
+                        target = Some(page);
+                        break;
+                    }
+                }
+            },
         }
 
         // No page with enough space found, so allocate a new one:
         // this can lead to a lot of new allocated pages, for example, if the the before_saving_hook fails.
         // Actually, the new_page must be deallocated, if the hook fails.
-        let mut new_page = self.store.allocate_page(self.layout, &self.table)
-            .map_err(|e| TableAccessError::InsertRowError(format!("Cannot allocate page: {}", e.to_string())))?;
+        let mut page = match target {
+            Some(page) => PageGuard::new(self.store, &self.layout, &self.table, page),
+            None => PageGuard::allocate(self.store, &self.layout, &self.table)
+                .map_err(|e| match e {
+                    StoreError::QuotaExceeded(msg) => TableAccessError::QuotaExceeded(msg),
+                    e => TableAccessError::InsertRowError(format!("Cannot allocate page: {}", e)),
+                })?,
+        };
 
         // If row size is larger than page data size, it will fail here
-        let slot_id = new_page.insert_record(row_data)?;
+        let slot_id = match (home, ordered_by) {
+            (Some(home), _) => page.insert_moved_record(home, &row_data)?,
+            (None, Some(col_index)) => page.insert_record_ordered(row_data, self.cluster_key(col_index))?,
+            (None, None) => page.insert_record(row_data)?,
+        };
+        Ok((page, slot_id))
+    }
+
+    // the key of a record of a clustered table, NULL and rows that can't be read first
+    fn cluster_key(&self, col_index: usize) -> impl Fn(&[u8]) -> Option<i32> + '_ {
+        move |data| Row::deserialize(data, self.table.schema()).ok()
+            .and_then(|row| match row.cells()[col_index] {
+                Cell::Int(value) => Some(value),
+                _ => None,
+            })
+    }
+
+    /// Orders the rows of every page by the Int column, so that find_clustered can use a binary search within
+    /// the pages. Like CLUSTER of Postgres, the order is not maintained: only insert_clustered keeps it, insert and
+    /// updates of the column don't. The indexes of the moved rows are updated. Pages with forwarded or moved rows
+    /// can't be ordered and are skipped. Returns the number of ordered pages.
+    pub fn cluster_pages(&self, col_name: &str) -> Result<usize, TableAccessError> {
+        let col_index = find_column_for_query_by_cell(self.table.schema(), col_name, &Cell::Int(NULL_INT))?;
+        let number_of_pages = self.store.read_metadata(&self.layout, &self.table)?.number_of_pages();
+        let mut ordered = 0;
+        for page_id in 1..=number_of_pages {
+            let mut page = self.store.read_page(&self.layout, page_id, &self.table)?;
+            if !page.can_be_ordered() {
+                continue;
+            }
+            let moved = page.sort_slots_by_key(self.cluster_key(col_index))?;
+            self.store.write_page(&self.layout, &page, &self.table)?;
+            for (old_slot, new_slot) in moved {
+                if old_slot != new_slot && !self.indexed_columns.is_empty() {
+                    self.repoint_index(page_id, new_slot)?;
+                }
+            }
+            ordered += 1;
+        }
+        Ok(ordered)
+    }
+
+    /// Inserts the row into a page of a clustered table (see cluster_pages) at its place in the order of the column.
+    /// The rows behind it move by one slot, their index entries are updated.
+    pub fn insert_clustered(&self, row: &Row, col_name: &str) -> Result<(), TableAccessError> {
+        let col_index = find_column_for_query_by_cell(self.table.schema(), col_name, &Cell::Int(NULL_INT))?;
+        self.insert_placed(row.clone(), Some(col_index)).map(|_| ())
+    }
+
+    /// Rows with the value in the Int column of a clustered table: only the matching rows of a page are read
+    /// (binary search). The pages must be ordered by the column (cluster_pages, insert_clustered),
+    /// if a page has forwarded or moved rows, the table is scanned like find does.
+    pub fn find_clustered(&'db self, col_name: &str, value: i32) -> Result<QueryResult<'db, (Record, Row)>, TableAccessError> {
+        let col_index = find_column_for_query_by_cell(self.table.schema(), col_name, &Cell::Int(value))?;
+        let key = self.cluster_key(col_index);
+        let mut positions = Vec::new();
+        for page in self.store.seq_page_iterator(&self.layout, &self.table)? {
+            let page = page?;
+            if !page.can_be_ordered() {
+                return self.find(col_name, Cell::Int(value));
+            }
+            positions.extend(page.binary_search_by_key(&Some(value), &key).into_iter().map(|slot_id| (page.page_id(), slot_id)));
+        }
+
+        let iter = IndexedRowIterator::new(&self.table, self.store, &self.layout, positions);
+        Ok(self.with_blobs(QueryResult::from_indexes(iter, self.table.schema().clone())))
+    }
+
+    /// Inserts a row given as column name => value (e.g. a HashMap or BTreeMap).
+    /// Missing columns get a default value, except indexed columns, which must always be set.
+    pub fn insert_map<'m, K, M>(&self, values: M) -> Result<(), TableAccessError>
+    where
+        K: AsRef<str> + 'm,
+        M: IntoIterator<Item = (&'m K, &'m Cell)>,
+    {
+        self.insert_map_returning(values).map(|_| ())
+    }
+
+    /// Same as insert_map, but returns the inserted row with the default values (INSERT ... RETURNING)
+    pub fn insert_map_returning<'m, K, M>(&self, values: M) -> Result<Row, TableAccessError>
+    where
+        K: AsRef<str> + 'm,
+        M: IntoIterator<Item = (&'m K, &'m Cell)>,
+    {
+        let row = self.row_from_map(values)?;
+        self.insert_returning(row)
+    }
+
+    /// The row of insert_map, without inserting it
+    pub fn row_from_map<'m, K, M>(&self, values: M) -> Result<Row, TableAccessError>
+    where
+        K: AsRef<str> + 'm,
+        M: IntoIterator<Item = (&'m K, &'m Cell)>,
+    {
+        let mut builder = Row::builder(self.table.schema());
+        for (col_name, cell) in values {
+            builder = builder.set(col_name.as_ref(), cell.clone())?;
+        }
+
+        let indexed_columns = self.column_index_to_btree_pointer_map()?;
+        let row = builder.build_with_defaults(|index, column| {
+            if indexed_columns.contains_key(&index) {
+                None
+            } else {
+                Some(default_cell(&column.col_type))
+            }
+        })?;
+
+        Ok(row)
+    }
+
+    pub fn insert(&self, row: &Row) -> Result<(), TableAccessError> {
+        self.insert_returning(row.clone()).map(|_| ())
+    }
+
+    /// Inserts the row through the stages of the write pipeline (see database/write_pipeline.rs),
+    /// returns the row as it is stored (the hooks may change it)
+    pub fn insert_returning(&self, row: Row) -> Result<Row, TableAccessError> {
+        self.insert_placed(row, None)
+    }
+
+    // ordered_by: the column of a clustered table (see insert_clustered)
+    fn insert_placed(&self, row: Row, ordered_by: Option<usize>) -> Result<Row, TableAccessError> {
+        let pipeline = &self.write_pipeline;
+        let schema = self.table.schema();
+        let mut insert = PendingInsert::new(&self.table, row);
+
+        insert.row().validate(schema)?;
+        pipeline.run(WriteStage::Validate, &mut insert)?;
+
+        pipeline.run(WriteStage::Defaults, &mut insert)?;
+        if pipeline.changes_rows() {
+            insert.row().validate(schema)?;
+        }
+
+        let uic = self.index_insert_command(insert.row())?;
+        pipeline.run(WriteStage::IndexCheck, &mut insert)?;
+
+        let row = blob::store_blobs(self.store, &self.layout, &self.table, insert.row())?;
+        let row_data = row.serialize_for(schema)
+            .map_err(|e| TableAccessError::InsertRowError(e.to_string()))?;
+        let (page, slot_id) = self.place(row_data, None, ordered_by)?;
+        let page_id = page.page_id();
+        // the rows behind the new row on an ordered page were moved by one slot
+        let shifted: Vec<usize> = match ordered_by {
+            Some(_) => page.live_slot_indexes().into_iter().filter(|index| *index > slot_id).collect(),
+            None => Vec::new(),
+        };
+        insert.set_location((page_id, slot_id));
+        // the page is only written, if the row is accepted
+        let accepted = pipeline.run(WriteStage::Placement, &mut insert)
+            // there is no write-ahead log yet
+            .and_then(|_| pipeline.run(WriteStage::Wal, &mut insert))
+            .and_then(|_| self.update_index(page_id, slot_id, uic));
+        if let Err(err) = accepted {
+            page.discard();
+            return Err(err);
+        }
+        page.write()
+            .map_err(|e| TableAccessError::InsertRowError(format!("Cannot write page: {}", e)))?;
+        if !self.indexed_columns.is_empty() {
+            for slot_id in shifted {
+                self.repoint_index(page_id, slot_id)?;
+            }
+        }
+        self.count_row_changes(1);
+        pipeline.run(WriteStage::Write, &mut insert)?;
+
+        self.compact_found_pages();
+        Ok(insert.into_row())
+    }
+
+    // the index entries of a new row, its values must not be in the (unique) indexes yet
+    fn index_insert_command(&self, row: &Row) -> Result<UpdateIndexCommand, TableAccessError> {
+        let mut uic = UpdateIndexCommand::new();
+        for (col_idx, btree_idx) in self.column_index_to_btree_pointer_map()? {
+            let val = row.cells()[col_idx].expect_int("Indexed value must be of type Int")
+                .map_err(|e| TableAccessError::InsertRowError(e.to_string()))?;
+            let exists = self.indexed_columns[btree_idx].1.borrow().find(val)
+                .map_err(|e| TableAccessError::InsertRowError(e.to_string()))?
+                .is_some();
+            if exists {
+                return Err(TableAccessError::InsertRowError(
+                    format!("Unique key constraint error: value {} of column '{}' exists", val, self.table.schema().columns[col_idx].name)
+                ));
+            }
+            uic.push_insert((btree_idx, val));
+        }
+        Ok(uic)
+    }
+
+    /// Inserts the rows one after another with insert, e.g. for INSERT with several VALUES rows.
+    /// There are no transactions: if a row is rejected, the rows before it stay inserted and the error
+    /// tells which row failed (InsertManyError). Returns the number of inserted rows.
+    pub fn insert_many(&self, rows: &[Row]) -> Result<usize, TableAccessError> {
+        for (i, row) in rows.iter().enumerate() {
+            self.insert(row).map_err(|err| TableAccessError::InsertManyError { row: i + 1, error: Box::new(err) })?;
+        }
+        Ok(rows.len())
+    }
+
+    /// INSERT ... SELECT: inserts all rows of the query result, which must have the column types of this table
+    /// (in the same order). A varchar of the result may be longer than the column, then the values are checked.
+    /// Returns the number of inserted rows, see insert_all.
+    pub fn insert_from<'r, I: IntoRow + 'r>(&self, source: QueryResult<'r, I>) -> Result<usize, TableAccessError> {
+        check_insert_schema(source.schema(), self.table.schema())?;
+        let rows = source.rows()?.into_iter().map(IntoRow::into_row).collect();
+        self.insert_all(rows)
+    }
+
+    /// Inserts the rows with the bulk loader: they are validated and checked against the unique indexes first,
+    /// so nothing is written if one of them is invalid. Then they are packed into new pages (load_pages),
+    /// the free space of the existing pages is not used. Returns the number of inserted rows.
+    pub fn insert_all(&self, rows: Vec<Row>) -> Result<usize, TableAccessError> {
+        let schema = self.table.schema();
+        for row in rows.iter() {
+            row.validate(schema)?;
+        }
+        for (col_idx, btree_idx) in self.column_index_to_btree_pointer_map()? {
+            let btree = self.indexed_columns[btree_idx].1.borrow();
+            let mut values = HashSet::new();
+            for row in rows.iter() {
+                let value = row.cells()[col_idx].expect_int("Indexed value must be of type Int")
+                    .map_err(|e| TableAccessError::InsertRowError(e.to_string()))?;
+                let exists = btree.find(value)
+                    .map_err(|e| TableAccessError::InsertRowError(e.to_string()))?
+                    .is_some();
+                if exists || !values.insert(value) {
+                    return Err(TableAccessError::InsertRowError(
+                        format!("Duplicate value {} for unique column '{}'", value, schema.columns[col_idx].name)
+                    ));
+                }
+            }
+        }
+
+        let rows = self.store_blobs(rows)?;
+        let pages = pack_rows(rows.iter(), schema, &self.layout)?;
+        self.load_pages(pages.into_iter().map(Ok))?;
+        Ok(rows.len())
+    }
+
+    /// The rows as they are stored, with the BLOB values written to the overflow pages (e.g. for pack_rows)
+    pub(crate) fn store_blobs(&self, rows: Vec<Row>) -> Result<Vec<Row>, TableAccessError> {
+        if !self.table.has_blobs() {
+            return Ok(rows);
+        }
+        rows.iter()
+            .map(|row| Ok(blob::store_blobs(self.store, &self.layout, &self.table, row)?))
+            .collect()
+    }
+
+    /// Bulk loader: appends whole pages (with the layout of this table) and adds their rows to the indexes.
+    /// The pages are written in batches instead of once per row. The rows are not validated.
+    pub fn load_pages<I: IntoIterator<Item = Result<Page, StoreError>>>(&self, pages: I) -> Result<(), TableAccessError> {
+        const BATCH_SIZE: usize = 32;
+        let col_index_btree_map = self.column_index_to_btree_pointer_map()?;
+
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+        for page in pages {
+            let mut page = page?;
+            // allocate_page writes an empty page, which is overwritten by the batch
+            let allocated = self.store.allocate_page(&self.layout, &self.table)?;
+            page.set_page_id(allocated.page_id());
+            batch.push(page);
+
+            if batch.len() == BATCH_SIZE {
+                self.write_loaded_pages(&mut batch, &col_index_btree_map)?;
+            }
+        }
+        self.write_loaded_pages(&mut batch, &col_index_btree_map)
+    }
+
+    fn write_loaded_pages(&self, batch: &mut Vec<Page>, col_index_btree_map: &HashMap<usize, usize>) -> Result<(), TableAccessError> {
+        self.store.write_pages(&self.layout, &batch.iter().collect::<Vec<&Page>>(), &self.table)?;
+
+        for page in batch.drain(..) {
+            for record in page.record_iterator() {
+                let row = Row::deserialize(record.data(), self.table.schema())
+                    .map_err(|err| StoreError::from(err.in_record(self.table.name(), *record.page_id(), *record.record_index())))?;
+                let mut uic = UpdateIndexCommand::new();
+                for (col_idx, btree_idx) in col_index_btree_map {
+                    let val = row.cells()[*col_idx].expect_int("Indexed value must be of type Int")
+                        .map_err(|e| TableAccessError::InsertRowError(e.to_string()))?;
+                    uic.push_insert((*btree_idx, val));
+                }
+                self.update_index(*record.page_id(), *record.record_index(), uic)?;
+                self.count_row_changes(1);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, collections::{BTreeMap, HashMap}};
+
+    use tempfile::tempdir;
+
+    use crate::{data::page::{PageDataLayout, Record}, 
+        database::{Database, NULL_INT, sort::SortKey, table_access::{QueryResult, TableAccess, TableAccessError}}, store::{IndexedRowIterator, Quota, ReadConsistency, Store, StoreError, file_store::FileStore, index_position, row_batch::{BATCH_SIZE, ColumnVector}, sample::SampleSize, bounding_box::BoundingBox}, 
+        table::{Column, ColumnType, TableSchema, table::{Cell, Point, Row, Table}},
+    };
+
+    #[test]
+    fn should_delete_multiple() {
+        let schema = TableSchema::new(vec![
+            Column::new(1, "value", ColumnType::Varchar(7)),
+            Column::new(2, "someint", ColumnType::Int),
+        ]);
+
+        let table = Table::new(1, "test".to_owned(), schema);
+        let base_dir = tempdir().unwrap();
+        let store = FileStore::new(base_dir.path());
+        let layout = PageDataLayout::new(64).unwrap();
+        store.create(&layout, &table).unwrap();
+
+        let access = TableAccess::new(table, &store, &layout);
+
+        let first = Row::new(vec![
+            Cell::Varchar("Rabbit".to_owned()),
+            Cell::Int(42),
+        ]);
+        let second = Row::new(vec![
+            Cell::Varchar("Rabbit".to_owned()),
+            Cell::Int(52),
+        ]);
+        let third = Row::new(vec![
+            Cell::Varchar("Rabbit".to_owned()),
+            Cell::Int(62),
+        ]);
+        let fourth = Row::new(vec![
+            Cell::Varchar("Hare".to_owned()),
+            Cell::Int(82),
+        ]);
+
+        access.insert(&first).unwrap();
+        access.insert(&second).unwrap();
+        access.insert(&third).unwrap();
+        access.insert(&fourth).unwrap();
+
+        // Act: DELETE FROM test WHERE value = "Rabbit"
+        let result = access.find("value", Cell::Varchar("Rabbit".to_owned())).unwrap();
+
+        access.delete(result).unwrap();
+
+        // Assert: Only row with value = "Hare" should remain
+        let result = access.find_all().unwrap();
+        let rows = result.rows().unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].1.cells(), &[Cell::Varchar("Hare".to_owned()), Cell::Int(82)]);
+    }
+
+    // There are no transactions: every write is visible immediately and nothing is validated at the end
+    // of a read-modify-write sequence. This test documents that write skew is NOT detected (see README).
+    #[test]
+    fn write_skew_is_not_detected_without_transactions() {
+        let schema = TableSchema::new(vec![
+            Column::new(1, "doctor", ColumnType::Varchar(10)),
+            Column::new(2, "on_call", ColumnType::Byte),
+        ]);
+        let table = Table::new(1, "test".to_owned(), schema);
+        let base_dir = tempdir().unwrap();
+        let store = FileStore::new(base_dir.path());
+        let layout = PageDataLayout::new(128).unwrap();
+        store.create(&layout, &table).unwrap();
+
+        let access = TableAccess::new(table, &store, &layout);
+        access.insert(&Row::new(vec![Cell::Varchar("Alice".to_owned()), Cell::Byte(1)])).unwrap();
+        access.insert(&Row::new(vec![Cell::Varchar("Bob".to_owned()), Cell::Byte(1)])).unwrap();
+
+        // Constraint checked by the application: at least one doctor must be on call.
+        // Two interleaved "transactions" both read the state first ...
+        let on_call = |access: &TableAccess<FileStore>| access.find("on_call", Cell::Byte(1)).unwrap().rows().unwrap().len();
+        let alice_sees = on_call(&access);
+        let bob_sees = on_call(&access);
+
+        // ... and then both write, based on a state that is no longer valid
+        if alice_sees > 1 {
+            let alice = access.find("doctor", Cell::Varchar("Alice".to_owned())).unwrap();
+            access.update(alice, vec![("on_call", Cell::Byte(0))]).unwrap();
+        }
+        if bob_sees > 1 {
+            let bob = access.find("doctor", Cell::Varchar("Bob".to_owned())).unwrap();
+            access.update(bob, vec![("on_call", Cell::Byte(0))]).unwrap();
+        }
+
+        // both writes succeeded: the constraint is violated and no error was reported
+        assert_eq!(on_call(&access), 0);
+    }
+
+    #[test]
+    fn should_see_own_writes_immediately() {
+        let schema = TableSchema::new(vec![
+            Column::new(1, "id", ColumnType::Int),
+        ]);
+        let table = Table::new(1, "test".to_owned(), schema);
+        let base_dir = tempdir().unwrap();
+        let store = FileStore::new(base_dir.path());
+        let layout = PageDataLayout::new(128).unwrap();
+        store.create(&layout, &table).unwrap();
 
-        before_saving_hook(self, (new_page.page_id(), slot_id))?;
+        let writer = TableAccess::new(table.clone(), &store, &layout);
+        let reader = TableAccess::new(table, &store, &layout);
 
-        self.store.write_page(self.layout, &new_page, &self.table)
-            .map_err(|e| TableAccessError::InsertRowError(format!("Cannot write new allocated page: {}", e.to_string())))?;
+        writer.insert(&Row::new(vec![Cell::Int(1)])).unwrap();
+        assert_eq!(reader.find_all().unwrap().rows().unwrap().len(), 1);
 
-        Ok((new_page.page_id(), slot_id))
+        // a QueryResult is lazy: pages are read while iterating, so there is no snapshot
+        let result = reader.find_all().unwrap();
+        writer.insert(&Row::new(vec![Cell::Int(2)])).unwrap();
+        assert_eq!(result.rows().unwrap().len(), 2);
     }
 
-    pub fn insert(&self, row: &Row) -> Result<(), TableAccessError> {
-        row.validate(self.table.schema())?;
+    #[test]
+    fn should_see_rows_on_new_pages_only_with_read_uncommitted() {
+        let schema = TableSchema::new(vec![
+            Column::new(1, "id", ColumnType::Int),
+        ]);
+        let table = Table::new(1, "test".to_owned(), schema);
+        let base_dir = tempdir().unwrap();
+        let store = FileStore::new(base_dir.path());
+        // one row per page
+        let layout = PageDataLayout::new(44).unwrap();
+        store.create(&layout, &table).unwrap();
 
-        let col_index_btree_map = self.column_index_to_btree_pointer_map()?;
+        let writer = TableAccess::new(table.clone(), &store, &layout);
+        let reader = TableAccess::new(table, &store, &layout);
+        writer.insert(&Row::new(vec![Cell::Int(1)])).unwrap();
 
-        let mut uic = UpdateIndexCommand::new();
-        for (col_idx, btree_idx) in col_index_btree_map {
-            let val = row.cells()[col_idx].expect_int("Indexed value must be of type Int")
-                .map_err(|e| TableAccessError::InsertRowError(e.to_string()))?;
-            uic.push_insert((btree_idx, val));
-        }
+        let committed = reader.find_all_with(ReadConsistency::ReadCommitted).unwrap();
+        let uncommitted = reader.find_all_with(ReadConsistency::ReadUncommitted).unwrap();
+        writer.insert(&Row::new(vec![Cell::Int(2)])).unwrap();
+        writer.insert(&Row::new(vec![Cell::Int(3)])).unwrap();
+        assert_eq!(store.read_metadata(&layout, reader.table()).unwrap().number_of_pages(), 3);
 
-        self.raw_insert(row.serialize(), move |s, (page_id, slot_id)| {
-                    s.update_index(page_id, slot_id, uic)
-        })?;
-        
-        Ok(())
+        assert_eq!(committed.rows().unwrap().len(), 1);
+        assert_eq!(uncommitted.rows().unwrap().len(), 3);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::cell::RefCell;
+    #[test]
+    fn should_scan_a_sample_of_the_pages() {
+        let schema = TableSchema::new(vec![
+            Column::new(1, "id", ColumnType::Int),
+        ]);
+        let table = Table::new(1, "test".to_owned(), schema);
+        let base_dir = tempdir().unwrap();
+        let store = FileStore::new(base_dir.path());
+        // one row per page
+        let layout = PageDataLayout::new(44).unwrap();
+        store.create(&layout, &table).unwrap();
+        let access = TableAccess::new(table, &store, &layout);
+        for i in 0..200 {
+            access.insert(&Row::new(vec![Cell::Int(i)])).unwrap();
+        }
 
-    use tempfile::tempdir;
+        let ids = |size, seed| -> Vec<i32> {
+            access.scan_sample(size, seed).unwrap().rows().unwrap().into_iter()
+                .map(|(_, row)| match row.cells()[0] { Cell::Int(id) => id, _ => panic!() })
+                .collect()
+        };
+        let sample = ids(SampleSize::Fraction(0.25), 3);
+        assert!((25..75).contains(&sample.len()), "{}", sample.len());
+        assert!(sample.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(ids(SampleSize::Fraction(0.25), 3), sample);
+        assert_ne!(ids(SampleSize::Fraction(0.25), 4), sample);
+
+        assert_eq!(ids(SampleSize::Fraction(1.0), 3).len(), 200);
+        assert!(ids(SampleSize::Fraction(0.0), 3).is_empty());
+        let rows = ids(SampleSize::Rows(20), 3).len();
+        assert!((5..40).contains(&rows), "{}", rows);
+
+        assert!(matches!(access.scan_sample(SampleSize::Fraction(2.0), 3), Err(TableAccessError::LoadRowsError(_))));
+    }
 
-    use crate::{data::page::PageDataLayout, 
-        database::table_access::TableAccess, store::{IndexedRowIterator, Store, file_store::FileStore}, 
-        table::{Column, ColumnType, TableSchema, table::{Cell, Row, Table}},
-    };
+    #[test]
+    fn should_find_rows_of_clustered_pages_by_binary_search() {
+        let base_dir = tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_dir.path()));
+        db.drop_create().unwrap();
+        let table = db.create_table_with_page_size("t", vec![("id", ColumnType::Int, false, true), ("group", ColumnType::Int, false, false)], 256).unwrap();
+        let access = db.table_access(table).unwrap();
+        for i in [5, 1, 9, 3, 7, 2, 8] {
+            access.insert(&Row::new(vec![Cell::Int(i), Cell::Int(i % 2)])).unwrap();
+        }
+        access.delete(access.find("id", Cell::Int(9)).unwrap()).unwrap();
+
+        assert_eq!(access.cluster_pages("group").unwrap(), 1);
+        access.insert_clustered(&Row::new(vec![Cell::Int(4), Cell::Int(0)]), "group").unwrap();
+        let ids = |result: QueryResult<(Record, Row)>| result.rows().unwrap().into_iter()
+            .map(|(_, row)| row.cells()[0].clone())
+            .collect::<Vec<Cell>>();
+        assert_eq!(ids(access.find_all().unwrap()), vec![Cell::Int(2), Cell::Int(8), Cell::Int(4), Cell::Int(5), Cell::Int(1), Cell::Int(3), Cell::Int(7)]);
+
+        assert_eq!(ids(access.find_clustered("group", 0).unwrap()), vec![Cell::Int(2), Cell::Int(8), Cell::Int(4)]);
+        assert!(access.find_clustered("group", 5).unwrap().rows().unwrap().is_empty());
+        // the index follows the moved rows
+        for i in [1, 2, 3, 4, 5, 7, 8] {
+            assert_eq!(ids(access.find("id", Cell::Int(i)).unwrap()), vec![Cell::Int(i)]);
+        }
+    }
 
     #[test]
-    fn should_delete_multiple() {
+    fn should_skip_pages_by_zone_map() {
         let schema = TableSchema::new(vec![
-            Column::new(1, "value", ColumnType::Varchar(7)),
-            Column::new(2, "someint", ColumnType::Int),
+            Column::new(1, "id", ColumnType::Int),
+            Column::new(2, "group", ColumnType::Int),
         ]);
-
         let table = Table::new(1, "test".to_owned(), schema);
         let base_dir = tempdir().unwrap();
         let store = FileStore::new(base_dir.path());
-        let layout = PageDataLayout::new(64).unwrap();
+        let layout = PageDataLayout::new(128).unwrap();
         store.create(&layout, &table).unwrap();
+        let access = TableAccess::new(table.clone(), &store, &layout);
+        // ids ascending, groups spread over all pages
+        for i in 0..200 {
+            access.insert(&Row::new(vec![Cell::Int(i), Cell::Int(i % 7)])).unwrap();
+        }
+        let number_of_pages = store.read_metadata(&layout, &table).unwrap().number_of_pages();
+        assert!(number_of_pages > 10);
+
+        let pages_read = |col_name: &str, value: i32| {
+            let before = store.io_stats();
+            let rows = access.find(col_name, Cell::Int(value)).unwrap().rows().unwrap();
+            (rows.len(), store.io_stats().since(&before).pages_read)
+        };
+        assert_eq!(pages_read("id", 150), (1, 1));
+        assert_eq!(pages_read("id", 500), (0, 0));
+        assert_eq!(pages_read("group", 3).1, number_of_pages);
+
+        // the range of the page follows updates and deletes
+        let row = access.find("id", Cell::Int(150)).unwrap();
+        access.update(row, vec![("id", Cell::Int(1000))]).unwrap();
+        assert_eq!(pages_read("id", 1000), (1, 1));
+        assert_eq!(pages_read("id", 150), (0, 0));
+        access.delete(access.find("id", Cell::Int(1000)).unwrap()).unwrap();
+        assert_eq!(pages_read("id", 1000), (0, 0));
+    }
 
-        let access = TableAccess::new(table, &store, &layout);
-
-        let first = Row::new(vec![
-            Cell::Varchar("Rabbit".to_owned()),
-            Cell::Int(42),
-        ]);
-        let second = Row::new(vec![
-            Cell::Varchar("Rabbit".to_owned()),
-            Cell::Int(52),
+    #[test]
+    fn should_find_points_within_a_box_and_the_nearest_points() {
+        let schema = TableSchema::new(vec![
+            Column::new(1, "id", ColumnType::Int),
+            Column::new(2, "location", ColumnType::Point),
         ]);
-        let third = Row::new(vec![
-            Cell::Varchar("Rabbit".to_owned()),
-            Cell::Int(62),
+        let table = Table::new(1, "test".to_owned(), schema);
+        let base_dir = tempdir().unwrap();
+        let store = FileStore::new(base_dir.path());
+        let layout = PageDataLayout::new(128).unwrap();
+        store.create(&layout, &table).unwrap();
+        let access = TableAccess::new(table.clone(), &store, &layout);
+        // x ascending with the pages
+        for i in 0..200 {
+            access.insert(&Row::new(vec![Cell::Int(i), Cell::Point(Point(i as f64, (i % 5) as f64))])).unwrap();
+        }
+        assert!(store.read_metadata(&layout, &table).unwrap().number_of_pages() > 50);
+        let ids = |rows: Vec<(Record, Row)>| rows.iter().map(|(_, row)| row.cells()[0].clone()).collect::<Vec<Cell>>();
+
+        let before = store.io_stats();
+        let rows = access.within_bbox("location", BoundingBox::new(Point(102.0, 4.0), Point(100.0, 1.0))).unwrap().rows().unwrap();
+        assert_eq!(ids(rows), vec![Cell::Int(101), Cell::Int(102)]);
+        assert!(store.io_stats().since(&before).pages_read <= 2);
+
+        let before = store.io_stats();
+        let rows = access.nearest("location", Point(50.2, 0.0), 3).unwrap().rows().unwrap();
+        assert_eq!(ids(rows), vec![Cell::Int(50), Cell::Int(51), Cell::Int(52)]);
+        assert!(store.io_stats().since(&before).pages_read <= 3);
+        assert_eq!(access.nearest("location", Point(500.0, 500.0), 1).unwrap().rows().unwrap().len(), 1);
+        assert!(access.nearest("location", Point(0.0, 0.0), 0).unwrap().rows().unwrap().is_empty());
+
+        // the box of the page follows updates
+        access.update(access.find("id", Cell::Int(0)).unwrap(), vec![("location", Cell::Point(Point(1000.0, 1000.0)))]).unwrap();
+        let rows = access.nearest("location", Point(999.0, 999.0), 1).unwrap().rows().unwrap();
+        assert_eq!(ids(rows), vec![Cell::Int(0)]);
+        assert!(matches!(access.within_bbox("id", BoundingBox::empty()), Err(TableAccessError::LoadRowsError(_))));
+        assert!(matches!(access.insert(&Row::new(vec![Cell::Int(1), Cell::Point(Point(f64::NAN, 0.0))])), Err(TableAccessError::InsertRowError(_))));
+    }
+
+    #[test]
+    fn should_enforce_quota() {
+        let schema = TableSchema::new(vec![
+            Column::new(1, "value", ColumnType::Varchar(100)),
         ]);
-        let fourth = Row::new(vec![
-            Cell::Varchar("Hare".to_owned()),
-            Cell::Int(82),
+        let table = Table::new(1, "test".to_owned(), schema);
+        let base_dir = tempdir().unwrap();
+        let store = FileStore::new(base_dir.path()).with_quota(Quota {
+            max_pages_per_table: Some(2),
+            max_row_size: Some(50),
+            ..Quota::default()
+        });
+        let layout = PageDataLayout::new(128).unwrap();
+        store.create(&layout, &table).unwrap();
+        let access = TableAccess::new(table.clone(), &store, &layout);
+
+        let too_large = access.insert(&Row::new(vec![Cell::Varchar("x".repeat(60))]));
+        assert!(matches!(too_large, Err(TableAccessError::QuotaExceeded(_))));
+
+        let mut result = Ok(());
+        for _ in 0..10 {
+            result = access.insert(&Row::new(vec![Cell::Varchar("x".repeat(40))]));
+            if result.is_err() {
+                break;
+            }
+        }
+        assert!(matches!(result, Err(TableAccessError::QuotaExceeded(_))));
+        assert_eq!(store.read_metadata(&layout, &table).unwrap().number_of_pages(), 2);
+
+        // total size: the file already has the metadata and two pages
+        let size = std::fs::metadata(base_dir.path().join(table.file_path())).unwrap().len();
+        let store = FileStore::new(base_dir.path()).with_quota(Quota { max_total_size: Some(size + 127), ..Quota::default() });
+        assert!(matches!(store.allocate_page(&layout, &table), Err(StoreError::QuotaExceeded(_))));
+    }
+
+    #[test]
+    fn should_scan_in_batches() {
+        let schema = TableSchema::new(vec![
+            Column::new(1, "id", ColumnType::Int),
+            Column::new(2, "name", ColumnType::Varchar(10)),
         ]);
+        let table = Table::new(1, "test".to_owned(), schema);
+        let base_dir = tempdir().unwrap();
+        let store = FileStore::new(base_dir.path());
+        let layout = PageDataLayout::new(16384).unwrap();
+        store.create(&layout, &table).unwrap();
+        let access = TableAccess::new(table.clone(), &store, &layout);
 
-        access.insert(&first).unwrap();
-        access.insert(&second).unwrap();
-        access.insert(&third).unwrap();
-        access.insert(&fourth).unwrap();
+        for i in 1..=600 {
+            access.insert(&Row::new(vec![Cell::Int(i), Cell::Varchar(format!("row{}", i))])).unwrap();
+        }
 
-        // Act: DELETE FROM test WHERE value = "Rabbit"
-        let result = access.find("value", Cell::Varchar("Rabbit".to_owned())).unwrap();
+        let mut sum = 0i64;
+        let mut rows = 0;
+        let mut batches = 0;
+        for batch in access.find_all_batches().unwrap() {
+            let batch = batch.unwrap();
+            assert!(batch.len() <= BATCH_SIZE);
+            if let Some(ColumnVector::Int(ids)) = batch.column(0) {
+                sum += ids.iter().map(|id| *id as i64).sum::<i64>();
+            }
+            rows += batch.len();
+            batches += 1;
+        }
+        // one page: 256 + 256 + 88 rows
+        assert_eq!(batches, 3);
+        assert_eq!(rows, 600);
+        assert_eq!(sum, 600 * 601 / 2);
+
+        // row by row over batch boundaries
+        let ids: Vec<i32> = access.find_all().unwrap().rows().unwrap().iter()
+            .map(|(_, row)| row.cells()[0].expect_int("id").unwrap())
+            .collect();
+        assert_eq!(ids, (1..=600).collect::<Vec<i32>>());
+    }
 
-        access.delete(result).unwrap();
+    #[test]
+    fn should_stop_scan_at_first_match() {
+        let schema = TableSchema::new(vec![Column::new(1, "id", ColumnType::Int)]);
+        let table = Table::new(1, "test".to_owned(), schema);
+        let base_dir = tempdir().unwrap();
+        let store = FileStore::new(base_dir.path());
+        // a few rows per page
+        let layout = PageDataLayout::new(64).unwrap();
+        store.create(&layout, &table).unwrap();
+        let access = TableAccess::new(table.clone(), &store, &layout);
+        assert!(!access.any().unwrap());
+        assert!(access.first().unwrap().is_none());
 
-        // Assert: Only row with value = "Hare" should remain
-        let result = access.find_all().unwrap();
-        let rows = result.rows();
-        assert_eq!(rows.len(), 1);
-        assert_eq!(rows[0].1.cells(), &[Cell::Varchar("Hare".to_owned()), Cell::Int(82)]);
+        for i in 1..=100 {
+            access.insert(&Row::new(vec![Cell::Int(i)])).unwrap();
+        }
+        let pages = store.read_metadata(&layout, &table).unwrap().number_of_pages();
+        assert!(pages > 10);
+
+        let before = store.io_stats();
+        assert!(access.exists_where(|row| row.cells()[0] == Cell::Int(2)).unwrap());
+        assert!(store.io_stats().since(&before).pages_read <= 2);
+
+        let (_, first) = access.first().unwrap().unwrap();
+        assert_eq!(first.cells()[0], Cell::Int(1));
+        assert!(access.any().unwrap());
+        let (_, found) = access.first_where(|row| row.cells()[0] == Cell::Int(50)).unwrap().unwrap();
+        assert_eq!(found.cells()[0], Cell::Int(50));
+
+        let before = store.io_stats();
+        assert!(!access.exists_where(|row| row.cells()[0] == Cell::Int(0)).unwrap());
+        assert_eq!(store.io_stats().since(&before).pages_read, pages);
     }
 
     #[test]
@@ -597,12 +2039,12 @@ mod tests {
 
         // Assert: row with value "Rabbit" shouldn't exist anymore
         let result = access.find("value", Cell::Varchar("Rabbit".to_owned())).unwrap();
-        let rows = result.rows();
+        let rows = result.rows().unwrap();
         assert_eq!(rows.len(), 0);
 
         // Assert: row with value "Bear" exists instead
         let result = access.find("value", Cell::Varchar("Bear".to_owned())).unwrap();
-        let rows = result.rows();
+        let rows = result.rows().unwrap();
 
         assert_eq!(rows.len(), 3);
     }
@@ -640,12 +2082,12 @@ mod tests {
 
         // Assert: row with value "Rabbit" shouldn't exist anymore
         let result = access.find("value", Cell::Varchar("Rabbit".to_owned())).unwrap();
-        let rows = result.rows();
+        let rows = result.rows().unwrap();
         assert_eq!(rows.len(), 0);
 
         // Assert: row with value "Bear" exists instead
         let result = access.find("value", Cell::Varchar("Bear".to_owned())).unwrap();
-        let rows = result.rows();
+        let rows = result.rows().unwrap();
         assert_eq!(rows.len(), 1);
     }
 
@@ -659,7 +2101,7 @@ mod tests {
         let table = Table::new(1, "test".to_owned(), schema);
         let base_dir = tempdir().unwrap();
         let store = FileStore::new(base_dir.path());
-        let layout = PageDataLayout::new(32).unwrap();
+        let layout = PageDataLayout::new(44).unwrap();
         store.create(&layout, &table).unwrap();
 
         let btree = RefCell::new(store.read_btree(1).unwrap());
@@ -684,7 +2126,7 @@ mod tests {
         let err = res.unwrap_err();
         assert!(err.to_string().contains("Unique key constraint"));
 
-        let rows = access.find_all().unwrap().rows();
+        let rows = access.find_all().unwrap().rows().unwrap();
         assert_eq!(rows.len(), 1);
     }
 
@@ -698,7 +2140,7 @@ mod tests {
         let table = Table::new(1, "test".to_owned(), schema);
         let base_dir = tempdir().unwrap();
         let store = FileStore::new(base_dir.path());
-        let layout = PageDataLayout::new(32).unwrap();
+        let layout = PageDataLayout::new(44).unwrap();
         store.create(&layout, &table).unwrap();
 
         let btree = RefCell::new(store.read_btree(1).unwrap());
@@ -717,11 +2159,11 @@ mod tests {
         let btree = store.read_btree(1).unwrap();
         let result = btree.find(42).unwrap();
         assert!(result.is_some());
-        let (page_id, slot_id) = result.unwrap();
+        let (page_id, slot_id) = index_position(result.unwrap()).unwrap();
         let page = store.read_page(&layout, page_id, &table).unwrap();
 
-        let row = page.read_slot(slot_id as usize)
-            .map(|data| Row::deserialize(data, table.schema())).unwrap();
+        let row = page.read_slot(slot_id)
+            .map(|data| Row::deserialize(data, table.schema()).unwrap()).unwrap();
 
         let cells = row.cells();
         assert_eq!(cells, &vec![Cell::Int(42), Cell::Byte(1)]);
@@ -737,7 +2179,7 @@ mod tests {
         let table = Table::new(1, "test".to_owned(), schema);
         let base_dir = tempdir().unwrap();
         let store = FileStore::new(base_dir.path());
-        let layout = PageDataLayout::new(32).unwrap();
+        let layout = PageDataLayout::new(44).unwrap();
         store.create(&layout, &table).unwrap();
 
         let btree = RefCell::new(store.read_btree(1).unwrap());
@@ -755,7 +2197,7 @@ mod tests {
         let indexes_used = access.index_used.borrow();
         assert_eq!(*indexes_used, vec![42]);
 
-        let rows = query.rows();
+        let rows = query.rows().unwrap();
         let cells = rows[0].1.cells();
 
         assert_eq!(
@@ -778,7 +2220,7 @@ mod tests {
         let table = Table::new(1, "test".to_owned(), schema);
         let base_dir = tempdir().unwrap();
         let store = FileStore::new(base_dir.path());
-        let layout = PageDataLayout::new(32).unwrap();
+        let layout = PageDataLayout::new(44).unwrap();
         store.create(&layout, &table).unwrap();
 
         let btree = RefCell::new(store.read_btree(1).unwrap());
@@ -811,7 +2253,7 @@ mod tests {
         let base_dir = tempdir().unwrap();
         let store = FileStore::new(base_dir.path());
         // Small page size, so we will have 2 pages (14 bytes header, 5 bytes row + 7 bytes slot size)
-        let layout = PageDataLayout::new(32).unwrap();
+        let layout = PageDataLayout::new(44).unwrap();
         store.create(&layout, &table).unwrap();
 
         let btree = RefCell::new(store.read_btree(1).unwrap());
@@ -841,17 +2283,131 @@ mod tests {
         let result = btree.find(99).unwrap();
         assert!(result.is_some());
         // Vector, because in future indexes with multiple values may exist:
-        let indexes = vec![result.unwrap()];
+        let indexes = vec![index_position(result.unwrap()).unwrap()];
         let mut iter = IndexedRowIterator::new(&table, &store, &layout, indexes);
 
         let next = iter.next();
         assert!(next.is_some());
-        let next_row = next.unwrap();
+        let next_row = next.unwrap().unwrap();
         let cells = next_row.1.cells();
         assert_eq!(cells, &vec![Cell::Int(99), Cell::Byte(2)]);
         assert!(iter.next().is_none());
     }
 
+    #[test]
+    fn should_keep_slot_of_updated_row_if_it_fits_and_move_index_entries_otherwise() {
+        let schema = TableSchema::new(vec![
+            Column::new(1, "id", ColumnType::Int),
+            Column::new(2, "name", ColumnType::Varchar(30)),
+        ]);
+
+        let table = Table::new(1, "test".to_owned(), schema);
+        let base_dir = tempdir().unwrap();
+        let store = FileStore::new(base_dir.path());
+        let layout = PageDataLayout::new(76).unwrap();
+        store.create(&layout, &table).unwrap();
+        let btree = RefCell::new(store.read_btree(1).unwrap());
+        let access = TableAccess::new(table.clone(), &store, &layout)
+            .with_indexes(vec![(1, btree)]);
+
+        access.insert(&Row::new(vec![Cell::Int(1), Cell::Varchar("a".to_owned())])).unwrap();
+        access.insert(&Row::new(vec![Cell::Int(2), Cell::Varchar("b".to_owned())])).unwrap();
+        let location = |id: i32| index_position(store.read_btree(1).unwrap().find(id).unwrap().unwrap()).unwrap();
+        let before = location(1);
+
+        // longer, but still fits into the page: same slot, so the index entry stays valid
+        access.update(access.find("id", Cell::Int(1)).unwrap(), vec![("name", Cell::Varchar("abcdef".to_owned()))]).unwrap();
+        assert_eq!(location(1), before);
+        let (record, row) = access.find("id", Cell::Int(1)).unwrap().first().unwrap().unwrap();
+        assert_eq!((*record.page_id(), *record.record_index()), before);
+        assert_eq!(row.cells()[1], Cell::Varchar("abcdef".to_owned()));
+
+        // doesn't fit into the page any more: the row moves, and the entry of the unchanged id with it
+        let long = "x".repeat(30);
+        access.update(access.find("id", Cell::Int(2)).unwrap(), vec![("name", Cell::Varchar(long.clone()))]).unwrap();
+        assert_ne!(location(2).0, before.0);
+        let mut iter = IndexedRowIterator::new(&table, &store, &layout, vec![location(2)]);
+        let (_, row) = iter.next().unwrap().unwrap();
+        assert_eq!(row.cells(), &vec![Cell::Int(2), Cell::Varchar(long)]);
+        assert_eq!(access.find_all().unwrap().rows().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn should_only_read_pages_with_free_space_to_insert() {
+        let schema = TableSchema::new(vec![
+            Column::new(1, "id", ColumnType::Int),
+            Column::new(2, "name", ColumnType::Varchar(20)),
+        ]);
+
+        let table = Table::new(1, "test".to_owned(), schema);
+        let base_dir = tempdir().unwrap();
+        let store = FileStore::new(base_dir.path());
+        let layout = PageDataLayout::new(132).unwrap();
+        store.create(&layout, &table).unwrap();
+        let access = TableAccess::new(table.clone(), &store, &layout);
+
+        for id in 0..40 {
+            access.insert(&Row::new(vec![Cell::Int(id), Cell::Varchar(format!("name {}", id))])).unwrap();
+        }
+        let pages = store.read_metadata(&layout, &table).unwrap().number_of_pages();
+        assert!(pages > 5);
+
+        // the full pages are skipped: at most the last page is read
+        let before = store.io_stats();
+        access.insert(&Row::new(vec![Cell::Int(40), Cell::Varchar("name 40".to_owned())])).unwrap();
+        assert!(store.io_stats().since(&before).pages_read <= 1);
+        assert_eq!(access.find_all().unwrap().rows().unwrap().len(), 41);
+    }
+
+    #[test]
+    fn should_keep_the_address_of_a_moved_row_with_a_forwarding_pointer() {
+        let schema = TableSchema::new(vec![
+            Column::new(1, "id", ColumnType::Int),
+            Column::new(2, "name", ColumnType::Varchar(60)),
+        ]);
+
+        let table = Table::new(1, "test".to_owned(), schema);
+        let base_dir = tempdir().unwrap();
+        let store = FileStore::new(base_dir.path());
+        let layout = PageDataLayout::new(100).unwrap();
+        store.create(&layout, &table).unwrap();
+        let btree = RefCell::new(store.read_btree(1).unwrap());
+        let access = TableAccess::new(table.clone(), &store, &layout)
+            .with_indexes(vec![(1, btree)]);
+
+        access.insert(&Row::new(vec![Cell::Int(1), Cell::Varchar("first".to_owned())])).unwrap();
+        access.insert(&Row::new(vec![Cell::Int(2), Cell::Varchar("second".to_owned())])).unwrap();
+        let location = |id: i32| index_position(store.read_btree(1).unwrap().find(id).unwrap().unwrap()).unwrap();
+        let home = location(2);
+
+        // doesn't fit into the page any more: the row moves, its slot points to it
+        let long = "x".repeat(40);
+        access.update(access.find("id", Cell::Int(2)).unwrap(), vec![("name", Cell::Varchar(long.clone()))]).unwrap();
+        assert_eq!(location(2), home);
+        let (record, row) = access.find("id", Cell::Int(2)).unwrap().first().unwrap().unwrap();
+        assert_ne!(*record.page_id(), home.0);
+        assert_eq!(row.cells(), &vec![Cell::Int(2), Cell::Varchar(long)]);
+        let (_, row) = IndexedRowIterator::new(&table, &store, &layout, vec![home]).next().unwrap().unwrap();
+        assert_eq!(row.cells()[0], Cell::Int(2));
+
+        // moved again (and the id changed): still one pointer from the home slot
+        let longer = "y".repeat(45);
+        access.update(access.find("id", Cell::Int(2)).unwrap(), vec![("id", Cell::Int(3)), ("name", Cell::Varchar(longer.clone()))]).unwrap();
+        assert_eq!(location(3), home);
+        assert!(store.read_btree(1).unwrap().find(2).unwrap().is_none());
+        let (_, row) = IndexedRowIterator::new(&table, &store, &layout, vec![home]).next().unwrap().unwrap();
+        assert_eq!(row.cells(), &vec![Cell::Int(3), Cell::Varchar(longer)]);
+        // a scan sees the moved row once
+        assert_eq!(access.find_all().unwrap().rows().unwrap().len(), 2);
+
+        // the delete of the moved row removes the pointer, too
+        access.delete(access.find("id", Cell::Int(3)).unwrap()).unwrap();
+        let home_page = store.read_page(&layout, home.0, &table).unwrap();
+        assert_eq!(home_page.forward(home.1), None);
+        assert!(home_page.read_slot(home.1).is_none());
+        assert_eq!(access.find_all().unwrap().rows().unwrap().len(), 1);
+    }
+
     #[test]
     fn should_update_in_place_rows() {
         let schema = TableSchema::new(vec![
@@ -863,7 +2419,7 @@ mod tests {
         let base_dir = tempdir().unwrap();
         let store = FileStore::new(base_dir.path());
         // Small page size, so we will have 2 pages (14 bytes header, 5 bytes row + 7 bytes slot size)
-        let layout = PageDataLayout::new(32).unwrap();
+        let layout = PageDataLayout::new(44).unwrap();
         store.create(&layout, &table).unwrap();
 
         let access = TableAccess::new(table, &store, &layout);
@@ -886,15 +2442,45 @@ mod tests {
 
         // Assert: row with value 88 shouldn't exist anymore
         let result = access.find("value", Cell::Int(82)).unwrap();
-        let rows = result.rows();
+        let rows = result.rows().unwrap();
         assert_eq!(rows.len(), 0);
 
         // Assert: row with value 99 exists instead
         let result = access.find("value", Cell::Int(99)).unwrap();
-        let rows = result.rows();
+        let rows = result.rows().unwrap();
         assert_eq!(rows.len(), 1);
     }
 
+    #[test]
+    fn should_update_only_rows_with_the_expected_version() {
+        let schema = TableSchema::new(vec![
+            Column::new(1, "id", ColumnType::Int),
+            Column::new(2, "name", ColumnType::Varchar(10)),
+            Column::new(3, "version", ColumnType::Int),
+        ]);
+
+        let table = Table::new(1, "test".to_owned(), schema);
+        let base_dir = tempdir().unwrap();
+        let store = FileStore::new(base_dir.path());
+        let layout = PageDataLayout::new(128).unwrap();
+        store.create(&layout, &table).unwrap();
+
+        let access = TableAccess::new(table, &store, &layout);
+        access.insert(&Row::new(vec![Cell::Int(1), Cell::Varchar("first".to_owned()), Cell::Int(0)])).unwrap();
+
+        let updated = access.update_versioned(access.find("id", Cell::Int(1)).unwrap(), "version", 0, vec![("name", Cell::Varchar("second".to_owned()))]).unwrap();
+        assert_eq!(updated.rows().unwrap()[0].cells(), &vec![Cell::Int(1), Cell::Varchar("second".to_owned()), Cell::Int(1)]);
+
+        // a second writer that has read version 0, too
+        let result = access.update_versioned(access.find("id", Cell::Int(1)).unwrap(), "version", 0, vec![("name", Cell::Varchar("third".to_owned()))]);
+        assert!(matches!(result, Err(TableAccessError::StaleRow { expected: 0, found: 1 })));
+        assert!(access.update_versioned(access.find("id", Cell::Int(1)).unwrap(), "version", 1, vec![("version", Cell::Int(5))]).is_err());
+        assert!(access.update_versioned(access.find("id", Cell::Int(1)).unwrap(), "name", 1, vec![]).is_err());
+
+        let rows = access.find("id", Cell::Int(1)).unwrap().rows().unwrap();
+        assert_eq!(rows[0].1.cells(), &vec![Cell::Int(1), Cell::Varchar("second".to_owned()), Cell::Int(1)]);
+    }
+
 
     #[test]
     fn should_insert_two_rows() {
@@ -921,12 +2507,46 @@ mod tests {
         access.insert(&second_row).unwrap();
 
         let result = access.find_all().unwrap();
-        let rows = result.rows().into_iter().map(|(_, row)| row).collect::<Vec<Row>>();
+        let rows = result.rows().unwrap().into_iter().map(|(_, row)| row).collect::<Vec<Row>>();
         assert_eq!(rows.len(), 2);
         assert_eq!(rows[0].cells(), &[Cell::Varchar("Hans".to_owned())]);
         assert_eq!(rows[1].cells(), &[Cell::Varchar("Rabbit".to_owned())]);
     }
 
+    #[test]
+    fn should_insert_many_and_report_the_rejected_row() {
+        let schema = TableSchema::new(vec![
+            Column::new(1, "name", ColumnType::Varchar(10))
+        ]);
+
+        let table = Table::new(1, "test".to_owned(), schema);
+        let base_dir = tempdir().unwrap();
+        let store = FileStore::new(base_dir.path());
+        let layout = PageDataLayout::new(64).unwrap();
+        store.create(&layout, &table).unwrap();
+
+        let access = TableAccess::new(table, &store, &layout);
+
+        let rows: Vec<Row> = ["Hans", "Rabbit", "much too long", "Fox"].iter()
+            .map(|name| Row::new(vec![Cell::Varchar(name.to_string())]))
+            .collect();
+
+        assert_eq!(access.insert_many(&rows[..2]).unwrap(), 2);
+        let err = access.insert_many(&rows).unwrap_err();
+        let TableAccessError::InsertManyError { row, error } = err else {
+            panic!("Expected InsertManyError, got {:?}", err);
+        };
+        assert_eq!(row, 3);
+        assert!(matches!(*error, TableAccessError::InsertRowError(_)));
+
+        // no transactions: the rows before the rejected one stay inserted, the rows after it are not inserted
+        let names = access.find_all().unwrap().rows().unwrap().into_iter()
+            .map(|(_, row)| row.cells()[0].clone())
+            .collect::<Vec<Cell>>();
+        assert_eq!(names.len(), 4);
+        assert!(!names.contains(&Cell::Varchar("Fox".to_owned())));
+    }
+
     #[test]
     fn should_find_a_row() {
         let schema = TableSchema::new(vec![
@@ -957,7 +2577,7 @@ mod tests {
         access.insert(&second_row).unwrap();
 
         let result = access.find("name", Cell::Varchar("Hans".to_owned())).unwrap();
-        let rows = result.rows();
+        let rows = result.rows().unwrap();
         assert_eq!(rows.len(), 1);
         let row = rows.get(0).unwrap();
         assert!(matches!(row.1.cells().as_slice(), [Cell::Int(id), Cell::Varchar(name)] if *id == 1 && name == "Hans"));
@@ -993,7 +2613,7 @@ mod tests {
         access.insert(&second_row).unwrap();
 
         let result = access.find("name", Cell::Varchar("Hans".to_owned())).unwrap();
-        let rows = result.rows();
+        let rows = result.rows().unwrap();
         assert_eq!(rows.len(), 2);
         let row = rows.get(0).unwrap();
         assert!(matches!(row.1.cells().as_slice(), [Cell::Int(id), Cell::Varchar(name)] if *id == 1 && name == "Hans"));
@@ -1100,7 +2720,7 @@ mod tests {
         assert_eq!(result.schema.columns[2], Column::new(1, "person_id", ColumnType::Int));
         assert_eq!(result.schema.columns[3], Column::new(2, "address", ColumnType::Varchar(64)));
 
-        let rows = result.rows();
+        let rows = result.rows().unwrap();
         assert_eq!(rows.len(), 2);
         let cells_hans = rows[0].cells();
         assert_eq!(
@@ -1113,4 +2733,171 @@ mod tests {
             vec![Cell::Int(2), Cell::Varchar("Rabbit".to_owned()), Cell::Int(2), Cell::Varchar("Bergmansweg 10".to_owned())]);
     }
 
-}
\ No newline at end of file
+
+    #[test]
+    fn should_merge_join_ordered_inputs() {
+        let base_path = tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        let persons = db.create_table("persons", vec![("id", ColumnType::Int, false, true), ("name", ColumnType::Varchar(10), false, false)]).unwrap();
+        let addresses = db.create_table("addresses", vec![("person_id", ColumnType::Int), ("address", ColumnType::Varchar(20))]).unwrap();
+        let person_access = db.table_access(persons).unwrap();
+        let address_access = db.table_access(addresses).unwrap();
+        for id in [5, 1, 4, 2, 3] {
+            person_access.insert(&Row::new(vec![Cell::Int(id), Cell::Varchar(format!("p{}", id))])).unwrap();
+        }
+        for (person_id, address) in [(4, "a"), (2, "b"), (NULL_INT, "c"), (4, "d"), (9, "e"), (1, "f"), (2, "g")] {
+            address_access.insert(&Row::new(vec![Cell::Int(person_id), Cell::Varchar(address.to_owned())])).unwrap();
+        }
+
+        let by_index = person_access.scan_by_index("id").unwrap();
+        assert_eq!(by_index.ordered_by(), Some(0));
+        let ids: Vec<Cell> = by_index.rows().unwrap().into_iter().map(|(_, row)| row.cells()[0].clone()).collect();
+        assert_eq!(ids, (1..=5).map(Cell::Int).collect::<Vec<Cell>>());
+        assert!(person_access.scan_by_index("name").is_err());
+
+        let sorted_addresses = || address_access.scan_sorted(&[SortKey::asc("person_id").nulls_first()]).unwrap();
+        let merged = person_access.scan_by_index("id").unwrap()
+            .merge_join(sorted_addresses(), "id", "person_id").unwrap();
+        assert_eq!(merged.schema().columns.len(), 4);
+        assert_eq!(merged.ordered_by(), Some(0));
+        let merged = merged.rows().unwrap();
+
+        let mut hashed = person_access.find_all().unwrap()
+            .hash_join(address_access.find_all().unwrap(), "id", "person_id").unwrap()
+            .rows().unwrap();
+        hashed.sort_by(|a, b| a.cells()[0].expect_int("id").unwrap().cmp(&b.cells()[0].expect_int("id").unwrap()));
+        assert_eq!(merged, hashed);
+        let addresses: Vec<Cell> = merged.iter().map(|row| row.cells()[3].clone()).collect();
+        assert_eq!(addresses, ["f", "b", "g", "a", "d"].map(|a| Cell::Varchar(a.to_owned())));
+
+        // join() picks the merge join only for ordered inputs, both give the same rows
+        let auto = person_access.scan_by_index("id").unwrap().join(sorted_addresses(), "id", "person_id").unwrap();
+        assert_eq!(auto.ordered_by(), Some(0));
+        assert_eq!(auto.rows().unwrap(), merged);
+        let fallback = person_access.find_all().unwrap().join(address_access.find_all().unwrap(), "id", "person_id").unwrap();
+        assert_eq!(fallback.ordered_by(), None);
+        assert_eq!(fallback.rows().unwrap().len(), 5);
+
+        // DESC is not an order for the merge join
+        assert_eq!(address_access.scan_sorted(&[SortKey::desc("person_id")]).unwrap().ordered_by(), None);
+        let unordered = person_access.scan_by_index("id").unwrap()
+            .merge_join(address_access.find_all().unwrap(), "id", "person_id").unwrap();
+        assert!(unordered.rows().is_err());
+    }
+
+    #[test]
+    fn should_semi_and_anti_join() {
+        let base_path = tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        let persons = db.create_table("persons", vec![("id", ColumnType::Int), ("name", ColumnType::Varchar(20))]).unwrap();
+        let orders = db.create_table("orders", vec![("person_id", ColumnType::Int)]).unwrap();
+        let person_access = db.table_access(persons).unwrap();
+        let order_access = db.table_access(orders).unwrap();
+        for id in [1, 2, 3, NULL_INT] {
+            person_access.insert(&Row::new(vec![Cell::Int(id), Cell::Varchar(format!("p{}", id))])).unwrap();
+        }
+        for person_id in [3, 1, 3, NULL_INT] {
+            order_access.insert(&Row::new(vec![Cell::Int(person_id)])).unwrap();
+        }
+        let ids = |result: QueryResult<(Record, Row)>| -> Vec<Cell> {
+            result.rows().unwrap().into_iter().map(|(_, row)| row.cells()[0].clone()).collect()
+        };
+
+        let semi = person_access.find_all().unwrap().semi_join(order_access.find_all().unwrap(), "id", "person_id").unwrap();
+        assert_eq!(semi.schema(), person_access.table().schema());
+        assert_eq!(ids(semi), vec![Cell::Int(1), Cell::Int(3)]);
+        let anti = person_access.find_all().unwrap().anti_join(order_access.find_all().unwrap(), "id", "person_id").unwrap();
+        assert_eq!(ids(anti), vec![Cell::Int(2), Cell::Int(NULL_INT)]);
+        assert!(person_access.find_all().unwrap().semi_join(order_access.find_all().unwrap(), "name", "person_id").is_err());
+    }
+
+    #[test]
+    fn should_reuse_materialized_result_in_several_joins() {
+        let base_path = tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        let persons = db.create_table("persons", vec![("id", ColumnType::Int)]).unwrap();
+        let orders = db.create_table("orders", vec![("person_id", ColumnType::Int), ("total", ColumnType::Int)]).unwrap();
+        let person_access = db.table_access(persons).unwrap();
+        let order_access = db.table_access(orders).unwrap();
+        for id in [1, 2, 3] {
+            person_access.insert(&Row::new(vec![Cell::Int(id)])).unwrap();
+        }
+        for (person_id, total) in [(1, 50), (3, 200), (3, 300)] {
+            order_access.insert(&Row::new(vec![Cell::Int(person_id), Cell::Int(total)])).unwrap();
+        }
+
+        let large = order_access.find_all().unwrap()
+            .filter(|(_, row)| matches!(row.cells()[1], Cell::Int(total) if total > 100))
+            .materialize().unwrap();
+        assert_eq!(large.len(), 2);
+        let reads_before = db.io_stats().pages_read;
+
+        let joined = person_access.find_all().unwrap().hash_join(large.result(), "id", "person_id").unwrap();
+        assert_eq!(joined.rows().unwrap().len(), 2);
+        let buyers = person_access.find_all().unwrap().semi_join(large.result(), "id", "person_id").unwrap();
+        let ids: Vec<Cell> = buyers.rows().unwrap().into_iter().map(|(_, row)| row.cells()[0].clone()).collect();
+        assert_eq!(ids, vec![Cell::Int(3)]);
+        // the orders are not read again, only the persons
+        assert_eq!(db.io_stats().pages_read - reads_before, 2);
+        assert_eq!(large.result().schema(), order_access.table().schema());
+    }
+
+    #[test]
+    fn should_insert_map_with_defaults_for_missing_columns() {
+        let schema = TableSchema::new(vec![
+            Column::new(1, "id", ColumnType::Int),
+            Column::new(2, "name", ColumnType::Varchar(10)),
+            Column::new(3, "age", ColumnType::Int),
+        ]);
+
+        let table = Table::new(1, "test".to_owned(), schema);
+        let base_dir = tempdir().unwrap();
+        let store = FileStore::new(base_dir.path());
+        let layout = PageDataLayout::new(64).unwrap();
+        store.create(&layout, &table).unwrap();
+
+        let access = TableAccess::new(table, &store, &layout);
+
+        let values = HashMap::from([
+            ("name", Cell::Varchar("Hans".to_owned())),
+            ("id", Cell::Int(1)),
+        ]);
+        access.insert_map(&values).unwrap();
+
+        let values = BTreeMap::from([("id".to_owned(), Cell::Int(2))]);
+        access.insert_map(&values).unwrap();
+
+        let rows = access.find_all().unwrap().rows().unwrap();
+        assert_eq!(rows[0].1.cells(), &vec![Cell::Int(1), Cell::Varchar("Hans".to_owned()), Cell::Int(NULL_INT)]);
+        assert_eq!(rows[1].1.cells(), &vec![Cell::Int(2), Cell::Varchar("".to_owned()), Cell::Int(NULL_INT)]);
+
+        let values = HashMap::from([("unknown", Cell::Int(1))]);
+        assert!(access.insert_map(&values).is_err());
+    }
+
+    #[test]
+    fn insert_map_should_require_indexed_columns() {
+        let schema = TableSchema::new(vec![
+            Column::new(1, "value", ColumnType::Int),
+            Column::new(2, "byte", ColumnType::Byte),
+        ]);
+
+        let table = Table::new(1, "test".to_owned(), schema);
+        let base_dir = tempdir().unwrap();
+        let store = FileStore::new(base_dir.path());
+        let layout = PageDataLayout::new(32).unwrap();
+        store.create(&layout, &table).unwrap();
+
+        let btree = RefCell::new(store.read_btree(1).unwrap());
+        let access = TableAccess::new(table, &store, &layout)
+            .with_indexes(vec![(1, btree)]);
+
+        let values = HashMap::from([("byte", Cell::Byte(1))]);
+        let res = access.insert_map(&values);
+        assert!(res.is_err());
+        assert!(res.unwrap_err().to_string().contains("No value set for column 'value'"));
+    }
+}
@@ -0,0 +1,160 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    database::{Database, virtual_table::{Constraint, VirtualRows, VirtualTable, VirtualTableError}},
+    store::Store,
+    table::{Column, ColumnType, TableSchema, identifier::Identifier, table::{Cell, Row}},
+};
+
+// Table functions: a virtual table created from arguments, in SQL `SELECT ... FROM name(arg, ...)`.
+// Built in:
+// - generate_series(start, end [, step]): the integers from start to end (inclusive), step defaults to 1,
+//   a negative step counts down. One INT column "generate_series".
+// - unnest(value, ...): one row per argument. There is no array type, so the elements are passed as arguments,
+//   they must have the same type. One column "unnest" of their type.
+// Users can add their own with Database::register_table_function, a name of a built-in function is replaced.
+
+pub type TableFunction = dyn Fn(&[Cell]) -> Result<Rc<dyn VirtualTable>, VirtualTableError>;
+
+pub(super) fn builtin_table_functions() -> HashMap<String, Rc<TableFunction>> {
+    let mut functions: HashMap<String, Rc<TableFunction>> = HashMap::new();
+    functions.insert("generate_series".to_owned(), Rc::new(GenerateSeries::from_args));
+    functions.insert("unnest".to_owned(), Rc::new(Unnest::from_args));
+    functions
+}
+
+impl<S: Store> Database<S> {
+    pub fn register_table_function<F>(&self, name: &str, function: F)
+    where
+        F: Fn(&[Cell]) -> Result<Rc<dyn VirtualTable>, VirtualTableError> + 'static,
+    {
+        self.table_functions.borrow_mut().insert(Identifier::normalize(name), Rc::new(function));
+    }
+
+    /// The virtual table of the function for the arguments
+    pub fn call_table_function(&self, name: &str, args: &[Cell]) -> Result<Rc<dyn VirtualTable>, VirtualTableError> {
+        let function = self.table_functions.borrow()
+            .get(&Identifier::normalize(name))
+            .cloned()
+            .ok_or_else(|| VirtualTableError::Failed(name.to_owned(), "table function does not exist".to_owned()))?;
+        function(args)
+    }
+}
+
+pub struct GenerateSeries {
+    start: i32,
+    end: i32,
+    step: i32,
+}
+
+impl GenerateSeries {
+    pub fn new(start: i32, end: i32, step: i32) -> Result<Self, VirtualTableError> {
+        if step == 0 {
+            return Err(VirtualTableError::Failed("generate_series".to_owned(), "step must not be 0".to_owned()));
+        }
+        Ok(Self { start, end, step })
+    }
+
+    fn from_args(args: &[Cell]) -> Result<Rc<dyn VirtualTable>, VirtualTableError> {
+        match args {
+            [Cell::Int(start), Cell::Int(end)] => Ok(Rc::new(Self::new(*start, *end, 1)?)),
+            [Cell::Int(start), Cell::Int(end), Cell::Int(step)] => Ok(Rc::new(Self::new(*start, *end, *step)?)),
+            _ => Err(VirtualTableError::Failed("generate_series".to_owned(), "expects (start INT, end INT [, step INT])".to_owned())),
+        }
+    }
+}
+
+impl VirtualTable for GenerateSeries {
+    fn schema(&self) -> TableSchema {
+        TableSchema::of([Column::new(1, "generate_series", ColumnType::Int)])
+    }
+
+    fn scan(&self, _constraints: &[Constraint]) -> Result<VirtualRows<'_>, VirtualTableError> {
+        // i64, so the last step can't overflow
+        let (end, step) = (self.end as i64, self.step as i64);
+        let values = std::iter::successors(Some(self.start as i64), move |value| Some(value + step))
+            .take_while(move |value| match step > 0 {
+                true => *value <= end,
+                false => *value >= end,
+            });
+        Ok(Box::new(values.map(|value| Ok(Row::new(vec![Cell::Int(value as i32)])))))
+    }
+}
+
+pub struct Unnest {
+    values: Vec<Cell>,
+    col_type: ColumnType,
+}
+
+impl Unnest {
+    pub fn new(values: Vec<Cell>) -> Result<Self, VirtualTableError> {
+        let error = |msg: &str| VirtualTableError::Failed("unnest".to_owned(), msg.to_owned());
+        let col_type = match values.first() {
+            Some(Cell::Int(_)) => ColumnType::Int,
+            Some(Cell::Byte(_)) => ColumnType::Byte,
+            Some(Cell::Blob(_)) => ColumnType::Blob,
+            Some(Cell::Point(_)) => ColumnType::Point,
+            Some(Cell::Varchar(_)) => {
+                let len = values.iter()
+                    .map(|value| match value {
+                        Cell::Varchar(value) => value.len(),
+                        _ => 0,
+                    })
+                    .max()
+                    .unwrap_or(0);
+                ColumnType::Varchar(u16::try_from(len.max(1)).map_err(|_| error("value is too long"))?)
+            },
+            None => return Err(error("expects at least one value")),
+        };
+        if values.iter().any(|value| std::mem::discriminant(value) != std::mem::discriminant(&values[0])) {
+            return Err(error("all values must have the same type"));
+        }
+        Ok(Self { values, col_type })
+    }
+
+    fn from_args(args: &[Cell]) -> Result<Rc<dyn VirtualTable>, VirtualTableError> {
+        Ok(Rc::new(Self::new(args.to_vec())?))
+    }
+}
+
+impl VirtualTable for Unnest {
+    fn schema(&self) -> TableSchema {
+        TableSchema::of([Column::new(1, "unnest", self.col_type.clone())])
+    }
+
+    fn scan(&self, _constraints: &[Constraint]) -> Result<VirtualRows<'_>, VirtualTableError> {
+        Ok(Box::new(self.values.iter().map(|value| Ok(Row::new(vec![value.clone()])))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::{Database, table_functions::{GenerateSeries, Unnest}, virtual_table::VirtualTable}, store::file_store::FileStore, table::{ColumnType, table::Cell}};
+
+    fn values(table: &dyn VirtualTable) -> Vec<Cell> {
+        table.scan(&[]).unwrap().map(|row| row.unwrap().cells()[0].clone()).collect()
+    }
+
+    #[test]
+    fn should_generate_series() {
+        assert_eq!(values(&GenerateSeries::new(1, 7, 3).unwrap()), vec![Cell::Int(1), Cell::Int(4), Cell::Int(7)]);
+        assert_eq!(values(&GenerateSeries::new(3, 1, -1).unwrap()), vec![Cell::Int(3), Cell::Int(2), Cell::Int(1)]);
+        assert!(values(&GenerateSeries::new(3, 1, 1).unwrap()).is_empty());
+        assert_eq!(values(&GenerateSeries::new(i32::MAX - 1, i32::MAX, 5).unwrap()), vec![Cell::Int(i32::MAX - 1)]);
+        assert!(GenerateSeries::new(1, 2, 0).is_err());
+    }
+
+    #[test]
+    fn should_unnest_values_of_one_type() {
+        let unnest = Unnest::new(vec![Cell::Varchar("a".to_owned()), Cell::Varchar("bcd".to_owned())]).unwrap();
+        assert_eq!(unnest.schema().columns[0].col_type, ColumnType::Varchar(3));
+        assert_eq!(values(&unnest).len(), 2);
+        assert!(Unnest::new(vec![Cell::Int(1), Cell::Varchar("a".to_owned())]).is_err());
+        assert!(Unnest::new(vec![]).is_err());
+
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        assert_eq!(values(db.call_table_function("GENERATE_SERIES", &[Cell::Int(1), Cell::Int(2)]).unwrap().as_ref()), vec![Cell::Int(1), Cell::Int(2)]);
+        assert!(db.call_table_function("missing", &[]).is_err());
+    }
+}
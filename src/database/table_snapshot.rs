@@ -0,0 +1,103 @@
+use tempfile::TempDir;
+
+use crate::{
+    data::page::{PageDataLayout, PageId},
+    database::table_access::{TableAccess, TableAccessError},
+    store::{Store, StoreError, file_store::FileStore},
+    table::table::Table,
+};
+
+// A frozen read view of a table (TableAccess::snapshot), e.g. for an export or a long scan that must not see the
+// rows inserted, updated or deleted while it runs. There is no MVCC yet: an insert can go into any page with free
+// space and an update changes the page in place, so keeping the number of pages is not enough. Instead the pages
+// (and the overflow pages of the BLOB values) are copied into a temporary FileStore when the snapshot is taken,
+// the copy is deleted with the snapshot. Taking it reads the whole table once.
+//
+// The snapshot has no indexes, find on it scans the pages.
+
+const COPY_BATCH_SIZE: usize = 64;
+
+pub struct TableSnapshot {
+    store: FileStore,
+    table: Table,
+    layout: PageDataLayout,
+    pages: PageId,
+    // keeps the copied files alive until the snapshot is dropped
+    _copy_dir: TempDir,
+}
+
+impl TableSnapshot {
+    pub(crate) fn copy<S: Store>(store: &S, layout: &PageDataLayout, table: &Table) -> Result<Self, StoreError> {
+        let copy_dir = tempfile::tempdir()?;
+        let copy = FileStore::new(copy_dir.path());
+        let pages = copy_pages(store, &copy, layout, table)?;
+        if table.has_blobs() {
+            copy_pages(store, &copy, layout, &table.overflow_table())?;
+        }
+        Ok(Self { store: copy, table: table.clone(), layout: layout.clone(), pages, _copy_dir: copy_dir })
+    }
+
+    /// Number of pages of the table when the snapshot was taken
+    pub fn pages(&self) -> PageId {
+        self.pages
+    }
+
+    /// Read access to the rows of the snapshot. Changes through it are lost with the snapshot.
+    pub fn access(&self) -> TableAccess<'_, FileStore> {
+        TableAccess::new(self.table.clone(), &self.store, &self.layout)
+    }
+
+    pub fn row_count(&self) -> Result<usize, TableAccessError> {
+        let access = self.access();
+        let rows = access.find_all()?.rows()?.len();
+        Ok(rows)
+    }
+}
+
+// returns the number of copied pages
+fn copy_pages<S: Store>(from: &S, to: &FileStore, layout: &PageDataLayout, table: &Table) -> Result<PageId, StoreError> {
+    let pages = from.read_metadata(layout, table)?.number_of_pages();
+    to.create(layout, table)?;
+    let page_ids: Vec<PageId> = (1..=pages).collect();
+    for batch in page_ids.chunks(COPY_BATCH_SIZE) {
+        let pages = from.read_pages(layout, batch, table)?;
+        for _ in batch {
+            to.allocate_page(layout, table)?;
+        }
+        to.write_pages(layout, &pages.iter().collect::<Vec<_>>(), table)?;
+    }
+    Ok(pages)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::Database, store::file_store::FileStore, table::{ColumnType, table::{Cell, Row}}};
+
+    #[test]
+    fn should_not_see_the_changes_after_the_snapshot() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        let persons = db.create_table("persons", vec![("id", ColumnType::Int, false, true), ("name", ColumnType::Varchar(100), false, false), ("photo", ColumnType::Blob, false, false)]).unwrap();
+        let access = db.table_access(persons).unwrap();
+        for id in 0..200 {
+            access.insert(&Row::new(vec![Cell::Int(id), Cell::Varchar(format!("person {}", id)), Cell::Blob(vec![id as u8; 10])])).unwrap();
+        }
+        access.delete(access.find("id", Cell::Int(5)).unwrap()).unwrap();
+
+        let snapshot = access.snapshot().unwrap();
+        assert!(snapshot.pages() > 1);
+        // the insert fills the free slot of the deleted row, so a page count alone wouldn't hide it
+        access.insert(&Row::new(vec![Cell::Int(200), Cell::Varchar("new".to_owned()), Cell::Blob(vec![])])).unwrap();
+        access.update(access.find("id", Cell::Int(10)).unwrap(), vec![("name", Cell::Varchar("changed".to_owned()))]).unwrap();
+        access.delete(access.find("id", Cell::Int(20)).unwrap()).unwrap();
+
+        assert_eq!(snapshot.row_count().unwrap(), 199);
+        let snapshot_access = snapshot.access();
+        let row = |id: i32| snapshot_access.find("id", Cell::Int(id)).unwrap().rows().unwrap();
+        assert_eq!(row(10)[0].1.cells()[1], Cell::Varchar("person 10".to_owned()));
+        assert_eq!(row(20)[0].1.cells()[2], Cell::Blob(vec![20; 10]));
+        assert!(row(200).is_empty());
+        assert_eq!(access.find_all().unwrap().rows().unwrap().len(), 199);
+    }
+}
@@ -0,0 +1,96 @@
+use crate::{
+    database::{CreateTableError, Database, DatabaseError, table_access::{IntoRow, QueryResult, TableAccess}},
+    store::Store,
+    table::{identifier::Identifier, table::Table},
+};
+
+// A temporary table holds an intermediate result, so that several following queries can read it
+// (e.g. from the REPL: SELECT ... FROM _temp_1 WHERE ...) without computing it again.
+// There is no separate temp store: it is a table of the database with a reserved name (_temp_<n>), so it is not
+// dumped and its row changes are not tracked by the statistics. The handle drops it when it goes out of scope.
+// If the process stops before, the table stays in the database and has to be dropped by hand.
+pub const TEMP_TABLE_PREFIX: &str = "_temp_";
+
+pub struct TempTable<'db, S: Store> {
+    db: &'db Database<S>,
+    table: Table,
+    rows: usize,
+}
+
+impl<'db, S: Store> TempTable<'db, S> {
+    pub fn name(&self) -> &str {
+        self.table.name()
+    }
+
+    pub fn table(&self) -> &Table {
+        &self.table
+    }
+
+    /// Number of rows written into the table
+    pub fn len(&self) -> usize {
+        self.rows
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows == 0
+    }
+
+    pub fn table_access(&self) -> Result<TableAccess<'db, S>, DatabaseError> {
+        self.db.table_access(self.table.clone())
+    }
+}
+
+impl<S: Store> Drop for TempTable<'_, S> {
+    fn drop(&mut self) {
+        // drop can't return the error, the table stays then (like after a crash)
+        let _ = self.db.drop_table(self.table.name());
+    }
+}
+
+impl<S: Store> Database<S> {
+    /// Writes the rows into a new temporary table (see TempTable), it gets the next free name _temp_<n>
+    pub fn create_temp_table_from<'r, I: IntoRow + 'r>(&self, source: QueryResult<'r, I>) -> Result<TempTable<'_, S>, CreateTableError> {
+        let mut n = 1;
+        let name = loop {
+            let name = format!("{}{}", TEMP_TABLE_PREFIX, n);
+            match self.read_table(&name) {
+                Err(DatabaseError::TableNotFound(_)) => break name,
+                Err(err) => return Err(err.into()),
+                Ok(_) => n += 1,
+            }
+        };
+
+        let (table, rows) = self.create_table_with_identifier_from(Identifier::parse(&name)?, source)?;
+        Ok(TempTable { db: self, table, rows })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::{Database, DatabaseError}, store::file_store::FileStore, table::{ColumnType, table::{Cell, Row}}};
+
+    #[test]
+    fn should_drop_the_temp_table_with_its_handle() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        db.create_table("t", vec![("id", ColumnType::Int)]).unwrap();
+        let access = db.table_access(db.read_table("t").unwrap()).unwrap();
+        for i in 0..5 {
+            access.insert(&Row::new(vec![Cell::Int(i)])).unwrap();
+        }
+
+        let first = db.create_temp_table_from(access.find_all().unwrap().filter(|(_, row)| row.cells()[0] != Cell::Int(0))).unwrap();
+        let second = db.create_temp_table_from(access.find_all().unwrap()).unwrap();
+        assert_eq!((first.name(), first.len()), ("_temp_1", 4));
+        assert_eq!((second.name(), second.len()), ("_temp_2", 5));
+        assert_eq!(first.table_access().unwrap().find_all().unwrap().rows().unwrap().len(), 4);
+
+        drop(first);
+        assert!(matches!(db.read_table("_temp_1"), Err(DatabaseError::TableNotFound(_))));
+        // the free name is used again
+        let third = db.create_temp_table_from(access.find_all().unwrap()).unwrap();
+        assert_eq!(third.name(), "_temp_1");
+        assert!(db.read_table("_temp_2").is_ok());
+    }
+}
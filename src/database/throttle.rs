@@ -0,0 +1,153 @@
+use std::{rc::Rc, time::Duration};
+
+use crate::{clock::{Clock, SystemClock}, database::Database, store::Store};
+
+// I/O limits for maintenance operations (analyze, vacuum, disk_usage, dump, snapshot_stream and the page-wise load
+// of imports with deferred constraints, which also builds the indexes). Foreground reads and writes
+// are never throttled.
+//
+// A Throttle counts the pages and bytes of one operation and sleeps as soon as the operation is faster
+// than the limits allow since its start.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ResourceConfig {
+    pub max_pages_per_sec: Option<u32>,
+    pub max_bytes_per_sec: Option<u64>,
+}
+
+impl ResourceConfig {
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.max_pages_per_sec.is_none() && self.max_bytes_per_sec.is_none()
+    }
+}
+
+pub struct Throttle {
+    config: ResourceConfig,
+    clock: Rc<dyn Clock>,
+    started: Duration,
+    pages: u64,
+    bytes: u64,
+    slept: Duration,
+}
+
+impl Throttle {
+    pub fn new(config: ResourceConfig) -> Self {
+        Self::with_clock(config, Rc::new(SystemClock::default()))
+    }
+
+    pub fn with_clock(config: ResourceConfig, clock: Rc<dyn Clock>) -> Self {
+        Self { config, started: clock.monotonic(), clock, pages: 0, bytes: 0, slept: Duration::ZERO }
+    }
+
+    /// Counts the I/O and sleeps if the limits are exceeded
+    pub fn consume(&mut self, pages: u64, bytes: u64) {
+        if self.config.is_unlimited() {
+            return;
+        }
+        self.pages += pages;
+        self.bytes += bytes;
+
+        let elapsed = self.clock.monotonic().saturating_sub(self.started);
+        let delay = self.required_time().saturating_sub(elapsed);
+        if !delay.is_zero() {
+            self.clock.sleep(delay);
+            self.slept += delay;
+        }
+    }
+
+    // minimum time for the I/O so far
+    fn required_time(&self) -> Duration {
+        let by_pages = self.config.max_pages_per_sec
+            .map(|max| self.pages as f64 / max.max(1) as f64)
+            .unwrap_or(0.0);
+        let by_bytes = self.config.max_bytes_per_sec
+            .map(|max| self.bytes as f64 / max.max(1) as f64)
+            .unwrap_or(0.0);
+        Duration::from_secs_f64(by_pages.max(by_bytes))
+    }
+
+    /// Total time this operation was delayed
+    pub fn slept(&self) -> Duration {
+        self.slept
+    }
+}
+
+impl<S: Store> Database<S> {
+    pub fn with_resource_config(mut self, config: ResourceConfig) -> Self {
+        self.resource_config = config;
+        self
+    }
+
+    pub fn resource_config(&self) -> ResourceConfig {
+        self.resource_config
+    }
+
+    // a new throttle for a maintenance operation
+    pub(crate) fn throttle(&self) -> Throttle {
+        Throttle::with_clock(self.resource_config, self.clock.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{rc::Rc, time::{Duration, Instant, UNIX_EPOCH}};
+
+    use crate::{clock::{Clock, ManualClock}, database::{Database, throttle::{ResourceConfig, Throttle}}, store::file_store::FileStore, table::{ColumnType, table::{Cell, Row}}};
+
+    #[test]
+    fn should_compute_required_time_from_limits() {
+        let mut throttle = Throttle::new(ResourceConfig { max_pages_per_sec: Some(100), max_bytes_per_sec: Some(1000) });
+        throttle.pages = 50;
+        throttle.bytes = 100;
+        assert_eq!(throttle.required_time(), Duration::from_millis(500));
+        throttle.bytes = 2000;
+        assert_eq!(throttle.required_time(), Duration::from_secs(2));
+
+        let mut unlimited = Throttle::new(ResourceConfig::unlimited());
+        unlimited.consume(1_000_000, 1 << 40);
+        assert_eq!(unlimited.slept(), Duration::ZERO);
+    }
+
+    #[test]
+    fn should_throttle_analyze() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()))
+            .with_resource_config(ResourceConfig { max_pages_per_sec: Some(50), max_bytes_per_sec: None });
+        db.drop_create().unwrap();
+        let table = db.create_table("t", vec![("id", ColumnType::Int), ("name", ColumnType::Varchar(1000))]).unwrap();
+        let access = db.table_access(table).unwrap();
+        for i in 0..20 {
+            access.insert(&Row::new(vec![Cell::Int(i), Cell::Varchar("x".repeat(1000))])).unwrap();
+        }
+
+        let start = Instant::now();
+        let statistics = db.analyze("t").unwrap();
+        assert!(statistics.pages >= 5);
+        // the first pages are read within the allowed rate
+        assert!(start.elapsed() >= Duration::from_millis(20 * (statistics.pages as u64 - 1)));
+    }
+
+    #[test]
+    fn should_sleep_on_the_clock_of_the_database() {
+        let base_path = tempfile::tempdir().unwrap();
+        let clock = Rc::new(ManualClock::new(UNIX_EPOCH));
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()))
+            .with_resource_config(ResourceConfig { max_pages_per_sec: Some(2), max_bytes_per_sec: None })
+            .with_clock(clock.clone());
+        db.drop_create().unwrap();
+        let table = db.create_table("t", vec![("id", ColumnType::Int), ("name", ColumnType::Varchar(1000))]).unwrap();
+        let access = db.table_access(table).unwrap();
+        for i in 0..10 {
+            access.insert(&Row::new(vec![Cell::Int(i), Cell::Varchar("x".repeat(1000))])).unwrap();
+        }
+
+        let start = Instant::now();
+        let statistics = db.analyze("t").unwrap();
+        // no real sleep, but the clock moved by the time needed for all pages
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(clock.monotonic(), Duration::from_millis(500 * statistics.pages as u64));
+    }
+}
@@ -0,0 +1,312 @@
+use std::{collections::BTreeMap, time::{Duration, UNIX_EPOCH}};
+
+use thiserror::Error;
+
+use crate::{
+    database::{CreateColumnCommand, CreateTableError, Database, DatabaseError, NULL_INT, table_access::{QueryResult, TableAccessError}},
+    store::Store,
+    table::{ColumnType, table::{Cell, Row, Table}},
+};
+
+// Time-partitioned table: the rows of a time series are written into one table per day (or week), so old data can be
+// dropped with its table instead of deleting rows, and a query over a time range only reads the tables of the range.
+// - the base table (created as usual) defines the schema, it holds no rows
+// - the timestamp column is an INT column with the seconds since the Unix epoch (UTC)
+// - partition tables are named <base>_<yyyymmdd> by the first day of their period (weeks start on Monday), they
+//   are created on the first insert into the period and found again by their name in the catalog
+// - the partitions have the unique indexes of the base table, a value is unique within its partition only
+// - prune() drops the partitions that ended before now - retention (database clock), an insert creating a new
+//   partition prunes, too. Rows older than the retention window are rejected.
+const SECONDS_PER_DAY: i64 = 86_400;
+
+#[derive(Debug, Error)]
+pub enum TimeSeriesError {
+    #[error("TimeSeriesError - column '{0}' must be an INT column with the seconds since the epoch")]
+    InvalidTimeColumn(String),
+    #[error("TimeSeriesError - row has no timestamp in column '{0}'")]
+    MissingTimestamp(String),
+    #[error("TimeSeriesError - timestamp {0} is older than the retention window")]
+    OutsideRetention(i32),
+    #[error("TimeSeriesError - {0}")]
+    DatabaseError(String),
+}
+
+impl From<DatabaseError> for TimeSeriesError {
+    fn from(err: DatabaseError) -> Self {
+        TimeSeriesError::DatabaseError(err.to_string())
+    }
+}
+
+impl From<CreateTableError> for TimeSeriesError {
+    fn from(err: CreateTableError) -> Self {
+        TimeSeriesError::DatabaseError(err.to_string())
+    }
+}
+
+impl From<TableAccessError> for TimeSeriesError {
+    fn from(err: TableAccessError) -> Self {
+        TimeSeriesError::DatabaseError(err.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Partitioning {
+    Daily,
+    Weekly,
+}
+
+impl Partitioning {
+    fn days(&self) -> i64 {
+        match self {
+            Partitioning::Daily => 1,
+            Partitioning::Weekly => 7,
+        }
+    }
+
+    /// First day (since the epoch) of the period that contains the timestamp
+    fn period_start(&self, timestamp: i64) -> i64 {
+        let day = timestamp.div_euclid(SECONDS_PER_DAY);
+        match self {
+            Partitioning::Daily => day,
+            // 1970-01-01 was a Thursday
+            Partitioning::Weekly => day - (day + 3).rem_euclid(7),
+        }
+    }
+}
+
+pub struct TimeSeriesTable<'db, S: Store> {
+    db: &'db Database<S>,
+    base: Table,
+    time_column: usize,
+    partitioning: Partitioning,
+    retention: Option<Duration>,
+}
+
+impl<'db, S: Store> TimeSeriesTable<'db, S> {
+    /// Partitions older than the window are dropped (by default nothing is dropped)
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    pub fn base(&self) -> &Table {
+        &self.base
+    }
+
+    /// Names of the partition tables, the oldest first
+    pub fn partitions(&self) -> Result<Vec<String>, TimeSeriesError> {
+        Ok(self.partition_tables()?.into_values().collect())
+    }
+
+    pub fn insert(&self, row: &Row) -> Result<(), TimeSeriesError> {
+        let start = self.partition_of(row)?;
+        let table = self.partition_table(start)?;
+        self.db.table_access(table)?.insert(row)?;
+        Ok(())
+    }
+
+    /// Inserts the rows with one bulk insert per partition
+    pub fn insert_all(&self, rows: Vec<Row>) -> Result<usize, TimeSeriesError> {
+        let mut by_partition: BTreeMap<i64, Vec<Row>> = BTreeMap::new();
+        for row in rows {
+            by_partition.entry(self.partition_of(&row)?).or_default().push(row);
+        }
+
+        let mut count = 0;
+        for (start, rows) in by_partition {
+            let table = self.partition_table(start)?;
+            count += self.db.table_access(table)?.insert_all(rows)?;
+        }
+        Ok(count)
+    }
+
+    /// Rows with from <= timestamp < to, only the partitions that overlap the range are read
+    pub fn range(&self, from: i32, to: i32) -> Result<QueryResult<'db, Row>, TimeSeriesError> {
+        let (from, to) = (from as i64, to as i64);
+        let mut rows = Vec::new();
+        for (start, name) in self.partition_tables()? {
+            let end = start + self.partitioning.days();
+            if end * SECONDS_PER_DAY <= from || start * SECONDS_PER_DAY >= to {
+                continue;
+            }
+
+            let access = self.db.table_access(self.db.read_table(&name)?)?;
+            for (_, row) in access.find_all()?.rows()? {
+                if let Cell::Int(ts) = row.cells()[self.time_column] && (from..to).contains(&(ts as i64)) {
+                    rows.push(row);
+                }
+            }
+        }
+        Ok(QueryResult::from_rows(rows, self.base.schema().clone()))
+    }
+
+    /// Drops the partitions that ended before the retention window, returns their number
+    pub fn prune(&self) -> Result<usize, TimeSeriesError> {
+        let Some(cutoff) = self.cutoff() else {
+            return Ok(0);
+        };
+
+        let mut dropped = 0;
+        for (start, name) in self.partition_tables()? {
+            if (start + self.partitioning.days()) * SECONDS_PER_DAY <= cutoff {
+                self.db.drop_table(&name)?;
+                dropped += 1;
+            }
+        }
+        Ok(dropped)
+    }
+
+    /// Oldest timestamp in the retention window
+    fn cutoff(&self) -> Option<i64> {
+        let retention = self.retention?;
+        let now = self.db.clock().now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        Some(now.as_secs() as i64 - retention.as_secs() as i64)
+    }
+
+    fn partition_of(&self, row: &Row) -> Result<i64, TimeSeriesError> {
+        let col_name = || self.base.schema().columns[self.time_column].name.clone();
+        let ts = match row.cells().get(self.time_column) {
+            Some(Cell::Int(ts)) if *ts != NULL_INT => *ts,
+            _ => return Err(TimeSeriesError::MissingTimestamp(col_name())),
+        };
+        if self.cutoff().is_some_and(|cutoff| (ts as i64) < cutoff) {
+            return Err(TimeSeriesError::OutsideRetention(ts));
+        }
+        Ok(self.partitioning.period_start(ts as i64))
+    }
+
+    /// The partition of the period, created if it doesn't exist yet
+    fn partition_table(&self, start: i64) -> Result<Table, TimeSeriesError> {
+        let name = partition_name(self.base.name(), start);
+        match self.db.read_table(&name) {
+            Ok(table) => return Ok(table),
+            Err(DatabaseError::TableNotFound(_)) => {},
+            Err(err) => return Err(err.into()),
+        }
+
+        let indexed = self.db.table_access(self.base.clone())?.indexed_column_ids();
+        let commands: Vec<CreateColumnCommand> = self.base.schema().columns.iter()
+            .map(|c| (c.name.as_str(), c.col_type.clone(), false, indexed.contains(&c.id), c.encrypted).into())
+            .collect();
+        let table = self.db.create_table(&name, commands)?;
+        self.prune()?;
+        Ok(table)
+    }
+
+    /// Partitions in the catalog by the first day of their period
+    fn partition_tables(&self) -> Result<BTreeMap<i64, String>, TimeSeriesError> {
+        let prefix = format!("{}_", self.base.name());
+        let access = self.db.table_access(self.db.read_table("tables")?)?;
+        let query = access.find_all()?;
+        let name_index = query.schema().find_index_by_name("name")
+            .ok_or_else(|| DatabaseError::CorruptedDatabase("Column 'name' not found in 'tables' table".to_owned()))?;
+
+        let mut partitions = BTreeMap::new();
+        for (_, row) in query.rows()? {
+            if let Cell::Varchar(name) = &row.cells()[name_index]
+                && let Some(start) = name.strip_prefix(&prefix).and_then(parse_date) {
+                partitions.insert(start, name.clone());
+            }
+        }
+        Ok(partitions)
+    }
+}
+
+impl<S: Store> Database<S> {
+    /// Time-partitioned access to the table (see database/time_series.rs), the table defines the schema
+    /// and time_column is its INT column with the timestamp of a row
+    pub fn time_series(&self, table_name: &str, time_column: &str, partitioning: Partitioning) -> Result<TimeSeriesTable<'_, S>, TimeSeriesError> {
+        let base = self.read_table(table_name)?;
+        let time_column = base.schema().columns.iter()
+            .position(|c| c.name == time_column && c.col_type == ColumnType::Int)
+            .ok_or_else(|| TimeSeriesError::InvalidTimeColumn(time_column.to_owned()))?;
+        Ok(TimeSeriesTable { db: self, base, time_column, partitioning, retention: None })
+    }
+}
+
+fn partition_name(base: &str, start: i64) -> String {
+    let (year, month, day) = civil_from_days(start);
+    format!("{}_{:04}{:02}{:02}", base, year, month, day)
+}
+
+/// yyyymmdd to days since the epoch
+fn parse_date(date: &str) -> Option<i64> {
+    if date.len() != 8 || !date.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let (year, month, day) = (date[..4].parse().ok()?, date[4..6].parse().ok()?, date[6..].parse().ok()?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    Some(days_from_civil(year, month, day))
+}
+
+// proleptic Gregorian calendar, algorithms of Howard Hinnant ("chrono-compatible low-level date algorithms")
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{rc::Rc, time::{Duration, UNIX_EPOCH}};
+
+    use crate::{clock::ManualClock, database::{Database, DatabaseError, time_series::{Partitioning, TimeSeriesError}}, store::file_store::FileStore, table::{ColumnType, table::{Cell, Row}}};
+
+    #[test]
+    fn should_write_rows_into_partitions_and_drop_them_after_the_retention() {
+        // 2026-10-16 (a Friday), 00:00 UTC
+        const DAY: i32 = 86_400;
+        const FRIDAY: i32 = 1_792_108_800;
+        let base_path = tempfile::tempdir().unwrap();
+        let clock = Rc::new(ManualClock::new(UNIX_EPOCH + Duration::from_secs(FRIDAY as u64)));
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path())).with_clock(clock.clone());
+        db.drop_create().unwrap();
+        db.create_table("metrics", vec![("ts", ColumnType::Int), ("value", ColumnType::Int)]).unwrap();
+        let row = |ts: i32, value: i32| Row::new(vec![Cell::Int(ts), Cell::Int(value)]);
+
+        assert!(matches!(db.time_series("metrics", "value2", Partitioning::Daily), Err(TimeSeriesError::InvalidTimeColumn(_))));
+        let series = db.time_series("metrics", "ts", Partitioning::Daily).unwrap()
+            .with_retention(Duration::from_secs(2 * DAY as u64));
+        series.insert(&row(FRIDAY + 10, 1)).unwrap();
+        assert_eq!(series.insert_all(vec![row(FRIDAY - DAY, 2), row(FRIDAY + 20, 3), row(FRIDAY - 2 * DAY, 4)]).unwrap(), 3);
+        assert!(matches!(series.insert(&row(FRIDAY - 3 * DAY, 5)), Err(TimeSeriesError::OutsideRetention(_))));
+        assert_eq!(series.partitions().unwrap(), vec!["metrics_20261014", "metrics_20261015", "metrics_20261016"]);
+
+        let values = |from: i32, to: i32| -> Vec<Cell> {
+            series.range(from, to).unwrap().rows().unwrap().into_iter().map(|row| row.cells()[1].clone()).collect()
+        };
+        assert_eq!(values(FRIDAY - DAY, FRIDAY + 20), vec![Cell::Int(2), Cell::Int(1)]);
+        assert_eq!(values(0, i32::MAX), vec![Cell::Int(4), Cell::Int(2), Cell::Int(1), Cell::Int(3)]);
+
+        // the partition of Wednesday ends at Thursday 00:00, which is out of the window two days later
+        clock.advance(Duration::from_secs(DAY as u64));
+        assert_eq!(series.prune().unwrap(), 1);
+        assert!(matches!(db.read_table("metrics_20261014"), Err(DatabaseError::TableNotFound(_))));
+        // a new partition prunes, too
+        clock.advance(Duration::from_secs(DAY as u64));
+        series.insert(&row(FRIDAY + 2 * DAY, 6)).unwrap();
+        assert_eq!(series.partitions().unwrap(), vec!["metrics_20261016", "metrics_20261018"]);
+
+        let weekly = db.time_series("metrics", "ts", Partitioning::Weekly).unwrap();
+        weekly.insert(&row(FRIDAY, 7)).unwrap();
+        assert!(db.read_table("metrics_20261012").is_ok());
+    }
+}
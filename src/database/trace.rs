@@ -0,0 +1,81 @@
+use std::{cell::Cell, rc::Rc, time::{Duration, SystemTime}};
+
+use crate::{database::Database, store::Store};
+
+// Spans of queries and their operators for the tracing of the host application.
+// playdb has no dependency on the `tracing` crate: the application installs a Tracer (Database::with_tracer)
+// and forwards the finished spans, e.g. into tracing/OpenTelemetry. The root span of a query has no parent,
+// so a bridge attaches it to the current span of the application.
+// The field names follow the OpenTelemetry conventions for databases (db.*), playdb specific fields are playdb.*.
+//
+// Spans are reported when they are finished, so children come before their parent.
+// Only SQL queries (SELECT) are traced at the moment.
+pub const FIELD_DB_SYSTEM: &str = "db.system";
+pub const FIELD_DB_NAME: &str = "db.name";
+pub const FIELD_DB_OPERATION: &str = "db.operation";
+pub const FIELD_TABLE: &str = "db.sql.table";
+pub const FIELD_DETAIL: &str = "playdb.detail";
+pub const FIELD_ROWS_OUT: &str = "playdb.rows_out";
+pub const FIELD_PAGES_READ: &str = "playdb.pages_read";
+pub const FIELD_CACHE_HITS: &str = "playdb.cache_hits";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Str(String),
+    U64(u64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub id: u64,
+    pub parent: Option<u64>,
+    pub name: String,
+    pub start: SystemTime,
+    pub duration: Duration,
+    pub fields: Vec<(&'static str, FieldValue)>,
+}
+
+impl Span {
+    pub fn new(name: &str, parent: Option<u64>, start: SystemTime, duration: Duration) -> Self {
+        Self { id: next_span_id(), parent, name: name.to_owned(), start, duration, fields: Vec::new() }
+    }
+
+    pub fn with_field(mut self, name: &'static str, value: FieldValue) -> Self {
+        self.fields.push((name, value));
+        self
+    }
+
+    pub fn field(&self, name: &str) -> Option<&FieldValue> {
+        self.fields.iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, value)| value)
+    }
+}
+
+pub trait Tracer {
+    fn on_span(&self, span: &Span);
+}
+
+thread_local! {
+    static NEXT_SPAN_ID: Cell<u64> = const { Cell::new(1) };
+}
+
+// unique per thread, that's enough because a Database is used by one thread
+fn next_span_id() -> u64 {
+    NEXT_SPAN_ID.with(|id| {
+        let next = id.get();
+        id.set(next + 1);
+        next
+    })
+}
+
+impl<S: Store> Database<S> {
+    pub fn with_tracer(mut self, tracer: Rc<dyn Tracer>) -> Self {
+        self.tracer = Some(tracer);
+        self
+    }
+
+    pub(crate) fn tracer(&self) -> Option<&Rc<dyn Tracer>> {
+        self.tracer.as_ref()
+    }
+}
@@ -0,0 +1,310 @@
+use crate::{
+    database::{Database, DatabaseError, table_access::TableAccess},
+    store::{format_upgrade::FormatUpgrade, file_store::FileStore, kv_store},
+    table::table::{Cell, Table},
+};
+
+// Upgrade of the on-disk format: when the format of the table files changes (PageDataLayout::FORMAT_VERSION), a
+// database of an older version cannot be opened anymore. upgrade_format rewrites its table files page by page into
+// the current format instead of a dump and restore (see store/format_upgrade.rs for the rewrite of a file).
+//
+// - first the catalog tables, because the other tables can only be found with them, then the tables of the catalog
+// - rows that don't fit into their page anymore are moved, the entries of the indexes are updated for them
+// - it can be interrupted and called again: a table is replaced only when its new file is complete, and the moved
+//   rows are kept in a file until their indexes are updated, so the updates are repeated
+// - the overflow files of the BLOB values are upgraded with their table, the KvStore namespaces after the tables:
+//   they are not in the catalog, their files are found in the directory of the database
+// - the B-tree files of the indexes have their own format, they are not changed
+// - with FileStore::with_upgrade_on_open, the store upgrades each table file when it reads its header for the first
+//   time instead, table_access and KvStore::open update the indexes of the moved rows. The tables that are never
+//   opened keep their format until upgrade_format is called.
+
+impl Database<FileStore> {
+    /// Rewrites the tables that have an older format, returns them with the result of their upgrade.
+    /// Calling it again (e.g. after a crash) finishes an interrupted upgrade, an upgraded database is not changed.
+    pub fn upgrade_format(&self) -> Result<Vec<(String, FormatUpgrade)>, DatabaseError> {
+        let mut upgraded = Vec::new();
+        let mut tables = Vec::new();
+        for table in [self.table_instance(), self.col_table_instance()] {
+            if let Some(upgrade) = self.store.upgrade_table_file(&table)? {
+                upgraded.push((table.name().to_owned(), upgrade));
+            }
+            tables.push(table);
+        }
+
+        for name in self.catalog_table_names()? {
+            let table = self.read_table(&name)?;
+            if table.id() <= 2 {
+                continue;
+            }
+            if let Some(upgrade) = self.store.upgrade_table_file(&table)? {
+                upgraded.push((name, upgrade));
+            }
+            if table.has_blobs() {
+                let overflow = table.overflow_table();
+                if let Some(upgrade) = self.store.upgrade_overflow_file(&overflow)? {
+                    upgraded.push((overflow.name().to_owned(), upgrade));
+                }
+                self.store.finish_upgrade(&overflow)?;
+            }
+            tables.push(table);
+        }
+
+        // the indexes are read with the catalog, so all tables are upgraded first
+        for table in tables.iter() {
+            let moved = self.store.moved_records(table)?;
+            if !moved.is_empty() {
+                let access = self.table_access(table.clone())?;
+                for (page_id, slot_id) in moved {
+                    access.repoint_index(page_id, slot_id)?;
+                }
+            }
+            self.store.finish_upgrade(table)?;
+        }
+
+        for namespace in self.kv_namespaces(&tables)? {
+            let table = kv_store::namespace_table(namespace);
+            if let Some(upgrade) = self.store.upgrade_table_file(&table)? {
+                upgraded.push((table.name().to_owned(), upgrade));
+            }
+            let moved = self.store.moved_records(&table)?;
+            if !moved.is_empty() {
+                self.kv_store(namespace)?.index_moved(&moved)?;
+            }
+            self.store.finish_upgrade(&table)?;
+        }
+
+        Ok(upgraded)
+    }
+
+    // namespaces with a page file: it has the name of an overflow file (see Table::file_path), the overflow files of
+    // the tables are skipped
+    fn kv_namespaces(&self, tables: &[Table]) -> Result<Vec<u16>, DatabaseError> {
+        let mut namespaces = Vec::new();
+        for entry in std::fs::read_dir(self.store.base_path()).map_err(|e| DatabaseError::UnknownError(e.to_string()))? {
+            let name = entry.map_err(|e| DatabaseError::UnknownError(e.to_string()))?.file_name();
+            let Some(id) = name.to_str()
+                .and_then(|name| name.strip_prefix("table_")?.strip_suffix(".blob"))
+                .and_then(|id| id.parse::<i32>().ok()) else {
+                continue;
+            };
+            if tables.iter().any(|table| table.id() == id && table.has_blobs()) {
+                continue;
+            }
+            if let Ok(namespace) = u16::try_from(id - 1) {
+                namespaces.push(namespace);
+            }
+        }
+        namespaces.sort();
+        Ok(namespaces)
+    }
+
+    // names of all tables in the catalog (including the catalog tables)
+    fn catalog_table_names(&self) -> Result<Vec<String>, DatabaseError> {
+        // without the indexes, the table 'indexes' may not be upgraded yet
+        let access = TableAccess::new(self.table_instance(), &self.store, &self.layout);
+        let query = access.find_all()?;
+        let name_index = query.schema().find_index_by_name("name")
+            .ok_or_else(|| DatabaseError::CorruptedDatabase("Column 'name' not found in 'tables' table".to_owned()))?;
+
+        query.rows()?.into_iter()
+            .map(|(_, row)| match &row.cells()[name_index] {
+                Cell::Varchar(name) => Ok(name.clone()),
+                _ => Err(DatabaseError::CorruptedDatabase("Column 'name' has wrong type in 'tables' table".to_owned())),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{data::{checksum::Crc32, page::PageDataLayout}, database::Database, store::{Store, file_store::FileStore, kv_store}, table::{ColumnType, table::{Cell, Row, Table}}};
+
+    // Rewrites the table file into the format of version 2 (header in front of the pages, page header without LSN) or
+    // 4 (header in the metadata file, page header with LSN) with page ids of 4 bytes and the given page size, as an
+    // older playdb wrote it. With a page size that is smaller by the difference of the page headers, the pages have the
+    // same page data, so full pages are written, which don't fit into their page after the upgrade.
+    // The tables have no moved rows, their pointers would be 4 bytes shorter.
+    fn downgrade(store: &FileStore, layout: &PageDataLayout, table: &Table, version: u8, page_size: usize) {
+        let header_size = if version == 2 { 18 } else { 26 };
+        let metadata = store.read_metadata(layout, table).unwrap();
+        let mut header = vec![0u8; 28];
+        header[0..4].copy_from_slice(&PageDataLayout::MAGIC);
+        header[4] = version;
+        header[8..12].copy_from_slice(&(metadata.next_id() as i32).to_be_bytes());
+        header[12..16].copy_from_slice(&(metadata.number_of_pages() as i32).to_be_bytes());
+        header[16..18].copy_from_slice(&(page_size as u16).to_be_bytes());
+        header[18..20].copy_from_slice(&28u16.to_be_bytes());
+        header[24..28].copy_from_slice(&(metadata.number_of_pages() as i32).to_be_bytes());
+
+        let mut pages = Vec::new();
+        for page_id in 1..=metadata.number_of_pages() {
+            let page = store.read_page(layout, page_id, table).unwrap().serialize();
+            let delta = (page_size - header_size) - (page.len() - 30);
+            let slots = u32::from_be_bytes([0, page[15], page[16], page[17]]) as usize;
+            let mut data = vec![0u8; page_size - header_size];
+            data[..slots].copy_from_slice(&page[30..30 + slots]);
+            data[slots + delta..].copy_from_slice(&page[30 + slots..]);
+            for pos in (1..slots).step_by(7) {
+                let offset = u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]) as usize + delta;
+                data[pos..pos + 4].copy_from_slice(&(offset as u32).to_be_bytes());
+            }
+
+            let mut old = page[..6].to_vec();
+            let row_offset = u32::from_be_bytes([old[2], old[3], old[4], old[5]]) as usize + delta;
+            old[2..6].copy_from_slice(&(row_offset as u32).to_be_bytes());
+            old.extend((page_id as i32).to_be_bytes());
+            old.extend(&page[14..18]);
+            old.extend([0u8; 4]);
+            if version > 2 {
+                old.extend(&page[22..30]);
+            }
+            old.extend(data);
+            let mut crc = Crc32::new();
+            crc.update(&old[..14]);
+            crc.update(&old[18..]);
+            let checksum = crc.finish();
+            old[14..18].copy_from_slice(&checksum.to_be_bytes());
+            pages.extend(old);
+        }
+        match version {
+            2 => {
+                header.extend(pages);
+                std::fs::write(store.file_path(table), header).unwrap();
+                std::fs::remove_file(store.meta_path(table)).unwrap();
+            },
+            _ => {
+                // the free space map is written again by the upgrade
+                header.extend(vec![0u8; metadata.number_of_pages() as usize * 2]);
+                std::fs::write(store.file_path(table), pages).unwrap();
+                std::fs::write(store.meta_path(table), header).unwrap();
+            },
+        }
+    }
+
+    #[test]
+    fn should_upgrade_all_tables_and_keep_the_indexes() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        let persons = db.create_table_with_page_size("persons", vec![("id", ColumnType::Int, false, true), ("name", ColumnType::Varchar(100), false, false)], 520).unwrap();
+        let access = db.table_access(persons.clone()).unwrap();
+        for id in 0..300 {
+            access.insert(&Row::new(vec![Cell::Int(id), Cell::Varchar(format!("person {}", id))])).unwrap();
+        }
+        let layout = db.table_layout(&persons).unwrap();
+        let pages = db.store.read_metadata(&layout, &persons).unwrap().number_of_pages();
+        drop(access);
+
+        for table in [db.table_instance(), db.col_table_instance(), db.read_table("sequences").unwrap(), db.read_table("indexes").unwrap()] {
+            downgrade(&db.store, &db.layout, &table, 2, db.layout.page_size());
+        }
+        downgrade(&db.store, &layout, &persons, 2, 508);
+        assert!(db.read_table("persons").is_err());
+
+        let upgraded = db.upgrade_format().unwrap();
+        let names: Vec<&str> = upgraded.iter().map(|(name, _)| name.as_str()).collect();
+        assert!(names.contains(&"tables") && names.contains(&"indexes") && names.contains(&"persons"));
+        let persons_upgrade = upgraded.iter().find(|(name, _)| name == "persons").unwrap().1;
+        assert_eq!(persons_upgrade.from_version, 2);
+        assert!(persons_upgrade.moved_records > 0, "full pages must move rows");
+        assert!(persons_upgrade.pages > pages);
+
+        let persons = db.read_table("persons").unwrap();
+        assert_eq!(db.table_layout(&persons).unwrap().page_size(), 508);
+        let access = db.table_access(persons.clone()).unwrap();
+        assert_eq!(access.find_all().unwrap().rows().unwrap().len(), 300);
+        for id in [0, 150, 299] {
+            let rows = access.find("id", Cell::Int(id)).unwrap().rows().unwrap();
+            assert_eq!(rows[0].1.cells()[1], Cell::Varchar(format!("person {}", id)));
+        }
+        assert!(db.store.moved_records(&persons).unwrap().is_empty());
+        assert!(db.upgrade_format().unwrap().is_empty());
+    }
+
+    #[test]
+    fn should_split_the_blob_chunks_and_upgrade_the_kv_namespaces() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        let files = db.create_table_with_page_size("files", vec![("id", ColumnType::Int, false, true), ("data", ColumnType::Blob, false, false)], 132).unwrap();
+        let access = db.table_access(files.clone()).unwrap();
+        let values: Vec<Vec<u8>> = (0..3).map(|i| (0..250).map(|b| (b * i) as u8).collect()).collect();
+        for (id, value) in values.iter().enumerate() {
+            access.insert(&Row::new(vec![Cell::Int(id as i32), Cell::Blob(value.clone())])).unwrap();
+        }
+        drop(access);
+        let kv = db.kv_store(7).unwrap();
+        kv.put("name", b"playdb").unwrap();
+        drop(kv);
+
+        // 4 bytes smaller: the full chunks of version 4 don't fit into their page after the upgrade
+        let layout = db.table_layout(&files).unwrap();
+        let overflow_pages = db.store.read_metadata(&layout, &files.overflow_table()).unwrap().number_of_pages();
+        downgrade(&db.store, &layout, &files, 4, 128);
+        downgrade(&db.store, &layout, &files.overflow_table(), 4, 128);
+        downgrade(&db.store, &db.layout, &kv_store::namespace_table(7), 4, db.layout.page_size());
+
+        let upgraded = db.upgrade_format().unwrap();
+        let names: Vec<&str> = upgraded.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["files", "files (overflow)", "_kv_7"]);
+        let overflow_upgrade = upgraded[1].1;
+        assert_eq!((overflow_upgrade.from_version, overflow_upgrade.moved_records), (4, 0));
+        assert!(overflow_upgrade.pages > overflow_pages, "full chunks must be split");
+
+        let files = db.read_table("files").unwrap();
+        let access = db.table_access(files).unwrap();
+        for (id, value) in values.iter().enumerate() {
+            let rows = access.find("id", Cell::Int(id as i32)).unwrap().rows().unwrap();
+            assert_eq!(rows[0].1.cells()[1], Cell::Blob(value.clone()));
+        }
+        assert_eq!(db.kv_store(7).unwrap().get("name").unwrap(), Some(b"playdb".to_vec()));
+        assert!(db.upgrade_format().unwrap().is_empty());
+    }
+
+    #[test]
+    fn should_upgrade_the_files_when_the_store_opens_them() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        let persons = db.create_table_with_page_size("persons", vec![("id", ColumnType::Int, false, true), ("name", ColumnType::Varchar(100), false, false)], 520).unwrap();
+        let access = db.table_access(persons.clone()).unwrap();
+        for id in 0..300 {
+            access.insert(&Row::new(vec![Cell::Int(id), Cell::Varchar(format!("person {}", id))])).unwrap();
+        }
+        drop(access);
+        let files = db.create_table_with_page_size("files", vec![("id", ColumnType::Int, false, true), ("data", ColumnType::Blob, false, false)], 132).unwrap();
+        let value: Vec<u8> = (0..250).map(|b| b as u8).collect();
+        db.table_access(files.clone()).unwrap().insert(&Row::new(vec![Cell::Int(1), Cell::Blob(value.clone())])).unwrap();
+        db.kv_store(7).unwrap().put("name", b"playdb").unwrap();
+
+        for table in [db.table_instance(), db.col_table_instance(), db.read_table("sequences").unwrap(), db.read_table("indexes").unwrap()] {
+            downgrade(&db.store, &db.layout, &table, 2, db.layout.page_size());
+        }
+        downgrade(&db.store, &db.table_layout(&persons).unwrap(), &persons, 2, 508);
+        let layout = db.table_layout(&files).unwrap();
+        downgrade(&db.store, &layout, &files, 4, 128);
+        downgrade(&db.store, &layout, &files.overflow_table(), 4, 128);
+        downgrade(&db.store, &db.layout, &kv_store::namespace_table(7), 4, db.layout.page_size());
+        drop(db);
+
+        // a read-only store cannot upgrade them
+        let db = Database::new_with_store("test_db", FileStore::new_read_only(base_path.path()).with_upgrade_on_open());
+        assert!(db.read_table("persons").is_err());
+        assert!(db.store.moved_records(&persons).unwrap().is_empty());
+
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()).with_upgrade_on_open());
+        let access = db.table_access(db.read_table("persons").unwrap()).unwrap();
+        for id in [0, 150, 299] {
+            let rows = access.find("id", Cell::Int(id)).unwrap().rows().unwrap();
+            assert_eq!(rows[0].1.cells()[1], Cell::Varchar(format!("person {}", id)));
+        }
+        assert!(db.store.moved_records(&persons).unwrap().is_empty(), "the moved rows are indexed when the table is accessed");
+        let access = db.table_access(db.read_table("files").unwrap()).unwrap();
+        assert_eq!(access.find("id", Cell::Int(1)).unwrap().rows().unwrap()[0].1.cells()[1], Cell::Blob(value));
+        assert_eq!(db.kv_store(7).unwrap().get("name").unwrap(), Some(b"playdb".to_vec()));
+        // the tables that were not opened yet
+        let names: Vec<String> = db.upgrade_format().unwrap().into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["sequences"]);
+    }
+}
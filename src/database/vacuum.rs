@@ -0,0 +1,140 @@
+use crate::{
+    data::page::PageId,
+    database::{Database, DatabaseError},
+    store::{PageIterator, Store},
+};
+
+// A delete only marks the slot of a row (tombstone), the row keeps its space on the page until an insert
+// reuses the slot or needs the space and compacts the page. vacuum() compacts every page with dead space
+// (deleted rows and the holes of updated rows) and writes it back, so the free space of a page is one area again.
+//
+// Slot indexes don't change (see Page::compact), so the indexes stay valid and are not rewritten.
+// Tombstones are kept (only the ones at the end of a page are removed). The pages without slots at the end of the
+// table are removed (Store::truncate), the FileStore shortens the file, so their space is returned to the OS.
+// Empty pages in between stay allocated and are reused by inserts: punching holes into the file (sparse files)
+// needs fallocate, which the standard library doesn't offer.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct VacuumStats {
+    pub pages_scanned: usize,
+    pub pages_rewritten: usize,
+    // deleted rows on the rewritten pages
+    pub dead_rows: usize,
+    pub bytes_reclaimed: usize,
+    // empty pages removed from the end of the table
+    pub pages_truncated: PageId,
+}
+
+impl<S: Store> Database<S> {
+    /// Reclaims the space of deleted and updated rows of the table, throttled like the other maintenance operations
+    pub fn vacuum(&self, table_name: &str) -> Result<VacuumStats, DatabaseError> {
+        let table = self.read_table(table_name)?;
+        let layout = self.table_layout(&table)?;
+
+        let mut stats = VacuumStats::default();
+        let mut throttle = self.throttle();
+        // the last page that keeps a slot after the compaction
+        let mut last_used: PageId = 0;
+        for page in PageIterator::try_new(&table, &self.store, &layout)? {
+            let mut page = page?;
+            stats.pages_scanned += 1;
+            throttle.consume(1, layout.page_size() as u64);
+
+            let dead_space = page.dead_space();
+            if dead_space > 0 {
+                stats.dead_rows += page.dead_rows();
+                page.compact();
+                self.store.write_page(&layout, &page, &table)?;
+                stats.pages_rewritten += 1;
+                stats.bytes_reclaimed += dead_space;
+                throttle.consume(1, layout.page_size() as u64);
+            }
+            if page.slot_size() > 0 {
+                last_used = page.page_id();
+            }
+        }
+
+        stats.pages_truncated = self.store.truncate(&layout, &table, last_used)?;
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::{Database, vacuum::VacuumStats}, store::{Store, file_store::FileStore}, table::{ColumnType, table::{Cell, Row}}};
+
+    #[test]
+    fn should_reclaim_the_space_of_deleted_rows() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        db.create_table("t", vec![("id", ColumnType::Int, false, true), ("name", ColumnType::Varchar(20), false, false)]).unwrap();
+
+        let access = db.table_access(db.read_table("t").unwrap()).unwrap();
+        for i in 0..10 {
+            access.insert(&Row::new(vec![Cell::Int(i), Cell::Varchar(format!("name {}", i))])).unwrap();
+        }
+        for i in [2, 5, 9] {
+            access.delete(access.find("id", Cell::Int(i)).unwrap()).unwrap();
+        }
+        let used = |db: &Database<FileStore>| {
+            let page = db.store.read_page(&db.layout, 1, access.table()).unwrap();
+            page.row_data_size() + page.slot_size()
+        };
+        let used_before = used(&db);
+
+        let stats = db.vacuum("t").unwrap();
+        assert_eq!((stats.pages_scanned, stats.pages_rewritten, stats.dead_rows), (1, 1, 3));
+        assert!(stats.bytes_reclaimed > 0);
+        assert_eq!(used_before - used(&db), stats.bytes_reclaimed);
+
+        // the index still finds the rows that were moved
+        let rows = access.find("id", Cell::Int(7)).unwrap().rows().unwrap();
+        assert_eq!(rows[0].1.cells()[1], Cell::Varchar("name 7".to_owned()));
+        assert_eq!(access.find_all().unwrap().rows().unwrap().len(), 7);
+
+        // the emptied slots are reused
+        access.insert(&Row::new(vec![Cell::Int(10), Cell::Varchar("name 10".to_owned())])).unwrap();
+        assert_eq!(access.find("id", Cell::Int(10)).unwrap().rows().unwrap().len(), 1);
+
+        let stats = db.vacuum("t").unwrap();
+        assert_eq!(stats, VacuumStats { pages_scanned: 1, ..VacuumStats::default() });
+    }
+
+    #[test]
+    fn should_truncate_the_empty_pages_at_the_end_of_the_file() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()).with_extent_size(4));
+        db.drop_create().unwrap();
+        let table = db.create_table_with_page_size("t", vec![("id", ColumnType::Int, false, true), ("name", ColumnType::Varchar(100), false, false)], 256).unwrap();
+        let access = db.table_access(table.clone()).unwrap();
+        for i in 0..40 {
+            access.insert(&Row::new(vec![Cell::Int(i), Cell::Varchar(format!("a longer name {}", i))])).unwrap();
+        }
+        let layout = db.table_layout(&table).unwrap();
+        let pages = db.store.read_metadata(&layout, &table).unwrap().number_of_pages();
+        let file_size = || std::fs::metadata(base_path.path().join(table.file_path())).unwrap().len();
+        assert!(pages > 4 && file_size() > pages * 256, "the last extent is preallocated");
+
+        // the rows of the last pages and one in between
+        for i in (0..40).filter(|i| *i >= 20 || *i == 5) {
+            access.delete(access.find("id", Cell::Int(i)).unwrap()).unwrap();
+        }
+        let used = (1..=pages)
+            .filter(|page_id| db.store.read_page(&layout, *page_id, &table).unwrap().live_rows() > 0)
+            .max().unwrap();
+
+        let stats = db.vacuum("t").unwrap();
+        assert_eq!(stats.pages_truncated, pages - used);
+        assert_eq!(file_size(), used * 256);
+        assert_eq!(db.store.read_metadata(&layout, &table).unwrap().number_of_pages(), used);
+        assert_eq!(access.find_all().unwrap().rows().unwrap().len(), 19);
+
+        // the next page gets the id after the last one again
+        for i in 20..40 {
+            access.insert(&Row::new(vec![Cell::Int(i), Cell::Varchar(format!("a longer name {}", i))])).unwrap();
+        }
+        assert_eq!(access.find("id", Cell::Int(39)).unwrap().rows().unwrap().len(), 1);
+        assert!(db.store.read_metadata(&layout, &table).unwrap().number_of_pages() > used);
+        assert_eq!(db.vacuum("t").unwrap().pages_truncated, 0);
+    }
+}
@@ -0,0 +1,71 @@
+use std::rc::Rc;
+
+use thiserror::Error;
+
+use crate::{
+    database::{Database, DatabaseError},
+    store::{Store, predicate::CompareOp},
+    table::{TableSchema, identifier::Identifier, table::{Cell, Row}},
+};
+
+// Virtual tables: rows of user code (a CSV directory, an API, process metrics) that are queried like a table,
+// registered with Database::register_virtual_table. In SQL, a virtual table can be used in the FROM clause
+// of a query and of its subqueries, so it can be combined with stored tables (e.g. WHERE id IN (SELECT ... FROM vt)).
+// Name resolution: CTE, then table of an attached database, system view (see system_views.rs), virtual table,
+// then stored table. A virtual table is read only, INSERT, UPDATE and DELETE only find stored tables.
+//
+// Pushdown: the conditions `col op literal` of the query are offered to the table with accepts(). The accepted ones
+// are passed to scan(), the table can use them to read less (e.g. fetch one id from the API). The query checks
+// all conditions again on every returned row, so a table may also return rows that don't match.
+// Every row is validated against the schema (number of cells, types, VARCHAR length) before it is used.
+
+pub type VirtualRows<'a> = Box<dyn Iterator<Item = Result<Row, VirtualTableError>> + 'a>;
+
+pub trait VirtualTable {
+    fn schema(&self) -> TableSchema;
+
+    /// Whether scan() uses the constraint, only these are passed to scan()
+    fn accepts(&self, _constraint: &Constraint) -> bool {
+        false
+    }
+
+    fn scan(&self, constraints: &[Constraint]) -> Result<VirtualRows<'_>, VirtualTableError>;
+}
+
+/// Condition of the query on a column of the virtual table (index into its schema)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constraint {
+    pub column: usize,
+    pub op: CompareOp,
+    pub value: Cell,
+}
+
+#[derive(Debug, Error)]
+pub enum VirtualTableError {
+    #[error("Table already exists: {0}")]
+    AlreadyExists(String),
+    #[error("Virtual table '{0}' failed: {1}")]
+    Failed(String, String),
+}
+
+impl<S: Store> Database<S> {
+    /// Fails if there is a stored table with the name, registering a name again replaces the virtual table
+    pub fn register_virtual_table(&self, name: &str, table: Rc<dyn VirtualTable>) -> Result<(), VirtualTableError> {
+        let name = Identifier::normalize(name);
+        match self.read_table(&name) {
+            Ok(_) => return Err(VirtualTableError::AlreadyExists(name)),
+            Err(DatabaseError::TableNotFound(_)) => {},
+            Err(err) => return Err(VirtualTableError::Failed(name, err.to_string())),
+        }
+        self.virtual_tables.borrow_mut().insert(name, table);
+        Ok(())
+    }
+
+    pub fn virtual_table(&self, name: &str) -> Option<Rc<dyn VirtualTable>> {
+        self.virtual_tables.borrow().get(&Identifier::normalize(name)).cloned()
+    }
+
+    pub fn drop_virtual_table(&self, name: &str) -> bool {
+        self.virtual_tables.borrow_mut().remove(&Identifier::normalize(name)).is_some()
+    }
+}
@@ -0,0 +1,209 @@
+use std::{fmt::Display, rc::Rc};
+
+use crate::{
+    data::page::PageId,
+    database::{Database, DatabaseError, table_access::TableAccessError},
+    store::Store,
+    table::{identifier::Identifier, table::{Row, Table}},
+};
+
+// The insert path of TableAccess::insert (and insert_many, insert_map, SQL INSERT) as a fixed order of stages:
+// validate -> defaults -> index check -> placement -> WAL -> write. Features like triggers, change data capture or
+// constraint checks are hooks of a stage (Database::add_write_hook), instead of each changing TableAccess::insert.
+// A hook runs after the built-in part of its stage:
+// - Validate: the row is checked against the schema
+// - Defaults: the row has all cells here (insert_map fills the missing ones), hooks can set generated values,
+//   e.g. the next value of a sequence. A changed row is validated again.
+// - IndexCheck: the values of the unique indexes must not exist yet. From here on the row cannot be changed.
+// - Placement: the BLOB values are stored, the page with space for the row is chosen (location), nothing is written
+// - Wal: there is no write-ahead log yet, the stage is the place of the log record (before the page is written)
+// - Write: the page is written and the indexes are updated
+// An error of a hook rejects the row: before Write nothing is written (except the BLOB values of the row, and a page
+// allocated for it stays empty). There are no transactions, so an error of a Write hook is returned, but the row
+// stays inserted. The bulk loader (insert_all, load_pages) doesn't use the pipeline.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WriteStage {
+    Validate,
+    Defaults,
+    IndexCheck,
+    Placement,
+    Wal,
+    Write,
+}
+
+impl Display for WriteStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            WriteStage::Validate => "validate",
+            WriteStage::Defaults => "defaults",
+            WriteStage::IndexCheck => "index check",
+            WriteStage::Placement => "placement",
+            WriteStage::Wal => "wal",
+            WriteStage::Write => "write",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+pub type WriteHook = dyn Fn(&mut PendingInsert) -> Result<(), String>;
+
+/// The row on its way through the pipeline
+pub struct PendingInsert<'a> {
+    table: &'a Table,
+    row: Row,
+    stage: WriteStage,
+    location: Option<(PageId, usize)>,
+}
+
+impl<'a> PendingInsert<'a> {
+    pub(crate) fn new(table: &'a Table, row: Row) -> Self {
+        Self { table, row, stage: WriteStage::Validate, location: None }
+    }
+
+    pub fn table(&self) -> &Table {
+        self.table
+    }
+
+    pub fn stage(&self) -> WriteStage {
+        self.stage
+    }
+
+    pub fn row(&self) -> &Row {
+        &self.row
+    }
+
+    /// The row to change, None from the index check on (the indexes and the page are chosen for the row)
+    pub fn row_mut(&mut self) -> Option<&mut Row> {
+        (self.stage < WriteStage::IndexCheck).then_some(&mut self.row)
+    }
+
+    /// (page id, slot) of the row, known from the placement on
+    pub fn location(&self) -> Option<(PageId, usize)> {
+        self.location
+    }
+
+    pub(crate) fn set_location(&mut self, location: (PageId, usize)) {
+        self.location = Some(location);
+    }
+
+    pub(crate) fn into_row(self) -> Row {
+        self.row
+    }
+}
+
+/// The hooks of the stages of a table, they run in the order they were added
+#[derive(Clone, Default)]
+pub struct WritePipeline {
+    hooks: Vec<(WriteStage, Rc<WriteHook>)>,
+}
+
+impl WritePipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_hook(mut self, stage: WriteStage, hook: Rc<WriteHook>) -> Self {
+        self.hooks.push((stage, hook));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Whether a hook can change the row (then it is validated again)
+    pub(crate) fn changes_rows(&self) -> bool {
+        self.hooks.iter().any(|(stage, _)| *stage < WriteStage::IndexCheck)
+    }
+
+    pub(crate) fn run(&self, stage: WriteStage, insert: &mut PendingInsert) -> Result<(), TableAccessError> {
+        insert.stage = stage;
+        for (_, hook) in self.hooks.iter().filter(|(s, _)| *s == stage) {
+            hook(insert).map_err(|reason| TableAccessError::InsertRejected { stage, reason })?;
+        }
+        Ok(())
+    }
+}
+
+impl<S: Store> Database<S> {
+    /// Adds a hook to the insert path of the table, after the hooks added before
+    pub fn add_write_hook(&self, table_name: &str, stage: WriteStage, hook: Rc<WriteHook>) -> Result<(), DatabaseError> {
+        let table = self.read_table(&Identifier::normalize(table_name))?;
+        let mut pipelines = self.write_pipelines.borrow_mut();
+        let pipeline = pipelines.remove(&table.id()).unwrap_or_default();
+        pipelines.insert(table.id(), pipeline.with_hook(stage, hook));
+        Ok(())
+    }
+
+    /// Removes all hooks of the table
+    pub fn clear_write_hooks(&self, table_name: &str) -> Result<(), DatabaseError> {
+        let table = self.read_table(&Identifier::normalize(table_name))?;
+        self.write_pipelines.borrow_mut().remove(&table.id());
+        Ok(())
+    }
+
+    pub(crate) fn write_pipeline(&self, table: &Table) -> Option<WritePipeline> {
+        self.write_pipelines.borrow().get(&table.id()).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+    use crate::{database::{Database, table_access::TableAccessError, write_pipeline::WriteStage}, store::file_store::FileStore, table::{ColumnType, table::{Cell, Row}}};
+
+    #[test]
+    fn should_run_the_hooks_of_the_stages_in_order() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        db.create_table("persons", vec![("id", ColumnType::Int, false, true), ("name", ColumnType::Varchar(20), false, false)]).unwrap();
+
+        let log = Rc::new(RefCell::new(Vec::new()));
+        for stage in [WriteStage::Write, WriteStage::Validate, WriteStage::Placement] {
+            let log = Rc::clone(&log);
+            db.add_write_hook("persons", stage, Rc::new(move |insert| {
+                log.borrow_mut().push((insert.stage(), insert.location().is_some()));
+                Ok(())
+            })).unwrap();
+        }
+        // a trigger: names are stored in upper case, an empty name is rejected
+        db.add_write_hook("persons", WriteStage::Defaults, Rc::new(|insert| {
+            let row = insert.row_mut().ok_or("row cannot be changed")?;
+            let Cell::Varchar(name) = &row.cells()[1] else {
+                return Err("name expected".to_owned());
+            };
+            if name.is_empty() {
+                return Err("name must not be empty".to_owned());
+            }
+            *row = Row::new(vec![row.cells()[0].clone(), Cell::Varchar(name.to_uppercase())]);
+            Ok(())
+        })).unwrap();
+        db.add_write_hook("persons", WriteStage::IndexCheck, Rc::new(|insert| {
+            insert.row_mut().map_or(Ok(()), |_| Err("row must not be changed any more".to_owned()))
+        })).unwrap();
+
+        let access = db.table_access(db.read_table("persons").unwrap()).unwrap();
+        let row = access.insert_map_returning(&HashMap::from([("id", Cell::Int(1)), ("name", Cell::Varchar("ada".to_owned()))])).unwrap();
+        assert_eq!(row.cells()[1], Cell::Varchar("ADA".to_owned()));
+        assert_eq!(*log.borrow(), vec![(WriteStage::Validate, false), (WriteStage::Placement, true), (WriteStage::Write, true)]);
+        let stored = access.find("id", Cell::Int(1)).unwrap().rows().unwrap();
+        assert_eq!(stored[0].1.cells()[1], Cell::Varchar("ADA".to_owned()));
+
+        let err = access.insert_map(&HashMap::from([("id", Cell::Int(2)), ("name", Cell::Varchar(String::new()))])).unwrap_err();
+        assert!(matches!(err, TableAccessError::InsertRejected { stage: WriteStage::Defaults, .. }), "{}", err);
+        // the duplicate is found before the placement
+        log.borrow_mut().clear();
+        assert!(access.insert_map(&HashMap::from([("id", Cell::Int(1)), ("name", Cell::Varchar("bob".to_owned()))])).is_err());
+        assert_eq!(*log.borrow(), vec![(WriteStage::Validate, false)]);
+        assert_eq!(access.find_all().unwrap().rows().unwrap().len(), 1);
+
+        db.clear_write_hooks("persons").unwrap();
+        log.borrow_mut().clear();
+        let access = db.table_access(db.read_table("persons").unwrap()).unwrap();
+        access.insert_map(&HashMap::from([("id", Cell::Int(2)), ("name", Cell::Varchar(String::new()))])).unwrap();
+        assert!(log.borrow().is_empty());
+    }
+}
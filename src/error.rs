@@ -0,0 +1,380 @@
+use std::fmt::Display;
+
+use thiserror::Error;
+
+use crate::{
+    data::page::{PageError, PageId},
+    database::{CreateTableError, DatabaseError, export::ExportError, migrations::MigrationError, seq_access::SeqAccessError, table_access::TableAccessError},
+    store::{StoreError, kv_store::KvStoreError},
+    table::{SchemaError, identifier::IdentifierError, table::{CellDeserializationError, CellError, RowValidationError}},
+};
+
+// One error type for applications (and later the wire protocols): every module error converts into PlaydbError.
+// The numeric codes are stable, new codes are only appended. The first digit is the category:
+//   1xxx store (I/O, files), 2xxx page, 3xxx validation of input, 4xxx catalog, 5xxx transactions, 9xxx other.
+// There are no transactions yet, so there are no 5xxx codes.
+//
+// Most module errors only carry a message, so table, page and column are only set where the error knows them.
+// Callers can add them with with_table/with_page/with_column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    Io = 1000,
+    ReadOnly = 1001,
+    QuotaExceeded = 1002,
+    CorruptedData = 1003,
+    ChecksumMismatch = 1004,
+    FailpointTriggered = 1005,
+    LayoutMismatch = 1006,
+    UnknownFileFormat = 1007,
+    PageFull = 2000,
+    PageCorrupted = 2001,
+    RecordUpdateFailed = 2002,
+    EntryTooLarge = 2003,
+    RowLengthMismatch = 3000,
+    TypeMismatch = 3001,
+    ValueTooLong = 3002,
+    UnknownColumn = 3003,
+    MissingValue = 3004,
+    InvalidIdentifier = 3005,
+    ConstraintViolation = 3006,
+    SyntaxError = 3007,
+    InvalidFormat = 3008,
+    MissingEncryptionKey = 3009,
+    StaleRow = 3010,
+    WrongEncryptionKey = 3011,
+    TableNotFound = 4000,
+    TableAlreadyExists = 4001,
+    InvalidSchema = 4002,
+    CorruptedCatalog = 4003,
+    SequenceError = 4004,
+    MigrationFailed = 4005,
+    Internal = 9000,
+}
+
+impl ErrorCode {
+    pub fn as_u16(&self) -> u16 {
+        *self as u16
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorDetails {
+    pub code: ErrorCode,
+    pub message: String,
+    pub table: Option<String>,
+    pub page: Option<PageId>,
+    pub column: Option<String>,
+}
+
+impl ErrorDetails {
+    fn new<M: Display>(code: ErrorCode, message: M) -> Self {
+        Self { code, message: message.to_string(), table: None, page: None, column: None }
+    }
+
+    fn column(mut self, column: &str) -> Self {
+        self.column = Some(column.to_owned());
+        self
+    }
+}
+
+impl Display for ErrorDetails {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "E{}: {}", self.code.as_u16(), self.message)?;
+        let context: Vec<String> = [
+            self.table.as_ref().map(|t| format!("table={}", t)),
+            self.page.map(|p| format!("page={}", p)),
+            self.column.as_ref().map(|c| format!("column={}", c)),
+        ].into_iter().flatten().collect();
+        if !context.is_empty() {
+            write!(f, " ({})", context.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Error, PartialEq)]
+pub enum PlaydbError {
+    #[error("{0}")]
+    Store(ErrorDetails),
+    #[error("{0}")]
+    Page(ErrorDetails),
+    #[error("{0}")]
+    Validation(ErrorDetails),
+    #[error("{0}")]
+    Catalog(ErrorDetails),
+    #[error("{0}")]
+    Other(ErrorDetails),
+}
+
+impl PlaydbError {
+    pub fn details(&self) -> &ErrorDetails {
+        match self {
+            PlaydbError::Store(details)
+            | PlaydbError::Page(details)
+            | PlaydbError::Validation(details)
+            | PlaydbError::Catalog(details)
+            | PlaydbError::Other(details) => details,
+        }
+    }
+
+    fn details_mut(&mut self) -> &mut ErrorDetails {
+        match self {
+            PlaydbError::Store(details)
+            | PlaydbError::Page(details)
+            | PlaydbError::Validation(details)
+            | PlaydbError::Catalog(details)
+            | PlaydbError::Other(details) => details,
+        }
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        self.details().code
+    }
+
+    pub fn with_table(mut self, table: &str) -> Self {
+        self.details_mut().table = Some(table.to_owned());
+        self
+    }
+
+    pub fn with_page(mut self, page_id: PageId) -> Self {
+        self.details_mut().page = Some(page_id);
+        self
+    }
+
+    pub fn with_column(mut self, column: &str) -> Self {
+        self.details_mut().column = Some(column.to_owned());
+        self
+    }
+
+    // the category follows from the code
+    fn from_details(details: ErrorDetails) -> Self {
+        match details.code.as_u16() / 1000 {
+            1 => PlaydbError::Store(details),
+            2 => PlaydbError::Page(details),
+            3 => PlaydbError::Validation(details),
+            4 => PlaydbError::Catalog(details),
+            _ => PlaydbError::Other(details),
+        }
+    }
+
+    fn new<M: Display>(code: ErrorCode, message: M) -> Self {
+        Self::from_details(ErrorDetails::new(code, message))
+    }
+}
+
+impl From<StoreError> for PlaydbError {
+    fn from(err: StoreError) -> Self {
+        let code = match &err {
+            StoreError::IoError(_) => ErrorCode::Io,
+            StoreError::DeserializationError(_) => ErrorCode::CorruptedData,
+            StoreError::ReadBTreeStoreError(_) => ErrorCode::CorruptedData,
+            StoreError::ReadOnly => ErrorCode::ReadOnly,
+            StoreError::QuotaExceeded(_) => ErrorCode::QuotaExceeded,
+            StoreError::Injected(_) => ErrorCode::FailpointTriggered,
+            StoreError::ChecksumMismatch(_) => ErrorCode::ChecksumMismatch,
+            StoreError::LayoutMismatch(_) => ErrorCode::LayoutMismatch,
+            StoreError::UnknownFormat(_) => ErrorCode::UnknownFileFormat,
+        };
+        PlaydbError::new(code, err)
+    }
+}
+
+impl From<PageError> for PlaydbError {
+    fn from(err: PageError) -> Self {
+        let code = match &err {
+            PageError::InsertRowError => ErrorCode::PageFull,
+            PageError::ReadPageError => ErrorCode::PageCorrupted,
+            PageError::UpdateRecordError => ErrorCode::RecordUpdateFailed,
+            PageError::ChecksumMismatch => ErrorCode::ChecksumMismatch,
+            PageError::LayoutMismatch(_) => ErrorCode::LayoutMismatch,
+            PageError::UnknownFormat(_) => ErrorCode::UnknownFileFormat,
+            PageError::NotOrderable => ErrorCode::RecordUpdateFailed,
+        };
+        PlaydbError::new(code, err)
+    }
+}
+
+impl From<KvStoreError> for PlaydbError {
+    fn from(err: KvStoreError) -> Self {
+        match &err {
+            KvStoreError::EntryTooLarge(_) => PlaydbError::new(ErrorCode::EntryTooLarge, err),
+            KvStoreError::CorruptedEntry(page_id, _) => {
+                let page_id = *page_id;
+                PlaydbError::new(ErrorCode::PageCorrupted, err).with_page(page_id)
+            },
+            _ => PlaydbError::new(ErrorCode::Internal, err),
+        }
+    }
+}
+
+impl From<RowValidationError> for PlaydbError {
+    fn from(err: RowValidationError) -> Self {
+        let details = match &err {
+            RowValidationError::LengthMismatch => ErrorDetails::new(ErrorCode::RowLengthMismatch, &err),
+            RowValidationError::TypeMismatch(column, _, _) => ErrorDetails::new(ErrorCode::TypeMismatch, &err).column(column),
+            RowValidationError::VarcharTooLong(_, column) => ErrorDetails::new(ErrorCode::ValueTooLong, &err).column(column),
+            RowValidationError::UnknownColumn(column) => ErrorDetails::new(ErrorCode::UnknownColumn, &err).column(column),
+            RowValidationError::MissingValue(column) => ErrorDetails::new(ErrorCode::MissingValue, &err).column(column),
+            RowValidationError::InvalidPoint(column) => ErrorDetails::new(ErrorCode::InvalidFormat, &err).column(column),
+        };
+        PlaydbError::from_details(details)
+    }
+}
+
+impl From<CellDeserializationError> for PlaydbError {
+    fn from(err: CellDeserializationError) -> Self {
+        let context = err.context();
+        let mut details = ErrorDetails::new(ErrorCode::CorruptedData, &err);
+        details.table = context.table.clone();
+        details.page = context.page;
+        details.column = context.column.clone();
+        PlaydbError::from_details(details)
+    }
+}
+
+impl From<CellError> for PlaydbError {
+    fn from(err: CellError) -> Self {
+        match &err {
+            CellError::ExpectedInt(_) => PlaydbError::new(ErrorCode::TypeMismatch, err),
+            CellError::MissingKey(column) => {
+                let details = ErrorDetails::new(ErrorCode::MissingEncryptionKey, &err).column(column);
+                PlaydbError::from_details(details)
+            },
+            CellError::EncryptionFailed(column, _) => {
+                let details = ErrorDetails::new(ErrorCode::Internal, &err).column(column);
+                PlaydbError::from_details(details)
+            },
+        }
+    }
+}
+
+impl From<IdentifierError> for PlaydbError {
+    fn from(err: IdentifierError) -> Self {
+        PlaydbError::new(ErrorCode::InvalidIdentifier, err)
+    }
+}
+
+impl From<SchemaError> for PlaydbError {
+    fn from(err: SchemaError) -> Self {
+        PlaydbError::new(ErrorCode::InvalidSchema, err)
+    }
+}
+
+impl From<DatabaseError> for PlaydbError {
+    fn from(err: DatabaseError) -> Self {
+        match &err {
+            DatabaseError::TableNotFound(table) => {
+                let table = table.clone();
+                PlaydbError::new(ErrorCode::TableNotFound, err).with_table(&table)
+            },
+            DatabaseError::CorruptedDatabase(_) => PlaydbError::new(ErrorCode::CorruptedCatalog, err),
+            DatabaseError::UnknownError(_) => PlaydbError::new(ErrorCode::Internal, err),
+            DatabaseError::MissingEncryptionKey(table) => {
+                let table = table.clone();
+                PlaydbError::new(ErrorCode::MissingEncryptionKey, err).with_table(&table)
+            },
+            DatabaseError::WrongEncryptionKey(table) => {
+                let table = table.clone();
+                PlaydbError::new(ErrorCode::WrongEncryptionKey, err).with_table(&table)
+            },
+        }
+    }
+}
+
+impl From<CreateTableError> for PlaydbError {
+    fn from(err: CreateTableError) -> Self {
+        let code = match &err {
+            CreateTableError::InvalidSchemaDefinition(_) => ErrorCode::InvalidSchema,
+            CreateTableError::TableAlreadyExists => ErrorCode::TableAlreadyExists,
+            CreateTableError::InvalidIdentifier(_) => ErrorCode::InvalidIdentifier,
+            CreateTableError::UnknownError(_) => ErrorCode::Internal,
+        };
+        PlaydbError::new(code, err)
+    }
+}
+
+impl From<TableAccessError> for PlaydbError {
+    fn from(err: TableAccessError) -> Self {
+        let code = match &err {
+            TableAccessError::QuotaExceeded(_) => ErrorCode::QuotaExceeded,
+            TableAccessError::StaleRow { .. } => ErrorCode::StaleRow,
+            TableAccessError::InsertManyError { error, .. } if matches!(**error, TableAccessError::QuotaExceeded(_)) => ErrorCode::QuotaExceeded,
+            _ => ErrorCode::Internal,
+        };
+        PlaydbError::new(code, err)
+    }
+}
+
+impl From<SeqAccessError> for PlaydbError {
+    fn from(err: SeqAccessError) -> Self {
+        match &err {
+            SeqAccessError::ColumnNotFound(column) | SeqAccessError::NotASequenceColumn(column) => {
+                let details = ErrorDetails::new(ErrorCode::SequenceError, &err).column(column);
+                PlaydbError::from_details(details)
+            },
+            _ => PlaydbError::new(ErrorCode::SequenceError, err),
+        }
+    }
+}
+
+impl From<ExportError> for PlaydbError {
+    fn from(err: ExportError) -> Self {
+        let code = match &err {
+            ExportError::IoError(_) => ErrorCode::Io,
+            ExportError::InvalidFormat(_) => ErrorCode::InvalidFormat,
+            ExportError::TableError(_) => ErrorCode::Internal,
+            ExportError::ConstraintViolations(_) => ErrorCode::ConstraintViolation,
+            ExportError::ChecksumMismatch(_) => ErrorCode::ChecksumMismatch,
+        };
+        PlaydbError::new(code, err)
+    }
+}
+
+impl From<MigrationError> for PlaydbError {
+    fn from(err: MigrationError) -> Self {
+        PlaydbError::new(ErrorCode::MigrationFailed, err)
+    }
+}
+
+#[cfg(feature = "sql")]
+impl From<crate::sql::SqlError> for PlaydbError {
+    fn from(err: crate::sql::SqlError) -> Self {
+        let code = match &err {
+            crate::sql::SqlError::SyntaxError(_) => ErrorCode::SyntaxError,
+            crate::sql::SqlError::ExecutionError(_) => ErrorCode::Internal,
+        };
+        PlaydbError::new(code, err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::{Database, DatabaseError}, error::{ErrorCode, PlaydbError}, store::{StoreError, file_store::FileStore}, table::{ColumnType, table::{Cell, Row}}};
+
+    #[test]
+    fn should_convert_errors_with_code_and_context() {
+        let err = PlaydbError::from(DatabaseError::TableNotFound("persons".to_owned()));
+        assert!(matches!(err, PlaydbError::Catalog(_)));
+        assert_eq!(err.code(), ErrorCode::TableNotFound);
+        assert_eq!(err.code().as_u16(), 4000);
+        assert_eq!(err.details().table.as_deref(), Some("persons"));
+        assert_eq!(err.to_string(), "E4000: Table not found: persons (table=persons)");
+
+        let err = PlaydbError::from(StoreError::ReadOnly).with_table("persons").with_page(3);
+        assert!(matches!(err, PlaydbError::Store(_)));
+        assert_eq!(err.to_string(), "E1001: StoreError - Store is read only (table=persons, page=3)");
+
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        let table = db.create_table("persons", vec![("name", ColumnType::Varchar(3))]).unwrap();
+        let access = db.table_access(table).unwrap();
+        let row = Row::new(vec![Cell::Varchar("Alice".to_owned())]);
+        let err = PlaydbError::from(row.validate(access.table().schema()).unwrap_err());
+        assert!(matches!(err, PlaydbError::Validation(_)));
+        assert_eq!((err.code(), err.details().column.as_deref()), (ErrorCode::ValueTooLong, Some("name")));
+
+        let err: PlaydbError = db.create_table("persons", vec![("id", ColumnType::Int)]).unwrap_err().into();
+        assert_eq!(err.code(), ErrorCode::TableAlreadyExists);
+    }
+}
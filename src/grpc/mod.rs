@@ -0,0 +1,261 @@
+use std::{collections::HashMap, fs::create_dir_all, path::PathBuf};
+
+use crate::{
+    database::{Database, DatabaseError, NULL_INT, table_access::TableAccessError},
+    sql::{SqlError, executor::{self, ExecResult}},
+    store::file_store::FileStore,
+    table::{ColumnType, TableSchema, table::{Cell, Row}},
+};
+
+// Implementation of the service in proto/playdb.proto.
+//
+// The types below mirror the protobuf messages, so that the generated server (tonic + prost) only has
+// to convert messages and delegate to PlayDbService. The transport itself is not part of this crate yet:
+// it needs an async runtime and Database is not Send (Rc, RefCell), so the service has to run on a single
+// thread (e.g. a tokio LocalSet) or behind a channel. For StreamScan, the sink is the sender of the stream.
+
+pub const DEFAULT_BATCH_SIZE: usize = 1000;
+
+/// Subset of the gRPC status codes that the service returns
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Code {
+    Cancelled = 1,
+    InvalidArgument = 3,
+    NotFound = 5,
+    Internal = 13,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Status {
+    pub code: Code,
+    pub message: String,
+}
+
+impl Status {
+    fn new(code: Code, message: &str) -> Self {
+        Self { code, message: message.to_owned() }
+    }
+}
+
+impl From<DatabaseError> for Status {
+    fn from(err: DatabaseError) -> Self {
+        match err {
+            DatabaseError::TableNotFound(_) => Status::new(Code::NotFound, &err.to_string()),
+            _ => Status::new(Code::Internal, &err.to_string()),
+        }
+    }
+}
+
+impl From<TableAccessError> for Status {
+    fn from(err: TableAccessError) -> Self {
+        Status::new(Code::Internal, &err.to_string())
+    }
+}
+
+impl From<SqlError> for Status {
+    fn from(err: SqlError) -> Self {
+        match err {
+            SqlError::SyntaxError(_) => Status::new(Code::InvalidArgument, &err.to_string()),
+            SqlError::ExecutionError(_) => Status::new(Code::Internal, &err.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Int(i32),
+    Varchar(String),
+    Byte(u32),
+    Bytes(Vec<u8>),
+    Point(f64, f64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnInfo {
+    pub name: String,
+    pub col_type: ColumnType,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct OpenDatabaseResponse {
+    pub name: String,
+    pub created: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct StatementResult {
+    pub tag: String,
+    pub columns: Vec<ColumnInfo>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RowBatch {
+    pub columns: Vec<ColumnInfo>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+pub struct PlayDbService {
+    base_path: PathBuf,
+    databases: HashMap<String, Database<FileStore>>,
+}
+
+impl PlayDbService {
+    pub fn new(base_path: PathBuf) -> Self {
+        Self {
+            base_path,
+            databases: HashMap::new(),
+        }
+    }
+
+    pub fn open_database(&mut self, name: &str) -> Result<OpenDatabaseResponse, Status> {
+        if self.databases.contains_key(name) {
+            return Ok(OpenDatabaseResponse { name: name.to_owned(), created: false });
+        }
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(Status::new(Code::InvalidArgument, "Database names only allow alphanumeric chars and '_', '-'"));
+        }
+
+        let path = self.base_path.join(name);
+        let created = !path.exists();
+        if created {
+            create_dir_all(&path)
+                .map_err(|e| Status::new(Code::Internal, &format!("Cannot create database directory: {}", e)))?;
+        }
+
+        let db = Database::new_with_store(name, FileStore::new(&path));
+        if created {
+            db.drop_create()?;
+        }
+        self.databases.insert(name.to_owned(), db);
+
+        Ok(OpenDatabaseResponse { name: name.to_owned(), created })
+    }
+
+    pub fn execute(&self, database: &str, sql: &str) -> Result<Vec<StatementResult>, Status> {
+        let db = self.database(database)?;
+
+        let results = executor::execute(db, sql)?;
+        Ok(results.into_iter().map(|result| {
+            let tag = result.tag();
+            match result {
+                ExecResult::Rows { schema, rows } | ExecResult::CommandRows { schema, rows, .. } => StatementResult {
+                    tag,
+                    columns: column_infos(&schema),
+                    rows: rows.iter().map(to_values).collect(),
+                },
+                ExecResult::Command(_) => StatementResult { tag, columns: Vec::new(), rows: Vec::new() },
+            }
+        }).collect())
+    }
+
+    /// Sends the rows of a table in batches to the sink. Rows are read page by page, so the whole
+    /// table is never loaded at once. If the sink returns false (client went away), the scan stops.
+    pub fn stream_scan<F: FnMut(RowBatch) -> bool>(
+        &self,
+        database: &str,
+        table: &str,
+        batch_size: usize,
+        mut sink: F,
+    ) -> Result<(), Status> {
+        let db = self.database(database)?;
+        let batch_size = if batch_size == 0 { DEFAULT_BATCH_SIZE } else { batch_size };
+
+        let table = db.read_table(table)?;
+        let access = db.table_access(table)?;
+        let mut columns = column_infos(access.table().schema());
+
+        let mut rows = Vec::with_capacity(batch_size);
+        for res in access.find_all()? {
+            let (_, row) = res?;
+            rows.push(to_values(&row));
+
+            if rows.len() == batch_size {
+                let batch = RowBatch { columns: std::mem::take(&mut columns), rows: std::mem::take(&mut rows) };
+                if !sink(batch) {
+                    return Err(Status::new(Code::Cancelled, "Scan cancelled by client"));
+                }
+            }
+        }
+
+        // the last batch is also sent if it's empty, so that the client always receives the columns
+        if !rows.is_empty() || !columns.is_empty() {
+            sink(RowBatch { columns, rows });
+        }
+
+        Ok(())
+    }
+
+    fn database(&self, name: &str) -> Result<&Database<FileStore>, Status> {
+        self.databases.get(name)
+            .ok_or_else(|| Status::new(Code::NotFound, &format!("Database '{}' is not open", name)))
+    }
+}
+
+fn column_infos(schema: &TableSchema) -> Vec<ColumnInfo> {
+    schema.columns.iter()
+        .map(|c| ColumnInfo { name: c.name.clone(), col_type: c.col_type.clone() })
+        .collect()
+}
+
+fn to_values(row: &Row) -> Vec<Value> {
+    row.cells().iter().map(|cell| match cell {
+        Cell::Int(NULL_INT) => Value::Null,
+        Cell::Int(val) => Value::Int(*val),
+        Cell::Varchar(val) => Value::Varchar(val.clone()),
+        Cell::Byte(val) => Value::Byte(*val as u32),
+        Cell::Blob(val) => Value::Bytes(val.clone()),
+        Cell::Point(val) => Value::Point(val.x(), val.y()),
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grpc::{Code, PlayDbService, Value};
+
+    #[test]
+    fn should_open_execute_and_stream() {
+        let base_path = tempfile::tempdir().unwrap();
+        let mut service = PlayDbService::new(base_path.path().to_path_buf());
+
+        assert!(service.open_database("remote").unwrap().created);
+        assert!(!service.open_database("remote").unwrap().created);
+
+        let results = service.execute("remote", "CREATE TABLE t (id INT, name VARCHAR(10))").unwrap();
+        assert_eq!(results[0].tag, "CREATE TABLE");
+        for i in 0..5 {
+            service.execute("remote", &format!("INSERT INTO t VALUES ({}, 'row')", i)).unwrap();
+        }
+
+        let results = service.execute("remote", "SELECT id FROM t WHERE id = 3").unwrap();
+        assert_eq!(results[0].rows, vec![vec![Value::Int(3)]]);
+
+        let mut batches = Vec::new();
+        service.stream_scan("remote", "t", 2, |batch| {
+            batches.push(batch);
+            true
+        }).unwrap();
+
+        assert_eq!(batches.iter().map(|b| b.rows.len()).collect::<Vec<usize>>(), vec![2, 2, 1]);
+        assert_eq!(batches[0].columns.len(), 2);
+        assert!(batches[1].columns.is_empty());
+    }
+
+    #[test]
+    fn should_map_errors_to_status_codes() {
+        let base_path = tempfile::tempdir().unwrap();
+        let mut service = PlayDbService::new(base_path.path().to_path_buf());
+
+        assert_eq!(service.execute("unknown", "SELECT * FROM t").unwrap_err().code, Code::NotFound);
+        assert_eq!(service.open_database("../x").unwrap_err().code, Code::InvalidArgument);
+
+        service.open_database("remote").unwrap();
+        assert_eq!(service.execute("remote", "SELEC").unwrap_err().code, Code::InvalidArgument);
+        assert_eq!(service.stream_scan("remote", "missing", 0, |_| true).unwrap_err().code, Code::NotFound);
+
+        service.execute("remote", "CREATE TABLE t (id INT); INSERT INTO t VALUES (1); INSERT INTO t VALUES (2)").unwrap();
+        let cancelled = service.stream_scan("remote", "t", 1, |_| false);
+        assert_eq!(cancelled.unwrap_err().code, Code::Cancelled);
+    }
+}
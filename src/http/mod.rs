@@ -0,0 +1,409 @@
+use std::{io::{BufRead, BufReader, ErrorKind, Read, Take, Write}, net::TcpListener, time::Duration};
+
+use crate::{database::{Database, DatabaseError, table_access::{QueryResult, TableAccessError}}, store::Store, table::{ColumnType, TableSchema, table::{Cell, Point, Row, parse_hex, to_hex}}};
+
+// Minimal REST interface to inspect or feed a database during development:
+//   GET    /tables/{name}?col=value&...   scan (all filters are equality filters combined with AND)
+//   POST   /tables/{name}                 insert, body: col=value&... (application/x-www-form-urlencoded)
+//   DELETE /tables/{name}?col=value&...   delete matching rows
+//
+// Responses are JSON. The server is blocking and handles one request after another:
+// Database is not thread safe (Rc, RefCell), so an async runtime (axum, hyper) wouldn't help here.
+// A body larger than MAX_BODY_SIZE is rejected with 413 before it is read, a request line and headers larger than
+// MAX_HEADER_SIZE with 431. A client that sends nothing for READ_TIMEOUT gets 408, so it can't block the server.
+pub const MAX_BODY_SIZE: usize = 1024 * 1024;
+pub const MAX_HEADER_SIZE: usize = 8 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    pub body: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+#[derive(Debug)]
+struct HttpError {
+    status: u16,
+    msg: String,
+}
+
+impl HttpError {
+    fn new(status: u16, msg: &str) -> Self {
+        Self { status, msg: msg.to_owned() }
+    }
+}
+
+impl From<DatabaseError> for HttpError {
+    fn from(err: DatabaseError) -> Self {
+        match err {
+            DatabaseError::TableNotFound(_) => HttpError::new(404, &err.to_string()),
+            _ => HttpError::new(500, &err.to_string()),
+        }
+    }
+}
+
+impl From<TableAccessError> for HttpError {
+    fn from(err: TableAccessError) -> Self {
+        match err {
+            TableAccessError::InsertRowError(_) => HttpError::new(400, &err.to_string()),
+            _ => HttpError::new(500, &err.to_string()),
+        }
+    }
+}
+
+impl Response {
+    fn json(status: u16, body: String) -> Self {
+        Self { status, body }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            201 => "Created",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            408 => "Request Timeout",
+            413 => "Payload Too Large",
+            431 => "Request Header Fields Too Large",
+            _ => "Internal Server Error",
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            self.reason(),
+            self.body.len(),
+            self.body
+        ).into_bytes()
+    }
+}
+
+/// Serves the database until the listener fails. Blocks the current thread.
+pub fn serve<S: Store>(db: &Database<S>, addr: &str) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    for stream in listener.incoming() {
+        let mut stream = stream?;
+        // without a timeout, a client that doesn't send or read would block all others
+        if stream.set_read_timeout(Some(READ_TIMEOUT)).and_then(|_| stream.set_write_timeout(Some(READ_TIMEOUT))).is_err() {
+            continue;
+        }
+        let response = match read_request(&stream) {
+            Ok(request) => handle(db, &request),
+            Err(err) => Response::json(err.status, error_json(&err.msg)),
+        };
+        // a client that went away is not a reason to stop the server
+        let _ = stream.write_all(&response.to_bytes());
+    }
+    Ok(())
+}
+
+fn read_request<R: Read>(stream: R) -> Result<Request, HttpError> {
+    let mut reader = BufReader::new(stream.take(MAX_HEADER_SIZE as u64));
+    let request_line = read_head_line(&mut reader)?;
+
+    let mut content_length = 0;
+    loop {
+        let header = read_head_line(&mut reader)?;
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.trim().eq_ignore_ascii_case("content-length") {
+            content_length = value.trim().parse()
+                .map_err(|_| HttpError::new(400, &format!("Invalid Content-Length '{}'", value.trim())))?;
+        }
+    }
+    if content_length > MAX_BODY_SIZE {
+        return Err(HttpError::new(413, &format!("The body is larger than {} bytes", MAX_BODY_SIZE)));
+    }
+
+    let mut body = vec![0; content_length];
+    reader.get_mut().set_limit(content_length as u64);
+    reader.read_exact(&mut body).map_err(read_error)?;
+
+    parse_request(&request_line, &String::from_utf8_lossy(&body))
+        .ok_or_else(|| HttpError::new(400, "Invalid request line"))
+}
+
+// a line of the request line and headers, together they must not be larger than MAX_HEADER_SIZE
+fn read_head_line<R: Read>(reader: &mut BufReader<Take<R>>) -> Result<String, HttpError> {
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(read_error)?;
+    if !line.ends_with('\n') && reader.get_ref().limit() == 0 {
+        return Err(HttpError::new(431, &format!("The request line and headers are larger than {} bytes", MAX_HEADER_SIZE)));
+    }
+    Ok(line)
+}
+
+fn read_error(err: std::io::Error) -> HttpError {
+    match err.kind() {
+        ErrorKind::WouldBlock | ErrorKind::TimedOut => HttpError::new(408, "Timeout while reading the request"),
+        _ => HttpError::new(400, &err.to_string()),
+    }
+}
+
+pub fn parse_request(request_line: &str, body: &str) -> Option<Request> {
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next()?.to_owned();
+    let target = parts.next()?;
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    Some(Request {
+        method,
+        path: url_decode(path),
+        query: parse_form(query),
+        body: body.to_owned(),
+    })
+}
+
+fn parse_form(input: &str) -> Vec<(String, String)> {
+    input.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (url_decode(key), url_decode(value))
+        })
+        .collect()
+}
+
+fn url_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                match hex {
+                    Some(b) => {
+                        decoded.push(b);
+                        i += 2;
+                    },
+                    None => decoded.push(b'%'),
+                }
+            },
+            b => decoded.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+pub fn handle<S: Store>(db: &Database<S>, request: &Request) -> Response {
+    route(db, request).unwrap_or_else(|err| Response::json(err.status, error_json(&err.msg)))
+}
+
+fn route<S: Store>(db: &Database<S>, request: &Request) -> Result<Response, HttpError> {
+    let segments: Vec<&str> = request.path.split('/').filter(|s| !s.is_empty()).collect();
+    let table_name = match segments.as_slice() {
+        ["tables", name] => *name,
+        _ => return Err(HttpError::new(404, &format!("No resource at '{}'", request.path))),
+    };
+
+    let table = db.read_table(table_name)?;
+    let access = db.table_access(table)?;
+    let schema = access.table().schema().clone();
+
+    match request.method.as_str() {
+        "GET" => {
+            let rows = find_rows(&access, &schema, &request.query)?;
+            Ok(Response::json(200, rows_json(&schema, rows.iter().map(|(_, row)| row))))
+        },
+        "POST" => {
+            let values = parse_form(&request.body).into_iter()
+                .map(|(col_name, value)| Ok((col_name.clone(), parse_cell(&schema, &col_name, &value)?)))
+                .collect::<Result<Vec<(String, Cell)>, HttpError>>()?;
+
+            access.insert_map(values.iter().map(|(col_name, cell)| (col_name, cell)))?;
+            Ok(Response::json(201, "{\"inserted\":1}".to_owned()))
+        },
+        "DELETE" => {
+            let rows = find_rows(&access, &schema, &request.query)?;
+            let deleted = rows.len();
+            access.delete(QueryResult::from_rows(rows, schema))?;
+            Ok(Response::json(200, format!("{{\"deleted\":{}}}", deleted)))
+        },
+        _ => Err(HttpError::new(405, &format!("Method '{}' is not supported", request.method))),
+    }
+}
+
+fn find_rows<S: Store>(
+    access: &crate::database::table_access::TableAccess<'_, S>,
+    schema: &TableSchema,
+    query: &[(String, String)],
+) -> Result<Vec<(crate::data::page::Record, Row)>, HttpError> {
+    let filters = query.iter()
+        .map(|(col_name, value)| {
+            let cell = parse_cell(schema, col_name, value)?;
+            let index = schema.find_index_by_name(col_name)
+                .ok_or_else(|| HttpError::new(400, &format!("Column '{}' not found", col_name)))?;
+            Ok((col_name.as_str(), index, cell))
+        })
+        .collect::<Result<Vec<(&str, usize, Cell)>, HttpError>>()?;
+
+    // the first filter may use an index, the others are applied while scanning
+    let result = match filters.first() {
+        Some((col_name, _, cell)) => access.find(col_name, cell.clone())?,
+        None => access.find_all()?,
+    };
+
+    let rest: Vec<(usize, Cell)> = filters.into_iter().skip(1).map(|(_, index, cell)| (index, cell)).collect();
+    Ok(result
+        .filter(move |(_, row)| rest.iter().all(|(index, cell)| &row.cells()[*index] == cell))
+        .rows()?)
+}
+
+fn parse_cell(schema: &TableSchema, col_name: &str, value: &str) -> Result<Cell, HttpError> {
+    let index = schema.find_index_by_name(col_name)
+        .ok_or_else(|| HttpError::new(400, &format!("Column '{}' not found", col_name)))?;
+
+    let invalid = || HttpError::new(400, &format!("Invalid value '{}' for column '{}'", value, col_name));
+    match schema.columns[index].col_type {
+        ColumnType::Int => value.parse::<i32>().map(Cell::Int).map_err(|_| invalid()),
+        ColumnType::Varchar(_) => Ok(Cell::Varchar(value.to_owned())),
+        ColumnType::Byte => value.parse::<u8>().map(Cell::Byte).map_err(|_| invalid()),
+        ColumnType::Blob => parse_hex(value).map(Cell::Blob).ok_or_else(invalid),
+        ColumnType::Point => Point::parse(value).map(Cell::Point).ok_or_else(invalid),
+    }
+}
+
+fn rows_json<'r, I: Iterator<Item = &'r Row>>(schema: &TableSchema, rows: I) -> String {
+    let rows: Vec<String> = rows.map(|row| {
+        let fields: Vec<String> = schema.columns.iter()
+            .zip(row.cells().iter())
+            .map(|(col, cell)| format!("{}:{}", json_string(&col.name), cell_json(cell)))
+            .collect();
+        format!("{{{}}}", fields.join(","))
+    }).collect();
+
+    format!("[{}]", rows.join(","))
+}
+
+fn cell_json(cell: &Cell) -> String {
+    match cell {
+        Cell::Int(v) => v.to_string(),
+        Cell::Varchar(s) => json_string(s),
+        Cell::Byte(b) => b.to_string(),
+        Cell::Blob(b) => json_string(&to_hex(b)),
+        Cell::Point(p) => json_string(&p.to_string()),
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn error_json(msg: &str) -> String {
+    format!("{{\"error\":{}}}", json_string(msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::Database, http::{MAX_BODY_SIZE, MAX_HEADER_SIZE, handle, parse_request, read_request}, store::file_store::FileStore, table::ColumnType};
+
+    fn request(db: &Database<FileStore>, request_line: &str, body: &str) -> (u16, String) {
+        let request = parse_request(request_line, body).unwrap();
+        let response = handle(db, &request);
+        (response.status, response.body)
+    }
+
+    #[test]
+    fn should_reject_invalid_and_too_large_requests() {
+        let request = read_request("POST /tables/t HTTP/1.1\r\nContent-Length: 5\r\n\r\nid=1&".as_bytes()).unwrap();
+        assert_eq!(request.body, "id=1&");
+
+        let too_large = format!("POST /tables/t HTTP/1.1\r\nContent-Length: {}\r\n\r\n", MAX_BODY_SIZE + 1);
+        assert_eq!(read_request(too_large.as_bytes()).err().map(|e| e.status), Some(413));
+        let invalid = "POST /tables/t HTTP/1.1\r\nContent-Length: -1\r\n\r\n";
+        assert_eq!(read_request(invalid.as_bytes()).err().map(|e| e.status), Some(400));
+
+        // the request line and the headers are limited, the body is not part of the limit
+        let long_line = format!("GET /tables/{} HTTP/1.1\r\n\r\n", "t".repeat(MAX_HEADER_SIZE));
+        assert_eq!(read_request(long_line.as_bytes()).err().map(|e| e.status), Some(431));
+        let many_headers = format!("GET /tables/t HTTP/1.1\r\n{}\r\n", "X-Header: value\r\n".repeat(MAX_HEADER_SIZE / 10));
+        assert_eq!(read_request(many_headers.as_bytes()).err().map(|e| e.status), Some(431));
+        let large_body = format!("POST /tables/t HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}", MAX_HEADER_SIZE * 2, "x".repeat(MAX_HEADER_SIZE * 2));
+        assert_eq!(read_request(large_body.as_bytes()).unwrap().body.len(), MAX_HEADER_SIZE * 2);
+
+        // a client that doesn't send anything (read timeout of the stream)
+        struct Silent;
+        impl std::io::Read for Silent {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::WouldBlock.into())
+            }
+        }
+        assert_eq!(read_request(Silent).err().map(|e| e.status), Some(408));
+    }
+
+    #[test]
+    fn should_insert_scan_and_delete_rows() {
+        let base_path = tempfile::tempdir().unwrap();
+        let store = FileStore::new(base_path.path());
+        let db = Database::new_with_store("test_db", store);
+        db.drop_create().unwrap();
+        db.create_table("persons", vec![
+            ("id", ColumnType::Int),
+            ("name", ColumnType::Varchar(32)),
+        ]).unwrap();
+
+        assert_eq!(request(&db, "POST /tables/persons HTTP/1.1", "id=1&name=Hans+M%C3%BCller").0, 201);
+        assert_eq!(request(&db, "POST /tables/persons HTTP/1.1", "id=2&name=Rabbit").0, 201);
+
+        let (status, body) = request(&db, "GET /tables/persons HTTP/1.1", "");
+        assert_eq!(status, 200);
+        assert_eq!(body, "[{\"id\":1,\"name\":\"Hans Müller\"},{\"id\":2,\"name\":\"Rabbit\"}]");
+
+        let (_, body) = request(&db, "GET /tables/persons?name=Rabbit HTTP/1.1", "");
+        assert_eq!(body, "[{\"id\":2,\"name\":\"Rabbit\"}]");
+
+        let (status, body) = request(&db, "DELETE /tables/persons?id=1 HTTP/1.1", "");
+        assert_eq!(status, 200);
+        assert_eq!(body, "{\"deleted\":1}");
+
+        let (_, body) = request(&db, "GET /tables/persons HTTP/1.1", "");
+        assert_eq!(body, "[{\"id\":2,\"name\":\"Rabbit\"}]");
+    }
+
+    #[test]
+    fn should_return_error_responses() {
+        let base_path = tempfile::tempdir().unwrap();
+        let store = FileStore::new(base_path.path());
+        let db = Database::new_with_store("test_db", store);
+        db.drop_create().unwrap();
+        db.create_table("persons", vec![("id", ColumnType::Int)]).unwrap();
+
+        assert_eq!(request(&db, "GET /tables/unknown HTTP/1.1", "").0, 404);
+        assert_eq!(request(&db, "GET /unknown HTTP/1.1", "").0, 404);
+        assert_eq!(request(&db, "PUT /tables/persons HTTP/1.1", "").0, 405);
+        assert_eq!(request(&db, "GET /tables/persons?id=abc HTTP/1.1", "").0, 400);
+        assert_eq!(request(&db, "POST /tables/persons HTTP/1.1", "age=3").0, 400);
+    }
+}
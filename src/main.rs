@@ -1,4 +1,4 @@
-use crate::{database::Database, store::file_store::FileStore, table::{ColumnType, table::{Cell, Row}}};
+use crate::{database::{Database, system_views::STATS_TABLES}, store::file_store::FileStore, table::{ColumnType, table::{Cell, Row}}};
 
 // ignore dead_code while developing
 #[allow(dead_code)]
@@ -11,6 +11,22 @@ mod store;
 mod database;
 #[allow(unused)]
 mod tree;
+#[allow(dead_code)]
+mod error;
+#[allow(dead_code)]
+mod clock;
+#[cfg(feature = "playdb-http")]
+#[allow(dead_code)]
+mod http;
+#[cfg(feature = "sql")]
+#[allow(dead_code)]
+mod sql;
+#[cfg(feature = "playdb-pgwire")]
+#[allow(dead_code)]
+mod pgwire;
+#[cfg(feature = "playdb-grpc")]
+#[allow(dead_code)]
+mod grpc;
 
 fn create_table_persons(db: &Database<FileStore>) {
     let persons_table = db.create_table("persons", vec![
@@ -30,9 +46,14 @@ fn create_table_persons(db: &Database<FileStore>) {
         }
         let next_id = seq_acc.next_val("id").unwrap();
         
-        person_acc.insert(&Row::new(
-            vec![Cell::Int(next_id), Cell::Varchar("Some".to_owned()), Cell::Int(120 + i), Cell::Byte(1)]
-        )).unwrap();
+        let row = Row::builder(person_acc.table().schema())
+            .set("id", next_id).unwrap()
+            .set("name", "Some").unwrap()
+            .set("number", 120 + i).unwrap()
+            .set("flag", 1u8).unwrap()
+            .build().unwrap();
+
+        person_acc.insert(&row).unwrap();
     }
 }
 
@@ -40,7 +61,7 @@ fn find_by_id_index(db: &Database<FileStore>, id: i32) {
     let table = db.read_table("persons").unwrap();
     let tbl_acc = db.table_access(table).unwrap();
 
-    for (_, r) in  tbl_acc.find("id", Cell::Int(id)).unwrap().rows() {
+    for (_, r) in  tbl_acc.find("id", Cell::Int(id)).unwrap().rows().unwrap() {
         println!("{:?}", r);
     }
 }
@@ -49,15 +70,25 @@ fn find_by_number_without_index(db: &Database<FileStore>, num: i32) {
     let table = db.read_table("persons").unwrap();
     let tbl_acc = db.table_access(table).unwrap();
 
-    for (_, r) in  tbl_acc.find("number", Cell::Int(num)).unwrap().rows() {
+    for (_, r) in  tbl_acc.find("number", Cell::Int(num)).unwrap().rows().unwrap() {
         println!("{:?}", r);
     }
 }
 
+fn print_table_stats(db: &Database<FileStore>) {
+    for r in db.system_view(STATS_TABLES).unwrap().rows().unwrap() {
+        println!("{:?}", r);
+    }
+}
 
 fn main() {
-    let db = Database::new("testdb");
+    let db = Database::new("testdb").expect("Cannot open database testdb");
     // create_table_persons(&db);
     // find_by_id_index(&db, 19999);
     // find_by_number_without_index(&db, 20000);
+    print_table_stats(&db);
+    // needs feature playdb-http:
+    // http::serve(&db, "127.0.0.1:8080").unwrap();
+    // needs feature playdb-pgwire (connect with: psql -h 127.0.0.1 -p 5433):
+    // pgwire::serve(&db, "127.0.0.1:5433").unwrap();
 }
@@ -0,0 +1,310 @@
+use std::{io::{self, Read, Write}, net::TcpListener};
+
+use crate::{database::{Database, NULL_INT}, sql::{SqlError, executor::{self, ExecResult}, parser}, store::Store, table::{ColumnType, TableSchema, table::{Cell, to_hex}}};
+
+// Postgres frontend/backend protocol (version 3), simple query flow only:
+//   startup (SSL is declined, no authentication) -> ReadyForQuery
+//   Query -> RowDescription, DataRow*, CommandComplete (per statement) -> ReadyForQuery
+//   Terminate
+// Everything is sent in text format. The extended query protocol (Parse/Bind/Execute) is answered with an error.
+//
+// Like the http module, connections are handled one after another, because Database is not thread safe.
+
+const PROTOCOL_VERSION_3: i32 = 196608;
+const SSL_REQUEST: i32 = 80877103;
+const GSSENC_REQUEST: i32 = 80877104;
+const CANCEL_REQUEST: i32 = 80877102;
+
+// type oids from pg_type
+const INT2_OID: i32 = 21;
+const INT4_OID: i32 = 23;
+const VARCHAR_OID: i32 = 1043;
+const BYTEA_OID: i32 = 17;
+const POINT_OID: i32 = 600;
+
+pub fn serve<S: Store>(db: &Database<S>, addr: &str) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let mut reader = stream.try_clone()?;
+        let mut writer = stream;
+        // a client that went away is not a reason to stop the server
+        let _ = handle_connection(db, &mut reader, &mut writer);
+    }
+    Ok(())
+}
+
+pub fn handle_connection<S: Store, R: Read, W: Write>(db: &Database<S>, reader: &mut R, writer: &mut W) -> io::Result<()> {
+    if !startup(reader, writer)? {
+        return Ok(());
+    }
+
+    loop {
+        let mut msg_type = [0u8; 1];
+        match reader.read_exact(&mut msg_type) {
+            Ok(()) => {},
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(err) => return Err(err),
+        }
+        let body = read_body(reader)?;
+
+        match msg_type[0] {
+            b'Q' => {
+                let sql = String::from_utf8_lossy(body.strip_suffix(&[0]).unwrap_or(&body)).into_owned();
+                simple_query(db, &sql, writer)?;
+                ready_for_query(writer)?;
+            },
+            b'X' => return Ok(()),
+            b'P' => error_response(writer, "0A000", "Extended query protocol is not supported")?,
+            // end of an extended query cycle
+            b'S' => ready_for_query(writer)?,
+            // other messages of the extended protocol are ignored, the error was sent already on Parse
+            _ => {},
+        }
+        writer.flush()?;
+    }
+}
+
+/// Returns false if the client does not want to run queries (e.g. cancel request)
+fn startup<R: Read, W: Write>(reader: &mut R, writer: &mut W) -> io::Result<bool> {
+    loop {
+        let body = read_body(reader)?;
+        if body.len() < 4 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Startup message too short"));
+        }
+        let code = i32::from_be_bytes([body[0], body[1], body[2], body[3]]);
+
+        match code {
+            SSL_REQUEST | GSSENC_REQUEST => {
+                writer.write_all(b"N")?;
+                writer.flush()?;
+            },
+            CANCEL_REQUEST => return Ok(false),
+            PROTOCOL_VERSION_3 => break,
+            _ => {
+                error_response(writer, "08P01", &format!("Unsupported protocol version {}", code))?;
+                writer.flush()?;
+                return Ok(false);
+            }
+        }
+    }
+
+    // AuthenticationOk
+    message(writer, b'R', &0i32.to_be_bytes())?;
+    for (name, value) in [
+        ("server_version", "14.0 (playdb)"),
+        ("server_encoding", "UTF8"),
+        ("client_encoding", "UTF8"),
+        ("DateStyle", "ISO, MDY"),
+        ("integer_datetimes", "on"),
+        ("standard_conforming_strings", "on"),
+    ] {
+        let mut body = Vec::new();
+        put_str(&mut body, name);
+        put_str(&mut body, value);
+        message(writer, b'S', &body)?;
+    }
+    ready_for_query(writer)?;
+    writer.flush()?;
+
+    Ok(true)
+}
+
+fn simple_query<S: Store, W: Write>(db: &Database<S>, sql: &str, writer: &mut W) -> io::Result<()> {
+    let statements = match parser::parse(sql) {
+        Ok(statements) => statements,
+        Err(err) => return sql_error(writer, err),
+    };
+
+    if statements.is_empty() {
+        return message(writer, b'I', &[]);
+    }
+
+    // statements are executed one by one, so that results before a failing statement are sent
+    for statement in statements {
+        match executor::execute_statement(db, statement) {
+            Ok(result) => {
+                if let ExecResult::Rows { schema, rows } | ExecResult::CommandRows { schema, rows, .. } = &result {
+                    message(writer, b'T', &row_description(schema))?;
+                    for row in rows {
+                        message(writer, b'D', &data_row(row.cells()))?;
+                    }
+                }
+                let mut body = Vec::new();
+                put_str(&mut body, &result.tag());
+                message(writer, b'C', &body)?;
+            },
+            Err(err) => return sql_error(writer, err),
+        }
+    }
+
+    Ok(())
+}
+
+fn row_description(schema: &TableSchema) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend_from_slice(&(schema.columns.len() as i16).to_be_bytes());
+    for column in schema.columns.iter() {
+        let (type_oid, type_len): (i32, i16) = match column.col_type {
+            ColumnType::Int => (INT4_OID, 4),
+            // there is no unsigned single byte type in Postgres
+            ColumnType::Byte => (INT2_OID, 2),
+            ColumnType::Varchar(_) => (VARCHAR_OID, -1),
+            ColumnType::Blob => (BYTEA_OID, -1),
+            ColumnType::Point => (POINT_OID, 16),
+        };
+
+        put_str(&mut body, &column.name);
+        body.extend_from_slice(&0i32.to_be_bytes()); // table oid
+        body.extend_from_slice(&0i16.to_be_bytes()); // column attribute number
+        body.extend_from_slice(&type_oid.to_be_bytes());
+        body.extend_from_slice(&type_len.to_be_bytes());
+        body.extend_from_slice(&(-1i32).to_be_bytes()); // type modifier
+        body.extend_from_slice(&0i16.to_be_bytes()); // text format
+    }
+    body
+}
+
+fn data_row(cells: &[Cell]) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend_from_slice(&(cells.len() as i16).to_be_bytes());
+    for cell in cells {
+        let value = match cell {
+            Cell::Int(NULL_INT) => {
+                body.extend_from_slice(&(-1i32).to_be_bytes());
+                continue;
+            },
+            Cell::Int(val) => val.to_string(),
+            Cell::Byte(val) => val.to_string(),
+            Cell::Varchar(val) => val.clone(),
+            // text format of bytea
+            Cell::Blob(val) => format!("\\x{}", to_hex(val)),
+            // same text format as the point type of Postgres
+            Cell::Point(val) => val.to_string(),
+        };
+        body.extend_from_slice(&(value.len() as i32).to_be_bytes());
+        body.extend_from_slice(value.as_bytes());
+    }
+    body
+}
+
+fn sql_error<W: Write>(writer: &mut W, err: SqlError) -> io::Result<()> {
+    let code = match err {
+        SqlError::SyntaxError(_) => "42601",
+        SqlError::ExecutionError(_) => "XX000",
+    };
+    error_response(writer, code, &err.to_string())
+}
+
+fn error_response<W: Write>(writer: &mut W, code: &str, msg: &str) -> io::Result<()> {
+    let mut body = Vec::new();
+    for (field, value) in [(b'S', "ERROR"), (b'V', "ERROR"), (b'C', code), (b'M', msg)] {
+        body.push(field);
+        put_str(&mut body, value);
+    }
+    body.push(0);
+    message(writer, b'E', &body)
+}
+
+fn ready_for_query<W: Write>(writer: &mut W) -> io::Result<()> {
+    // 'I': idle, there are no transactions
+    message(writer, b'Z', b"I")
+}
+
+fn message<W: Write>(writer: &mut W, msg_type: u8, body: &[u8]) -> io::Result<()> {
+    writer.write_all(&[msg_type])?;
+    writer.write_all(&((body.len() + 4) as i32).to_be_bytes())?;
+    writer.write_all(body)
+}
+
+fn put_str(buf: &mut Vec<u8>, value: &str) {
+    buf.extend_from_slice(value.as_bytes());
+    buf.push(0);
+}
+
+// reads the length prefix (which includes itself) and the message body
+fn read_body<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let len = i32::from_be_bytes(len);
+    if !(4..=1 << 24).contains(&len) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Invalid message length {}", len)));
+    }
+
+    let mut body = vec![0u8; len as usize - 4];
+    reader.read_exact(&mut body)?;
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::{database::Database, pgwire::{PROTOCOL_VERSION_3, SSL_REQUEST, handle_connection}, store::file_store::FileStore};
+
+    fn frontend_message(msg_type: Option<u8>, body: &[u8]) -> Vec<u8> {
+        let mut msg = Vec::new();
+        if let Some(t) = msg_type {
+            msg.push(t);
+        }
+        msg.extend_from_slice(&((body.len() + 4) as i32).to_be_bytes());
+        msg.extend_from_slice(body);
+        msg
+    }
+
+    // splits the backend output into (type, body)
+    fn backend_messages(mut output: &[u8]) -> Vec<(u8, Vec<u8>)> {
+        let mut messages = Vec::new();
+        while !output.is_empty() {
+            let msg_type = output[0];
+            if msg_type == b'N' && messages.is_empty() {
+                messages.push((b'N', Vec::new()));
+                output = &output[1..];
+                continue;
+            }
+            let len = i32::from_be_bytes([output[1], output[2], output[3], output[4]]) as usize;
+            messages.push((msg_type, output[5..1 + len].to_vec()));
+            output = &output[1 + len..];
+        }
+        messages
+    }
+
+    #[test]
+    fn should_answer_simple_queries() {
+        let base_path = tempfile::tempdir().unwrap();
+        let store = FileStore::new(base_path.path());
+        let db = Database::new_with_store("test_db", store);
+        db.drop_create().unwrap();
+
+        let mut startup = PROTOCOL_VERSION_3.to_be_bytes().to_vec();
+        startup.extend_from_slice(b"user\0psql\0database\0test_db\0\0");
+
+        let mut input = Vec::new();
+        input.extend(frontend_message(None, &SSL_REQUEST.to_be_bytes()));
+        input.extend(frontend_message(None, &startup));
+        input.extend(frontend_message(Some(b'Q'), b"CREATE TABLE t (id INT, name VARCHAR(10)); INSERT INTO t VALUES (7, 'seven')\0"));
+        input.extend(frontend_message(Some(b'Q'), b"SELECT * FROM t\0"));
+        input.extend(frontend_message(Some(b'Q'), b"SELECT * FROM missing\0"));
+        input.extend(frontend_message(Some(b'Q'), b";\0"));
+        input.extend(frontend_message(Some(b'X'), &[]));
+
+        let mut output = Vec::new();
+        handle_connection(&db, &mut Cursor::new(input), &mut output).unwrap();
+
+        let types: Vec<u8> = backend_messages(&output).iter().map(|(t, _)| *t).collect();
+        assert_eq!(
+            String::from_utf8(types).unwrap(),
+            // ssl declined, auth ok, 6 parameters, ready
+            "NRSSSSSSZ".to_owned()
+            + "CCZ" // create, insert
+            + "TDCZ" // select
+            + "EZ" // error
+            + "IZ" // empty query
+        );
+
+        let messages = backend_messages(&output);
+        let data_row = &messages[13].1;
+        assert_eq!(data_row, &[0, 2, 0, 0, 0, 1, b'7', 0, 0, 0, 5, b's', b'e', b'v', b'e', b'n']);
+        assert_eq!(messages[14].1, b"SELECT 1\0");
+    }
+}
@@ -0,0 +1,341 @@
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
+
+use crate::{
+    database::Database,
+    sql::{CommonTableExpression, RecursiveTerm, Select, SqlError, executor::ExecResult, query::{Mode, OperatorStats, PlanNode, Query}, subquery::same_table},
+    store::Store,
+    table::{TableSchema, table::{Cell, Row}},
+};
+
+// Common table expressions: WITH name AS (SELECT ...) SELECT ... FROM name.
+// A CTE can be used in the FROM clause of the query, of its subqueries and of the CTEs after it. It hides a table
+// with the same name. A CTE can't reference itself (a name in its own SELECT is a table or an earlier CTE).
+// How a CTE is executed depends on the number of references:
+// - once: inlined, it's executed where it's used ("Subquery Scan on name")
+// - more than once: materialized, it's executed at the first use and the other uses read the kept rows
+//   ("CTE Scan on name", the plan of the CTE is shown at the first use)
+// - never: not executed at all
+// The rows of a CTE are kept in memory, and conditions of the outer query are not pushed down into the CTE,
+// so a CTE is always read completely (no index lookup on a CTE, either).
+//
+// WITH RECURSIVE name AS (seed UNION [ALL] recursive term): the seed is executed once, then the recursive term is
+// executed again and again. In the recursive term, the name is the work table: the rows of the previous iteration.
+// The iteration stops at the fixpoint, when an iteration returns no (new) rows. UNION removes duplicates
+// (so a traversal of a graph with cycles stops), UNION ALL keeps them.
+// There is no temp store yet, the work table and the result are kept in memory, like the rows of any other CTE.
+// After MAX_ITERATIONS, the query fails instead of running forever (e.g. UNION ALL over a cycle).
+const MAX_ITERATIONS: usize = 1000;
+
+pub(super) struct Cte {
+    name: String,
+    select: Select,
+    recursive: Option<RecursiveTerm>,
+    // the CTEs before this one and the ones of the outer queries
+    parent: Option<Rc<Cte>>,
+    // in the query, its subqueries and the CTEs after this one
+    references: usize,
+    materialized: RefCell<Option<(TableSchema, Rc<Vec<Row>>)>>,
+}
+
+/// Rows of a CTE as input of a query
+pub(super) struct CteRows {
+    pub schema: TableSchema,
+    pub rows: Rc<Vec<Row>>,
+    pub operator: String,
+    // plan of the CTE if it was executed for this use
+    pub plan: Option<PlanNode>,
+}
+
+impl Cte {
+    /// The CTEs of the select on top of the ones visible in the outer query
+    pub fn chain(select: &Select, outer: Option<Rc<Cte>>) -> Option<Rc<Cte>> {
+        select.ctes.iter().enumerate().fold(outer, |parent, (position, cte)| {
+            let count = usize::from(same_table(&select.table, &cte.name))
+                + select.subqueries.iter().map(|filter| references(&filter.subquery, &cte.name)).sum::<usize>()
+                + select.ctes[position + 1..].iter().map(|later| cte_references(later, &cte.name)).sum::<usize>();
+            Some(Rc::new(Cte {
+                name: cte.name.clone(),
+                select: cte.select.clone(),
+                recursive: cte.recursive.clone(),
+                parent,
+                references: count,
+                materialized: RefCell::new(None),
+            }))
+        })
+    }
+
+    // the rows of the previous iteration of a recursive CTE
+    fn work_table(&self, schema: TableSchema, rows: Vec<Row>) -> Rc<Cte> {
+        Rc::new(Cte {
+            name: self.name.clone(),
+            select: self.select.clone(),
+            recursive: None,
+            parent: self.parent.clone(),
+            references: 0,
+            materialized: RefCell::new(Some((schema, Rc::new(rows)))),
+        })
+    }
+
+    fn query<'db, S: Store>(&self, db: &'db Database<S>) -> Query<'db, S> {
+        Query::with_ctes(db, self.select.clone(), self.parent.clone())
+    }
+
+    fn is_materialized(&self) -> bool {
+        self.references > 1
+    }
+
+    /// Schema of the rows, without executing the CTE
+    pub fn schema<S: Store>(&self, db: &Database<S>) -> Result<TableSchema, SqlError> {
+        if let Some((schema, _)) = self.materialized.borrow().as_ref() {
+            return Ok(schema.clone());
+        }
+        let (result, _) = self.query(db).execute(Mode::Plan)?;
+        rows_of(result).map(|(schema, _)| schema)
+    }
+
+    pub fn rows<S: Store>(&self, db: &Database<S>, mode: Mode) -> Result<CteRows, SqlError> {
+        if let Some((schema, rows)) = self.materialized.borrow().as_ref() {
+            return Ok(CteRows { schema: schema.clone(), rows: rows.clone(), operator: format!("CTE Scan on {}", self.name), plan: None });
+        }
+        let (result, plan) = self.query(db).execute(mode)?;
+        let (schema, rows) = rows_of(result)?;
+        let (rows, plan) = match &self.recursive {
+            Some(term) => self.iterate(db, mode, term, &schema, rows, plan)?,
+            None => (rows, plan),
+        };
+        let rows = Rc::new(rows);
+        let operator = match self.is_materialized() {
+            true => "CTE Scan",
+            false => "Subquery Scan",
+        };
+        // EXPLAIN without ANALYZE has no rows to keep
+        if self.is_materialized() && mode != Mode::Plan {
+            *self.materialized.borrow_mut() = Some((schema.clone(), rows.clone()));
+        }
+        Ok(CteRows { schema, rows, operator: format!("{} on {}", operator, self.name), plan: Some(plan) })
+    }
+
+    // Executes the recursive term until the fixpoint, returns all rows and the plan "Recursive Union" with the
+    // plan of the seed and of the first iteration
+    fn iterate<S: Store>(&self, db: &Database<S>, mode: Mode, term: &RecursiveTerm, schema: &TableSchema, seed: Vec<Row>, seed_plan: PlanNode)
+        -> Result<(Vec<Row>, PlanNode), SqlError> {
+        let mut seen: HashSet<Vec<Cell>> = HashSet::new();
+        let mut distinct = |rows: Vec<Row>| -> Vec<Row> {
+            match term.all {
+                true => rows,
+                false => rows.into_iter().filter(|row| seen.insert(row.cells().clone())).collect(),
+            }
+        };
+
+        let mut all = distinct(seed);
+        let mut work = all.clone();
+        let mut iterations = 0;
+        let mut term_plan = None;
+        loop {
+            let (result, plan) = Query::with_ctes(db, term.select.clone(), Some(self.work_table(schema.clone(), work))).execute(mode)?;
+            let (term_schema, rows) = rows_of(result)?;
+            check_union_schema(schema, &term_schema)?;
+            term_plan.get_or_insert(plan);
+            // EXPLAIN without ANALYZE only plans the recursive term
+            if mode == Mode::Plan {
+                break;
+            }
+            iterations += 1;
+
+            work = distinct(rows);
+            if work.is_empty() {
+                break;
+            }
+            if iterations >= MAX_ITERATIONS {
+                return Err(SqlError::ExecutionError(format!("Recursive CTE '{}' did not finish after {} iterations", self.name, MAX_ITERATIONS)));
+            }
+            all.extend(work.iter().cloned());
+        }
+
+        let union = if term.all { "UNION ALL" } else { "UNION" };
+        let detail = match mode {
+            Mode::Plan => union.to_owned(),
+            _ => format!("{} iterations={}", union, iterations),
+        };
+        let stats = (mode == Mode::Instrumented).then(|| OperatorStats { rows: all.len(), ..OperatorStats::default() });
+        let plan = PlanNode::new("Recursive Union".to_owned(), Some(detail), stats, vec![seed_plan].into_iter().chain(term_plan).collect());
+        Ok((all, plan))
+    }
+}
+
+// the recursive term must return the columns of the seed
+fn check_union_schema(seed: &TableSchema, term: &TableSchema) -> Result<(), SqlError> {
+    if seed.columns.len() != term.columns.len() {
+        return Err(SqlError::ExecutionError(format!(
+            "Each UNION query must have the same number of columns: {} vs {}", seed.columns.len(), term.columns.len()
+        )));
+    }
+    for (a, b) in seed.columns.iter().zip(term.columns.iter()) {
+        if a.col_type.raw_type() != b.col_type.raw_type() {
+            return Err(SqlError::ExecutionError(format!(
+                "UNION types of column '{}' don't match: {} vs {}", a.name, a.col_type.raw_type(), b.col_type.raw_type()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The nearest CTE with the name
+pub(super) fn find(ctes: Option<&Rc<Cte>>, name: &str) -> Option<Rc<Cte>> {
+    let mut current = ctes;
+    while let Some(cte) = current {
+        if same_table(&cte.name, name) {
+            return Some(cte.clone());
+        }
+        current = cte.parent.as_ref();
+    }
+    None
+}
+
+// FROM clauses with the name in the select, its subqueries and CTEs
+fn references(select: &Select, name: &str) -> usize {
+    usize::from(same_table(&select.table, name))
+        + select.subqueries.iter().map(|filter| references(&filter.subquery, name)).sum::<usize>()
+        + select.ctes.iter().map(|cte| cte_references(cte, name)).sum::<usize>()
+}
+
+fn cte_references(cte: &CommonTableExpression, name: &str) -> usize {
+    references(&cte.select, name) + cte.recursive.as_ref().map_or(0, |term| references(&term.select, name))
+}
+
+fn rows_of(result: ExecResult) -> Result<(TableSchema, Vec<Row>), SqlError> {
+    match result {
+        ExecResult::Rows { schema, rows } => Ok((schema, rows)),
+        ExecResult::Command(_) | ExecResult::CommandRows { .. } => Err(SqlError::ExecutionError("Common table expression did not return rows".to_owned())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::Database, sql::{SqlError, executor::{ExecResult, execute}}, store::file_store::FileStore, table::table::Cell};
+
+    fn setup(base_path: &std::path::Path) -> Database<FileStore> {
+        let db = Database::new_with_store("test_db", FileStore::new(base_path));
+        db.drop_create().unwrap();
+        execute(&db, "
+            CREATE TABLE persons (id INT UNIQUE, name VARCHAR(20));
+            INSERT INTO persons VALUES (1, 'Alice');
+            INSERT INTO persons VALUES (2, 'Bob');
+            INSERT INTO persons VALUES (3, 'Carol');
+            CREATE TABLE orders (id INT UNIQUE, person_id INT, total INT);
+            INSERT INTO orders VALUES (10, 1, 50);
+            INSERT INTO orders VALUES (11, 1, 150);
+            INSERT INTO orders VALUES (12, 3, 200);
+        ").unwrap();
+        db
+    }
+
+    fn ids(db: &Database<FileStore>, sql: &str) -> Vec<i32> {
+        match execute(db, sql).unwrap().remove(0) {
+            ExecResult::Rows { rows, .. } => rows.iter().map(|row| row.cells()[0].expect_int("id").unwrap()).collect(),
+            ExecResult::Command(tag) | ExecResult::CommandRows { tag, .. } => panic!("Expected rows, got command {}", tag),
+        }
+    }
+
+    fn plan(db: &Database<FileStore>, sql: &str) -> Vec<String> {
+        match execute(db, &format!("EXPLAIN ANALYZE {}", sql)).unwrap().remove(0) {
+            ExecResult::Rows { rows, .. } => rows.iter().map(|row| match &row.cells()[0] {
+                Cell::Varchar(line) => line.clone(),
+                other => panic!("Expected a varchar, got {:?}", other),
+            }).collect(),
+            ExecResult::Command(tag) | ExecResult::CommandRows { tag, .. } => panic!("Expected rows, got command {}", tag),
+        }
+    }
+
+    #[test]
+    fn should_inline_cte_that_is_used_once() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = setup(base_path.path());
+
+        let sql = "WITH large AS (SELECT person_id, total FROM orders WHERE total > 100) SELECT person_id FROM large WHERE total < 180";
+        assert_eq!(ids(&db, sql), vec![1]);
+        let lines = plan(&db, sql);
+        assert!(lines[1].starts_with("  -> Filter (total < 180) (rows=1 "), "{}", lines[1]);
+        assert!(lines[2].starts_with("    -> Subquery Scan on large (rows=2 "), "{}", lines[2]);
+        assert!(lines[3].starts_with("      -> Project (person_id, total) (rows=2 "), "{}", lines[3]);
+
+        // a CTE hides a table, a later CTE uses an earlier one, ORDER BY and LIMIT on a CTE
+        assert_eq!(ids(&db, "WITH orders AS (SELECT id FROM persons WHERE id > 1), top AS (SELECT * FROM orders ORDER BY id DESC) \
+            SELECT id FROM top LIMIT 1"), vec![3]);
+        // an unused CTE is not executed
+        assert_eq!(ids(&db, "WITH unused AS (SELECT * FROM missing) SELECT id FROM persons WHERE id = 2"), vec![2]);
+        assert!(execute(&db, "WITH used AS (SELECT * FROM missing) SELECT * FROM used").is_err());
+        assert!(execute(&db, "WITH large AS (SELECT total FROM orders) SELECT person_id FROM large").is_err());
+    }
+
+    #[test]
+    fn should_materialize_cte_that_is_used_more_than_once() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = setup(base_path.path());
+
+        let sql = "WITH buyers AS (SELECT person_id FROM orders WHERE total > 100) \
+            SELECT id FROM persons WHERE id IN (SELECT person_id FROM buyers) AND NOT EXISTS (SELECT * FROM buyers WHERE person_id = 3)";
+        assert!(ids(&db, sql).is_empty());
+        assert_eq!(ids(&db, &sql.replace("person_id = 3", "person_id = 2")), vec![1, 3]);
+
+        let lines = plan(&db, sql);
+        let scans: Vec<&String> = lines.iter().filter(|line| line.contains("Scan on")).collect();
+        // the orders are read once, the second use of the CTE reads the kept rows
+        assert_eq!(scans.iter().filter(|line| line.contains("Seq Scan on orders")).count(), 1, "{:?}", lines);
+        assert!(scans.iter().any(|line| line.trim_start().starts_with("-> CTE Scan on buyers (rows=2 ")), "{:?}", lines);
+        assert_eq!(scans.iter().filter(|line| line.contains("CTE Scan on buyers")).count(), 2, "{:?}", lines);
+
+        // the CTE in a correlated subquery that runs row by row is executed once, too
+        let sql = "WITH buyers AS (SELECT person_id, total FROM orders) \
+            SELECT id FROM persons WHERE EXISTS (SELECT * FROM buyers WHERE person_id = persons.id AND total > persons.id) \
+            AND id IN (SELECT person_id FROM buyers)";
+        assert_eq!(ids(&db, sql), vec![1, 3]);
+        let lines = plan(&db, sql);
+        assert_eq!(lines.iter().filter(|line| line.contains("Seq Scan on orders (rows=")).count(), 1, "{:?}", lines);
+    }
+
+    #[test]
+    fn should_traverse_hierarchy_with_recursive_cte() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        execute(&db, "
+            CREATE TABLE employees (id INT UNIQUE, manager_id INT);
+            INSERT INTO employees VALUES (1, 0);
+            INSERT INTO employees VALUES (2, 1);
+            INSERT INTO employees VALUES (3, 1);
+            INSERT INTO employees VALUES (4, 2);
+            INSERT INTO employees VALUES (5, 4);
+            INSERT INTO employees VALUES (6, 3);
+            INSERT INTO employees VALUES (7, 9);
+        ").unwrap();
+
+        // everybody who reports to 2, directly or indirectly
+        let reports = "WITH RECURSIVE reports AS (SELECT id FROM employees WHERE id = 2 \
+            UNION ALL SELECT id FROM employees WHERE manager_id IN (SELECT id FROM reports)) SELECT id FROM reports ORDER BY id";
+        assert_eq!(ids(&db, reports), vec![2, 4, 5]);
+        let lines = plan(&db, reports);
+        assert!(lines[2].starts_with("    -> Subquery Scan on reports (rows=3 "), "{}", lines[2]);
+        assert!(lines[3].starts_with("      -> Recursive Union (UNION ALL iterations=3) (rows=3 "), "{}", lines[3]);
+        let lines = match execute(&db, &format!("EXPLAIN {}", reports)).unwrap().remove(0) {
+            ExecResult::Rows { rows, .. } => rows.len(),
+            ExecResult::Command(tag) | ExecResult::CommandRows { tag, .. } => panic!("Expected rows, got command {}", tag),
+        };
+        assert!(lines > 4);
+
+        // the chain of managers of 5, the CTE is used twice (materialized)
+        let managers = "WITH RECURSIVE chain AS (SELECT id, manager_id FROM employees WHERE id = 5 \
+            UNION SELECT id, manager_id FROM employees WHERE id IN (SELECT manager_id FROM chain)) \
+            SELECT id FROM chain WHERE id IN (SELECT manager_id FROM chain)";
+        assert_eq!(ids(&db, managers), vec![4, 2, 1]);
+
+        // with a cycle, UNION stops when no new rows are found, UNION ALL fails
+        execute(&db, "UPDATE employees SET manager_id = 5 WHERE id = 1").unwrap();
+        assert_eq!(ids(&db, &reports.replace("UNION ALL", "UNION")), vec![1, 2, 3, 4, 5, 6]);
+        assert!(matches!(execute(&db, reports), Err(SqlError::ExecutionError(message)) if message.contains("did not finish")));
+
+        assert!(execute(&db, "WITH RECURSIVE r AS (SELECT id FROM employees UNION SELECT id, manager_id FROM r) SELECT * FROM r").is_err());
+        // RECURSIVE without UNION is a plain CTE
+        assert_eq!(ids(&db, "WITH RECURSIVE r AS (SELECT id FROM employees WHERE id = 7) SELECT * FROM r"), vec![7]);
+    }
+}
@@ -0,0 +1,575 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use crate::{
+    data::page::Record,
+    database::{CreateColumnCommand, Database, table_access::{QueryResult, TableAccess, TableAccessError}},
+    sql::{CompareOp, Condition, Insert, InsertSource, Literal, Projection, SqlError, Statement, parser, query::Query},
+    store::Store,
+    table::{Column, ColumnType, TableSchema, table::{Cell, Point, Row, parse_hex}},
+};
+
+pub enum ExecResult {
+    Rows {
+        schema: TableSchema,
+        rows: Vec<Row>,
+    },
+    // Command tag as expected by Postgres clients, e.g. "INSERT 0 1"
+    Command(String),
+    // INSERT/UPDATE/DELETE ... RETURNING: the affected rows and the tag of the command
+    CommandRows {
+        tag: String,
+        schema: TableSchema,
+        rows: Vec<Row>,
+    },
+}
+
+impl ExecResult {
+    pub fn tag(&self) -> String {
+        match self {
+            ExecResult::Rows { rows, .. } => format!("SELECT {}", rows.len()),
+            ExecResult::Command(tag) | ExecResult::CommandRows { tag, .. } => tag.clone(),
+        }
+    }
+}
+
+/// Parses and executes all statements in `sql`. Stops at the first failing statement.
+pub fn execute<S: Store>(db: &Database<S>, sql: &str) -> Result<Vec<ExecResult>, SqlError> {
+    parser::parse(sql)?
+        .into_iter()
+        .map(|statement| execute_statement(db, statement))
+        .collect()
+}
+
+pub fn execute_statement<S: Store>(db: &Database<S>, statement: Statement) -> Result<ExecResult, SqlError> {
+    match statement {
+        Statement::Select(select) => Query::new(db, select).run(),
+        Statement::Explain(explain) => {
+            let query = Query::new(db, explain.select);
+            let plan = match explain.analyze {
+                true => query.run_instrumented()?.1,
+                false => query.plan()?,
+            };
+            Ok(plan.into_result())
+        },
+        Statement::Insert(Insert { table, columns, source: InsertSource::Select(select), returning }) => {
+            let table = db.read_table(&table)?;
+            let access = db.table_access(table)?;
+            let returning = returning_columns(access.table().schema(), returning)?;
+
+            let result = Query::new(db, *select).run()?;
+            let ExecResult::Rows { schema: result_schema, rows } = result else {
+                return Err(SqlError::ExecutionError("INSERT ... SELECT: the query did not return rows".to_owned()));
+            };
+
+            let (count, inserted) = match columns {
+                // like INSERT ... VALUES with columns: the other columns get defaults
+                Some(columns) => {
+                    if columns.len() != result_schema.columns.len() {
+                        return Err(SqlError::ExecutionError(format!("INSERT has {} columns, but the query returns {}", columns.len(), result_schema.columns.len())));
+                    }
+                    let rows = rows.into_iter()
+                        .map(|row| access.row_from_map(columns.iter().zip(row.cells().iter())))
+                        .collect::<Result<Vec<Row>, TableAccessError>>()?;
+                    // the rows are only cloned for RETURNING
+                    let inserted = returning.as_ref().map(|_| rows.clone()).unwrap_or_default();
+                    (access.insert_all(rows)?, inserted)
+                },
+                None => {
+                    let inserted = returning.as_ref().map(|_| rows.clone()).unwrap_or_default();
+                    (access.insert_from(QueryResult::from_rows(rows, result_schema))?, inserted)
+                },
+            };
+
+            command_result(format!("INSERT 0 {}", count), access.table().schema(), returning, inserted)
+        },
+        Statement::Insert(Insert { table, columns, source: InsertSource::Values(values), returning }) => {
+            let table = db.read_table(&table)?;
+            let access = db.table_access(table)?;
+            let returning = returning_columns(access.table().schema(), returning)?;
+
+            // all values are converted before the first row is inserted, but there are no transactions:
+            // if a row is rejected by insert_many, the rows before it stay inserted
+            let rows = values.into_iter()
+                .enumerate()
+                .map(|(i, values)| values_to_row(&access, columns.as_deref(), values).map_err(|err| match err {
+                    SqlError::ExecutionError(msg) => SqlError::ExecutionError(format!("Row {}: {}", i + 1, msg)),
+                    err => err,
+                }))
+                .collect::<Result<Vec<Row>, SqlError>>()?;
+            let count = access.insert_many(&rows)?;
+
+            command_result(format!("INSERT 0 {}", count), access.table().schema(), returning, rows)
+        },
+        Statement::Update(update) => {
+            let table = db.read_table(&update.table)?;
+            let access = db.table_access(table)?;
+            let schema = access.table().schema().clone();
+            let returning = returning_columns(&schema, update.returning)?;
+
+            let mut assignments = Vec::new();
+            for (name, literal) in update.assignments {
+                let index = column_index(&schema, &name)?;
+                assignments.push((name, to_cell(literal, &schema.columns[index])?));
+            }
+
+            let rows = query(&access, &update.filter)?.rows()?;
+            let count = rows.len();
+            let updated = access.update_returning(
+                QueryResult::from_rows(rows, schema.clone()),
+                assignments.iter().map(|(name, cell)| (name.as_str(), cell.clone())).collect(),
+            )?;
+
+            command_result(format!("UPDATE {}", count), &schema, returning, updated.rows()?)
+        },
+        Statement::Delete(delete) => {
+            let table = db.read_table(&delete.table)?;
+            let access = db.table_access(table)?;
+            let returning = returning_columns(access.table().schema(), delete.returning)?;
+
+            let rows = query(&access, &delete.filter)?.rows()?;
+            let count = rows.len();
+            let deleted = access.delete_returning(QueryResult::from_rows(rows, access.table().schema().clone()))?;
+
+            command_result(format!("DELETE {}", count), access.table().schema(), returning, deleted.rows()?)
+        },
+        Statement::CreateTable(create) => {
+            let columns: Vec<CreateColumnCommand> = create.columns.into_iter()
+                .map(|c| (c.name.as_str(), c.col_type, false, c.unique).into())
+                .collect();
+            db.create_table(&create.name, columns)?;
+
+            Ok(ExecResult::Command("CREATE TABLE".to_owned()))
+        },
+        Statement::CreateTableAs(create) => {
+            let ExecResult::Rows { schema, rows } = Query::new(db, *create.select).run()? else {
+                return Err(SqlError::ExecutionError("CREATE TABLE AS: the query did not return rows".to_owned()));
+            };
+            let (_, count) = db.create_table_from(&create.name, QueryResult::from_rows(rows, schema))?;
+
+            // Postgres reports CREATE TABLE AS like the SELECT
+            Ok(ExecResult::Command(format!("SELECT {}", count)))
+        },
+        Statement::DropTable(name) => {
+            db.drop_table(&name)?;
+            Ok(ExecResult::Command("DROP TABLE".to_owned()))
+        },
+    }
+}
+
+pub(crate) struct Scan<'db> {
+    pub result: QueryResult<'db, (Record, Row)>,
+    // position of the condition in the filter that was passed to find()
+    pub lookup: Option<usize>,
+    pub uses_index: bool,
+    // remaining conditions as (column index, op, value)
+    pub conditions: Vec<(usize, CompareOp, Cell)>,
+}
+
+// The first equality condition is passed to find(), so that an index can be used.
+// All other conditions are evaluated on the loaded rows.
+pub(crate) fn scan<'db, S: Store>(
+    access: &'db TableAccess<'db, S>,
+    filter: &[Condition],
+) -> Result<Scan<'db>, SqlError> {
+    let schema = access.table().schema();
+    let mut conditions = conditions(schema, filter)?;
+
+    let lookup = conditions.iter().position(|(_, op, _)| *op == CompareOp::Eq);
+    let (result, uses_index) = match lookup {
+        Some(position) => {
+            let (index, _, cell) = conditions.remove(position);
+            let uses_index = access.indexed_column_ids().contains(&schema.columns[index].id);
+            (access.find(&filter[position].column, cell)?, uses_index)
+        },
+        None => (access.find_all()?, false),
+    };
+
+    Ok(Scan { result, lookup, uses_index, conditions })
+}
+
+// The conditions as (column index, op, value)
+pub(crate) fn conditions(schema: &TableSchema, filter: &[Condition]) -> Result<Vec<(usize, CompareOp, Cell)>, SqlError> {
+    filter.iter()
+        .map(|condition| {
+            let index = column_index(schema, &condition.column)?;
+            Ok((index, condition.op, to_cell(condition.value.clone(), &schema.columns[index])?))
+        })
+        .collect()
+}
+
+fn query<'db, S: Store>(
+    access: &'db TableAccess<'db, S>,
+    filter: &[Condition],
+) -> Result<QueryResult<'db, (Record, Row)>, SqlError> {
+    let scan = scan(access, filter)?;
+    let conditions = scan.conditions;
+
+    Ok(scan.result.filter(move |(_, row)| {
+        conditions.iter().all(|(index, op, cell)| matches(&row.cells()[*index], *op, cell))
+    }))
+}
+
+pub(crate) fn matches(left: &Cell, op: CompareOp, right: &Cell) -> bool {
+    let ordering = match (left, right) {
+        (Cell::Int(l), Cell::Int(r)) => l.cmp(r),
+        (Cell::Varchar(l), Cell::Varchar(r)) => l.cmp(r),
+        (Cell::Byte(l), Cell::Byte(r)) => l.cmp(r),
+        _ => return false,
+    };
+
+    match op {
+        CompareOp::Eq => ordering == Ordering::Equal,
+        CompareOp::NotEq => ordering != Ordering::Equal,
+        CompareOp::Less => ordering == Ordering::Less,
+        CompareOp::LessEq => ordering != Ordering::Greater,
+        CompareOp::Greater => ordering == Ordering::Greater,
+        CompareOp::GreaterEq => ordering != Ordering::Less,
+    }
+}
+
+// The columns of RETURNING are resolved before anything is written
+fn returning_columns(schema: &TableSchema, returning: Option<Projection>) -> Result<Option<Vec<usize>>, SqlError> {
+    returning.map(|projection| match projection {
+        Projection::All => Ok((0..schema.columns.len()).collect()),
+        Projection::Columns(names) => names.iter()
+            .map(|name| column_index(schema, name))
+            .collect::<Result<Vec<usize>, SqlError>>(),
+        Projection::ApproxCountDistinct(_) => Err(SqlError::ExecutionError("Aggregate functions are not allowed in RETURNING".to_owned())),
+        Projection::Items(_) => Err(SqlError::ExecutionError("Function calls are not allowed in RETURNING".to_owned())),
+    }).transpose()
+}
+
+fn command_result(tag: String, schema: &TableSchema, returning: Option<Vec<usize>>, rows: Vec<Row>) -> Result<ExecResult, SqlError> {
+    let Some(indexes) = returning else {
+        return Ok(ExecResult::Command(tag));
+    };
+    let schema = TableSchema::try_new(indexes.iter().map(|i| schema.columns[*i].clone()).collect())
+        .map_err(|e| SqlError::ExecutionError(e.to_string()))?;
+    let rows = rows.iter()
+        .map(|row| Row::new(indexes.iter().map(|i| row.cells()[*i].clone()).collect()))
+        .collect();
+    Ok(ExecResult::CommandRows { tag, schema, rows })
+}
+
+fn values_to_row<S: Store>(access: &TableAccess<'_, S>, columns: Option<&[String]>, values: Vec<Literal>) -> Result<Row, SqlError> {
+    let schema = access.table().schema();
+    match columns {
+        Some(columns) => {
+            if columns.len() != values.len() {
+                return Err(SqlError::ExecutionError("INSERT has a different number of columns and values".to_owned()));
+            }
+            let mut map = HashMap::new();
+            for (name, literal) in columns.iter().zip(values) {
+                let index = column_index(schema, name)?;
+                map.insert(name.clone(), to_cell(literal, &schema.columns[index])?);
+            }
+            Ok(access.row_from_map(&map)?)
+        },
+        None => {
+            if schema.columns.len() != values.len() {
+                return Err(SqlError::ExecutionError(format!("INSERT expects {} values", schema.columns.len())));
+            }
+            let cells = values.into_iter()
+                .zip(schema.columns.iter())
+                .map(|(literal, column)| to_cell(literal, column))
+                .collect::<Result<Vec<Cell>, SqlError>>()?;
+            Ok(Row::new(cells))
+        },
+    }
+}
+
+pub(crate) fn column_index(schema: &TableSchema, name: &str) -> Result<usize, SqlError> {
+    schema.find_index_by_name(name)
+        .ok_or_else(|| SqlError::ExecutionError(format!("Column '{}' does not exist", name)))
+}
+
+pub(super) fn to_cell(literal: Literal, column: &Column) -> Result<Cell, SqlError> {
+    let type_error = |literal: &Literal| SqlError::ExecutionError(
+        format!("Value {:?} is not valid for column '{}' of type {}", literal, column.name, column.col_type)
+    );
+
+    match (&column.col_type, &literal) {
+        (ColumnType::Int, Literal::Int(value)) => i32::try_from(*value)
+            .map(Cell::Int)
+            .map_err(|_| type_error(&literal)),
+        (ColumnType::Byte, Literal::Int(value)) => u8::try_from(*value)
+            .map(Cell::Byte)
+            .map_err(|_| type_error(&literal)),
+        (ColumnType::Varchar(_), Literal::String(value)) => Ok(Cell::Varchar(value.clone())),
+        // BLOB values are written as hex strings, e.g. '\x0aff' or '0aff'
+        (ColumnType::Blob, Literal::String(value)) => parse_hex(value)
+            .map(Cell::Blob)
+            .ok_or_else(|| type_error(&literal)),
+        // POINT values are written like in Postgres, e.g. '(1.5,-2)'
+        (ColumnType::Point, Literal::String(value)) => Point::parse(value)
+            .map(Cell::Point)
+            .ok_or_else(|| type_error(&literal)),
+        _ => Err(type_error(&literal)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::Database, sql::{SqlError, executor::{ExecResult, execute}}, store::file_store::FileStore, table::{ColumnType, table::{Cell, Point}}};
+
+    fn rows_of(result: &ExecResult) -> Vec<Vec<Cell>> {
+        match result {
+            ExecResult::Rows { rows, .. } | ExecResult::CommandRows { rows, .. } => rows.iter().map(|r| r.cells().clone()).collect(),
+            ExecResult::Command(tag) => panic!("Expected rows, got command {}", tag),
+        }
+    }
+
+    #[test]
+    fn should_execute_dml_statements() {
+        let base_path = tempfile::tempdir().unwrap();
+        let store = FileStore::new(base_path.path());
+        let db = Database::new_with_store("test_db", store);
+        db.drop_create().unwrap();
+
+        let results = execute(&db, "
+            CREATE TABLE persons (id INT UNIQUE, name VARCHAR(100), age BYTE);
+            INSERT INTO persons VALUES (1, 'Alice', 30);
+            INSERT INTO persons (id, name) VALUES (2, 'Bob');
+            INSERT INTO persons VALUES (3, 'Carol', 50);
+        ").unwrap();
+        let tags: Vec<String> = results.iter().map(|r| r.tag()).collect();
+        assert_eq!(tags, vec!["CREATE TABLE", "INSERT 0 1", "INSERT 0 1", "INSERT 0 1"]);
+
+        let result = execute(&db, "SELECT name, age FROM persons WHERE age > 20 AND id <> 3").unwrap();
+        assert_eq!(rows_of(&result[0]), vec![vec![Cell::Varchar("Alice".to_owned()), Cell::Byte(30)]]);
+
+        let result = execute(&db, "UPDATE persons SET age = 31 WHERE id = 1").unwrap();
+        assert_eq!(result[0].tag(), "UPDATE 1");
+
+        let result = execute(&db, "DELETE FROM persons WHERE age < 40").unwrap();
+        assert_eq!(result[0].tag(), "DELETE 2");
+
+        let result = execute(&db, "SELECT * FROM persons").unwrap();
+        assert_eq!(result[0].tag(), "SELECT 1");
+        assert_eq!(rows_of(&result[0]), vec![vec![Cell::Int(3), Cell::Varchar("Carol".to_owned()), Cell::Byte(50)]]);
+
+        let result = execute(&db, "DROP TABLE persons").unwrap();
+        assert_eq!(result[0].tag(), "DROP TABLE");
+    }
+
+    #[test]
+    fn should_reject_values_that_do_not_match_the_column_type() {
+        let base_path = tempfile::tempdir().unwrap();
+        let store = FileStore::new(base_path.path());
+        let db = Database::new_with_store("test_db", store);
+        db.drop_create().unwrap();
+
+        execute(&db, "CREATE TABLE t (id INT, flag BYTE)").unwrap();
+
+        assert!(matches!(execute(&db, "INSERT INTO t VALUES ('a', 1)"), Err(SqlError::ExecutionError(_))));
+        assert!(matches!(execute(&db, "INSERT INTO t VALUES (1, 256)"), Err(SqlError::ExecutionError(_))));
+        assert!(matches!(execute(&db, "INSERT INTO t VALUES (3000000000, 1)"), Err(SqlError::ExecutionError(_))));
+        assert!(matches!(execute(&db, "SELECT missing FROM t"), Err(SqlError::ExecutionError(_))));
+        assert!(matches!(execute(&db, "SELECT * FROM missing"), Err(SqlError::ExecutionError(_))));
+    }
+
+    #[test]
+    fn should_store_blob_values_written_as_hex() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+
+        execute(&db, "
+            CREATE TABLE files (id INT UNIQUE, content BLOB);
+            INSERT INTO files VALUES (1, '\\x0AFF'), (2, '');
+        ").unwrap();
+        let result = execute(&db, "SELECT content FROM files").unwrap();
+        assert_eq!(rows_of(&result[0]), vec![vec![Cell::Blob(vec![0x0a, 0xff])], vec![Cell::Blob(vec![])]]);
+        assert!(matches!(execute(&db, "INSERT INTO files VALUES (3, 'xyz')"), Err(SqlError::ExecutionError(_))));
+    }
+
+    #[test]
+    fn should_store_point_values_written_like_in_postgres() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+
+        execute(&db, "
+            CREATE TABLE places (id INT UNIQUE, location POINT);
+            INSERT INTO places VALUES (1, '(1.5,-2)'), (2, '( 3 , 4 )');
+        ").unwrap();
+        let result = execute(&db, "SELECT location FROM places").unwrap();
+        assert_eq!(rows_of(&result[0]), vec![vec![Cell::Point(Point(1.5, -2.0))], vec![Cell::Point(Point(3.0, 4.0))]]);
+        assert!(matches!(execute(&db, "INSERT INTO places VALUES (3, '(1,NaN)')"), Err(SqlError::ExecutionError(_))));
+    }
+
+    #[test]
+    fn should_insert_rows_of_a_query() {
+        let base_path = tempfile::tempdir().unwrap();
+        let store = FileStore::new(base_path.path());
+        let db = Database::new_with_store("test_db", store);
+        db.drop_create().unwrap();
+
+        execute(&db, "
+            CREATE TABLE orders (id INT UNIQUE, total INT);
+            CREATE TABLE archive (id INT UNIQUE, total INT);
+            INSERT INTO orders VALUES (1, 50);
+            INSERT INTO orders VALUES (2, 150);
+            INSERT INTO orders VALUES (3, 250);
+        ").unwrap();
+
+        let result = execute(&db, "INSERT INTO archive SELECT * FROM orders WHERE total > 100").unwrap();
+        assert_eq!(result[0].tag(), "INSERT 0 2");
+        let result = execute(&db, "SELECT * FROM archive ORDER BY id").unwrap();
+        assert_eq!(rows_of(&result[0]), vec![vec![Cell::Int(2), Cell::Int(150)], vec![Cell::Int(3), Cell::Int(250)]]);
+
+        // a duplicate in the unique column rejects the whole statement
+        assert!(matches!(execute(&db, "INSERT INTO archive SELECT * FROM orders"), Err(SqlError::ExecutionError(_))));
+        let result = execute(&db, "SELECT * FROM archive").unwrap();
+        assert_eq!(result[0].tag(), "SELECT 2");
+
+        // columns not in the list get their defaults
+        let result = execute(&db, "INSERT INTO archive (id) SELECT id FROM orders WHERE id = 1").unwrap();
+        assert_eq!(result[0].tag(), "INSERT 0 1");
+        let result = execute(&db, "SELECT id FROM archive WHERE id = 1").unwrap();
+        assert_eq!(rows_of(&result[0]), vec![vec![Cell::Int(1)]]);
+
+        // the query is read completely before inserting, so a table can be copied into itself
+        execute(&db, "CREATE TABLE numbers (n INT)").unwrap();
+        execute(&db, "INSERT INTO numbers VALUES (1)").unwrap();
+        execute(&db, "INSERT INTO numbers SELECT * FROM numbers").unwrap();
+        let result = execute(&db, "INSERT INTO numbers SELECT * FROM numbers").unwrap();
+        assert_eq!(result[0].tag(), "INSERT 0 2");
+
+        assert!(matches!(execute(&db, "INSERT INTO archive SELECT id FROM orders"), Err(SqlError::ExecutionError(_))));
+        assert!(matches!(execute(&db, "INSERT INTO archive (id, total) SELECT id FROM orders"), Err(SqlError::ExecutionError(_))));
+    }
+
+    #[test]
+    fn should_create_table_from_query() {
+        let base_path = tempfile::tempdir().unwrap();
+        let store = FileStore::new(base_path.path());
+        let db = Database::new_with_store("test_db", store);
+        db.drop_create().unwrap();
+
+        execute(&db, "
+            CREATE TABLE orders (id INT UNIQUE, customer VARCHAR(20), total INT);
+            INSERT INTO orders VALUES (1, 'Alice', 50);
+            INSERT INTO orders VALUES (2, 'Bob', 150);
+            INSERT INTO orders VALUES (3, 'Carol', 250);
+        ").unwrap();
+
+        let result = execute(&db, "CREATE TABLE big_orders AS SELECT customer, total FROM orders WHERE total > 100").unwrap();
+        assert_eq!(result[0].tag(), "SELECT 2");
+
+        let table = db.read_table("big_orders").unwrap();
+        let types: Vec<(&str, ColumnType)> = table.schema().columns.iter().map(|c| (c.name.as_str(), c.col_type.clone())).collect();
+        assert_eq!(types, vec![("customer", ColumnType::Varchar(20)), ("total", ColumnType::Int)]);
+        let result = execute(&db, "SELECT * FROM big_orders ORDER BY total").unwrap();
+        assert_eq!(rows_of(&result[0]), vec![
+            vec![Cell::Varchar("Bob".to_owned()), Cell::Int(150)],
+            vec![Cell::Varchar("Carol".to_owned()), Cell::Int(250)],
+        ]);
+
+        // an empty result still creates the table
+        let result = execute(&db, "CREATE TABLE no_orders AS SELECT id FROM orders WHERE total > 1000").unwrap();
+        assert_eq!(result[0].tag(), "SELECT 0");
+        assert!(db.read_table("no_orders").is_ok());
+
+        assert!(matches!(execute(&db, "CREATE TABLE big_orders AS SELECT * FROM orders"), Err(SqlError::ExecutionError(_))));
+        let result = execute(&db, "SELECT * FROM big_orders").unwrap();
+        assert_eq!(result[0].tag(), "SELECT 2");
+        assert!(matches!(execute(&db, "CREATE TABLE broken AS SELECT * FROM missing"), Err(SqlError::ExecutionError(_))));
+        assert!(db.read_table("broken").is_err());
+    }
+
+    #[test]
+    fn should_insert_several_rows_and_report_the_failing_one() {
+        let base_path = tempfile::tempdir().unwrap();
+        let store = FileStore::new(base_path.path());
+        let db = Database::new_with_store("test_db", store);
+        db.drop_create().unwrap();
+
+        let result = execute(&db, "
+            CREATE TABLE persons (id INT UNIQUE, name VARCHAR(10));
+            INSERT INTO persons VALUES (1, 'Alice'), (2, 'Bob');
+            INSERT INTO persons (id) VALUES (3), (4), (5);
+        ").unwrap();
+        assert_eq!(result[1].tag(), "INSERT 0 2");
+        assert_eq!(result[2].tag(), "INSERT 0 3");
+
+        // values that can't be converted are found before anything is inserted
+        let Err(SqlError::ExecutionError(msg)) = execute(&db, "INSERT INTO persons VALUES (6, 'Dave'), ('x', 'Eve')") else {
+            panic!("Expected an execution error");
+        };
+        assert!(msg.starts_with("Row 2: "), "{}", msg);
+        assert_eq!(execute(&db, "SELECT * FROM persons").unwrap()[0].tag(), "SELECT 5");
+
+        // a duplicate is only found while inserting: the rows before it stay inserted
+        let Err(SqlError::ExecutionError(msg)) = execute(&db, "INSERT INTO persons VALUES (6, 'Dave'), (1, 'Eve'), (7, 'Frank')") else {
+            panic!("Expected an execution error");
+        };
+        assert!(msg.contains("row 2 not inserted"), "{}", msg);
+        let result = execute(&db, "SELECT id FROM persons WHERE id > 5").unwrap();
+        assert_eq!(rows_of(&result[0]), vec![vec![Cell::Int(6)]]);
+    }
+
+    #[test]
+    fn should_estimate_distinct_values_with_approx_count_distinct() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+
+        execute(&db, "CREATE TABLE orders (id INT, customer VARCHAR(10))").unwrap();
+        let values: Vec<String> = (0..40).map(|i| format!("({}, 'c{}')", i, i % 8)).collect();
+        execute(&db, &format!("INSERT INTO orders VALUES {}", values.join(", "))).unwrap();
+
+        let result = execute(&db, "SELECT APPROX_COUNT_DISTINCT(customer) FROM orders").unwrap();
+        let ExecResult::Rows { schema, .. } = &result[0] else {
+            panic!("Expected rows");
+        };
+        assert_eq!(schema.columns[0].name, "approx_count_distinct");
+        assert_eq!(rows_of(&result[0]), vec![vec![Cell::Int(8)]]);
+
+        // the filter is applied before, the limit after the aggregate
+        let result = execute(&db, "SELECT approx_count_distinct(customer) FROM orders WHERE id < 4 LIMIT 1").unwrap();
+        assert_eq!(rows_of(&result[0]), vec![vec![Cell::Int(4)]]);
+
+        assert!(execute(&db, "DELETE FROM orders RETURNING approx_count_distinct(id)").is_err());
+        assert!(execute(&db, "SELECT approx_count_distinct(unknown) FROM orders").is_err());
+    }
+
+    #[test]
+    fn should_return_affected_rows_with_returning() {
+        let base_path = tempfile::tempdir().unwrap();
+        let store = FileStore::new(base_path.path());
+        let db = Database::new_with_store("test_db", store);
+        db.drop_create().unwrap();
+
+        execute(&db, "CREATE TABLE persons (id INT UNIQUE, name VARCHAR(10), age BYTE)").unwrap();
+
+        // the defaults of the missing columns are returned
+        let result = execute(&db, "INSERT INTO persons (id, name) VALUES (1, 'Alice'), (2, 'Bob') RETURNING *").unwrap();
+        assert_eq!(result[0].tag(), "INSERT 0 2");
+        assert_eq!(rows_of(&result[0]), vec![
+            vec![Cell::Int(1), Cell::Varchar("Alice".to_owned()), Cell::Byte(0)],
+            vec![Cell::Int(2), Cell::Varchar("Bob".to_owned()), Cell::Byte(0)],
+        ]);
+
+        let result = execute(&db, "UPDATE persons SET age = 40 WHERE id = 2 RETURNING name, age").unwrap();
+        assert_eq!(result[0].tag(), "UPDATE 1");
+        let ExecResult::CommandRows { schema, .. } = &result[0] else {
+            panic!("Expected rows of the command");
+        };
+        assert_eq!(schema.columns.iter().map(|c| c.name.as_str()).collect::<Vec<&str>>(), vec!["name", "age"]);
+        assert_eq!(rows_of(&result[0]), vec![vec![Cell::Varchar("Bob".to_owned()), Cell::Byte(40)]]);
+
+        let result = execute(&db, "DELETE FROM persons WHERE age < 18 RETURNING id").unwrap();
+        assert_eq!(result[0].tag(), "DELETE 1");
+        assert_eq!(rows_of(&result[0]), vec![vec![Cell::Int(1)]]);
+
+        let result = execute(&db, "INSERT INTO persons SELECT * FROM persons WHERE id = 3 RETURNING id").unwrap();
+        assert_eq!(result[0].tag(), "INSERT 0 0");
+        assert_eq!(rows_of(&result[0]), Vec::<Vec<Cell>>::new());
+
+        // an unknown column is found before anything is changed
+        assert!(matches!(execute(&db, "DELETE FROM persons RETURNING missing"), Err(SqlError::ExecutionError(_))));
+        assert_eq!(execute(&db, "SELECT * FROM persons").unwrap()[0].tag(), "SELECT 1");
+        assert_eq!(execute(&db, "DELETE FROM persons").unwrap()[0].tag(), "DELETE 1");
+    }
+}
@@ -0,0 +1,162 @@
+use crate::{
+    database::{Database, functions::ScalarFunction},
+    sql::{FunctionArg, FunctionCall, FunctionComparison, Literal, SqlError, executor::{column_index, to_cell}, query::op_text, subquery::local_name},
+    store::Store,
+    table::{Column, ColumnType, TableSchema, table::{Cell, Row}},
+};
+
+// Calls of user-defined functions (see database/functions.rs) in the SELECT list and in the WHERE clause.
+// A call is bound to the schema of the query before the scan: the function is looked up, the columns of the
+// arguments are resolved and their types (and the types of the literals) are checked against the signature.
+// Literals are converted once, when the call is bound. A comparison with the result of a call is false for NULL.
+
+#[derive(Debug)]
+pub(super) struct BoundCall {
+    function: ScalarFunction,
+    args: Vec<BoundArg>,
+}
+
+#[derive(Debug)]
+enum BoundArg {
+    Column(usize),
+    Value(Cell),
+}
+
+impl BoundCall {
+    pub fn bind<S: Store>(db: &Database<S>, call: &FunctionCall, table: &str, schema: &TableSchema) -> Result<Self, SqlError> {
+        let function = db.function(&call.name)?;
+        let mut args = Vec::with_capacity(call.args.len());
+        let mut arg_types = Vec::with_capacity(call.args.len());
+        for (position, arg) in call.args.iter().enumerate() {
+            match arg {
+                FunctionArg::Column(name) => {
+                    let index = column_index(schema, local_name(table, name))?;
+                    arg_types.push(schema.columns[index].col_type.clone());
+                    args.push(BoundArg::Column(index));
+                },
+                FunctionArg::Literal(literal) => {
+                    // the literal gets the type of the parameter, if there is one
+                    let col_type = function.signature().args.get(position).cloned().unwrap_or(literal_type(literal));
+                    let param = Column::new(position as i32 + 1, &format!("argument {} of {}", position + 1, function.name()), col_type.clone());
+                    args.push(BoundArg::Value(to_cell(literal.clone(), &param)?));
+                    arg_types.push(col_type);
+                },
+            }
+        }
+        function.check(&arg_types)?;
+
+        Ok(Self { function, args })
+    }
+
+    pub fn return_type(&self) -> &ColumnType {
+        &self.function.signature().returns
+    }
+
+    pub fn column(&self, id: i32) -> Column {
+        Column::new(id, self.function.name(), self.return_type().clone())
+    }
+
+    pub fn call(&self, row: &Row) -> Result<Cell, SqlError> {
+        let args: Vec<Cell> = self.args.iter()
+            .map(|arg| match arg {
+                BoundArg::Column(index) => row.cells()[*index].clone(),
+                BoundArg::Value(cell) => cell.clone(),
+            })
+            .collect();
+        Ok(self.function.call(&args)?)
+    }
+}
+
+/// Binds the call and converts the literal to the type of the result
+pub(super) fn bind_comparison<S: Store>(db: &Database<S>, comparison: &FunctionComparison, table: &str, schema: &TableSchema) -> Result<(BoundCall, Cell), SqlError> {
+    let call = BoundCall::bind(db, &comparison.call, table, schema)?;
+    let result = Column::new(0, &format!("result of {}", call.function.name()), call.return_type().clone());
+    let value = to_cell(comparison.value.clone(), &result)?;
+    Ok((call, value))
+}
+
+fn literal_type(literal: &Literal) -> ColumnType {
+    match literal {
+        Literal::Int(_) => ColumnType::Int,
+        Literal::String(value) => ColumnType::Varchar(value.len() as u16),
+    }
+}
+
+pub(super) fn call_text(call: &FunctionCall) -> String {
+    let args: Vec<String> = call.args.iter()
+        .map(|arg| match arg {
+            FunctionArg::Column(name) => name.clone(),
+            FunctionArg::Literal(literal) => literal_text(literal),
+        })
+        .collect();
+    format!("{}({})", call.name, args.join(", "))
+}
+
+pub(super) fn function_comparison_text(comparison: &FunctionComparison) -> String {
+    format!("{} {} {}", call_text(&comparison.call), op_text(comparison.op), literal_text(&comparison.value))
+}
+
+pub(super) fn literal_text(literal: &Literal) -> String {
+    match literal {
+        Literal::Int(v) => v.to_string(),
+        Literal::String(v) => format!("'{}'", v.replace('\'', "''")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::{Database, functions::Signature}, sql::{SqlError, executor::{ExecResult, execute}}, store::file_store::FileStore, table::{ColumnType, table::Cell}};
+
+    fn setup(base_path: &std::path::Path) -> Database<FileStore> {
+        let db = Database::new_with_store("test_db", FileStore::new(base_path));
+        db.drop_create().unwrap();
+        db.register_fn("slugify", Signature::new(vec![ColumnType::Varchar(100)], ColumnType::Varchar(100)), |args| match &args[0] {
+            Cell::Varchar(value) => Ok(Cell::Varchar(value.to_lowercase().replace(' ', "-"))),
+            other => Err(format!("not a varchar: {:?}", other)),
+        }).unwrap();
+        execute(&db, "
+            CREATE TABLE persons (id INT UNIQUE, name VARCHAR(20));
+            INSERT INTO persons VALUES (1, 'Bob Smith'), (2, 'Alice Jones');
+        ").unwrap();
+        db
+    }
+
+    fn rows_of(result: &ExecResult) -> Vec<Vec<Cell>> {
+        match result {
+            ExecResult::Rows { rows, .. } => rows.iter().map(|r| r.cells().clone()).collect(),
+            other => panic!("Expected rows, got {:?}", other.tag()),
+        }
+    }
+
+    #[test]
+    fn should_call_functions_in_the_select_list_and_the_where_clause() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = setup(base_path.path());
+
+        let results = execute(&db, "SELECT id, SLUGIFY(name) FROM persons WHERE slugify(name) = 'alice-jones'").unwrap();
+        assert_eq!(rows_of(&results[0]), vec![vec![Cell::Int(2), Cell::Varchar("alice-jones".to_owned())]]);
+        match &results[0] {
+            ExecResult::Rows { schema, .. } => assert_eq!(schema.columns[1].name, "slugify"),
+            _ => unreachable!(),
+        }
+
+        let results = execute(&db, "SELECT slugify('Hello World') FROM persons WHERE id = 1").unwrap();
+        assert_eq!(rows_of(&results[0]), vec![vec![Cell::Varchar("hello-world".to_owned())]]);
+    }
+
+    #[test]
+    fn should_check_the_signature_before_the_scan() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = setup(base_path.path());
+
+        for sql in [
+            "EXPLAIN SELECT slugify(id) FROM persons",
+            "EXPLAIN SELECT slugify(name, name) FROM persons",
+            "EXPLAIN SELECT id FROM persons WHERE slugify(name) = 1",
+            "EXPLAIN SELECT unknown(name) FROM persons",
+        ] {
+            assert!(matches!(execute(&db, sql), Err(SqlError::ExecutionError(_))), "{}", sql);
+        }
+        assert!(matches!(execute(&db, "DELETE FROM persons WHERE slugify(name) = 'bob-smith'"), Err(SqlError::SyntaxError(_))));
+    }
+}
@@ -0,0 +1,262 @@
+pub mod parser;
+pub mod executor;
+pub mod query;
+pub mod subquery;
+pub mod cte;
+pub mod function;
+
+use thiserror::Error;
+
+use crate::{database::{CreateTableError, DatabaseError, functions::FunctionError, sort::SortKey, virtual_table::VirtualTableError, table_access::TableAccessError}, table::{ColumnType, table::RowValidationError}};
+
+// Supported subset (keywords are case insensitive):
+//   [WITH [RECURSIVE] name AS (SELECT ... [UNION [ALL] SELECT ...]), ...] SELECT * | item, ... | APPROX_COUNT_DISTINCT(col) FROM table [WHERE cond [AND cond]*] [ORDER BY col [ASC | DESC] [NULLS FIRST | LAST], ...] [LIMIT n]
+//   INSERT INTO table [(col, ...)] VALUES (literal, ...) [, (literal, ...)]* | [WITH ...] SELECT ... [RETURNING * | col, ...]
+//   UPDATE table SET col = literal [, ...] [WHERE ...] [RETURNING * | col, ...]
+//   DELETE FROM table [WHERE ...] [RETURNING * | col, ...]
+//   CREATE TABLE table (col INT | VARCHAR(n) | BYTE [UNIQUE], ...)
+//   CREATE TABLE table AS [WITH ...] SELECT ...   (columns get the names and types of the query result)
+//   DROP TABLE table
+//   EXPLAIN [ANALYZE] SELECT ...
+// cond: col (= | <> | != | < | <= | > | >=) literal
+//     | col op col                           (only in SELECT)
+//     | col op (SELECT col FROM ...)         (only in SELECT, scalar subquery: at most one row)
+//     | col [NOT] IN (SELECT col FROM ...)   (only in SELECT)
+//     | [NOT] EXISTS (SELECT ...)            (only in SELECT)
+//     | fn(arg, ...) op literal              (only in SELECT)
+// table: a table, a virtual table (see database/virtual_table.rs) or a CTE of the WITH clause of this or an outer query (see sql/cte.rs)
+//      | alias.table, a table of an attached database (see database/attach.rs)
+//      | name(literal, ...), a table function, e.g. generate_series(1, 10) (see database/table_functions.rs)
+// col: column or table.column. In a subquery, a column of the outer query makes it correlated (see sql/query.rs).
+//      The table is the longest prefix that names the table of the query or the outer query (alias.table.column).
+// literal: integer or 'string' ('' for a quote inside the string)
+// item: col | fn(arg, ...), fn is a function of Database::register_fn (see sql/function.rs), arg: col | literal
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Select(Select),
+    Insert(Insert),
+    Update(Update),
+    Delete(Delete),
+    CreateTable(CreateTable),
+    CreateTableAs(CreateTableAs),
+    DropTable(String),
+    Explain(Explain),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Projection {
+    All,
+    Columns(Vec<String>),
+    // one row with the estimated number of distinct values of the column (see database::cardinality)
+    ApproxCountDistinct(String),
+    // a list with at least one function call, a list of columns only is Columns
+    Items(Vec<SelectItem>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelectItem {
+    Column(String),
+    Call(FunctionCall),
+}
+
+/// Call of a function registered with Database::register_fn
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionCall {
+    pub name: String,
+    pub args: Vec<FunctionArg>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FunctionArg {
+    Column(String),
+    Literal(Literal),
+}
+
+/// fn(arg, ...) op literal
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionComparison {
+    pub call: FunctionCall,
+    pub op: CompareOp,
+    pub value: Literal,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Select {
+    pub ctes: Vec<CommonTableExpression>,
+    pub projection: Projection,
+    pub table: String,
+    // FROM name(literal, ...): a table function (see database/table_functions.rs)
+    pub table_args: Option<Vec<Literal>>,
+    pub filter: Vec<Condition>,
+    pub order_by: Vec<SortKey>,
+    pub limit: Option<usize>,
+    pub column_filter: Vec<ColumnComparison>,
+    pub subqueries: Vec<SubqueryFilter>,
+    pub function_filter: Vec<FunctionComparison>,
+}
+
+/// WITH name AS (SELECT ...)
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommonTableExpression {
+    pub name: String,
+    pub select: Select,
+    // WITH RECURSIVE name AS (select UNION [ALL] recursive)
+    pub recursive: Option<RecursiveTerm>,
+}
+
+/// The part after UNION of a recursive CTE, it reads the rows of the previous iteration from the CTE
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecursiveTerm {
+    // UNION ALL keeps duplicates
+    pub all: bool,
+    pub select: Select,
+}
+
+/// Subquery in the WHERE clause, combined with the other conditions by AND
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubqueryFilter {
+    pub predicate: SubqueryPredicate,
+    // NOT IN, NOT EXISTS
+    pub negated: bool,
+    pub subquery: Box<Select>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubqueryPredicate {
+    Exists,
+    In(String),
+    // col op (SELECT ...)
+    Compare(String, CompareOp),
+}
+
+/// Comparison of two columns (e.g. the correlation of a subquery with its outer query)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnComparison {
+    pub left: String,
+    pub op: CompareOp,
+    pub right: String,
+}
+
+/// ANALYZE executes the query and adds row counts, timings and page I/O to the plan
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explain {
+    pub analyze: bool,
+    pub select: Select,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Insert {
+    pub table: String,
+    pub columns: Option<Vec<String>>,
+    pub source: InsertSource,
+    pub returning: Option<Projection>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InsertSource {
+    Values(Vec<Vec<Literal>>),
+    // INSERT ... SELECT, the rows are inserted with the bulk loader
+    Select(Box<Select>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Update {
+    pub table: String,
+    pub assignments: Vec<(String, Literal)>,
+    pub filter: Vec<Condition>,
+    pub returning: Option<Projection>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delete {
+    pub table: String,
+    pub filter: Vec<Condition>,
+    pub returning: Option<Projection>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateTable {
+    pub name: String,
+    pub columns: Vec<ColumnDefinition>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateTableAs {
+    pub name: String,
+    pub select: Box<Select>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnDefinition {
+    pub name: String,
+    pub col_type: ColumnType,
+    pub unique: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    pub column: String,
+    pub op: CompareOp,
+    pub value: Literal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    NotEq,
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Int(i64),
+    String(String),
+}
+
+#[derive(Debug, Error)]
+pub enum SqlError {
+    #[error("Syntax error: {0}")]
+    SyntaxError(String),
+    #[error("Execution error: {0}")]
+    ExecutionError(String),
+}
+
+impl From<DatabaseError> for SqlError {
+    fn from(err: DatabaseError) -> Self {
+        SqlError::ExecutionError(err.to_string())
+    }
+}
+
+impl From<FunctionError> for SqlError {
+    fn from(err: FunctionError) -> Self {
+        SqlError::ExecutionError(err.to_string())
+    }
+}
+
+impl From<VirtualTableError> for SqlError {
+    fn from(err: VirtualTableError) -> Self {
+        SqlError::ExecutionError(err.to_string())
+    }
+}
+
+impl From<TableAccessError> for SqlError {
+    fn from(err: TableAccessError) -> Self {
+        SqlError::ExecutionError(err.to_string())
+    }
+}
+
+impl From<CreateTableError> for SqlError {
+    fn from(err: CreateTableError) -> Self {
+        SqlError::ExecutionError(err.to_string())
+    }
+}
+
+impl From<RowValidationError> for SqlError {
+    fn from(err: RowValidationError) -> Self {
+        SqlError::ExecutionError(err.to_string())
+    }
+}
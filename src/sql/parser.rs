@@ -0,0 +1,907 @@
+use crate::{database::sort::SortKey, sql::{ColumnComparison, ColumnDefinition, CommonTableExpression, CompareOp, Condition, CreateTable, CreateTableAs, Delete, Explain, FunctionArg, FunctionCall, FunctionComparison, Insert, InsertSource, Literal, Projection, RecursiveTerm, Select, SelectItem, SqlError, Statement, SubqueryFilter, SubqueryPredicate, Update}, table::{ColumnType, identifier::Identifier}};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    // Identifiers are kept as written (quoted ones with quotes),
+    // table and column lookups apply the identifier rules (see table::identifier).
+    Ident(String),
+    Number(i64),
+    Str(String),
+    Symbol(&'static str),
+}
+
+const SYMBOLS: [&str; 14] = ["<>", "!=", "<=", ">=", "(", ")", ",", ";", "*", "=", "<", ">", "-", "."];
+
+fn tokenize(sql: &str) -> Result<Vec<Token>, SqlError> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            let number: String = chars[start..i].iter().collect();
+            let number = number.parse::<i64>()
+                .map_err(|_| SqlError::SyntaxError(format!("Number too large: {}", number)))?;
+            tokens.push(Token::Number(number));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else if c == '\'' || c == '"' {
+            // strings and quoted identifiers: the quote char is escaped by doubling it
+            let mut value = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(SqlError::SyntaxError(format!("Unterminated {} quote", c))),
+                    Some(q) if *q == c && chars.get(i + 1) == Some(&c) => {
+                        value.push(c);
+                        i += 2;
+                    },
+                    Some(q) if *q == c => {
+                        i += 1;
+                        break;
+                    },
+                    Some(other) => {
+                        value.push(*other);
+                        i += 1;
+                    }
+                }
+            }
+
+            if c == '\'' {
+                tokens.push(Token::Str(value));
+            } else {
+                tokens.push(Token::Ident(format!("\"{}\"", value.replace('"', "\"\""))));
+            }
+        } else {
+            let rest: String = chars[i..std::cmp::min(i + 2, chars.len())].iter().collect();
+            let symbol = SYMBOLS.iter()
+                .find(|s| rest.starts_with(**s))
+                .ok_or_else(|| SqlError::SyntaxError(format!("Unexpected character '{}'", c)))?;
+            tokens.push(Token::Symbol(symbol));
+            i += symbol.len();
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+struct WhereClause {
+    conditions: Vec<Condition>,
+    column_filter: Vec<ColumnComparison>,
+    subqueries: Vec<SubqueryFilter>,
+    function_filter: Vec<FunctionComparison>,
+}
+
+/// Parses one or more statements separated by ';'
+pub fn parse(sql: &str) -> Result<Vec<Statement>, SqlError> {
+    let mut parser = Parser {
+        tokens: tokenize(sql)?,
+        pos: 0,
+    };
+
+    let mut statements = Vec::new();
+    loop {
+        while parser.accept_symbol(";") {}
+        if parser.peek().is_none() {
+            break;
+        }
+        statements.push(parser.statement()?);
+        if parser.peek().is_some() && !parser.accept_symbol(";") {
+            return Err(parser.unexpected("';' or end of statement"));
+        }
+    }
+
+    Ok(statements)
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn unexpected(&self, expected: &str) -> SqlError {
+        match self.peek() {
+            Some(token) => SqlError::SyntaxError(format!("Expected {}, found {:?}", expected, token)),
+            None => SqlError::SyntaxError(format!("Expected {}, found end of input", expected)),
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(ident)) if ident.eq_ignore_ascii_case(keyword))
+    }
+
+    fn accept_keyword(&mut self, keyword: &str) -> bool {
+        if self.is_keyword(keyword) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), SqlError> {
+        if self.accept_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.unexpected(keyword))
+        }
+    }
+
+    fn accept_symbol(&mut self, symbol: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), SqlError> {
+        if self.accept_symbol(symbol) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("'{}'", symbol)))
+        }
+    }
+
+    fn identifier(&mut self) -> Result<String, SqlError> {
+        match self.peek() {
+            Some(Token::Ident(ident)) => {
+                let ident = ident.clone();
+                self.pos += 1;
+                Ok(ident)
+            },
+            _ => Err(self.unexpected("identifier")),
+        }
+    }
+
+    // column, table.column or alias.table.column (kept as written, see split_qualified)
+    fn column_name(&mut self) -> Result<String, SqlError> {
+        let mut name = self.identifier()?;
+        while self.accept_symbol(".") {
+            name = format!("{}.{}", name, self.identifier()?);
+        }
+        Ok(name)
+    }
+
+    fn identifier_list(&mut self) -> Result<Vec<String>, SqlError> {
+        let mut idents = vec![self.identifier()?];
+        while self.accept_symbol(",") {
+            idents.push(self.identifier()?);
+        }
+        Ok(idents)
+    }
+
+    fn literal(&mut self) -> Result<Literal, SqlError> {
+        let negative = self.accept_symbol("-");
+        match self.next() {
+            Some(Token::Number(n)) => Ok(Literal::Int(if negative { -n } else { n })),
+            Some(Token::Str(s)) if !negative => Ok(Literal::String(s)),
+            _ => {
+                self.pos -= 1;
+                Err(self.unexpected("literal"))
+            }
+        }
+    }
+
+    fn statement(&mut self) -> Result<Statement, SqlError> {
+        if self.is_keyword("SELECT") || self.is_keyword("WITH") {
+            self.query().map(Statement::Select)
+        } else if self.accept_keyword("INSERT") {
+            self.insert()
+        } else if self.accept_keyword("UPDATE") {
+            self.update()
+        } else if self.accept_keyword("DELETE") {
+            self.delete()
+        } else if self.accept_keyword("CREATE") {
+            self.create_table()
+        } else if self.accept_keyword("DROP") {
+            self.expect_keyword("TABLE")?;
+            Ok(Statement::DropTable(self.identifier()?))
+        } else if self.accept_keyword("EXPLAIN") {
+            self.explain()
+        } else {
+            Err(self.unexpected("SELECT, WITH, INSERT, UPDATE, DELETE, CREATE, DROP or EXPLAIN"))
+        }
+    }
+
+    // only SELECT, ANALYZE must not change data
+    fn explain(&mut self) -> Result<Statement, SqlError> {
+        let analyze = self.accept_keyword("ANALYZE");
+        Ok(Statement::Explain(Explain { analyze, select: self.query()? }))
+    }
+
+    // [WITH [RECURSIVE] name AS (SELECT ... [UNION [ALL] SELECT ...]), ...] SELECT ...
+    fn query(&mut self) -> Result<Select, SqlError> {
+        let mut ctes = Vec::new();
+        if self.accept_keyword("WITH") {
+            let recursive = self.accept_keyword("RECURSIVE");
+            loop {
+                let name = self.identifier()?;
+                self.expect_keyword("AS")?;
+                self.expect_symbol("(")?;
+                let select = self.query()?;
+                let recursive = match self.accept_keyword("UNION") {
+                    true if !recursive => return Err(SqlError::SyntaxError("UNION is only supported in WITH RECURSIVE".to_owned())),
+                    true => Some(RecursiveTerm { all: self.accept_keyword("ALL"), select: self.query()? }),
+                    false => None,
+                };
+                self.expect_symbol(")")?;
+                ctes.push(CommonTableExpression { name, select, recursive });
+                if !self.accept_symbol(",") {
+                    break;
+                }
+            }
+        }
+        self.expect_keyword("SELECT")?;
+        let mut select = self.select()?;
+        select.ctes = ctes;
+        Ok(select)
+    }
+
+    fn select(&mut self) -> Result<Select, SqlError> {
+        let projection = self.projection()?;
+
+        self.expect_keyword("FROM")?;
+        // alias.table of an attached database
+        let table = self.column_name()?;
+        let table_args = match self.accept_symbol("(") {
+            true => Some(self.table_args()?),
+            false => None,
+        };
+        let WhereClause { conditions: filter, column_filter, subqueries, function_filter } = self.where_clause_with_subqueries()?;
+        let order_by = self.order_by()?;
+        let limit = match self.accept_keyword("LIMIT") {
+            true => match self.next() {
+                Some(Token::Number(n)) if n >= 0 => Some(n as usize),
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.unexpected("number of rows"));
+                },
+            },
+            false => None,
+        };
+
+        Ok(Select { ctes: vec![], projection, table, table_args, filter, order_by, limit, column_filter, subqueries, function_filter })
+    }
+
+    // the literals of a table function after '('
+    fn table_args(&mut self) -> Result<Vec<Literal>, SqlError> {
+        let mut args = Vec::new();
+        if self.accept_symbol(")") {
+            return Ok(args);
+        }
+        loop {
+            args.push(self.literal()?);
+            if !self.accept_symbol(",") {
+                break;
+            }
+        }
+        self.expect_symbol(")")?;
+        Ok(args)
+    }
+
+    fn order_by(&mut self) -> Result<Vec<SortKey>, SqlError> {
+        let mut keys = Vec::new();
+        if !self.accept_keyword("ORDER") {
+            return Ok(keys);
+        }
+        self.expect_keyword("BY")?;
+
+        loop {
+            let column = self.identifier()?;
+            let mut key = match self.accept_keyword("DESC") {
+                true => SortKey::desc(&column),
+                false => {
+                    self.accept_keyword("ASC");
+                    SortKey::asc(&column)
+                },
+            };
+            if self.accept_keyword("NULLS") {
+                key = if self.accept_keyword("FIRST") {
+                    key.nulls_first()
+                } else if self.accept_keyword("LAST") {
+                    key.nulls_last()
+                } else {
+                    return Err(self.unexpected("FIRST or LAST"));
+                };
+            }
+            keys.push(key);
+
+            if !self.accept_symbol(",") {
+                break;
+            }
+        }
+
+        Ok(keys)
+    }
+
+    fn insert(&mut self) -> Result<Statement, SqlError> {
+        self.expect_keyword("INTO")?;
+        let table = self.identifier()?;
+
+        let columns = if self.accept_symbol("(") {
+            let columns = self.identifier_list()?;
+            self.expect_symbol(")")?;
+            Some(columns)
+        } else {
+            None
+        };
+
+        if self.is_keyword("SELECT") || self.is_keyword("WITH") {
+            let source = InsertSource::Select(Box::new(self.query()?));
+            let returning = self.returning()?;
+            return Ok(Statement::Insert(Insert { table, columns, source, returning }));
+        }
+
+        self.expect_keyword("VALUES")?;
+        let mut values = Vec::new();
+        loop {
+            self.expect_symbol("(")?;
+            let mut row = vec![self.literal()?];
+            while self.accept_symbol(",") {
+                row.push(self.literal()?);
+            }
+            self.expect_symbol(")")?;
+            values.push(row);
+            if !self.accept_symbol(",") {
+                break;
+            }
+        }
+
+        let returning = self.returning()?;
+        Ok(Statement::Insert(Insert { table, columns, source: InsertSource::Values(values), returning }))
+    }
+
+    fn projection(&mut self) -> Result<Projection, SqlError> {
+        if self.accept_symbol("*") {
+            Ok(Projection::All)
+        } else if self.is_keyword("APPROX_COUNT_DISTINCT") && matches!(self.tokens.get(self.pos + 1), Some(Token::Symbol("("))) {
+            self.pos += 2;
+            let column = self.identifier()?;
+            self.expect_symbol(")")?;
+            Ok(Projection::ApproxCountDistinct(column))
+        } else {
+            let mut items = vec![self.select_item()?];
+            while self.accept_symbol(",") {
+                items.push(self.select_item()?);
+            }
+            if items.iter().any(|item| matches!(item, SelectItem::Call(_))) {
+                return Ok(Projection::Items(items));
+            }
+            Ok(Projection::Columns(items.into_iter()
+                .filter_map(|item| match item {
+                    SelectItem::Column(name) => Some(name),
+                    SelectItem::Call(_) => None,
+                })
+                .collect()))
+        }
+    }
+
+    fn select_item(&mut self) -> Result<SelectItem, SqlError> {
+        match self.is_function_call() {
+            true => Ok(SelectItem::Call(self.function_call()?)),
+            false => Ok(SelectItem::Column(self.identifier()?)),
+        }
+    }
+
+    fn is_function_call(&self) -> bool {
+        matches!((self.peek(), self.tokens.get(self.pos + 1)), (Some(Token::Ident(_)), Some(Token::Symbol("("))))
+    }
+
+    // name(arg, ...), arg: col | literal
+    fn function_call(&mut self) -> Result<FunctionCall, SqlError> {
+        let name = self.identifier()?;
+        self.expect_symbol("(")?;
+        let mut args = Vec::new();
+        if !self.accept_symbol(")") {
+            loop {
+                args.push(match self.peek() {
+                    Some(Token::Ident(_)) => FunctionArg::Column(self.column_name()?),
+                    _ => FunctionArg::Literal(self.literal()?),
+                });
+                if !self.accept_symbol(",") {
+                    break;
+                }
+            }
+            self.expect_symbol(")")?;
+        }
+        Ok(FunctionCall { name, args })
+    }
+
+    fn returning(&mut self) -> Result<Option<Projection>, SqlError> {
+        match self.accept_keyword("RETURNING") {
+            true => Ok(Some(self.projection()?)),
+            false => Ok(None),
+        }
+    }
+
+    fn update(&mut self) -> Result<Statement, SqlError> {
+        let table = self.identifier()?;
+        self.expect_keyword("SET")?;
+
+        let mut assignments = Vec::new();
+        loop {
+            let column = self.identifier()?;
+            self.expect_symbol("=")?;
+            assignments.push((column, self.literal()?));
+            if !self.accept_symbol(",") {
+                break;
+            }
+        }
+
+        let filter = self.where_clause()?;
+        let returning = self.returning()?;
+        Ok(Statement::Update(Update { table, assignments, filter, returning }))
+    }
+
+    fn delete(&mut self) -> Result<Statement, SqlError> {
+        self.expect_keyword("FROM")?;
+        let table = self.identifier()?;
+        let filter = self.where_clause()?;
+        let returning = self.returning()?;
+        Ok(Statement::Delete(Delete { table, filter, returning }))
+    }
+
+    fn create_table(&mut self) -> Result<Statement, SqlError> {
+        self.expect_keyword("TABLE")?;
+        let name = self.identifier()?;
+        if self.accept_keyword("AS") {
+            let select = Box::new(self.query()?);
+            return Ok(Statement::CreateTableAs(CreateTableAs { name, select }));
+        }
+        self.expect_symbol("(")?;
+
+        let mut columns = Vec::new();
+        loop {
+            let col_name = self.identifier()?;
+            let col_type = self.column_type()?;
+            let unique = self.accept_keyword("UNIQUE");
+            columns.push(ColumnDefinition { name: col_name, col_type, unique });
+            if !self.accept_symbol(",") {
+                break;
+            }
+        }
+        self.expect_symbol(")")?;
+
+        Ok(Statement::CreateTable(CreateTable { name, columns }))
+    }
+
+    fn column_type(&mut self) -> Result<ColumnType, SqlError> {
+        if self.accept_keyword("INT") || self.accept_keyword("INTEGER") {
+            Ok(ColumnType::Int)
+        } else if self.accept_keyword("BYTE") {
+            Ok(ColumnType::Byte)
+        } else if self.accept_keyword("BLOB") {
+            Ok(ColumnType::Blob)
+        } else if self.accept_keyword("POINT") {
+            Ok(ColumnType::Point)
+        } else if self.accept_keyword("VARCHAR") {
+            self.expect_symbol("(")?;
+            let len = match self.next() {
+                Some(Token::Number(n)) if n > 0 && n <= u16::MAX as i64 => n as u16,
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.unexpected("varchar length"));
+                }
+            };
+            self.expect_symbol(")")?;
+            Ok(ColumnType::Varchar(len))
+        } else {
+            Err(self.unexpected("column type (INT, VARCHAR(n), BYTE)"))
+        }
+    }
+
+    // UPDATE and DELETE only support comparisons with literals
+    fn where_clause(&mut self) -> Result<Vec<Condition>, SqlError> {
+        let start = self.pos;
+        let clause = self.where_clause_with_subqueries()?;
+        if !clause.column_filter.is_empty() || !clause.subqueries.is_empty() || !clause.function_filter.is_empty() {
+            self.pos = start;
+            return Err(SqlError::SyntaxError("Subqueries, function calls and comparisons of columns are only supported in SELECT".to_owned()));
+        }
+        Ok(clause.conditions)
+    }
+
+    fn where_clause_with_subqueries(&mut self) -> Result<WhereClause, SqlError> {
+        let mut clause = WhereClause { conditions: Vec::new(), column_filter: Vec::new(), subqueries: Vec::new(), function_filter: Vec::new() };
+        if !self.accept_keyword("WHERE") {
+            return Ok(clause);
+        }
+
+        loop {
+            let negated = self.accept_keyword("NOT");
+            if negated || self.is_keyword("EXISTS") {
+                self.expect_keyword("EXISTS")?;
+                clause.subqueries.push(SubqueryFilter { predicate: SubqueryPredicate::Exists, negated, subquery: Box::new(self.subquery()?) });
+            } else if self.is_function_call() {
+                let call = self.function_call()?;
+                let op = self.compare_op()?;
+                clause.function_filter.push(FunctionComparison { call, op, value: self.literal()? });
+            } else {
+                let column = self.column_name()?;
+                let negated = self.accept_keyword("NOT");
+                if negated || self.accept_keyword("IN") {
+                    if negated {
+                        self.expect_keyword("IN")?;
+                    }
+                    clause.subqueries.push(SubqueryFilter { predicate: SubqueryPredicate::In(column), negated, subquery: Box::new(self.subquery()?) });
+                } else {
+                    let op = self.compare_op()?;
+                    match self.peek() {
+                        Some(Token::Symbol("(")) => clause.subqueries.push(SubqueryFilter {
+                            predicate: SubqueryPredicate::Compare(column, op),
+                            negated: false,
+                            subquery: Box::new(self.subquery()?),
+                        }),
+                        Some(Token::Ident(_)) => clause.column_filter.push(ColumnComparison { left: column, op, right: self.column_name()? }),
+                        _ => clause.conditions.push(Condition { column, op, value: self.literal()? }),
+                    }
+                }
+            }
+
+            if !self.accept_keyword("AND") {
+                break;
+            }
+        }
+
+        Ok(clause)
+    }
+
+    fn subquery(&mut self) -> Result<Select, SqlError> {
+        self.expect_symbol("(")?;
+        let select = self.query()?;
+        self.expect_symbol(")")?;
+        Ok(select)
+    }
+
+    fn compare_op(&mut self) -> Result<CompareOp, SqlError> {
+        match self.next() {
+            Some(Token::Symbol("=")) => Ok(CompareOp::Eq),
+            Some(Token::Symbol("<>")) | Some(Token::Symbol("!=")) => Ok(CompareOp::NotEq),
+            Some(Token::Symbol("<")) => Ok(CompareOp::Less),
+            Some(Token::Symbol("<=")) => Ok(CompareOp::LessEq),
+            Some(Token::Symbol(">")) => Ok(CompareOp::Greater),
+            Some(Token::Symbol(">=")) => Ok(CompareOp::GreaterEq),
+            _ => {
+                self.pos -= 1;
+                Err(self.unexpected("comparison operator"))
+            }
+        }
+    }
+}
+
+/// Splits `table.column` into its parts (a quoted identifier may contain a '.')
+pub fn split_qualified(name: &str) -> (Option<&str>, &str) {
+    match qualifier_ends(name).first() {
+        Some(i) => (Some(&name[..*i]), &name[i + 1..]),
+        None => (None, name),
+    }
+}
+
+/// Splits a qualified column name at the longest prefix that is one of `tables` (each part compared like
+/// Identifier::normalize), e.g. `crm.persons.id` into `crm.persons` and `id` for a table of an attached database.
+/// Without such a prefix, it is split like split_qualified.
+pub fn split_qualified_by<'n>(name: &'n str, tables: &[&str]) -> (Option<&'n str>, &'n str) {
+    qualifier_ends(name).into_iter().rev()
+        .find(|i| tables.iter().any(|table| normalized_parts(table) == normalized_parts(&name[..*i])))
+        .map(|i| (Some(&name[..i]), &name[i + 1..]))
+        .unwrap_or_else(|| split_qualified(name))
+}
+
+/// The parts of a (qualified) name with the identifier rules applied
+pub(super) fn normalized_parts(name: &str) -> Vec<String> {
+    let mut start = 0;
+    let mut parts = Vec::new();
+    for end in qualifier_ends(name).into_iter().chain([name.len()]) {
+        parts.push(Identifier::normalize(&name[start..end]));
+        start = end + 1;
+    }
+    parts
+}
+
+// positions of the dots between the parts of a name (not in quotes)
+fn qualifier_ends(name: &str) -> Vec<usize> {
+    let mut quoted = false;
+    let mut ends = Vec::new();
+    for (i, c) in name.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '.' if !quoted => ends.push(i),
+            _ => {},
+        }
+    }
+    ends
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::sort::SortKey, sql::{ColumnComparison, ColumnDefinition, CompareOp, Condition, CreateTable, Explain, Insert, InsertSource, Literal, Projection, Select, SqlError, Statement, SubqueryPredicate, parser::{parse, split_qualified, split_qualified_by}}, table::ColumnType};
+
+    #[test]
+    fn should_parse_select_with_where() {
+        let statements = parse("select id, \"Name\" FROM persons WHERE id >= 10 and name = 'O''Neil';").unwrap();
+
+        assert_eq!(statements, vec![Statement::Select(Select {
+            ctes: vec![],
+            projection: Projection::Columns(vec!["id".to_owned(), "\"Name\"".to_owned()]),
+            table: "persons".to_owned(),
+            table_args: None,
+            filter: vec![
+                Condition { column: "id".to_owned(), op: CompareOp::GreaterEq, value: Literal::Int(10) },
+                Condition { column: "name".to_owned(), op: CompareOp::Eq, value: Literal::String("O'Neil".to_owned()) },
+            ],
+            order_by: vec![],
+            limit: None,
+            column_filter: vec![],
+            subqueries: vec![],
+            function_filter: vec![],
+        })]);
+    }
+
+    #[test]
+    fn should_parse_order_by() {
+        let statements = parse("SELECT * FROM t WHERE id > 1 ORDER BY a, b DESC, c ASC NULLS FIRST, d DESC NULLS LAST").unwrap();
+        let Statement::Select(select) = &statements[0] else {
+            panic!("Expected a select");
+        };
+        assert_eq!(select.filter.len(), 1);
+        assert_eq!(select.order_by, vec![
+            SortKey::asc("a"),
+            SortKey::desc("b"),
+            SortKey::asc("c").nulls_first(),
+            SortKey::desc("d").nulls_last(),
+        ]);
+
+        assert!(matches!(parse("SELECT * FROM t ORDER a"), Err(SqlError::SyntaxError(_))));
+        assert!(matches!(parse("SELECT * FROM t ORDER BY a NULLS"), Err(SqlError::SyntaxError(_))));
+
+        let Statement::Select(select) = parse("SELECT * FROM t ORDER BY a DESC LIMIT 10").unwrap().remove(0) else {
+            panic!("Expected a select");
+        };
+        assert_eq!((select.order_by.len(), select.limit), (1, Some(10)));
+        assert!(matches!(parse("SELECT * FROM t LIMIT -1"), Err(SqlError::SyntaxError(_))));
+        assert!(matches!(parse("SELECT * FROM t LIMIT"), Err(SqlError::SyntaxError(_))));
+    }
+
+    #[test]
+    fn should_parse_subqueries() {
+        let Statement::Select(select) = parse("SELECT * FROM persons WHERE age > 18 AND id NOT IN (SELECT person_id FROM orders WHERE total > 100) AND EXISTS (SELECT * FROM config) ORDER BY id").unwrap().remove(0) else {
+            panic!("Expected a select");
+        };
+        assert_eq!(select.filter.len(), 1);
+        assert_eq!(select.order_by, vec![SortKey::asc("id")]);
+        assert_eq!(select.subqueries.len(), 2);
+        let not_in = &select.subqueries[0];
+        assert_eq!((&not_in.predicate, not_in.negated), (&SubqueryPredicate::In("id".to_owned()), true));
+        assert_eq!(not_in.subquery.table, "orders");
+        assert_eq!(not_in.subquery.filter.len(), 1);
+        let exists = &select.subqueries[1];
+        assert_eq!((&exists.predicate, exists.negated), (&SubqueryPredicate::Exists, false));
+
+        let Statement::Select(select) = parse("SELECT id FROM a WHERE NOT EXISTS (SELECT id FROM b WHERE x IN (SELECT y FROM c))").unwrap().remove(0) else {
+            panic!("Expected a select");
+        };
+        assert!(select.subqueries[0].negated);
+        assert_eq!(select.subqueries[0].subquery.subqueries[0].predicate, SubqueryPredicate::In("x".to_owned()));
+
+        assert!(matches!(parse("SELECT * FROM a WHERE id IN (1, 2)"), Err(SqlError::SyntaxError(_))));
+        assert!(matches!(parse("SELECT * FROM a WHERE NOT id = 1"), Err(SqlError::SyntaxError(_))));
+        assert!(matches!(parse("DELETE FROM a WHERE id IN (SELECT id FROM b)"), Err(SqlError::SyntaxError(_))));
+    }
+
+    #[test]
+    fn should_parse_correlated_and_scalar_subqueries() {
+        let Statement::Select(select) = parse("SELECT * FROM orders WHERE total > (SELECT \"limit\" FROM accounts WHERE accounts.id = orders.account_id) AND a <= b").unwrap().remove(0) else {
+            panic!("Expected a select");
+        };
+        assert_eq!(select.column_filter, vec![ColumnComparison { left: "a".to_owned(), op: CompareOp::LessEq, right: "b".to_owned() }]);
+        let scalar = &select.subqueries[0];
+        assert_eq!(scalar.predicate, SubqueryPredicate::Compare("total".to_owned(), CompareOp::Greater));
+        assert_eq!(scalar.subquery.column_filter, vec![
+            ColumnComparison { left: "accounts.id".to_owned(), op: CompareOp::Eq, right: "orders.account_id".to_owned() },
+        ]);
+
+        assert_eq!(split_qualified("persons.id"), (Some("persons"), "id"));
+        assert_eq!(split_qualified("\"a.b\".\"c.d\""), (Some("\"a.b\""), "\"c.d\""));
+        assert_eq!(split_qualified_by("crm.Persons.id", &["crm.persons", "crm"]), (Some("crm.Persons"), "id"));
+        assert_eq!(split_qualified_by("crm.\"a.b\".id", &["crm.\"a.b\""]), (Some("crm.\"a.b\""), "id"));
+        assert_eq!(split_qualified_by("persons.id", &["orders"]), (Some("persons"), "id"));
+        assert_eq!(split_qualified("\"a.b\""), (None, "\"a.b\""));
+        assert!(matches!(parse("UPDATE t SET a = 1 WHERE a = b"), Err(SqlError::SyntaxError(_))));
+        assert!(matches!(parse("SELECT * FROM t WHERE t. = 1"), Err(SqlError::SyntaxError(_))));
+    }
+
+    #[test]
+    fn should_parse_with_clause() {
+        let Statement::Select(select) = parse("WITH adults AS (SELECT id FROM persons WHERE age >= 18), \
+            buyers AS (SELECT * FROM adults WHERE id IN (SELECT person_id FROM orders)) \
+            SELECT * FROM buyers WHERE EXISTS (WITH o AS (SELECT id FROM orders) SELECT id FROM o)").unwrap().remove(0) else {
+            panic!("Expected a select");
+        };
+        let names: Vec<&str> = select.ctes.iter().map(|cte| cte.name.as_str()).collect();
+        assert_eq!(names, vec!["adults", "buyers"]);
+        assert_eq!(select.ctes[0].select.filter, vec![Condition { column: "age".to_owned(), op: CompareOp::GreaterEq, value: Literal::Int(18) }]);
+        assert_eq!(select.ctes[1].select.table, "adults");
+        assert_eq!(select.table, "buyers");
+        assert_eq!(select.subqueries[0].subquery.ctes[0].name, "o");
+
+        assert!(matches!(&parse("EXPLAIN WITH a AS (SELECT * FROM t) SELECT * FROM a").unwrap()[0], Statement::Explain(_)));
+        assert!(matches!(parse("WITH a (SELECT * FROM t) SELECT * FROM a"), Err(SqlError::SyntaxError(_))));
+        assert!(matches!(parse("WITH a AS (SELECT * FROM t)"), Err(SqlError::SyntaxError(_))));
+    }
+
+    #[test]
+    fn should_parse_recursive_with_clause() {
+        let Statement::Select(select) = parse("WITH RECURSIVE reports AS (SELECT id FROM employees WHERE id = 1 \
+            UNION ALL SELECT id FROM employees WHERE manager_id IN (SELECT id FROM reports)) SELECT * FROM reports").unwrap().remove(0) else {
+            panic!("Expected a select");
+        };
+        let recursive = select.ctes[0].recursive.as_ref().unwrap();
+        assert!(recursive.all);
+        assert_eq!(recursive.select.subqueries[0].subquery.table, "reports");
+        assert_eq!(select.ctes[0].select.filter.len(), 1);
+
+        let Statement::Select(select) = parse("WITH RECURSIVE a AS (SELECT * FROM t UNION SELECT * FROM a) SELECT * FROM a").unwrap().remove(0) else {
+            panic!("Expected a select");
+        };
+        assert!(!select.ctes[0].recursive.as_ref().unwrap().all);
+        assert!(matches!(parse("WITH a AS (SELECT * FROM t UNION SELECT * FROM a) SELECT * FROM a"), Err(SqlError::SyntaxError(_))));
+    }
+
+    #[test]
+    fn should_parse_multiple_statements() {
+        let statements = parse("CREATE TABLE t (id INT UNIQUE, name VARCHAR(20)); INSERT INTO t VALUES (-1, 'x')").unwrap();
+
+        assert_eq!(statements, vec![
+            Statement::CreateTable(CreateTable {
+                name: "t".to_owned(),
+                columns: vec![
+                    ColumnDefinition { name: "id".to_owned(), col_type: ColumnType::Int, unique: true },
+                    ColumnDefinition { name: "name".to_owned(), col_type: ColumnType::Varchar(20), unique: false },
+                ],
+            }),
+            Statement::Insert(Insert {
+                table: "t".to_owned(),
+                columns: None,
+                source: InsertSource::Values(vec![vec![Literal::Int(-1), Literal::String("x".to_owned())]]),
+                returning: None,
+            }),
+        ]);
+    }
+
+    #[test]
+    fn should_parse_insert_with_several_rows() {
+        let Statement::Insert(insert) = parse("INSERT INTO t (id, name) VALUES (1, 'a'), (2, 'b'), (3)").unwrap().remove(0) else {
+            panic!("Expected an insert");
+        };
+        assert_eq!(insert.source, InsertSource::Values(vec![
+            vec![Literal::Int(1), Literal::String("a".to_owned())],
+            vec![Literal::Int(2), Literal::String("b".to_owned())],
+            vec![Literal::Int(3)],
+        ]));
+        assert!(matches!(parse("INSERT INTO t VALUES (1), "), Err(SqlError::SyntaxError(_))));
+        assert!(matches!(parse("INSERT INTO t VALUES (1) (2)"), Err(SqlError::SyntaxError(_))));
+    }
+
+    #[test]
+    fn should_parse_returning() {
+        let statements = parse("INSERT INTO t (id) VALUES (1) RETURNING *; \
+            UPDATE t SET name = 'x' WHERE id = 1 RETURNING id, name; \
+            DELETE FROM t RETURNING id; \
+            INSERT INTO t SELECT * FROM s WHERE id > 1 RETURNING id").unwrap();
+        assert!(matches!(&statements[0], Statement::Insert(insert) if insert.returning == Some(Projection::All)));
+        assert!(matches!(&statements[1], Statement::Update(update)
+            if update.returning == Some(Projection::Columns(vec!["id".to_owned(), "name".to_owned()])) && update.filter.len() == 1));
+        assert!(matches!(&statements[2], Statement::Delete(delete) if delete.returning == Some(Projection::Columns(vec!["id".to_owned()]))));
+        assert!(matches!(&statements[3], Statement::Insert(insert) if insert.returning.is_some()));
+        assert!(matches!(&statements[3], Statement::Insert(Insert { source: InsertSource::Select(select), .. }) if select.filter.len() == 1));
+
+        assert!(matches!(parse("DELETE FROM t RETURNING"), Err(SqlError::SyntaxError(_))));
+    }
+
+    #[test]
+    fn should_parse_approx_count_distinct() {
+        let Statement::Select(select) = parse("SELECT approx_count_distinct(name) FROM t WHERE id > 1").unwrap().remove(0) else {
+            panic!("Expected a select");
+        };
+        assert_eq!(select.projection, Projection::ApproxCountDistinct("name".to_owned()));
+
+        // without parentheses it is a column
+        let Statement::Select(select) = parse("SELECT approx_count_distinct FROM t").unwrap().remove(0) else {
+            panic!("Expected a select");
+        };
+        assert_eq!(select.projection, Projection::Columns(vec!["approx_count_distinct".to_owned()]));
+        assert!(matches!(parse("SELECT APPROX_COUNT_DISTINCT(name FROM t"), Err(SqlError::SyntaxError(_))));
+    }
+
+    #[test]
+    fn should_parse_insert_select() {
+        let Statement::Insert(insert) = parse("INSERT INTO archive (id) SELECT id FROM orders WHERE total > 100").unwrap().remove(0) else {
+            panic!("Expected an insert");
+        };
+        assert_eq!(insert.columns, Some(vec!["id".to_owned()]));
+        let InsertSource::Select(select) = insert.source else {
+            panic!("Expected a select");
+        };
+        assert_eq!(select.table, "orders");
+
+        let Statement::Insert(insert) = parse("INSERT INTO archive WITH o AS (SELECT * FROM orders) SELECT * FROM o").unwrap().remove(0) else {
+            panic!("Expected an insert");
+        };
+        assert!(matches!(insert.source, InsertSource::Select(select) if select.ctes.len() == 1));
+        assert!(matches!(parse("INSERT INTO archive (SELECT * FROM orders)"), Err(SqlError::SyntaxError(_))));
+    }
+
+    #[test]
+    fn should_parse_create_table_as() {
+        let Statement::CreateTableAs(create) = parse("CREATE TABLE big_orders AS SELECT id, total FROM orders WHERE total > 100").unwrap().remove(0) else {
+            panic!("Expected create table as");
+        };
+        assert_eq!(create.name, "big_orders");
+        assert_eq!(create.select.table, "orders");
+        assert_eq!(create.select.projection, Projection::Columns(vec!["id".to_owned(), "total".to_owned()]));
+
+        assert!(matches!(&parse("CREATE TABLE t AS WITH o AS (SELECT * FROM orders) SELECT * FROM o").unwrap()[0], Statement::CreateTableAs(_)));
+        assert!(matches!(parse("CREATE TABLE t AS"), Err(SqlError::SyntaxError(_))));
+    }
+
+    #[test]
+    fn should_parse_explain() {
+        let statements = parse("EXPLAIN ANALYZE SELECT * FROM t WHERE id = 1; explain select id from t").unwrap();
+
+        assert_eq!(statements, vec![
+            Statement::Explain(Explain {
+                analyze: true,
+                select: Select {
+                    ctes: vec![],
+                    projection: Projection::All,
+                    table: "t".to_owned(),
+                    table_args: None,
+                    filter: vec![Condition { column: "id".to_owned(), op: CompareOp::Eq, value: Literal::Int(1) }],
+                    order_by: vec![],
+                    limit: None,
+                    column_filter: vec![],
+                    subqueries: vec![],
+                    function_filter: vec![],
+                },
+            }),
+            Statement::Explain(Explain {
+                analyze: false,
+                select: Select { ctes: vec![], projection: Projection::Columns(vec!["id".to_owned()]), table: "t".to_owned(), table_args: None, filter: vec![], order_by: vec![], limit: None, column_filter: vec![], subqueries: vec![], function_filter: vec![] },
+            }),
+        ]);
+        assert!(matches!(parse("EXPLAIN DELETE FROM t"), Err(SqlError::SyntaxError(_))));
+    }
+
+    #[test]
+    fn should_report_syntax_errors() {
+        assert!(matches!(parse("SELECT FROM t"), Err(SqlError::SyntaxError(_))));
+        assert!(matches!(parse("SELECT * FROM t WHERE a ~ 1"), Err(SqlError::SyntaxError(_))));
+        assert!(matches!(parse("INSERT INTO t VALUES ('abc"), Err(SqlError::SyntaxError(_))));
+        assert!(matches!(parse("SELECT * FROM t x"), Err(SqlError::SyntaxError(_))));
+        assert!(matches!(parse("CREATE TABLE t (a VARCHAR(0))"), Err(SqlError::SyntaxError(_))));
+    }
+}
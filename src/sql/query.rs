@@ -0,0 +1,979 @@
+use std::{rc::Rc, time::{Duration, Instant, SystemTime}};
+
+use crate::{
+    database::{Database, cardinality::HyperLogLog, table_access::{QueryResult, TableAccess}, temp_table::TempTable, sort::{RowComparator, TopK}, virtual_table::{Constraint, VirtualTable}, trace::{FIELD_CACHE_HITS, FIELD_DB_NAME, FIELD_DB_OPERATION, FIELD_DB_SYSTEM, FIELD_DETAIL, FIELD_PAGES_READ, FIELD_ROWS_OUT, FIELD_TABLE, FieldValue, Span, Tracer}},
+    sql::{CompareOp, Condition, Literal, Projection, Select, SelectItem, SqlError, cte::{self, Cte, CteRows}, function::{BoundCall, bind_comparison, call_text, function_comparison_text, literal_text}, executor::{ExecResult, column_index, conditions, matches, scan}, subquery::{Scope, SubqueryJoin, comparison_text, is_null, local_name}},
+    store::{Store, predicate},
+    table::{Column, ColumnType, TableSchema, table::{Cell, Row}},
+};
+
+// A SELECT is executed as: scan (index or sequential) -> filter (remaining conditions) -> [sort] -> project.
+// The sort needs all rows of the filter before it returns the first one. With a LIMIT, the sort only keeps
+// the best rows (Top-N Sort, see database::sort::TopK), without ORDER BY the scan stops at the limit.
+// run_instrumented() measures every operator while pulling the rows through it:
+// time is only the time spent in the operator itself, page I/O is attributed to the scan.
+// With a Tracer (Database::with_tracer) every query is instrumented and reported as spans (see database/trace.rs).
+// Subqueries of the WHERE clause are evaluated after the filter (see sql/subquery.rs). The ones that are executed
+// once run before the scan, the scan is skipped if the result excludes all rows (e.g. EXISTS without rows).
+// Instead of a table, the query can read a CTE of its WITH clause or of an outer query (see sql/cte.rs)
+// or a virtual table (see database/virtual_table.rs), which gets the conditions it accepts for pushdown.
+// Function calls (see sql/function.rs) are bound before the scan, a wrong call fails without reading a page.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct OperatorStats {
+    pub rows: usize,
+    pub time: Duration,
+    pub pages_read: u64,
+    pub cache_hits: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanNode {
+    pub operator: String,
+    pub detail: Option<String>,
+    // None if the plan was not executed (EXPLAIN without ANALYZE)
+    pub stats: Option<OperatorStats>,
+    pub children: Vec<PlanNode>,
+    // table of a scan
+    pub table: Option<String>,
+}
+
+impl PlanNode {
+    pub(super) fn new(operator: String, detail: Option<String>, stats: Option<OperatorStats>, children: Vec<PlanNode>) -> Self {
+        Self { operator, detail, stats, children, table: None }
+    }
+
+    /// One line per operator, children are indented below their parent
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        self.collect_lines(0, &mut lines);
+        lines
+    }
+
+    fn collect_lines(&self, depth: usize, lines: &mut Vec<String>) {
+        let mut line = match depth {
+            0 => self.operator.clone(),
+            _ => format!("{}-> {}", "  ".repeat(depth), self.operator),
+        };
+        if let Some(detail) = &self.detail {
+            line.push_str(&format!(" ({})", detail));
+        }
+        if let Some(stats) = &self.stats {
+            line.push_str(&format!(" (rows={} time={:.3}ms", stats.rows, stats.time.as_secs_f64() * 1000.0));
+            if self.children.is_empty() {
+                line.push_str(&format!(" pages={} hits={}", stats.pages_read, stats.cache_hits));
+            }
+            line.push(')');
+        }
+        lines.push(line);
+
+        for child in &self.children {
+            child.collect_lines(depth + 1, lines);
+        }
+    }
+
+    /// The plan as result of an EXPLAIN statement (one row per line)
+    pub fn into_result(self) -> ExecResult {
+        ExecResult::Rows {
+            schema: TableSchema::of([Column::new(1, "QUERY PLAN", ColumnType::Varchar(u16::MAX))]),
+            rows: self.lines().into_iter().map(|line| Row::new(vec![Cell::Varchar(line)])).collect(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub(super) enum Mode {
+    // only opens the scan
+    Plan,
+    Run,
+    Instrumented,
+}
+
+pub struct Query<'db, S: Store> {
+    db: &'db Database<S>,
+    select: Select,
+    // CTEs visible in the query
+    ctes: Option<Rc<Cte>>,
+}
+
+// rows of a table, of a CTE or of a virtual table
+enum Source<'db, S: Store> {
+    Table(TableAccess<'db, S>),
+    Cte(CteRows),
+    Virtual(Rc<dyn VirtualTable>, TableSchema),
+}
+
+impl<'db, S: Store> Query<'db, S> {
+    pub fn new(db: &'db Database<S>, select: Select) -> Self {
+        Self::with_ctes(db, select, None)
+    }
+
+    pub(super) fn with_ctes(db: &'db Database<S>, select: Select, outer: Option<Rc<Cte>>) -> Self {
+        let ctes = Cte::chain(&select, outer);
+        Self { db, select, ctes }
+    }
+
+    /// A subquery, it sees the CTEs of this query
+    pub(super) fn nested(&self, select: Select) -> Self {
+        Self::with_ctes(self.db, select, self.ctes.clone())
+    }
+
+    /// This query with a changed select (e.g. other conditions), but the same CTEs
+    pub(super) fn with_select(&self, select: Select) -> Self {
+        Self { db: self.db, select, ctes: self.ctes.clone() }
+    }
+
+    /// Schema of the table, virtual table or CTE in the FROM clause
+    pub(super) fn source_schema(&self) -> Result<TableSchema, SqlError> {
+        if let Some(cte) = self.cte() {
+            return cte.schema(self.db);
+        }
+        match self.virtual_table()? {
+            Some(table) => Ok(table.schema()),
+            None => Ok(self.db.read_table(&self.select.table)?.schema().clone()),
+        }
+    }
+
+    fn source(&self, mode: Mode) -> Result<Source<'db, S>, SqlError> {
+        if let Some(cte) = self.cte() {
+            return cte.rows(self.db, mode).map(Source::Cte);
+        }
+        match self.virtual_table()? {
+            Some(table) => {
+                let schema = table.schema();
+                Ok(Source::Virtual(table, schema))
+            },
+            None => {
+                let table = self.db.read_table(&self.select.table)?;
+                Ok(Source::Table(self.db.table_access(table)?))
+            },
+        }
+    }
+
+    fn cte(&self) -> Option<Rc<Cte>> {
+        match self.select.table_args {
+            Some(_) => None,
+            None => cte::find(self.ctes.as_ref(), &self.select.table),
+        }
+    }
+
+    // a table function, a table of an attached database, a system view or a registered virtual table
+    fn virtual_table(&self) -> Result<Option<Rc<dyn VirtualTable>>, SqlError> {
+        let Some(args) = &self.select.table_args else {
+            if let Some(table) = self.db.foreign_table(&self.select.table)? {
+                return Ok(Some(table));
+            }
+            if let Some(view) = self.db.system_view_table(&self.select.table)? {
+                return Ok(Some(view));
+            }
+            return Ok(self.db.virtual_table(&self.select.table));
+        };
+        let args = args.iter()
+            .map(|arg| match arg {
+                Literal::Int(value) => i32::try_from(*value)
+                    .map(Cell::Int)
+                    .map_err(|_| SqlError::ExecutionError(format!("Argument {} of {} is out of range", value, self.select.table))),
+                Literal::String(value) => Ok(Cell::Varchar(value.clone())),
+            })
+            .collect::<Result<Vec<Cell>, SqlError>>()?;
+        Ok(Some(self.db.call_table_function(&self.select.table, &args)?))
+    }
+
+    /// The plan without executing it
+    pub fn plan(&self) -> Result<PlanNode, SqlError> {
+        self.execute(Mode::Plan).map(|(_, plan)| plan)
+    }
+
+    pub fn run(&self) -> Result<ExecResult, SqlError> {
+        match self.db.tracer() {
+            Some(_) => self.run_instrumented().map(|(result, _)| result),
+            None => self.execute(Mode::Run).map(|(result, _)| result),
+        }
+    }
+
+    /// Executes the query and writes its rows into a temporary table (see database::temp_table):
+    /// following queries read the table by its name instead of executing this query again.
+    pub fn materialize(&self) -> Result<TempTable<'db, S>, SqlError> {
+        let (schema, rows) = match self.run()? {
+            ExecResult::Rows { schema, rows } => (schema, rows),
+            other => return Err(SqlError::ExecutionError(format!("Query returned no rows: {}", other.tag()))),
+        };
+        Ok(self.db.create_temp_table_from(QueryResult::from_rows(rows, schema))?)
+    }
+
+    /// Executes the query and returns the plan with row counts, timings and page I/O of every operator
+    pub fn run_instrumented(&self) -> Result<(ExecResult, PlanNode), SqlError> {
+        let clock = self.db.clock();
+        let (start, started) = (clock.now(), clock.monotonic());
+        let (result, plan) = self.execute(Mode::Instrumented)?;
+        if let Some(tracer) = self.db.tracer() {
+            self.trace(tracer.as_ref(), &result, &plan, start, clock.monotonic().saturating_sub(started));
+        }
+        Ok((result, plan))
+    }
+
+    fn trace(&self, tracer: &dyn Tracer, result: &ExecResult, plan: &PlanNode, start: SystemTime, duration: Duration) {
+        let rows = match result {
+            ExecResult::Rows { rows, .. } | ExecResult::CommandRows { rows, .. } => rows.len(),
+            ExecResult::Command(_) => 0,
+        };
+        let query = Span::new(&format!("SELECT {}", self.select.table), None, start, duration)
+            .with_field(FIELD_DB_SYSTEM, FieldValue::Str("playdb".to_owned()))
+            .with_field(FIELD_DB_NAME, FieldValue::Str(self.db.name.clone()))
+            .with_field(FIELD_DB_OPERATION, FieldValue::Str("SELECT".to_owned()))
+            .with_field(FIELD_TABLE, FieldValue::Str(self.select.table.clone()))
+            .with_field(FIELD_ROWS_OUT, FieldValue::U64(rows as u64));
+        let (_, pages_read) = trace_operator(tracer, plan, query.id, start);
+        tracer.on_span(&query.with_field(FIELD_PAGES_READ, FieldValue::U64(pages_read)));
+    }
+
+    pub(super) fn execute(&self, mode: Mode) -> Result<(ExecResult, PlanNode), SqlError> {
+        let instrument = mode == Mode::Instrumented;
+        // a CTE is executed here, before the I/O of the scan is measured
+        let source = self.source(mode)?;
+        let schema = match &source {
+            Source::Table(access) => access.table().schema(),
+            Source::Cte(cte) => &cte.schema,
+            Source::Virtual(_, schema) => schema,
+        };
+
+        let outputs = match &self.select.projection {
+            Projection::All => (0..schema.columns.len()).map(Output::Column).collect(),
+            Projection::Columns(names) => names.iter()
+                .map(|name| column_index(schema, name).map(Output::Column))
+                .collect::<Result<Vec<Output>, SqlError>>()?,
+            Projection::ApproxCountDistinct(name) => vec![Output::Column(column_index(schema, name)?)],
+            Projection::Items(items) => items.iter()
+                .map(|item| match item {
+                    SelectItem::Column(name) => column_index(schema, name).map(Output::Column),
+                    SelectItem::Call(call) => BoundCall::bind(self.db, call, &self.select.table, schema).map(Output::Call),
+                })
+                .collect::<Result<Vec<Output>, SqlError>>()?,
+        };
+        // an aggregate returns one row, so the LIMIT is applied to its result and not to the scan
+        let aggregate = matches!(self.select.projection, Projection::ApproxCountDistinct(_));
+        let projected_schema = match aggregate {
+            true => TableSchema::of([Column::new(1, "approx_count_distinct", ColumnType::Int)]),
+            false => TableSchema::try_new(outputs.iter()
+                .enumerate()
+                .map(|(position, output)| match output {
+                    Output::Column(i) => schema.columns[*i].clone(),
+                    Output::Call(call) => call.column(position as i32 + 1),
+                })
+                .collect())
+                .map_err(|e| SqlError::ExecutionError(e.to_string()))?,
+        };
+        let comparator = match self.select.order_by.is_empty() {
+            true => None,
+            false => Some(RowComparator::new(schema, &self.select.order_by)?),
+        };
+
+        let scope = Scope { table: &self.select.table, schema, outer: None };
+        let column_filter = self.select.column_filter.iter()
+            .map(|comparison| Ok((scope.local(&comparison.left)?, comparison.op, scope.local(&comparison.right)?)))
+            .collect::<Result<Vec<(usize, CompareOp, usize)>, SqlError>>()?;
+        let function_filter = self.select.function_filter.iter()
+            .map(|comparison| Ok((bind_comparison(self.db, comparison, &self.select.table, schema)?, comparison.op)))
+            .collect::<Result<Vec<((BoundCall, Cell), CompareOp)>, SqlError>>()?;
+        // before the scan, so the I/O of the subqueries is not counted for the scan
+        let semi_joins = self.select.subqueries.iter()
+            .map(|filter| SubqueryJoin::new(self, filter, &scope, mode))
+            .collect::<Result<Vec<_>, SqlError>>()?;
+        let mut semi_join_stats = vec![OperatorStats::default(); semi_joins.len()];
+        let excludes_all = semi_joins.iter().any(|join| join.excludes_all());
+
+        let filter: Vec<Condition> = self.select.filter.iter()
+            .map(|condition| Condition { column: local_name(&self.select.table, &condition.column).to_owned(), ..condition.clone() })
+            .collect();
+        let io_before = self.db.io_stats();
+        // positions of the conditions pushed down into a virtual table
+        let mut pushdown = Vec::new();
+        let (mut scan_iter, lookup, uses_index, conditions): (Box<dyn Iterator<Item = Result<Row, SqlError>> + '_>, _, _, _) = match &source {
+            Source::Table(access) => {
+                let scan = scan(access, &filter)?;
+                (Box::new(scan.result.into_iter().map(|res| res.map(|(_, row)| row).map_err(SqlError::from))), scan.lookup, scan.uses_index, scan.conditions)
+            },
+            Source::Cte(cte) => {
+                let rows = cte.rows.clone();
+                (Box::new((0..rows.len()).map(move |i| Ok(rows[i].clone()))), None, false, conditions(schema, &filter)?)
+            },
+            Source::Virtual(table, schema) => {
+                let conditions = conditions(schema, &filter)?;
+                let constraints: Vec<Constraint> = conditions.iter()
+                    .enumerate()
+                    .map(|(position, (column, op, value))| (position, Constraint { column: *column, op: store_op(*op), value: value.clone() }))
+                    .filter(|(_, constraint)| table.accepts(constraint))
+                    .map(|(position, constraint)| {
+                        pushdown.push(position);
+                        constraint
+                    })
+                    .collect();
+                let rows = table.scan(&constraints)?.map(move |row| {
+                    let row = row?;
+                    row.validate(schema)?;
+                    Ok(row)
+                });
+                (Box::new(rows), None, false, conditions)
+            },
+        };
+
+        let mut scan_stats = OperatorStats::default();
+        let mut filter_stats = OperatorStats::default();
+        let mut sort_stats = OperatorStats::default();
+        let mut limit_stats = OperatorStats::default();
+        let mut project_stats = OperatorStats::default();
+        let limit = self.select.limit.filter(|_| !aggregate);
+        // with ORDER BY and LIMIT, only the best `limit` rows are kept while scanning
+        let mut top = match (&comparator, limit) {
+            (Some(comparator), Some(limit)) => Some(TopK::new(comparator, limit, |row: &Row| row)),
+            _ => None,
+        };
+        let mut filtered = Vec::new();
+        // EXPLAIN without ANALYZE only opens the scan
+        if mode != Mode::Plan && !excludes_all {
+            loop {
+                // without ORDER BY, the scan stops as soon as the limit is reached
+                if comparator.is_none() && limit.is_some_and(|limit| filtered.len() >= limit) {
+                    break;
+                }
+                let next = timed(instrument, &mut scan_stats.time, || scan_iter.next());
+                let row = match next {
+                    Some(res) => res?,
+                    None => break,
+                };
+                scan_stats.rows += 1;
+
+                let keep = timed(instrument, &mut filter_stats.time, || {
+                    let keep = conditions.iter().all(|(index, op, cell)| matches(&row.cells()[*index], *op, cell))
+                        && column_filter.iter().all(|(left, op, right)| {
+                            let (left, right) = (&row.cells()[*left], &row.cells()[*right]);
+                            !is_null(left) && !is_null(right) && matches(left, *op, right)
+                        });
+                    if !keep {
+                        return Ok(false);
+                    }
+                    for ((call, value), op) in function_filter.iter() {
+                        let result = call.call(&row)?;
+                        if is_null(&result) || !matches(&result, *op, value) {
+                            return Ok(false);
+                        }
+                    }
+                    Ok::<bool, SqlError>(true)
+                })?;
+                if !keep {
+                    continue;
+                }
+                filter_stats.rows += 1;
+
+                let mut joined = true;
+                for (join, join_stats) in semi_joins.iter().zip(semi_join_stats.iter_mut()) {
+                    if !timed(instrument, &mut join_stats.time, || join.matches(&row, schema))? {
+                        joined = false;
+                        break;
+                    }
+                    join_stats.rows += 1;
+                }
+                if !joined {
+                    continue;
+                }
+
+                match top.as_mut() {
+                    Some(top) => timed(instrument, &mut sort_stats.time, || top.push(row)),
+                    None => filtered.push(row),
+                }
+            }
+        }
+        if let Some(top) = top {
+            filtered = timed(instrument, &mut sort_stats.time, || top.into_sorted());
+        } else if let Some(comparator) = &comparator {
+            timed(instrument, &mut sort_stats.time, || comparator.sort(&mut filtered, |row| row));
+        }
+        sort_stats.rows = filtered.len();
+        if let Some(limit) = limit {
+            filtered.truncate(limit);
+        }
+        limit_stats.rows = filtered.len();
+
+        let mut rows = Vec::with_capacity(filtered.len());
+        let mut sketch = aggregate.then(HyperLogLog::new);
+        for row in filtered {
+            let projected = timed(instrument, &mut project_stats.time, || {
+                outputs.iter()
+                    .map(|output| match output {
+                        Output::Column(i) => Ok(row.cells()[*i].clone()),
+                        Output::Call(call) => call.call(&row),
+                    })
+                    .collect::<Result<Vec<Cell>, SqlError>>()
+                    .map(Row::new)
+            })?;
+            match sketch.as_mut() {
+                Some(sketch) if !is_null(&projected.cells()[0]) => sketch.add(&projected.cells()[0]),
+                Some(_) => {},
+                None => {
+                    project_stats.rows += 1;
+                    rows.push(projected);
+                },
+            }
+        }
+        if let Some(sketch) = sketch {
+            let count = i32::try_from(sketch.count()).unwrap_or(i32::MAX);
+            rows.push(Row::new(vec![Cell::Int(count)]));
+            rows.truncate(self.select.limit.unwrap_or(1));
+            project_stats.rows = rows.len();
+        }
+        let io = self.db.io_stats().since(&io_before);
+        scan_stats.pages_read = io.pages_read;
+        scan_stats.cache_hits = io.cache_hits;
+
+        let stats = |stats: OperatorStats| instrument.then_some(stats);
+        let mut scan_node = match &source {
+            Source::Table(_) => PlanNode::new(
+                match (lookup, uses_index) {
+                    (Some(_), true) => format!("Index Scan on {}", self.select.table),
+                    _ => format!("Seq Scan on {}", self.select.table),
+                },
+                lookup.map(|position| condition_text(&filter[position])),
+                stats(scan_stats),
+                vec![],
+            ),
+            Source::Cte(cte) => PlanNode::new(cte.operator.clone(), None, stats(scan_stats), cte.plan.iter().cloned().collect()),
+            Source::Virtual(..) => {
+                let pushed: Vec<String> = pushdown.iter().map(|position| condition_text(&filter[*position])).collect();
+                let operator = match self.select.table_args {
+                    Some(_) => "Function Scan on",
+                    None => "Virtual Scan on",
+                };
+                PlanNode::new(format!("{} {}", operator, self.select.table), (!pushed.is_empty()).then(|| pushed.join(" AND ")), stats(scan_stats), vec![])
+            },
+        };
+        scan_node.table = Some(self.select.table.clone());
+        let filter_conditions: Vec<String> = filter.iter()
+            .enumerate()
+            .filter(|(position, _)| Some(*position) != lookup)
+            .map(|(_, condition)| condition_text(condition))
+            .chain(self.select.column_filter.iter().map(comparison_text))
+            .chain(self.select.function_filter.iter().map(function_comparison_text))
+            .collect();
+        let input = match filter_conditions.is_empty() {
+            true => scan_node,
+            false => PlanNode::new("Filter".to_owned(), Some(filter_conditions.join(" AND ")), stats(filter_stats), vec![scan_node]),
+        };
+        let input = semi_joins.into_iter()
+            .zip(semi_join_stats)
+            .fold(input, |input, (join, join_stats)| join.into_node(input, stats(join_stats)));
+        let input = match comparator {
+            None => input,
+            Some(_) => {
+                let keys: Vec<String> = self.select.order_by.iter().map(|key| key.to_string()).collect();
+                let operator = match limit {
+                    Some(_) => "Top-N Sort",
+                    None => "Sort",
+                };
+                PlanNode::new(operator.to_owned(), Some(keys.join(", ")), stats(sort_stats), vec![input])
+            },
+        };
+        let input = match limit {
+            None => input,
+            Some(limit) => PlanNode::new("Limit".to_owned(), Some(limit.to_string()), stats(limit_stats), vec![input]),
+        };
+        let plan = PlanNode::new("Project".to_owned(), Some(projection_text(&self.select.projection)), stats(project_stats), vec![input]);
+
+        Ok((ExecResult::Rows { schema: projected_schema, rows }, plan))
+    }
+}
+
+// Reports the operator and its children. The operators are pipelined, so all of them start with the query
+// and the duration is the time of the operator including its children.
+// Returns (duration, pages read by the subtree).
+fn trace_operator(tracer: &dyn Tracer, node: &PlanNode, parent: u64, start: SystemTime) -> (Duration, u64) {
+    let stats = node.stats.unwrap_or_default();
+    let mut span = Span::new(&node.operator, Some(parent), start, stats.time);
+    let mut pages_read = stats.pages_read;
+    for child in node.children.iter() {
+        let (duration, pages) = trace_operator(tracer, child, span.id, start);
+        span.duration += duration;
+        pages_read += pages;
+    }
+
+    if let Some(detail) = &node.detail {
+        span = span.with_field(FIELD_DETAIL, FieldValue::Str(detail.clone()));
+    }
+    span = span.with_field(FIELD_ROWS_OUT, FieldValue::U64(stats.rows as u64));
+    if let Some(table) = &node.table {
+        span = span.with_field(FIELD_TABLE, FieldValue::Str(table.clone()));
+    }
+    if node.children.is_empty() {
+        span = span.with_field(FIELD_PAGES_READ, FieldValue::U64(stats.pages_read))
+            .with_field(FIELD_CACHE_HITS, FieldValue::U64(stats.cache_hits));
+    }
+    let duration = span.duration;
+    tracer.on_span(&span);
+    (duration, pages_read)
+}
+
+fn timed<T, F: FnOnce() -> T>(enabled: bool, time: &mut Duration, f: F) -> T {
+    if !enabled {
+        return f();
+    }
+    let start = Instant::now();
+    let result = f();
+    *time += start.elapsed();
+    result
+}
+
+pub(super) fn projection_text(projection: &Projection) -> String {
+    match projection {
+        Projection::All => "*".to_owned(),
+        Projection::Columns(names) => names.join(", "),
+        Projection::ApproxCountDistinct(name) => format!("approx_count_distinct({})", name),
+        Projection::Items(items) => items.iter()
+            .map(|item| match item {
+                SelectItem::Column(name) => name.clone(),
+                SelectItem::Call(call) => call_text(call),
+            })
+            .collect::<Vec<String>>()
+            .join(", "),
+    }
+}
+
+pub(super) fn op_text(op: CompareOp) -> &'static str {
+    match op {
+        CompareOp::Eq => "=",
+        CompareOp::NotEq => "<>",
+        CompareOp::Less => "<",
+        CompareOp::LessEq => "<=",
+        CompareOp::Greater => ">",
+        CompareOp::GreaterEq => ">=",
+    }
+}
+
+fn condition_text(condition: &Condition) -> String {
+    format!("{} {} {}", condition.column, op_text(condition.op), literal_text(&condition.value))
+}
+
+fn store_op(op: CompareOp) -> predicate::CompareOp {
+    match op {
+        CompareOp::Eq => predicate::CompareOp::Eq,
+        CompareOp::NotEq => predicate::CompareOp::Ne,
+        CompareOp::Less => predicate::CompareOp::Lt,
+        CompareOp::LessEq => predicate::CompareOp::Le,
+        CompareOp::Greater => predicate::CompareOp::Gt,
+        CompareOp::GreaterEq => predicate::CompareOp::Ge,
+    }
+}
+
+// a column of the result: a column of the input or the result of a function call
+enum Output {
+    Column(usize),
+    Call(BoundCall),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::{database::{Database, NULL_INT, trace::{FIELD_PAGES_READ, FIELD_ROWS_OUT, FIELD_TABLE, FieldValue, Span, Tracer}, virtual_table::{Constraint, VirtualRows, VirtualTable, VirtualTableError}}, sql::{Statement, executor::{ExecResult, execute}, parser::parse, query::Query}, store::{file_store::FileStore, page_cache::CachedStore, predicate}, table::{Column, ColumnType, TableSchema, table::{Cell, Row}}};
+
+    fn plan_lines(result: &ExecResult) -> Vec<String> {
+        match result {
+            ExecResult::Rows { rows, .. } => rows.iter().map(|row| match &row.cells()[0] {
+                Cell::Varchar(line) => line.clone(),
+                other => panic!("Expected a varchar, got {:?}", other),
+            }).collect(),
+            ExecResult::Command(tag) | ExecResult::CommandRows { tag, .. } => panic!("Expected rows, got command {}", tag),
+        }
+    }
+
+    #[test]
+    fn should_explain_and_analyze_select() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        execute(&db, "
+            CREATE TABLE persons (id INT UNIQUE, name VARCHAR(100), age BYTE);
+            INSERT INTO persons VALUES (1, 'Alice', 30);
+            INSERT INTO persons VALUES (2, 'Bob', 20);
+            INSERT INTO persons VALUES (3, 'Carol', 50);
+        ").unwrap();
+
+        let result = execute(&db, "EXPLAIN SELECT name FROM persons WHERE age > 25 AND name <> 'O''Neil'").unwrap();
+        assert_eq!(plan_lines(&result[0]), vec![
+            "Project (name)",
+            "  -> Filter (age > 25 AND name <> 'O''Neil')",
+            "    -> Seq Scan on persons",
+        ]);
+
+        let result = execute(&db, "EXPLAIN ANALYZE SELECT * FROM persons WHERE id = 2 AND age < 30").unwrap();
+        let lines = plan_lines(&result[0]);
+        assert!(lines[0].starts_with("Project (*) (rows=1 time="), "{}", lines[0]);
+        assert!(lines[1].starts_with("  -> Filter (age < 30) (rows=1 time="), "{}", lines[1]);
+        assert!(lines[2].starts_with("    -> Index Scan on persons (id = 2) (rows=1 time="), "{}", lines[2]);
+        assert!(lines[2].ends_with("pages=1 hits=0)"), "{}", lines[2]);
+
+        let Statement::Select(select) = parse("SELECT id FROM persons WHERE age >= 30").unwrap().remove(0) else {
+            panic!("Expected a select");
+        };
+        let (result, plan) = Query::new(&db, select).run_instrumented().unwrap();
+        assert_eq!(result.tag(), "SELECT 2");
+        let scan = &plan.children[0].children[0];
+        assert_eq!(scan.operator, "Seq Scan on persons");
+        assert_eq!(scan.stats.unwrap().rows, 3);
+        assert_eq!(plan.children[0].stats.unwrap().rows, 2);
+    }
+
+    #[test]
+    fn should_order_by_multiple_columns() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        execute(&db, "
+            CREATE TABLE persons (id INT, name VARCHAR(100), age BYTE);
+            INSERT INTO persons VALUES (1, 'Carol', 30);
+            INSERT INTO persons VALUES (2, 'Alice', 20);
+            INSERT INTO persons VALUES (3, 'Bob', 30);
+            INSERT INTO persons VALUES (4, 'Dave', 50);
+        ").unwrap();
+
+        let result = execute(&db, "SELECT id FROM persons WHERE id <> 4 ORDER BY age DESC, name").unwrap();
+        let ExecResult::Rows { rows, .. } = &result[0] else {
+            panic!("Expected rows");
+        };
+        let ids: Vec<Cell> = rows.iter().map(|row| row.cells()[0].clone()).collect();
+        assert_eq!(ids, vec![Cell::Int(3), Cell::Int(1), Cell::Int(2)]);
+
+        let result = execute(&db, "EXPLAIN ANALYZE SELECT name FROM persons WHERE age > 25 ORDER BY age DESC NULLS LAST, id").unwrap();
+        let lines = plan_lines(&result[0]);
+        assert!(lines[0].starts_with("Project (name) (rows=3 "), "{}", lines[0]);
+        assert!(lines[1].starts_with("  -> Sort (age DESC NULLS LAST, id) (rows=3 "), "{}", lines[1]);
+        assert!(lines[2].starts_with("    -> Filter (age > 25)"), "{}", lines[2]);
+
+        assert!(execute(&db, "SELECT * FROM persons ORDER BY unknown").is_err());
+    }
+
+    #[test]
+    fn should_keep_only_the_top_rows_with_limit() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        execute(&db, "CREATE TABLE scores (id INT, score INT)").unwrap();
+        for i in 0..200 {
+            execute(&db, &format!("INSERT INTO scores VALUES ({}, {})", i, (i * 37) % 101)).unwrap();
+        }
+
+        let ids = |sql: &str| -> Vec<Cell> {
+            match execute(&db, sql).unwrap().remove(0) {
+                ExecResult::Rows { rows, .. } => rows.iter().map(|row| row.cells()[0].clone()).collect(),
+                ExecResult::Command(tag) | ExecResult::CommandRows { tag, .. } => panic!("Expected rows, got command {}", tag),
+            }
+        };
+        let all = ids("SELECT id FROM scores ORDER BY score DESC, id");
+        assert_eq!(ids("SELECT id FROM scores ORDER BY score DESC, id LIMIT 10"), all[..10].to_vec());
+        assert_eq!(ids("SELECT id FROM scores WHERE id < 5 LIMIT 3"), vec![Cell::Int(0), Cell::Int(1), Cell::Int(2)]);
+        assert!(ids("SELECT id FROM scores ORDER BY score LIMIT 0").is_empty());
+
+        let result = execute(&db, "EXPLAIN ANALYZE SELECT id FROM scores ORDER BY score DESC LIMIT 10").unwrap();
+        let lines = plan_lines(&result[0]);
+        assert!(lines[1].starts_with("  -> Limit (10) (rows=10 "), "{}", lines[1]);
+        assert!(lines[2].starts_with("    -> Top-N Sort (score DESC) (rows=10 "), "{}", lines[2]);
+        assert!(lines[3].starts_with("      -> Seq Scan on scores (rows=200 "), "{}", lines[3]);
+
+        // without ORDER BY, the scan stops at the limit
+        let result = execute(&db, "EXPLAIN ANALYZE SELECT id FROM scores LIMIT 5").unwrap();
+        let lines = plan_lines(&result[0]);
+        assert!(lines[2].starts_with("    -> Seq Scan on scores (rows=5 "), "{}", lines[2]);
+    }
+
+    #[test]
+    fn should_execute_in_and_exists_subqueries_as_semi_joins() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        execute(&db, "
+            CREATE TABLE persons (id INT, name VARCHAR(100));
+            INSERT INTO persons VALUES (1, 'Alice');
+            INSERT INTO persons VALUES (2, 'Bob');
+            INSERT INTO persons VALUES (3, 'Carol');
+            CREATE TABLE orders (person_id INT, total INT);
+            INSERT INTO orders VALUES (1, 50);
+            INSERT INTO orders VALUES (1, 150);
+            INSERT INTO orders VALUES (3, 200);
+            CREATE TABLE empty (id INT);
+        ").unwrap();
+
+        let ids = |sql: &str| -> Vec<Cell> {
+            match execute(&db, sql).unwrap().remove(0) {
+                ExecResult::Rows { rows, .. } => rows.iter().map(|row| row.cells()[0].clone()).collect(),
+                ExecResult::Command(tag) | ExecResult::CommandRows { tag, .. } => panic!("Expected rows, got command {}", tag),
+            }
+        };
+        // every person at most once, even with several orders
+        assert_eq!(ids("SELECT id FROM persons WHERE id IN (SELECT person_id FROM orders)"), vec![Cell::Int(1), Cell::Int(3)]);
+        assert_eq!(ids("SELECT id FROM persons WHERE id NOT IN (SELECT person_id FROM orders WHERE total > 100)"), vec![Cell::Int(2)]);
+        assert_eq!(ids("SELECT id FROM persons WHERE id > 1 AND id IN (SELECT person_id FROM orders) ORDER BY id DESC"), vec![Cell::Int(3)]);
+        assert_eq!(ids("SELECT id FROM persons WHERE EXISTS (SELECT * FROM orders) LIMIT 1"), vec![Cell::Int(1)]);
+        assert!(ids("SELECT id FROM persons WHERE EXISTS (SELECT * FROM empty)").is_empty());
+        assert_eq!(ids("SELECT id FROM persons WHERE NOT EXISTS (SELECT * FROM empty)").len(), 3);
+
+        // NOT IN with a NULL in the subquery matches no row
+        execute(&db, &format!("INSERT INTO orders VALUES ({}, 10)", NULL_INT)).unwrap();
+        assert!(ids("SELECT id FROM persons WHERE id NOT IN (SELECT person_id FROM orders)").is_empty());
+        assert_eq!(ids("SELECT id FROM persons WHERE id IN (SELECT person_id FROM orders)").len(), 2);
+
+        assert!(execute(&db, "SELECT id FROM persons WHERE id IN (SELECT * FROM orders)").is_err());
+        assert!(execute(&db, "SELECT id FROM persons WHERE name IN (SELECT person_id FROM orders)").is_err());
+
+        let result = execute(&db, "EXPLAIN ANALYZE SELECT name FROM persons WHERE id NOT IN (SELECT person_id FROM orders WHERE total > 100)").unwrap();
+        let lines = plan_lines(&result[0]);
+        assert!(lines[1].starts_with("  -> Hash Anti Join (id NOT IN (SELECT person_id FROM orders)) (rows=1 "), "{}", lines[1]);
+        assert!(lines[2].starts_with("    -> Seq Scan on persons (rows=3 "), "{}", lines[2]);
+        assert!(lines[3].starts_with("    -> Project (person_id) (rows=2 "), "{}", lines[3]);
+        assert!(lines[5].starts_with("        -> Seq Scan on orders (rows=4 "), "{}", lines[5]);
+
+        // a false EXISTS skips the scan
+        let result = execute(&db, "EXPLAIN ANALYZE SELECT id FROM persons WHERE EXISTS (SELECT id FROM empty)").unwrap();
+        let lines = plan_lines(&result[0]);
+        assert!(lines[1].starts_with("  -> One-Time Filter (EXISTS (SELECT id FROM empty)) (rows=0 "), "{}", lines[1]);
+        assert!(lines[2].starts_with("    -> Seq Scan on persons (rows=0 "), "{}", lines[2]);
+        assert!(lines[4].starts_with("      -> Limit (1) "), "{}", lines[4]);
+    }
+
+    #[test]
+    fn should_report_cache_hits() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", CachedStore::new(FileStore::new(base_path.path()), 64));
+        db.drop_create().unwrap();
+        execute(&db, "CREATE TABLE t (id INT); INSERT INTO t VALUES (1)").unwrap();
+
+        let result = execute(&db, "EXPLAIN ANALYZE SELECT * FROM t").unwrap();
+        let lines = plan_lines(&result[0]);
+        assert!(lines[1].ends_with("pages=0 hits=1)"), "{}", lines[1]);
+    }
+
+    #[derive(Default)]
+    struct CollectingTracer {
+        spans: RefCell<Vec<Span>>,
+    }
+
+    impl Tracer for CollectingTracer {
+        fn on_span(&self, span: &Span) {
+            self.spans.borrow_mut().push(span.clone());
+        }
+    }
+
+    #[test]
+    fn should_report_spans_of_query_and_operators() {
+        let base_path = tempfile::tempdir().unwrap();
+        let tracer = Rc::new(CollectingTracer::default());
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()))
+            .with_tracer(tracer.clone());
+        db.drop_create().unwrap();
+        execute(&db, "
+            CREATE TABLE persons (id INT, age BYTE);
+            INSERT INTO persons VALUES (1, 30);
+            INSERT INTO persons VALUES (2, 20);
+        ").unwrap();
+        assert!(tracer.spans.borrow().is_empty());
+
+        execute(&db, "SELECT id FROM persons WHERE age > 25").unwrap();
+        let spans = tracer.spans.borrow();
+        let names: Vec<&str> = spans.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["Seq Scan on persons", "Filter", "Project", "SELECT persons"]);
+
+        let (scan, filter, project, query) = (&spans[0], &spans[1], &spans[2], &spans[3]);
+        assert_eq!(query.parent, None);
+        assert_eq!((project.parent, filter.parent, scan.parent), (Some(query.id), Some(project.id), Some(filter.id)));
+        assert_eq!(scan.field(FIELD_TABLE), Some(&FieldValue::Str("persons".to_owned())));
+        assert_eq!(scan.field(FIELD_ROWS_OUT), Some(&FieldValue::U64(2)));
+        assert_eq!(scan.field(FIELD_PAGES_READ), Some(&FieldValue::U64(1)));
+        assert_eq!(query.field(FIELD_ROWS_OUT), Some(&FieldValue::U64(1)));
+        assert_eq!(query.field(FIELD_PAGES_READ), Some(&FieldValue::U64(1)));
+        assert!(project.duration >= filter.duration && filter.duration >= scan.duration);
+    }
+
+    #[test]
+    fn should_materialize_the_result_into_a_temp_table() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        execute(&db, "
+            CREATE TABLE persons (id INT UNIQUE, name VARCHAR(100), age BYTE);
+            INSERT INTO persons VALUES (1, 'Alice', 30), (2, 'Bob', 20), (3, 'Carol', 50);
+        ").unwrap();
+
+        let Statement::Select(select) = parse("SELECT id, name FROM persons WHERE age >= 30").unwrap().remove(0) else {
+            panic!("Expected a select");
+        };
+        let adults = Query::new(&db, select).materialize().unwrap();
+        assert_eq!(adults.len(), 2);
+
+        // the source changes, the temp table keeps the result
+        execute(&db, "DELETE FROM persons WHERE id = 3").unwrap();
+        let result = execute(&db, &format!("SELECT name FROM {} WHERE id > 1", adults.name())).unwrap();
+        assert_eq!(result[0].tag(), "SELECT 1");
+        let result = execute(&db, &format!("SELECT * FROM {}", adults.name())).unwrap();
+        assert_eq!(result[0].tag(), "SELECT 2");
+
+        let name = adults.name().to_owned();
+        drop(adults);
+        assert!(execute(&db, &format!("SELECT * FROM {}", name)).is_err());
+    }
+
+    // id, name: rows 1..=3, the constraints of the last scan are recorded
+    struct Metrics {
+        scans: RefCell<Vec<Vec<Constraint>>>,
+        invalid: bool,
+    }
+
+    impl VirtualTable for Metrics {
+        fn schema(&self) -> TableSchema {
+            TableSchema::new(vec![Column::new(1, "id", ColumnType::Int), Column::new(2, "name", ColumnType::Varchar(10))])
+        }
+
+        fn accepts(&self, constraint: &Constraint) -> bool {
+            constraint.column == 0 && constraint.op == predicate::CompareOp::Eq
+        }
+
+        fn scan(&self, constraints: &[Constraint]) -> Result<VirtualRows<'_>, VirtualTableError> {
+            self.scans.borrow_mut().push(constraints.to_vec());
+            let constraints = constraints.to_vec();
+            let invalid = self.invalid;
+            Ok(Box::new((1..=3)
+                .map(move |id| match invalid {
+                    true => Row::new(vec![Cell::Int(id)]),
+                    false => Row::new(vec![Cell::Int(id), Cell::Varchar(format!("metric_{}", id))]),
+                })
+                .filter(move |row| constraints.iter().all(|c| row.cells()[c.column] == c.value))
+                .map(Ok)))
+        }
+    }
+
+    #[test]
+    fn should_query_the_system_views() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        execute(&db, "
+            CREATE TABLE persons (id INT UNIQUE, name VARCHAR(20));
+            INSERT INTO persons VALUES (1, 'Alice'), (2, 'Bob');
+        ").unwrap();
+
+        let result = execute(&db, "SELECT pages, rows FROM _Stats_Tables WHERE name = 'persons'").unwrap();
+        assert_eq!(rows(&result[0]), vec![vec![Cell::Int(1), Cell::Int(2)]]);
+        // in a subquery and combined with a stored table
+        let result = execute(&db, "SELECT name FROM persons WHERE id IN (SELECT rows FROM _stats_tables WHERE name = 'persons')").unwrap();
+        assert_eq!(rows(&result[0]), vec![vec![Cell::Varchar("Bob".to_owned())]]);
+        assert!(rows(&execute(&db, "SELECT * FROM _locks").unwrap()[0]).is_empty());
+
+        // read only
+        assert!(execute(&db, "DELETE FROM _stats_tables").is_err());
+    }
+
+    #[test]
+    fn should_query_a_virtual_table_with_pushdown() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        execute(&db, "
+            CREATE TABLE alerts (metric_id INT, level VARCHAR(10));
+            INSERT INTO alerts VALUES (2, 'warn'), (5, 'error');
+        ").unwrap();
+        let metrics = Rc::new(Metrics { scans: RefCell::new(vec![]), invalid: false });
+        db.register_virtual_table("metrics", metrics.clone()).unwrap();
+        assert!(matches!(db.register_virtual_table("alerts", metrics.clone()), Err(VirtualTableError::AlreadyExists(_))));
+
+        let result = execute(&db, "SELECT name FROM metrics WHERE id = 2 AND name <> 'x'").unwrap();
+        assert_eq!(result[0].tag(), "SELECT 1");
+        assert_eq!(metrics.scans.borrow().last().unwrap(), &vec![Constraint { column: 0, op: predicate::CompareOp::Eq, value: Cell::Int(2) }]);
+
+        let result = execute(&db, "EXPLAIN SELECT name FROM metrics WHERE id = 2 AND name <> 'x'").unwrap();
+        assert_eq!(plan_lines(&result[0]), vec![
+            "Project (name)",
+            "  -> Filter (id = 2 AND name <> 'x')",
+            "    -> Virtual Scan on metrics (id = 2)",
+        ]);
+
+        // combined with a stored table
+        let result = execute(&db, "SELECT level FROM alerts WHERE metric_id IN (SELECT id FROM metrics)").unwrap();
+        assert_eq!(result[0].tag(), "SELECT 1");
+        assert!(execute(&db, "INSERT INTO metrics VALUES (4, 'x')").is_err());
+
+        db.register_virtual_table("broken", Rc::new(Metrics { scans: RefCell::new(vec![]), invalid: true })).unwrap();
+        assert!(execute(&db, "SELECT * FROM broken").is_err());
+        assert!(db.drop_virtual_table("broken"));
+        assert!(execute(&db, "SELECT * FROM broken").is_err());
+    }
+
+    #[test]
+    fn should_select_from_table_functions() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        execute(&db, "
+            CREATE TABLE readings (day INT, value INT);
+            INSERT INTO readings VALUES (1, 10), (2, 12), (4, 9);
+        ").unwrap();
+
+        let result = execute(&db, "SELECT * FROM generate_series(1, 5, 2)").unwrap();
+        assert_eq!(rows(&result[0]), vec![vec![Cell::Int(1)], vec![Cell::Int(3)], vec![Cell::Int(5)]]);
+
+        // the days without a reading
+        let result = execute(&db, "SELECT generate_series FROM generate_series(1, 5) WHERE generate_series NOT IN (SELECT day FROM readings)").unwrap();
+        assert_eq!(rows(&result[0]), vec![vec![Cell::Int(3)], vec![Cell::Int(5)]]);
+
+        let result = execute(&db, "SELECT unnest FROM unnest('b', 'a', 'c') ORDER BY unnest DESC LIMIT 2").unwrap();
+        assert_eq!(rows(&result[0]), vec![vec![Cell::Varchar("c".to_owned())], vec![Cell::Varchar("b".to_owned())]]);
+
+        let result = execute(&db, "EXPLAIN SELECT * FROM generate_series(1, 3) WHERE generate_series > 1").unwrap();
+        assert_eq!(plan_lines(&result[0]), vec![
+            "Project (*)",
+            "  -> Filter (generate_series > 1)",
+            "    -> Function Scan on generate_series",
+        ]);
+
+        let result = execute(&db, "CREATE TABLE numbers AS SELECT * FROM generate_series(1, 100); SELECT * FROM numbers").unwrap();
+        assert_eq!(result[1].tag(), "SELECT 100");
+        assert!(execute(&db, "SELECT * FROM generate_series(1, 2, 0)").is_err());
+        assert!(execute(&db, "SELECT * FROM readings(1)").is_err());
+    }
+
+    fn rows(result: &ExecResult) -> Vec<Vec<Cell>> {
+        match result {
+            ExecResult::Rows { rows, .. } => rows.iter().map(|row| row.cells().clone()).collect(),
+            other => panic!("Expected rows, got {}", other.tag()),
+        }
+    }
+
+    #[test]
+    fn should_join_a_table_of_an_attached_database() {
+        let other_path = tempfile::tempdir().unwrap();
+        let other = Database::new_with_store("other", FileStore::new(other_path.path()));
+        other.drop_create().unwrap();
+        execute(&other, "
+            CREATE TABLE persons (id INT UNIQUE, name VARCHAR(20));
+            INSERT INTO persons VALUES (1, 'Alice'), (2, 'Bob'), (3, 'Carol');
+        ").unwrap();
+
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        execute(&db, "
+            CREATE TABLE orders (person_id INT, total INT);
+            INSERT INTO orders VALUES (1, 10), (3, 20);
+        ").unwrap();
+        db.attach(other_path.path(), "crm").unwrap();
+
+        let result = execute(&db, "SELECT name FROM crm.persons WHERE id IN (SELECT person_id FROM orders) ORDER BY name").unwrap();
+        assert_eq!(rows(&result[0]), vec![vec![Cell::Varchar("Alice".to_owned())], vec![Cell::Varchar("Carol".to_owned())]]);
+        let result = execute(&db, "SELECT total FROM orders WHERE person_id IN (SELECT id FROM crm.persons WHERE name = 'Carol')").unwrap();
+        assert_eq!(rows(&result[0]), vec![vec![Cell::Int(20)]]);
+
+        // the longest prefix that names the table of the query is the qualifier: crm.persons, not crm
+        let result = execute(&db, "SELECT name FROM crm.persons WHERE CRM.persons.id = 2").unwrap();
+        assert_eq!(rows(&result[0]), vec![vec![Cell::Varchar("Bob".to_owned())]]);
+        let result = execute(&db, "SELECT total FROM orders WHERE EXISTS (SELECT * FROM crm.persons WHERE crm.persons.id = orders.person_id AND name = 'Alice')").unwrap();
+        assert_eq!(rows(&result[0]), vec![vec![Cell::Int(10)]]);
+
+        // read only
+        assert!(execute(&db, "INSERT INTO crm.persons VALUES (4, 'Dave')").is_err());
+        assert!(execute(&db, "DELETE FROM crm.persons").is_err());
+    }
+}
@@ -0,0 +1,466 @@
+use std::{cell::Cell as Counter, collections::{HashMap, HashSet}};
+
+use crate::{
+    database::NULL_INT,
+    sql::{ColumnComparison, CompareOp, Condition, Literal, Projection, Select, SqlError, SubqueryFilter, SubqueryPredicate, executor::{ExecResult, column_index, matches}, parser::{normalized_parts, split_qualified_by}, query::{Mode, OperatorStats, PlanNode, Query, op_text, projection_text}},
+    store::Store,
+    table::{TableSchema, table::{Cell, Row, to_hex}},
+};
+
+// Subqueries of the WHERE clause: EXISTS, IN and scalar comparisons (col op (SELECT ...)).
+// A subquery is correlated if its WHERE clause compares a column of the subquery with a column of the outer query
+// (e.g. EXISTS (SELECT * FROM orders WHERE orders.person_id = persons.id)). Two strategies:
+// - decorrelated (no correlation or only by equality, without LIMIT): the subquery is executed once without the
+//   correlation, its rows are grouped by the correlated columns in a hash table and every row of the outer query
+//   probes its group (hash semi / anti join, for scalar subqueries a hash join).
+// - row by row (any other correlation, e.g. <, or a LIMIT): the subquery is executed for every row of the outer query,
+//   with the values of the row instead of the outer columns. An equality on an indexed column uses the index.
+// Like in SQL, NULL never matches: x IN (...) is unknown if x is NULL, or if x is not found and the subquery returned
+// a NULL, so NOT IN doesn't match then, either. A scalar subquery without rows is NULL, with more than one row it fails.
+//
+// Not supported (yet): table aliases, columns of a query two levels up and conditions on outer columns that
+// don't compare with a column of the subquery (e.g. WHERE persons.age > 30 inside the subquery).
+pub(super) enum ColumnRef {
+    Local(usize),
+    Outer(usize),
+}
+
+/// Columns visible in the WHERE clause of a query
+pub(super) struct Scope<'a> {
+    pub table: &'a str,
+    pub schema: &'a TableSchema,
+    // table and schema of the outer query, if the query is a subquery
+    pub outer: Option<(&'a str, &'a TableSchema)>,
+}
+
+impl Scope<'_> {
+    // unqualified names are looked up in the table of the query first
+    pub fn resolve(&self, name: &str) -> Result<ColumnRef, SqlError> {
+        let tables: Vec<&str> = [Some(self.table), self.outer.map(|(table, _)| table)].into_iter().flatten().collect();
+        let (qualifier, column) = split_qualified_by(name, &tables);
+        let outer = |table: Option<&str>| match self.outer {
+            Some((outer_table, outer_schema)) if table.is_none_or(|t| same_table(t, outer_table)) => outer_schema.find_index_by_name(column),
+            _ => None,
+        };
+
+        match qualifier {
+            Some(table) if same_table(table, self.table) => column_index(self.schema, column).map(ColumnRef::Local),
+            Some(table) => outer(Some(table))
+                .map(ColumnRef::Outer)
+                .ok_or_else(|| SqlError::ExecutionError(format!("Column '{}' does not exist (unknown table '{}')", name, table))),
+            None => self.schema.find_index_by_name(column)
+                .map(ColumnRef::Local)
+                .or_else(|| outer(None).map(ColumnRef::Outer))
+                .ok_or_else(|| SqlError::ExecutionError(format!("Column '{}' does not exist", name))),
+        }
+    }
+
+    /// Index of a column of this query (not of the outer query)
+    pub fn local(&self, name: &str) -> Result<usize, SqlError> {
+        match self.resolve(name)? {
+            ColumnRef::Local(index) => Ok(index),
+            ColumnRef::Outer(_) => Err(SqlError::ExecutionError(format!("Column '{}' of the outer query must be compared with a column of the subquery", name))),
+        }
+    }
+}
+
+pub(super) fn same_table(a: &str, b: &str) -> bool {
+    normalized_parts(a) == normalized_parts(b)
+}
+
+/// The column name without the table, if it is the table of the query
+pub(super) fn local_name<'n>(table: &str, name: &'n str) -> &'n str {
+    match split_qualified_by(name, &[table]) {
+        (Some(qualifier), column) if same_table(qualifier, table) => column,
+        _ => name,
+    }
+}
+
+// column of the subquery (quoted, as it's used in a Condition or Projection) compared with a column of the outer row
+struct Correlation {
+    inner: String,
+    // inner op outer
+    op: CompareOp,
+    outer: usize,
+}
+
+// Rows of the subquery for one value of the correlated columns
+#[derive(Default)]
+struct Group {
+    rows: usize,
+    // values of IN without NULLs
+    values: HashSet<Cell>,
+    has_null: bool,
+    // value of a scalar subquery
+    first: Option<Cell>,
+}
+
+impl Group {
+    fn add(&mut self, value: Option<Cell>) {
+        self.rows += 1;
+        if let Some(value) = value {
+            if is_null(&value) {
+                self.has_null = true;
+            } else {
+                self.values.insert(value.clone());
+            }
+            self.first.get_or_insert(value);
+        }
+    }
+}
+
+enum Strategy {
+    // key: values of the correlated columns (empty without correlation)
+    Groups(HashMap<Vec<Cell>, Group>),
+    // the subquery without the correlation, which is added as conditions for every row
+    RowByRow { select: Select, executions: Counter<usize> },
+}
+
+pub(super) struct SubqueryJoin<'q, 'db, S: Store> {
+    // the subquery, it sees the CTEs of the outer query
+    inner: Query<'db, S>,
+    filter: &'q SubqueryFilter,
+    // column of the outer query for IN and comparisons
+    column: Option<usize>,
+    correlation: Vec<Correlation>,
+    strategy: Strategy,
+    plan: PlanNode,
+}
+
+impl<'q, 'db, S: Store> SubqueryJoin<'q, 'db, S> {
+    pub fn new(query: &Query<'db, S>, filter: &'q SubqueryFilter, outer: &Scope, mode: Mode) -> Result<Self, SqlError> {
+        let subquery = &filter.subquery;
+        let inner = query.nested((**subquery).clone());
+        let inner_schema = &inner.source_schema()?;
+        let scope = Scope { table: &subquery.table, schema: inner_schema, outer: Some((outer.table, outer.schema)) };
+
+        let column = match &filter.predicate {
+            SubqueryPredicate::Exists => None,
+            SubqueryPredicate::In(column) | SubqueryPredicate::Compare(column, _) => Some(outer.local(column)?),
+        };
+
+        let mut local = Vec::new();
+        let mut correlation = Vec::new();
+        for comparison in subquery.column_filter.iter() {
+            let inner_name = |index: usize| quote(&inner_schema.columns[index].name);
+            match (scope.resolve(&comparison.left)?, scope.resolve(&comparison.right)?) {
+                (ColumnRef::Local(_), ColumnRef::Local(_)) => local.push(comparison.clone()),
+                (ColumnRef::Local(inner), ColumnRef::Outer(outer)) => correlation.push(Correlation { inner: inner_name(inner), op: comparison.op, outer }),
+                (ColumnRef::Outer(outer), ColumnRef::Local(inner)) => correlation.push(Correlation { inner: inner_name(inner), op: flip(comparison.op), outer }),
+                (ColumnRef::Outer(_), ColumnRef::Outer(_)) => return Err(SqlError::ExecutionError(
+                    format!("Subquery compares two columns of the outer query: {} {} {}", comparison.left, op_text(comparison.op), comparison.right)
+                )),
+            }
+        }
+
+        let mut select = (**subquery).clone();
+        select.column_filter = local;
+        if filter.predicate == SubqueryPredicate::Exists && (correlation.is_empty() || subquery.limit.is_some()) {
+            // EXISTS only needs one row
+            select.limit = Some(select.limit.map_or(1, |limit| limit.min(1)));
+        }
+
+        let decorrelate = correlation.iter().all(|c| c.op == CompareOp::Eq) && (correlation.is_empty() || subquery.limit.is_none());
+        let mut join = Self { inner, filter, column, correlation, strategy: Strategy::Groups(HashMap::new()), plan: PlanNode::new(String::new(), None, None, vec![]) };
+        if !decorrelate {
+            join.plan = join.inner.with_select(select.clone()).execute(Mode::Plan)?.1;
+            join.strategy = Strategy::RowByRow { select, executions: Counter::new(0) };
+            return Ok(join);
+        }
+
+        // the value (IN, comparison) followed by the correlated columns
+        if !join.correlation.is_empty() {
+            let value = match join.column {
+                Some(_) => Some(value_column(&select, inner_schema)?),
+                None => None,
+            };
+            select.projection = Projection::Columns(value.into_iter().chain(join.correlation.iter().map(|c| c.inner.clone())).collect());
+            select.order_by.clear();
+        }
+        let (result, plan) = join.inner.with_select(select).execute(mode)?;
+        let (_, rows) = join.check_result(result, outer.schema)?;
+        let key_start = join.column.map_or(0, |_| 1);
+
+        let mut groups: HashMap<Vec<Cell>, Group> = HashMap::new();
+        for row in rows {
+            let key = match join.correlation.is_empty() {
+                true => Vec::new(),
+                false => row.cells()[key_start..].to_vec(),
+            };
+            // NULL = x is never true
+            if key.iter().any(is_null) {
+                continue;
+            }
+            let value = join.column.map(|_| row.cells()[0].clone());
+            groups.entry(key).or_default().add(value);
+        }
+        join.strategy = Strategy::Groups(groups);
+        join.plan = plan;
+        Ok(join)
+    }
+
+    // the rows of the subquery, if it returns one column for IN and comparisons of that type
+    fn check_result(&self, result: ExecResult, outer_schema: &TableSchema) -> Result<(TableSchema, Vec<Row>), SqlError> {
+        let ExecResult::Rows { schema, rows } = result else {
+            return Err(SqlError::ExecutionError("Subquery did not return rows".to_owned()));
+        };
+        if let Some(index) = self.column {
+            let expected = match (&self.strategy, self.correlation.is_empty()) {
+                (Strategy::Groups(_), false) => 1 + self.correlation.len(),
+                _ => 1,
+            };
+            if schema.columns.len() != expected {
+                return Err(SqlError::ExecutionError(format!("Subquery must return one column, not {}", schema.columns.len() + 1 - expected)));
+            }
+            let (this_type, that_type) = (outer_schema.columns[index].col_type.raw_type(), schema.columns[0].col_type.raw_type());
+            if this_type != that_type {
+                return Err(SqlError::ExecutionError(format!(
+                    "Column '{}' and subquery have different types: {} vs {}", outer_schema.columns[index].name, this_type, that_type
+                )));
+            }
+        }
+        Ok((schema, rows))
+    }
+
+    /// true if no row can match (e.g. EXISTS without rows), the outer query doesn't need to scan then
+    pub fn excludes_all(&self) -> bool {
+        match &self.strategy {
+            Strategy::Groups(groups) if self.correlation.is_empty() && self.filter.predicate == SubqueryPredicate::Exists => {
+                groups.is_empty() != self.filter.negated
+            },
+            _ => false,
+        }
+    }
+
+    pub fn matches(&self, row: &Row, outer_schema: &TableSchema) -> Result<bool, SqlError> {
+        let key: Vec<Cell> = self.correlation.iter().map(|c| row.cells()[c.outer].clone()).collect();
+        match &self.strategy {
+            Strategy::Groups(groups) => self.evaluate(groups.get(&key), row),
+            Strategy::RowByRow { select, executions } => {
+                let group = match key.iter().any(is_null) {
+                    true => Group::default(),
+                    false => {
+                        executions.set(executions.get() + 1);
+                        self.execute_for(select, &key, outer_schema)?
+                    },
+                };
+                self.evaluate(Some(&group), row)
+            },
+        }
+    }
+
+    fn execute_for(&self, select: &Select, key: &[Cell], outer_schema: &TableSchema) -> Result<Group, SqlError> {
+        let mut select = select.clone();
+        for (correlation, cell) in self.correlation.iter().zip(key) {
+            select.filter.push(Condition { column: correlation.inner.clone(), op: correlation.op, value: to_literal(cell) });
+        }
+        let (result, _) = self.inner.with_select(select).execute(Mode::Run)?;
+        let (_, rows) = self.check_result(result, outer_schema)?;
+
+        let mut group = Group::default();
+        for row in rows {
+            group.add(self.column.map(|_| row.cells()[0].clone()));
+        }
+        Ok(group)
+    }
+
+    fn evaluate(&self, group: Option<&Group>, row: &Row) -> Result<bool, SqlError> {
+        let empty = Group::default();
+        let group = group.unwrap_or(&empty);
+        let value = self.column.map(|index| &row.cells()[index]);
+
+        Ok(match (&self.filter.predicate, value) {
+            (SubqueryPredicate::Exists, _) => (group.rows > 0) != self.filter.negated,
+            (SubqueryPredicate::In(_), Some(value)) => {
+                let found = !is_null(value) && group.values.contains(value);
+                let unknown = !found && group.rows > 0 && (is_null(value) || group.has_null);
+                match self.filter.negated {
+                    false => found,
+                    true => !found && !unknown,
+                }
+            },
+            (SubqueryPredicate::Compare(_, op), Some(value)) => {
+                if group.rows > 1 {
+                    return Err(SqlError::ExecutionError("More than one row returned by a subquery used as an expression".to_owned()));
+                }
+                match &group.first {
+                    Some(first) => !is_null(value) && !is_null(first) && matches(value, *op, first),
+                    None => false,
+                }
+            },
+            _ => false,
+        })
+    }
+
+    pub fn into_node(self, input: PlanNode, stats: Option<OperatorStats>) -> PlanNode {
+        let correlated = !self.correlation.is_empty();
+        let operator = match (&self.strategy, &self.filter.predicate, self.filter.negated) {
+            (Strategy::RowByRow { .. }, _, _) => "Subquery Loop",
+            (_, SubqueryPredicate::Exists, _) if !correlated => "One-Time Filter",
+            (_, SubqueryPredicate::Compare(..), _) if !correlated => "Scalar Filter",
+            (_, SubqueryPredicate::Compare(..), _) => "Hash Scalar Join",
+            (_, _, false) => "Hash Semi Join",
+            (_, _, true) => "Hash Anti Join",
+        };
+        let mut detail = subquery_text(self.filter);
+        if let (Strategy::RowByRow { executions, .. }, Some(_)) = (&self.strategy, stats) {
+            detail.push_str(&format!(" executions={}", executions.get()));
+        }
+        PlanNode::new(operator.to_owned(), Some(detail), stats, vec![input, self.plan])
+    }
+}
+
+// The single column of the subquery for IN and comparisons
+fn value_column(select: &Select, schema: &TableSchema) -> Result<String, SqlError> {
+    match &select.projection {
+        Projection::Columns(names) if names.len() == 1 => Ok(local_name(&select.table, &names[0]).to_owned()),
+        Projection::All if schema.columns.len() == 1 => Ok(quote(&schema.columns[0].name)),
+        Projection::Columns(names) => Err(SqlError::ExecutionError(format!("Subquery must return one column, not {}", names.len()))),
+        Projection::All => Err(SqlError::ExecutionError(format!("Subquery must return one column, not {}", schema.columns.len()))),
+        Projection::ApproxCountDistinct(_) => Err(SqlError::ExecutionError("Aggregate functions are not supported in subqueries".to_owned())),
+        Projection::Items(_) => Err(SqlError::ExecutionError("Function calls are not supported in the result of a subquery".to_owned())),
+    }
+}
+
+pub(super) fn subquery_text(filter: &SubqueryFilter) -> String {
+    let subquery = &filter.subquery;
+    let not = if filter.negated { "NOT " } else { "" };
+    let mut select = format!("SELECT {} FROM {}", projection_text(&subquery.projection), subquery.table);
+    if !subquery.column_filter.is_empty() {
+        let comparisons: Vec<String> = subquery.column_filter.iter().map(comparison_text).collect();
+        select.push_str(&format!(" WHERE {}", comparisons.join(" AND ")));
+    }
+    match &filter.predicate {
+        SubqueryPredicate::Exists => format!("{}EXISTS ({})", not, select),
+        SubqueryPredicate::In(column) => format!("{} {}IN ({})", column, not, select),
+        SubqueryPredicate::Compare(column, op) => format!("{} {} ({})", column, op_text(*op), select),
+    }
+}
+
+pub(super) fn comparison_text(comparison: &ColumnComparison) -> String {
+    format!("{} {} {}", comparison.left, op_text(comparison.op), comparison.right)
+}
+
+// a op b == b flip(op) a
+fn flip(op: CompareOp) -> CompareOp {
+    match op {
+        CompareOp::Less => CompareOp::Greater,
+        CompareOp::LessEq => CompareOp::GreaterEq,
+        CompareOp::Greater => CompareOp::Less,
+        CompareOp::GreaterEq => CompareOp::LessEq,
+        op => op,
+    }
+}
+
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn to_literal(cell: &Cell) -> Literal {
+    match cell {
+        Cell::Int(value) => Literal::Int(*value as i64),
+        Cell::Byte(value) => Literal::Int(*value as i64),
+        Cell::Varchar(value) => Literal::String(value.clone()),
+        Cell::Blob(value) => Literal::String(to_hex(value)),
+        Cell::Point(value) => Literal::String(value.to_string()),
+    }
+}
+
+pub(super) fn is_null(cell: &Cell) -> bool {
+    *cell == Cell::Int(NULL_INT)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::{Database, NULL_INT}, sql::executor::{ExecResult, execute}, store::file_store::FileStore, table::table::Cell};
+
+    fn setup(base_path: &std::path::Path) -> Database<FileStore> {
+        let db = Database::new_with_store("test_db", FileStore::new(base_path));
+        db.drop_create().unwrap();
+        execute(&db, "
+            CREATE TABLE persons (id INT UNIQUE, name VARCHAR(20), max_total INT);
+            INSERT INTO persons VALUES (1, 'Alice', 100);
+            INSERT INTO persons VALUES (2, 'Bob', 100);
+            INSERT INTO persons VALUES (3, 'Carol', 500);
+            CREATE TABLE orders (id INT UNIQUE, person_id INT, total INT);
+            INSERT INTO orders VALUES (10, 1, 50);
+            INSERT INTO orders VALUES (11, 1, 150);
+            INSERT INTO orders VALUES (12, 3, 200);
+        ").unwrap();
+        db
+    }
+
+    fn ids(db: &Database<FileStore>, sql: &str) -> Vec<i32> {
+        match execute(db, sql).unwrap().remove(0) {
+            ExecResult::Rows { rows, .. } => rows.iter().map(|row| row.cells()[0].expect_int("id").unwrap()).collect(),
+            ExecResult::Command(tag) | ExecResult::CommandRows { tag, .. } => panic!("Expected rows, got command {}", tag),
+        }
+    }
+
+    fn plan(db: &Database<FileStore>, sql: &str) -> Vec<String> {
+        match execute(db, &format!("EXPLAIN ANALYZE {}", sql)).unwrap().remove(0) {
+            ExecResult::Rows { rows, .. } => rows.iter().map(|row| match &row.cells()[0] {
+                Cell::Varchar(line) => line.clone(),
+                other => panic!("Expected a varchar, got {:?}", other),
+            }).collect(),
+            ExecResult::Command(tag) | ExecResult::CommandRows { tag, .. } => panic!("Expected rows, got command {}", tag),
+        }
+    }
+
+    #[test]
+    fn should_decorrelate_equality_into_hash_joins() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = setup(base_path.path());
+
+        let exists = "SELECT id FROM persons WHERE EXISTS (SELECT * FROM orders WHERE orders.person_id = persons.id AND total > 100)";
+        assert_eq!(ids(&db, exists), vec![1, 3]);
+        let lines = plan(&db, exists);
+        assert!(lines[1].starts_with("  -> Hash Semi Join (EXISTS (SELECT * FROM orders WHERE orders.person_id = persons.id)) (rows=2 "), "{}", lines[1]);
+        // the subquery is executed once
+        assert!(lines[3].starts_with("    -> Project (\"person_id\") (rows=2 "), "{}", lines[3]);
+
+        assert_eq!(ids(&db, "SELECT id FROM persons WHERE NOT EXISTS (SELECT * FROM orders WHERE person_id = persons.id)"), vec![2]);
+        // unqualified columns are looked up in the subquery first: id is orders.id here
+        assert_eq!(ids(&db, "SELECT id FROM persons WHERE NOT EXISTS (SELECT * FROM orders WHERE person_id = id)"), vec![1, 2, 3]);
+        assert_eq!(ids(&db, "SELECT id FROM persons WHERE NOT EXISTS (SELECT * FROM orders WHERE person_id = persons.id AND id = 12)"), vec![1, 2]);
+
+        // scalar: the total of the order of a person (at most one row per person)
+        let scalar = "SELECT id FROM persons WHERE max_total >= (SELECT total FROM orders WHERE person_id = persons.id AND total > 100)";
+        assert_eq!(ids(&db, scalar), vec![3]);
+        assert!(plan(&db, scalar)[1].starts_with("  -> Hash Scalar Join "));
+        assert!(execute(&db, "SELECT id FROM persons WHERE max_total > (SELECT total FROM orders WHERE person_id = persons.id)").is_err());
+        assert_eq!(ids(&db, "SELECT id FROM persons WHERE max_total = (SELECT max_total FROM persons WHERE id = 3)"), vec![3]);
+        assert!(ids(&db, "SELECT id FROM persons WHERE max_total = (SELECT total FROM orders WHERE id = 99)").is_empty());
+        assert!(execute(&db, "SELECT id FROM persons WHERE max_total = (SELECT total FROM orders)").is_err());
+
+        execute(&db, "INSERT INTO orders VALUES (14, 2, 100)").unwrap();
+        assert_eq!(ids(&db, "SELECT id FROM persons WHERE max_total IN (SELECT total FROM orders WHERE orders.person_id = persons.id)"), vec![2]);
+        assert_eq!(ids(&db, "SELECT id FROM persons WHERE max_total NOT IN (SELECT total FROM orders WHERE orders.person_id = persons.id)"), vec![1, 3]);
+
+        // no NULL matches
+        execute(&db, &format!("INSERT INTO orders VALUES (13, {}, 10)", NULL_INT)).unwrap();
+        execute(&db, &format!("INSERT INTO persons VALUES ({}, 'Nobody', 10)", NULL_INT)).unwrap();
+        assert_eq!(ids(&db, "SELECT id FROM persons WHERE EXISTS (SELECT * FROM orders WHERE person_id = persons.id)"), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn should_execute_other_correlations_row_by_row() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = setup(base_path.path());
+
+        // persons with an order above their max_total
+        let sql = "SELECT id FROM persons WHERE EXISTS (SELECT * FROM orders WHERE person_id = persons.id AND total > persons.max_total)";
+        assert_eq!(ids(&db, sql), vec![1]);
+        let lines = plan(&db, sql);
+        assert!(lines[1].starts_with("  -> Subquery Loop (EXISTS (SELECT * FROM orders WHERE person_id = persons.id AND total > persons.max_total) executions=3) (rows=1 "), "{}", lines[1]);
+
+        // LIMIT applies per row of the outer query
+        assert_eq!(ids(&db, "SELECT id FROM persons WHERE max_total < (SELECT total FROM orders WHERE person_id = persons.id ORDER BY total DESC LIMIT 1)"), vec![1]);
+        assert_eq!(ids(&db, "SELECT id FROM persons WHERE max_total < (SELECT total FROM orders WHERE persons.id = person_id AND persons.max_total < total)"), vec![1]);
+
+        // comparisons of columns without a subquery
+        assert_eq!(ids(&db, "SELECT id FROM orders WHERE total > person_id AND orders.id <> 11"), vec![10, 12]);
+        assert!(execute(&db, "SELECT id FROM orders WHERE total > persons.id").is_err());
+        assert!(execute(&db, "SELECT id FROM persons WHERE EXISTS (SELECT * FROM orders WHERE persons.id = persons.max_total)").is_err());
+    }
+}
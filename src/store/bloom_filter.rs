@@ -0,0 +1,210 @@
+use crate::{data::page::{Page, PageId}, store::StoreError, table::{TableSchema, table::{Cell, Row}}};
+
+// Bloom filters: a small bloom filter per page over the values of one chosen column (Database::create_bloom_filter).
+// The FileStore keeps them in a sidecar file next to the table file ('<table file>.bloom') and updates the filter of
+// a page whenever it writes the page, like the zone maps (see zone_map.rs). A scan for a value (TableAccess::find)
+// skips the pages whose filter doesn't contain it without reading them. Unlike a zone map this also works for
+// values that are not in the order of the pages and for Varchar columns, but only for equality.
+//
+// File format (big endian): 4 bytes magic "PDBB", 1 byte version, 1 byte number of hash functions, 2 bytes size of
+// a filter in bytes, 4 bytes column id, then one entry per page at position page_id - 1: 1 byte state (0 = unknown)
+// and the filter.
+// - the column is stored by its id, if it's dropped the filters are not used anymore
+// - a page without entry (behind the end of the file) or with rows that cannot be read with the schema is unknown
+//   and is always read
+// - the hash of a value is the FNV-1a of its serialized cell, so it is the same on every platform and version
+
+const MAGIC: [u8; 4] = *b"PDBB";
+const VERSION: u8 = 1;
+pub(crate) const HEADER_SIZE: usize = 12;
+const STATE_KNOWN: u8 = 1;
+// 1024 bits and 4 hashes: about 1% false positives for 100 values per page
+pub(crate) const FILTER_SIZE: u16 = 128;
+pub(crate) const HASHES: u8 = 4;
+
+/// What the header of the sidecar file describes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct BloomHeader {
+    pub(crate) column_id: i32,
+    pub(crate) filter_size: u16,
+    pub(crate) hashes: u8,
+}
+
+impl BloomHeader {
+    pub(crate) fn new(column_id: i32) -> Self {
+        Self { column_id, filter_size: FILTER_SIZE, hashes: HASHES }
+    }
+
+    pub(crate) fn serialize(&self) -> Vec<u8> {
+        let mut buf = MAGIC.to_vec();
+        buf.push(VERSION);
+        buf.push(self.hashes);
+        buf.extend_from_slice(&self.filter_size.to_be_bytes());
+        buf.extend_from_slice(&self.column_id.to_be_bytes());
+        buf
+    }
+
+    pub(crate) fn deserialize(buf: &[u8]) -> Result<Self, StoreError> {
+        if buf.len() < HEADER_SIZE || buf[0..4] != MAGIC {
+            return Err(StoreError::UnknownFormat("not a playdb bloom filter".to_owned()));
+        }
+        if buf[4] != VERSION {
+            return Err(StoreError::UnknownFormat(format!("bloom filter version {} is not supported (expected {})", buf[4], VERSION)));
+        }
+        let header = Self {
+            hashes: buf[5],
+            filter_size: u16::from_be_bytes([buf[6], buf[7]]),
+            column_id: i32::from_be_bytes([buf[8], buf[9], buf[10], buf[11]]),
+        };
+        if header.hashes == 0 || header.filter_size == 0 {
+            return Err(StoreError::DeserializationError("bloom filter without hashes or bits".to_owned()));
+        }
+        Ok(header)
+    }
+
+    fn entry_size(&self) -> usize {
+        1 + self.filter_size as usize
+    }
+
+    /// Position of the entry of the page in the sidecar file
+    pub(crate) fn entry_offset(&self, page_id: PageId) -> u64 {
+        HEADER_SIZE as u64 + page_id.saturating_sub(1) * self.entry_size() as u64
+    }
+
+    // bit positions of the value (double hashing)
+    fn bits(&self, cell: &Cell) -> Vec<usize> {
+        let hash = fnv1a(&cell.serialize());
+        let (h1, h2) = (hash as u32 as usize, (hash >> 32) as usize | 1);
+        let bits = self.filter_size as usize * 8;
+        (0..self.hashes as usize)
+            .map(|i| h1.wrapping_add(i.wrapping_mul(h2)) % bits)
+            .collect()
+    }
+
+    /// The entry of the page, unknown if a row cannot be deserialized or the column doesn't exist anymore
+    pub(crate) fn page_entry(&self, page: &Page, schema: &TableSchema) -> Vec<u8> {
+        let unknown = vec![0; self.entry_size()];
+        let Some(col_index) = schema.find_index_by_id(&self.column_id) else {
+            return unknown;
+        };
+        let mut entry = vec![0; self.entry_size()];
+        entry[0] = STATE_KNOWN;
+        for record in page.clone().record_iterator() {
+            let Ok(row) = Row::deserialize(record.data(), schema) else {
+                return unknown;
+            };
+            for bit in self.bits(&row.cells()[col_index]) {
+                entry[1 + bit / 8] |= 1 << (bit % 8);
+            }
+        }
+        entry
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BloomFilters {
+    header: BloomHeader,
+    // index page_id - 1: the filter of the page, None if unknown
+    pages: Vec<Option<Vec<u8>>>,
+}
+
+impl BloomFilters {
+    pub(crate) fn deserialize(buf: &[u8]) -> Result<Self, StoreError> {
+        let header = BloomHeader::deserialize(buf)?;
+        // a torn entry at the end is ignored, the page is unknown
+        let pages = buf[HEADER_SIZE..].chunks_exact(header.entry_size())
+            .map(|entry| (entry[0] == STATE_KNOWN).then(|| entry[1..].to_vec()))
+            .collect();
+        Ok(Self { header, pages })
+    }
+
+    /// Id of the column the filters are built over
+    pub fn column_id(&self) -> i32 {
+        self.header.column_id
+    }
+
+    /// false only if the page certainly has no row with the value in the column
+    pub fn may_contain(&self, page_id: PageId, cell: &Cell) -> bool {
+        self.contains_bits(page_id, &self.header.bits(cell))
+    }
+
+    fn contains_bits(&self, page_id: PageId, bits: &[usize]) -> bool {
+        let filter = page_id.checked_sub(1).and_then(|index| usize::try_from(index).ok())
+            .and_then(|index| self.pages.get(index))
+            .and_then(|filter| filter.as_ref());
+        filter.is_none_or(|filter| bits.iter().all(|bit| filter[bit / 8] & (1 << (bit % 8)) != 0))
+    }
+
+    /// Filter for a PageIterator that skips the pages without the value, None if the filters are over another column
+    pub fn probe(self, column_id: i32, cell: &Cell) -> Option<BloomProbe> {
+        (self.header.column_id == column_id).then(|| BloomProbe { bits: self.header.bits(cell), filters: self })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BloomProbe {
+    filters: BloomFilters,
+    bits: Vec<usize>,
+}
+
+impl BloomProbe {
+    pub fn keep(&self, page_id: PageId) -> bool {
+        self.filters.contains_bits(page_id, &self.bits)
+    }
+}
+
+// stable across versions and platforms (the filters are persisted)
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{data::page::{Page, PageDataLayout}, store::bloom_filter::{BloomFilters, BloomHeader}, table::{Column, ColumnType, TableSchema, table::{Cell, Row}}};
+
+    #[test]
+    fn should_contain_the_values_of_the_page() {
+        let schema = TableSchema::new(vec![
+            Column::new(1, "id", ColumnType::Int),
+            Column::new(2, "name", ColumnType::Varchar(20)),
+        ]);
+        let layout = PageDataLayout::new(512).unwrap();
+        let header = BloomHeader::new(2);
+
+        let mut page = Page::new(&layout);
+        page.set_page_id(1);
+        for id in 0..20 {
+            page.insert_record(Row::new(vec![Cell::Int(id), Cell::Varchar(format!("name {}", id))]).serialize()).unwrap();
+        }
+        let deleted = page.insert_record(Row::new(vec![Cell::Int(99), Cell::Varchar("deleted".to_owned())]).serialize()).unwrap();
+        page.delete_record(deleted);
+        let mut garbage = Page::new(&layout);
+        garbage.insert_record(vec![1, 2]).unwrap();
+
+        let mut file = header.serialize();
+        file.extend(header.page_entry(&page, &schema));
+        file.extend(header.page_entry(&Page::new(&layout), &schema));
+        file.extend(header.page_entry(&garbage, &schema));
+
+        let filters = BloomFilters::deserialize(&file).unwrap();
+        assert_eq!(filters.column_id(), 2);
+        assert!((0..20).all(|id| filters.may_contain(1, &Cell::Varchar(format!("name {}", id)))));
+        let false_positives = (20..1020).filter(|id| filters.may_contain(1, &Cell::Varchar(format!("name {}", id)))).count();
+        assert!(false_positives < 50, "{} false positives", false_positives);
+        assert!(!filters.may_contain(2, &Cell::Varchar("name 1".to_owned())), "a page without rows contains nothing");
+        assert!(filters.may_contain(3, &Cell::Varchar("name 1".to_owned())) && filters.may_contain(4, &Cell::Varchar("x".to_owned())), "unknown pages must be read");
+
+        let probe = filters.clone().probe(2, &Cell::Varchar("name 3".to_owned())).unwrap();
+        assert!(probe.keep(1) && !probe.keep(2) && probe.keep(3));
+        assert!(filters.probe(1, &Cell::Int(3)).is_none());
+
+        // the column was dropped
+        let other_schema = TableSchema::new(vec![Column::new(1, "id", ColumnType::Int)]);
+        assert_eq!(header.page_entry(&page, &other_schema)[0], 0);
+    }
+}
@@ -0,0 +1,246 @@
+use crate::{data::page::{Page, PageId}, store::StoreError, table::{ColumnType, TableSchema, table::{Cell, Point, Row}}};
+
+// Bounding boxes: the smallest rectangle around the values of the Point columns per page. The FileStore keeps them
+// in a sidecar file next to the table file ('<table file>.bbox') and updates the entry of a page whenever it writes
+// the page, like the zone maps of the Int columns (see zone_map.rs). This is the leaf level of an R-tree without the
+// inner nodes: TableAccess::within_bbox skips the pages whose box doesn't intersect the searched one, and
+// TableAccess::nearest reads the pages in the order of their distance to the point and stops as soon as the
+// next page is farther away than the k-th nearest value found so far. With one entry per page, the boxes are
+// only small if rows that are close to each other are inserted close to each other.
+//
+// File format (big endian): 4 bytes magic "PDBR", 1 byte version, 1 byte reserved, 2 bytes number of columns,
+// then one entry per page at position page_id - 1: 1 byte state (0 = unknown), min x, min y, max x, max y
+// (f64, 8 bytes each) per column.
+// - tracked are the Point columns that are not encrypted, in schema order
+// - a page without entry or with rows that cannot be read with the schema is unknown and is always read
+// - a page without rows has an empty box (min > max)
+
+const MAGIC: [u8; 4] = *b"PDBR";
+const VERSION: u8 = 1;
+const HEADER_SIZE: usize = 8;
+const STATE_KNOWN: u8 = 1;
+const BOX_SIZE: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+impl BoundingBox {
+    /// The box with the two corners (in any order)
+    pub fn new(a: Point, b: Point) -> Self {
+        Self {
+            min_x: a.x().min(b.x()),
+            min_y: a.y().min(b.y()),
+            max_x: a.x().max(b.x()),
+            max_y: a.y().max(b.y()),
+        }
+    }
+
+    /// The box of a page without rows, it contains nothing
+    pub fn empty() -> Self {
+        Self { min_x: f64::INFINITY, min_y: f64::INFINITY, max_x: f64::NEG_INFINITY, max_y: f64::NEG_INFINITY }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min_x > self.max_x || self.min_y > self.max_y
+    }
+
+    /// The border belongs to the box
+    pub fn contains(&self, point: &Point) -> bool {
+        self.min_x <= point.x() && point.x() <= self.max_x && self.min_y <= point.y() && point.y() <= self.max_y
+    }
+
+    pub fn intersects(&self, other: &BoundingBox) -> bool {
+        self.min_x <= other.max_x && other.min_x <= self.max_x && self.min_y <= other.max_y && other.min_y <= self.max_y
+    }
+
+    fn extend(&mut self, point: &Point) {
+        self.min_x = self.min_x.min(point.x());
+        self.min_y = self.min_y.min(point.y());
+        self.max_x = self.max_x.max(point.x());
+        self.max_y = self.max_y.max(point.y());
+    }
+
+    /// Squared distance of the nearest point of the box to the point (0 inside), infinite for an empty box
+    pub fn distance_squared(&self, point: &Point) -> f64 {
+        if self.is_empty() {
+            return f64::INFINITY;
+        }
+        let dx = (self.min_x - point.x()).max(0.0).max(point.x() - self.max_x);
+        let dy = (self.min_y - point.y()).max(0.0).max(point.y() - self.max_y);
+        dx * dx + dy * dy
+    }
+}
+
+/// Schema indexes of the columns that have bounding boxes
+pub fn tracked_columns(schema: &TableSchema) -> Vec<usize> {
+    schema.columns.iter()
+        .enumerate()
+        .filter(|(_, column)| column.col_type == ColumnType::Point && !column.encrypted)
+        .map(|(index, _)| index)
+        .collect()
+}
+
+pub(crate) fn header(columns: usize) -> Vec<u8> {
+    let mut buf = MAGIC.to_vec();
+    buf.push(VERSION);
+    buf.push(0);
+    buf.extend_from_slice(&(columns as u16).to_be_bytes());
+    buf
+}
+
+pub(crate) fn entry_size(columns: usize) -> usize {
+    1 + columns * BOX_SIZE
+}
+
+/// Position of the entry of the page in the sidecar file
+pub(crate) fn entry_offset(page_id: PageId, columns: usize) -> u64 {
+    HEADER_SIZE as u64 + page_id.saturating_sub(1) * entry_size(columns) as u64
+}
+
+/// The entry of the page, unknown if a row cannot be deserialized
+pub(crate) fn page_entry(page: &Page, schema: &TableSchema, columns: &[usize]) -> Vec<u8> {
+    let mut boxes = vec![BoundingBox::empty(); columns.len()];
+    for record in page.clone().record_iterator() {
+        let Ok(row) = Row::deserialize(record.data(), schema) else {
+            return vec![0; entry_size(columns.len())];
+        };
+        for (bbox, col_index) in boxes.iter_mut().zip(columns) {
+            if let Some(Cell::Point(point)) = row.cells().get(*col_index) {
+                bbox.extend(point);
+            }
+        }
+    }
+
+    let mut buf = vec![STATE_KNOWN];
+    for bbox in boxes {
+        for value in [bbox.min_x, bbox.min_y, bbox.max_x, bbox.max_y] {
+            buf.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+    buf
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoundingBoxes {
+    columns: Vec<usize>,
+    // index page_id - 1: the box per tracked column, None if unknown
+    pages: Vec<Option<Vec<BoundingBox>>>,
+}
+
+impl BoundingBoxes {
+    /// Reads the sidecar file. None, if it was written for other columns than the schema has.
+    pub(crate) fn deserialize(buf: &[u8], schema: &TableSchema) -> Result<Option<Self>, StoreError> {
+        if buf.len() < HEADER_SIZE || buf[0..4] != MAGIC {
+            return Err(StoreError::UnknownFormat("not a playdb bounding box file".to_owned()));
+        }
+        if buf[4] != VERSION {
+            return Err(StoreError::UnknownFormat(format!("bounding box version {} is not supported (expected {})", buf[4], VERSION)));
+        }
+        let columns = tracked_columns(schema);
+        if u16::from_be_bytes([buf[6], buf[7]]) as usize != columns.len() {
+            return Ok(None);
+        }
+
+        let coordinate = |b: &[u8]| f64::from_be_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]);
+        // a torn entry at the end is ignored, the page is unknown
+        let pages = buf[HEADER_SIZE..].chunks_exact(entry_size(columns.len()))
+            .map(|entry| {
+                (entry[0] == STATE_KNOWN).then(|| entry[1..].chunks_exact(BOX_SIZE)
+                    .map(|b| BoundingBox {
+                        min_x: coordinate(&b[0..8]),
+                        min_y: coordinate(&b[8..16]),
+                        max_x: coordinate(&b[16..24]),
+                        max_y: coordinate(&b[24..32]),
+                    })
+                    .collect())
+            })
+            .collect();
+
+        Ok(Some(Self { columns, pages }))
+    }
+
+    /// The box of the column on the page, None if unknown (or the column has no bounding boxes)
+    pub fn bbox(&self, page_id: PageId, col_index: usize) -> Option<BoundingBox> {
+        let position = self.columns.iter().position(|c| *c == col_index)?;
+        let boxes = self.pages.get(usize::try_from(page_id.checked_sub(1)?).ok()?)?.as_ref()?;
+        boxes.get(position).copied()
+    }
+
+    /// Squared distance of the page to the point: a lower bound for the distance of its values,
+    /// 0 if the page is unknown (it must be read), infinite if it has no rows
+    pub fn distance_squared(&self, page_id: PageId, col_index: usize, point: &Point) -> f64 {
+        self.bbox(page_id, col_index).map_or(0.0, |bbox| bbox.distance_squared(point))
+    }
+
+    /// Filter for a PageIterator that skips the pages outside of the box, None if the column has no bounding boxes
+    pub fn filter(self, col_index: usize, bbox: BoundingBox) -> Option<BoundingBoxFilter> {
+        self.columns.contains(&col_index).then_some(BoundingBoxFilter { boxes: self, col_index, bbox })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BoundingBoxFilter {
+    boxes: BoundingBoxes,
+    col_index: usize,
+    bbox: BoundingBox,
+}
+
+impl BoundingBoxFilter {
+    pub fn keep(&self, page_id: PageId) -> bool {
+        self.boxes.bbox(page_id, self.col_index)
+            .is_none_or(|page_box| page_box.intersects(&self.bbox))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{data::page::{Page, PageDataLayout}, store::bounding_box::{BoundingBox, BoundingBoxes, header, page_entry, tracked_columns}, table::{Column, ColumnType, TableSchema, table::{Cell, Point, Row}}};
+
+    #[test]
+    fn should_keep_the_box_around_the_points_of_a_page() {
+        let schema = TableSchema::new(vec![
+            Column::new(1, "id", ColumnType::Int),
+            Column::new(2, "location", ColumnType::Point),
+        ]);
+        let layout = PageDataLayout::new(256).unwrap();
+        let columns = tracked_columns(&schema);
+        assert_eq!(columns, vec![1]);
+
+        let mut page = Page::new(&layout);
+        page.set_page_id(1);
+        for (id, x, y) in [(1, 1.0, 5.0), (2, -2.0, 3.0), (3, 4.0, -1.5)] {
+            page.insert_record(Row::new(vec![Cell::Int(id), Cell::Point(Point(x, y))]).serialize()).unwrap();
+        }
+        let deleted = page.insert_record(Row::new(vec![Cell::Int(4), Cell::Point(Point(100.0, 100.0))]).serialize()).unwrap();
+        page.delete_record(deleted);
+
+        let mut file = header(columns.len());
+        file.extend(page_entry(&page, &schema, &columns));
+        file.extend(page_entry(&Page::new(&layout), &schema, &columns));
+        let mut garbage = Page::new(&layout);
+        garbage.insert_record(vec![1, 2]).unwrap();
+        file.extend(page_entry(&garbage, &schema, &columns));
+
+        let boxes = BoundingBoxes::deserialize(&file, &schema).unwrap().unwrap();
+        assert_eq!(boxes.bbox(1, 1), Some(BoundingBox::new(Point(-2.0, -1.5), Point(4.0, 5.0))));
+        assert_eq!(boxes.bbox(1, 0), None);
+        assert!(boxes.bbox(2, 1).unwrap().is_empty());
+        assert_eq!((boxes.bbox(3, 1), boxes.bbox(4, 1)), (None, None));
+
+        assert_eq!(boxes.distance_squared(1, 1, &Point(0.0, 0.0)), 0.0);
+        assert_eq!(boxes.distance_squared(1, 1, &Point(7.0, 9.0)), 9.0 + 16.0);
+        assert_eq!(boxes.distance_squared(2, 1, &Point(0.0, 0.0)), f64::INFINITY);
+        assert_eq!(boxes.distance_squared(3, 1, &Point(50.0, 50.0)), 0.0, "unknown pages must be read");
+
+        let filter = boxes.clone().filter(1, BoundingBox::new(Point(4.0, 5.0), Point(10.0, 10.0))).unwrap();
+        assert!(filter.keep(1) && !filter.keep(2) && filter.keep(3));
+        let outside = boxes.clone().filter(1, BoundingBox::new(Point(4.5, 0.0), Point(10.0, 10.0))).unwrap();
+        assert!(!outside.keep(1));
+        assert!(boxes.filter(0, BoundingBox::empty()).is_none());
+    }
+}
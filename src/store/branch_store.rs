@@ -0,0 +1,218 @@
+use std::{cell::RefCell, collections::{HashMap, HashSet, hash_map::Entry}, fs::OpenOptions, io::{Read, Write}, path::{Path, PathBuf}};
+
+use crate::{data::page::{Page, PageDataLayout, PageFileMetadata, PageId}, store::{IoStats, Quota, Store, StoreError, file_store::FileStore, free_space::FreeSpaceMap}, table::table::Table, tree::store::BTreeStore};
+
+// Copy-on-write branch of a database (see Database::branch). The branch directory only contains what the branch
+// has changed, all other pages are read from the directory of the parent (which is opened read-only):
+// - a table file of the branch has the metadata file of the parent (copied on the first write) and the pages that
+//   were written in the branch at their usual position, the file is sparse
+// - '<table file>.pages' is the page mapping: the ids of the pages that are in the branch file (8 bytes each),
+//   a page is read from the branch if its id is in the mapping, otherwise from the parent
+// - '<table file>.dropped' marks a table of the parent that was dropped in the branch
+// - tables created in the branch only exist in the branch directory
+// - index files are copied as a whole on their first access, the B-tree has no page mapping
+//
+// The parent pages are not protected: writes to the parent after the branch was created show through the pages
+// the branch hasn't changed. Branch from a database that is not written anymore (e.g. a restored snapshot).
+
+pub struct BranchStore {
+    parent: FileStore,
+    own: FileStore,
+    // table file => page mapping, loaded on the first access of the table
+    own_pages: RefCell<HashMap<String, HashSet<PageId>>>,
+}
+
+impl BranchStore {
+    pub fn new(parent_path: &Path, branch_path: &Path) -> Self {
+        Self {
+            parent: FileStore::new_read_only(parent_path),
+            own: FileStore::new(branch_path),
+            own_pages: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn parent_path(&self) -> &Path {
+        self.parent.base_path()
+    }
+
+    pub fn base_path(&self) -> &Path {
+        self.own.base_path()
+    }
+
+    fn mapping_path(&self, table: &Table) -> PathBuf {
+        self.own.base_path().join(format!("{}.pages", table.file_path()))
+    }
+
+    fn dropped_path(&self, table: &Table) -> PathBuf {
+        self.own.base_path().join(format!("{}.dropped", table.file_path()))
+    }
+
+    fn has_own_file(&self, table: &Table) -> bool {
+        self.own.file_path(table).exists()
+    }
+
+    fn check_not_dropped(&self, table: &Table) -> Result<(), StoreError> {
+        match self.dropped_path(table).exists() {
+            true => Err(StoreError::IoError(format!("Data structure '{}' was dropped in the branch", table.file_path()))),
+            false => Ok(()),
+        }
+    }
+
+    fn with_mapping<R, F: FnOnce(&mut HashSet<PageId>) -> R>(&self, table: &Table, f: F) -> Result<R, StoreError> {
+        let mut own_pages = self.own_pages.borrow_mut();
+        let page_ids = match own_pages.entry(table.file_path()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let mut page_ids = HashSet::new();
+                let path = self.mapping_path(table);
+                if path.exists() {
+                    let mut bytes = Vec::new();
+                    std::fs::File::open(path)?.read_to_end(&mut bytes)?;
+                    page_ids.extend(bytes.chunks_exact(8).filter_map(|id| id.try_into().ok()).map(PageId::from_be_bytes));
+                }
+                entry.insert(page_ids)
+            }
+        };
+        Ok(f(page_ids))
+    }
+
+    fn is_own_page(&self, table: &Table, page_id: PageId) -> Result<bool, StoreError> {
+        self.with_mapping(table, |page_ids| page_ids.contains(&page_id))
+    }
+
+    // after the page was written, so the mapping never points to a page that isn't in the branch file
+    fn add_own_page(&self, table: &Table, page_id: PageId) -> Result<(), StoreError> {
+        if self.with_mapping(table, |page_ids| page_ids.insert(page_id))? {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(self.mapping_path(table))?
+                .write_all(&page_id.to_be_bytes())?;
+        }
+        Ok(())
+    }
+
+    // the first write of a parent table copies the metadata file, so allocated pages continue after the pages of
+    // the parent (the table file is created last, it marks the table as copied)
+    fn ensure_own_file(&self, table: &Table) -> Result<(), StoreError> {
+        self.check_not_dropped(table)?;
+        if self.has_own_file(table) {
+            return Ok(());
+        }
+        std::fs::copy(self.parent.meta_path(table), self.own.meta_path(table))?;
+        std::fs::File::create(self.own.file_path(table))?;
+        Ok(())
+    }
+
+    /// Number of pages of the table in the branch file (written or allocated in the branch)
+    pub fn own_pages(&self, table: &Table) -> Result<usize, StoreError> {
+        self.with_mapping(table, |page_ids| page_ids.len())
+    }
+}
+
+impl Store for BranchStore {
+    fn read_btree(&self, btree_id: i32) -> Result<BTreeStore, StoreError> {
+        let own_path = self.own.btree_path(btree_id);
+        let parent_path = self.parent.btree_path(btree_id);
+        if !own_path.exists() && parent_path.exists() {
+            std::fs::copy(parent_path, own_path)?;
+        }
+        self.own.read_btree(btree_id)
+    }
+
+    fn delete_all(&self) -> Result<(), StoreError> {
+        self.own.delete_all()
+    }
+
+    fn create(&self, layout: &PageDataLayout, table: &Table) -> Result<(), StoreError> {
+        if self.parent.file_path(table).exists() && !self.dropped_path(table).exists() {
+            return Err(StoreError::IoError(format!("Data structure '{}' already exists", table.file_path())));
+        }
+        self.own.create(layout, table)
+    }
+
+    fn delete(&self, table: &Table) -> Result<(), StoreError> {
+        self.check_not_dropped(table)?;
+        if self.has_own_file(table) {
+            self.own.delete(table)?;
+        }
+        if self.mapping_path(table).exists() {
+            std::fs::remove_file(self.mapping_path(table))?;
+        }
+        self.own_pages.borrow_mut().remove(&table.file_path());
+        if self.parent.file_path(table).exists() {
+            std::fs::File::create(self.dropped_path(table))?;
+        }
+        Ok(())
+    }
+
+    fn read_metadata(&self, layout: &PageDataLayout, table: &Table) -> Result<PageFileMetadata, StoreError> {
+        self.check_not_dropped(table)?;
+        match self.has_own_file(table) {
+            true => self.own.read_metadata(layout, table),
+            false => self.parent.read_metadata(layout, table),
+        }
+    }
+
+    fn read_page_size(&self, table: &Table) -> Result<usize, StoreError> {
+        self.check_not_dropped(table)?;
+        match self.has_own_file(table) {
+            true => self.own.read_page_size(table),
+            false => self.parent.read_page_size(table),
+        }
+    }
+
+    fn read_page(&self, layout: &PageDataLayout, page_id: PageId, table: &Table) -> Result<Page, StoreError> {
+        self.check_not_dropped(table)?;
+        match self.is_own_page(table, page_id)? {
+            true => self.own.read_page(layout, page_id, table),
+            false => self.parent.read_page(layout, page_id, table),
+        }
+    }
+
+    fn write_page(&self, layout: &PageDataLayout, page: &Page, table: &Table) -> Result<(), StoreError> {
+        self.ensure_own_file(table)?;
+        self.own.write_page(layout, page, table)?;
+        self.add_own_page(table, page.page_id())
+    }
+
+    fn allocate_page(&self, layout: &PageDataLayout, table: &Table) -> Result<Page, StoreError> {
+        self.ensure_own_file(table)?;
+        let page = self.own.allocate_page(layout, table)?;
+        self.add_own_page(table, page.page_id())?;
+        Ok(page)
+    }
+
+    fn quota(&self) -> Quota {
+        self.own.quota()
+    }
+
+    fn read_free_space(&self, table: &Table) -> Result<Option<FreeSpaceMap>, StoreError> {
+        self.check_not_dropped(table)?;
+        match self.has_own_file(table) {
+            true => self.own.read_free_space(table),
+            false => self.parent.read_free_space(table),
+        }
+    }
+
+    /// Size of the branch file, the shared pages of the parent are not counted (holes of the sparse file are)
+    fn disk_size(&self, table: &Table) -> Result<u64, StoreError> {
+        match self.has_own_file(table) {
+            true => self.own.disk_size(table),
+            false => Ok(0),
+        }
+    }
+
+    fn btree_disk_size(&self, btree_id: i32) -> Result<u64, StoreError> {
+        self.own.btree_disk_size(btree_id)
+    }
+
+    fn io_stats(&self) -> IoStats {
+        let (own, parent) = (self.own.io_stats(), self.parent.io_stats());
+        IoStats {
+            pages_read: own.pages_read + parent.pages_read,
+            pages_written: own.pages_written,
+            cache_hits: 0,
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use crate::store::StoreError;
+
+// Named points in the FileStore where tests can inject an error or a panic (feature "failpoints").
+// Without the feature every point is a no-op.
+//
+// Failpoints are set per thread, so tests running in parallel don't affect each other.
+// There is no WAL yet, so there is no point after a WAL append.
+pub const BEFORE_METADATA_WRITE: &str = "before-metadata-write";
+// the first half of the page is written before the point fails (a torn page)
+pub const MID_PAGE_WRITE: &str = "mid-page-write";
+
+#[cfg(feature = "failpoints")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FailAction {
+    // the operation returns StoreError::Injected
+    Error,
+    Panic,
+}
+
+#[cfg(feature = "failpoints")]
+thread_local! {
+    static FAILPOINTS: std::cell::RefCell<std::collections::HashMap<String, FailAction>> = Default::default();
+}
+
+/// Activates the failpoint until the guard is dropped (or remove/clear is called)
+#[cfg(feature = "failpoints")]
+pub fn set(name: &str, action: FailAction) -> FailGuard {
+    FAILPOINTS.with(|points| points.borrow_mut().insert(name.to_owned(), action));
+    FailGuard { name: name.to_owned() }
+}
+
+#[cfg(feature = "failpoints")]
+pub fn remove(name: &str) {
+    FAILPOINTS.with(|points| points.borrow_mut().remove(name));
+}
+
+#[cfg(feature = "failpoints")]
+pub fn clear() {
+    FAILPOINTS.with(|points| points.borrow_mut().clear());
+}
+
+#[cfg(feature = "failpoints")]
+pub struct FailGuard {
+    name: String,
+}
+
+#[cfg(feature = "failpoints")]
+impl Drop for FailGuard {
+    fn drop(&mut self) {
+        remove(&self.name);
+    }
+}
+
+#[cfg(feature = "failpoints")]
+pub(crate) fn is_set(name: &str) -> bool {
+    FAILPOINTS.with(|points| points.borrow().contains_key(name))
+}
+
+#[cfg(feature = "failpoints")]
+pub(crate) fn eval(name: &str) -> Result<(), StoreError> {
+    match FAILPOINTS.with(|points| points.borrow().get(name).copied()) {
+        Some(FailAction::Error) => Err(StoreError::Injected(name.to_owned())),
+        Some(FailAction::Panic) => panic!("Failpoint '{}' triggered", name),
+        None => Ok(()),
+    }
+}
+
+#[cfg(not(feature = "failpoints"))]
+pub(crate) fn is_set(_name: &str) -> bool {
+    false
+}
+
+#[cfg(not(feature = "failpoints"))]
+pub(crate) fn eval(_name: &str) -> Result<(), StoreError> {
+    Ok(())
+}
+
+#[cfg(all(test, feature = "failpoints"))]
+mod tests {
+    use crate::{database::Database, store::{StoreError, failpoints::{self, BEFORE_METADATA_WRITE, FailAction, MID_PAGE_WRITE}, file_store::FileStore}, table::{ColumnType, table::{Cell, Row}}};
+
+    #[test]
+    fn should_inject_errors_at_failpoints() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        let table = db.create_table("t", vec![("id", ColumnType::Int)]).unwrap();
+        let access = db.table_access(table).unwrap();
+
+        {
+            let _guard = failpoints::set(BEFORE_METADATA_WRITE, FailAction::Error);
+            // the first insert allocates a page and writes the metadata
+            let err = access.insert(&Row::new(vec![Cell::Int(1)])).unwrap_err();
+            assert!(err.to_string().contains(BEFORE_METADATA_WRITE), "{}", err);
+        }
+        access.insert(&Row::new(vec![Cell::Int(1)])).unwrap();
+
+        {
+            let _guard = failpoints::set(MID_PAGE_WRITE, FailAction::Error);
+            assert!(access.insert(&Row::new(vec![Cell::Int(2)])).is_err());
+        }
+        assert!(matches!(failpoints::eval(MID_PAGE_WRITE), Ok(())));
+
+        let _guard = failpoints::set(MID_PAGE_WRITE, FailAction::Error);
+        assert!(matches!(failpoints::eval(MID_PAGE_WRITE), Err(StoreError::Injected(name)) if name == MID_PAGE_WRITE));
+        failpoints::clear();
+        assert!(!failpoints::is_set(MID_PAGE_WRITE));
+    }
+
+    #[test]
+    #[should_panic(expected = "Failpoint 'before-metadata-write' triggered")]
+    fn should_panic_at_failpoint() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", FileStore::new(base_path.path()));
+        db.drop_create().unwrap();
+        let table = db.create_table("t", vec![("id", ColumnType::Int)]).unwrap();
+
+        let _guard = failpoints::set(BEFORE_METADATA_WRITE, FailAction::Panic);
+        let _ = db.table_access(table).unwrap().insert(&Row::new(vec![Cell::Int(1)]));
+    }
+}
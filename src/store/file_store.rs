@@ -1,44 +1,354 @@
-use std::{fs::remove_file, io::{Read, Seek, SeekFrom, Write}, path::{Path, PathBuf}};
+use std::{cell::Cell, collections::HashMap, fs::remove_file, io::{Read, Seek, SeekFrom, Write}, path::{Path, PathBuf}, sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard}};
 
-use crate::{data::page::{Page, PageDataLayout, PageFileMetadata}, store::{Store, StoreError}, table::table::Table, tree::store::BTreeStore};
+use crate::{data::page::{Page, PageDataLayout, PageError, PageFileMetadata, PageId, compress_page, decompress_page}, store::{IoStats, Quota, Store, StoreError, bloom_filter::{self, BloomFilters, BloomHeader}, failpoints, free_space::{self, FreeSpaceMap}, zone_map::{self, ZoneMap}, bounding_box::{self, BoundingBoxes}}, table::table::Table, tree::store::BTreeStore};
 
 // Defines how many keys fit into one node
 const BTREE_MAX_DEGREE: u16 = 500;
+// Pages a table file grows by (see with_extent_size)
+const DEFAULT_EXTENT_SIZE: u16 = 1;
 
 pub struct FileStore {
     base_path: PathBuf,
+    read_only: bool,
+    quota: Quota,
+    extent_size: u16,
+    upgrade_on_open: bool,
+    io_stats: Cell<IoStats>,
+    // shared with the stores of read_only_view: a page is not read while it is written or the file is truncated
+    page_lock: Arc<RwLock<()>>,
 }
 impl FileStore {
+    /// Doesn't check the directory: if it is missing, every operation fails with an IoError (see try_new)
     pub fn new(base_path: &Path) -> Self {
-        if !base_path.is_dir() {
-            // TODO: Use proper error handling
-            panic!("FileStore needs a directory as a base_path");
-        }
         Self { 
             base_path: base_path.to_path_buf(),
+            read_only: false,
+            quota: Quota::default(),
+            extent_size: DEFAULT_EXTENT_SIZE,
+            upgrade_on_open: false,
+            io_stats: Cell::new(IoStats::default()),
+            page_lock: Arc::default(),
          }
     }
 
-    fn file_path(&self, table: &Table) -> PathBuf {
+    pub fn try_new(base_path: &Path) -> Result<Self, StoreError> {
+        if !base_path.is_dir() {
+            return Err(StoreError::IoError(format!("FileStore needs a directory as a base_path: {:?}", base_path)));
+        }
+        Ok(Self::new(base_path))
+    }
+
+    pub fn with_quota(mut self, quota: Quota) -> Self {
+        self.quota = quota;
+        self
+    }
+
+    /// A table file grows by `pages` pages at once instead of one page per allocation (fewer, larger writes and
+    /// less fragmentation of the file on disk). The header records how many pages are preallocated,
+    /// the preallocated pages are zeros and are not in use until allocate_page returns them.
+    pub fn with_extent_size(mut self, pages: u16) -> Self {
+        self.extent_size = pages.max(1);
+        self
+    }
+
+    /// A table file of an older format version is upgraded when its header is read for the first time instead of
+    /// failing with StoreError::UnknownFormat (see format_upgrade.rs). The indexes of the records the upgrade
+    /// moves are updated by Database::table_access and KvStore::open (see Store::moved_records).
+    pub fn with_upgrade_on_open(mut self) -> Self {
+        self.upgrade_on_open = true;
+        self
+    }
+
+    /// All operations that would change files fail with StoreError::ReadOnly
+    pub fn new_read_only(base_path: &Path) -> Self {
+        Self {
+            read_only: true,
+            ..Self::new(base_path)
+        }
+    }
+
+    /// A read-only store on the same directory for another thread (see compaction.rs): its page reads wait for
+    /// the page writes and truncations of this store, so they don't see half written pages
+    pub(crate) fn read_only_view(&self) -> Self {
+        Self {
+            page_lock: self.page_lock.clone(),
+            ..Self::new_read_only(&self.base_path)
+        }
+    }
+
+    // the lock only protects (), so a panic while it was held leaves nothing inconsistent behind
+    fn read_lock(&self) -> RwLockReadGuard<'_, ()> {
+        self.page_lock.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write_lock(&self) -> RwLockWriteGuard<'_, ()> {
+        self.page_lock.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn base_path(&self) -> &Path {
+        &self.base_path
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub fn upgrades_on_open(&self) -> bool {
+        self.upgrade_on_open
+    }
+
+    fn check_writable(&self) -> Result<(), StoreError> {
+        if self.read_only {
+            return Err(StoreError::ReadOnly);
+        }
+        Ok(())
+    }
+
+    // new_pages: the number of pages the file grows by, the size is only checked if it grows
+    fn check_quota(&self, layout: &PageDataLayout, metadata: &PageFileMetadata, table: &Table, new_pages: PageId) -> Result<(), StoreError> {
+        if let Some(max_pages) = self.quota.max_pages_per_table
+            && metadata.number_of_pages() >= max_pages {
+            return Err(StoreError::QuotaExceeded(format!("Table '{}' already has the maximum of {} pages", table.name(), max_pages)));
+        }
+
+        if let Some(max_total_size) = self.quota.max_total_size
+            && new_pages > 0 {
+            let mut total_size = 0;
+            for entry in std::fs::read_dir(&self.base_path)? {
+                total_size += entry?.metadata()?.len();
+            }
+            if total_size + new_pages * layout.page_size() as u64 > max_total_size {
+                return Err(StoreError::QuotaExceeded(format!("New pages would exceed the maximum size of {} bytes (current size: {} bytes)", max_total_size, total_size)));
+            }
+        }
+
+        Ok(())
+    }
+
+    // the size of the next extent for the page, it doesn't exceed the maximum pages of the quota
+    fn extent_pages(&self, page_id: PageId) -> PageId {
+        let extent = self.extent_size as PageId;
+        match self.quota.max_pages_per_table {
+            // the table may have more pages than max_pages, if the quota was lowered (check_quota rejects the page)
+            Some(max_pages) => extent.min(max_pages.saturating_sub(page_id - 1)).max(1),
+            None => extent,
+        }
+    }
+
+    fn count_io(&self, pages_read: usize, pages_written: usize) {
+        let mut stats = self.io_stats.get();
+        stats.pages_read += pages_read as u64;
+        stats.pages_written += pages_written as u64;
+        self.io_stats.set(stats);
+    }
+
+    pub(crate) fn btree_path(&self, btree_id: i32) -> PathBuf {
+        self.base_path.join(format!("btreeindex_{}.dat", btree_id))
+    }
+
+    pub(crate) fn file_path(&self, table: &Table) -> PathBuf {
         self.base_path.join(table.file_path())
     }
 
+    // header and free space map of the table file (see free_space.rs)
+    pub(crate) fn meta_path(&self, table: &Table) -> PathBuf {
+        self.base_path.join(format!("{}.meta", table.file_path()))
+    }
+
+    fn zone_map_path(&self, table: &Table) -> PathBuf {
+        self.base_path.join(format!("{}.zones", table.file_path()))
+    }
+
+    fn bounding_box_path(&self, table: &Table) -> PathBuf {
+        self.base_path.join(format!("{}.bbox", table.file_path()))
+    }
+
+    fn bloom_filter_path(&self, table: &Table) -> PathBuf {
+        self.base_path.join(format!("{}.bloom", table.file_path()))
+    }
+
     fn delete_file(&self, table: &Table) -> Result<(), StoreError> {
         remove_file(self.file_path(&table))
             .map_err(|e| StoreError::IoError(e.to_string()))?;
+        if self.meta_path(table).exists() {
+            remove_file(self.meta_path(table))?;
+        }
+        self.delete_sidecars(table)
+    }
+
+    // the zone map, the bounding boxes and the bloom filters of the table
+    pub(super) fn delete_sidecars(&self, table: &Table) -> Result<(), StoreError> {
+        for path in [self.zone_map_path(table), self.bounding_box_path(table), self.bloom_filter_path(table)] {
+            if path.exists() {
+                remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    // the entries of the pages in the zone map (see zone_map.rs), after the pages were written
+    fn write_zone_map(&self, pages: &[&Page], table: &Table) -> Result<(), StoreError> {
+        let columns = zone_map::tracked_columns(table.schema());
+        if columns.is_empty() {
+            return Ok(());
+        }
+        let path = self.zone_map_path(table);
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        if file.metadata()?.len() == 0 {
+            file.write_all(&zone_map::header(columns.len()))?;
+        }
+        for page in pages {
+            file.seek(SeekFrom::Start(zone_map::entry_offset(page.page_id(), columns.len())))?;
+            file.write_all(&zone_map::page_entry(page, table.schema(), &columns))?;
+        }
+        Ok(())
+    }
+
+    // the entries of the pages in the bounding boxes (see bounding_box.rs), after the pages were written
+    fn write_bounding_boxes(&self, pages: &[&Page], table: &Table) -> Result<(), StoreError> {
+        let columns = bounding_box::tracked_columns(table.schema());
+        if columns.is_empty() {
+            return Ok(());
+        }
+        let path = self.bounding_box_path(table);
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        if file.metadata()?.len() == 0 {
+            file.write_all(&bounding_box::header(columns.len()))?;
+        }
+        for page in pages {
+            file.seek(SeekFrom::Start(bounding_box::entry_offset(page.page_id(), columns.len())))?;
+            file.write_all(&bounding_box::page_entry(page, table.schema(), &columns))?;
+        }
+        Ok(())
+    }
+
+    // the entries of the pages in the free space map (see free_space.rs), after the pages were written
+    fn write_free_space(&self, pages: &[&Page], table: &Table) -> Result<(), StoreError> {
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(self.meta_path(table))?;
+        for page in pages {
+            file.seek(SeekFrom::Start(free_space::entry_offset(page.page_id())))?;
+            file.write_all(&free_space::page_entry(page))?;
+        }
+        Ok(())
+    }
+
+    // the bloom filters of the pages (see bloom_filter.rs), if the table has them
+    fn write_bloom_filter(&self, pages: &[&Page], table: &Table) -> Result<(), StoreError> {
+        let path = self.bloom_filter_path(table);
+        if !path.exists() {
+            return Ok(());
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)?;
+        let mut header = [0u8; bloom_filter::HEADER_SIZE];
+        file.read_exact(&mut header)?;
+        let header = BloomHeader::deserialize(&header)?;
+        for page in pages {
+            file.seek(SeekFrom::Start(header.entry_offset(page.page_id())))?;
+            file.write_all(&header.page_entry(page, table.schema()))?;
+        }
+        Ok(())
+    }
+
+    // the entries of the pages behind the end of a truncated table in the metadata file and the sidecar files
+    fn truncate_sidecars(&self, table: &Table, number_of_pages: PageId) -> Result<(), StoreError> {
+        let next_page = number_of_pages + 1;
+        let mut ends = vec![
+            (self.meta_path(table), free_space::entry_offset(next_page)),
+            (self.zone_map_path(table), zone_map::entry_offset(next_page, zone_map::tracked_columns(table.schema()).len())),
+            (self.bounding_box_path(table), bounding_box::entry_offset(next_page, bounding_box::tracked_columns(table.schema()).len())),
+        ];
+        let bloom_path = self.bloom_filter_path(table);
+        if bloom_path.exists() {
+            let mut header = [0u8; bloom_filter::HEADER_SIZE];
+            std::fs::File::open(&bloom_path)?.read_exact(&mut header)?;
+            ends.push((bloom_path, BloomHeader::deserialize(&header)?.entry_offset(next_page)));
+        }
 
+        for (path, len) in ends {
+            if !path.exists() {
+                continue;
+            }
+            let file = std::fs::OpenOptions::new()
+                .write(true)
+                .open(path)?;
+            if file.metadata()?.len() > len {
+                file.set_len(len)?;
+            }
+        }
         Ok(())
     }
 
     fn init(&self, layout: &PageDataLayout, table: &Table) -> Result<(), StoreError> {
-        let metadata = PageFileMetadata::new();
+        std::fs::File::create(self.meta_path(table))?;
+        let metadata = PageFileMetadata::new(layout);
         self.write_metadata(layout, &metadata, table)
     }
 
+    // the header of the table file, without checking it against a layout
+    fn read_file_header(&self, table: &Table) -> Result<PageFileMetadata, StoreError> {
+        let path: PathBuf = self.meta_path(table);
+        if !path.exists() {
+            if self.file_path(table).exists() {
+                if self.upgrade_before_open(table)? {
+                    return self.read_file_header(table);
+                }
+                // the table files of version 1 to 3 have the header at their start
+                return Err(StoreError::UnknownFormat(format!("table '{}': format version {} is not supported (expected {})",
+                    table.name(), self.format_version(table)?, PageDataLayout::FORMAT_VERSION)));
+            }
+            return Err(StoreError::IoError(format!("No such data structure '{}' found (forget to call create?)", table.file_path())));
+        }
+
+        // allocate_page and truncate rewrite the header under the write lock; the lock is released before an upgrade
+        let buf = {
+            let _lock = self.read_lock();
+            let mut file = std::fs::OpenOptions::new()
+                .read(true)
+                .open(path)?;
+
+            let fmeta = file.metadata()?;
+            if fmeta.len() < PageDataLayout::META_DATA_SIZE as u64 {
+                None
+            } else {
+                let mut buf = vec![0u8; PageDataLayout::META_DATA_SIZE];
+                file.read_exact(&mut buf)?;
+                Some(buf)
+            }
+        };
+        let Some(buf) = buf else {
+            // the header of version 4 is shorter
+            if self.upgrade_before_open(table)? {
+                return self.read_file_header(table);
+            }
+            return Err(StoreError::IoError("Metadata size is smaller than expected".to_string()));
+        };
+
+        match PageFileMetadata::deserialize(&buf) {
+            Err(PageError::UnknownFormat(_)) if self.upgrade_before_open(table)? => self.read_file_header(table),
+            Err(PageError::UnknownFormat(msg)) => Err(StoreError::UnknownFormat(format!("table '{}': {}", table.name(), msg))),
+            result => result.map_err(StoreError::from),
+        }
+    }
+
     fn write_metadata(&self, layout: &PageDataLayout, metadata: &PageFileMetadata, table: &Table) -> Result<(), StoreError> {
+        self.check_writable()?;
+        failpoints::eval(failpoints::BEFORE_METADATA_WRITE)?;
         let mut file = std::fs::OpenOptions::new()
             .write(true)
-            .open(self.file_path(&table))?;
+            .open(self.meta_path(table))?;
 
         file.write_all(&metadata.serialize(layout))?;
 
@@ -47,6 +357,7 @@ impl FileStore {
 }
 impl Store for FileStore {
     fn delete_all(&self) -> Result<(), StoreError> {
+        self.check_writable()?;
         for entry in std::fs::read_dir(&self.base_path)? {
             let entry = entry?;
             let path = entry.path();
@@ -60,89 +371,339 @@ impl Store for FileStore {
     }
 
     fn read_metadata(&self, layout: &PageDataLayout, table: &Table) -> Result<PageFileMetadata, StoreError> {
-        let path: PathBuf = self.file_path(table);
-        if !path.exists() {
-            return Err(StoreError::IoError(format!("No such data structure '{}' found (forget to call create?)", table.file_path())));
-        }
-
-        let mut file = std::fs::OpenOptions::new()
-            .read(true)
-            .open(path)?;
-
-        let fmeta = file.metadata().unwrap();
-        if fmeta.len() < layout.metadata_size() as u64 {
-            return Err(StoreError::IoError("Metadata size is smaller than expected".to_string()));
-        }
-
-        let mut buf = vec![0u8; layout.metadata_size()];
-        file.read_exact(&mut buf)?;
+        let metadata = self.read_file_header(table)?;
+        metadata.check_layout(layout)
+            .map_err(|e| StoreError::LayoutMismatch(format!("table '{}': {}", table.name(), e)))?;
+        Ok(metadata)
+    }
 
-        Ok(PageFileMetadata::deserialize(&buf))
+    fn read_page_size(&self, table: &Table) -> Result<usize, StoreError> {
+        Ok(self.read_file_header(table)?.page_size())
     }
 
-    fn read_page<'database>(&self, layout: &'database PageDataLayout, page_id: i32, table: &Table) -> Result<Page<'database>, StoreError> {
+    fn read_page(&self, layout: &PageDataLayout, page_id: PageId, table: &Table) -> Result<Page, StoreError> {
         let mut page_data = vec![0; layout.page_size()];
+        let _lock = self.read_lock();
 
         let mut file = std::fs::OpenOptions::new()
             .read(true)
             .open(self.base_path.join(table.file_path()))?;
 
-        let page_pos = page_id - 1;
-        file.seek(SeekFrom::Start((layout.metadata_size() + page_pos as usize * layout.page_size()) as u64))?;
+        file.seek(SeekFrom::Start(page_position(layout, page_id)?))?;
     
         file.read_exact(&mut page_data)?;
+        self.count_io(1, 0);
 
-        let p = Page::deserialize(&page_data, layout);
-        Ok(p)
+        read_page_data(&page_data, layout, page_id, table)
     }
 
     fn write_page(&self, layout: &PageDataLayout, page: &Page, table: &Table) -> Result<(), StoreError> {
-        let data = page.serialize();
+        self.check_writable()?;
+        let data = page_data(page, layout);
+        let _lock = self.write_lock();
 
         let mut file = std::fs::OpenOptions::new()
             .write(true)
             .open(self.base_path.join(table.file_path()))?;
-        let page_pos = page.page_id() - 1;
-        file.seek(SeekFrom::Start((layout.metadata_size() + page_pos as usize * layout.page_size()) as u64))?;
+        file.seek(SeekFrom::Start(page_position(layout, page.page_id())?))?;
+        if failpoints::is_set(failpoints::MID_PAGE_WRITE) {
+            file.write_all(&data[..data.len() / 2])?;
+            failpoints::eval(failpoints::MID_PAGE_WRITE)?;
+        }
         file.write_all(&data)?;
-        Ok(())
+        self.count_io(0, 1);
+        self.write_free_space(&[page], table)?;
+        self.write_zone_map(&[page], table)?;
+        self.write_bounding_boxes(&[page], table)?;
+        self.write_bloom_filter(&[page], table)
     }
     
-    fn allocate_page<'database>(&self, layout: &'database PageDataLayout, table: &Table) -> Result<Page<'database>, StoreError> {
+    fn read_pages(&self, layout: &PageDataLayout, page_ids: &[PageId], table: &Table) -> Result<Vec<Page>, StoreError> {
+        let mut sorted: Vec<PageId> = page_ids.to_vec();
+        sorted.sort();
+        sorted.dedup();
+        let _lock = self.read_lock();
+
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .open(self.base_path.join(table.file_path()))?;
+
+        // pages with consecutive ids are read with one seek and one read
+        let mut pages = HashMap::new();
+        let mut run_start = 0;
+        while run_start < sorted.len() {
+            let mut run_end = run_start + 1;
+            while run_end < sorted.len() && sorted[run_end] == sorted[run_end - 1] + 1 {
+                run_end += 1;
+            }
+
+            let mut data = vec![0; (run_end - run_start) * layout.page_size()];
+            file.seek(SeekFrom::Start(page_position(layout, sorted[run_start])?))?;
+            file.read_exact(&mut data)?;
+
+            for (page_id, page_data) in sorted[run_start..run_end].iter().zip(data.chunks_exact(layout.page_size())) {
+                pages.insert(*page_id, page_data.to_vec());
+            }
+
+            run_start = run_end;
+        }
+        self.count_io(sorted.len(), 0);
+
+        page_ids.iter()
+            .map(|page_id| {
+                let data = pages.get(page_id).ok_or_else(|| StoreError::IoError(format!("Page {} was not read", page_id)))?;
+                read_page_data(data, layout, *page_id, table)
+            })
+            .collect()
+    }
+
+    fn write_pages(&self, layout: &PageDataLayout, pages: &[&Page], table: &Table) -> Result<(), StoreError> {
+        self.check_writable()?;
+
+        let mut sorted: Vec<&Page> = pages.to_vec();
+        sorted.sort_by_key(|page| page.page_id());
+        let _lock = self.write_lock();
+
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(self.base_path.join(table.file_path()))?;
+
+        // pages with consecutive ids are written with one seek and one write
+        let mut run_start = 0;
+        while run_start < sorted.len() {
+            let mut run_end = run_start + 1;
+            while run_end < sorted.len() && sorted[run_end].page_id() == sorted[run_end - 1].page_id() + 1 {
+                run_end += 1;
+            }
+
+            let data: Vec<u8> = sorted[run_start..run_end].iter()
+                .flat_map(|page| page_data(page, layout))
+                .collect();
+
+            file.seek(SeekFrom::Start(page_position(layout, sorted[run_start].page_id())?))?;
+            if failpoints::is_set(failpoints::MID_PAGE_WRITE) {
+                file.write_all(&data[..layout.page_size() / 2])?;
+                failpoints::eval(failpoints::MID_PAGE_WRITE)?;
+            }
+            file.write_all(&data)?;
+
+            run_start = run_end;
+        }
+        self.count_io(0, sorted.len());
+
+        self.write_free_space(&sorted, table)?;
+        self.write_zone_map(&sorted, table)?;
+        self.write_bounding_boxes(&sorted, table)?;
+        self.write_bloom_filter(&sorted, table)
+    }
+
+    fn allocate_page(&self, layout: &PageDataLayout, table: &Table) -> Result<Page, StoreError> {
+        self.check_writable()?;
         let mut metadata = self.read_metadata(layout, table)?;
+        // the page is behind the preallocated pages: the file grows by the next extent
+        let page_id = metadata.next_id();
+        let extent = match page_id > metadata.allocated_pages() {
+            true => self.extent_pages(page_id),
+            false => 0,
+        };
+        self.check_quota(layout, &metadata, table, extent)?;
         let mut new_page = Page::new(layout);
         new_page.set_page_id(metadata.allocate_next_page_id());
+        let lock = self.write_lock();
+        if extent > 0 {
+            let allocated_pages = page_id - 1 + extent;
+            let file = std::fs::OpenOptions::new()
+                .write(true)
+                .open(self.file_path(table))?;
+            let len = allocated_pages * layout.page_size() as u64;
+            // never shrinks the file, e.g. if the header was written by a store with another extent size
+            if file.metadata()?.len() < len {
+                file.set_len(len)?;
+            }
+            metadata.set_allocated_pages(allocated_pages);
+        }
         
         // ToDo: here we can get into an inconsistent state if write_page fails after write_metadata succeeded
         self.write_metadata(layout, &metadata, table)?;
+        drop(lock);
         self.write_page(layout, &new_page, table)?;
         Ok(new_page)
     }
     
+    fn truncate(&self, layout: &PageDataLayout, table: &Table, number_of_pages: PageId) -> Result<PageId, StoreError> {
+        self.check_writable()?;
+        let mut metadata = self.read_metadata(layout, table)?;
+        let number_of_pages = number_of_pages.min(metadata.number_of_pages());
+        if metadata.allocated_pages().max(metadata.number_of_pages()) <= number_of_pages {
+            return Ok(0);
+        }
+        let removed = metadata.number_of_pages().saturating_sub(number_of_pages);
+        metadata.truncate(number_of_pages);
+        let _lock = self.write_lock();
+        // the header first: if set_len fails, the pages behind the end are overwritten by the next allocations
+        self.write_metadata(layout, &metadata, table)?;
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(self.file_path(table))?;
+        file.set_len(number_of_pages * layout.page_size() as u64)?;
+        self.truncate_sidecars(table, number_of_pages)?;
+        Ok(removed)
+    }
+
     fn create(&self, layout: &PageDataLayout, table: &Table) -> Result<(), StoreError> {
+        self.check_writable()?;
         if std::fs::exists(self.file_path(&table))? {
             return Err(StoreError::IoError(format!("Data structure '{}' already exists", table.file_path())));
         }
         std::fs::File::create(self.file_path(&table))?;
+        // e.g. left over from a table file that was deleted outside of the store
+        self.delete_sidecars(table)?;
         self.init(layout, table)
     }
     
     fn delete(&self, table: &Table) -> Result<(), StoreError> {
+        self.check_writable()?;
         self.delete_file(table)
     }
     
+    fn quota(&self) -> Quota {
+        self.quota
+    }
+
+    fn io_stats(&self) -> IoStats {
+        self.io_stats.get()
+    }
+
+    fn disk_size(&self, table: &Table) -> Result<u64, StoreError> {
+        let meta_size = match self.meta_path(table).exists() {
+            true => std::fs::metadata(self.meta_path(table))?.len(),
+            false => 0,
+        };
+        Ok(std::fs::metadata(self.file_path(table))?.len() + meta_size)
+    }
+
+    fn btree_disk_size(&self, btree_id: i32) -> Result<u64, StoreError> {
+        Ok(std::fs::metadata(self.btree_path(btree_id))?.len())
+    }
+
+    fn read_zone_map(&self, table: &Table) -> Result<Option<ZoneMap>, StoreError> {
+        let path = self.zone_map_path(table);
+        if zone_map::tracked_columns(table.schema()).is_empty() || !path.exists() {
+            return Ok(None);
+        }
+        ZoneMap::deserialize(&std::fs::read(path)?, table.schema())
+            .map_err(|e| match e {
+                StoreError::UnknownFormat(msg) => StoreError::UnknownFormat(format!("zone map of table '{}': {}", table.name(), msg)),
+                e => e,
+            })
+    }
+
+    fn read_bounding_boxes(&self, table: &Table) -> Result<Option<BoundingBoxes>, StoreError> {
+        let path = self.bounding_box_path(table);
+        if bounding_box::tracked_columns(table.schema()).is_empty() || !path.exists() {
+            return Ok(None);
+        }
+        BoundingBoxes::deserialize(&std::fs::read(path)?, table.schema())
+            .map_err(|e| match e {
+                StoreError::UnknownFormat(msg) => StoreError::UnknownFormat(format!("bounding boxes of table '{}': {}", table.name(), msg)),
+                e => e,
+            })
+    }
+
+    fn moved_records(&self, table: &Table) -> Result<Vec<(PageId, usize)>, StoreError> {
+        FileStore::moved_records(self, table)
+    }
+
+    fn finish_upgrade(&self, table: &Table) -> Result<(), StoreError> {
+        FileStore::finish_upgrade(self, table)
+    }
+
+    fn read_free_space(&self, table: &Table) -> Result<Option<FreeSpaceMap>, StoreError> {
+        let path = self.meta_path(table);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(FreeSpaceMap::deserialize(&std::fs::read(path)?)))
+    }
+
+    fn create_bloom_filter(&self, layout: &PageDataLayout, table: &Table, column_id: i32) -> Result<(), StoreError> {
+        self.check_writable()?;
+        let header = BloomHeader::new(column_id);
+        let mut buf = header.serialize();
+        let pages = self.read_metadata(layout, table)?.number_of_pages();
+        for page_id in 1..=pages {
+            buf.extend(header.page_entry(&self.read_page(layout, page_id, table)?, table.schema()));
+        }
+        // the pages written while the file is built have to update it, so it is written at once
+        std::fs::write(self.bloom_filter_path(table), buf)?;
+        Ok(())
+    }
+
+    fn drop_bloom_filter(&self, table: &Table) -> Result<(), StoreError> {
+        self.check_writable()?;
+        let path = self.bloom_filter_path(table);
+        if path.exists() {
+            remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    fn read_bloom_filter(&self, table: &Table) -> Result<Option<BloomFilters>, StoreError> {
+        let path = self.bloom_filter_path(table);
+        if !path.exists() {
+            return Ok(None);
+        }
+        BloomFilters::deserialize(&std::fs::read(path)?)
+            .map(Some)
+            .map_err(|e| match e {
+                StoreError::UnknownFormat(msg) => StoreError::UnknownFormat(format!("bloom filter of table '{}': {}", table.name(), msg)),
+                e => e,
+            })
+    }
+
     fn read_btree(&self, btree_id: i32) -> Result<BTreeStore, StoreError> {
-        let index_file = format!("btreeindex_{}.dat", btree_id);
-        let full_path = self.base_path.join(index_file);
+        let full_path = self.btree_path(btree_id);
+        if self.read_only {
+            return Ok(BTreeStore::open_read_only(&full_path)?);
+        }
         Ok(BTreeStore::new(&full_path, BTREE_MAX_DEGREE)?)
     }
 }
 
+fn page_data(page: &Page, layout: &PageDataLayout) -> Vec<u8> {
+    if layout.compression() {
+        compress_page(page.serialize())
+    } else {
+        page.serialize()
+    }
+}
+
+// compressed pages can always be read, also with a layout without compression
+// Position of the page in the table file, the pages are numbered from 1
+fn page_position(layout: &PageDataLayout, page_id: PageId) -> Result<u64, StoreError> {
+    page_id.checked_sub(1)
+        .and_then(|pos| pos.checked_mul(layout.page_size() as u64))
+        .ok_or_else(|| StoreError::IoError(format!("Invalid page id {}", page_id)))
+}
+
+fn read_page_data(data: &[u8], layout: &PageDataLayout, page_id: PageId, table: &Table) -> Result<Page, StoreError> {
+    let data = decompress_page(data, layout).map_err(|err| page_error(err, page_id, table))?;
+    Page::deserialize(&data, layout).map_err(|err| page_error(err, page_id, table))
+}
+
+// a checksum mismatch means the file was changed outside of playdb (or by a torn write), so the error names the page
+pub(super) fn page_error(err: PageError, page_id: PageId, table: &Table) -> StoreError {
+    match err {
+        PageError::ChecksumMismatch => StoreError::ChecksumMismatch(format!("page {} of table '{}'", page_id, table.name())),
+        err => err.into(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use tempfile::tempdir;
 
-    use crate::{data::page::PageDataLayout, store::{PageIterator, Store, file_store::FileStore}, table::{Column, ColumnType, TableSchema, table::{Cell, Row, Table}}};
+    use crate::{data::page::{PageDataLayout, PageFileMetadata, PageId}, store::{PageIterator, Quota, ReadConsistency, Store, StoreError, file_store::FileStore, prefetch::PrefetchMode}, table::{Column, ColumnType, TableSchema, table::{Cell, Row, Table}}};
 
     struct Sequence {
             col_id: i32,
@@ -195,7 +756,7 @@ mod tests {
 
         let loaded_page = store.read_page(&layout, 1, &table).unwrap();
 
-        let row = Row::deserialize(loaded_page.row_data(), table.schema());
+        let row = Row::deserialize(loaded_page.row_data(), table.schema()).unwrap();
 
         assert_eq!(row.cells().len(), 1);
         matches!(row.cells().get(0).unwrap(), Cell::Int(42));
@@ -233,7 +794,7 @@ mod tests {
         store.write_page(&layout, &second_page, &table).unwrap();
         let loaded_page = store.read_page(&layout, 2, &table).unwrap();
 
-        let row = Row::deserialize(loaded_page.row_data(), table.schema());
+        let row = Row::deserialize(loaded_page.row_data(), table.schema()).unwrap();
 
         assert_eq!(row.cells().len(), 1);
         matches!(row.cells().get(0).unwrap(), Cell::Int(42));
@@ -272,11 +833,66 @@ mod tests {
     }
 
     #[test]
-    fn should_iterate_over_pages() {
+    fn should_write_multiple_pages_at_once() {
         let dir = tempdir().unwrap();
         let store = FileStore::new(dir.path());
+        let layout = PageDataLayout::new(44).unwrap();
+        let table = Table::new(1, "test".to_owned(), TableSchema::new(vec![
+            Column::new(1, "id", ColumnType::Int)
+        ]));
+        store.create(&layout, &table).unwrap();
 
+        let mut pages = Vec::new();
+        for _ in 0..4 {
+            pages.push(store.allocate_page(&layout, &table).unwrap());
+        }
+        for page in pages.iter_mut() {
+            let row = Row::new(vec![Cell::Int(page.page_id() as i32 * 10)]);
+            page.insert_record(row.serialize()).unwrap();
+        }
+
+        // unordered and with a gap (page 3 is not written)
+        store.write_pages(&layout, &[&pages[3], &pages[0], &pages[1]], &table).unwrap();
+
+        for (page_id, expected_rows) in [(1, 1), (2, 1), (3, 0), (4, 1)] {
+            let page = store.read_page(&layout, page_id, &table).unwrap();
+            let rows: Vec<Row> = page.record_iterator()
+                .map(|r| Row::deserialize(r.data(), table.schema()).unwrap())
+                .collect();
+            assert_eq!(rows.len(), expected_rows);
+            if expected_rows == 1 {
+                assert_eq!(rows[0].cells()[0], Cell::Int(page_id as i32 * 10));
+            }
+        }
+    }
+
+    #[test]
+    fn should_read_multiple_pages_at_once() {
+        let dir = tempdir().unwrap();
+        let store = FileStore::new(dir.path());
         let layout = PageDataLayout::new(32).unwrap();
+        let table = Table::new(1, "test".to_owned(), TableSchema::new(vec![
+            Column::new(1, "id", ColumnType::Int)
+        ]));
+        store.create(&layout, &table).unwrap();
+
+        for _ in 0..5 {
+            store.allocate_page(&layout, &table).unwrap();
+        }
+
+        let pages = store.read_pages(&layout, &[4, 1, 2, 5, 1], &table).unwrap();
+        let ids: Vec<PageId> = pages.iter().map(|p| p.page_id()).collect();
+        assert_eq!(ids, vec![4, 1, 2, 5, 1]);
+
+        assert!(store.read_pages(&layout, &[5, 6], &table).is_err());
+    }
+
+    #[test]
+    fn should_iterate_over_pages() {
+        let dir = tempdir().unwrap();
+        let store = FileStore::new(dir.path());
+
+        let layout = PageDataLayout::new(44).unwrap();
 
         let schema = TableSchema::new(vec![
             Column::new(1, "id", ColumnType::Int)
@@ -295,12 +911,313 @@ mod tests {
         new_page.insert_record(row.serialize()).unwrap();
         store.write_page(&layout, &new_page, &table).unwrap();
 
-        let mut iter = PageIterator::new(&table, &store, &layout);
+        let mut iter = PageIterator::try_new(&table, &store, &layout).unwrap();
 
-        let page = iter.next().unwrap();
+        let page = iter.next().unwrap().unwrap();
 
         assert_eq!(page.page_id(), 1);
         matches!(page.data_offset(), 28);
     }
-}
 
+    #[test]
+    fn page_iterator_should_not_visit_pages_allocated_after_creation() {
+        let dir = tempdir().unwrap();
+        let store = FileStore::new(dir.path());
+        let layout = PageDataLayout::new(32).unwrap();
+        let table = Table::new(1, "test".to_owned(), TableSchema::new(vec![
+            Column::new(1, "id", ColumnType::Int)
+        ]));
+
+        store.create(&layout, &table).unwrap();
+        store.allocate_page(&layout, &table).unwrap();
+
+        let iter = PageIterator::try_new(&table, &store, &layout).unwrap();
+        store.allocate_page(&layout, &table).unwrap();
+
+        let pages = iter.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(pages.len(), 1);
+    }
+
+    #[test]
+    fn page_iterator_should_visit_new_pages_and_not_read_ahead_with_read_uncommitted() {
+        let dir = tempdir().unwrap();
+        let store = FileStore::new(dir.path());
+        let layout = PageDataLayout::new(32).unwrap();
+        let table = Table::new(1, "test".to_owned(), TableSchema::new(vec![
+            Column::new(1, "id", ColumnType::Int)
+        ]));
+
+        store.create(&layout, &table).unwrap();
+        for _ in 0..20 {
+            store.allocate_page(&layout, &table).unwrap();
+        }
+
+        let mut iter = PageIterator::try_new(&table, &store, &layout).unwrap()
+            .with_consistency(ReadConsistency::ReadUncommitted);
+        assert!(iter.next().unwrap().is_ok());
+        store.allocate_page(&layout, &table).unwrap();
+
+        let page_ids: Vec<PageId> = iter.by_ref().map(|p| p.unwrap().page_id()).collect();
+        assert_eq!(page_ids, (2..=21).collect::<Vec<PageId>>());
+        assert_eq!(iter.prefetch_stats().pages_prefetched, 0);
+    }
+
+    #[test]
+    fn page_iterator_should_read_ahead_on_sequential_scans() {
+        let dir = tempdir().unwrap();
+        let store = FileStore::new(dir.path());
+        let layout = PageDataLayout::new(32).unwrap();
+        let table = Table::new(1, "test".to_owned(), TableSchema::new(vec![
+            Column::new(1, "id", ColumnType::Int)
+        ]));
+
+        store.create(&layout, &table).unwrap();
+        for _ in 0..50 {
+            store.allocate_page(&layout, &table).unwrap();
+        }
+
+        // a scan that stops early doesn't prefetch
+        let mut iter = PageIterator::try_new(&table, &store, &layout).unwrap();
+        iter.next().unwrap().unwrap();
+        assert_eq!(iter.prefetch_stats().mode, PrefetchMode::None);
+        assert_eq!(iter.prefetch_stats().pages_prefetched, 0);
+
+        let mut iter = PageIterator::try_new(&table, &store, &layout).unwrap();
+        let page_ids: Vec<PageId> = iter.by_ref().map(|p| p.unwrap().page_id()).collect();
+        assert_eq!(page_ids, (1..=50).collect::<Vec<PageId>>());
+        assert_eq!(iter.prefetch_stats().mode, PrefetchMode::Readahead);
+        assert_eq!(iter.prefetch_stats().pages_read, 50);
+        assert_eq!(iter.prefetch_stats().pages_prefetched, 44);
+    }
+
+    #[test]
+    fn page_iterator_should_stop_if_table_has_shrunk() {
+        let dir = tempdir().unwrap();
+        let store = FileStore::new(dir.path());
+        let layout = PageDataLayout::new(32).unwrap();
+        let table = Table::new(1, "test".to_owned(), TableSchema::new(vec![
+            Column::new(1, "id", ColumnType::Int)
+        ]));
+
+        store.create(&layout, &table).unwrap();
+        store.allocate_page(&layout, &table).unwrap();
+        store.allocate_page(&layout, &table).unwrap();
+
+        let mut iter = PageIterator::try_new(&table, &store, &layout).unwrap();
+        assert_eq!(iter.next().unwrap().unwrap().page_id(), 1);
+
+        // shrink the table to one page
+        // next_id: 3, number_of_pages: 1
+        let mut buf = store.read_metadata(&layout, &table).unwrap().serialize(&layout);
+        buf[8..16].copy_from_slice(&3u64.to_be_bytes());
+        buf[16..24].copy_from_slice(&1u64.to_be_bytes());
+        let metadata = PageFileMetadata::deserialize(&buf).unwrap();
+        store.write_metadata(&layout, &metadata, &table).unwrap();
+        let file = std::fs::OpenOptions::new().write(true).open(store.file_path(&table)).unwrap();
+        file.set_len(layout.page_size() as u64).unwrap();
+
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn page_iterator_should_fail_if_table_does_not_exist() {
+        let dir = tempdir().unwrap();
+        let store = FileStore::new(dir.path());
+        let layout = PageDataLayout::new(32).unwrap();
+        let table = Table::new(1, "test".to_owned(), TableSchema::new(vec![
+            Column::new(1, "id", ColumnType::Int)
+        ]));
+
+        assert!(PageIterator::try_new(&table, &store, &layout).is_err());
+    }
+
+    #[test]
+    fn should_return_errors_for_missing_directories_and_corrupted_files() {
+        let dir = tempdir().unwrap();
+        let missing = dir.path().join("missing");
+        assert!(FileStore::try_new(&missing).is_err());
+
+        let layout = PageDataLayout::new(128).unwrap();
+        let table = Table::new(1, "t".to_owned(), TableSchema::new(vec![Column::new(1, "id", ColumnType::Int)]));
+        assert!(FileStore::new(&missing).create(&layout, &table).is_err());
+
+        let store = FileStore::try_new(dir.path()).unwrap();
+        store.create(&layout, &table).unwrap();
+        let page = store.allocate_page(&layout, &table).unwrap();
+        let path = dir.path().join(table.file_path());
+        let mut content = std::fs::read(&path).unwrap();
+        // page header with an invalid offset of the slots
+        content[0..16].fill(0xFF);
+        std::fs::write(&path, &content).unwrap();
+
+        assert!(store.read_page(&layout, page.page_id(), &table).is_err());
+        assert!(PageIterator::try_new(&table, &store, &layout).unwrap().any(|page| page.is_err()));
+    }
+
+    #[test]
+    fn should_detect_a_changed_byte_in_a_page_file() {
+        let dir = tempdir().unwrap();
+        let store = FileStore::new(dir.path());
+        let layout = PageDataLayout::new(128).unwrap();
+        let table = Table::new(1, "t".to_owned(), TableSchema::new(vec![Column::new(1, "id", ColumnType::Int)]));
+        store.create(&layout, &table).unwrap();
+
+        let mut page = store.allocate_page(&layout, &table).unwrap();
+        page.insert_record(Row::new(vec![Cell::Int(42)]).serialize()).unwrap();
+        store.write_page(&layout, &page, &table).unwrap();
+
+        // flip a bit of the row at the end of the page: without the checksum the page would still be valid
+        let path = dir.path().join(table.file_path());
+        let mut content = std::fs::read(&path).unwrap();
+        let last = layout.page_size() - 1;
+        content[last] ^= 0x01;
+        std::fs::write(&path, &content).unwrap();
+
+        let err = store.read_page(&layout, 1, &table).unwrap_err();
+        assert!(matches!(&err, StoreError::ChecksumMismatch(msg) if msg == "page 1 of table 't'"), "{:?}", err);
+        assert!(matches!(store.read_pages(&layout, &[1], &table), Err(StoreError::ChecksumMismatch(_))));
+    }
+
+    #[test]
+    fn should_not_open_a_table_file_with_another_layout() {
+        let dir = tempdir().unwrap();
+        let store = FileStore::new(dir.path());
+        let layout = PageDataLayout::new(128).unwrap();
+        let table = Table::new(1, "t".to_owned(), TableSchema::new(vec![Column::new(1, "id", ColumnType::Int)]));
+        store.create(&layout, &table).unwrap();
+        store.allocate_page(&layout, &table).unwrap();
+
+        let other = PageDataLayout::new(256).unwrap();
+        let err = store.read_metadata(&other, &table).unwrap_err();
+        assert!(matches!(&err, StoreError::LayoutMismatch(msg) if msg == "table 't': Page layout doesn't match the file: file has page size 128, but layout has 256"), "{:?}", err);
+        assert!(PageIterator::try_new(&table, &store, &other).is_err());
+        assert!(store.allocate_page(&other, &table).is_err());
+
+        assert_eq!(store.read_metadata(&layout, &table).unwrap().number_of_pages(), 1);
+    }
+
+    #[test]
+    fn should_reject_a_file_that_is_not_a_table() {
+        let dir = tempdir().unwrap();
+        let store = FileStore::new(dir.path());
+        let layout = PageDataLayout::new(128).unwrap();
+        let table = Table::new(1, "t".to_owned(), TableSchema::new(vec![Column::new(1, "id", ColumnType::Int)]));
+        std::fs::write(dir.path().join(table.file_path()), vec![0x7F; 256]).unwrap();
+
+        let err = store.read_metadata(&layout, &table).unwrap_err();
+        assert!(matches!(&err, StoreError::UnknownFormat(msg) if msg == "table 't': not a playdb table file"), "{:?}", err);
+        assert!(PageIterator::try_new(&table, &store, &layout).is_err());
+    }
+
+    #[test]
+    fn should_grow_the_file_by_extents() {
+        let dir = tempdir().unwrap();
+        let store = FileStore::new(dir.path())
+            .with_extent_size(4)
+            .with_quota(Quota { max_pages_per_table: Some(6), ..Quota::default() });
+        let layout = PageDataLayout::new(128).unwrap();
+        let table = Table::new(1, "test".to_owned(), TableSchema::new(vec![Column::new(1, "id", ColumnType::Int)]));
+        store.create(&layout, &table).unwrap();
+        let file_size = || std::fs::metadata(store.file_path(&table)).unwrap().len();
+        let pages = |pages: usize| (pages * layout.page_size()) as u64;
+
+        store.allocate_page(&layout, &table).unwrap();
+        assert_eq!(file_size(), pages(4));
+        for _ in 0..3 {
+            store.allocate_page(&layout, &table).unwrap();
+        }
+        let metadata = store.read_metadata(&layout, &table).unwrap();
+        assert_eq!((metadata.number_of_pages(), metadata.allocated_pages()), (4, 4));
+        assert_eq!(file_size(), pages(4));
+
+        // the next extent only has the 2 pages the quota allows
+        let page = store.allocate_page(&layout, &table).unwrap();
+        assert_eq!(page.page_id(), 5);
+        assert_eq!(file_size(), pages(6));
+        assert_eq!(store.read_page(&layout, 5, &table).unwrap().page_id(), 5);
+        store.allocate_page(&layout, &table).unwrap();
+        assert!(matches!(store.allocate_page(&layout, &table), Err(StoreError::QuotaExceeded(_))));
+        assert_eq!(store.read_metadata(&layout, &table).unwrap().allocated_pages(), 6);
+    }
+
+    #[test]
+    fn should_reject_new_pages_if_the_table_exceeds_a_lowered_quota() {
+        let dir = tempdir().unwrap();
+        let store = FileStore::new(dir.path()).with_extent_size(4);
+        let layout = PageDataLayout::new(128).unwrap();
+        let table = Table::new(1, "test".to_owned(), TableSchema::new(vec![Column::new(1, "id", ColumnType::Int)]));
+        store.create(&layout, &table).unwrap();
+        for _ in 0..4 {
+            store.allocate_page(&layout, &table).unwrap();
+        }
+
+        let store = FileStore::new(dir.path())
+            .with_extent_size(4)
+            .with_quota(Quota { max_pages_per_table: Some(2), ..Quota::default() });
+        assert!(matches!(store.allocate_page(&layout, &table), Err(StoreError::QuotaExceeded(_))));
+        assert_eq!(store.read_metadata(&layout, &table).unwrap().number_of_pages(), 4);
+    }
+
+    #[test]
+    fn should_keep_the_header_and_the_free_space_in_the_metadata_file() {
+        let dir = tempdir().unwrap();
+        let store = FileStore::new(dir.path());
+        let layout = PageDataLayout::new(128).unwrap();
+        let table = Table::new(1, "test".to_owned(), TableSchema::new(vec![Column::new(1, "id", ColumnType::Int)]));
+        store.create(&layout, &table).unwrap();
+        assert!(store.read_free_space(&table).unwrap().unwrap().pages_with(1, 0).is_empty());
+
+        let mut full = store.allocate_page(&layout, &table).unwrap();
+        while full.can_insert(&vec![1; 20]) {
+            full.insert_record(vec![1; 20]).unwrap();
+        }
+        let empty = store.allocate_page(&layout, &table).unwrap();
+        store.write_pages(&layout, &[&full, &empty], &table).unwrap();
+
+        // the table file has only pages
+        assert_eq!(std::fs::metadata(store.file_path(&table)).unwrap().len(), 2 * layout.page_size() as u64);
+        let meta = std::fs::read(store.meta_path(&table)).unwrap();
+        assert_eq!(&meta[0..4], b"PDBT");
+        assert_eq!(store.read_page(&layout, 1, &table).unwrap().serialize(), full.serialize());
+
+        let free_space = store.read_free_space(&table).unwrap().unwrap();
+        assert_eq!(free_space.free_space(2), Some(layout.page_data_size()));
+        // the record and its slot
+        assert_eq!(free_space.pages_with(20 + 7, 2), vec![2]);
+
+        store.delete_file(&table).unwrap();
+        assert!(!store.meta_path(&table).exists());
+    }
+
+    #[cfg(feature = "page-compression")]
+    #[test]
+    fn should_write_compressed_pages_and_read_them_with_any_layout() {
+        let dir = tempdir().unwrap();
+        let store = FileStore::new(dir.path());
+        let layout = PageDataLayout::new(512).unwrap().with_compression();
+        let table = Table::new(1, "t".to_owned(), TableSchema::new(vec![Column::new(1, "id", ColumnType::Int)]));
+        store.create(&layout, &table).unwrap();
+
+        let mut page = store.allocate_page(&layout, &table).unwrap();
+        for _ in 0..30 {
+            page.insert_record(Row::new(vec![Cell::Int(42)]).serialize()).unwrap();
+        }
+        store.write_page(&layout, &page, &table).unwrap();
+        let mut second_page = store.allocate_page(&layout, &table).unwrap();
+        second_page.insert_record(Row::new(vec![Cell::Int(7)]).serialize()).unwrap();
+        store.write_pages(&layout, &[&second_page], &table).unwrap();
+
+        // the file keeps its size, but most of the first page is 0
+        let content = std::fs::read(dir.path().join(table.file_path())).unwrap();
+        assert_eq!(content.len(), 2 * layout.page_size());
+        let first_page = &content[..layout.page_size()];
+        assert!(first_page[256..].iter().all(|b| *b == 0));
+
+        let uncompressed_layout = PageDataLayout::new(512).unwrap();
+        for layout in [&layout, &uncompressed_layout] {
+            assert_eq!(store.read_page(layout, 1, &table).unwrap().num_rows(), 30);
+            let pages = store.read_pages(layout, &[1, 2], &table).unwrap();
+            assert_eq!(pages.iter().map(|p| p.num_rows()).collect::<Vec<u16>>(), vec![30, 1]);
+        }
+    }
+}
@@ -0,0 +1,515 @@
+use std::{fs::OpenOptions, io::{Read, Write}, path::PathBuf};
+
+use crate::{
+    data::{checksum::Crc32, compression::decompress, page::{Page, PageDataLayout, PageError, PageFileMetadata, PageId}},
+    store::{StoreError, file_store::{FileStore, page_error}, free_space},
+    table::table::Table,
+};
+
+// Rewrites a table file of an older format version into the current one (see Database::upgrade_format).
+//
+// Format versions of table files:
+// 1: file header of 24 bytes, page header of 18 bytes (without LSN)
+// 2: file header of 28 bytes (+ allocated_pages), page header of 18 bytes
+// 3: file header of 28 bytes, page header of 26 bytes (+ 8 bytes LSN)
+// 4: the header is in the metadata file with the free space map (see free_space.rs), the table file has only pages
+// 5: page ids of 8 bytes: in the header (40 bytes), the page header (30 bytes) and the pointers of moved rows
+//
+// The page size stays the same, so the page data of every version is smaller than before. A page keeps its id and the
+// slot indexes of its records, except for records that don't fit anymore: they are moved to new pages at the end of
+// the file (their old slot is deleted) and their positions are returned, so the indexes can be updated. Only rows in
+// their home slot are moved, the forwarding pointers and moved rows (see SlotKind) keep their place.
+// The overflow files of the BLOB values are upgraded like table files, but a chunk that doesn't fit is split instead
+// (upgrade_overflow_file): the chains are only linked by page id, there is no index to update.
+//
+// There is no WAL yet. The upgrade is crash safe per table instead:
+// - the new file is written next to the table file ('<table file>.upgrade') and renamed over it when it is complete,
+//   the old file is not changed before, so an interrupted upgrade of a table is just started again
+// - the metadata file is written as '<table file>.meta.upgrade' and renamed before the table file: a metadata file
+//   of the current version next to a '.upgrade' file means the upgrade stopped between the two renames,
+//   the next upgrade only renames the table file
+// - the positions of the moved records are written to '<table file>.moved' before the rename and removed by
+//   finish_upgrade after the indexes were updated, so the index updates are repeated after a crash
+// The zone map, the bounding boxes and the bloom filters of the table are deleted, the zone map and the bounding boxes are written
+// again with the pages. The pages are written uncompressed, they are compressed again when they are written the next time
+// (if the table has compression).
+
+const V1_META_DATA_SIZE: usize = 24;
+const V2_META_DATA_SIZE: usize = 28;
+const V1_PAGE_HEADER_SIZE: usize = 18;
+const V3_PAGE_HEADER_SIZE: usize = 26;
+const OLD_INDEX_CHECKSUM: usize = 14;
+const OLD_INDEX_LSN: usize = 18;
+const INDEX_FLAGS: usize = 10;
+const FLAG_COMPRESSED: u8 = 0x01;
+const SLOT_SIZE: usize = 7;
+// flag byte of a slot: deleted, forwarding pointer, moved row (see SlotKind)
+const SLOT_DELETED: u8 = 0x01;
+const SLOT_MOVED: u8 = 0x02 | 0x04;
+// entry of the '.moved' file: 8 bytes page id, 4 bytes slot
+const MOVED_ENTRY_SIZE: usize = 12;
+// a chunk of a BLOB starts with the id of the next page of its chain (see database/blob.rs)
+const CHUNK_NEXT_PAGE_SIZE: usize = 4;
+
+/// Result of the upgrade of a table file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FormatUpgrade {
+    pub from_version: u8,
+    pub pages: PageId,
+    /// records that didn't fit into their page and were moved to a new page at the end of the file
+    pub moved_records: usize,
+}
+
+// header of a file of version 1 to 4 (the fields that are needed for the upgrade)
+struct OldHeader {
+    version: u8,
+    next_id: PageId,
+    number_of_pages: PageId,
+    page_size: usize,
+    format_flags: u8,
+}
+
+impl OldHeader {
+    // position of the first page in the table file
+    fn pages_start(&self) -> usize {
+        match self.version {
+            1 => V1_META_DATA_SIZE,
+            2 | 3 => V2_META_DATA_SIZE,
+            _ => 0,
+        }
+    }
+
+    fn page_header_size(&self) -> usize {
+        match self.version {
+            1 | 2 => V1_PAGE_HEADER_SIZE,
+            _ => V3_PAGE_HEADER_SIZE,
+        }
+    }
+}
+
+// a page of version 1 to 4: the LSN (0 before version 3) and the records by slot index (None for deleted slots)
+// with the flag byte of their slot
+struct OldPage {
+    lsn: u64,
+    records: Vec<Option<(u8, Vec<u8>)>>,
+}
+
+impl FileStore {
+    fn upgrade_path(&self, table: &Table) -> PathBuf {
+        self.base_path().join(format!("{}.upgrade", table.file_path()))
+    }
+
+    fn meta_upgrade_path(&self, table: &Table) -> PathBuf {
+        self.base_path().join(format!("{}.meta.upgrade", table.file_path()))
+    }
+
+    fn moved_path(&self, table: &Table) -> PathBuf {
+        self.base_path().join(format!("{}.moved", table.file_path()))
+    }
+
+    /// Format version of the table file (see above)
+    pub fn format_version(&self, table: &Table) -> Result<u8, StoreError> {
+        let path = match self.meta_path(table).exists() {
+            true => self.meta_path(table),
+            false => self.file_path(table),
+        };
+        let mut header = [0u8; 5];
+        std::fs::File::open(path)?.read_exact(&mut header)?;
+        if header[0..4] != PageDataLayout::MAGIC {
+            return Err(StoreError::UnknownFormat(format!("table '{}': not a playdb table file", table.name())));
+        }
+        Ok(header[4])
+    }
+
+    /// Rewrites the table file into the current format. None, if it already has the current format.
+    /// The indexes must be updated for the moved records (moved_records), then finish_upgrade is called.
+    pub fn upgrade_table_file(&self, table: &Table) -> Result<Option<FormatUpgrade>, StoreError> {
+        self.upgrade_file(table, false)
+    }
+
+    /// Rewrites the overflow file of the BLOB values of a table (Table::overflow_table) into the current format,
+    /// the chunks that don't fit into their page anymore are split. No records are moved, finish_upgrade can be called right away.
+    pub fn upgrade_overflow_file(&self, overflow: &Table) -> Result<Option<FormatUpgrade>, StoreError> {
+        self.upgrade_file(overflow, true)
+    }
+
+    // with_upgrade_on_open: upgrades a table file of an older version when its header cannot be read,
+    // false if the store doesn't upgrade it (then the header stays unreadable). The overflow file of a table is
+    // upgraded with it, because the BLOB chains are read without its header. The overflow files are finished right
+    // away, the other tables when their moved records are indexed.
+    pub(super) fn upgrade_before_open(&self, table: &Table) -> Result<bool, StoreError> {
+        if !self.upgrades_on_open() || self.is_read_only() || !self.file_path(table).exists() {
+            return Ok(false);
+        }
+        if !(1..PageDataLayout::FORMAT_VERSION).contains(&self.format_version(table)?) {
+            return Ok(false);
+        }
+        let overflow = table.is_overflow_table();
+        self.upgrade_file(table, overflow)?;
+        if overflow {
+            self.finish_upgrade(table)?;
+        } else if table.has_blobs() {
+            self.upgrade_before_open(&table.overflow_table())?;
+        }
+        Ok(true)
+    }
+
+    fn upgrade_file(&self, table: &Table, split_chunks: bool) -> Result<Option<FormatUpgrade>, StoreError> {
+        if self.is_read_only() {
+            return Err(StoreError::ReadOnly);
+        }
+        let version = self.format_version(table)?;
+        if version == PageDataLayout::FORMAT_VERSION {
+            if self.upgrade_path(table).exists() {
+                std::fs::rename(self.upgrade_path(table), self.file_path(table))?;
+            }
+            return Ok(None);
+        }
+        if !(1..PageDataLayout::FORMAT_VERSION).contains(&version) {
+            return Err(StoreError::UnknownFormat(format!("table '{}': format version {} cannot be upgraded", table.name(), version)));
+        }
+
+        let old_file = std::fs::read(self.file_path(table))?;
+        let header = match version {
+            4 => read_old_header(&std::fs::read(self.meta_path(table))?, version)?,
+            _ => read_old_header(&old_file, version)?,
+        };
+        let layout = u16::try_from(header.page_size).ok()
+            .and_then(|page_size| PageDataLayout::new(page_size).ok())
+            .ok_or_else(|| StoreError::LayoutMismatch(format!("table '{}' has an invalid page size {}", table.name(), header.page_size)))?;
+
+        let mut pages = Vec::with_capacity(header.number_of_pages as usize);
+        let mut moved = Vec::new();
+        // the second parts of split chunks, their pages follow the old pages
+        let mut split = Vec::new();
+        for page_id in 1..=header.number_of_pages {
+            let start = header.pages_start() + (page_id - 1) as usize * header.page_size;
+            let buf = old_file.get(start..start + header.page_size)
+                .ok_or_else(|| StoreError::IoError(format!("table '{}': page {} is missing", table.name(), page_id)))?;
+            let mut old_page = read_old_page(buf, header.page_size, header.page_header_size()).map_err(|e| page_error(e, page_id, table))?;
+            if split_chunks {
+                let new_page_id = header.number_of_pages + split.len() as PageId + 1;
+                split.extend(split_chunk(&layout, &mut old_page.records, new_page_id)?);
+            }
+            let (mut page, rest) = fit_records(&layout, page_id, old_page.records).map_err(|e| page_error(e, page_id, table))?;
+            page.set_lsn(old_page.lsn);
+            pages.push(page);
+            moved.extend(rest);
+        }
+
+        for rest in split {
+            pages.push(Page::with_records(&layout, pages.len() as PageId + 1, &[Some(rest.as_slice())])?);
+        }
+
+        // the moved records are packed into new pages
+        let mut moved_positions = Vec::with_capacity(moved.len());
+        let mut next_page: Option<Page> = None;
+        for record in moved {
+            if record.len() > layout.max_record_size() {
+                return Err(StoreError::IoError(format!("table '{}': a record of {} bytes doesn't fit into a page of the new format", table.name(), record.len())));
+            }
+            let page = match next_page.as_mut() {
+                Some(page) if page.can_insert(&record) => page,
+                _ => {
+                    if let Some(full) = next_page.take() {
+                        pages.push(full);
+                    }
+                    let mut page = Page::new(&layout);
+                    page.set_page_id(pages.len() as PageId + 1);
+                    next_page.insert(page)
+                },
+            };
+            let slot_id = page.insert_record(record)?;
+            moved_positions.push((page.page_id(), slot_id));
+        }
+        pages.extend(next_page);
+
+        let number_of_pages = pages.len() as PageId;
+        let next_id = header.next_id.max(number_of_pages + 1);
+        let metadata = PageFileMetadata::upgraded(&layout, next_id, number_of_pages, header.format_flags);
+        let mut new_file = OpenOptions::new().write(true).create(true).truncate(true).open(self.upgrade_path(table))?;
+        let mut meta_file = OpenOptions::new().write(true).create(true).truncate(true).open(self.meta_upgrade_path(table))?;
+        meta_file.write_all(&metadata.serialize(&layout))?;
+        for page in pages.iter() {
+            new_file.write_all(&page.serialize())?;
+            meta_file.write_all(&free_space::page_entry(page))?;
+        }
+        new_file.sync_all()?;
+        meta_file.sync_all()?;
+
+        let mut moved_file = OpenOptions::new().write(true).create(true).truncate(true).open(self.moved_path(table))?;
+        for (page_id, slot_id) in moved_positions.iter() {
+            moved_file.write_all(&page_id.to_be_bytes())?;
+            moved_file.write_all(&(*slot_id as u32).to_be_bytes())?;
+        }
+        moved_file.sync_all()?;
+
+        std::fs::rename(self.meta_upgrade_path(table), self.meta_path(table))?;
+        std::fs::rename(self.upgrade_path(table), self.file_path(table))?;
+        self.delete_sidecars(table)?;
+
+        Ok(Some(FormatUpgrade { from_version: version, pages: number_of_pages, moved_records: moved_positions.len() }))
+    }
+
+    /// (page id, slot id) of the records the last upgrade of the table has moved, until finish_upgrade is called
+    pub fn moved_records(&self, table: &Table) -> Result<Vec<(PageId, usize)>, StoreError> {
+        let path = self.moved_path(table);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        Ok(std::fs::read(path)?.chunks_exact(MOVED_ENTRY_SIZE)
+            .map(|entry| (
+                PageId::from_be_bytes([entry[0], entry[1], entry[2], entry[3], entry[4], entry[5], entry[6], entry[7]]),
+                u32::from_be_bytes([entry[8], entry[9], entry[10], entry[11]]) as usize,
+            ))
+            .collect())
+    }
+
+    /// Ends the upgrade of the table after the indexes were updated for the moved records
+    pub fn finish_upgrade(&self, table: &Table) -> Result<(), StoreError> {
+        for path in [self.moved_path(table), self.upgrade_path(table), self.meta_upgrade_path(table)] {
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// the header of version 1 to 3 is in front of the pages, the one of version 4 in the metadata file
+fn read_old_header(buf: &[u8], version: u8) -> Result<OldHeader, StoreError> {
+    let metadata_size = if version == 1 { V1_META_DATA_SIZE } else { V2_META_DATA_SIZE };
+    let header = buf.get(..metadata_size).ok_or_else(|| StoreError::IoError("Metadata size is smaller than expected".to_owned()))?;
+    let int = |pos: usize| i32::from_be_bytes([header[pos], header[pos + 1], header[pos + 2], header[pos + 3]]);
+    Ok(OldHeader {
+        version,
+        next_id: PageId::try_from(int(8)).unwrap_or(1),
+        number_of_pages: PageId::try_from(int(12)).unwrap_or(0),
+        page_size: u16::from_be_bytes([header[16], header[17]]) as usize,
+        format_flags: header[20],
+    })
+}
+
+fn read_old_page(buf: &[u8], page_size: usize, header_size: usize) -> Result<OldPage, PageError> {
+    let mut page = buf.to_vec();
+    if page[INDEX_FLAGS] & FLAG_COMPRESSED != 0 {
+        let compressed_len = u16::from_be_bytes([page[header_size], page[header_size + 1]]) as usize;
+        let compressed = page.get(header_size + 2..header_size + 2 + compressed_len).ok_or(PageError::ReadPageError)?;
+        let payload = decompress(compressed, page_size - header_size).map_err(|_| PageError::ReadPageError)?;
+        page.truncate(header_size);
+        page[INDEX_FLAGS] &= !FLAG_COMPRESSED;
+        page.extend_from_slice(&payload);
+    }
+
+    // the LSN (version 3 and 4) is behind the checksum, so the checksum covers it like the page data
+    let mut crc = Crc32::new();
+    crc.update(&page[..OLD_INDEX_CHECKSUM]);
+    crc.update(&page[OLD_INDEX_LSN..]);
+    let checksum = u32::from_be_bytes([page[14], page[15], page[16], page[17]]);
+    if crc.finish() != checksum {
+        return Err(PageError::ChecksumMismatch);
+    }
+    let lsn = match header_size {
+        V3_PAGE_HEADER_SIZE => u64::from_be_bytes(page[OLD_INDEX_LSN..V3_PAGE_HEADER_SIZE].try_into().map_err(|_| PageError::ReadPageError)?),
+        _ => 0,
+    };
+
+    let slots_offset = u32::from_be_bytes([0, page[11], page[12], page[13]]) as usize;
+    let data = &page[header_size..];
+    let records = data.get(..slots_offset).ok_or(PageError::ReadPageError)?
+        .chunks_exact(SLOT_SIZE)
+        .map(|slot| {
+            let offset = u32::from_be_bytes([slot[1], slot[2], slot[3], slot[4]]) as usize;
+            let length = u16::from_be_bytes([slot[5], slot[6]]) as usize;
+            if slot[0] & SLOT_DELETED != 0 {
+                return Ok(None);
+            }
+            let record = data.get(offset..offset + length).ok_or(PageError::ReadPageError)?;
+            Ok(Some((slot[0], widen_pointer(slot[0], record).ok_or(PageError::ReadPageError)?)))
+        })
+        .collect::<Result<_, PageError>>()?;
+    Ok(OldPage { lsn, records })
+}
+
+// The pointer of a forwarding slot or a moved row (4 bytes page id, 4 bytes slot) with a page id of 8 bytes,
+// other records are returned as they are
+fn widen_pointer(flag: u8, record: &[u8]) -> Option<Vec<u8>> {
+    if flag & SLOT_MOVED == 0 {
+        return Some(record.to_vec());
+    }
+    let page_id = PageId::try_from(i32::from_be_bytes(record.get(0..4)?.try_into().ok()?)).ok()?;
+    let mut widened = page_id.to_be_bytes().to_vec();
+    widened.extend_from_slice(record.get(4..)?);
+    Some(widened)
+}
+
+// The chunk of an overflow page (its only record) that doesn't fit anymore: the page keeps the first part with the
+// id of the new page as next page, the new page gets the rest with the old next page. Returns the record of the new page.
+fn split_chunk(layout: &PageDataLayout, records: &mut [Option<(u8, Vec<u8>)>], new_page_id: PageId) -> Result<Option<Vec<u8>>, StoreError> {
+    let Some(Some((_, chunk))) = records.first_mut() else {
+        return Ok(None);
+    };
+    let max_size = layout.max_record_size();
+    if chunk.len() <= max_size {
+        return Ok(None);
+    }
+    let new_page_id = u32::try_from(new_page_id)
+        .map_err(|_| StoreError::QuotaExceeded(format!("Page {} cannot be addressed by a BLOB chain", new_page_id)))?;
+
+    let mut rest = chunk[..CHUNK_NEXT_PAGE_SIZE].to_vec();
+    rest.extend_from_slice(&chunk[max_size..]);
+    chunk.truncate(max_size);
+    chunk[..CHUNK_NEXT_PAGE_SIZE].copy_from_slice(&new_page_id.to_be_bytes());
+    Ok(Some(rest))
+}
+
+// The page with as many records at their slot as fit, the last rows are moved if the page is too small
+fn fit_records(layout: &PageDataLayout, page_id: PageId, mut records: Vec<Option<(u8, Vec<u8>)>>) -> Result<(Page, Vec<Vec<u8>>), PageError> {
+    let mut moved = Vec::new();
+    loop {
+        let slots: Vec<Option<(u8, &[u8])>> = records.iter().map(|r| r.as_ref().map(|(flag, record)| (*flag, record.as_slice()))).collect();
+        match Page::with_flagged_records(layout, page_id, &slots) {
+            Ok(page) => return Ok((page, moved)),
+            Err(err) => {
+                let last = records.iter().rposition(|r| r.as_ref().is_some_and(|(flag, _)| flag & SLOT_MOVED == 0)).ok_or(err)?;
+                moved.extend(records[last].take().map(|(_, record)| record));
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use crate::{data::{checksum::Crc32, page::{PageDataLayout, PageId}}, store::{Store, file_store::FileStore}, table::{Column, ColumnType, TableSchema, table::{Cell, Row, Table}}};
+
+    // a page of version 1 to 4 (page header of 18 or 26 bytes) with the records and the flag bytes of their slots
+    fn old_page(page_size: usize, header_size: usize, page_id: i32, lsn: u64, records: &[Option<(u8, Vec<u8>)>]) -> Vec<u8> {
+        let mut page = vec![0u8; page_size];
+        let mut offset = page_size - header_size;
+        for (slot, record) in records.iter().enumerate() {
+            let (flag, record) = record.clone().unwrap_or((0x01, Vec::new()));
+            offset -= record.len();
+            page[header_size + offset..header_size + offset + record.len()].copy_from_slice(&record);
+            let pos = header_size + slot * 7;
+            page[pos] = flag;
+            page[pos + 1..pos + 5].copy_from_slice(&(offset as u32).to_be_bytes());
+            page[pos + 5..pos + 7].copy_from_slice(&(record.len() as u16).to_be_bytes());
+        }
+        page[0..2].copy_from_slice(&(records.len() as u16).to_be_bytes());
+        page[2..6].copy_from_slice(&(offset as u32).to_be_bytes());
+        page[6..10].copy_from_slice(&page_id.to_be_bytes());
+        page[10..14].copy_from_slice(&((records.len() * 7) as u32).to_be_bytes());
+        if header_size == 26 {
+            page[18..26].copy_from_slice(&lsn.to_be_bytes());
+        }
+        let mut crc = Crc32::new();
+        crc.update(&page[..14]);
+        crc.update(&page[18..]);
+        page[14..18].copy_from_slice(&crc.finish().to_be_bytes());
+        page
+    }
+
+    // the header of version 2 to 4 (in front of the pages or in the metadata file)
+    fn old_header(version: u8, page_size: usize, pages: usize) -> Vec<u8> {
+        let mut header = vec![0u8; 28];
+        header[0..4].copy_from_slice(b"PDBT");
+        header[4] = version;
+        header[8..12].copy_from_slice(&(pages as i32 + 1).to_be_bytes());
+        header[12..16].copy_from_slice(&(pages as i32).to_be_bytes());
+        header[16..18].copy_from_slice(&(page_size as u16).to_be_bytes());
+        header[18..20].copy_from_slice(&28u16.to_be_bytes());
+        header[24..28].copy_from_slice(&(pages as i32).to_be_bytes());
+        header
+    }
+
+    // a file of version 2 as playdb wrote it: the page header has no LSN
+    fn old_file(page_size: usize, pages: &[Vec<Option<Vec<u8>>>]) -> Vec<u8> {
+        let mut file = old_header(2, page_size, pages.len());
+        for (i, records) in pages.iter().enumerate() {
+            let records: Vec<Option<(u8, Vec<u8>)>> = records.iter().map(|r| r.clone().map(|r| (0, r))).collect();
+            file.extend(old_page(page_size, 18, i as i32 + 1, 0, &records));
+        }
+        file
+    }
+
+    fn row(id: i32) -> Option<Vec<u8>> {
+        Some(Row::new(vec![Cell::Int(id), Cell::Varchar("abcdefghij".to_owned())]).serialize())
+    }
+
+    #[test]
+    fn should_upgrade_a_table_file_and_move_the_records_that_dont_fit() {
+        let dir = tempdir().unwrap();
+        let store = FileStore::new(dir.path());
+        let schema = TableSchema::new(vec![Column::new(1, "id", ColumnType::Int), Column::new(2, "name", ColumnType::Varchar(10))]);
+        let table = Table::new(5, "test".to_owned(), schema);
+        let layout = PageDataLayout::new(64).unwrap();
+
+        // 46 bytes of page data in version 2: 2 records of 16 bytes + 2 slots, only 34 bytes in version 5
+        let full = vec![row(1), row(2)];
+        let with_deleted = vec![None, row(3)];
+        std::fs::write(dir.path().join(table.file_path()), old_file(64, &[full, with_deleted])).unwrap();
+        assert!(store.read_metadata(&layout, &table).is_err());
+
+        let upgrade = store.upgrade_table_file(&table).unwrap().unwrap();
+        assert_eq!((upgrade.from_version, upgrade.pages, upgrade.moved_records), (2, 3, 1));
+        assert_eq!(store.moved_records(&table).unwrap(), vec![(3, 0)]);
+        assert_eq!(store.format_version(&table).unwrap(), PageDataLayout::FORMAT_VERSION);
+
+        let ids = |page_id: PageId| -> Vec<(usize, Cell)> {
+            store.read_page(&layout, page_id, &table).unwrap().record_iterator()
+                .map(|r| (*r.record_index(), Row::deserialize(r.data(), table.schema()).unwrap().cells()[0].clone()))
+                .collect()
+        };
+        assert_eq!(ids(1), vec![(0, Cell::Int(1))]);
+        assert_eq!(ids(2), vec![(1, Cell::Int(3))]);
+        assert_eq!(ids(3), vec![(0, Cell::Int(2))]);
+        assert_eq!(store.read_metadata(&layout, &table).unwrap().number_of_pages(), 3);
+        assert_eq!(store.allocate_page(&layout, &table).unwrap().page_id(), 4);
+
+        assert!(store.upgrade_table_file(&table).unwrap().is_none());
+        store.finish_upgrade(&table).unwrap();
+        assert!(store.moved_records(&table).unwrap().is_empty());
+    }
+
+    #[test]
+    fn should_widen_the_page_ids_of_a_version_4_file() {
+        let dir = tempdir().unwrap();
+        let store = FileStore::new(dir.path());
+        let table = Table::new(5, "test".to_owned(), TableSchema::new(vec![Column::new(1, "id", ColumnType::Int)]));
+        let layout = PageDataLayout::new(64).unwrap();
+        let row = |id: i32| Row::new(vec![Cell::Int(id)]).serialize();
+        let pointer = |page_id: i32, slot: u32| [page_id.to_be_bytes(), slot.to_be_bytes()].concat();
+
+        // version 4: the header and the free space map in the metadata file, the row of page 1 slot 0 moved to page 2
+        let forward = vec![Some((0x02, pointer(2, 0))), Some((0, row(7)))];
+        let moved_in = vec![Some((0x04, [pointer(1, 0), row(8)].concat()))];
+        let mut v4 = old_page(64, 26, 1, 42, &forward);
+        v4.extend(old_page(64, 26, 2, 43, &moved_in));
+        let mut meta = old_header(4, 64, 2);
+        meta.extend([0u8; 4]);
+        std::fs::write(store.file_path(&table), &v4).unwrap();
+        std::fs::write(store.meta_path(&table), &meta).unwrap();
+        assert!(store.read_metadata(&layout, &table).is_err());
+
+        let upgrade = store.upgrade_table_file(&table).unwrap().unwrap();
+        assert_eq!((upgrade.from_version, upgrade.pages, upgrade.moved_records), (4, 2, 0));
+        let (page_1, page_2) = (store.read_page(&layout, 1, &table).unwrap(), store.read_page(&layout, 2, &table).unwrap());
+        assert_eq!((page_1.page_id(), page_1.lsn(), page_2.lsn()), (1, 42, 43));
+        assert_eq!(page_1.forward(0), Some((2, 0)));
+        assert_eq!(page_1.read_slot(1), Some(row(7).as_slice()));
+        assert_eq!(page_2.home(0), Some((1, 0)));
+        assert_eq!(page_2.read_slot(0), Some(row(8).as_slice()));
+        assert_eq!(store.read_free_space(&table).unwrap().unwrap().free_space(2), Some(page_2.free_space()));
+        assert_eq!(store.read_metadata(&layout, &table).unwrap().next_id(), 3);
+
+        // stopped between the renames: the metadata file is new, the table file still old
+        let upgraded = std::fs::read(store.file_path(&table)).unwrap();
+        std::fs::rename(store.file_path(&table), dir.path().join(format!("{}.upgrade", table.file_path()))).unwrap();
+        std::fs::write(store.file_path(&table), &v4).unwrap();
+        assert!(store.upgrade_table_file(&table).unwrap().is_none());
+        assert_eq!(std::fs::read(store.file_path(&table)).unwrap(), upgraded);
+        store.finish_upgrade(&table).unwrap();
+    }
+}
@@ -0,0 +1,90 @@
+use crate::data::page::{Page, PageDataLayout, PageId};
+
+// Metadata file: the FileStore keeps the header of a table file (PageFileMetadata: page allocation, layout) and the
+// free space map of its pages in a sidecar file ('<table file>.meta'), so the table file contains only pages and
+// page n starts at (n - 1) * page size. The free space map can grow with the table without moving the pages.
+//
+// File format (big endian): the header (PageDataLayout::META_DATA_SIZE bytes), then one entry per page at position
+// page_id - 1: 2 bytes free space of the page (Page::free_space). The entry is written with the page.
+// - a page without entry (behind the end of the file) is unknown, an insert reads it
+// - the header is written in place, the entries behind it are kept
+
+pub(crate) const ENTRY_SIZE: usize = 2;
+
+/// Position of the entry of the page in the metadata file
+pub(crate) fn entry_offset(page_id: PageId) -> u64 {
+    PageDataLayout::META_DATA_SIZE as u64 + page_id.saturating_sub(1) * ENTRY_SIZE as u64
+}
+
+pub(crate) fn page_entry(page: &Page) -> [u8; ENTRY_SIZE] {
+    // pages have at most 64 KiB
+    (page.free_space().min(u16::MAX as usize) as u16).to_be_bytes()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FreeSpaceMap {
+    // index page_id - 1: free space of the page
+    pages: Vec<u16>,
+}
+
+impl FreeSpaceMap {
+    /// The map of the metadata file (including the header)
+    pub(crate) fn deserialize(buf: &[u8]) -> Self {
+        let entries = buf.get(PageDataLayout::META_DATA_SIZE..).unwrap_or_default();
+        // a torn entry at the end is ignored, the page is unknown
+        let pages = entries.chunks_exact(ENTRY_SIZE)
+            .map(|entry| u16::from_be_bytes([entry[0], entry[1]]))
+            .collect();
+        Self { pages }
+    }
+
+    /// Free space of the page, None if it is unknown
+    pub fn free_space(&self, page_id: PageId) -> Option<usize> {
+        page_id.checked_sub(1)
+            .and_then(|index| usize::try_from(index).ok())
+            .and_then(|index| self.pages.get(index))
+            .map(|free| *free as usize)
+    }
+
+    /// Ids of the pages (up to number_of_pages) that may have space for a record of the size, in the order of the file
+    pub fn pages_with(&self, record_size: usize, number_of_pages: PageId) -> Vec<PageId> {
+        (1..=number_of_pages)
+            .filter(|page_id| self.free_space(*page_id).is_none_or(|free| free >= record_size))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{data::page::{Page, PageDataLayout}, store::free_space::{FreeSpaceMap, entry_offset, page_entry}};
+
+    #[test]
+    fn should_find_the_pages_with_space_for_a_record() {
+        let layout = PageDataLayout::new(64).unwrap();
+        let mut full = Page::new(&layout);
+        while full.can_insert(&vec![1; 10]) {
+            full.insert_record(vec![1; 10]).unwrap();
+        }
+        let mut half = Page::new(&layout);
+        half.insert_record(vec![2; 10]).unwrap();
+        let deleted = half.insert_record(vec![3; 10]).unwrap();
+        half.delete_record(deleted);
+
+        let mut file = vec![0u8; PageDataLayout::META_DATA_SIZE];
+        file.extend(page_entry(&full));
+        file.extend(page_entry(&half));
+        file.extend(page_entry(&Page::new(&layout)));
+        assert_eq!(entry_offset(3), (file.len() - 2) as u64);
+        // torn entry of page 4
+        file.push(0);
+
+        let map = FreeSpaceMap::deserialize(&file);
+        assert!(map.free_space(1).unwrap() < 10);
+        // the deleted record is free
+        assert_eq!(map.free_space(2), Some(layout.page_data_size() - 10 - 2 * 7));
+        assert_eq!(map.free_space(3), Some(layout.page_data_size()));
+        assert_eq!(map.free_space(4), None);
+        assert_eq!(map.pages_with(10, 5), vec![2, 3, 4, 5]);
+        assert_eq!(map.pages_with(layout.page_data_size(), 3), vec![3]);
+    }
+}
@@ -0,0 +1,341 @@
+use std::cell::RefCell;
+
+use thiserror::Error;
+
+use crate::{data::page::{Page, PageDataLayout, PageError, PageId}, store::{Store, StoreError, index_position, index_value}, table::{Column, ColumnType, TableSchema, table::Table}, tree::store::BTreeStore};
+
+// Key-value namespace stored in normal pages (same file format as tables).
+// Record: 2 bytes key length, key (UTF-8), value
+//
+// The B-tree maps a 32-bit hash of the key to the record. It's a unique index, so only one record per hash
+// can be indexed. Records with a colliding hash are stored without an index entry and are found by a scan.
+// Invariant: as long as there is a record with a given hash, the index has an entry for this hash.
+// Therefore, a missing index entry means that the key doesn't exist.
+//
+// Namespaces use negative ids for their page file and B-tree, so they never collide with tables and indexes.
+
+#[derive(Debug, Error)]
+pub enum KvStoreError {
+    #[error("KvStoreError - store error: {0}")]
+    StoreError(String),
+    #[error("KvStoreError - index error: {0}")]
+    IndexError(String),
+    #[error("KvStoreError - key and value are too large for a page: {0} bytes")]
+    EntryTooLarge(usize),
+    #[error("KvStoreError - corrupted entry on page {0}, slot {1}")]
+    CorruptedEntry(PageId, usize),
+}
+
+impl From<StoreError> for KvStoreError {
+    fn from(err: StoreError) -> Self {
+        KvStoreError::StoreError(err.to_string())
+    }
+}
+
+impl From<PageError> for KvStoreError {
+    fn from(err: PageError) -> Self {
+        KvStoreError::StoreError(err.to_string())
+    }
+}
+
+pub struct KvStore<'db, S: Store> {
+    store: &'db S,
+    layout: &'db PageDataLayout,
+    table: Table,
+    index: RefCell<BTreeStore>,
+}
+
+struct Entry {
+    page_id: PageId,
+    slot_id: usize,
+    key: String,
+    value: Vec<u8>,
+}
+
+pub fn namespace_id(namespace: u16) -> i32 {
+    -(namespace as i32) - 1
+}
+
+/// The table of the page file of the namespace
+pub(crate) fn namespace_table(namespace: u16) -> Table {
+    // the schema is only needed because the Store API works with tables
+    let schema = TableSchema::of([Column::new(1, "entry", ColumnType::Varchar(u16::MAX))]);
+    Table::new(namespace_id(namespace), format!("_kv_{}", namespace), schema)
+}
+
+impl<'db, S: Store> KvStore<'db, S> {
+    /// Opens the namespace and creates its file, if it doesn't exist yet
+    pub fn open(store: &'db S, layout: &'db PageDataLayout, namespace: u16) -> Result<Self, KvStoreError> {
+        let table = namespace_table(namespace);
+        if store.read_metadata(layout, &table).is_err() {
+            store.create(layout, &table)?;
+        }
+        let index = store.read_btree(table.id())?;
+
+        let kv = Self {
+            store,
+            layout,
+            table,
+            index: RefCell::new(index),
+        };
+        // moved by a format upgrade on open (FileStore::with_upgrade_on_open)
+        let moved = store.moved_records(&kv.table)?;
+        if !moved.is_empty() {
+            kv.index_moved(&moved)?;
+            store.finish_upgrade(&kv.table)?;
+        }
+        Ok(kv)
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<Vec<u8>>, KvStoreError> {
+        Ok(self.find_entry(key)?.map(|entry| entry.value))
+    }
+
+    /// Inserts or replaces the value of the key
+    pub fn put(&self, key: &str, value: &[u8]) -> Result<(), KvStoreError> {
+        let record = encode(key, value);
+        if !Page::new(self.layout).can_insert(&record) {
+            return Err(KvStoreError::EntryTooLarge(record.len()));
+        }
+
+        self.delete(key)?;
+
+        let (page_id, slot_id) = self.insert_record(record)?;
+        let hash = hash_key(key);
+        if self.index_find(hash)?.is_none() {
+            self.index.borrow_mut().insert(hash, index_value((page_id, slot_id))?)
+                .map_err(|e| KvStoreError::IndexError(e.to_string()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns true, if the key existed
+    pub fn delete(&self, key: &str) -> Result<bool, KvStoreError> {
+        let entry = match self.find_entry(key)? {
+            Some(entry) => entry,
+            None => return Ok(false),
+        };
+
+        let mut page = self.store.read_page(self.layout, entry.page_id, &self.table)?;
+        page.delete_record(entry.slot_id);
+        self.store.write_page(self.layout, &page, &self.table)?;
+
+        let hash = hash_key(key);
+        if self.index_find(hash)? == Some((entry.page_id, entry.slot_id)) {
+            let mut index = self.index.borrow_mut();
+            index.delete(hash)
+                .map_err(|e| KvStoreError::IndexError(e.to_string()))?;
+
+            // keep the invariant: another key with the same hash must be indexed now
+            if let Some(other) = self.scan(|other_key| hash_key(other_key) == hash)?.into_iter().next() {
+                index.insert(hash, index_value((other.page_id, other.slot_id))?)
+                    .map_err(|e| KvStoreError::IndexError(e.to_string()))?;
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Indexes the records an upgrade of the file format has moved to another position (see Database::upgrade_format).
+    /// The entry of their hash points to their old slot, which is empty now. Entries that point to a record are kept
+    /// (another key with the same hash), so it can be repeated.
+    pub(crate) fn index_moved(&self, moved: &[(PageId, usize)]) -> Result<(), KvStoreError> {
+        for &(page_id, slot_id) in moved {
+            let page = self.store.read_page(self.layout, page_id, &self.table)?;
+            let (key, _) = page.read_slot(slot_id).and_then(decode)
+                .ok_or(KvStoreError::CorruptedEntry(page_id, slot_id))?;
+            let hash = hash_key(&key);
+            let indexed = match self.index_find(hash)? {
+                Some((indexed_page, indexed_slot)) => self.store.read_page(self.layout, indexed_page, &self.table)?.read_slot(indexed_slot).is_some(),
+                None => false,
+            };
+            if !indexed {
+                let mut index = self.index.borrow_mut();
+                index.delete(hash)
+                    .map_err(|e| KvStoreError::IndexError(e.to_string()))?;
+                index.insert(hash, index_value((page_id, slot_id))?)
+                    .map_err(|e| KvStoreError::IndexError(e.to_string()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// All entries whose key starts with `prefix`, ordered by key
+    pub fn scan_prefix(&self, prefix: &str) -> Result<Vec<(String, Vec<u8>)>, KvStoreError> {
+        let mut entries: Vec<(String, Vec<u8>)> = self.scan(|key| key.starts_with(prefix))?
+            .into_iter()
+            .map(|entry| (entry.key, entry.value))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(entries)
+    }
+
+    fn find_entry(&self, key: &str) -> Result<Option<Entry>, KvStoreError> {
+        let (page_id, slot_id) = match self.index_find(hash_key(key))? {
+            Some(location) => location,
+            None => return Ok(None),
+        };
+
+        let page = self.store.read_page(self.layout, page_id, &self.table)?;
+        let data = page.read_slot(slot_id)
+            .ok_or(KvStoreError::CorruptedEntry(page_id, slot_id))?;
+        let (indexed_key, value) = decode(data)
+            .ok_or(KvStoreError::CorruptedEntry(page_id, slot_id))?;
+
+        if indexed_key == key {
+            return Ok(Some(Entry { page_id, slot_id, key: indexed_key, value }));
+        }
+
+        // hash collision: the key can only be found by a scan
+        Ok(self.scan(|other_key| other_key == key)?.into_iter().next())
+    }
+
+    fn scan<F: Fn(&str) -> bool>(&self, filter: F) -> Result<Vec<Entry>, KvStoreError> {
+        let mut entries = Vec::new();
+        for page in self.store.seq_page_iterator(self.layout, &self.table)? {
+            for record in page?.record_iterator() {
+                let (key, value) = decode(record.data())
+                    .ok_or(KvStoreError::CorruptedEntry(*record.page_id(), *record.record_index()))?;
+                if filter(&key) {
+                    entries.push(Entry { page_id: *record.page_id(), slot_id: *record.record_index(), key, value });
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    fn insert_record(&self, record: Vec<u8>) -> Result<(PageId, usize), KvStoreError> {
+        for page in self.store.seq_page_iterator(self.layout, &self.table)? {
+            let mut page = page?;
+            if page.can_insert(&record) {
+                let slot_id = page.insert_record(record)?;
+                self.store.write_page(self.layout, &page, &self.table)?;
+                return Ok((page.page_id(), slot_id));
+            }
+        }
+
+        let mut page = self.store.allocate_page(self.layout, &self.table)?;
+        let slot_id = page.insert_record(record)?;
+        self.store.write_page(self.layout, &page, &self.table)?;
+        Ok((page.page_id(), slot_id))
+    }
+
+    fn index_find(&self, hash: i32) -> Result<Option<(PageId, usize)>, KvStoreError> {
+        let position = self.index.borrow().find(hash)
+            .map_err(|e| KvStoreError::IndexError(e.to_string()))?;
+        Ok(position.map(index_position).transpose()?)
+    }
+}
+
+fn encode(key: &str, value: &[u8]) -> Vec<u8> {
+    let mut record = Vec::with_capacity(2 + key.len() + value.len());
+    record.extend_from_slice(&(key.len() as u16).to_be_bytes());
+    record.extend_from_slice(key.as_bytes());
+    record.extend_from_slice(value);
+    record
+}
+
+fn decode(record: &[u8]) -> Option<(String, Vec<u8>)> {
+    let key_len = u16::from_be_bytes(record.get(0..2)?.try_into().ok()?) as usize;
+    let key = String::from_utf8(record.get(2..2 + key_len)?.to_vec()).ok()?;
+    Some((key, record[2 + key_len..].to_vec()))
+}
+
+// FNV-1a: the hash is persisted in the B-tree, so it must never change (std's hashers don't guarantee that)
+fn hash_key(key: &str) -> i32 {
+    let mut hash: u32 = 0x811c9dc5;
+    for byte in key.as_bytes() {
+        hash ^= *byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    // i32::MIN is the NULL value of the B-tree
+    match hash as i32 {
+        i32::MIN => i32::MIN + 1,
+        h => h,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{data::page::PageDataLayout, store::{Store, file_store::FileStore, index_value, kv_store::{KvStore, KvStoreError, hash_key}}};
+
+    #[test]
+    fn should_put_get_and_delete_values() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileStore::new(dir.path());
+        let layout = PageDataLayout::new(128).unwrap();
+
+        let kv = KvStore::open(&store, &layout, 0).unwrap();
+        kv.put("config/page_size", &4096u32.to_be_bytes()).unwrap();
+        kv.put("config/name", b"playdb").unwrap();
+        kv.put("other", b"x").unwrap();
+        kv.put("config/name", b"playdb2").unwrap();
+
+        assert_eq!(kv.get("config/name").unwrap(), Some(b"playdb2".to_vec()));
+        assert_eq!(kv.get("missing").unwrap(), None);
+
+        let config = kv.scan_prefix("config/").unwrap();
+        let keys: Vec<&str> = config.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, vec!["config/name", "config/page_size"]);
+
+        assert!(kv.delete("other").unwrap());
+        assert!(!kv.delete("other").unwrap());
+        assert_eq!(kv.get("other").unwrap(), None);
+
+        // reopen: the data is persisted
+        drop(kv);
+        let kv = KvStore::open(&store, &layout, 0).unwrap();
+        assert_eq!(kv.get("config/page_size").unwrap(), Some(4096u32.to_be_bytes().to_vec()));
+
+        assert!(matches!(kv.put("big", &[0u8; 200]), Err(KvStoreError::EntryTooLarge(_))));
+    }
+
+    #[test]
+    fn should_find_keys_with_colliding_hashes() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileStore::new(dir.path());
+        let layout = PageDataLayout::new(128).unwrap();
+        let kv = KvStore::open(&store, &layout, 1).unwrap();
+
+        kv.put("a", b"1").unwrap();
+        kv.put("b", b"2").unwrap();
+
+        // simulate a collision: let the hash of "b" point to the record of "a"
+        let location_a = kv.index_find(hash_key("a")).unwrap().unwrap();
+        {
+            let mut index = kv.index.borrow_mut();
+            index.delete(hash_key("b")).unwrap();
+            index.insert(hash_key("b"), index_value(location_a).unwrap()).unwrap();
+        }
+
+        assert_eq!(kv.get("b").unwrap(), Some(b"2".to_vec()));
+        assert_eq!(kv.get("a").unwrap(), Some(b"1".to_vec()));
+    }
+
+    #[test]
+    fn should_index_the_records_moved_by_an_upgrade() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileStore::new(dir.path());
+        let layout = PageDataLayout::new(128).unwrap();
+        let kv = KvStore::open(&store, &layout, 2).unwrap();
+        kv.put("a", b"1").unwrap();
+        kv.put("b", b"2").unwrap();
+
+        // the record of "a" moved to a new page, its index entry points to the empty slot
+        let (page_id, slot_id) = kv.index_find(hash_key("a")).unwrap().unwrap();
+        let mut page = store.read_page(&layout, page_id, &kv.table).unwrap();
+        let record = page.read_slot(slot_id).unwrap().to_vec();
+        page.delete_record(slot_id);
+        store.write_page(&layout, &page, &kv.table).unwrap();
+        let mut new_page = store.allocate_page(&layout, &kv.table).unwrap();
+        let moved = (new_page.page_id(), new_page.insert_record(record).unwrap());
+        store.write_page(&layout, &new_page, &kv.table).unwrap();
+        assert!(matches!(kv.get("a"), Err(KvStoreError::CorruptedEntry(..))));
+
+        kv.index_moved(&[moved]).unwrap();
+        kv.index_moved(&[moved]).unwrap();
+        assert_eq!(kv.index_find(hash_key("a")).unwrap(), Some(moved));
+        assert_eq!((kv.get("a").unwrap(), kv.get("b").unwrap()), (Some(b"1".to_vec()), Some(b"2".to_vec())));
+    }
+}
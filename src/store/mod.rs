@@ -1,10 +1,37 @@
+// Pages and stores read data from disk: corrupted or hostile files must return errors, not panic.
+#![cfg_attr(not(test), deny(clippy::unwrap_used, clippy::expect_used))]
+
+pub mod failpoints;
+pub mod bloom_filter;
+pub mod bounding_box;
+pub mod branch_store;
 pub mod file_store;
+pub mod format_upgrade;
+pub mod free_space;
+pub mod kv_store;
+pub mod page_cache;
+pub mod page_guard;
+pub mod predicate;
+pub mod prefetch;
+pub mod row_batch;
+pub mod sample;
+pub mod timed_store;
+pub mod zone_map;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
 use thiserror::Error;
 
-use crate::{data::page::{Page, PageDataLayout, PageFileMetadata, Record, RecordIterator}, table::{TableSchema, table::{Row, Table}}, tree::store::{BTreeStore, BTreeStoreError}};
+use crate::{data::page::{Page, PageDataLayout, PageError, PageFileMetadata, PageId, Record, RecordIterator}, table::{TableSchema, table::{CellDeserializationError, Row, Table}}, tree::store::{BTreeStore, BTreeStoreError}};
+use prefetch::{PrefetchStats, Prefetcher};
+use row_batch::{BATCH_SIZE, RowBatch, RowBatchRows};
+use sample::PageSampler;
+use timed_store::StoreMetrics;
+use bloom_filter::{BloomFilters, BloomProbe};
+use free_space::FreeSpaceMap;
+use page_guard::PageGuard;
+use zone_map::{ZoneFilter, ZoneMap};
+use bounding_box::{BoundingBoxFilter, BoundingBoxes};
 
 // Store is always owned by a Database instance
 // ToDo:
@@ -21,14 +48,153 @@ pub trait Store {
     fn create(&self, layout: &PageDataLayout, table: &Table) -> Result<(), StoreError>;
     fn delete(&self, table: &Table) -> Result<(), StoreError>;
     fn read_metadata(&self, layout: &PageDataLayout, table: &Table) -> Result<PageFileMetadata, StoreError>;
-    fn read_page<'db>(&self, layout: &'db PageDataLayout, page_id: i32, table: &Table) -> Result<Page<'db>, StoreError>;
+    /// Page size the table was created with (stored in the header of the table file, see PageFileMetadata)
+    fn read_page_size(&self, table: &Table) -> Result<usize, StoreError>;
+    fn read_page(&self, layout: &PageDataLayout, page_id: PageId, table: &Table) -> Result<Page, StoreError>;
+    /// Reads several pages of a table at once. The pages are returned in the order of `page_ids`.
+    /// Stores can override this to reduce the number of I/O calls (e.g. for prefetching).
+    fn read_pages(&self, layout: &PageDataLayout, page_ids: &[PageId], table: &Table) -> Result<Vec<Page>, StoreError> {
+        page_ids.iter()
+            .map(|page_id| self.read_page(layout, *page_id, table))
+            .collect()
+    }
     fn write_page(&self, layout: &PageDataLayout, page: &Page, table: &Table) -> Result<(), StoreError>;
-    fn allocate_page<'db>(&self, layout: &'db PageDataLayout, table: &Table) -> Result<Page<'db>, StoreError>;
+    /// Writes several pages of a table at once. Stores can override this to reduce the number of I/O calls.
+    /// The pages are not written atomically: on error, some of them may have been written.
+    fn write_pages(&self, layout: &PageDataLayout, pages: &[&Page], table: &Table) -> Result<(), StoreError> {
+        for page in pages {
+            self.write_page(layout, page, table)?;
+        }
+        Ok(())
+    }
+    fn allocate_page(&self, layout: &PageDataLayout, table: &Table) -> Result<Page, StoreError>;
+    /// Removes the pages behind `number_of_pages` from the end of the table (and the preallocated ones), the next
+    /// allocate_page returns page number_of_pages + 1 again. Returns the number of pages that were removed,
+    /// 0 if the store cannot shrink a table.
+    fn truncate(&self, _layout: &PageDataLayout, _table: &Table, _number_of_pages: PageId) -> Result<PageId, StoreError> {
+        Ok(0)
+    }
+    /// Limits of the store. Page limits are enforced by allocate_page, the row size by TableAccess.
+    fn quota(&self) -> Quota {
+        Quota::default()
+    }
+    /// Pages in memory as (table id, page id, dirty). Empty, if the store has no buffer pool.
+    fn buffer_pool_pages(&self) -> Vec<(i32, PageId, bool)> {
+        Vec::new()
+    }
+    /// Number of pages the buffer pool can keep, None if the store has no buffer pool
+    fn buffer_pool_capacity(&self) -> Option<usize> {
+        None
+    }
+    /// Keeps the page in the buffer pool until it is unpinned as often as it was pinned (see PageGuard).
+    /// Nothing to do, if the store has no buffer pool.
+    fn pin_page(&self, _table: &Table, _page_id: PageId) {}
+    fn unpin_page(&self, _table: &Table, _page_id: PageId) {}
+    /// Size of the table file in bytes (0, if the store has no files)
+    fn disk_size(&self, _table: &Table) -> Result<u64, StoreError> {
+        Ok(0)
+    }
+    /// Size of the B-tree file in bytes (0, if the store has no files)
+    fn btree_disk_size(&self, _btree_id: i32) -> Result<u64, StoreError> {
+        Ok(0)
+    }
+    /// Page I/O since the store was created (all zero, if the store doesn't count)
+    fn io_stats(&self) -> IoStats {
+        IoStats::default()
+    }
+    /// Latencies of the store operations (empty, if the store doesn't measure them, see TimedStore)
+    fn metrics(&self) -> StoreMetrics {
+        StoreMetrics::default()
+    }
+    /// Min/max of the Int columns per page (see zone_map.rs), None if the store doesn't keep them
+    fn read_zone_map(&self, _table: &Table) -> Result<Option<ZoneMap>, StoreError> {
+        Ok(None)
+    }
+    /// Bounding boxes of the Point columns per page (see bounding_box.rs), None if the store doesn't keep them
+    fn read_bounding_boxes(&self, _table: &Table) -> Result<Option<BoundingBoxes>, StoreError> {
+        Ok(None)
+    }
+    /// Positions of the records an upgrade of the file format moved to another page (see format_upgrade.rs),
+    /// their index entries must be updated before finish_upgrade. Empty, if the store doesn't upgrade files.
+    fn moved_records(&self, _table: &Table) -> Result<Vec<(PageId, usize)>, StoreError> {
+        Ok(Vec::new())
+    }
+    /// Ends the upgrade of the table after the indexes were updated for the moved records
+    fn finish_upgrade(&self, _table: &Table) -> Result<(), StoreError> {
+        Ok(())
+    }
+    /// Free space of the pages of the table (see free_space.rs), None if the store doesn't keep it
+    fn read_free_space(&self, _table: &Table) -> Result<Option<FreeSpaceMap>, StoreError> {
+        Ok(None)
+    }
+    /// Builds a bloom filter per page over the column (see bloom_filter.rs), replaces the filters of another column
+    fn create_bloom_filter(&self, _layout: &PageDataLayout, _table: &Table, _column_id: i32) -> Result<(), StoreError> {
+        Err(StoreError::IoError("The store doesn't keep bloom filters".to_owned()))
+    }
+    fn drop_bloom_filter(&self, _table: &Table) -> Result<(), StoreError> {
+        Ok(())
+    }
+    /// The bloom filters of the pages, None if the table has none
+    fn read_bloom_filter(&self, _table: &Table) -> Result<Option<BloomFilters>, StoreError> {
+        Ok(None)
+    }
+    /// Reads the page to change it, see page_guard.rs
+    fn page_guard<'s>(&'s self, layout: &'s PageDataLayout, page_id: PageId, table: &'s Table) -> Result<PageGuard<'s, Self>, StoreError>
+    where
+        Self: Sized
+    {
+        PageGuard::read(self, layout, page_id, table)
+    }
     fn seq_page_iterator<'database>(&'database self, layout: &'database PageDataLayout, table: &'database crate::table::table::Table) -> Result<PageIterator<'database, Self>, StoreError> 
     where
         Self: Sized
     {
-        Ok(PageIterator::new(table, self, layout))
+        PageIterator::try_new(table, self, layout)
+    }
+}
+
+// The B-tree files store the position of a row as two INTs (page id, slot), see tree/store.rs. Page ids are 8 bytes
+// since format version 5, the indexes can address the first i32::MAX pages of a table until their format changes, too.
+pub(crate) fn index_value((page_id, slot_id): (PageId, usize)) -> Result<(i32, i32), StoreError> {
+    match (i32::try_from(page_id), i32::try_from(slot_id)) {
+        (Ok(page_id), Ok(slot_id)) => Ok((page_id, slot_id)),
+        _ => Err(StoreError::QuotaExceeded(format!("Page {} (slot {}) cannot be addressed by an index", page_id, slot_id))),
+    }
+}
+
+/// The position of an index entry (see index_value)
+pub(crate) fn index_position((page_id, slot_id): (i32, i32)) -> Result<(PageId, usize), StoreError> {
+    match (PageId::try_from(page_id), usize::try_from(slot_id)) {
+        (Ok(page_id), Ok(slot_id)) => Ok((page_id, slot_id)),
+        _ => Err(StoreError::DeserializationError(format!("Invalid position in index: page {}, slot {}", page_id, slot_id))),
+    }
+}
+
+/// Limits for embedded deployments (None means unlimited).
+/// The total size includes all files of the store, but B-tree files can still grow when the limit is reached.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Quota {
+    pub max_total_size: Option<u64>,
+    pub max_pages_per_table: Option<PageId>,
+    pub max_row_size: Option<usize>,
+}
+
+/// Page reads and writes of a store. A page that is served by a buffer pool counts as cache hit, not as read.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct IoStats {
+    pub pages_read: u64,
+    pub pages_written: u64,
+    pub cache_hits: u64,
+}
+
+impl IoStats {
+    /// Difference to an earlier snapshot
+    pub fn since(&self, earlier: &IoStats) -> IoStats {
+        IoStats {
+            pages_read: self.pages_read.saturating_sub(earlier.pages_read),
+            pages_written: self.pages_written.saturating_sub(earlier.pages_written),
+            cache_hits: self.cache_hits.saturating_sub(earlier.cache_hits),
+        }
     }
 }
 
@@ -36,23 +202,27 @@ pub struct IndexedRowIterator<'db, S: Store> {
     layout: &'db PageDataLayout,
     store: &'db S,
     table: &'db Table,
-    indexes: Vec<(i32, Vec<usize>)>, // page_id => Vec<slot_id>
+    indexes: Vec<(PageId, Vec<usize>)>, // page_id => Vec<slot_id>, popped in ascending order of page_id
     record_iter: Option<RecordIterator>,
     current_index: usize,
+    prefetcher: Prefetcher,
+    prefetched: HashMap<PageId, Page>,
 }
 
 impl<'db, S: Store> IndexedRowIterator<'db, S> {
-    pub fn new(table: &'db Table, store: &'db S, layout: &'db PageDataLayout, indexes: Vec<(i32, i32)>) -> Self {
-        let mut map: HashMap<i32, Vec<usize>> = HashMap::new();
+    pub fn new(table: &'db Table, store: &'db S, layout: &'db PageDataLayout, indexes: Vec<(PageId, usize)>) -> Self {
+        let mut map: HashMap<PageId, Vec<usize>> = HashMap::new();
 
         for (page_id, slot_id) in indexes {
-            map.entry(page_id).or_default().push(slot_id as usize);
+            map.entry(page_id).or_default().push(slot_id);
         }
 
         let mut index_vec = Vec::new();
         for (page_id, slots) in map {
             index_vec.push((page_id, slots));
         }
+        // visit the pages in order, so that lookups of neighbouring pages are sequential reads
+        index_vec.sort_by_key(|(page_id, _)| std::cmp::Reverse(*page_id));
 
         Self {
             table,
@@ -61,12 +231,40 @@ impl<'db, S: Store> IndexedRowIterator<'db, S> {
             indexes: index_vec,
             record_iter: None,
             current_index: 0,
+            prefetcher: Prefetcher::new(),
+            prefetched: HashMap::new(),
         }
     }
+
+    pub fn prefetch_stats(&self) -> PrefetchStats {
+        self.prefetcher.stats()
+    }
+
+    fn read_page(&mut self, page_id: PageId) -> Result<Page, StoreError> {
+        self.prefetcher.record_access(page_id);
+        if let Some(page) = self.prefetched.remove(&page_id) {
+            return Ok(page);
+        }
+
+        let ahead = self.prefetcher.readahead();
+        if ahead == 0 {
+            return self.store.read_page(self.layout, page_id, self.table);
+        }
+
+        // the next pages of this lookup (they don't have to be consecutive)
+        let mut page_ids = vec![page_id];
+        page_ids.extend(self.indexes.iter().rev().take(ahead).map(|(id, _)| *id));
+        let mut pages = self.store.read_pages(self.layout, &page_ids, self.table)?;
+        self.prefetcher.record_prefetched(pages.len() - 1);
+
+        let page = pages.remove(0);
+        self.prefetched.extend(pages.into_iter().map(|p| (p.page_id(), p)));
+        Ok(page)
+    }
 }
 
 impl<'db, S: Store> Iterator for IndexedRowIterator<'db, S> {
-    type Item = (Record, Row);
+    type Item = Result<(Record, Row), StoreError>;
 
     fn next(&mut self) -> Option<Self::Item> {
         let mut res = None;
@@ -75,8 +273,9 @@ impl<'db, S: Store> Iterator for IndexedRowIterator<'db, S> {
         while res.is_none() {
             if let Some(record_iter) = self.record_iter.as_mut() {
                 res = record_iter.next().map(|r| {
-                    let row = Row::deserialize(r.data(), self.table.schema());
-                    (r, row)
+                    let row = Row::deserialize(r.data(), self.table.schema())
+                        .map_err(|err| err.in_record(self.table.name(), *r.page_id(), *r.record_index()))?;
+                    Ok((r, row))
                 });
 
                 if res.is_none() {
@@ -86,9 +285,14 @@ impl<'db, S: Store> Iterator for IndexedRowIterator<'db, S> {
             } else {
                 let next = self.indexes.pop();
                 if let Some((page_id, slots)) = next {
-                    // Refactor unwrap here and in PageIterator as well
-                    let page = self.store.read_page(self.layout, page_id, self.table).unwrap();
-                    self.record_iter = Some(RecordIterator::from_slots(page, slots));
+                    match self.read_page(page_id) {
+                        Ok(page) => {
+                            // rows that were moved to another page are read there (next)
+                            self.indexes.extend(slots.iter().filter_map(|slot| page.forward(*slot)).map(|(page_id, slot)| (page_id, vec![slot])));
+                            self.record_iter = Some(RecordIterator::from_slots(page, slots));
+                        },
+                        Err(err) => return Some(Err(err)),
+                    }
                 } else {
                     return None;
                 }
@@ -99,68 +303,238 @@ impl<'db, S: Store> Iterator for IndexedRowIterator<'db, S> {
     }
 }
 
+/// Consistency of a single scan. There are no transactions yet: every write is visible immediately and a scan
+/// never waits for a writer, so the difference is only which pages a scan reads and when.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReadConsistency {
+    /// Only the pages that existed when the scan started are visited, prefetched pages can be older than the file
+    #[default]
+    ReadCommitted,
+    /// Dirty read for monitoring queries: every page is read when the scan reaches it (no readahead) and pages
+    /// allocated during the scan are visited, too. Don't use it for scans that insert into the same table,
+    /// they may never end. With transactions, these scans will see uncommitted changes instead of waiting.
+    ReadUncommitted,
+}
+
 pub struct PageIterator<'db, S: Store> {
     layout: &'db PageDataLayout,
     store: &'db S,
     table: &'db Table,
-    current_page_id: i32,
-    total_pages: i32,
+    current_page_id: PageId,
+    // Number of pages when the iterator has been created (snapshot).
+    // Pages allocated during the iteration (e.g. by an insert while scanning) are not visited,
+    // otherwise a scan that inserts could run forever.
+    total_pages: PageId,
+    done: bool,
+    prefetcher: Prefetcher,
+    readahead: VecDeque<Page>,
+    consistency: ReadConsistency,
+    // only the pages chosen by the sampler are read (see sample.rs)
+    sampler: Option<PageSampler>,
+    // the pages that cannot contain the searched value are not read (see zone_map.rs)
+    zone_filter: Option<ZoneFilter>,
+    // the same with the bloom filters of the pages (see bloom_filter.rs)
+    bloom_probe: Option<BloomProbe>,
+    // and the pages outside of a searched box (see bounding_box.rs)
+    bbox_filter: Option<BoundingBoxFilter>,
 }
 
 impl<'db, S: Store> PageIterator<'db, S> {
-    pub fn new(table: &'db Table, store: &'db S, layout: &'db PageDataLayout) -> Self {
-        // ToDo: better error handling
-        let metadata = store.read_metadata(layout, table).expect("Couldn't read metadata");
+    pub fn try_new(table: &'db Table, store: &'db S, layout: &'db PageDataLayout) -> Result<Self, StoreError> {
+        let metadata = store.read_metadata(layout, table)?;
         let total_pages = metadata.number_of_pages();
-        Self {
+        Ok(Self {
             table,
             layout,
             store,
             current_page_id: 1,
             total_pages,
+            done: false,
+            prefetcher: Prefetcher::new(),
+            readahead: VecDeque::new(),
+            consistency: ReadConsistency::default(),
+            sampler: None,
+            zone_filter: None,
+            bloom_probe: None,
+            bbox_filter: None,
+        })
+    }
+
+    pub fn with_consistency(mut self, consistency: ReadConsistency) -> Self {
+        self.consistency = consistency;
+        self
+    }
+
+    pub fn with_sampler(mut self, sampler: PageSampler) -> Self {
+        self.sampler = Some(sampler);
+        self
+    }
+
+    pub fn with_zone_filter(mut self, zone_filter: ZoneFilter) -> Self {
+        self.zone_filter = Some(zone_filter);
+        self
+    }
+
+    pub fn with_bloom_probe(mut self, bloom_probe: BloomProbe) -> Self {
+        self.bloom_probe = Some(bloom_probe);
+        self
+    }
+
+    pub fn with_bbox_filter(mut self, bbox_filter: BoundingBoxFilter) -> Self {
+        self.bbox_filter = Some(bbox_filter);
+        self
+    }
+
+    pub fn prefetch_stats(&self) -> PrefetchStats {
+        self.prefetcher.stats()
+    }
+
+    fn keeps_page(&self, page_id: PageId) -> bool {
+        self.zone_filter.as_ref().is_none_or(|filter| filter.keep(page_id))
+            && self.bloom_probe.as_ref().is_none_or(|probe| probe.keep(page_id))
+            && self.bbox_filter.as_ref().is_none_or(|filter| filter.keep(page_id))
+    }
+
+    // Prefetched pages were read before the current page was returned. Changes to them in between are not seen
+    // by this iterator (the same as for a page that is changed after it was returned).
+    fn read_next_pages(&mut self) -> Result<Page, StoreError> {
+        let page_id = self.current_page_id;
+        let ahead = self.prefetcher.readahead().min((self.total_pages - page_id) as usize);
+        // a sample skips pages, so the next pages are not read ahead
+        if ahead == 0 || self.consistency == ReadConsistency::ReadUncommitted || self.sampler.is_some() {
+            return self.store.read_page(self.layout, page_id, self.table);
         }
+
+        // the pages skipped by the zone filter, the bloom filters or the bounding boxes are not read ahead either
+        let page_ids: Vec<PageId> = (page_id..=self.total_pages)
+            .filter(|id| self.keeps_page(*id))
+            .take(ahead + 1)
+            .collect();
+        let mut pages: VecDeque<Page> = match self.store.read_pages(self.layout, &page_ids, self.table) {
+            Ok(pages) => pages.into(),
+            // e.g. the table has shrunk: the error handling of a single page read decides
+            Err(_) => return self.store.read_page(self.layout, page_id, self.table),
+        };
+        self.prefetcher.record_prefetched(page_ids.len() - 1);
+        let page = pages.pop_front().ok_or(StoreError::IoError(format!("Page {} not returned by read_pages", page_id)))?;
+        self.readahead = pages;
+        Ok(page)
     }
 }
 
 impl<'db, S: Store> Iterator for PageIterator<'db, S> {
-    type Item = Page<'db>;
+    type Item = Result<Page, StoreError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.current_page_id > self.total_pages {
+        if !self.done && self.current_page_id > self.total_pages && self.consistency == ReadConsistency::ReadUncommitted {
+            // pages allocated since the scan started
+            match self.store.read_metadata(self.layout, self.table) {
+                Ok(metadata) => self.total_pages = metadata.number_of_pages(),
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                },
+            }
+        }
+        if !self.done && let Some(sampler) = &mut self.sampler {
+            while self.current_page_id <= self.total_pages && !sampler.keep() {
+                self.current_page_id += 1;
+            }
+        }
+        if !self.done && (self.zone_filter.is_some() || self.bloom_probe.is_some() || self.bbox_filter.is_some()) {
+            while self.current_page_id <= self.total_pages && !self.keeps_page(self.current_page_id) {
+                self.current_page_id += 1;
+            }
+        }
+        if self.done || self.current_page_id > self.total_pages {
             return None;
         }
-        let page = self.store.read_page(self.layout, self.current_page_id, self.table).unwrap();
 
-        self.current_page_id += 1;
-        Some(page)
+        self.prefetcher.record_access(self.current_page_id);
+        let next = match self.readahead.pop_front() {
+            Some(page) => Ok(page),
+            None => self.read_next_pages(),
+        };
+
+        match next {
+            Ok(page) => {
+                self.current_page_id += 1;
+                Some(Ok(page))
+            },
+            Err(err) => {
+                self.done = true;
+                // The table may have shrunk since the snapshot was taken.
+                // In this case the page is just gone and the iteration is finished.
+                match self.store.read_metadata(self.layout, self.table) {
+                    Ok(metadata) if metadata.number_of_pages() < self.current_page_id => None,
+                    _ => Some(Err(err)),
+                }
+            }
+        }
     }
 }
 
+// Decodes the rows of a page batch by batch (see RowBatch).
+// next() returns the rows of the current batch one by one, next_batch() the whole batch.
 pub struct PageRowIterator {
     record_iterator: RecordIterator,
     schema: TableSchema,
+    batch_size: usize,
+    current: Option<RowBatchRows>,
 }
 
 impl PageRowIterator {
     pub fn new(page: Page, schema: TableSchema) -> Self {
+        Self::with_batch_size(page, schema, BATCH_SIZE)
+    }
+
+    pub fn with_batch_size(page: Page, schema: TableSchema, batch_size: usize) -> Self {
         Self { 
             record_iterator: page.record_iterator(),
-            schema 
+            schema,
+            batch_size: batch_size.max(1),
+            current: None,
+        }
+    }
+
+    /// The next batch of rows (or the rest of the current batch, if next() was called before)
+    pub fn next_batch(&mut self) -> Option<Result<RowBatch, StoreError>> {
+        if let Some(rows) = self.current.take() {
+            let mut batch = RowBatch::new(&self.schema, self.batch_size);
+            for (record, row) in rows {
+                if let Err(err) = batch.push(record, row) {
+                    return Some(Err(err));
+                }
+            }
+            if !batch.is_empty() {
+                return Some(Ok(batch));
+            }
+        }
+        self.decode_next_batch()
+    }
+
+    fn decode_next_batch(&mut self) -> Option<Result<RowBatch, StoreError>> {
+        match RowBatch::decode(&mut self.record_iterator, &self.schema, self.batch_size) {
+            Ok(batch) => (!batch.is_empty()).then_some(Ok(batch)),
+            Err(err) => Some(Err(err)),
         }
     }
 }
 
 impl Iterator for PageRowIterator {
     // Record is needed for accessing a slot directly (e.g., when we want to delete a row)
-    type Item = (Record, Row);
+    type Item = Result<(Record, Row), StoreError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.record_iterator.next()
-            .map(|r| {
-                let row = Row::deserialize(r.data(), &self.schema);
-
-                (r, row)
-            })
+        loop {
+            if let Some(row) = self.current.as_mut().and_then(|rows| rows.next()) {
+                return Some(Ok(row));
+            }
+            match self.decode_next_batch()? {
+                Ok(batch) => self.current = Some(batch.into_rows()),
+                Err(err) => return Some(Err(err)),
+            }
+        }
     }
 }
 
@@ -172,6 +546,18 @@ pub enum StoreError {
     DeserializationError(String),
     #[error("StoreError - Cannot read BTreeStore: {0}")]
     ReadBTreeStoreError(String),
+    #[error("StoreError - Store is read only")]
+    ReadOnly,
+    #[error("StoreError - Quota exceeded: {0}")]
+    QuotaExceeded(String),
+    #[error("StoreError - Failpoint '{0}' triggered")]
+    Injected(String),
+    #[error("StoreError - Checksum mismatch: {0}")]
+    ChecksumMismatch(String),
+    #[error("StoreError - Layout mismatch: {0}")]
+    LayoutMismatch(String),
+    #[error("StoreError - Unknown file format: {0}")]
+    UnknownFormat(String),
 }
 
 impl From<std::io::Error> for StoreError {
@@ -180,6 +566,23 @@ impl From<std::io::Error> for StoreError {
     }
 }
 
+impl From<PageError> for StoreError {
+    fn from(err: PageError) -> Self {
+        match err {
+            PageError::ChecksumMismatch => StoreError::ChecksumMismatch(err.to_string()),
+            PageError::LayoutMismatch(msg) => StoreError::LayoutMismatch(msg),
+            PageError::UnknownFormat(msg) => StoreError::UnknownFormat(msg),
+            err => StoreError::DeserializationError(err.to_string()),
+        }
+    }
+}
+
+impl From<CellDeserializationError> for StoreError {
+    fn from(err: CellDeserializationError) -> Self {
+        StoreError::DeserializationError(err.to_string())
+    }
+}
+
 impl From<BTreeStoreError> for StoreError {
     fn from(err: BTreeStoreError) -> Self {
         StoreError::ReadBTreeStoreError(err.to_string())
@@ -0,0 +1,284 @@
+use std::{cell::{Cell, RefCell}, collections::HashMap};
+
+use crate::{data::page::{Page, PageDataLayout, PageFileMetadata, PageId}, store::{IoStats, Quota, Store, StoreError, timed_store::StoreMetrics, zone_map::ZoneMap, bounding_box::BoundingBoxes, bloom_filter::BloomFilters, free_space::FreeSpaceMap}, table::table::Table, tree::store::BTreeStore};
+
+// Simple buffer pool: keeps up to `capacity` pages of all tables in memory.
+// - write-through: every write goes to the inner store immediately, so cached pages are never dirty
+// - eviction: least recently used page (found by a linear search, fine for a few thousand pages).
+//   Pinned pages (see PageGuard) are not evicted, while all pages are pinned the pool grows beyond its capacity.
+// - metadata and B-trees are not cached
+pub struct CachedStore<S: Store> {
+    inner: S,
+    capacity: usize,
+    // (table id, page id) => (page, last access)
+    pages: RefCell<HashMap<(i32, PageId), (Page, u64)>>,
+    // (table id, page id) => number of pins
+    pins: RefCell<HashMap<(i32, PageId), usize>>,
+    clock: Cell<u64>,
+    hits: Cell<u64>,
+}
+
+impl<S: Store> CachedStore<S> {
+    pub fn new(inner: S, capacity: usize) -> Self {
+        Self {
+            inner,
+            capacity,
+            pages: RefCell::new(HashMap::new()),
+            pins: RefCell::new(HashMap::new()),
+            clock: Cell::new(0),
+            hits: Cell::new(0),
+        }
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn contains(&self, table: &Table, page_id: PageId) -> bool {
+        self.pages.borrow().contains_key(&(table.id(), page_id))
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.set(self.clock.get() + 1);
+        self.clock.get()
+    }
+
+    fn put(&self, table: &Table, page: &Page) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut pages = self.pages.borrow_mut();
+        let pins = self.pins.borrow();
+        let key = (table.id(), page.page_id());
+        while !pages.contains_key(&key) && pages.len() >= self.capacity {
+            let lru = pages.iter()
+                .filter(|(key, _)| !pins.contains_key(key))
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| *key);
+            match lru {
+                Some(lru) => pages.remove(&lru),
+                None => break,
+            };
+        }
+        pages.insert(key, (page.clone(), self.tick()));
+    }
+
+    fn get(&self, table: &Table, page_id: PageId) -> Option<Page> {
+        let tick = self.tick();
+        self.pages.borrow_mut().get_mut(&(table.id(), page_id))
+            .map(|(page, last_used)| {
+                *last_used = tick;
+                page.clone()
+            })
+    }
+
+    fn evict_table(&self, table: &Table) {
+        self.pages.borrow_mut().retain(|(t_id, _), _| *t_id != table.id());
+    }
+
+    // the pages behind the end of a truncated table
+    fn evict_pages_after(&self, table: &Table, number_of_pages: PageId) {
+        self.pages.borrow_mut().retain(|(t_id, page_id), _| *t_id != table.id() || *page_id <= number_of_pages);
+    }
+}
+
+impl<S: Store> Store for CachedStore<S> {
+    fn read_btree(&self, btree_id: i32) -> Result<BTreeStore, StoreError> {
+        self.inner.read_btree(btree_id)
+    }
+
+    fn delete_all(&self) -> Result<(), StoreError> {
+        self.pages.borrow_mut().clear();
+        self.inner.delete_all()
+    }
+
+    fn create(&self, layout: &PageDataLayout, table: &Table) -> Result<(), StoreError> {
+        self.evict_table(table);
+        self.inner.create(layout, table)
+    }
+
+    fn delete(&self, table: &Table) -> Result<(), StoreError> {
+        self.evict_table(table);
+        self.inner.delete(table)
+    }
+
+    fn read_metadata(&self, layout: &PageDataLayout, table: &Table) -> Result<PageFileMetadata, StoreError> {
+        self.inner.read_metadata(layout, table)
+    }
+
+    fn truncate(&self, layout: &PageDataLayout, table: &Table, number_of_pages: PageId) -> Result<PageId, StoreError> {
+        self.evict_pages_after(table, number_of_pages);
+        self.inner.truncate(layout, table, number_of_pages)
+    }
+
+    fn read_page_size(&self, table: &Table) -> Result<usize, StoreError> {
+        self.inner.read_page_size(table)
+    }
+
+    fn read_page(&self, layout: &PageDataLayout, page_id: PageId, table: &Table) -> Result<Page, StoreError> {
+        if let Some(page) = self.get(table, page_id) {
+            self.hits.set(self.hits.get() + 1);
+            return Ok(page);
+        }
+        let page = self.inner.read_page(layout, page_id, table)?;
+        self.put(table, &page);
+        Ok(page)
+    }
+
+    fn read_pages(&self, layout: &PageDataLayout, page_ids: &[PageId], table: &Table) -> Result<Vec<Page>, StoreError> {
+        let missing: Vec<PageId> = page_ids.iter()
+            .copied()
+            .filter(|page_id| !self.contains(table, *page_id))
+            .collect();
+        self.hits.set(self.hits.get() + (page_ids.len() - missing.len()) as u64);
+        for page in self.inner.read_pages(layout, &missing, table)? {
+            self.put(table, &page);
+        }
+
+        // pages can be evicted again, if page_ids has more pages than the capacity
+        page_ids.iter()
+            .map(|page_id| match self.get(table, *page_id) {
+                Some(page) => Ok(page),
+                None => self.inner.read_page(layout, *page_id, table),
+            })
+            .collect()
+    }
+
+    fn write_page(&self, layout: &PageDataLayout, page: &Page, table: &Table) -> Result<(), StoreError> {
+        self.inner.write_page(layout, page, table)?;
+        self.put(table, page);
+        Ok(())
+    }
+
+    fn write_pages(&self, layout: &PageDataLayout, pages: &[&Page], table: &Table) -> Result<(), StoreError> {
+        self.inner.write_pages(layout, pages, table)?;
+        for page in pages {
+            self.put(table, page);
+        }
+        Ok(())
+    }
+
+    fn allocate_page(&self, layout: &PageDataLayout, table: &Table) -> Result<Page, StoreError> {
+        let page = self.inner.allocate_page(layout, table)?;
+        self.put(table, &page);
+        Ok(page)
+    }
+
+    fn quota(&self) -> Quota {
+        self.inner.quota()
+    }
+
+    fn disk_size(&self, table: &Table) -> Result<u64, StoreError> {
+        self.inner.disk_size(table)
+    }
+
+    fn btree_disk_size(&self, btree_id: i32) -> Result<u64, StoreError> {
+        self.inner.btree_disk_size(btree_id)
+    }
+
+    fn read_zone_map(&self, table: &Table) -> Result<Option<ZoneMap>, StoreError> {
+        self.inner.read_zone_map(table)
+    }
+
+    fn read_bounding_boxes(&self, table: &Table) -> Result<Option<BoundingBoxes>, StoreError> {
+        self.inner.read_bounding_boxes(table)
+    }
+
+    fn moved_records(&self, table: &Table) -> Result<Vec<(PageId, usize)>, StoreError> {
+        self.inner.moved_records(table)
+    }
+
+    fn finish_upgrade(&self, table: &Table) -> Result<(), StoreError> {
+        self.inner.finish_upgrade(table)
+    }
+
+    fn read_free_space(&self, table: &Table) -> Result<Option<FreeSpaceMap>, StoreError> {
+        self.inner.read_free_space(table)
+    }
+
+    fn create_bloom_filter(&self, layout: &PageDataLayout, table: &Table, column_id: i32) -> Result<(), StoreError> {
+        self.inner.create_bloom_filter(layout, table, column_id)
+    }
+
+    fn drop_bloom_filter(&self, table: &Table) -> Result<(), StoreError> {
+        self.inner.drop_bloom_filter(table)
+    }
+
+    fn read_bloom_filter(&self, table: &Table) -> Result<Option<BloomFilters>, StoreError> {
+        self.inner.read_bloom_filter(table)
+    }
+
+    fn io_stats(&self) -> IoStats {
+        IoStats {
+            cache_hits: self.hits.get(),
+            ..self.inner.io_stats()
+        }
+    }
+
+    fn metrics(&self) -> StoreMetrics {
+        self.inner.metrics()
+    }
+
+    fn buffer_pool_pages(&self) -> Vec<(i32, PageId, bool)> {
+        let mut pages: Vec<(i32, PageId, bool)> = self.pages.borrow().keys()
+            .map(|(t_id, page_id)| (*t_id, *page_id, false))
+            .collect();
+        pages.sort();
+        pages
+    }
+
+    fn buffer_pool_capacity(&self) -> Option<usize> {
+        Some(self.capacity)
+    }
+
+    fn pin_page(&self, table: &Table, page_id: PageId) {
+        *self.pins.borrow_mut().entry((table.id(), page_id)).or_insert(0) += 1;
+    }
+
+    fn unpin_page(&self, table: &Table, page_id: PageId) {
+        let mut pins = self.pins.borrow_mut();
+        if let Some(count) = pins.get_mut(&(table.id(), page_id)) {
+            *count -= 1;
+            if *count == 0 {
+                pins.remove(&(table.id(), page_id));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{data::page::PageDataLayout, store::{IoStats, Store, file_store::FileStore, page_cache::CachedStore}, table::{Column, ColumnType, TableSchema, table::Table}};
+
+    #[test]
+    fn should_cache_pages_and_evict_least_recently_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = CachedStore::new(FileStore::new(dir.path()), 2);
+        let layout = PageDataLayout::new(128).unwrap();
+        let table = Table::new(1, "test".to_owned(), TableSchema::new(vec![Column::new(1, "id", ColumnType::Int)]));
+        store.create(&layout, &table).unwrap();
+
+        for _ in 0..3 {
+            store.allocate_page(&layout, &table).unwrap();
+        }
+        // page 1 was evicted by page 3
+        assert_eq!(store.buffer_pool_pages(), vec![(1, 2, false), (1, 3, false)]);
+
+        store.read_page(&layout, 2, &table).unwrap();
+        store.read_page(&layout, 1, &table).unwrap();
+        assert_eq!(store.buffer_pool_pages(), vec![(1, 1, false), (1, 2, false)]);
+
+        // write-through: the file has the new content
+        let mut page = store.read_page(&layout, 1, &table).unwrap();
+        page.insert_record(vec![1, 2, 3, 4]).unwrap();
+        store.write_page(&layout, &page, &table).unwrap();
+        assert_eq!(store.inner().read_page(&layout, 1, &table).unwrap().read_slot(0), Some([1, 2, 3, 4].as_slice()));
+        assert_eq!(store.read_page(&layout, 1, &table).unwrap().read_slot(0), Some([1, 2, 3, 4].as_slice()));
+
+        // writes: 3 allocations and 1 update, reads: the miss of page 1 and the read via inner()
+        assert_eq!(store.io_stats(), IoStats { pages_read: 2, pages_written: 4, cache_hits: 3 });
+
+        store.delete(&table).unwrap();
+        assert!(store.buffer_pool_pages().is_empty());
+    }
+}
@@ -0,0 +1,126 @@
+use std::ops::{Deref, DerefMut};
+
+use crate::{data::page::{Page, PageDataLayout, PageId}, store::{Store, StoreError}, table::table::Table};
+
+// A page that is read (or allocated) to be changed. The guard pins the page in the buffer pool as long as it lives
+// (see Store::pin_page, CachedStore doesn't evict it), and every mutable access marks it dirty.
+// - write() writes a dirty page and returns the error, a guard that is dropped writes its dirty page, too
+//   (the error is lost then, so the callers that can handle it call write())
+// - discard() drops the changes: for the errors between the change of the page and its write
+//   (e.g. a duplicate value in a unique index), there are no transactions to roll them back
+// - a page that was not changed is not written
+// - during a panic, nothing is written
+pub struct PageGuard<'s, S: Store> {
+    store: &'s S,
+    layout: &'s PageDataLayout,
+    table: &'s Table,
+    page: Page,
+    dirty: bool,
+    released: bool,
+}
+
+impl<'s, S: Store> PageGuard<'s, S> {
+    pub fn read(store: &'s S, layout: &'s PageDataLayout, page_id: PageId, table: &'s Table) -> Result<Self, StoreError> {
+        let page = store.read_page(layout, page_id, table)?;
+        Ok(Self::new(store, layout, table, page))
+    }
+
+    /// A new page at the end of the table (allocate_page has written it empty)
+    pub fn allocate(store: &'s S, layout: &'s PageDataLayout, table: &'s Table) -> Result<Self, StoreError> {
+        let page = store.allocate_page(layout, table)?;
+        Ok(Self::new(store, layout, table, page))
+    }
+
+    /// Guard for a page that was read before
+    pub(crate) fn new(store: &'s S, layout: &'s PageDataLayout, table: &'s Table, page: Page) -> Self {
+        store.pin_page(table, page.page_id());
+        Self { store, layout, table, page, dirty: false, released: false }
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Writes the page, if it was changed
+    pub fn write(mut self) -> Result<(), StoreError> {
+        self.released = true;
+        match self.dirty {
+            true => self.store.write_page(self.layout, &self.page, self.table),
+            false => Ok(()),
+        }
+    }
+
+    /// Drops the changes, the page is not written
+    pub fn discard(mut self) {
+        self.released = true;
+    }
+}
+
+impl<S: Store> Deref for PageGuard<'_, S> {
+    type Target = Page;
+
+    fn deref(&self) -> &Page {
+        &self.page
+    }
+}
+
+impl<S: Store> DerefMut for PageGuard<'_, S> {
+    fn deref_mut(&mut self) -> &mut Page {
+        self.dirty = true;
+        &mut self.page
+    }
+}
+
+impl<S: Store> Drop for PageGuard<'_, S> {
+    fn drop(&mut self) {
+        if !self.released && self.dirty && !std::thread::panicking() {
+            let _ = self.store.write_page(self.layout, &self.page, self.table);
+        }
+        self.store.unpin_page(self.table, self.page.page_id());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{data::page::PageDataLayout, store::{Store, file_store::FileStore, page_cache::CachedStore, page_guard::PageGuard}, table::{Column, ColumnType, TableSchema, table::Table}};
+
+    #[test]
+    fn should_pin_the_page_and_write_it_only_if_it_was_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = CachedStore::new(FileStore::new(dir.path()), 1);
+        let layout = PageDataLayout::new(128).unwrap();
+        let table = Table::new(1, "test".to_owned(), TableSchema::new(vec![Column::new(1, "id", ColumnType::Int)]));
+        store.create(&layout, &table).unwrap();
+        let written = || store.io_stats().pages_written;
+
+        let mut guard = PageGuard::allocate(&store, &layout, &table).unwrap();
+        assert!(!guard.is_dirty());
+        // the pinned page 1 is not evicted by page 2, the pool grows beyond its capacity
+        store.allocate_page(&layout, &table).unwrap();
+        assert_eq!(store.buffer_pool_pages(), vec![(1, 1, false), (1, 2, false)]);
+        guard.insert_record(vec![1, 2, 3]).unwrap();
+        assert!(guard.is_dirty());
+        let before = written();
+        guard.write().unwrap();
+        assert_eq!(written(), before + 1);
+
+        // not changed: nothing is written
+        let guard = PageGuard::read(&store, &layout, 1, &table).unwrap();
+        assert_eq!(guard.read_slot(0), Some([1, 2, 3].as_slice()));
+        drop(guard);
+        assert_eq!(written(), before + 1);
+
+        // dropped: the change is written, discarded: it is not
+        PageGuard::read(&store, &layout, 1, &table).unwrap().insert_record(vec![4]).unwrap();
+        assert_eq!(written(), before + 2);
+        let mut guard = PageGuard::read(&store, &layout, 1, &table).unwrap();
+        guard.delete_record(0);
+        guard.discard();
+        let page = store.inner().read_page(&layout, 1, &table).unwrap();
+        assert_eq!((page.read_slot(0), page.read_slot(1)), (Some([1, 2, 3].as_slice()), Some([4].as_slice())));
+
+        // unpinned: the pages are evicted again
+        store.allocate_page(&layout, &table).unwrap();
+        assert_eq!(store.buffer_pool_pages(), vec![(1, 3, false)]);
+    }
+}
@@ -0,0 +1,182 @@
+use crate::database::NULL_INT;
+
+// Comparison predicates over the Int vectors of a RowBatch.
+// The result is a bitmask (one bit per row), so predicates can be combined with and/or over whole words.
+//
+// With the feature "simd" the comparison runs with SSE2 on x86_64 (4 values per instruction),
+// otherwise (and on other targets) a plain loop over 64 values per mask word, which the compiler vectorizes in most cases.
+// There is no std::simd on stable Rust yet.
+//
+// NULL (NULL_INT) never matches, also not for Ne.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CompareOp {
+    fn matches(&self, a: i32, b: i32) -> bool {
+        match self {
+            CompareOp::Eq => a == b,
+            CompareOp::Ne => a != b,
+            CompareOp::Lt => a < b,
+            CompareOp::Le => a <= b,
+            CompareOp::Gt => a > b,
+            CompareOp::Ge => a >= b,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectionMask {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl SelectionMask {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_selected(&self, index: usize) -> bool {
+        index < self.len && self.words[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    pub fn count(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn and(&self, other: &SelectionMask) -> SelectionMask {
+        self.combine(other, |a, b| a & b)
+    }
+
+    pub fn or(&self, other: &SelectionMask) -> SelectionMask {
+        self.combine(other, |a, b| a | b)
+    }
+
+    fn combine<F: Fn(u64, u64) -> u64>(&self, other: &SelectionMask, f: F) -> SelectionMask {
+        assert_eq!(self.len, other.len, "Masks of different batches cannot be combined");
+        SelectionMask {
+            words: self.words.iter().zip(other.words.iter()).map(|(a, b)| f(*a, *b)).collect(),
+            len: self.len,
+        }
+    }
+
+    /// Indices of the selected rows in ascending order
+    pub fn indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, word)| {
+            let mut word = *word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(i * 64 + bit)
+            })
+        })
+    }
+}
+
+pub fn compare_int(values: &[i32], op: CompareOp, value: i32) -> SelectionMask {
+    let words = values.chunks(64)
+        .map(|chunk| compare_chunk(chunk, op, value) & !compare_chunk(chunk, CompareOp::Eq, NULL_INT))
+        .collect();
+    SelectionMask { words, len: values.len() }
+}
+
+#[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+fn compare_chunk(chunk: &[i32], op: CompareOp, value: i32) -> u64 {
+    compare_chunk_scalar(chunk, op, value)
+}
+
+fn compare_chunk_scalar(chunk: &[i32], op: CompareOp, value: i32) -> u64 {
+    chunk.iter()
+        .enumerate()
+        .fold(0u64, |word, (i, v)| word | ((op.matches(*v, value) as u64) << i))
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+fn compare_chunk(chunk: &[i32], op: CompareOp, value: i32) -> u64 {
+    // SAFETY: SSE2 is part of every x86_64 CPU, so no runtime detection is needed
+    unsafe { compare_chunk_sse2(chunk, op, value) }
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+#[target_feature(enable = "sse2")]
+fn compare_chunk_sse2(chunk: &[i32], op: CompareOp, value: i32) -> u64 {
+    use std::arch::x86_64::{__m128i, _mm_castsi128_ps, _mm_cmpeq_epi32, _mm_cmpgt_epi32, _mm_cmplt_epi32, _mm_movemask_ps, _mm_set1_epi32, _mm_set_epi32};
+
+    // Ne, Le and Ge are the negation of Eq, Gt and Lt
+    let (base_op, negate) = match op {
+        CompareOp::Eq => (CompareOp::Eq, false),
+        CompareOp::Ne => (CompareOp::Eq, true),
+        CompareOp::Lt => (CompareOp::Lt, false),
+        CompareOp::Ge => (CompareOp::Lt, true),
+        CompareOp::Gt => (CompareOp::Gt, false),
+        CompareOp::Le => (CompareOp::Gt, true),
+    };
+
+    let needle = _mm_set1_epi32(value);
+    let mut word = 0u64;
+    let lanes = chunk.chunks_exact(4);
+    let rest = lanes.remainder();
+    for (i, lane) in lanes.enumerate() {
+        let values = _mm_set_epi32(lane[3], lane[2], lane[1], lane[0]);
+        let cmp: __m128i = match base_op {
+            CompareOp::Eq => _mm_cmpeq_epi32(values, needle),
+            CompareOp::Lt => _mm_cmplt_epi32(values, needle),
+            _ => _mm_cmpgt_epi32(values, needle),
+        };
+        let mut bits = _mm_movemask_ps(_mm_castsi128_ps(cmp)) as u64;
+        if negate {
+            bits ^= 0b1111;
+        }
+        word |= bits << (i * 4);
+    }
+
+    // a full chunk has no rest (and shifting by 64 would overflow)
+    match rest.is_empty() {
+        true => word,
+        false => word | (compare_chunk_scalar(rest, op, value) << (chunk.len() - rest.len())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::NULL_INT, store::predicate::{CompareOp, compare_int, compare_chunk_scalar}};
+
+    #[test]
+    fn should_select_matching_rows() {
+        // length is not a multiple of 4 or 64
+        let values: Vec<i32> = (0..150).map(|i| (i * 37) % 23 - 11).collect();
+        let ops = [CompareOp::Eq, CompareOp::Ne, CompareOp::Lt, CompareOp::Le, CompareOp::Gt, CompareOp::Ge];
+        for op in ops {
+            let mask = compare_int(&values, op, 3);
+            let expected: Vec<usize> = values.iter().enumerate()
+                .filter(|(_, v)| op.matches(**v, 3))
+                .map(|(i, _)| i)
+                .collect();
+            assert_eq!(mask.indices().collect::<Vec<usize>>(), expected, "{:?}", op);
+            assert_eq!(mask.count(), expected.len());
+            assert_eq!(mask.words[0], compare_chunk_scalar(&values[..64], op, 3));
+        }
+
+        let values = vec![NULL_INT, 5, 7, NULL_INT, 9];
+        let ne = compare_int(&values, CompareOp::Ne, 7);
+        assert_eq!(ne.indices().collect::<Vec<usize>>(), vec![1, 4]);
+        let lt = compare_int(&values, CompareOp::Lt, 8);
+        assert_eq!(lt.indices().collect::<Vec<usize>>(), vec![1, 2]);
+        assert_eq!(ne.and(&lt).indices().collect::<Vec<usize>>(), vec![1]);
+        assert_eq!(ne.or(&lt).indices().collect::<Vec<usize>>(), vec![1, 2, 4]);
+        assert!(ne.is_selected(4) && !ne.is_selected(3) && !ne.is_selected(5));
+    }
+}
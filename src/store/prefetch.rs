@@ -0,0 +1,106 @@
+// Adaptive readahead for page iterators (similar to the readahead of Linux):
+// - a scan starts without prefetching, so that point lookups and scans that stop early don't read too much
+// - after SEQUENTIAL_THRESHOLD reads of consecutive pages, it switches to readahead and the window doubles
+//   with every prefetch until MAX_WINDOW
+// - a non-consecutive read switches back to no prefetching
+use crate::data::page::PageId;
+
+pub const SEQUENTIAL_THRESHOLD: usize = 2;
+pub const MIN_WINDOW: usize = 4;
+pub const MAX_WINDOW: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum PrefetchMode {
+    #[default]
+    None,
+    Readahead,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PrefetchStats {
+    pub mode: PrefetchMode,
+    pub pages_read: usize,
+    pub pages_prefetched: usize,
+    pub mode_switches: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct Prefetcher {
+    last_page_id: Option<PageId>,
+    sequential_reads: usize,
+    window: usize,
+    stats: PrefetchStats,
+}
+
+impl Prefetcher {
+    pub fn new() -> Self {
+        Self {
+            window: MIN_WINDOW,
+            ..Self::default()
+        }
+    }
+
+    /// Must be called for every page that is returned by the iterator (prefetched or not)
+    pub fn record_access(&mut self, page_id: PageId) {
+        self.stats.pages_read += 1;
+        if self.last_page_id.is_some_and(|last| last + 1 == page_id) {
+            self.sequential_reads += 1;
+        } else {
+            self.sequential_reads = 0;
+            self.window = MIN_WINDOW;
+        }
+        self.last_page_id = Some(page_id);
+
+        let mode = if self.sequential_reads >= SEQUENTIAL_THRESHOLD { PrefetchMode::Readahead } else { PrefetchMode::None };
+        if mode != self.stats.mode {
+            self.stats.mode = mode;
+            self.stats.mode_switches += 1;
+        }
+    }
+
+    /// Number of pages that should be read ahead now
+    pub fn readahead(&self) -> usize {
+        match self.stats.mode {
+            PrefetchMode::None => 0,
+            PrefetchMode::Readahead => self.window,
+        }
+    }
+
+    pub fn record_prefetched(&mut self, pages: usize) {
+        self.stats.pages_prefetched += pages;
+        self.window = (self.window * 2).min(MAX_WINDOW);
+    }
+
+    pub fn stats(&self) -> PrefetchStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::store::prefetch::{MAX_WINDOW, MIN_WINDOW, PrefetchMode, Prefetcher};
+
+    #[test]
+    fn should_switch_between_no_prefetch_and_readahead() {
+        let mut prefetcher = Prefetcher::new();
+
+        for page_id in [7, 3, 12] {
+            prefetcher.record_access(page_id);
+            assert_eq!(prefetcher.readahead(), 0);
+        }
+
+        prefetcher.record_access(13);
+        prefetcher.record_access(14);
+        assert_eq!(prefetcher.stats().mode, PrefetchMode::Readahead);
+        assert_eq!(prefetcher.readahead(), MIN_WINDOW);
+        for _ in 0..10 {
+            prefetcher.record_prefetched(prefetcher.readahead());
+        }
+        assert_eq!(prefetcher.readahead(), MAX_WINDOW);
+
+        prefetcher.record_access(2);
+        assert_eq!(prefetcher.readahead(), 0);
+        assert_eq!(prefetcher.stats().mode_switches, 2);
+        assert_eq!(prefetcher.stats().pages_read, 6);
+    }
+}
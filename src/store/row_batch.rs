@@ -0,0 +1,232 @@
+use std::vec;
+
+use crate::{data::page::Record, store::{StoreError, predicate::{CompareOp, SelectionMask, compare_int}}, table::{ColumnType, TableSchema, table::{Cell, Point, Row}}};
+
+// Rows of a page are decoded in batches into one vector per column (structure of arrays).
+// Predicates and aggregations can then run over a plain Vec<i32> instead of matching every Cell.
+pub const BATCH_SIZE: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnVector {
+    Int(Vec<i32>),
+    Varchar(Vec<String>),
+    Byte(Vec<u8>),
+    Blob(Vec<Vec<u8>>),
+    Point(Vec<Point>),
+}
+
+impl ColumnVector {
+    fn with_capacity(col_type: &ColumnType, capacity: usize) -> Self {
+        match col_type {
+            ColumnType::Int => ColumnVector::Int(Vec::with_capacity(capacity)),
+            ColumnType::Varchar(_) => ColumnVector::Varchar(Vec::with_capacity(capacity)),
+            ColumnType::Byte => ColumnVector::Byte(Vec::with_capacity(capacity)),
+            ColumnType::Blob => ColumnVector::Blob(Vec::with_capacity(capacity)),
+            ColumnType::Point => ColumnVector::Point(Vec::with_capacity(capacity)),
+        }
+    }
+
+    fn push(&mut self, cell: Cell) -> Result<(), StoreError> {
+        match (self, cell) {
+            (ColumnVector::Int(values), Cell::Int(v)) => values.push(v),
+            (ColumnVector::Varchar(values), Cell::Varchar(v)) => values.push(v),
+            (ColumnVector::Byte(values), Cell::Byte(v)) => values.push(v),
+            (ColumnVector::Blob(values), Cell::Blob(v)) => values.push(v),
+            (ColumnVector::Point(values), Cell::Point(v)) => values.push(v),
+            (vector, cell) => return Err(StoreError::DeserializationError(
+                format!("Cannot push {:?} into a column vector of type {:?}", cell, vector.column_type()))),
+        }
+        Ok(())
+    }
+
+    fn column_type(&self) -> ColumnType {
+        match self {
+            ColumnVector::Int(_) => ColumnType::Int,
+            ColumnVector::Varchar(_) => ColumnType::Varchar(0),
+            ColumnVector::Byte(_) => ColumnType::Byte,
+            ColumnVector::Blob(_) => ColumnType::Blob,
+            ColumnVector::Point(_) => ColumnType::Point,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            ColumnVector::Int(values) => values.len(),
+            ColumnVector::Varchar(values) => values.len(),
+            ColumnVector::Byte(values) => values.len(),
+            ColumnVector::Blob(values) => values.len(),
+            ColumnVector::Point(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn cell(&self, index: usize) -> Option<Cell> {
+        match self {
+            ColumnVector::Int(values) => values.get(index).map(|v| Cell::Int(*v)),
+            ColumnVector::Varchar(values) => values.get(index).map(|v| Cell::Varchar(v.clone())),
+            ColumnVector::Byte(values) => values.get(index).map(|v| Cell::Byte(*v)),
+            ColumnVector::Blob(values) => values.get(index).map(|v| Cell::Blob(v.clone())),
+            ColumnVector::Point(values) => values.get(index).map(|v| Cell::Point(*v)),
+        }
+    }
+
+    fn into_cells(self) -> Vec<Cell> {
+        match self {
+            ColumnVector::Int(values) => values.into_iter().map(Cell::Int).collect(),
+            ColumnVector::Varchar(values) => values.into_iter().map(Cell::Varchar).collect(),
+            ColumnVector::Byte(values) => values.into_iter().map(Cell::Byte).collect(),
+            ColumnVector::Blob(values) => values.into_iter().map(Cell::Blob).collect(),
+            ColumnVector::Point(values) => values.into_iter().map(Cell::Point).collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RowBatch {
+    // records[i] belongs to the values at index i of every column
+    records: Vec<Record>,
+    columns: Vec<ColumnVector>,
+}
+
+impl RowBatch {
+    pub fn new(schema: &TableSchema, capacity: usize) -> Self {
+        Self {
+            records: Vec::with_capacity(capacity),
+            columns: schema.columns.iter()
+                .map(|col| ColumnVector::with_capacity(&col.col_type, capacity))
+                .collect(),
+        }
+    }
+
+    /// Decodes up to max_rows records. The batch is empty if the records are exhausted.
+    pub fn decode<I: Iterator<Item = Record>>(records: &mut I, schema: &TableSchema, max_rows: usize) -> Result<Self, StoreError> {
+        let mut batch = Self::new(schema, max_rows);
+        for record in records.take(max_rows) {
+            let mut offset = 0;
+            for (col, vector) in schema.columns.iter().zip(batch.columns.iter_mut()) {
+                let (cell, bytes_read) = Cell::deserialize(record.data().get(offset..).unwrap_or_default(), col)
+                    .map_err(|err| err.at_offset(offset))?;
+                offset += bytes_read;
+                vector.push(cell)?;
+            }
+            batch.records.push(record);
+        }
+        Ok(batch)
+    }
+
+    /// Fails if the row doesn't have the types of the schema of the batch
+    pub fn push(&mut self, record: Record, row: Row) -> Result<(), StoreError> {
+        if row.cells().len() != self.columns.len() {
+            return Err(StoreError::DeserializationError(format!("Row has {} cells, the batch {} columns", row.cells().len(), self.columns.len())));
+        }
+        for (vector, cell) in self.columns.iter_mut().zip(row.cells().iter()) {
+            vector.push(cell.clone())?;
+        }
+        self.records.push(record);
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    pub fn columns(&self) -> &[ColumnVector] {
+        &self.columns
+    }
+
+    pub fn column(&self, col_index: usize) -> Option<&ColumnVector> {
+        self.columns.get(col_index)
+    }
+
+    /// Rows where the Int column matches `op value` (None if the column is not of type Int)
+    pub fn select(&self, col_index: usize, op: CompareOp, value: i32) -> Option<SelectionMask> {
+        match self.columns.get(col_index)? {
+            ColumnVector::Int(values) => Some(compare_int(values, op, value)),
+            _ => None,
+        }
+    }
+
+    pub fn row(&self, index: usize) -> Option<Row> {
+        if index >= self.len() {
+            return None;
+        }
+        Some(Row::new(self.columns.iter().filter_map(|vector| vector.cell(index)).collect()))
+    }
+
+    pub fn into_rows(self) -> RowBatchRows {
+        RowBatchRows {
+            records: self.records.into_iter(),
+            columns: self.columns.into_iter().map(|vector| vector.into_cells().into_iter()).collect(),
+        }
+    }
+}
+
+pub struct RowBatchRows {
+    records: vec::IntoIter<Record>,
+    columns: Vec<vec::IntoIter<Cell>>,
+}
+
+impl Iterator for RowBatchRows {
+    type Item = (Record, Row);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = self.records.next()?;
+        let cells = self.columns.iter_mut()
+            .filter_map(|column| column.next())
+            .collect();
+        Some((record, Row::new(cells)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{data::page::{Page, PageDataLayout}, store::{predicate::CompareOp, row_batch::{ColumnVector, RowBatch}}, table::{Column, ColumnType, TableSchema, table::{Cell, Row}}};
+
+    #[test]
+    fn should_decode_records_into_column_vectors() {
+        let schema = TableSchema::new(vec![
+            Column::new(1, "id", ColumnType::Int),
+            Column::new(2, "name", ColumnType::Varchar(20)),
+            Column::new(3, "flag", ColumnType::Byte),
+        ]);
+        let layout = PageDataLayout::new(256).unwrap();
+        let mut page = Page::new(&layout);
+        for (id, name) in [(1, "Alice"), (2, "Bob"), (3, "Carol")] {
+            let row = Row::new(vec![Cell::Int(id), Cell::Varchar(name.to_owned()), Cell::Byte(id as u8 % 2)]);
+            page.insert_record(row.serialize()).unwrap();
+        }
+        page.delete_record(1);
+
+        let mut records = page.record_iterator();
+        let batch = RowBatch::decode(&mut records, &schema, 1).unwrap();
+        assert_eq!(batch.len(), 1);
+        assert_eq!(batch.column(0), Some(&ColumnVector::Int(vec![1])));
+
+        let batch = RowBatch::decode(&mut records, &schema, 10).unwrap();
+        assert_eq!(batch.columns(), &[
+            ColumnVector::Int(vec![3]),
+            ColumnVector::Varchar(vec!["Carol".to_owned()]),
+            ColumnVector::Byte(vec![1]),
+        ]);
+        assert_eq!(*batch.records()[0].record_index(), 2);
+        assert_eq!(batch.row(0), Some(Row::new(vec![Cell::Int(3), Cell::Varchar("Carol".to_owned()), Cell::Byte(1)])));
+        assert_eq!(batch.row(1), None);
+        assert_eq!(batch.select(0, CompareOp::Ge, 3).unwrap().indices().collect::<Vec<usize>>(), vec![0]);
+        assert!(batch.select(1, CompareOp::Eq, 3).is_none());
+
+        let rows: Vec<Row> = batch.into_rows().map(|(_, row)| row).collect();
+        assert_eq!(rows, vec![Row::new(vec![Cell::Int(3), Cell::Varchar("Carol".to_owned()), Cell::Byte(1)])]);
+        assert!(RowBatch::decode(&mut records, &schema, 10).unwrap().is_empty());
+    }
+}
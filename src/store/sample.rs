@@ -0,0 +1,116 @@
+use crate::{data::page::PageDataLayout, store::{Store, StoreError}, table::table::Table};
+
+// Page-level Bernoulli sampling: every page of a table is read with the probability `fraction` and
+// all rows of a read page are returned. Only the sampled pages are read, but rows on one page are not
+// independent (e.g. inserted together), so a sample of a table ordered by a column is less representative
+// than a row-level sample of the same size.
+//
+// The pages are chosen by a pseudo random generator (SplitMix64) with the given seed: the same seed returns
+// the same pages as long as the table doesn't change.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SampleSize {
+    // probability for each page, 0 <= fraction <= 1
+    Fraction(f64),
+    // about n rows: the fraction is estimated from the number of pages and the rows of the first page
+    Rows(usize),
+}
+
+impl SampleSize {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            SampleSize::Fraction(fraction) if !(0.0..=1.0).contains(fraction) => {
+                Err(format!("Sample fraction must be between 0 and 1, got {}", fraction))
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PageSampler {
+    fraction: f64,
+    state: u64,
+}
+
+impl PageSampler {
+    pub fn new(fraction: f64, seed: u64) -> Self {
+        Self {
+            fraction: fraction.clamp(0.0, 1.0),
+            state: seed,
+        }
+    }
+
+    pub fn fraction(&self) -> f64 {
+        self.fraction
+    }
+
+    // decides for the next page whether it is read
+    pub fn keep(&mut self) -> bool {
+        // 53 random bits, uniform in [0, 1)
+        let value = (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        value < self.fraction
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+/// Sampler for the table, SampleSize::Rows reads the first page to estimate the rows per page
+pub fn page_sampler<S: Store>(store: &S, layout: &PageDataLayout, table: &Table, size: SampleSize, seed: u64) -> Result<PageSampler, StoreError> {
+    let fraction = match size {
+        SampleSize::Fraction(fraction) => fraction,
+        SampleSize::Rows(rows) => {
+            let pages = store.read_metadata(layout, table)?.number_of_pages();
+            if pages == 0 {
+                1.0
+            } else {
+                let rows_per_page = store.read_page(layout, 1, table)?.live_rows().max(1);
+                rows as f64 / (pages as f64 * rows_per_page as f64)
+            }
+        },
+    };
+    Ok(PageSampler::new(fraction, seed))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::store::sample::{PageSampler, SampleSize};
+
+    #[test]
+    fn should_keep_about_the_fraction_of_pages_and_repeat_with_the_same_seed() {
+        let kept = |seed| {
+            let mut sampler = PageSampler::new(0.25, seed);
+            (0..1000).filter(|_| sampler.keep()).count()
+        };
+        let n = kept(42);
+        assert!((200..300).contains(&n), "{}", n);
+        assert_eq!(kept(42), n);
+
+        let pages = |seed| {
+            let mut sampler = PageSampler::new(0.5, seed);
+            (0..64).map(|_| sampler.keep()).collect::<Vec<_>>()
+        };
+        assert_eq!(pages(7), pages(7));
+        assert_ne!(pages(7), pages(8));
+
+        let mut all = PageSampler::new(1.0, 1);
+        assert!((0..100).all(|_| all.keep()));
+        let mut none = PageSampler::new(0.0, 1);
+        assert!((0..100).all(|_| !none.keep()));
+    }
+
+    #[test]
+    fn should_reject_fractions_outside_of_0_and_1() {
+        assert!(SampleSize::Fraction(0.5).validate().is_ok());
+        assert!(SampleSize::Rows(10).validate().is_ok());
+        assert!(SampleSize::Fraction(1.5).validate().is_err());
+        assert!(SampleSize::Fraction(-0.1).validate().is_err());
+        assert!(SampleSize::Fraction(f64::NAN).validate().is_err());
+    }
+}
@@ -0,0 +1,344 @@
+use std::{cell::RefCell, collections::HashMap, time::{Duration, Instant}};
+
+use crate::{data::page::{Page, PageDataLayout, PageFileMetadata, PageId}, store::{IoStats, Quota, Store, StoreError, zone_map::ZoneMap, bounding_box::BoundingBoxes, bloom_filter::BloomFilters, free_space::FreeSpaceMap}, table::table::Table, tree::store::BTreeStore};
+
+// Measures the latency of every store operation, per operation and table:
+//   Database::new_with_store("db", TimedStore::new(FileStore::new(path)))
+// The time includes everything below the Store (file I/O and (de)serialization of pages), so a slow query
+// with fast store operations is CPU bound. Failed operations are measured, too.
+pub struct TimedStore<S: Store> {
+    inner: S,
+    metrics: RefCell<StoreMetrics>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum StoreOperation {
+    ReadBTree,
+    DeleteAll,
+    Create,
+    Delete,
+    ReadMetadata,
+    ReadPage,
+    ReadPages,
+    WritePage,
+    WritePages,
+    AllocatePage,
+    Truncate,
+    DiskSize,
+}
+
+// Buckets are powers of two in nanoseconds, so a quantile is exact up to a factor of 2.
+// That's good enough to tell microseconds (page cache) from milliseconds (disk).
+const BUCKETS: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LatencyHistogram {
+    buckets: [u64; BUCKETS],
+    count: u64,
+    total: Duration,
+    max: Duration,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self { buckets: [0; BUCKETS], count: 0, total: Duration::ZERO, max: Duration::ZERO }
+    }
+}
+
+impl LatencyHistogram {
+    pub fn record(&mut self, latency: Duration) {
+        let nanos = latency.as_nanos().min(u64::MAX as u128) as u64;
+        let bucket = (u64::BITS - nanos.leading_zeros()) as usize;
+        self.buckets[bucket.min(BUCKETS - 1)] += 1;
+        self.count += 1;
+        self.total += latency;
+        self.max = self.max.max(latency);
+    }
+
+    pub fn merge(&mut self, other: &LatencyHistogram) {
+        for (bucket, count) in self.buckets.iter_mut().zip(other.buckets.iter()) {
+            *bucket += count;
+        }
+        self.count += other.count;
+        self.total += other.total;
+        self.max = self.max.max(other.max);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        self.total / self.count as u32
+    }
+
+    /// Upper bound of the bucket of the quantile q (0.0..=1.0), never more than max
+    pub fn quantile(&self, q: f64) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                let upper = if bucket == 0 { 0 } else { u64::MAX >> (u64::BITS as usize - bucket) };
+                return Duration::from_nanos(upper).min(self.max);
+            }
+        }
+        self.max
+    }
+
+    pub fn p50(&self) -> Duration {
+        self.quantile(0.5)
+    }
+
+    pub fn p99(&self) -> Duration {
+        self.quantile(0.99)
+    }
+}
+
+/// Latencies per operation and table (None for operations without a table, like read_btree)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StoreMetrics {
+    operations: HashMap<(StoreOperation, Option<String>), LatencyHistogram>,
+}
+
+impl StoreMetrics {
+    pub fn record(&mut self, operation: StoreOperation, table: Option<&str>, latency: Duration) {
+        self.operations.entry((operation, table.map(str::to_owned)))
+            .or_default()
+            .record(latency);
+    }
+
+    pub fn get(&self, operation: StoreOperation, table: Option<&str>) -> Option<&LatencyHistogram> {
+        self.operations.get(&(operation, table.map(str::to_owned)))
+    }
+
+    /// All tables together
+    pub fn operation(&self, operation: StoreOperation) -> LatencyHistogram {
+        let mut histogram = LatencyHistogram::default();
+        for ((op, _), h) in self.operations.iter() {
+            if *op == operation {
+                histogram.merge(h);
+            }
+        }
+        histogram
+    }
+
+    /// Sorted by operation and table
+    pub fn entries(&self) -> Vec<(StoreOperation, Option<&str>, &LatencyHistogram)> {
+        let mut entries: Vec<_> = self.operations.iter()
+            .map(|((op, table), h)| (*op, table.as_deref(), h))
+            .collect();
+        entries.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+        entries
+    }
+
+    /// Time spent in the store
+    pub fn total(&self) -> Duration {
+        self.operations.values().map(|h| h.total()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+}
+
+impl<S: Store> TimedStore<S> {
+    pub fn new(inner: S) -> Self {
+        Self { inner, metrics: RefCell::new(StoreMetrics::default()) }
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn reset(&self) {
+        *self.metrics.borrow_mut() = StoreMetrics::default();
+    }
+
+    fn timed<T, F: FnOnce() -> T>(&self, operation: StoreOperation, table: Option<&Table>, f: F) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.metrics.borrow_mut().record(operation, table.map(|t| t.name()), start.elapsed());
+        result
+    }
+}
+
+impl<S: Store> Store for TimedStore<S> {
+    fn read_btree(&self, btree_id: i32) -> Result<BTreeStore, StoreError> {
+        self.timed(StoreOperation::ReadBTree, None, || self.inner.read_btree(btree_id))
+    }
+
+    fn delete_all(&self) -> Result<(), StoreError> {
+        self.timed(StoreOperation::DeleteAll, None, || self.inner.delete_all())
+    }
+
+    fn create(&self, layout: &PageDataLayout, table: &Table) -> Result<(), StoreError> {
+        self.timed(StoreOperation::Create, Some(table), || self.inner.create(layout, table))
+    }
+
+    fn delete(&self, table: &Table) -> Result<(), StoreError> {
+        self.timed(StoreOperation::Delete, Some(table), || self.inner.delete(table))
+    }
+
+    fn read_metadata(&self, layout: &PageDataLayout, table: &Table) -> Result<PageFileMetadata, StoreError> {
+        self.timed(StoreOperation::ReadMetadata, Some(table), || self.inner.read_metadata(layout, table))
+    }
+
+    fn read_page_size(&self, table: &Table) -> Result<usize, StoreError> {
+        self.timed(StoreOperation::ReadMetadata, Some(table), || self.inner.read_page_size(table))
+    }
+
+    fn read_page(&self, layout: &PageDataLayout, page_id: PageId, table: &Table) -> Result<Page, StoreError> {
+        self.timed(StoreOperation::ReadPage, Some(table), || self.inner.read_page(layout, page_id, table))
+    }
+
+    fn read_pages(&self, layout: &PageDataLayout, page_ids: &[PageId], table: &Table) -> Result<Vec<Page>, StoreError> {
+        self.timed(StoreOperation::ReadPages, Some(table), || self.inner.read_pages(layout, page_ids, table))
+    }
+
+    fn write_page(&self, layout: &PageDataLayout, page: &Page, table: &Table) -> Result<(), StoreError> {
+        self.timed(StoreOperation::WritePage, Some(table), || self.inner.write_page(layout, page, table))
+    }
+
+    fn write_pages(&self, layout: &PageDataLayout, pages: &[&Page], table: &Table) -> Result<(), StoreError> {
+        self.timed(StoreOperation::WritePages, Some(table), || self.inner.write_pages(layout, pages, table))
+    }
+
+    fn allocate_page(&self, layout: &PageDataLayout, table: &Table) -> Result<Page, StoreError> {
+        self.timed(StoreOperation::AllocatePage, Some(table), || self.inner.allocate_page(layout, table))
+    }
+
+    fn truncate(&self, layout: &PageDataLayout, table: &Table, number_of_pages: PageId) -> Result<PageId, StoreError> {
+        self.timed(StoreOperation::Truncate, Some(table), || self.inner.truncate(layout, table, number_of_pages))
+    }
+
+    fn quota(&self) -> Quota {
+        self.inner.quota()
+    }
+
+    fn buffer_pool_pages(&self) -> Vec<(i32, PageId, bool)> {
+        self.inner.buffer_pool_pages()
+    }
+
+    fn buffer_pool_capacity(&self) -> Option<usize> {
+        self.inner.buffer_pool_capacity()
+    }
+
+    fn pin_page(&self, table: &Table, page_id: PageId) {
+        self.inner.pin_page(table, page_id)
+    }
+
+    fn unpin_page(&self, table: &Table, page_id: PageId) {
+        self.inner.unpin_page(table, page_id)
+    }
+
+    fn disk_size(&self, table: &Table) -> Result<u64, StoreError> {
+        self.timed(StoreOperation::DiskSize, Some(table), || self.inner.disk_size(table))
+    }
+
+    fn btree_disk_size(&self, btree_id: i32) -> Result<u64, StoreError> {
+        self.timed(StoreOperation::DiskSize, None, || self.inner.btree_disk_size(btree_id))
+    }
+
+    fn read_zone_map(&self, table: &Table) -> Result<Option<ZoneMap>, StoreError> {
+        self.inner.read_zone_map(table)
+    }
+
+    fn read_bounding_boxes(&self, table: &Table) -> Result<Option<BoundingBoxes>, StoreError> {
+        self.inner.read_bounding_boxes(table)
+    }
+
+    fn moved_records(&self, table: &Table) -> Result<Vec<(PageId, usize)>, StoreError> {
+        self.inner.moved_records(table)
+    }
+
+    fn finish_upgrade(&self, table: &Table) -> Result<(), StoreError> {
+        self.inner.finish_upgrade(table)
+    }
+
+    fn read_free_space(&self, table: &Table) -> Result<Option<FreeSpaceMap>, StoreError> {
+        self.inner.read_free_space(table)
+    }
+
+    fn create_bloom_filter(&self, layout: &PageDataLayout, table: &Table, column_id: i32) -> Result<(), StoreError> {
+        self.inner.create_bloom_filter(layout, table, column_id)
+    }
+
+    fn drop_bloom_filter(&self, table: &Table) -> Result<(), StoreError> {
+        self.inner.drop_bloom_filter(table)
+    }
+
+    fn read_bloom_filter(&self, table: &Table) -> Result<Option<BloomFilters>, StoreError> {
+        self.inner.read_bloom_filter(table)
+    }
+
+    fn io_stats(&self) -> IoStats {
+        self.inner.io_stats()
+    }
+
+    fn metrics(&self) -> StoreMetrics {
+        self.metrics.borrow().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{database::Database, store::{file_store::FileStore, page_cache::CachedStore, timed_store::{LatencyHistogram, StoreOperation, TimedStore}}, table::{ColumnType, table::{Cell, Row}}};
+
+    #[test]
+    fn should_compute_quantiles() {
+        let mut histogram = LatencyHistogram::default();
+        assert_eq!(histogram.p99(), Duration::ZERO);
+        for _ in 0..98 {
+            histogram.record(Duration::from_micros(10));
+        }
+        histogram.record(Duration::from_millis(5));
+        histogram.record(Duration::from_millis(6));
+
+        assert_eq!(histogram.count(), 100);
+        // 10us lies in the bucket 8192ns..16383ns
+        assert_eq!(histogram.p50(), Duration::from_nanos(16383));
+        assert!(histogram.p99() >= Duration::from_millis(5) && histogram.p99() <= Duration::from_millis(6));
+        assert_eq!(histogram.quantile(1.0), Duration::from_millis(6));
+        assert_eq!(histogram.max(), Duration::from_millis(6));
+    }
+
+    #[test]
+    fn should_record_store_operations_per_table() {
+        let base_path = tempfile::tempdir().unwrap();
+        let db = Database::new_with_store("test_db", TimedStore::new(CachedStore::new(FileStore::new(base_path.path()), 16)));
+        db.drop_create().unwrap();
+        let table = db.create_table("persons", vec![("id", ColumnType::Int)]).unwrap();
+        let access = db.table_access(table).unwrap();
+        for i in 0..10 {
+            access.insert(&Row::new(vec![Cell::Int(i)])).unwrap();
+        }
+        access.find_all().unwrap().rows().unwrap();
+
+        let metrics = db.metrics();
+        assert!(metrics.get(StoreOperation::AllocatePage, Some("persons")).is_some_and(|h| h.count() == 1));
+        assert!(metrics.get(StoreOperation::WritePage, Some("persons")).is_some_and(|h| h.count() >= 10));
+        assert!(metrics.operation(StoreOperation::ReadMetadata).count() > 0);
+        assert!(metrics.total() > Duration::ZERO);
+        assert!(metrics.entries().windows(2).all(|w| (w[0].0, w[0].1) <= (w[1].0, w[1].1)));
+
+        let plain = Database::new_with_store("plain_db", FileStore::new(base_path.path()));
+        assert!(plain.metrics().is_empty());
+    }
+}
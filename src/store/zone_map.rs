@@ -0,0 +1,184 @@
+use crate::{data::page::{Page, PageId}, store::StoreError, table::{ColumnType, TableSchema, table::{Cell, Row}}};
+
+// Zone maps: the minimum and maximum of the Int columns per page. The FileStore keeps them in a sidecar file
+// next to the table file ('<table file>.zones') and updates the entry of a page whenever it writes the page,
+// so a scan for a value (TableAccess::find) can skip the pages whose range cannot contain it without reading them.
+// On data that is inserted in the order of a column (ids, timestamps), a lookup reads a few pages instead of all.
+//
+// File format (big endian): 4 bytes magic "PDBZ", 1 byte version, 1 byte reserved, 2 bytes number of columns,
+// then one entry per page at position page_id - 1: 1 byte state (0 = unknown), min and max (4 bytes each) per column.
+// - tracked are the Int columns that are not encrypted, in schema order (the range of an encrypted column would
+//   leak its values)
+// - a page without entry (written before the file existed or behind its end) or with rows that cannot be read
+//   with the schema is unknown and is always read
+// - NULL is NULL_INT, the smallest Int, so it is part of the range. A page without rows has min > max.
+
+const MAGIC: [u8; 4] = *b"PDBZ";
+const VERSION: u8 = 1;
+const HEADER_SIZE: usize = 8;
+const STATE_KNOWN: u8 = 1;
+
+/// Schema indexes of the columns that have a zone map
+pub fn tracked_columns(schema: &TableSchema) -> Vec<usize> {
+    schema.columns.iter()
+        .enumerate()
+        .filter(|(_, column)| column.col_type == ColumnType::Int && !column.encrypted)
+        .map(|(index, _)| index)
+        .collect()
+}
+
+pub(crate) fn header(columns: usize) -> Vec<u8> {
+    let mut buf = MAGIC.to_vec();
+    buf.push(VERSION);
+    buf.push(0);
+    buf.extend_from_slice(&(columns as u16).to_be_bytes());
+    buf
+}
+
+pub(crate) fn entry_size(columns: usize) -> usize {
+    1 + columns * 8
+}
+
+/// Position of the entry of the page in the sidecar file
+pub(crate) fn entry_offset(page_id: PageId, columns: usize) -> u64 {
+    HEADER_SIZE as u64 + page_id.saturating_sub(1) * entry_size(columns) as u64
+}
+
+/// The entry of the page, unknown if a row cannot be deserialized (e.g. the pages of a KvStore namespace)
+pub(crate) fn page_entry(page: &Page, schema: &TableSchema, columns: &[usize]) -> Vec<u8> {
+    let mut ranges = vec![(i32::MAX, i32::MIN); columns.len()];
+    for record in page.clone().record_iterator() {
+        let Ok(row) = Row::deserialize(record.data(), schema) else {
+            return vec![0; entry_size(columns.len())];
+        };
+        for (range, col_index) in ranges.iter_mut().zip(columns) {
+            if let Some(Cell::Int(value)) = row.cells().get(*col_index) {
+                *range = (range.0.min(*value), range.1.max(*value));
+            }
+        }
+    }
+
+    let mut buf = vec![STATE_KNOWN];
+    for (min, max) in ranges {
+        buf.extend_from_slice(&min.to_be_bytes());
+        buf.extend_from_slice(&max.to_be_bytes());
+    }
+    buf
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZoneMap {
+    columns: Vec<usize>,
+    // index page_id - 1: min and max per tracked column, None if unknown
+    pages: Vec<Option<Vec<(i32, i32)>>>,
+}
+
+impl ZoneMap {
+    /// Reads the sidecar file. None, if it was written for other columns than the schema has.
+    pub(crate) fn deserialize(buf: &[u8], schema: &TableSchema) -> Result<Option<Self>, StoreError> {
+        if buf.len() < HEADER_SIZE || buf[0..4] != MAGIC {
+            return Err(StoreError::UnknownFormat("not a playdb zone map".to_owned()));
+        }
+        if buf[4] != VERSION {
+            return Err(StoreError::UnknownFormat(format!("zone map version {} is not supported (expected {})", buf[4], VERSION)));
+        }
+        let columns = tracked_columns(schema);
+        if u16::from_be_bytes([buf[6], buf[7]]) as usize != columns.len() {
+            return Ok(None);
+        }
+
+        // a torn entry at the end is ignored, the page is unknown
+        let pages = buf[HEADER_SIZE..].chunks_exact(entry_size(columns.len()))
+            .map(|entry| {
+                (entry[0] == STATE_KNOWN).then(|| entry[1..].chunks_exact(8)
+                    .map(|range| (
+                        i32::from_be_bytes([range[0], range[1], range[2], range[3]]),
+                        i32::from_be_bytes([range[4], range[5], range[6], range[7]]),
+                    ))
+                    .collect())
+            })
+            .collect();
+
+        Ok(Some(Self { columns, pages }))
+    }
+
+    /// Min and max of the column on the page, None if unknown (or the column has no zone map).
+    /// For a page without rows min is greater than max.
+    pub fn range(&self, page_id: PageId, col_index: usize) -> Option<(i32, i32)> {
+        let position = self.columns.iter().position(|c| *c == col_index)?;
+        let ranges = self.pages.get(usize::try_from(page_id.checked_sub(1)?).ok()?)?.as_ref()?;
+        ranges.get(position).copied()
+    }
+
+    /// false only if the page certainly has no row with the value in the column
+    pub fn may_contain(&self, page_id: PageId, col_index: usize, value: i32) -> bool {
+        self.range(page_id, col_index)
+            .is_none_or(|(min, max)| min <= value && value <= max)
+    }
+
+    /// Filter for a PageIterator that skips the pages without the value, None if the column has no zone map
+    pub fn filter(self, col_index: usize, value: i32) -> Option<ZoneFilter> {
+        self.columns.contains(&col_index).then_some(ZoneFilter { zone_map: self, col_index, value })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ZoneFilter {
+    zone_map: ZoneMap,
+    col_index: usize,
+    value: i32,
+}
+
+impl ZoneFilter {
+    pub fn keep(&self, page_id: PageId) -> bool {
+        self.zone_map.may_contain(page_id, self.col_index, self.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{data::page::{Page, PageDataLayout}, database::NULL_INT, store::zone_map::{ZoneMap, header, page_entry, tracked_columns}, table::{Column, ColumnType, TableSchema, table::{Cell, Row}}};
+
+    #[test]
+    fn should_keep_min_and_max_of_the_int_columns() {
+        let schema = TableSchema::new(vec![
+            Column::new(1, "id", ColumnType::Int),
+            Column::new(2, "name", ColumnType::Varchar(10)),
+            Column::new(3, "number", ColumnType::Int),
+        ]);
+        let layout = PageDataLayout::new(256).unwrap();
+        let columns = tracked_columns(&schema);
+        assert_eq!(columns, vec![0, 2]);
+
+        let mut page = Page::new(&layout);
+        page.set_page_id(1);
+        for (id, number) in [(5, 10), (3, NULL_INT), (9, -4)] {
+            page.insert_record(Row::new(vec![Cell::Int(id), Cell::Varchar("x".to_owned()), Cell::Int(number)]).serialize()).unwrap();
+        }
+        let deleted = page.insert_record(Row::new(vec![Cell::Int(100), Cell::Varchar("x".to_owned()), Cell::Int(0)]).serialize()).unwrap();
+        page.delete_record(deleted);
+
+        let mut file = header(columns.len());
+        file.extend(page_entry(&page, &schema, &columns));
+        file.extend(page_entry(&Page::new(&layout), &schema, &columns));
+        // the pages of another table format are unknown
+        let mut garbage = Page::new(&layout);
+        garbage.insert_record(vec![1, 2]).unwrap();
+        file.extend(page_entry(&garbage, &schema, &columns));
+
+        let zone_map = ZoneMap::deserialize(&file, &schema).unwrap().unwrap();
+        assert_eq!(zone_map.range(1, 0), Some((3, 9)));
+        assert_eq!(zone_map.range(1, 2), Some((NULL_INT, 10)));
+        assert_eq!(zone_map.range(1, 1), None);
+        assert!(zone_map.may_contain(1, 0, 9) && !zone_map.may_contain(1, 0, 100) && !zone_map.may_contain(1, 0, 2));
+        assert!(!zone_map.may_contain(2, 0, 5), "a page without rows contains nothing");
+        assert!(zone_map.may_contain(3, 0, 5) && zone_map.may_contain(4, 0, 5), "unknown pages must be read");
+
+        let filter = zone_map.clone().filter(0, 4).unwrap();
+        assert!(filter.keep(1) && !filter.keep(2) && filter.keep(3));
+        assert!(zone_map.filter(1, 4).is_none());
+
+        let other_schema = TableSchema::new(vec![Column::new(1, "id", ColumnType::Int)]);
+        assert!(ZoneMap::deserialize(&file, &other_schema).unwrap().is_none());
+    }
+}
@@ -0,0 +1,142 @@
+use thiserror::Error;
+
+// Column encryption with ChaCha20-Poly1305 (AEAD, RFC 8439) of the RustCrypto crates (feature encryption).
+// An encrypted value is stored as: 12 bytes nonce, ciphertext (same length as the plain serialized cell), 16 bytes tag.
+// A wrong key or modified data fails the tag check, decrypt returns None.
+// The nonces come from the random number generator of the operating system (getrandom), never from the Rng of
+// the Database: a repeated nonce would reveal the plaintexts.
+//
+// The key check value identifies a key without revealing it (first bytes of the ChaCha20 keystream with a fixed nonce).
+// It is stored in the catalog for every table with encrypted columns, see database/encryption.rs.
+//
+// Without the feature, there are no keys (ColumnKey has no values), so tables with encrypted columns can't be
+// created or accessed (MissingEncryptionKey).
+pub const KEY_LEN: usize = 32;
+pub const NONCE_LEN: usize = 12;
+pub const TAG_LEN: usize = 16;
+pub const CHECK_VALUE_LEN: usize = 16;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum EncryptionError {
+    #[error("EncryptionError - no random nonce from the operating system: {0}")]
+    NoRandomness(String),
+    #[error("EncryptionError - encryption failed")]
+    Failed,
+}
+
+#[cfg(feature = "encryption")]
+pub use key::ColumnKey;
+
+#[cfg(feature = "encryption")]
+mod key {
+    use std::rc::Rc;
+
+    use chacha20::{ChaCha20, cipher::{KeyIvInit, StreamCipher}};
+    use chacha20poly1305::{ChaCha20Poly1305, KeyInit, aead::Aead};
+
+    use crate::table::encryption::{CHECK_VALUE_LEN, EncryptionError, KEY_LEN, NONCE_LEN};
+
+    // never used for a value: the nonces of the values are random
+    const CHECK_VALUE_NONCE: [u8; NONCE_LEN] = *b"playdb-kcv\0\0";
+
+    /// Per-database key for encrypted columns. Debug never prints the key.
+    #[derive(Clone)]
+    pub struct ColumnKey {
+        key: Rc<[u8; KEY_LEN]>,
+    }
+
+    impl PartialEq for ColumnKey {
+        fn eq(&self, other: &Self) -> bool {
+            self.key == other.key
+        }
+    }
+
+    impl std::fmt::Debug for ColumnKey {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("ColumnKey(***)")
+        }
+    }
+
+    impl ColumnKey {
+        pub fn new(key: [u8; KEY_LEN]) -> Self {
+            Self { key: Rc::new(key) }
+        }
+
+        pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+            let mut nonce = [0u8; NONCE_LEN];
+            getrandom::fill(&mut nonce).map_err(|e| EncryptionError::NoRandomness(e.to_string()))?;
+            let sealed = self.cipher().encrypt(&nonce.into(), plaintext)
+                .map_err(|_| EncryptionError::Failed)?;
+            let mut out = Vec::with_capacity(NONCE_LEN + sealed.len());
+            out.extend_from_slice(&nonce);
+            out.extend(sealed);
+            Ok(out)
+        }
+
+        /// None, if the key is wrong or the data was modified
+        pub fn decrypt(&self, data: &[u8]) -> Option<Vec<u8>> {
+            let nonce: [u8; NONCE_LEN] = data.get(..NONCE_LEN)?.try_into().ok()?;
+            self.cipher().decrypt(&nonce.into(), &data[NONCE_LEN..]).ok()
+        }
+
+        pub fn check_value(&self) -> [u8; CHECK_VALUE_LEN] {
+            let mut check_value = [0u8; CHECK_VALUE_LEN];
+            ChaCha20::new(self.key.as_ref().into(), &CHECK_VALUE_NONCE.into()).apply_keystream(&mut check_value);
+            check_value
+        }
+
+        fn cipher(&self) -> ChaCha20Poly1305 {
+            ChaCha20Poly1305::new(self.key.as_ref().into())
+        }
+    }
+}
+
+/// Without the feature encryption there are no keys
+#[cfg(not(feature = "encryption"))]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnKey {}
+
+#[cfg(not(feature = "encryption"))]
+impl ColumnKey {
+    pub fn encrypt(&self, _plaintext: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        match *self {}
+    }
+
+    pub fn decrypt(&self, _data: &[u8]) -> Option<Vec<u8>> {
+        match *self {}
+    }
+
+    pub fn check_value(&self) -> [u8; CHECK_VALUE_LEN] {
+        match *self {}
+    }
+}
+
+#[cfg(all(test, feature = "encryption"))]
+mod tests {
+    use crate::table::encryption::{ColumnKey, NONCE_LEN, TAG_LEN};
+
+    #[test]
+    fn should_encrypt_with_different_nonces() {
+        let key = ColumnKey::new([7; 32]);
+        let a = key.encrypt(b"secret").unwrap();
+        let b = key.encrypt(b"secret").unwrap();
+
+        assert_ne!(a, b);
+        assert_eq!(a.len(), NONCE_LEN + 6 + TAG_LEN);
+        assert_eq!(key.decrypt(&a).unwrap(), b"secret");
+        assert_eq!(ColumnKey::new([8; 32]).decrypt(&a), None, "wrong key");
+        let mut modified = a.clone();
+        modified[NONCE_LEN] ^= 1;
+        assert_eq!(key.decrypt(&modified), None, "modified ciphertext");
+        assert_eq!(key.decrypt(&a[..TAG_LEN]), None);
+        assert_ne!(key.check_value(), ColumnKey::new([8; 32]).check_value());
+        assert_eq!(format!("{:?}", key), "ColumnKey(***)");
+
+        // values and check values written by earlier versions stay readable
+        let stored = [1u8; 12].into_iter()
+            .chain([15, 47, 226, 101, 245, 145, 107, 92, 147, 239, 104, 208, 142, 148, 146, 168, 1, 58, 130, 195, 123, 75])
+            .collect::<Vec<u8>>();
+        assert_eq!(key.decrypt(&stored).unwrap(), b"secret");
+        assert_eq!(key.check_value(), [9, 170, 137, 86, 18, 191, 76, 72, 221, 13, 217, 219, 168, 160, 101, 221]);
+    }
+}
@@ -0,0 +1,147 @@
+use thiserror::Error;
+
+// Identifier rules for table and column names:
+// - unquoted: [A-Za-z_][A-Za-z0-9_]*, folded to lower case (Persons == persons == PERSONS)
+// - quoted: "..." keeps the case and allows any character, a quote inside is written as ""
+// - max. MAX_IDENTIFIER_LENGTH bytes after unquoting
+// - names starting with '_' are reserved for system objects (e.g. _stats_tables)
+pub const MAX_IDENTIFIER_LENGTH: usize = 63;
+pub const RESERVED_PREFIX: char = '_';
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Identifier {
+    name: String,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum IdentifierError {
+    #[error("Identifier must not be empty")]
+    Empty,
+    #[error("Identifier '{0}' is longer than {max} bytes", max = MAX_IDENTIFIER_LENGTH)]
+    TooLong(String),
+    #[error("Identifier '{0}' contains invalid character '{1}' (use quotes)")]
+    InvalidCharacter(String, char),
+    #[error("Identifier '{0}' has an unterminated quote")]
+    UnterminatedQuote(String),
+    #[error("Identifier '{0}' is reserved for system objects")]
+    Reserved(String),
+}
+
+impl Identifier {
+    /// Parses a raw (maybe quoted) identifier into its canonical form
+    pub fn parse(raw: &str) -> Result<Self, IdentifierError> {
+        let raw = raw.trim();
+        let name = if raw.starts_with('"') {
+            Self::unquote(raw)?
+        } else {
+            if let Some(c) = raw.chars().next().filter(|c| c.is_ascii_digit()) {
+                return Err(IdentifierError::InvalidCharacter(raw.to_owned(), c));
+            }
+            if let Some(c) = raw.chars().find(|c| !c.is_ascii_alphanumeric() && *c != '_') {
+                return Err(IdentifierError::InvalidCharacter(raw.to_owned(), c));
+            }
+            raw.to_ascii_lowercase()
+        };
+
+        if name.is_empty() {
+            return Err(IdentifierError::Empty);
+        }
+        if name.len() > MAX_IDENTIFIER_LENGTH {
+            return Err(IdentifierError::TooLong(name));
+        }
+
+        Ok(Self { name })
+    }
+
+    /// Same as parse, but rejects names that are reserved for system objects.
+    /// Used when user defined objects are created.
+    pub fn parse_user_defined(raw: &str) -> Result<Self, IdentifierError> {
+        let ident = Self::parse(raw)?;
+        if ident.is_reserved() {
+            return Err(IdentifierError::Reserved(ident.name));
+        }
+        Ok(ident)
+    }
+
+    /// Lookups should never fail because of the identifier rules, it's just not found then.
+    pub fn normalize(raw: &str) -> String {
+        Self::parse(raw)
+            .map(|i| i.name)
+            .unwrap_or_else(|_| raw.to_owned())
+    }
+
+    pub fn is_reserved(&self) -> bool {
+        self.name.starts_with(RESERVED_PREFIX)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.name
+    }
+
+    pub fn into_string(self) -> String {
+        self.name
+    }
+
+    /// The quoted form of a canonical name, parse returns the name unchanged
+    pub fn quote(name: &str) -> String {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+
+    fn unquote(raw: &str) -> Result<String, IdentifierError> {
+        let inner = raw.strip_prefix('"')
+            .and_then(|r| r.strip_suffix('"'))
+            .filter(|_| raw.len() >= 2)
+            .ok_or_else(|| IdentifierError::UnterminatedQuote(raw.to_owned()))?;
+
+        let mut name = String::new();
+        let mut chars = inner.chars();
+        while let Some(c) = chars.next() {
+            if c == '"' {
+                // only "" is allowed inside quotes
+                if chars.next() != Some('"') {
+                    return Err(IdentifierError::UnterminatedQuote(raw.to_owned()));
+                }
+            }
+            name.push(c);
+        }
+
+        Ok(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_fold_unquoted_identifiers_to_lower_case() {
+        assert_eq!(Identifier::parse("Persons").unwrap().as_str(), "persons");
+        assert_eq!(Identifier::parse("  PERSON_2 ").unwrap().as_str(), "person_2");
+    }
+
+    #[test]
+    fn should_keep_case_of_quoted_identifiers() {
+        assert_eq!(Identifier::parse("\"Persons\"").unwrap().as_str(), "Persons");
+        assert_eq!(Identifier::parse("\"my \"\"table\"\"\"").unwrap().as_str(), "my \"table\"");
+        assert_eq!(Identifier::parse(&Identifier::quote("my \"table\"")).unwrap().as_str(), "my \"table\"");
+    }
+
+    #[test]
+    fn should_reject_invalid_identifiers() {
+        assert_eq!(Identifier::parse(""), Err(IdentifierError::Empty));
+        assert_eq!(Identifier::parse("\"\""), Err(IdentifierError::Empty));
+        assert!(matches!(Identifier::parse("1abc"), Err(IdentifierError::InvalidCharacter(_, '1'))));
+        assert!(matches!(Identifier::parse("my table"), Err(IdentifierError::InvalidCharacter(_, ' '))));
+        assert!(matches!(Identifier::parse("\"abc"), Err(IdentifierError::UnterminatedQuote(_))));
+        assert!(matches!(Identifier::parse("\"a\"b\""), Err(IdentifierError::UnterminatedQuote(_))));
+        assert!(matches!(Identifier::parse(&"a".repeat(64)), Err(IdentifierError::TooLong(_))));
+        assert!(Identifier::parse(&"a".repeat(63)).is_ok());
+    }
+
+    #[test]
+    fn should_reject_reserved_names_for_user_defined_objects() {
+        assert!(Identifier::parse("_tables").is_ok());
+        assert!(matches!(Identifier::parse_user_defined("_tables"), Err(IdentifierError::Reserved(_))));
+        assert!(matches!(Identifier::parse_user_defined("\"_Tables\""), Err(IdentifierError::Reserved(_))));
+    }
+}
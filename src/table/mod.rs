@@ -1,6 +1,14 @@
+// Schemas and cells are built from the catalog and from user input: invalid ones must return errors, not panic.
+#![cfg_attr(not(test), deny(clippy::unwrap_used, clippy::expect_used))]
 use std::fmt::Display;
 
+use thiserror::Error;
+
+use crate::table::{encryption::ColumnKey, identifier::Identifier};
+
 pub mod table;
+pub mod identifier;
+pub mod encryption;
 // Table: play_attribute
 
 #[derive(Debug, PartialEq, Clone)]
@@ -8,12 +16,17 @@ pub enum ColumnType { // Byte type
     Int,            // 0x00
     Varchar(u16),   // 0x01 length is stored separately
     Byte,           // 0x02
+    Blob,           // 0x03 stored in overflow pages, the row has a pointer (see database/blob.rs)
+    Point,          // 0x04 two f64 (x, y), 16 bytes
 }
 #[derive(Debug, Clone, PartialEq)]
 pub struct Column {
     pub id: i32,
     pub name: String,
     pub col_type: ColumnType,
+    // values are encrypted on disk, the key is only known at runtime (see Database::with_encryption_key)
+    pub encrypted: bool,
+    pub key: Option<ColumnKey>,
 }
 
 // needs Clone for now, because it is shared across QueryResult and this is the quickest solution
@@ -22,14 +35,32 @@ pub struct TableSchema {
     pub columns: Vec<Column>,
 }
 
+#[derive(Debug, Error, PartialEq)]
+pub enum SchemaError {
+    #[error("A schema needs at least one column")]
+    NoColumns,
+}
+
 impl TableSchema {
-    pub fn new(columns: Vec<Column>) -> Self {
+    /// Only for tests, other code uses try_new or `of`
+    #[cfg(test)]
+    pub(crate) fn new(columns: Vec<Column>) -> Self {
+        Self { columns }
+    }
+
+    /// Schema of columns known at compile time, an empty array doesn't compile
+    pub fn of<const N: usize>(columns: [Column; N]) -> Self {
+        const { assert!(N > 0, "A schema needs at least one column") };
+        Self { columns: columns.into() }
+    }
+
+    pub fn try_new(columns: Vec<Column>) -> Result<Self, SchemaError> {
         if columns.is_empty() {
-            panic!("Cannot crate empty schema");
+            return Err(SchemaError::NoColumns);
         }
-        Self {
+        Ok(Self {
             columns,
-        }
+        })
     }
 
     pub fn find_index_by_id(&self, column_id: &i32) -> Option<usize> {
@@ -37,6 +68,7 @@ impl TableSchema {
     }
 
     pub fn find_index_by_name(&self, column_name: &str) -> Option<usize> {
+        let column_name = Identifier::normalize(column_name);
         self.columns.iter().position(|c| c.name == column_name)
     }
 }
@@ -47,6 +79,8 @@ impl Display for ColumnType {
             ColumnType::Int => f.write_str("Int"),
             ColumnType::Varchar(_) => f.write_str("Varchar"),
             ColumnType::Byte => f.write_str("Byte"),
+            ColumnType::Blob => f.write_str("Blob"),
+            ColumnType::Point => f.write_str("Point"),
         }
     }
 }
@@ -57,6 +91,8 @@ impl ColumnType {
             ColumnType::Int => false,
             ColumnType::Varchar(_) => true,
             ColumnType::Byte => false,
+            ColumnType::Blob => true,
+            ColumnType::Point => false,
         }
     }
 
@@ -65,6 +101,8 @@ impl ColumnType {
             ColumnType::Int => ColumnType::Int,
             ColumnType::Varchar(_) => ColumnType::Varchar(0),
             ColumnType::Byte => ColumnType::Byte,
+            ColumnType::Blob => ColumnType::Blob,
+            ColumnType::Point => ColumnType::Point,
         }
     }
 }
@@ -75,6 +113,16 @@ impl Column {
             id,
             name: name.to_string(),
             col_type,
+            encrypted: false,
+            key: None,
+        }
+    }
+
+    pub fn new_encrypted(id: i32, name: &str, col_type: ColumnType, key: Option<ColumnKey>) -> Self {
+        Self {
+            encrypted: true,
+            key,
+            ..Self::new(id, name, col_type)
         }
     }
 }
\ No newline at end of file
@@ -1,17 +1,73 @@
-use std::rc::Rc;
+use std::{fmt::Display, rc::Rc};
 
 use thiserror::Error;
 
-use crate::table::{self, ColumnType, TableSchema};
+use crate::{data::page::PageId, table::{self, ColumnType, TableSchema, encryption::EncryptionError}};
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum Cell {
     Int(i32),
     Varchar(String),
     Byte(u8),
+    Blob(Vec<u8>),
+    Point(Point),
 }
 
-#[derive(Debug, PartialEq)]
+/// A point (x, y) of a POINT column. The coordinates are compared by their bits, so that Cell stays Eq and Hash
+/// (NaN is rejected by the row validation, 0.0 and -0.0 are different values).
+#[derive(Debug, Clone, Copy)]
+pub struct Point(pub f64, pub f64);
+
+impl Point {
+    pub fn x(&self) -> f64 {
+        self.0
+    }
+
+    pub fn y(&self) -> f64 {
+        self.1
+    }
+
+    /// Inverse of Display (spaces are allowed), None if it is not a point
+    pub fn parse(text: &str) -> Option<Point> {
+        let inner = text.trim().strip_prefix('(')?.strip_suffix(')')?;
+        let (x, y) = inner.split_once(',')?;
+        let point = Point(x.trim().parse().ok()?, y.trim().parse().ok()?);
+        point.is_valid().then_some(point)
+    }
+
+    pub fn distance_squared(&self, other: &Point) -> f64 {
+        let (dx, dy) = (self.0 - other.0, self.1 - other.1);
+        dx * dx + dy * dy
+    }
+
+    fn is_valid(&self) -> bool {
+        !self.0.is_nan() && !self.1.is_nan()
+    }
+}
+
+impl PartialEq for Point {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_bits() == other.0.to_bits() && self.1.to_bits() == other.1.to_bits()
+    }
+}
+
+impl Eq for Point {}
+
+impl std::hash::Hash for Point {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+        self.1.to_bits().hash(state);
+    }
+}
+
+/// Text form of a POINT value (SQL literals, exports, pgwire): `(x,y)` like in Postgres
+impl Display for Point {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({},{})", self.0, self.1)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Row {
     cells: Vec<Cell>,
 }
@@ -36,6 +92,85 @@ pub enum RowValidationError {
     TypeMismatch(String, String, String),
     #[error("Varchar length exceeds maximum of {0} for column '{1}'")]
     VarcharTooLong(u16, String),
+    #[error("Column '{0}' does not exist")]
+    UnknownColumn(String),
+    #[error("No value set for column '{0}'")]
+    MissingValue(String),
+    #[error("Point of column '{0}' has a NaN coordinate")]
+    InvalidPoint(String),
+}
+
+/// Builds a row by column names, so that the order of the cells always follows the schema.
+pub struct RowBuilder<'s> {
+    schema: &'s TableSchema,
+    cells: Vec<Option<Cell>>,
+}
+
+impl<'s> RowBuilder<'s> {
+    pub fn set<C: Into<Cell>>(mut self, column_name: &str, value: C) -> Result<Self, RowValidationError> {
+        let index = self.schema.find_index_by_name(column_name)
+            .ok_or_else(|| RowValidationError::UnknownColumn(column_name.to_owned()))?;
+
+        let cell = value.into();
+        validate_cell(&cell, &self.schema.columns[index])?;
+        self.cells[index] = Some(cell);
+
+        Ok(self)
+    }
+
+    pub fn build(self) -> Result<Row, RowValidationError> {
+        self.build_with_defaults(|_, _| None)
+    }
+
+    /// Columns without a value get the cell returned by `default` (index in schema, column).
+    /// If `default` returns None, the value is missing.
+    pub fn build_with_defaults<F: Fn(usize, &table::Column) -> Option<Cell>>(self, default: F) -> Result<Row, RowValidationError> {
+        let cells = self.cells.into_iter()
+            .zip(self.schema.columns.iter())
+            .enumerate()
+            .map(|(index, (cell, column))| {
+                cell.or_else(|| default(index, column))
+                    .ok_or_else(|| RowValidationError::MissingValue(column.name.clone()))
+            })
+            .collect::<Result<Vec<Cell>, RowValidationError>>()?;
+
+        Ok(Row::new(cells))
+    }
+}
+
+fn validate_cell(cell: &Cell, column: &table::Column) -> Result<(), RowValidationError> {
+    match (cell, &column.col_type) {
+        (Cell::Int(_), ColumnType::Int) => {
+            // always valid
+        }
+        (Cell::Varchar(input), ColumnType::Varchar(max_len)) => {
+            if input.len() > *max_len as usize {
+                return Err(RowValidationError::VarcharTooLong(*max_len, column.name.clone()));
+            }
+        }
+        (Cell::Byte(_), ColumnType::Byte) => {
+            // always valid
+        }
+        (Cell::Blob(_), ColumnType::Blob) => {
+            // the length is only limited by the pointer (u32)
+        }
+        (Cell::Point(point), ColumnType::Point) => {
+            if !point.is_valid() {
+                return Err(RowValidationError::InvalidPoint(column.name.clone()));
+            }
+        }
+        _ => {
+            return Err(
+                RowValidationError::TypeMismatch(
+                    column.name.clone(),
+                    column.col_type.to_string(),
+                    cell.column_type().to_string()
+                )
+            );
+        }
+    }
+
+    Ok(())
 }
 
 impl Row {
@@ -45,6 +180,13 @@ impl Row {
         }
     }
 
+    pub fn builder(schema: &TableSchema) -> RowBuilder<'_> {
+        RowBuilder {
+            schema,
+            cells: vec![None; schema.columns.len()],
+        }
+    }
+
     pub fn cells(&self) -> &Vec<Cell> {
         &self.cells
     }
@@ -58,17 +200,26 @@ impl Row {
         bytes
     }
 
-    // ToDo: return Result<Row, RowDeserializationError> instead of using unwrap
-    pub fn deserialize(row_data: &[u8], schema: &TableSchema) -> Self {
+    /// Like serialize, but encrypts the cells of encrypted columns
+    pub fn serialize_for(&self, schema: &TableSchema) -> Result<Vec<u8>, CellError> {
+        let mut bytes = Vec::new();
+        for (cell, column) in self.cells.iter().zip(schema.columns.iter()) {
+            bytes.extend(cell.serialize_for(column)?);
+        }
+        Ok(bytes)
+    }
+
+    pub fn deserialize(row_data: &[u8], schema: &TableSchema) -> Result<Self, CellDeserializationError> {
         let mut cells = Vec::new();
         let mut offset = 0;
         for col in schema.columns.iter() {
-            let (cell, bytes_read) = Cell::deserialize(&row_data[offset..], &col).unwrap();
+            let (cell, bytes_read) = Cell::deserialize(row_data.get(offset..).unwrap_or_default(), col)
+                .map_err(|err| err.at_offset(offset))?;
             offset += bytes_read;
             cells.push(cell);
         }
 
-        Row { cells }
+        Ok(Row { cells })
     }
 
     pub fn validate(&self, schema: &TableSchema) -> Result<(), RowValidationError> {
@@ -77,28 +228,7 @@ impl Row {
         }
 
         for (cell, column) in self.cells.iter().zip(schema.columns.iter()) {
-            match (cell, &column.col_type) {
-                (Cell::Int(_), ColumnType::Int) => {
-                    // always valid
-                }
-                (Cell::Varchar(input), ColumnType::Varchar(max_len)) => {
-                    if input.len() > *max_len as usize {
-                        return Err(RowValidationError::VarcharTooLong(*max_len, column.name.clone()));
-                    }
-                }
-                (Cell::Byte(_), ColumnType::Byte) => {
-                    // always valid
-                }
-                _ => {
-                    return Err(
-                        RowValidationError::TypeMismatch(
-                            column.name.clone(),
-                            column.col_type.to_string(),
-                            cell.column_type().to_string()
-                        )
-                    );
-                }
-            }
+            validate_cell(cell, column)?;
         }
 
         Ok(())
@@ -131,7 +261,27 @@ impl Table {
     }
 
     pub fn file_path(&self) -> String {
-        format!("table_{}.dat", self.id())
+        match self.id() {
+            id if id < 0 => format!("table_{}.blob", -id),
+            id => format!("table_{}.dat", id),
+        }
+    }
+
+    /// The overflow pages of the BLOB values of this table (see database/blob.rs).
+    /// It has the negative id of the table, so its pages have their own keys in the page cache.
+    pub fn overflow_table(&self) -> Table {
+        let schema = TableSchema::of([table::Column::new(1, "chunk", ColumnType::Blob)]);
+        Table::new(-self.id(), format!("{} (overflow)", self.name()), schema)
+    }
+
+    /// A table returned by overflow_table (the KvStore namespaces have negative ids, too)
+    pub fn is_overflow_table(&self) -> bool {
+        let columns = &self.schema().columns;
+        self.id() < 0 && columns.len() == 1 && columns[0].name == "chunk" && columns[0].col_type == ColumnType::Blob
+    }
+
+    pub fn has_blobs(&self) -> bool {
+        self.schema().columns.iter().any(|c| c.col_type == ColumnType::Blob)
     }
 
     pub fn validate_row(&self, row: &Row) -> Result<(), RowValidationError> {
@@ -140,16 +290,158 @@ impl Table {
 }
 
 
+impl From<i32> for Cell {
+    fn from(value: i32) -> Self {
+        Cell::Int(value)
+    }
+}
+
+impl From<&str> for Cell {
+    fn from(value: &str) -> Self {
+        Cell::Varchar(value.to_owned())
+    }
+}
+
+impl From<String> for Cell {
+    fn from(value: String) -> Self {
+        Cell::Varchar(value)
+    }
+}
+
+impl From<Vec<u8>> for Cell {
+    fn from(value: Vec<u8>) -> Self {
+        Cell::Blob(value)
+    }
+}
+
+impl From<Point> for Cell {
+    fn from(value: Point) -> Self {
+        Cell::Point(value)
+    }
+}
+
+impl From<u8> for Cell {
+    fn from(value: u8) -> Self {
+        Cell::Byte(value)
+    }
+}
+
+// Where the deserialization failed. Cell::deserialize sets the column, the expected type and the bytes,
+// the callers add the offset in the row and the record (table, page, slot) if they know it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeserializationContext {
+    pub reason: String,
+    pub table: Option<String>,
+    pub page: Option<PageId>,
+    pub slot: Option<usize>,
+    pub column: Option<String>,
+    pub expected: Option<String>,
+    // byte offset of the cell in the row
+    pub offset: usize,
+    // the first HEXDUMP_BYTES bytes of the cell
+    pub bytes: Vec<u8>,
+}
+
+const HEXDUMP_BYTES: usize = 16;
+
+impl Display for DeserializationContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at byte {}", self.reason, self.offset)?;
+        if let Some(column) = &self.column {
+            write!(f, " of column '{}'", column)?;
+        }
+        if let Some(expected) = &self.expected {
+            write!(f, ", expected {}", expected)?;
+        }
+        if let Some(table) = &self.table {
+            write!(f, ", table '{}'", table)?;
+        }
+        if let Some(page) = self.page {
+            write!(f, ", page {}", page)?;
+        }
+        if let Some(slot) = self.slot {
+            write!(f, ", slot {}", slot)?;
+        }
+        write!(f, ", bytes: [{}]", hexdump(&self.bytes))
+    }
+}
+
+pub fn hexdump(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" ")
+}
+
+/// Text form of a BLOB value (SQL literals, exports, pgwire): lowercase hex without separators
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Inverse of to_hex (also accepts uppercase and the `\x` prefix of Postgres), None if it is not valid hex
+pub fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    let text = text.strip_prefix("\\x").unwrap_or(text);
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len()).step_by(2)
+        .map(|i| text.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+        .collect()
+}
+
 #[derive(Debug, Error)]
 pub enum CellDeserializationError {
-    #[error("Cell deserialization error")]
-    InvalidData,
+    // boxed, to keep the Result small
+    #[error("Cell deserialization error: {0}")]
+    InvalidData(Box<DeserializationContext>),
+}
+
+impl CellDeserializationError {
+    fn new(reason: &str, row_data: &[u8], column: &table::Column) -> Self {
+        CellDeserializationError::InvalidData(Box::new(DeserializationContext {
+            reason: reason.to_owned(),
+            column: Some(column.name.clone()),
+            expected: Some(match &column.col_type {
+                ColumnType::Varchar(len) => format!("Varchar({})", len),
+                col_type => col_type.to_string(),
+            }),
+            bytes: row_data.iter().take(HEXDUMP_BYTES).copied().collect(),
+            ..Default::default()
+        }))
+    }
+
+    pub fn context(&self) -> &DeserializationContext {
+        match self {
+            CellDeserializationError::InvalidData(context) => context,
+        }
+    }
+
+    /// Adds the offset of the cell in the row
+    pub fn at_offset(mut self, offset: usize) -> Self {
+        match &mut self {
+            CellDeserializationError::InvalidData(context) => context.offset += offset,
+        }
+        self
+    }
+
+    /// Adds the record the row was read from
+    pub fn in_record(mut self, table: &str, page_id: PageId, slot: usize) -> Self {
+        match &mut self {
+            CellDeserializationError::InvalidData(context) => {
+                context.table = Some(table.to_owned());
+                context.page = Some(page_id);
+                context.slot = Some(slot);
+            },
+        }
+        self
+    }
 }
 
 #[derive(Debug, Error)]
 pub enum CellError {
     #[error("The cell is not an Int. Msg: {0}")]
     ExpectedInt(String),
+    #[error("No encryption key for encrypted column '{0}'")]
+    MissingKey(String),
+    #[error("Encryption of column '{0}' failed: {1}")]
+    EncryptionFailed(String, EncryptionError),
 }
 
 impl Cell {
@@ -165,6 +457,8 @@ impl Cell {
             Cell::Int(_) => ColumnType::Int,
             Cell::Varchar(_) => ColumnType::Varchar(0),
             Cell::Byte(_) => ColumnType::Byte,
+            Cell::Blob(_) => ColumnType::Blob,
+            Cell::Point(_) => ColumnType::Point,
         }
     }
 
@@ -178,6 +472,8 @@ impl Cell {
             Cell::Int(_) => ColumnType::Int,
             Cell::Varchar(_) => ColumnType::Varchar(0),
             Cell::Byte(_) => ColumnType::Byte,
+            Cell::Blob(_) => ColumnType::Blob,
+            Cell::Point(_) => ColumnType::Point,
         };
 
         cell_type_only == col_type_only
@@ -194,56 +490,95 @@ impl Cell {
             },
             Cell::Byte(b) => {
                 vec![*b]
+            },
+            Cell::Blob(b) => {
+                // 4 Bytes for length + bytes, in a stored row these are the 8 bytes of the pointer to the overflow pages
+                let mut bytes = (b.len() as u32).to_be_bytes().to_vec();
+                bytes.extend_from_slice(b);
+                bytes
+            },
+            Cell::Point(Point(x, y)) => {
+                let mut bytes = x.to_be_bytes().to_vec();
+                bytes.extend_from_slice(&y.to_be_bytes());
+                bytes
             }
         }
     }
 
-    // ToDo: better error handling
+    // Encrypted: 2 bytes length + encrypted plain serialization
+    pub fn serialize_for(&self, column: &table::Column) -> Result<Vec<u8>, CellError> {
+        if !column.encrypted {
+            return Ok(self.serialize());
+        }
+        let key = column.key.as_ref()
+            .ok_or_else(|| CellError::MissingKey(column.name.clone()))?;
+        let encrypted = key.encrypt(&self.serialize())
+            .map_err(|e| CellError::EncryptionFailed(column.name.clone(), e))?;
+        let mut bytes = (encrypted.len() as u16).to_be_bytes().to_vec();
+        bytes.extend(encrypted);
+        Ok(bytes)
+    }
+
+    fn deserialize_encrypted(row_data: &[u8], column: &table::Column) -> Result<(Self, usize), CellDeserializationError> {
+        let invalid = |reason: &str| CellDeserializationError::new(reason, row_data, column);
+        let key = column.key.as_ref().ok_or_else(|| invalid("no encryption key"))?;
+        let len = row_data.get(0..2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]) as usize)
+            .ok_or_else(|| invalid("missing length of the encrypted value"))?;
+        let encrypted = row_data.get(2..2 + len).ok_or_else(|| invalid("encrypted value exceeds the row"))?;
+        let plain = key.decrypt(encrypted).ok_or_else(|| invalid("decryption failed (wrong key or modified value)"))?;
+
+        let plain_column = table::Column::new(column.id, &column.name, column.col_type.clone());
+        let (cell, _) = Cell::deserialize(&plain, &plain_column)?;
+        Ok((cell, 2 + len))
+    }
+
     // Gets always the next slice of the row_data
     // Returns: (Cell, number of bytes read)
-    pub fn deserialize<'a>(row_data: &'a [u8], column: &table::Column) -> Result<(Self, usize), CellDeserializationError> {
+    pub fn deserialize(row_data: &[u8], column: &table::Column) -> Result<(Self, usize), CellDeserializationError> {
+        if column.encrypted {
+            return Self::deserialize_encrypted(row_data, column);
+        }
+        let invalid = |reason: &str| CellDeserializationError::new(reason, row_data, column);
         match &column.col_type {
             ColumnType::Int => {
-                if row_data.len() < 4 {
-                    return Err(CellDeserializationError::InvalidData);
-                }
-                let int_bytes = &row_data[0..4];
-                let int_value = i32::from_be_bytes(
-                    int_bytes.try_into()
-                        .map_err(|_| CellDeserializationError::InvalidData)?
-                );
+                let int_bytes = row_data.get(0..4)
+                    .ok_or_else(|| invalid("an Int needs 4 bytes"))?;
+                let int_value = i32::from_be_bytes([int_bytes[0], int_bytes[1], int_bytes[2], int_bytes[3]]);
                 Ok((Cell::Int(int_value), 4))
             }
             ColumnType::Varchar(len) => {
-                if row_data.len() < 2 {
-                    // needs at least 2 bytes for length
-                    return Err(CellDeserializationError::InvalidData);
-                }
-                let len_bytes = &row_data[0..2];
-                let str_len = u16::from_be_bytes(
-                    len_bytes.try_into()
-                        .map_err(|_| CellDeserializationError::InvalidData)?
-                ) as usize;
-                if row_data.len() < (2 + str_len) {
-                    return Err(CellDeserializationError::InvalidData);
-                }
-
+                // needs at least 2 bytes for length
+                let str_len = row_data.get(0..2)
+                    .map(|b| u16::from_be_bytes([b[0], b[1]]) as usize)
+                    .ok_or_else(|| invalid("missing length of the Varchar"))?;
                 if str_len > *len as usize {
-                    return Err(CellDeserializationError::InvalidData);
+                    return Err(invalid(&format!("Varchar length {} exceeds the column length", str_len)));
                 }
-
-                let str_bytes = &row_data[2..2 + str_len];
+                let str_bytes = row_data.get(2..2 + str_len)
+                    .ok_or_else(|| invalid(&format!("Varchar of length {} exceeds the row", str_len)))?;
                 let str_value = String::from_utf8(str_bytes.to_vec())
-                    .map_err(|_| CellDeserializationError::InvalidData)?;
+                    .map_err(|_| invalid("Varchar is not valid UTF-8"))?;
 
                 Ok((Cell::Varchar(str_value), 2 + str_len))
             },
             ColumnType::Byte => {
-                if row_data.len() < 1 {
-                    return Err(CellDeserializationError::InvalidData);
-                }
-                let byte_value = row_data[0];
+                let byte_value = *row_data.first().ok_or_else(|| invalid("missing Byte"))?;
                 Ok((Cell::Byte(byte_value), 1))
+            },
+            ColumnType::Blob => {
+                let blob_len = row_data.get(0..4)
+                    .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize)
+                    .ok_or_else(|| invalid("missing length of the Blob"))?;
+                let blob_bytes = row_data.get(4..4 + blob_len)
+                    .ok_or_else(|| invalid(&format!("Blob of length {} exceeds the row", blob_len)))?;
+                Ok((Cell::Blob(blob_bytes.to_vec()), 4 + blob_len))
+            },
+            ColumnType::Point => {
+                let bytes = row_data.get(0..16)
+                    .ok_or_else(|| invalid("a Point needs 16 bytes"))?;
+                let coordinate = |b: &[u8]| f64::from_be_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]);
+                Ok((Cell::Point(Point(coordinate(&bytes[0..8]), coordinate(&bytes[8..16]))), 16))
             }
         }
     }
@@ -274,7 +609,7 @@ mod tests {
 
         let serialized = row.serialize();
 
-        let deserialized_row = Row::deserialize(&serialized, &schema);
+        let deserialized_row = Row::deserialize(&serialized, &schema).unwrap();
         let cells = deserialized_row.cells;
 
         assert!(matches!(&cells[0], Cell::Int(42)));
@@ -393,4 +728,76 @@ mod tests {
         assert!(result.is_err());
         matches!(result.unwrap_err(), RowValidationError::VarcharTooLong(10, name) if name == "name");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn should_build_row_in_schema_order() {
+        let schema = TableSchema::new(vec![
+            Column::new(1, "id", ColumnType::Int),
+            Column::new(2, "name", ColumnType::Varchar(10)),
+            Column::new(3, "flag", ColumnType::Byte),
+        ]);
+
+        let row = Row::builder(&schema)
+            .set("name", "Hans").unwrap()
+            .set("flag", 1u8).unwrap()
+            .set("id", 1).unwrap()
+            .build().unwrap();
+
+        assert_eq!(row.cells(), &vec![Cell::Int(1), Cell::Varchar("Hans".to_owned()), Cell::Byte(1)]);
+    }
+
+    #[test]
+    fn row_builder_should_validate_values() {
+        let schema = TableSchema::new(vec![
+            Column::new(1, "id", ColumnType::Int),
+            Column::new(2, "name", ColumnType::Varchar(4)),
+        ]);
+
+        let res = Row::builder(&schema).set("id", "Hans");
+        assert!(matches!(res, Err(RowValidationError::TypeMismatch(name, _, _)) if name == "id"));
+
+        let res = Row::builder(&schema).set("name", "Rabbit");
+        assert!(matches!(res, Err(RowValidationError::VarcharTooLong(4, name)) if name == "name"));
+
+        let res = Row::builder(&schema).set("age", 30);
+        assert!(matches!(res, Err(RowValidationError::UnknownColumn(name)) if name == "age"));
+
+        let res = Row::builder(&schema).set("id", 1).unwrap().build();
+        assert!(matches!(res, Err(RowValidationError::MissingValue(name)) if name == "name"));
+    }
+
+    #[test]
+    fn should_not_deserialize_truncated_rows() {
+        let schema = TableSchema::new(vec![
+            Column::new(1, "id", ColumnType::Int),
+            Column::new(2, "name", ColumnType::Varchar(10)),
+        ]);
+        let bytes = Row::new(vec![Cell::Int(1), Cell::Varchar("Alice".to_owned())]).serialize();
+        assert!(Row::deserialize(&bytes, &schema).is_ok());
+        for len in [0, 3, 5, bytes.len() - 1] {
+            assert!(Row::deserialize(&bytes[..len], &schema).is_err(), "{}", len);
+        }
+        assert!(TableSchema::try_new(vec![]).is_err());
+    }
+
+    #[test]
+    fn should_describe_where_deserialization_failed() {
+        let schema = TableSchema::new(vec![
+            Column::new(1, "id", ColumnType::Int),
+            Column::new(2, "name", ColumnType::Varchar(3)),
+        ]);
+        let bytes = Row::new(vec![Cell::Int(1), Cell::Varchar("Alice".to_owned())]).serialize();
+        let err = Row::deserialize(&bytes, &schema).unwrap_err().in_record("persons", 3, 2);
+        let context = err.context();
+        assert_eq!(context.offset, 4);
+        assert_eq!(context.column.as_deref(), Some("name"));
+        assert_eq!(context.expected.as_deref(), Some("Varchar(3)"));
+        assert_eq!(context.bytes, bytes[4..].to_vec());
+        assert_eq!(err.to_string(), "Cell deserialization error: Varchar length 5 exceeds the column length at byte 4 of column 'name', \
+            expected Varchar(3), table 'persons', page 3, slot 2, bytes: [00 05 41 6c 69 63 65]");
+
+        let err = Row::deserialize(&bytes[..2], &schema).unwrap_err();
+        assert_eq!((err.context().offset, err.context().bytes.clone()), (0, vec![0, 0]));
+        assert_eq!(hexdump(&[0xde, 0xad, 0x01]), "de ad 01");
+    }
+}
@@ -68,6 +68,25 @@ fn next_leaf_offset(max_degree: u16) -> usize {
     values_offset(max_degree) + values_array_size(max_degree)
 }
 
+fn read_meta_data(file: &mut File) -> std::io::Result<StoreMetaData> {
+    let mut metadata_bytes = [0u8; META_DATA_HEADER_SIZE];
+    file.read_exact(&mut metadata_bytes)?;
+
+    let max_degree = u16::from_be_bytes(metadata_bytes[0..2].try_into().unwrap());
+    let number_of_pages = u32::from_be_bytes(metadata_bytes[2..6].try_into().unwrap());
+    let first_deleted_page = i32::from_be_bytes(metadata_bytes[6..10].try_into().unwrap());
+    let root = i32::from_be_bytes(metadata_bytes[10..14].try_into().unwrap());
+
+    Ok(StoreMetaData {
+        max_degree,
+        number_of_pages,
+        first_deleted_page: read_i32_with_null(first_deleted_page),
+        root: read_i32_with_null(root),
+        changed: false,
+        unique_index: true,
+    })
+}
+
 fn meta_data_to_bytes(store_meta_data: &StoreMetaData) -> Vec<u8> {
     let mut metadata_bytes = [0u8; META_DATA_HEADER_SIZE];
     metadata_bytes[0..2].copy_from_slice(&store_meta_data.max_degree.to_be_bytes());
@@ -406,23 +425,7 @@ impl BTreeStore {
 
         let file = match OpenOptions::new().read(true).write(true).open(file_path) {
             Ok(mut f) if file_size >= META_DATA_HEADER_SIZE as u64 => {
-                let mut metadata_bytes = [0u8; META_DATA_HEADER_SIZE];
-                f.read_exact(&mut metadata_bytes).expect("Cannot read meta data from file");
-
-                let max_degree = u16::from_be_bytes(metadata_bytes[0..2].try_into().unwrap());
-                let number_of_pages = u32::from_be_bytes(metadata_bytes[2..6].try_into().unwrap());
-                let first_deleted_page = i32::from_be_bytes(metadata_bytes[6..10].try_into().unwrap());
-                let root = i32::from_be_bytes(metadata_bytes[10..14].try_into().unwrap());
-
-                store_meta_data = StoreMetaData {
-                    max_degree,
-                    number_of_pages,
-                    first_deleted_page: read_i32_with_null(first_deleted_page),
-                    root: read_i32_with_null(root),
-                    changed: false,
-                    unique_index: true,
-                };
-
+                store_meta_data = read_meta_data(&mut f).expect("Cannot read meta data from file");
                 f
             }
             _ => {
@@ -459,6 +462,28 @@ impl BTreeStore {
         })
     }
 
+    /// All pages of the file, including deleted pages that can be reused
+    pub fn number_of_pages(&self) -> u32 {
+        self.meta_data.borrow().number_of_pages
+    }
+
+    /// Opens an existing tree without write access: every operation that changes the tree fails
+    /// on writing its pages, so the file itself is never modified (e.g. for read replicas).
+    pub fn open_read_only(file_path: &Path) -> Result<Self, BTreeStoreError> {
+        let mut file = OpenOptions::new().read(true).open(file_path)
+            .map_err(|e| BTreeStoreError { msg: format!("Cannot open '{}': {}", file_path.display(), e) })?;
+
+        let store_meta_data = read_meta_data(&mut file)
+            .map_err(|e| BTreeStoreError { msg: format!("Cannot read meta data of '{}': {}", file_path.display(), e) })?;
+
+        let rc_meta_data = Rc::new(RefCell::new(store_meta_data));
+
+        Ok(BTreeStore {
+            pager: NodePager::new(file, Rc::clone(&rc_meta_data)),
+            meta_data: rc_meta_data
+        })
+    }
+
     #[allow(dead_code)]
     fn page_size(&self) -> u32 {
         self.pager.page_size()
@@ -535,6 +560,44 @@ impl BTreeStore {
         Ok(result)
     }
 
+    /// Values of the keys from..=to, in ascending key order. Only the leaves of the range are read.
+    pub fn find_range(&self, from: i32, to: i32) -> Result<Vec<(i32, i32)>, BTreeStoreError> {
+        // the leaf where `from` is or would be inserted (find_node only finds the leaf of an existing key)
+        let mut leaf = self.root()?;
+        while !leaf.is_leaf() {
+            let child = leaf.keys().partition_point(|key| *key <= from);
+            leaf = self.pager.read_page(leaf.children()[child])?;
+        }
+
+        let mut result = Vec::new();
+        let mut node = Some(leaf);
+        while let Some(next) = node {
+            for (key, value) in next.keys().iter().zip(next.values().iter()) {
+                if *key > to {
+                    return Ok(result);
+                }
+                if *key >= from {
+                    result.push(*value);
+                }
+            }
+            node = self.next_node(&next)?
+        }
+
+        Ok(result)
+    }
+
+    /// Values of all keys, in ascending key order
+    pub fn values_in_key_order(&self) -> Result<Vec<(i32, i32)>, BTreeStoreError> {
+        let mut result = Vec::new();
+        let mut node = self.find_left_most_node()?;
+        while let Some(next) = node {
+            result.extend_from_slice(next.values());
+            node = self.next_node(&next)?
+        }
+
+        Ok(result)
+    }
+
     pub fn find_left_most_node(&self) -> Result<Option<NodePage>, BTreeStoreError> {
         let root = self.root()?;
 
@@ -775,6 +838,20 @@ mod tests {
         assert_eq!(result, vec![(8, 8), (10, 10), (20, 20), (50, 50), (100, 100)]);
     }
 
+    #[test]
+    fn find_range() {
+        let temp = NamedTempFile::new().unwrap();
+        let mut btree= BTreeStore::new(temp.path(), 4).unwrap();
+        for key in [1, 10, 2, 5, 100, 3, 4, 50, 20, 6, 7, 8] {
+            btree.insert(key, (key, key)).unwrap();
+        }
+
+        assert_eq!(btree.find_range(4, 20).unwrap(), vec![(4, 4), (5, 5), (6, 6), (7, 7), (8, 8), (10, 10), (20, 20)]);
+        assert_eq!(btree.find_range(11, 19).unwrap(), vec![]);
+        assert_eq!(btree.find_range(90, 1000).unwrap(), vec![(100, 100)]);
+        assert_eq!(btree.find_range(5, 4).unwrap(), vec![]);
+    }
+
     #[test]
     fn delete_everything_except_one_key() {
         let temp = NamedTempFile::new().unwrap();